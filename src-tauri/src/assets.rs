@@ -0,0 +1,192 @@
+//! Decoded images (iTerm2, Sixel, Kitty), theme backgrounds and export
+//! previews served to the webview over the `rain-asset` URI scheme, so render
+//! frames and IPC replies carry a short URL instead of a base64 copy.
+//!
+//! Every asset has an owner: a session, or [`THEME_OWNER`]. URLs carry a
+//! random token the owner gets with its first asset, so knowing a session ID
+//! isn't enough to read its images; they stop resolving once the owner is
+//! removed. Each stored asset gets a new URL, so the webview may cache
+//! responses forever. The oldest assets are evicted once the registry holds
+//! more than [`MAX_TOTAL_BYTES`].
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+/// Name of the URI scheme registered in `lib.rs`.
+pub const SCHEME: &str = "rain-asset";
+
+/// Owner of the active theme's background image.
+pub const THEME_OWNER: &str = "theme";
+
+/// Bytes kept across all sessions before the oldest assets are dropped.
+pub const MAX_TOTAL_BYTES: usize = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Asset {
+    pub mime: &'static str,
+    pub bytes: Arc<[u8]>,
+}
+
+type AssetKey = (String, u64);
+
+#[derive(Debug, Default)]
+pub struct AssetRegistry {
+    next_id: u64,
+    assets: HashMap<AssetKey, Asset>,
+    /// Access token of each owner with assets.
+    tokens: HashMap<String, String>,
+    /// Insertion order, for eviction.
+    order: VecDeque<AssetKey>,
+    total_bytes: usize,
+}
+
+impl AssetRegistry {
+    /// Store an image for `session_id` and return the URL serving it.
+    pub fn insert(&mut self, session_id: &str, bytes: Vec<u8>) -> String {
+        let mime = sniff_mime(&bytes);
+        self.insert_typed(session_id, bytes, mime)
+    }
+
+    /// Store `bytes` of content type `mime` for `owner` and return the URL
+    /// serving them.
+    pub fn insert_typed(&mut self, owner: &str, bytes: Vec<u8>, mime: &'static str) -> String {
+        self.next_id += 1;
+        let key = (owner.to_string(), self.next_id);
+        let asset = Asset {
+            mime,
+            bytes: bytes.into(),
+        };
+        self.total_bytes += asset.bytes.len();
+        self.assets.insert(key.clone(), asset);
+        self.order.push_back(key);
+
+        // The newest asset stays even when it alone is over the limit.
+        while self.total_bytes > MAX_TOTAL_BYTES && self.order.len() > 1 {
+            if let Some(oldest) = self.order.pop_front() {
+                self.remove(&oldest);
+            }
+        }
+        let token = self
+            .tokens
+            .entry(owner.to_string())
+            .or_insert_with(|| uuid::Uuid::new_v4().simple().to_string());
+        url(owner, token, self.next_id)
+    }
+
+    /// The asset at a URL path (`/<owner>/<token>/<asset id>`).
+    pub fn resolve(&self, path: &str) -> Option<Asset> {
+        let mut parts = path.trim_start_matches('/').splitn(3, '/');
+        let (owner, token, id) = (parts.next()?, parts.next()?, parts.next()?);
+        if self.tokens.get(owner).map(String::as_str) != Some(token) {
+            return None;
+        }
+        let id = id.parse().ok()?;
+        self.assets.get(&(owner.to_string(), id)).cloned()
+    }
+
+    /// Drop every asset of a destroyed session, or of another owner.
+    pub fn remove_session(&mut self, session_id: &str) {
+        let keys: Vec<AssetKey> = self
+            .order
+            .iter()
+            .filter(|(owner, _)| owner == session_id)
+            .cloned()
+            .collect();
+        for key in &keys {
+            self.remove(key);
+        }
+        self.order.retain(|(owner, _)| owner != session_id);
+        self.tokens.remove(session_id);
+    }
+
+    fn remove(&mut self, key: &AssetKey) {
+        if let Some(asset) = self.assets.remove(key) {
+            self.total_bytes -= asset.bytes.len();
+        }
+    }
+}
+
+/// Where one session's terminal stores its images.
+#[derive(Debug, Clone)]
+pub struct AssetSink {
+    session_id: String,
+    registry: Arc<Mutex<AssetRegistry>>,
+}
+
+impl AssetSink {
+    pub fn new(session_id: &str, registry: Arc<Mutex<AssetRegistry>>) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            registry,
+        }
+    }
+
+    /// Store an image and return its URL.
+    pub fn store(&self, bytes: Vec<u8>) -> String {
+        self.registry.lock().insert(&self.session_id, bytes)
+    }
+}
+
+/// URL of an asset. Webviews on Windows only load custom schemes through
+/// `http://<scheme>.localhost`.
+fn url(owner: &str, token: &str, id: u64) -> String {
+    if cfg!(windows) {
+        format!("http://{}.localhost/{}/{}/{}", SCHEME, owner, token, id)
+    } else {
+        format!("{}://localhost/{}/{}/{}", SCHEME, owner, token, id)
+    }
+}
+
+/// Content type from the image's magic bytes.
+fn sniff_mime(bytes: &[u8]) -> &'static str {
+    match bytes {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xff, 0xd8, 0xff, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(url: &str) -> &str {
+        &url[url.find("localhost").unwrap() + "localhost".len()..]
+    }
+
+    #[test]
+    fn assets_resolve_only_under_their_session() {
+        let mut registry = AssetRegistry::default();
+        let png = b"\x89PNG\r\n\x1a\n....".to_vec();
+        let url = registry.insert("s1", png.clone());
+        assert!(url.contains("/s1/") && url.ends_with("/1"));
+        let token = &registry.tokens["s1"];
+
+        let asset = registry.resolve(path(&url)).unwrap();
+        assert_eq!((asset.mime, &asset.bytes[..]), ("image/png", &png[..]));
+        assert!(registry.resolve(&format!("/s2/{}/1", token)).is_none());
+        assert!(registry.resolve(&format!("/s1/{}/x", token)).is_none());
+        // The session ID and asset number alone don't reach the image.
+        assert!(registry.resolve("/s1/1").is_none());
+        assert!(registry.resolve("/s1/guess/1").is_none());
+
+        registry.remove_session("s1");
+        assert!(registry.resolve(path(&url)).is_none());
+        assert_eq!(registry.total_bytes, 0);
+    }
+
+    #[test]
+    fn oldest_assets_are_evicted_over_the_limit() {
+        let mut registry = AssetRegistry::default();
+        let first = registry.insert("s1", vec![0; MAX_TOTAL_BYTES / 2]);
+        let second = registry.insert("s1", vec![0; MAX_TOTAL_BYTES / 2]);
+        let third = registry.insert("s2", vec![0; 1]);
+        assert!(registry.resolve(path(&first)).is_none());
+        assert!(registry.resolve(path(&second)).is_some());
+        assert!(registry.resolve(path(&third)).is_some());
+    }
+}
@@ -0,0 +1,218 @@
+//! Whole-app state export for support and migration ("save everything").
+//!
+//! An archive is a single JSON document holding the config file, the saved
+//! workspace, the frontend's settings storage (profiles, theme, keybindings,
+//! triggers) and diagnostic metadata. Scrollback is only included on request.
+//! Secrets are redacted on the way out: tokens live in the OS credential
+//! store and are never exported, and anything that looks like a secret in
+//! the exported settings (e.g. a profile's `GITHUB_TOKEN`) is replaced with
+//! [`REDACTED`] and dropped again on import.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const ARCHIVE_FORMAT: &str = "rain-app-state";
+pub const ARCHIVE_VERSION: u32 = 1;
+
+pub const REDACTED: &str = "[redacted]";
+
+/// Frontend storage keys carried in an archive. Command history and
+/// directory frecency stay on the machine.
+pub const FRONTEND_KEYS: &[&str] = &[
+    "rain-config",
+    "rain-theme",
+    "rain-keybindings",
+    "rain-profiles",
+    "rain-active-profile-id",
+    "rain-triggers",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostics {
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub session_count: usize,
+    pub safe_mode: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrollbackEntry {
+    pub session_id: String,
+    pub cwd: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Archive {
+    pub format: String,
+    pub version: u32,
+    /// Unix time in milliseconds.
+    pub exported_at: u64,
+    pub diagnostics: Diagnostics,
+    /// Contents of `config.json`.
+    #[serde(default)]
+    pub config: Option<Value>,
+    /// Contents of `workspace.json`.
+    #[serde(default)]
+    pub workspace: Option<Value>,
+    /// Frontend storage entries, keyed as in [`FRONTEND_KEYS`].
+    #[serde(default)]
+    pub frontend: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scrollback: Vec<ScrollbackEntry>,
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    [
+        "token",
+        "secret",
+        "passw",
+        "apikey",
+        "api_key",
+        "api-key",
+        "credential",
+    ]
+    .iter()
+    .any(|marker| key.contains(marker))
+}
+
+/// Replace string values under secret-looking keys, at any depth.
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if is_secret_key(key) && value.is_string() {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// Remove entries redacted at export so they don't overwrite anything with
+/// a placeholder.
+pub fn strip_redacted(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, value| value.as_str() != Some(REDACTED));
+            map.values_mut().for_each(strip_redacted);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_redacted),
+        _ => {}
+    }
+}
+
+/// Keep only known keys; JSON entries are redacted, plain strings (the theme
+/// name, the active profile id) pass through.
+pub fn sanitize_frontend(entries: BTreeMap<String, String>) -> BTreeMap<String, String> {
+    entries
+        .into_iter()
+        .filter(|(key, _)| FRONTEND_KEYS.contains(&key.as_str()))
+        .map(|(key, raw)| match serde_json::from_str::<Value>(&raw) {
+            Ok(mut json) if json.is_object() || json.is_array() => {
+                redact(&mut json);
+                (key, json.to_string())
+            }
+            _ => (key, raw),
+        })
+        .collect()
+}
+
+/// Frontend entries to restore from an archive, with redacted placeholders
+/// removed.
+pub fn restorable_frontend(entries: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    entries
+        .iter()
+        .filter(|(key, _)| FRONTEND_KEYS.contains(&key.as_str()))
+        .map(|(key, raw)| match serde_json::from_str::<Value>(raw) {
+            Ok(mut json) if json.is_object() || json.is_array() => {
+                strip_redacted(&mut json);
+                (key.clone(), json.to_string())
+            }
+            _ => (key.clone(), raw.clone()),
+        })
+        .collect()
+}
+
+pub fn parse(data: &str) -> Result<Archive, String> {
+    let archive: Archive =
+        serde_json::from_str(data).map_err(|e| format!("Not a Rain app state archive: {}", e))?;
+    if archive.format != ARCHIVE_FORMAT {
+        return Err(format!("Not a Rain app state archive: {}", archive.format));
+    }
+    if archive.version > ARCHIVE_VERSION {
+        return Err(format!(
+            "Archive version {} is newer than this Rain supports ({})",
+            archive.version, ARCHIVE_VERSION
+        ));
+    }
+    Ok(archive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn secrets_are_redacted_and_dropped_on_import() {
+        let profiles = json!([{
+            "id": "work",
+            "env": { "GITHUB_TOKEN": "ghp_x", "EDITOR": "vim", "DB_PASSWORD": "hunter2" }
+        }]);
+        let mut entries = BTreeMap::new();
+        entries.insert("rain-profiles".to_string(), profiles.to_string());
+        entries.insert("rain-theme".to_string(), "nord".to_string());
+        entries.insert("rain-history".to_string(), "[\"ls\"]".to_string());
+
+        let exported = sanitize_frontend(entries);
+        assert!(!exported.contains_key("rain-history"));
+        assert_eq!(exported["rain-theme"], "nord");
+        let env: Value = serde_json::from_str(&exported["rain-profiles"]).unwrap();
+        assert_eq!(env[0]["env"]["GITHUB_TOKEN"], REDACTED);
+        assert_eq!(env[0]["env"]["DB_PASSWORD"], REDACTED);
+        assert_eq!(env[0]["env"]["EDITOR"], "vim");
+
+        let restored = restorable_frontend(&exported);
+        let env: Value = serde_json::from_str(&restored["rain-profiles"]).unwrap();
+        assert_eq!(env[0]["env"], json!({ "EDITOR": "vim" }));
+    }
+
+    #[test]
+    fn parse_checks_format_and_version() {
+        let archive = Archive {
+            format: ARCHIVE_FORMAT.to_string(),
+            version: ARCHIVE_VERSION,
+            exported_at: 1,
+            diagnostics: Diagnostics {
+                app_version: "0.1.0".to_string(),
+                os: "linux".to_string(),
+                arch: "x86_64".to_string(),
+                session_count: 0,
+                safe_mode: false,
+            },
+            config: Some(json!({ "fontSize": 13 })),
+            workspace: None,
+            frontend: BTreeMap::new(),
+            scrollback: Vec::new(),
+        };
+        let data = serde_json::to_string(&archive).unwrap();
+        assert!(!data.contains("scrollback"));
+        assert_eq!(parse(&data).unwrap(), archive);
+
+        let newer = data.replace("\"version\":1", "\"version\":99");
+        assert!(parse(&newer).unwrap_err().contains("newer"));
+        assert!(parse(r#"{"format":"other"}"#).is_err());
+    }
+}
@@ -0,0 +1,80 @@
+//! Runs Rain's terminal engine without a window and prints every render
+//! frame as a line of JSON.
+//!
+//!     rain-headless [--rows N] [--cols N] [--shell PATH] [--cwd DIR]
+//!                   [--input TEXT] [--idle-timeout SECS]
+//!     rain-headless --replay FILE [--rows N] [--cols N] [--chunk BYTES]
+//!
+//! Without `--replay` a shell is started on a PTY and `--input` is typed
+//! into it; the run ends when the shell exits or has been idle for
+//! `--idle-timeout` seconds, with the shell's exit code.
+
+use std::io::{BufWriter, Write};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use rain_lib::headless::{self, ShellRun, DEFAULT_CHUNK_SIZE};
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("rain-headless: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<ExitCode, String> {
+    let mut run = ShellRun {
+        shell: None,
+        cwd: None,
+        rows: 24,
+        cols: 80,
+        input: Vec::new(),
+        idle_timeout: None,
+    };
+    let mut replay = None;
+    let mut chunk = DEFAULT_CHUNK_SIZE;
+
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", flag));
+        match flag.as_str() {
+            "--rows" => run.rows = parse(&flag, &value()?)?,
+            "--cols" => run.cols = parse(&flag, &value()?)?,
+            "--shell" => run.shell = Some(value()?),
+            "--cwd" => run.cwd = Some(value()?),
+            "--input" => run.input = value()?.into_bytes(),
+            "--idle-timeout" => {
+                run.idle_timeout = Some(Duration::from_secs_f64(parse(&flag, &value()?)?))
+            }
+            "--replay" => replay = Some(value()?),
+            "--chunk" => chunk = parse(&flag, &value()?)?,
+            other => return Err(format!("unknown argument {}", other)),
+        }
+    }
+
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let code = match replay {
+        Some(path) => {
+            let bytes =
+                std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            headless::replay(&bytes, run.rows, run.cols, chunk, &mut out)?;
+            ExitCode::SUCCESS
+        }
+        None => match headless::run_shell(&run, &mut out)? {
+            Some(code) => ExitCode::from(code.clamp(0, 255) as u8),
+            None => ExitCode::SUCCESS,
+        },
+    };
+    out.flush().map_err(|e| e.to_string())?;
+    Ok(code)
+}
+
+fn parse<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", flag, value))
+}
@@ -0,0 +1,276 @@
+//! System clipboard access with selection awareness.
+//!
+//! Linux (X11 and Wayland) has a separate PRIMARY selection that is set by
+//! selecting text and pasted with a middle click. Other platforms only have
+//! the regular clipboard, so PRIMARY reads come back empty and writes are
+//! ignored there.
+//!
+//! Text the terminal copies (through OSC 52 or a copy action in the UI) is
+//! also kept in a bounded in-memory history, so it can be copied again
+//! later. Pinned entries stay until unpinned.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Which system selection to read or write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    Clipboard,
+    Primary,
+}
+
+impl Selection {
+    /// Map an OSC 52 `Pc` selector to selections. `c` is the clipboard,
+    /// `p`/`s` are PRIMARY; cut buffers (`0`-`7`) are not supported. An empty
+    /// selector means the default, `s 0`, which we treat as the clipboard.
+    pub fn from_osc52_targets(targets: &str) -> Vec<Selection> {
+        if targets.is_empty() {
+            return vec![Selection::Clipboard];
+        }
+        let mut out = Vec::new();
+        for c in targets.chars() {
+            let selection = match c {
+                'c' => Selection::Clipboard,
+                'p' | 's' => Selection::Primary,
+                _ => continue,
+            };
+            if !out.contains(&selection) {
+                out.push(selection);
+            }
+        }
+        out
+    }
+}
+
+pub fn write_text(selection: Selection, text: &str) -> Result<(), String> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|e| format!("Clipboard unavailable: {}", e))?;
+    match selection {
+        Selection::Clipboard => clipboard.set_text(text.to_string()),
+        Selection::Primary => return platform::set_primary(&mut clipboard, text),
+    }
+    .map_err(|e| format!("Clipboard write failed: {}", e))
+}
+
+pub fn read_text(selection: Selection) -> Option<String> {
+    let mut clipboard = arboard::Clipboard::new().ok()?;
+    match selection {
+        Selection::Clipboard => clipboard.get_text().ok(),
+        Selection::Primary => platform::get_primary(&mut clipboard),
+    }
+}
+
+/// Unpinned entries the history keeps before dropping the oldest.
+pub const HISTORY_LIMIT: usize = 50;
+/// Larger copies are left out of the history; they would only be kept
+/// around in memory.
+pub const HISTORY_MAX_BYTES: usize = 1 << 20;
+
+/// Where a history entry was copied from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CopySource {
+    /// A program in the terminal, through OSC 52.
+    Osc52,
+    /// A copy action in the UI.
+    Copy,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub text: String,
+    pub source: CopySource,
+    /// Unix time in milliseconds of the latest copy.
+    pub copied_at: u64,
+    pub pinned: bool,
+}
+
+/// Copied text, newest first.
+#[derive(Debug)]
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+    next_id: u64,
+}
+
+impl History {
+    pub const fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Add a copy at the front. Copying text already in the history moves
+    /// that entry to the front instead, keeping its id and pin. Returns the
+    /// entry's id, or `None` when the text is empty or too large to keep.
+    pub fn record(&mut self, text: &str, source: CopySource) -> Option<u64> {
+        if text.is_empty() || text.len() > HISTORY_MAX_BYTES {
+            return None;
+        }
+        let copied_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let entry = match self.entries.iter().position(|e| e.text == text) {
+            Some(idx) => {
+                let mut entry = self.entries.remove(idx).expect("index in range");
+                entry.source = source;
+                entry.copied_at = copied_at;
+                entry
+            }
+            None => {
+                let id = self.next_id;
+                self.next_id += 1;
+                HistoryEntry {
+                    id,
+                    text: text.to_string(),
+                    source,
+                    copied_at,
+                    pinned: false,
+                }
+            }
+        };
+        let id = entry.id;
+        self.entries.push_front(entry);
+
+        let mut unpinned = self.entries.iter().filter(|e| !e.pinned).count();
+        while unpinned > HISTORY_LIMIT {
+            let Some(oldest) = self.entries.iter().rposition(|e| !e.pinned) else {
+                break;
+            };
+            self.entries.remove(oldest);
+            unpinned -= 1;
+        }
+        Some(id)
+    }
+
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<&HistoryEntry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    /// Returns false when there is no such entry.
+    pub fn set_pinned(&mut self, id: u64, pinned: bool) -> bool {
+        match self.entries.iter_mut().find(|e| e.id == id) {
+            Some(entry) => {
+                entry.pinned = pinned;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, id: u64) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|e| e.id != id);
+        self.entries.len() != before
+    }
+
+    /// Forget every entry that isn't pinned.
+    pub fn clear(&mut self) {
+        self.entries.retain(|e| e.pinned);
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The app-wide clipboard history, kept in `AppState` and shared with every
+/// session's terminal for OSC 52 copies.
+pub type SharedHistory = Arc<Mutex<History>>;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use arboard::{GetExtLinux, LinuxClipboardKind, SetExtLinux};
+
+    pub fn set_primary(clipboard: &mut arboard::Clipboard, text: &str) -> Result<(), String> {
+        clipboard
+            .set()
+            .clipboard(LinuxClipboardKind::Primary)
+            .text(text.to_string())
+            .map_err(|e| format!("Primary selection write failed: {}", e))
+    }
+
+    pub fn get_primary(clipboard: &mut arboard::Clipboard) -> Option<String> {
+        clipboard
+            .get()
+            .clipboard(LinuxClipboardKind::Primary)
+            .text()
+            .ok()
+    }
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+mod platform {
+    pub fn set_primary(_clipboard: &mut arboard::Clipboard, _text: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn get_primary(_clipboard: &mut arboard::Clipboard) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc52_targets_map_to_selections() {
+        assert_eq!(
+            Selection::from_osc52_targets(""),
+            vec![Selection::Clipboard]
+        );
+        assert_eq!(
+            Selection::from_osc52_targets("c"),
+            vec![Selection::Clipboard]
+        );
+        assert_eq!(Selection::from_osc52_targets("p"), vec![Selection::Primary]);
+        assert_eq!(
+            Selection::from_osc52_targets("cps"),
+            vec![Selection::Clipboard, Selection::Primary]
+        );
+        assert!(Selection::from_osc52_targets("01").is_empty());
+    }
+
+    #[test]
+    fn history_moves_repeats_to_the_front_and_keeps_pinned_entries() {
+        let mut history = History::new();
+        let first = history.record("first", CopySource::Copy).unwrap();
+        assert_eq!(history.record("", CopySource::Copy), None);
+        assert!(history.set_pinned(first, true));
+        for i in 0..HISTORY_LIMIT {
+            history.record(&format!("copy {i}"), CopySource::Osc52);
+        }
+        assert_eq!(history.record("first", CopySource::Osc52), Some(first));
+
+        let entries = history.entries();
+        assert_eq!(entries.len(), HISTORY_LIMIT + 1);
+        assert_eq!(entries[0].id, first);
+        assert!(entries[0].pinned);
+        assert_eq!(entries[0].source, CopySource::Osc52);
+
+        // One more copy drops the oldest unpinned entry, not the pinned one.
+        history.record("another", CopySource::Copy);
+        let texts: Vec<_> = history.entries().into_iter().map(|e| e.text).collect();
+        assert!(texts.contains(&"first".to_string()));
+        assert!(!texts.contains(&"copy 0".to_string()));
+
+        history.clear();
+        assert_eq!(history.entries().len(), 1);
+        assert!(history.remove(first));
+        assert!(history.get(first).is_none());
+    }
+}
@@ -0,0 +1,153 @@
+//! Window-effect capability detection.
+//!
+//! Transparency and blur need a compositing window manager. macOS and
+//! Windows always composite; on Linux a bare X11 session (no picom, xfwm4
+//! compositing off, ...) renders transparent windows black, so the UI has to
+//! fall back to an opaque background.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompositorCapabilities {
+    /// "macos", "windows", "wayland" or "x11".
+    pub display_server: &'static str,
+    /// Best-effort compositor name (e.g. "KWin", "Hyprland").
+    pub compositor: Option<String>,
+    /// Whether a compositing manager is active.
+    pub composited: bool,
+    /// Whether per-pixel transparency will render correctly.
+    pub transparency: bool,
+    /// Whether background blur behind the window is available.
+    pub blur: bool,
+    /// Human-readable hint when effects had to be degraded.
+    pub advice: Option<String>,
+}
+
+impl CompositorCapabilities {
+    /// Platforms where the OS compositor is always on.
+    #[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+    pub fn native(display_server: &'static str) -> Self {
+        Self {
+            display_server,
+            compositor: None,
+            composited: true,
+            transparency: true,
+            blur: true,
+            advice: None,
+        }
+    }
+
+    /// Linux capabilities from the session environment plus the toolkit's
+    /// view of whether the screen is composited.
+    pub fn linux(env: impl Fn(&str) -> Option<String>, composited: bool) -> Self {
+        let wayland = env("WAYLAND_DISPLAY").is_some_and(|v| !v.is_empty())
+            || env("XDG_SESSION_TYPE").is_some_and(|v| v.eq_ignore_ascii_case("wayland"));
+        let compositor = identify_compositor(&env);
+        // Wayland compositors always composite, whatever GDK reports for
+        // XWayland.
+        let composited = composited || wayland;
+        // Rain can't request blur itself on Linux; only compositors with
+        // their own blur rules can provide it.
+        let blur = composited
+            && compositor
+                .as_deref()
+                .is_some_and(|name| matches!(name, "KWin" | "Hyprland"));
+
+        let advice = if !composited {
+            Some(
+                "No compositor detected: window transparency and blur are disabled. \
+                 Start a compositor such as picom to enable them."
+                    .to_string(),
+            )
+        } else if !blur {
+            Some(format!(
+                "{} does not provide background blur; blur is disabled.",
+                compositor.as_deref().unwrap_or("This compositor")
+            ))
+        } else {
+            None
+        };
+
+        Self {
+            display_server: if wayland { "wayland" } else { "x11" },
+            compositor,
+            composited,
+            transparency: composited,
+            blur,
+            advice,
+        }
+    }
+}
+
+/// Guess the running compositor from well-known session variables.
+fn identify_compositor(env: &impl Fn(&str) -> Option<String>) -> Option<String> {
+    if env("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        return Some("Hyprland".to_string());
+    }
+    if env("SWAYSOCK").is_some() {
+        return Some("sway".to_string());
+    }
+    if env("NIRI_SOCKET").is_some() {
+        return Some("niri".to_string());
+    }
+    let desktop = env("XDG_CURRENT_DESKTOP")?;
+    let desktop_upper = desktop.to_ascii_uppercase();
+    if desktop_upper.split(':').any(|d| d == "KDE") {
+        Some("KWin".to_string())
+    } else if desktop_upper.split(':').any(|d| d == "GNOME") {
+        Some("Mutter".to_string())
+    } else if desktop.is_empty() {
+        None
+    } else {
+        Some(desktop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env_from(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |key| {
+            vars.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.to_string())
+        }
+    }
+
+    #[test]
+    fn bare_x11_disables_effects() {
+        let caps = CompositorCapabilities::linux(env_from(&[("XDG_SESSION_TYPE", "x11")]), false);
+        assert_eq!(caps.display_server, "x11");
+        assert!(!caps.transparency);
+        assert!(!caps.blur);
+        assert!(caps.advice.is_some());
+    }
+
+    #[test]
+    fn wayland_compositors_are_identified() {
+        let caps = CompositorCapabilities::linux(
+            env_from(&[
+                ("WAYLAND_DISPLAY", "wayland-0"),
+                ("XDG_CURRENT_DESKTOP", "KDE"),
+            ]),
+            false,
+        );
+        assert_eq!(caps.display_server, "wayland");
+        assert_eq!(caps.compositor.as_deref(), Some("KWin"));
+        assert!(caps.transparency && caps.blur);
+        assert_eq!(caps.advice, None);
+
+        let caps = CompositorCapabilities::linux(
+            env_from(&[
+                ("WAYLAND_DISPLAY", "wayland-1"),
+                ("XDG_CURRENT_DESKTOP", "ubuntu:GNOME"),
+            ]),
+            true,
+        );
+        assert_eq!(caps.compositor.as_deref(), Some("Mutter"));
+        assert!(caps.transparency);
+        assert!(!caps.blur);
+    }
+}
@@ -0,0 +1,279 @@
+//! Layout of the native terminal right-click menu.
+//!
+//! The frontend reports what it hit-tested (selection, link, block); the
+//! backend adds what only it knows (alt screen, shell integration, tmux pane)
+//! and decides which items to show. The chosen item comes back as a
+//! [`MenuAction`] on the `terminal-context-action` event.
+
+use serde::{Deserialize, Serialize};
+
+/// Prefix on every menu item ID so app-wide menu events can be filtered.
+pub const ID_PREFIX: &str = "terminal-menu:";
+
+/// What the frontend found under the pointer.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MenuContext {
+    pub has_selection: bool,
+    pub link_url: Option<String>,
+    /// ID of the command block under the pointer, if any.
+    pub block_id: Option<String>,
+}
+
+/// Backend state of the session the menu was opened on.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SessionMenuState {
+    pub alt_screen: bool,
+    pub shell_integration: bool,
+    pub tmux_pane: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MenuAction {
+    Copy,
+    Paste,
+    SelectAll,
+    SearchSelection,
+    OpenLink,
+    CopyLink,
+    CopyBlockOutput,
+    CopyBlockMarkdown,
+    ShareBlock,
+    SaveBlock,
+    PreviewBlock,
+    SplitRight,
+    SplitDown,
+    TmuxDetach,
+    Clear,
+    Export,
+}
+
+const ALL_ACTIONS: [MenuAction; 16] = [
+    MenuAction::Copy,
+    MenuAction::Paste,
+    MenuAction::SelectAll,
+    MenuAction::SearchSelection,
+    MenuAction::OpenLink,
+    MenuAction::CopyLink,
+    MenuAction::CopyBlockOutput,
+    MenuAction::CopyBlockMarkdown,
+    MenuAction::ShareBlock,
+    MenuAction::SaveBlock,
+    MenuAction::PreviewBlock,
+    MenuAction::SplitRight,
+    MenuAction::SplitDown,
+    MenuAction::TmuxDetach,
+    MenuAction::Clear,
+    MenuAction::Export,
+];
+
+impl MenuAction {
+    fn name(self) -> &'static str {
+        match self {
+            MenuAction::Copy => "copy",
+            MenuAction::Paste => "paste",
+            MenuAction::SelectAll => "select-all",
+            MenuAction::SearchSelection => "search-selection",
+            MenuAction::OpenLink => "open-link",
+            MenuAction::CopyLink => "copy-link",
+            MenuAction::CopyBlockOutput => "copy-block-output",
+            MenuAction::CopyBlockMarkdown => "copy-block-markdown",
+            MenuAction::ShareBlock => "share-block",
+            MenuAction::SaveBlock => "save-block",
+            MenuAction::PreviewBlock => "preview-block",
+            MenuAction::SplitRight => "split-right",
+            MenuAction::SplitDown => "split-down",
+            MenuAction::TmuxDetach => "tmux-detach",
+            MenuAction::Clear => "clear",
+            MenuAction::Export => "export",
+        }
+    }
+
+    /// Menu item ID, e.g. `terminal-menu:copy`.
+    pub fn id(self) -> String {
+        format!("{}{}", ID_PREFIX, self.name())
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        let name = id.strip_prefix(ID_PREFIX)?;
+        ALL_ACTIONS.into_iter().find(|action| action.name() == name)
+    }
+
+    /// Label with an `&` mnemonic for keyboard navigation (ignored on macOS).
+    pub fn label(self) -> &'static str {
+        match self {
+            MenuAction::Copy => "&Copy",
+            MenuAction::Paste => "&Paste",
+            MenuAction::SelectAll => "Select &All",
+            MenuAction::SearchSelection => "&Search Selection",
+            MenuAction::OpenLink => "&Open Link",
+            MenuAction::CopyLink => "Copy &Link",
+            MenuAction::CopyBlockOutput => "Copy Command &Output",
+            MenuAction::CopyBlockMarkdown => "Copy as &Markdown",
+            MenuAction::ShareBlock => "Share Command O&utput…",
+            MenuAction::SaveBlock => "Sa&ve Command Output…",
+            MenuAction::PreviewBlock => "Previe&w Command Output",
+            MenuAction::SplitRight => "Split Pane &Right",
+            MenuAction::SplitDown => "Split Pane &Down",
+            MenuAction::TmuxDetach => "Detac&h tmux",
+            MenuAction::Clear => "Cl&ear",
+            MenuAction::Export => "E&xport",
+        }
+    }
+
+    /// Shortcut shown next to the item, matching the in-app key bindings.
+    pub fn accelerator(self) -> Option<&'static str> {
+        match self {
+            MenuAction::Copy => Some("CmdOrCtrl+C"),
+            MenuAction::Paste => Some("CmdOrCtrl+V"),
+            MenuAction::SelectAll => Some("CmdOrCtrl+A"),
+            MenuAction::Clear => Some("CmdOrCtrl+K"),
+            MenuAction::Export => Some("CmdOrCtrl+S"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuEntry {
+    Item { action: MenuAction, enabled: bool },
+    Separator,
+}
+
+fn item(action: MenuAction, enabled: bool) -> MenuEntry {
+    MenuEntry::Item { action, enabled }
+}
+
+/// Build the menu for one right-click.
+pub fn entries(ctx: &MenuContext, session: SessionMenuState) -> Vec<MenuEntry> {
+    let mut out = vec![
+        item(MenuAction::Copy, ctx.has_selection),
+        item(MenuAction::Paste, true),
+        item(MenuAction::SelectAll, true),
+    ];
+
+    if ctx.has_selection {
+        out.push(MenuEntry::Separator);
+        out.push(item(MenuAction::SearchSelection, true));
+    }
+
+    if ctx.link_url.is_some() {
+        out.push(MenuEntry::Separator);
+        out.push(item(MenuAction::OpenLink, true));
+        out.push(item(MenuAction::CopyLink, true));
+    }
+
+    // Block output is only addressable while the shell reports blocks, and
+    // full-screen apps hide the blocks underneath them.
+    if ctx.block_id.is_some() && session.shell_integration && !session.alt_screen {
+        out.push(MenuEntry::Separator);
+        out.push(item(MenuAction::CopyBlockOutput, true));
+        out.push(item(MenuAction::CopyBlockMarkdown, true));
+        out.push(item(MenuAction::ShareBlock, true));
+        out.push(item(MenuAction::SaveBlock, true));
+        out.push(item(MenuAction::PreviewBlock, true));
+    }
+
+    out.push(MenuEntry::Separator);
+    out.push(item(MenuAction::SplitRight, true));
+    out.push(item(MenuAction::SplitDown, true));
+    if session.tmux_pane {
+        out.push(item(MenuAction::TmuxDetach, true));
+    }
+
+    out.push(MenuEntry::Separator);
+    out.push(item(MenuAction::Clear, !session.alt_screen));
+    out.push(item(MenuAction::Export, true));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn actions(entries: &[MenuEntry]) -> Vec<MenuAction> {
+        entries
+            .iter()
+            .filter_map(|e| match e {
+                MenuEntry::Item { action, .. } => Some(*action),
+                MenuEntry::Separator => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn action_ids_round_trip() {
+        for action in ALL_ACTIONS {
+            assert_eq!(MenuAction::from_id(&action.id()), Some(action));
+        }
+        assert_eq!(MenuAction::from_id("copy"), None);
+        assert_eq!(MenuAction::from_id("terminal-menu:nope"), None);
+    }
+
+    #[test]
+    fn plain_click_has_no_contextual_items() {
+        let list = entries(&MenuContext::default(), SessionMenuState::default());
+        assert_eq!(
+            list[0],
+            MenuEntry::Item {
+                action: MenuAction::Copy,
+                enabled: false
+            }
+        );
+        let acts = actions(&list);
+        assert!(!acts.contains(&MenuAction::SearchSelection));
+        assert!(!acts.contains(&MenuAction::OpenLink));
+        assert!(!acts.contains(&MenuAction::CopyBlockOutput));
+        assert!(!acts.contains(&MenuAction::ShareBlock));
+        assert!(!acts.contains(&MenuAction::TmuxDetach));
+    }
+
+    #[test]
+    fn block_items_need_shell_integration_and_primary_screen() {
+        let ctx = MenuContext {
+            block_id: Some("b1".into()),
+            ..Default::default()
+        };
+        let mut session = SessionMenuState {
+            shell_integration: true,
+            ..Default::default()
+        };
+        let acts = actions(&entries(&ctx, session));
+        assert!(acts.contains(&MenuAction::CopyBlockOutput));
+        assert!(acts.contains(&MenuAction::CopyBlockMarkdown));
+        assert!(acts.contains(&MenuAction::ShareBlock));
+        assert!(acts.contains(&MenuAction::SaveBlock));
+        assert!(acts.contains(&MenuAction::PreviewBlock));
+
+        session.alt_screen = true;
+        let list = entries(&ctx, session);
+        assert!(!actions(&list).contains(&MenuAction::CopyBlockOutput));
+        assert!(list.contains(&MenuEntry::Item {
+            action: MenuAction::Clear,
+            enabled: false
+        }));
+    }
+
+    #[test]
+    fn link_and_tmux_items() {
+        let ctx = MenuContext {
+            has_selection: true,
+            link_url: Some("https://example.com".into()),
+            ..Default::default()
+        };
+        let session = SessionMenuState {
+            tmux_pane: true,
+            ..Default::default()
+        };
+        let acts = actions(&entries(&ctx, session));
+        for expected in [
+            MenuAction::SearchSelection,
+            MenuAction::OpenLink,
+            MenuAction::CopyLink,
+            MenuAction::TmuxDetach,
+        ] {
+            assert!(acts.contains(&expected), "missing {:?}", expected);
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! Thin OS keychain integration so tokens Rain needs (settings sync, AI
+//! endpoints) never sit in the plain-text config file.
+//!
+//! - macOS: login Keychain (generic passwords)
+//! - Windows: Credential Manager (generic credentials)
+//! - Linux/BSD: Secret Service via libsecret's `secret-tool`
+
+/// Service name every secret is filed under.
+const SERVICE: &str = "com.rain.terminal";
+
+/// Keys are namespaced identifiers such as `sync.token` or `ai/openai`.
+fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty() || key.len() > 128 {
+        return Err("Secret key must be 1-128 characters".to_string());
+    }
+    if !key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | ':' | '/'))
+    {
+        return Err(format!("Invalid secret key: {}", key));
+    }
+    Ok(())
+}
+
+/// Store (or overwrite) a secret in the OS credential store.
+pub fn store_secret(key: &str, secret: &str) -> Result<(), String> {
+    validate_key(key)?;
+    platform::store(key, secret)
+}
+
+/// Fetch a secret. Returns `None` when nothing is stored under `key`.
+pub fn get_secret(key: &str) -> Result<Option<String>, String> {
+    validate_key(key)?;
+    platform::get(key)
+}
+
+/// Remove a secret. Deleting a missing key is not an error.
+pub fn delete_secret(key: &str) -> Result<(), String> {
+    validate_key(key)?;
+    platform::delete(key)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use security_framework::passwords;
+
+    use super::SERVICE;
+
+    /// errSecItemNotFound
+    const ITEM_NOT_FOUND: i32 = -25300;
+
+    pub fn store(key: &str, secret: &str) -> Result<(), String> {
+        passwords::set_generic_password(SERVICE, key, secret.as_bytes())
+            .map_err(|e| format!("Keychain write failed: {}", e))
+    }
+
+    pub fn get(key: &str) -> Result<Option<String>, String> {
+        match passwords::get_generic_password(SERVICE, key) {
+            Ok(bytes) => String::from_utf8(bytes)
+                .map(Some)
+                .map_err(|_| "Keychain item is not valid UTF-8".to_string()),
+            Err(e) if e.code() == ITEM_NOT_FOUND => Ok(None),
+            Err(e) => Err(format!("Keychain read failed: {}", e)),
+        }
+    }
+
+    pub fn delete(key: &str) -> Result<(), String> {
+        match passwords::delete_generic_password(SERVICE, key) {
+            Ok(()) => Ok(()),
+            Err(e) if e.code() == ITEM_NOT_FOUND => Ok(()),
+            Err(e) => Err(format!("Keychain delete failed: {}", e)),
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use windows_sys::Win32::Foundation::{GetLastError, ERROR_NOT_FOUND};
+    use windows_sys::Win32::Security::Credentials::{
+        CredDeleteW, CredFree, CredReadW, CredWriteW, CREDENTIALW, CRED_PERSIST_LOCAL_MACHINE,
+        CRED_TYPE_GENERIC,
+    };
+
+    use super::SERVICE;
+
+    fn target_name(key: &str) -> Vec<u16> {
+        format!("{}/{}", SERVICE, key)
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .collect()
+    }
+
+    pub fn store(key: &str, secret: &str) -> Result<(), String> {
+        let mut target = target_name(key);
+        let mut blob = secret.as_bytes().to_vec();
+        let mut user: Vec<u16> = "rain".encode_utf16().chain(std::iter::once(0)).collect();
+
+        let ok = unsafe {
+            let mut credential: CREDENTIALW = std::mem::zeroed();
+            credential.Type = CRED_TYPE_GENERIC;
+            credential.TargetName = target.as_mut_ptr();
+            credential.CredentialBlobSize = blob.len() as u32;
+            credential.CredentialBlob = blob.as_mut_ptr();
+            credential.Persist = CRED_PERSIST_LOCAL_MACHINE;
+            credential.UserName = user.as_mut_ptr();
+            CredWriteW(&credential, 0)
+        };
+        if ok == 0 {
+            return Err(format!(
+                "Credential Manager write failed: error {}",
+                unsafe { GetLastError() }
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn get(key: &str) -> Result<Option<String>, String> {
+        let target = target_name(key);
+        let mut credential: *mut CREDENTIALW = std::ptr::null_mut();
+        let ok = unsafe { CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &mut credential) };
+        if ok == 0 {
+            let err = unsafe { GetLastError() };
+            if err == ERROR_NOT_FOUND {
+                return Ok(None);
+            }
+            return Err(format!("Credential Manager read failed: error {}", err));
+        }
+
+        let bytes = unsafe {
+            let cred = &*credential;
+            let blob = if cred.CredentialBlob.is_null() {
+                Vec::new()
+            } else {
+                std::slice::from_raw_parts(cred.CredentialBlob, cred.CredentialBlobSize as usize)
+                    .to_vec()
+            };
+            CredFree(credential as *const _);
+            blob
+        };
+        String::from_utf8(bytes)
+            .map(Some)
+            .map_err(|_| "Stored credential is not valid UTF-8".to_string())
+    }
+
+    pub fn delete(key: &str) -> Result<(), String> {
+        let target = target_name(key);
+        let ok = unsafe { CredDeleteW(target.as_ptr(), CRED_TYPE_GENERIC, 0) };
+        if ok == 0 {
+            let err = unsafe { GetLastError() };
+            if err != ERROR_NOT_FOUND {
+                return Err(format!("Credential Manager delete failed: error {}", err));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod platform {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    use super::SERVICE;
+
+    fn secret_tool() -> Command {
+        Command::new("secret-tool")
+    }
+
+    fn spawn_error(e: std::io::Error) -> String {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            "secret-tool not found; install libsecret-tools to store secrets".to_string()
+        } else {
+            format!("Failed to run secret-tool: {}", e)
+        }
+    }
+
+    pub fn store(key: &str, secret: &str) -> Result<(), String> {
+        // The secret goes over stdin so it never shows up in the process list.
+        let mut child = secret_tool()
+            .args(["store", "--label", &format!("Rain: {}", key)])
+            .args(["service", SERVICE, "account", key])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(spawn_error)?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(secret.as_bytes())
+                .map_err(|e| format!("Failed to pass secret to secret-tool: {}", e))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("secret-tool store failed: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "secret-tool store failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn get(key: &str) -> Result<Option<String>, String> {
+        let output = secret_tool()
+            .args(["lookup", "service", SERVICE, "account", key])
+            .stdin(Stdio::null())
+            .output()
+            .map_err(spawn_error)?;
+        // secret-tool exits non-zero with empty stderr when nothing matches.
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.trim().is_empty() {
+                return Ok(None);
+            }
+            return Err(format!("secret-tool lookup failed: {}", stderr.trim()));
+        }
+        String::from_utf8(output.stdout)
+            .map(Some)
+            .map_err(|_| "Stored secret is not valid UTF-8".to_string())
+    }
+
+    pub fn delete(key: &str) -> Result<(), String> {
+        let output = secret_tool()
+            .args(["clear", "service", SERVICE, "account", key])
+            .stdin(Stdio::null())
+            .output()
+            .map_err(spawn_error)?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() && !stderr.trim().is_empty() {
+            return Err(format!("secret-tool clear failed: {}", stderr.trim()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_keys_are_validated() {
+        assert!(validate_key("sync.token").is_ok());
+        assert!(validate_key("ai/openai:default").is_ok());
+        assert!(validate_key("").is_err());
+        assert!(validate_key("has space").is_err());
+        assert!(validate_key(&"k".repeat(129)).is_err());
+    }
+}
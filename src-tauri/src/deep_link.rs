@@ -0,0 +1,208 @@
+//! `rain://` deep links, e.g. from docs and runbooks.
+//!
+//! - `rain://open?path=/abs/dir` opens a tab in a directory
+//! - `rain://run?command=make%20test&cwd=/abs/dir` runs a command in a new
+//!   tab, after the user confirms it in a native dialog
+//! - `rain://tmux?session=name` attaches a tmux session
+//!
+//! Links come from untrusted pages, so parsing is strict: unknown actions or
+//! parameters, relative paths and control characters are all rejected.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+pub const SCHEME: &str = "rain";
+
+/// Longest command a link may carry.
+const MAX_COMMAND_LEN: usize = 1024;
+/// Longest tmux session name a link may carry.
+const MAX_SESSION_NAME_LEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum DeepLink {
+    Open {
+        path: PathBuf,
+    },
+    Run {
+        command: String,
+        cwd: Option<PathBuf>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Tmux {
+        session_name: String,
+    },
+}
+
+/// Decode one `application/x-www-form-urlencoded` component.
+fn decode_component(raw: &str) -> Result<String, String> {
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' => {
+                let byte = bytes
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| format!("Invalid escape in link: {}", raw))?;
+                out.push(byte);
+                i += 2;
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8(out).map_err(|_| "Link parameter is not valid UTF-8".to_string())
+}
+
+fn parse_query(query: &str) -> Result<Vec<(String, String)>, String> {
+    let mut params = Vec::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let key = decode_component(key)?;
+        if params.iter().any(|(k, _)| *k == key) {
+            return Err(format!("Duplicate link parameter: {}", key));
+        }
+        params.push((key, decode_component(value)?));
+    }
+    Ok(params)
+}
+
+fn take_param(params: &mut Vec<(String, String)>, key: &str) -> Option<String> {
+    let index = params.iter().position(|(k, _)| k == key)?;
+    Some(params.remove(index).1)
+}
+
+fn absolute_path(value: &str) -> Result<PathBuf, String> {
+    if value.chars().any(char::is_control) {
+        return Err("Link path contains control characters".to_string());
+    }
+    let path = PathBuf::from(value);
+    if !path.is_absolute() {
+        return Err(format!("Link path must be absolute: {}", value));
+    }
+    Ok(path)
+}
+
+fn validate_command(command: &str) -> Result<(), String> {
+    if command.trim().is_empty() {
+        return Err("Link command is empty".to_string());
+    }
+    if command.len() > MAX_COMMAND_LEN {
+        return Err(format!(
+            "Link command is longer than {} bytes",
+            MAX_COMMAND_LEN
+        ));
+    }
+    // A newline would submit a second, unconfirmed command line.
+    if command.chars().any(char::is_control) {
+        return Err("Link command contains control characters".to_string());
+    }
+    Ok(())
+}
+
+fn validate_session_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_SESSION_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid tmux session name: {}", name))
+    }
+}
+
+/// Parse and validate a `rain://` link.
+pub fn parse(url: &str) -> Result<DeepLink, String> {
+    let rest = url
+        .strip_prefix(SCHEME)
+        .and_then(|r| r.strip_prefix("://"))
+        .ok_or_else(|| format!("Not a {}:// link", SCHEME))?;
+    let rest = rest.split('#').next().unwrap_or(rest);
+    let (action, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let mut params = parse_query(query)?;
+
+    let link = match action.trim_end_matches('/') {
+        "open" => {
+            let path = take_param(&mut params, "path").ok_or("Link is missing `path`")?;
+            DeepLink::Open {
+                path: absolute_path(&path)?,
+            }
+        }
+        "run" => {
+            let command = take_param(&mut params, "command").ok_or("Link is missing `command`")?;
+            validate_command(&command)?;
+            let cwd = take_param(&mut params, "cwd")
+                .map(|cwd| absolute_path(&cwd))
+                .transpose()?;
+            DeepLink::Run { command, cwd }
+        }
+        "tmux" => {
+            let session_name =
+                take_param(&mut params, "session").ok_or("Link is missing `session`")?;
+            validate_session_name(&session_name)?;
+            DeepLink::Tmux { session_name }
+        }
+        other => return Err(format!("Unknown link action: {}", other)),
+    };
+
+    if let Some((key, _)) = params.first() {
+        return Err(format!("Unexpected link parameter: {}", key));
+    }
+    Ok(link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_action() {
+        assert_eq!(
+            parse("rain://open?path=/tmp/my%20project").unwrap(),
+            DeepLink::Open {
+                path: PathBuf::from("/tmp/my project")
+            }
+        );
+        assert_eq!(
+            parse("rain://run/?command=make+test&cwd=%2Fsrv").unwrap(),
+            DeepLink::Run {
+                command: "make test".into(),
+                cwd: Some(PathBuf::from("/srv")),
+            }
+        );
+        assert_eq!(
+            parse("rain://tmux?session=deploy-1").unwrap(),
+            DeepLink::Tmux {
+                session_name: "deploy-1".into()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_unsafe_or_malformed_links() {
+        for bad in [
+            "https://open?path=/tmp",
+            "rain://open",
+            "rain://open?path=relative/dir",
+            "rain://open?path=/tmp&extra=1",
+            "rain://open?path=/tmp&path=/etc",
+            "rain://run?command=ls%0Arm%20-rf%20~",
+            "rain://run?command=%20%20",
+            "rain://run?command=ls&cwd=..",
+            "rain://run?command=%zz",
+            "rain://tmux?session=a;b",
+            "rain://exec?command=ls",
+        ] {
+            assert!(parse(bad).is_err(), "accepted {}", bad);
+        }
+        let long = format!("rain://run?command={}", "x".repeat(MAX_COMMAND_LEN + 1));
+        assert!(parse(&long).is_err());
+    }
+}
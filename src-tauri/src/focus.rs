@@ -0,0 +1,127 @@
+//! Which session has keyboard focus, for focus in/out reports (mode 1004).
+//!
+//! Each window tells the backend which of its sessions has keyboard focus,
+//! and native window focus events say which window is focused. A session is
+//! focused while both hold, so switching panes, tabs or windows all report
+//! through the same change.
+
+use std::collections::HashMap;
+
+/// Sessions that lost and gained focus in one update.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FocusChange {
+    pub lost: Option<String>,
+    pub gained: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct FocusTracker {
+    /// Session with keyboard focus in each window, by window label.
+    sessions: HashMap<String, String>,
+    focused_window: Option<String>,
+}
+
+impl FocusTracker {
+    pub fn focused_session(&self) -> Option<&str> {
+        let window = self.focused_window.as_ref()?;
+        self.sessions.get(window).map(String::as_str)
+    }
+
+    /// `session_id` gained or lost keyboard focus within `window`. Losing it
+    /// only counts while it is still that window's focused session, so a
+    /// late blur can't undo the next pane's focus.
+    pub fn set_session_focus(
+        &mut self,
+        window: &str,
+        session_id: &str,
+        focused: bool,
+    ) -> FocusChange {
+        self.update(|tracker| {
+            if focused {
+                tracker
+                    .sessions
+                    .insert(window.to_string(), session_id.to_string());
+            } else if tracker.sessions.get(window).map(String::as_str) == Some(session_id) {
+                tracker.sessions.remove(window);
+            }
+        })
+    }
+
+    pub fn set_window_focus(&mut self, window: &str, focused: bool) -> FocusChange {
+        self.update(|tracker| {
+            if focused {
+                tracker.focused_window = Some(window.to_string());
+            } else if tracker.focused_window.as_deref() == Some(window) {
+                tracker.focused_window = None;
+            }
+        })
+    }
+
+    pub fn remove_window(&mut self, window: &str) -> FocusChange {
+        self.update(|tracker| {
+            tracker.sessions.remove(window);
+            if tracker.focused_window.as_deref() == Some(window) {
+                tracker.focused_window = None;
+            }
+        })
+    }
+
+    fn update(&mut self, apply: impl FnOnce(&mut Self)) -> FocusChange {
+        let before = self.focused_session().map(str::to_string);
+        apply(self);
+        let after = self.focused_session().map(str::to_string);
+        if before == after {
+            return FocusChange::default();
+        }
+        FocusChange {
+            lost: before,
+            gained: after,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(lost: Option<&str>, gained: Option<&str>) -> FocusChange {
+        FocusChange {
+            lost: lost.map(str::to_string),
+            gained: gained.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn sessions_are_focused_with_their_window() {
+        let mut focus = FocusTracker::default();
+        assert_eq!(
+            focus.set_session_focus("main", "a", true),
+            change(None, None)
+        );
+        assert_eq!(
+            focus.set_window_focus("main", true),
+            change(None, Some("a"))
+        );
+
+        // Pane switch: blur then focus, or a blur that arrives late.
+        assert_eq!(
+            focus.set_session_focus("main", "b", true),
+            change(Some("a"), Some("b"))
+        );
+        assert_eq!(
+            focus.set_session_focus("main", "a", false),
+            change(None, None)
+        );
+
+        // Another window takes focus; the blur of the first comes after.
+        focus.set_session_focus("w1", "c", true);
+        assert_eq!(
+            focus.set_window_focus("w1", true),
+            change(Some("b"), Some("c"))
+        );
+        assert_eq!(focus.set_window_focus("main", false), change(None, None));
+
+        assert_eq!(focus.remove_window("w1"), change(Some("c"), None));
+        assert_eq!(focus.focused_session(), None);
+    }
+}
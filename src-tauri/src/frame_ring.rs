@@ -0,0 +1,207 @@
+//! In-memory ring of encoded render frames, for sessions that produce
+//! output faster than frames can go through the event bridge one by one.
+//!
+//! The render pump appends each frame to its session's ring, which the
+//! `rain-frames` URI scheme serves from, and only emits a small
+//! `frame-ready` event with the ring's new head. The webview then fetches
+//! everything from its read position to the head in one request, however
+//! many frames that is. A reader that falls more than a ring behind gets
+//! told so and starts over from a full redraw.
+//!
+//! Like `rain-asset` URLs, ring URLs carry a random token of the ring, so
+//! knowing a session ID isn't enough to read its output.
+
+/// Name of the URI scheme registered in `lib.rs`.
+pub const SCHEME: &str = "rain-frames";
+
+/// Bytes of encoded frames each session's ring holds.
+pub const RING_CAPACITY: usize = 8 * 1024 * 1024;
+
+/// Each record is its length as a little-endian u32, then the frame.
+const LEN_PREFIX: usize = 4;
+
+pub struct FrameRing {
+    buf: Vec<u8>,
+    /// Required in the URL of every read.
+    token: String,
+    /// Bytes written since the ring was created; the next record starts at
+    /// `head % capacity`.
+    head: u64,
+}
+
+/// What a reader gets from [`FrameRing::read_from`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum RingRead {
+    /// The records from the read position to the head, in order.
+    Frames(Vec<u8>),
+    /// The read position was already overwritten (or is past the head).
+    Overrun,
+}
+
+impl FrameRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buf: vec![0; capacity],
+            token: uuid::Uuid::new_v4().simple().to_string(),
+            head: 0,
+        }
+    }
+
+    /// URL the webview reads this ring from, with `?from=<cursor>`
+    /// appended. Webviews on Windows only load custom schemes through
+    /// `http://<scheme>.localhost`.
+    pub fn url(&self, session_id: &str) -> String {
+        if cfg!(windows) {
+            format!("http://{}.localhost/{}/{}", SCHEME, session_id, self.token)
+        } else {
+            format!("{}://localhost/{}/{}", SCHEME, session_id, self.token)
+        }
+    }
+
+    /// Whether a request carried this ring's token.
+    pub fn accepts(&self, token: &str) -> bool {
+        self.token == token
+    }
+
+    pub fn head(&self) -> u64 {
+        self.head
+    }
+
+    fn capacity(&self) -> u64 {
+        self.buf.len() as u64
+    }
+
+    /// Append one encoded frame. False, with nothing written, when it
+    /// doesn't fit in the ring at all.
+    pub fn push(&mut self, frame: &[u8]) -> bool {
+        let Ok(len) = u32::try_from(frame.len()) else {
+            return false;
+        };
+        if (LEN_PREFIX + frame.len()) as u64 > self.capacity() {
+            return false;
+        }
+        self.write(&len.to_le_bytes());
+        self.write(frame);
+        true
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let start = (self.head % self.capacity()) as usize;
+        let first = bytes.len().min(self.buf.len() - start);
+        self.buf[start..start + first].copy_from_slice(&bytes[..first]);
+        self.buf[..bytes.len() - first].copy_from_slice(&bytes[first..]);
+        self.head += bytes.len() as u64;
+    }
+
+    /// The records written since `cursor`, a head this ring reported
+    /// before; the caller's next cursor is `cursor` plus their length.
+    pub fn read_from(&self, cursor: u64) -> RingRead {
+        if cursor > self.head || self.head - cursor > self.capacity() {
+            return RingRead::Overrun;
+        }
+        let len = (self.head - cursor) as usize;
+        let start = (cursor % self.capacity()) as usize;
+        let first = len.min(self.buf.len() - start);
+        let mut out = Vec::with_capacity(len);
+        out.extend_from_slice(&self.buf[start..start + first]);
+        out.extend_from_slice(&self.buf[..len - first]);
+        RingRead::Frames(out)
+    }
+}
+
+/// A ring read: whose ring, the token the URL carried, and from where.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RingRequest<'a> {
+    pub session_id: &'a str,
+    pub token: &'a str,
+    pub cursor: u64,
+}
+
+/// Parse a ring request's URI (`/<session>/<token>?from=<cursor>`).
+pub fn parse_request<'a>(path: &'a str, query: Option<&str>) -> Option<RingRequest<'a>> {
+    let (session_id, token) = path.strip_prefix('/')?.split_once('/')?;
+    let cursor = query?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("from="))?
+        .parse()
+        .ok()?;
+    (!session_id.is_empty() && !token.is_empty()).then_some(RingRequest {
+        session_id,
+        token,
+        cursor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(mut bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut out = Vec::new();
+        while !bytes.is_empty() {
+            let len = u32::from_le_bytes(bytes[..LEN_PREFIX].try_into().unwrap()) as usize;
+            out.push(bytes[LEN_PREFIX..LEN_PREFIX + len].to_vec());
+            bytes = &bytes[LEN_PREFIX + len..];
+        }
+        out
+    }
+
+    #[test]
+    fn readers_get_every_frame_since_their_cursor_across_the_wrap() {
+        let mut ring = FrameRing::new(32);
+        assert!(ring.push(b"first"));
+        let cursor = ring.head();
+        assert!(ring.push(b"second"));
+        assert!(ring.push(b"third!"));
+        assert!(ring.push(b"fourth"));
+        assert_eq!(ring.head(), 4 * 4 + 5 + 6 + 6 + 6);
+        let RingRead::Frames(bytes) = ring.read_from(cursor) else {
+            panic!("cursor is still in the ring");
+        };
+        assert_eq!(
+            records(&bytes),
+            vec![b"second".to_vec(), b"third!".to_vec(), b"fourth".to_vec()]
+        );
+        assert_eq!(ring.read_from(ring.head()), RingRead::Frames(Vec::new()));
+
+        // The start of "first" is overwritten by now.
+        assert_eq!(ring.read_from(0), RingRead::Overrun);
+        assert_eq!(ring.read_from(ring.head() + 1), RingRead::Overrun);
+        assert!(!ring.push(&[0; 29]));
+    }
+
+    #[test]
+    fn requests_name_a_session_its_token_and_a_cursor() {
+        let request = |session_id, token, cursor| {
+            Some(RingRequest {
+                session_id,
+                token,
+                cursor,
+            })
+        };
+        assert_eq!(
+            parse_request("/abc/t0k", Some("from=42")),
+            request("abc", "t0k", 42)
+        );
+        assert_eq!(
+            parse_request("/abc/t0k", Some("x=1&from=7")),
+            request("abc", "t0k", 7)
+        );
+        assert_eq!(parse_request("/abc/t0k", None), None);
+        assert_eq!(parse_request("/abc", Some("from=1")), None);
+        assert_eq!(parse_request("/abc/", Some("from=1")), None);
+        assert_eq!(parse_request("//t0k", Some("from=1")), None);
+        assert_eq!(parse_request("/abc/t0k", Some("from=-1")), None);
+    }
+
+    #[test]
+    fn ring_urls_carry_a_token_other_rings_reject() {
+        let (ring, other) = (FrameRing::new(16), FrameRing::new(16));
+        let url = ring.url("abc");
+        let path = &url[url.find("localhost").unwrap() + "localhost".len()..];
+        let request = parse_request(path, Some("from=0")).unwrap();
+        assert_eq!(request.session_id, "abc");
+        assert!(ring.accepts(request.token));
+        assert!(!other.accepts(request.token));
+    }
+}
@@ -0,0 +1,198 @@
+//! The terminal engine without a window, for integration tests and for
+//! reproducing rendering bugs from recorded output.
+//!
+//! Both modes drive the same parser and frame builder as a GUI session and
+//! write every frame to `out` as one line of JSON, exactly as the frontend
+//! would receive it in a `render-frame` event. Only built with the
+//! `headless` feature, which doesn't need the `app` one: without it the
+//! crate builds no Tauri. The `rain-headless` binary is the command-line
+//! front.
+
+use std::io::Write;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::pty::reader::{
+    spawn_pty_threads, FrameSink, PtyThreadConfig, RenderFramePayload, SessionEndPayload,
+};
+use crate::pty::PtyManager;
+use crate::terminal::TerminalState;
+
+/// Size of the reads a replay is split into, like the PTY reader's buffer.
+pub const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+/// A shell to start and drive.
+#[derive(Debug, Clone)]
+pub struct ShellRun {
+    pub shell: Option<String>,
+    pub cwd: Option<String>,
+    pub rows: u16,
+    pub cols: u16,
+    /// Typed into the PTY once the shell has started (end it with `exit\r`
+    /// for the run to finish on its own).
+    pub input: Vec<u8>,
+    /// Stop once the shell has printed nothing for this long.
+    pub idle_timeout: Option<Duration>,
+}
+
+/// Feed recorded PTY output through a fresh terminal in `chunk_size` reads,
+/// writing a frame after each read that changed something.
+pub fn replay(
+    bytes: &[u8],
+    rows: u16,
+    cols: u16,
+    chunk_size: usize,
+    out: &mut dyn Write,
+) -> Result<(), String> {
+    let mut state = TerminalState::new(rows, cols);
+    let mut parser = vte::Parser::new();
+    for chunk in bytes.chunks(chunk_size.max(1)) {
+        state.advance(&mut parser, chunk);
+        // Nothing is listening for DSR/DA replies.
+        state.take_pending_responses();
+        write_frame(&mut state, out)?;
+    }
+    Ok(())
+}
+
+/// What the render pump of a [`run_shell`] session hands the main thread.
+enum Output {
+    /// A frame, already encoded as a line of JSON.
+    Frame(String),
+    /// The shell exited, with this code when it could be read.
+    Ended(Option<i32>),
+}
+
+/// Sends a session's frames to the thread writing them out. Frames are
+/// never acked, so the parser never pauses reading for this frontend.
+struct ChannelSink(Sender<Output>);
+
+impl FrameSink for ChannelSink {
+    fn render_frame(&self, payload: &RenderFramePayload) {
+        match serde_json::to_string(&payload.frame) {
+            Ok(line) => {
+                let _ = self.0.send(Output::Frame(line));
+            }
+            Err(e) => tracing::warn!("Failed to encode frame: {}", e),
+        }
+    }
+
+    fn session_ended(&self, payload: &SessionEndPayload) {
+        let _ = self.0.send(Output::Ended(payload.exit_code));
+    }
+}
+
+/// Start a shell on a real PTY, type `run.input`, and write frames until the
+/// shell exits or goes idle. Returns the shell's exit code when it exited.
+///
+/// The session runs on the same parser and render-pump threads as a GUI
+/// session, so frames come out at the same pace and with the same
+/// coalescing; idle means no frame for `run.idle_timeout`.
+pub fn run_shell(run: &ShellRun, out: &mut dyn Write) -> Result<Option<i32>, String> {
+    let spawned = PtyManager::new()
+        .spawn_session(
+            run.shell.as_deref(),
+            run.cwd.as_deref(),
+            run.rows,
+            run.cols,
+            None,
+            None,
+            None,
+        )
+        .map_err(|e| format!("Failed to start shell: {}", e))?;
+    let mut session = spawned.session;
+
+    let (tx, rx) = channel();
+    let config = PtyThreadConfig {
+        session_id: Uuid::new_v4().to_string(),
+        command: false,
+        state: session.state(),
+        writer: session.writer(),
+        input: session.input(),
+        child: session.child(),
+        exit_code: session.exit_code(),
+        io: session.io_counters(),
+        running: session.running(),
+        visible: session.visibility(),
+        read_only: session.read_only_flag(),
+        watch: session.watch(),
+    };
+    let handles = spawn_pty_threads(spawned.reader, config, ChannelSink(tx));
+    session.set_thread_handles(handles.parser, handles.render_pump, handles.render_waker);
+
+    if !run.input.is_empty() {
+        session
+            .write_input(&run.input)
+            .map_err(|e| format!("Failed to write input: {}", e))?;
+    }
+
+    let mut exit_code = None;
+    loop {
+        let next = match run.idle_timeout {
+            Some(timeout) => rx.recv_timeout(timeout).ok(),
+            None => rx.recv().ok(),
+        };
+        match next {
+            Some(Output::Frame(line)) => write_line(&line, out)?,
+            Some(Output::Ended(code)) => {
+                // The PTY can reach EOF before the shell is reaped.
+                exit_code = code.or_else(|| {
+                    let status = session.child().lock().wait().ok()?;
+                    Some(status.exit_code() as i32)
+                });
+                break;
+            }
+            None => break,
+        }
+    }
+
+    // Joins the threads, so the frame a synchronized update still held back
+    // is in the channel by the time it returns.
+    session.kill();
+    for output in rx.try_iter() {
+        if let Output::Frame(line) = output {
+            write_line(&line, out)?;
+        }
+    }
+    Ok(exit_code)
+}
+
+fn write_line(line: &str, out: &mut dyn Write) -> Result<(), String> {
+    writeln!(out, "{}", line).map_err(|e| format!("Failed to write frame: {}", e))
+}
+
+fn write_frame(state: &mut TerminalState, out: &mut dyn Write) -> Result<(), String> {
+    let Some(snapshot) = state.take_render_snapshot() else {
+        return Ok(());
+    };
+    serde_json::to_writer(&mut *out, &snapshot.into_frame())
+        .map_err(|e| format!("Failed to write frame: {}", e))?;
+    writeln!(out).map_err(|e| format!("Failed to write frame: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_writes_one_json_frame_per_changing_read() {
+        let mut out = Vec::new();
+        replay(b"hello\x1b]0;title\x07\x1b[6n", 4, 20, 5, &mut out).unwrap();
+        let frames: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert!(frames.len() >= 2);
+        let first_row = frames[0]["lines"][0]["spans"][0]["text"].as_str().unwrap();
+        assert_eq!(first_row.trim_end(), "hello");
+        let titled = frames
+            .iter()
+            .flat_map(|frame| frame["events"].as_array().unwrap())
+            .any(|event| event["type"] == "TitleChanged" && event["title"] == "title");
+        assert!(titled);
+    }
+}
@@ -0,0 +1,47 @@
+//! Global hotkey backends.
+//!
+//! The global-shortcut plugin grabs keys through X11/Win32/Carbon, which
+//! Wayland compositors don't allow. On Wayland sessions the hotkey is bound
+//! through the XDG GlobalShortcuts portal instead (KDE, GNOME 48+, Hyprland).
+
+use serde::Serialize;
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub mod portal;
+pub mod trigger;
+
+/// Whether the configured hotkey can actually fire, for the settings UI.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HotkeyCapabilities {
+    /// "native" (global-shortcut plugin), "portal" (XDG portal) or "none".
+    pub backend: &'static str,
+    pub supported: bool,
+    /// Why the hotkey won't work, or what the user has to do.
+    pub reason: Option<String>,
+    /// Trigger the portal actually bound, which the user may have changed.
+    pub bound_trigger: Option<String>,
+}
+
+impl HotkeyCapabilities {
+    pub fn native() -> Self {
+        Self {
+            backend: "native",
+            supported: true,
+            reason: None,
+            bound_trigger: None,
+        }
+    }
+}
+
+/// Wayland sessions need the portal; everything else uses the plugin.
+pub fn needs_portal() -> bool {
+    cfg!(not(any(target_os = "macos", target_os = "windows")))
+        && std::env::var("WAYLAND_DISPLAY").is_ok_and(|v| !v.is_empty())
+}
@@ -0,0 +1,106 @@
+//! Global hotkey via the XDG GlobalShortcuts portal (Wayland).
+
+use ashpd::desktop::global_shortcuts::{GlobalShortcuts, NewShortcut};
+use futures_channel::oneshot;
+use futures_util::future::{self, Either};
+use futures_util::StreamExt;
+use parking_lot::Mutex;
+use tauri::{AppHandle, Emitter};
+
+use super::{trigger, HotkeyCapabilities};
+
+const SHORTCUT_ID: &str = "toggle-window";
+
+/// Cancels the active portal session when dropped or signalled.
+pub type ActiveSession = Mutex<Option<oneshot::Sender<()>>>;
+
+/// Whether the portal implements GlobalShortcuts at all.
+pub async fn is_available() -> bool {
+    GlobalShortcuts::new().await.is_ok()
+}
+
+/// Bind `accelerator` through the portal, replacing any previous binding.
+/// Binding happens asynchronously; the outcome is reported through the
+/// `global-hotkey-status` event.
+pub fn register(
+    app: AppHandle,
+    active: &ActiveSession,
+    accelerator: &str,
+    on_activated: fn(&AppHandle),
+) {
+    unregister(active);
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+    *active.lock() = Some(cancel_tx);
+
+    let preferred = trigger::to_portal_trigger(accelerator);
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = run(&app, preferred.as_deref(), cancel_rx, on_activated).await {
+            tracing::warn!("Global hotkey portal failed: {}", e);
+            emit_status(
+                &app,
+                HotkeyCapabilities {
+                    backend: "portal",
+                    supported: false,
+                    reason: Some(format!("Shortcut portal error: {}", e)),
+                    bound_trigger: None,
+                },
+            );
+        }
+    });
+}
+
+/// Close the current portal session, if any.
+pub fn unregister(active: &ActiveSession) {
+    if let Some(cancel) = active.lock().take() {
+        let _ = cancel.send(());
+    }
+}
+
+async fn run(
+    app: &AppHandle,
+    preferred: Option<&str>,
+    mut cancel: oneshot::Receiver<()>,
+    on_activated: fn(&AppHandle),
+) -> ashpd::Result<()> {
+    let proxy = GlobalShortcuts::new().await?;
+    let session = proxy.create_session().await?;
+    let shortcut = NewShortcut::new(SHORTCUT_ID, "Show or hide Rain").preferred_trigger(preferred);
+    let bound = proxy
+        .bind_shortcuts(&session, &[shortcut], None)
+        .await?
+        .response()?;
+
+    let bound_trigger = bound
+        .shortcuts()
+        .iter()
+        .find(|s| s.id() == SHORTCUT_ID)
+        .map(|s| s.trigger_description().to_string())
+        .filter(|t| !t.is_empty());
+    tracing::info!("Global hotkey bound via portal: {:?}", bound_trigger);
+    emit_status(
+        app,
+        HotkeyCapabilities {
+            backend: "portal",
+            supported: true,
+            reason: None,
+            bound_trigger,
+        },
+    );
+
+    let mut activated = proxy.receive_activated().await?;
+    // Runs until the portal stream ends or `unregister` fires the cancel.
+    while let Either::Left((Some(event), _)) = future::select(activated.next(), &mut cancel).await {
+        if event.shortcut_id() == SHORTCUT_ID {
+            on_activated(app);
+        }
+    }
+
+    session.close().await?;
+    Ok(())
+}
+
+fn emit_status(app: &AppHandle, status: HotkeyCapabilities) {
+    if let Err(e) = app.emit("global-hotkey-status", &status) {
+        tracing::warn!("Failed to emit global hotkey status: {}", e);
+    }
+}
@@ -0,0 +1,89 @@
+/// Convert a Tauri accelerator (`CmdOrCtrl+Shift+Space`) into the
+/// XDG shortcuts trigger format (`CTRL+SHIFT+space`) used as the portal's
+/// preferred trigger. Returns `None` for keys we can't express.
+pub fn to_portal_trigger(accelerator: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    let mut key = None;
+
+    for token in accelerator.split('+').map(str::trim) {
+        let modifier = match token.to_ascii_lowercase().as_str() {
+            "cmdorctrl" | "commandorcontrol" | "ctrl" | "control" => Some("CTRL"),
+            "alt" | "option" => Some("ALT"),
+            "shift" => Some("SHIFT"),
+            "super" | "meta" | "cmd" | "command" => Some("LOGO"),
+            _ => None,
+        };
+        match modifier {
+            Some(m) if !parts.contains(&m) => parts.push(m),
+            Some(_) => {}
+            None if key.is_none() => key = Some(keysym_name(token)?),
+            None => return None,
+        }
+    }
+
+    let key = key?;
+    let mut trigger = parts.join("+");
+    if !trigger.is_empty() {
+        trigger.push('+');
+    }
+    trigger.push_str(&key);
+    Some(trigger)
+}
+
+/// xkb keysym name for an accelerator key code.
+fn keysym_name(key: &str) -> Option<String> {
+    let lower = key.to_ascii_lowercase();
+    let name = match lower.as_str() {
+        "space" => "space",
+        "enter" | "return" => "Return",
+        "tab" => "Tab",
+        "escape" | "esc" => "Escape",
+        "backspace" => "BackSpace",
+        "delete" => "Delete",
+        "backquote" | "`" => "grave",
+        "minus" | "-" => "minus",
+        "equal" | "=" => "equal",
+        "comma" | "," => "comma",
+        "period" | "." => "period",
+        "slash" | "/" => "slash",
+        "up" | "arrowup" => "Up",
+        "down" | "arrowdown" => "Down",
+        "left" | "arrowleft" => "Left",
+        "right" | "arrowright" => "Right",
+        _ => {
+            let key = lower.strip_prefix("key").unwrap_or(&lower);
+            let key = key.strip_prefix("digit").unwrap_or(key);
+            if key.len() == 1 && key.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Some(key.to_string());
+            }
+            if let Some(n) = key.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                if (1..=24).contains(&n) {
+                    return Some(format!("F{}", n));
+                }
+            }
+            return None;
+        }
+    };
+    Some(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accelerators_convert_to_portal_triggers() {
+        assert_eq!(
+            to_portal_trigger("CmdOrCtrl+Shift+Space").as_deref(),
+            Some("CTRL+SHIFT+space")
+        );
+        assert_eq!(
+            to_portal_trigger("Alt+Backquote").as_deref(),
+            Some("ALT+grave")
+        );
+        assert_eq!(to_portal_trigger("Super+KeyT").as_deref(), Some("LOGO+t"));
+        assert_eq!(to_portal_trigger("F12").as_deref(), Some("F12"));
+        assert_eq!(to_portal_trigger("Ctrl+Shift").as_deref(), None);
+        assert_eq!(to_portal_trigger("Ctrl+A+B").as_deref(), None);
+    }
+}
@@ -0,0 +1,83 @@
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::clipboard::History;
+use crate::ipc::commands::session::forget_session;
+use crate::ipc::AppState;
+
+/// What [`reset_backend_state`] tore down.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendResetReport {
+    pub sessions: usize,
+    pub tmux_controllers: usize,
+    pub session_transfers: usize,
+    pub tab_transfers: usize,
+    pub ghost_windows: usize,
+    pub warm_shells: usize,
+}
+
+/// Teardown is only exposed to debug builds and builds with the
+/// `automation` feature so a stray IPC call can't wipe a user's sessions.
+fn reset_allowed() -> bool {
+    cfg!(any(debug_assertions, feature = "automation"))
+}
+
+/// Destroy every session, tmux controller, pending transfer and ghost window,
+/// and clear the rest of the app-wide state sessions leave behind, so
+/// end-to-end UI tests can run repeatedly against one app instance.
+#[tauri::command]
+pub fn reset_backend_state(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<BackendResetReport, String> {
+    if !reset_allowed() {
+        return Err(
+            "reset_backend_state is only available in debug or automation builds".to_string(),
+        );
+    }
+
+    let mut report = BackendResetReport::default();
+
+    // Take everything out of the locks first; killing sessions and joining
+    // tmux threads must not happen while AppState mutexes are held.
+    let sessions: Vec<_> = state.sessions.lock().drain().collect();
+    report.sessions = sessions.len();
+    for (session_id, mut session) in sessions {
+        forget_session(&state, &session_id);
+        session.kill();
+    }
+    let warm_shells = state.warm_pool.lock().clear();
+    report.warm_shells = warm_shells.len();
+    for shell in warm_shells {
+        shell.discard();
+    }
+
+    if let Some(mut controller) = state.tmux_controller.lock().take() {
+        controller.shutdown();
+        report.tmux_controllers = 1;
+    }
+
+    report.session_transfers = std::mem::take(&mut *state.session_transfer_state.lock()).len();
+    report.tab_transfers = std::mem::take(&mut *state.tab_transfer_manifests.lock()).len();
+    *state.context_menu.lock() = None;
+    state.pending_open_directories.lock().clear();
+    state.pending_deep_links.lock().clear();
+    state.jobs.lock().clear();
+    *state.crashed_journal.lock() = None;
+    *state.focus.lock() = Default::default();
+    state.update.lock().reset();
+    *state.clipboard_history.lock() = History::new();
+
+    for (label, window) in app.webview_windows() {
+        if label.starts_with("ghost-") {
+            window
+                .close()
+                .map_err(|e| format!("Failed to close ghost window {}: {}", label, e))?;
+            report.ghost_windows += 1;
+        }
+    }
+
+    tracing::info!("Backend state reset: {:?}", report);
+    Ok(report)
+}
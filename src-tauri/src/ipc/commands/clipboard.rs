@@ -0,0 +1,87 @@
+use tauri::State;
+
+use crate::clipboard::{self, CopySource, HistoryEntry, Selection};
+use crate::ipc::commands::session::writable_session;
+use crate::ipc::AppState;
+
+/// Mirror copied text into the PRIMARY selection (Linux only; a no-op
+/// elsewhere) so it can be middle-click pasted in other apps.
+#[tauri::command]
+pub fn set_primary_selection(text: String) -> Result<(), String> {
+    clipboard::write_text(Selection::Primary, &text)
+}
+
+/// Paste the PRIMARY selection into a session, as on middle click.
+/// Returns false when the selection is empty or unavailable.
+#[tauri::command]
+pub fn paste_primary(state: State<'_, AppState>, session_id: String) -> Result<bool, String> {
+    let text = match clipboard::read_text(Selection::Primary) {
+        Some(text) if !text.is_empty() => text,
+        _ => return Ok(false),
+    };
+
+    let sessions = state.sessions.lock();
+    writable_session(&sessions, &session_id)
+        .map_err(|e| e.to_string())?
+        .write_paste(&text)
+        .map_err(|e| format!("Write error: {}", e))?;
+    Ok(true)
+}
+
+/// Note text the UI copied to the clipboard itself, so it shows up in the
+/// clipboard history next to OSC 52 copies.
+#[tauri::command]
+pub fn record_clipboard_copy(state: State<'_, AppState>, text: String) {
+    state
+        .clipboard_history
+        .lock()
+        .record(&text, CopySource::Copy);
+}
+
+/// Clipboard history, newest first.
+#[tauri::command]
+pub fn list_clipboard_history(state: State<'_, AppState>) -> Vec<HistoryEntry> {
+    state.clipboard_history.lock().entries()
+}
+
+#[tauri::command]
+pub fn pin_clipboard_entry(
+    state: State<'_, AppState>,
+    id: u64,
+    pinned: bool,
+) -> Result<(), String> {
+    if state.clipboard_history.lock().set_pinned(id, pinned) {
+        Ok(())
+    } else {
+        Err(format!("Clipboard entry not found: {}", id))
+    }
+}
+
+/// Copy a history entry to the clipboard again, moving it to the front.
+#[tauri::command]
+pub fn recopy_clipboard_entry(state: State<'_, AppState>, id: u64) -> Result<(), String> {
+    let (text, source) = state
+        .clipboard_history
+        .lock()
+        .get(id)
+        .map(|entry| (entry.text.clone(), entry.source))
+        .ok_or_else(|| format!("Clipboard entry not found: {}", id))?;
+    clipboard::write_text(Selection::Clipboard, &text)?;
+    state.clipboard_history.lock().record(&text, source);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn remove_clipboard_entry(state: State<'_, AppState>, id: u64) -> Result<(), String> {
+    if state.clipboard_history.lock().remove(id) {
+        Ok(())
+    } else {
+        Err(format!("Clipboard entry not found: {}", id))
+    }
+}
+
+/// Forget all unpinned entries.
+#[tauri::command]
+pub fn clear_clipboard_history(state: State<'_, AppState>) {
+    state.clipboard_history.lock().clear();
+}
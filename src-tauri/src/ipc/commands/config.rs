@@ -1,10 +1,28 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Manager, State};
+
+use crate::backup::{self, Archive, Diagnostics, ScrollbackEntry};
+use crate::ipc::AppState;
+use crate::safe_mode::{self, StartupMode};
 
 /// Session restore: save workspace state to disk.
 #[tauri::command]
-pub fn save_workspace(app: AppHandle, workspace: String) -> Result<(), String> {
+pub fn save_workspace(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    workspace: String,
+) -> Result<(), String> {
+    // Keep the user's real layout for the next normal launch.
+    if state.startup_mode.lock().safe_mode {
+        return Ok(());
+    }
+    save_workspace_file(&app, &workspace)
+}
+
+fn save_workspace_file(app: &AppHandle, workspace: &str) -> Result<(), String> {
     let dir = app
         .path()
         .app_data_dir()
@@ -16,9 +34,29 @@ pub fn save_workspace(app: AppHandle, workspace: String) -> Result<(), String> {
     Ok(())
 }
 
-/// Session restore: load workspace state from disk.
+/// Session restore: load workspace state from disk. After a crash this is
+/// instead a workspace rebuilt from the session journal, marked with
+/// `recoveredSessions` so the UI can ask before restoring it. Nothing is
+/// restored in safe mode.
 #[tauri::command]
-pub fn load_workspace(app: AppHandle) -> Result<Option<String>, String> {
+pub fn load_workspace(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, String> {
+    if state.startup_mode.lock().safe_mode {
+        state.crashed_journal.lock().take();
+        tracing::info!("Safe mode: skipping workspace restore");
+        return Ok(None);
+    }
+    if let Some(crashed) = state.crashed_journal.lock().take() {
+        if let Some(workspace) = crate::journal::recovered_workspace(&crashed) {
+            tracing::info!(
+                "Offering {} sessions from the crash journal",
+                crashed.sessions.len()
+            );
+            return Ok(Some(workspace.to_string()));
+        }
+    }
     let dir = app
         .path()
         .app_data_dir()
@@ -82,3 +120,170 @@ pub fn save_text_to_file(_app: AppHandle, content: String, default_name: String)
 pub fn get_app_version() -> String {
     env!("CARGO_PKG_VERSION").to_string()
 }
+
+/// Whether this launch runs in safe mode: default theme, no window effects,
+/// no plugins and no workspace restore.
+#[tauri::command]
+pub fn get_startup_mode(state: State<'_, AppState>) -> StartupMode {
+    *state.startup_mode.lock()
+}
+
+/// Count the previous run as a crash unless it cleared its launch marker, and
+/// decide whether this launch starts in safe mode.
+pub fn record_launch(app: &AppHandle) {
+    let forced = safe_mode::forced_by_env(std::env::var(safe_mode::SAFE_MODE_ENV).ok().as_deref());
+    let crashes = match app.path().app_data_dir() {
+        Ok(dir) => safe_mode::record_launch(&dir).unwrap_or_else(|e| {
+            tracing::warn!("Launch marker not written: {}", e);
+            0
+        }),
+        Err(e) => {
+            tracing::warn!("Launch marker not written: App data dir error: {}", e);
+            0
+        }
+    };
+    let mode = StartupMode::new(crashes, forced);
+    if mode.safe_mode {
+        tracing::warn!(
+            "Starting in safe mode ({} consecutive abnormal exits{})",
+            crashes,
+            if forced { ", forced" } else { "" }
+        );
+    }
+    *app.state::<AppState>().startup_mode.lock() = mode;
+}
+
+/// Clean shutdown: the next launch doesn't count this run as a crash.
+pub fn clear_launch_marker(app: &AppHandle) {
+    if let Ok(dir) = app.path().app_data_dir() {
+        safe_mode::clear(&dir);
+    }
+}
+
+/// Path given by the caller, or one picked in a native file dialog. `None`
+/// when the dialog is cancelled.
+fn archive_path(path: Option<String>, save: bool) -> Option<PathBuf> {
+    if let Some(path) = path {
+        return Some(PathBuf::from(path));
+    }
+    let dialog = rfd::FileDialog::new().add_filter("Rain app state", &["json"]);
+    if save {
+        dialog.set_file_name("rain-app-state.json").save_file()
+    } else {
+        dialog.pick_file()
+    }
+}
+
+fn read_json_file(path: &std::path::Path) -> Option<serde_json::Value> {
+    let data = std::fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&data) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            tracing::warn!("Skipping unreadable {:?} in app state export: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Export config, the saved workspace, the frontend's settings storage
+/// (`frontend`, profiles/theme/keybindings/triggers) and diagnostic metadata
+/// to one sanitized archive. Scrollback of live sessions is only included
+/// with `include_scrollback`. Returns the path written, or `None` when the
+/// file dialog was cancelled.
+#[tauri::command]
+pub fn export_app_state(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path: Option<String>,
+    frontend: BTreeMap<String, String>,
+    include_scrollback: bool,
+) -> Result<Option<String>, String> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("App data dir error: {}", e))?;
+    let mut config = read_json_file(&config_file_path(&app)?);
+    if let Some(config) = config.as_mut() {
+        backup::redact(config);
+    }
+    let workspace = read_json_file(&data_dir.join("workspace.json"));
+
+    let scrollback = if include_scrollback {
+        let sessions = state.sessions.lock();
+        sessions
+            .iter()
+            .map(|(session_id, session)| {
+                let mut ts = session.state.lock();
+                ts.ensure_resident();
+                ScrollbackEntry {
+                    session_id: session_id.clone(),
+                    cwd: ts.shell.cwd.clone(),
+                    text: ts.scrollback_text(),
+                }
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let archive = Archive {
+        format: backup::ARCHIVE_FORMAT.to_string(),
+        version: backup::ARCHIVE_VERSION,
+        exported_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64),
+        diagnostics: Diagnostics {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            session_count: state.sessions.lock().len(),
+            safe_mode: state.startup_mode.lock().safe_mode,
+        },
+        config,
+        workspace,
+        frontend: backup::sanitize_frontend(frontend),
+        scrollback,
+    };
+
+    let Some(path) = archive_path(path, true) else {
+        return Ok(None);
+    };
+    let json =
+        serde_json::to_string_pretty(&archive).map_err(|e| format!("Serialize error: {}", e))?;
+    std::fs::write(&path, json).map_err(|e| format!("Write error: {}", e))?;
+    tracing::info!("App state exported to {:?}", path);
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+/// Restore an archive from `export_app_state`: the config file and saved
+/// workspace are written here, and the frontend storage entries are returned
+/// for the webview to apply. Returns `None` when the file dialog was
+/// cancelled.
+#[tauri::command]
+pub fn import_app_state(
+    app: AppHandle,
+    path: Option<String>,
+) -> Result<Option<BTreeMap<String, String>>, String> {
+    let Some(path) = archive_path(path, false) else {
+        return Ok(None);
+    };
+    let data = std::fs::read_to_string(&path).map_err(|e| format!("Read error: {}", e))?;
+    let archive = backup::parse(&data)?;
+
+    if let Some(mut config) = archive.config {
+        backup::strip_redacted(&mut config);
+        let contents =
+            serde_json::to_string_pretty(&config).map_err(|e| format!("Serialize error: {}", e))?;
+        write_config_file(app.clone(), contents)?;
+    }
+    if let Some(workspace) = archive.workspace {
+        save_workspace_file(&app, &workspace.to_string())?;
+    }
+    tracing::info!(
+        "App state imported from {:?} (exported by Rain {} on {})",
+        path,
+        archive.diagnostics.app_version,
+        archive.diagnostics.os
+    );
+    Ok(Some(backup::restorable_frontend(&archive.frontend)))
+}
@@ -0,0 +1,23 @@
+use crate::credentials;
+
+/// Store a secret (token, API key) in the OS credential store.
+#[tauri::command]
+pub fn store_secret(key: String, secret: String) -> Result<(), String> {
+    credentials::store_secret(&key, &secret)?;
+    tracing::info!("Stored secret {}", key);
+    Ok(())
+}
+
+/// Read a secret from the OS credential store. `None` when not set.
+#[tauri::command]
+pub fn get_secret(key: String) -> Result<Option<String>, String> {
+    credentials::get_secret(&key)
+}
+
+/// Remove a secret from the OS credential store.
+#[tauri::command]
+pub fn delete_secret(key: String) -> Result<(), String> {
+    credentials::delete_secret(&key)?;
+    tracing::info!("Deleted secret {}", key);
+    Ok(())
+}
@@ -1,7 +1,10 @@
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::ipc::AppState;
 
 // ---------------------------------------------------------------------------
 // list_directory
@@ -345,68 +348,200 @@ fn scan_taskfile(dir: &Path) -> Option<Vec<ProjectScript>> {
 // scan_path_commands
 // ---------------------------------------------------------------------------
 
-#[tauri::command]
-pub fn scan_path_commands() -> Result<Vec<String>, String> {
-    let path_var = std::env::var("PATH").unwrap_or_default();
-    let sep = if cfg!(windows) { ';' } else { ':' };
-    let dirs: Vec<&str> = path_var.split(sep).collect();
+const MAX_PATH_COMMANDS: usize = 5000;
 
-    let mut seen = HashSet::new();
-    let mut commands = BTreeSet::new();
-    const MAX_COMMANDS: usize = 5000;
+/// Executables found in one PATH directory, keyed by the directory's mtime.
+/// Adding or removing entries bumps the mtime, so unchanged directories can
+/// be served from cache without a `read_dir`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PathDirEntry {
+    mtime_ns: u64,
+    commands: Vec<String>,
+}
 
-    for dir_str in dirs {
-        if dir_str.is_empty() {
-            continue;
-        }
-        let dir = Path::new(dir_str);
-        if !dir.is_dir() {
-            continue;
+/// Disk-backed cache of PATH executables, refreshed incrementally.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PathCommandCache {
+    dirs: HashMap<String, PathDirEntry>,
+    #[serde(skip)]
+    loaded: bool,
+}
+
+impl PathCommandCache {
+    /// Load the persisted cache once per process; a missing or corrupt file
+    /// just means a cold cache.
+    fn ensure_loaded(&mut self, file: &Path) {
+        if self.loaded {
+            return;
+        }
+        self.loaded = true;
+        if let Ok(data) = std::fs::read_to_string(file) {
+            match serde_json::from_str::<PathCommandCache>(&data) {
+                Ok(cached) => self.dirs = cached.dirs,
+                Err(e) => tracing::warn!("Ignoring corrupt PATH command cache: {}", e),
+            }
         }
-        // Skip duplicate PATH entries
-        let canonical = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
-        if !seen.insert(canonical) {
-            continue;
+    }
+
+    fn save(&self, file: &Path) {
+        let result = file
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| {
+                let data = serde_json::to_string(self).map_err(std::io::Error::other)?;
+                std::fs::write(file, data)
+            });
+        if let Err(e) = result {
+            tracing::warn!("Failed to write PATH command cache: {}", e);
         }
+    }
 
-        let read = match std::fs::read_dir(dir) {
-            Ok(r) => r,
-            Err(_) => continue,
-        };
+    /// Rescan only PATH directories whose mtime changed, drop entries for
+    /// directories no longer on PATH, and return the merged command list.
+    /// The second value reports whether the cache changed.
+    fn refresh(&mut self) -> (Vec<String>, bool) {
+        let path_var = std::env::var("PATH").unwrap_or_default();
+        let sep = if cfg!(windows) { ';' } else { ':' };
 
-        for entry in read {
-            let entry = match entry {
-                Ok(e) => e,
-                Err(_) => continue,
+        let mut seen = HashSet::new();
+        let mut changed = false;
+        let mut commands = BTreeSet::new();
+
+        for dir_str in path_var.split(sep) {
+            if dir_str.is_empty() {
+                continue;
+            }
+            let dir = Path::new(dir_str);
+            let Ok(meta) = std::fs::metadata(dir) else {
+                continue;
             };
-            let name = entry.file_name().to_string_lossy().into_owned();
-            if name.starts_with('.') {
+            if !meta.is_dir() {
                 continue;
             }
+            // Skip duplicate PATH entries
+            let canonical = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+            let key = canonical.to_string_lossy().into_owned();
+            if !seen.insert(key.clone()) {
+                continue;
+            }
+
+            let mtime_ns = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_nanos() as u64);
+            let entry = match self.dirs.get(&key) {
+                Some(entry) if mtime_ns != 0 && entry.mtime_ns == mtime_ns => entry,
+                _ => {
+                    changed = true;
+                    self.dirs.insert(
+                        key.clone(),
+                        PathDirEntry {
+                            mtime_ns,
+                            commands: scan_dir_commands(dir),
+                        },
+                    );
+                    &self.dirs[&key]
+                }
+            };
 
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                if let Ok(meta) = entry.metadata() {
-                    if meta.permissions().mode() & 0o111 == 0 {
-                        continue;
+            if commands.len() < MAX_PATH_COMMANDS {
+                for name in &entry.commands {
+                    commands.insert(name.clone());
+                    if commands.len() >= MAX_PATH_COMMANDS {
+                        break;
                     }
-                } else {
-                    continue;
                 }
             }
+        }
 
-            commands.insert(name);
-            if commands.len() >= MAX_COMMANDS {
-                break;
-            }
+        let before = self.dirs.len();
+        self.dirs.retain(|key, _| seen.contains(key));
+        changed |= self.dirs.len() != before;
+
+        (commands.into_iter().collect(), changed)
+    }
+}
+
+/// List executables in a single directory.
+fn scan_dir_commands(dir: &Path) -> Vec<String> {
+    let read = match std::fs::read_dir(dir) {
+        Ok(r) => r,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut commands = Vec::new();
+    for entry in read {
+        let entry = match entry {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
         }
-        if commands.len() >= MAX_COMMANDS {
-            break;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Ok(meta) = entry.metadata() {
+                if meta.permissions().mode() & 0o111 == 0 {
+                    continue;
+                }
+            } else {
+                continue;
+            }
         }
+
+        commands.push(name);
     }
+    commands.sort();
+    commands
+}
+
+fn path_cache_file(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| format!("App cache dir error: {}", e))?;
+    Ok(dir.join("path-commands.json"))
+}
 
-    Ok(commands.into_iter().collect())
+fn refresh_path_cache(app: &AppHandle, state: &AppState) -> Result<Vec<String>, String> {
+    let file = path_cache_file(app)?;
+    let mut cache = state.path_command_cache.lock();
+    cache.ensure_loaded(&file);
+    let (commands, changed) = cache.refresh();
+    if changed {
+        cache.save(&file);
+    }
+    Ok(commands)
+}
+
+#[tauri::command]
+pub fn scan_path_commands(app: AppHandle, state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    refresh_path_cache(&app, &state)
+}
+
+/// Warm the PATH command cache in the background at startup so the first
+/// palette open doesn't pay for a cold scan.
+pub fn spawn_path_cache_warmup(app: AppHandle) {
+    let spawned = std::thread::Builder::new()
+        .name("path-cache-warmup".to_string())
+        .spawn(move || {
+            let started = std::time::Instant::now();
+            match refresh_path_cache(&app, &app.state::<AppState>()) {
+                Ok(commands) => tracing::debug!(
+                    "PATH command cache warmed: {} commands in {:?}",
+                    commands.len(),
+                    started.elapsed()
+                ),
+                Err(e) => tracing::warn!("PATH command cache warm-up failed: {}", e),
+            }
+        });
+    if let Err(e) = spawned {
+        tracing::warn!("Failed to spawn PATH cache warm-up: {}", e);
+    }
 }
 
 // ---------------------------------------------------------------------------
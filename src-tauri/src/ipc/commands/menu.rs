@@ -0,0 +1,121 @@
+use tauri::menu::{Menu, MenuEvent, MenuItemBuilder, PredefinedMenuItem};
+use tauri::{AppHandle, Emitter, LogicalPosition, Manager, State, Window};
+
+use crate::context_menu::{self, MenuAction, MenuContext, MenuEntry, SessionMenuState};
+use crate::ipc::AppState;
+
+/// The context menu currently on screen. Native menus report only the item
+/// ID, so the session and hit-test context are kept until an item fires.
+#[derive(Debug, Clone)]
+pub struct PendingContextMenu {
+    pub session_id: String,
+    pub window_label: String,
+    pub context: MenuContext,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextMenuActionPayload {
+    pub session_id: String,
+    pub action: MenuAction,
+    pub link_url: Option<String>,
+    pub block_id: Option<String>,
+}
+
+fn session_menu_state(state: &AppState, session_id: &str) -> Result<SessionMenuState, String> {
+    {
+        let sessions = state.sessions.lock();
+        if let Some(session) = sessions.get(session_id) {
+            let terminal_state = session.state();
+            let ts = terminal_state.lock();
+            return Ok(SessionMenuState {
+                alt_screen: ts.using_alt,
+                shell_integration: ts.shell.active,
+                tmux_pane: false,
+            });
+        }
+    }
+
+    let ctrl = state.tmux_controller.lock();
+    if let Some(ref controller) = *ctrl {
+        let handles = controller.pane_handles.lock();
+        if let Some(handle) = handles.get(session_id) {
+            let ts = handle.state.lock();
+            return Ok(SessionMenuState {
+                alt_screen: ts.using_alt,
+                shell_integration: ts.shell.active,
+                tmux_pane: true,
+            });
+        }
+    }
+
+    Err(format!("Session not found: {}", session_id))
+}
+
+/// Show the native terminal context menu at (`x`, `y`) in logical window
+/// coordinates. The chosen item is delivered as a `terminal-context-action`
+/// event to the same window.
+#[tauri::command]
+pub fn show_terminal_context_menu(
+    window: Window,
+    state: State<'_, AppState>,
+    session_id: String,
+    x: f64,
+    y: f64,
+    context: MenuContext,
+) -> Result<(), String> {
+    let session = session_menu_state(&state, &session_id)?;
+
+    let menu = Menu::new(&window).map_err(|e| format!("Failed to create menu: {}", e))?;
+    for entry in context_menu::entries(&context, session) {
+        let appended = match entry {
+            MenuEntry::Separator => {
+                PredefinedMenuItem::separator(&window).and_then(|separator| menu.append(&separator))
+            }
+            MenuEntry::Item { action, enabled } => {
+                let mut builder =
+                    MenuItemBuilder::with_id(action.id(), action.label()).enabled(enabled);
+                if let Some(accelerator) = action.accelerator() {
+                    builder = builder.accelerator(accelerator);
+                }
+                builder.build(&window).and_then(|item| menu.append(&item))
+            }
+        };
+        appended.map_err(|e| format!("Failed to build context menu: {}", e))?;
+    }
+
+    *state.context_menu.lock() = Some(PendingContextMenu {
+        session_id,
+        window_label: window.label().to_string(),
+        context,
+    });
+    window
+        .popup_menu_at(&menu, LogicalPosition::new(x, y))
+        .map_err(|e| format!("Failed to show context menu: {}", e))
+}
+
+/// App-wide menu event hook; forwards terminal context menu picks to the
+/// window that opened the menu.
+pub fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    let Some(action) = MenuAction::from_id(event.id().as_ref()) else {
+        return;
+    };
+    let state = app.state::<AppState>();
+    let Some(pending) = state.context_menu.lock().take() else {
+        return;
+    };
+
+    let payload = ContextMenuActionPayload {
+        session_id: pending.session_id,
+        action,
+        link_url: pending.context.link_url,
+        block_id: pending.context.block_id,
+    };
+    if let Err(e) = app.emit_to(
+        pending.window_label.as_str(),
+        "terminal-context-action",
+        &payload,
+    ) {
+        tracing::warn!("Failed to emit context menu action: {}", e);
+    }
+}
@@ -1,30 +1,102 @@
 #![allow(unused_imports)]
 
+pub mod automation;
+pub mod clipboard;
 pub mod config;
+pub mod credentials;
 pub mod filesystem;
+pub mod menu;
 pub mod session;
+pub mod share;
+pub mod theme;
 pub mod tmux;
 pub mod transfer;
+pub mod update;
 pub mod window;
 
 // Re-export all commands for external use (e.g. ipc::commands::create_session)
 pub use filesystem::{list_directory, scan_path_commands, scan_project_commands, snoop_path_context};
+pub use automation::reset_backend_state;
+pub use clipboard::{
+    clear_clipboard_history,
+    list_clipboard_history,
+    paste_primary,
+    pin_clipboard_entry,
+    recopy_clipboard_entry,
+    record_clipboard_copy,
+    remove_clipboard_entry,
+    set_primary_selection,
+};
 pub use config::{
+    export_app_state,
     get_app_version,
+    get_startup_mode,
+    import_app_state,
     load_workspace,
     read_config_file,
     save_text_to_file,
     save_workspace,
     write_config_file,
 };
+pub use credentials::{delete_secret, get_secret, store_secret};
+pub use menu::show_terminal_context_menu;
 pub use session::{
+    ack_frame,
+    attach_frame_channel,
+    cancel_queued_command,
+    clear_selection,
+    copy_block_as_markdown,
     create_session,
     destroy_session,
+    extend_selection,
+    find_idle_session,
     get_block_output,
+    get_block_structured,
+    get_block_text,
+    get_current_command_line,
+    get_event_schema,
+    get_foreground_process,
+    get_line_range_output,
+    get_scrollback_range,
+    get_selection_text,
+    get_session_io_stats,
+    hibernate_session,
+    inject_remote_hooks,
+    list_active_jobs,
+    list_queued_commands,
+    paste_text,
+    queue_command,
+    recover_session,
     request_full_redraw,
     resize_terminal,
+    resolve_new_session_cwd,
+    restart_session,
+    run_terminal_selftest,
+    search_scrollback,
+    search_visible,
+    send_mouse_event,
+    send_signal,
+    session_has_running_children,
+    set_ambiguous_width,
+    set_bold_is_bright,
+    set_color_filter,
+    set_min_contrast,
+    set_custom_osc_codes,
+    set_device_attributes,
+    set_session_read_only,
+    set_session_visible,
+    set_shell_args,
+    set_viewport_top,
+    set_warm_pool_enabled,
+    set_hibernation_config,
+    set_scrollback_limit,
+    set_selection,
+    transfer_text_between_sessions,
     write_input,
+    write_input_batched,
 };
+pub use share::{discard_download, export_output, preview_export, save_download, share_block};
+pub use theme::{list_themes, set_theme};
 pub use tmux::{
     tmux_close_pane,
     tmux_detach,
@@ -40,6 +112,7 @@ pub use tmux::{
 pub use transfer::{
     commit_tab_transfer_adopt,
     emit_cross_window,
+    extend_tab_transfer,
     list_rain_windows,
     prepare_tab_transfer_adopt,
     release_tab_transfer_adopt,
@@ -48,14 +121,24 @@ pub use transfer::{
     take_session_transfer_state,
     take_tab_transfer_manifest,
 };
+pub use update::{check_for_updates, install_update, take_update_changelog};
 pub use window::{
     close_drag_ghost,
     create_child_window,
     create_drag_ghost,
+    get_compositor_capabilities,
+    get_global_hotkey_capabilities,
     get_hostname,
     quit_app,
     register_global_hotkey,
+    set_explorer_integration,
+    set_session_focus,
+    set_tray_enabled,
     set_window_blur_radius,
     set_window_opacity,
+    set_window_progress,
+    show_notification,
+    take_pending_deep_links,
+    take_pending_open_directories,
     toggle_window_visibility,
 };
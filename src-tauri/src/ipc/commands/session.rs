@@ -1,10 +1,46 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use tauri::{AppHandle, Emitter, State};
+use parking_lot::Mutex;
+use tauri::ipc::JavaScriptChannelId;
+use tauri::{AppHandle, Emitter, Manager, State, Webview};
 use uuid::Uuid;
 
-use crate::ipc::AppState;
-use crate::pty::reader::spawn_pty_threads;
+use crate::assets::AssetSink;
+use crate::frame_ring::{self, FrameRing};
+use crate::ipc::{AppState, HibernationConfig};
+use crate::jobs::{self, CpuSampler, Job};
+use crate::journal::{self, JournalEntry};
+use crate::process::{self, ForegroundProcess, ProcessInfo, Signal};
+use crate::pty::io_stats::IoStats;
+use crate::pty::reader::{spawn_pty_threads, PtyThreadConfig, SessionStalledPayload};
+use crate::pty::session::LaunchSpec;
+use crate::pty::warm::{WarmKey, WarmShell};
+use crate::pty::watchdog::ParserHealth;
+use crate::pty::Session;
+use crate::render::color_filter::ColorFilter;
+use crate::render::copy::CopyOptions;
+use crate::render::export::ExportFormat;
+use crate::render::frame::{
+    EventSchema, ScrollbackPage, EVENT_SCHEMA_VERSION, TERMINAL_EVENT_TYPES,
+};
+use crate::render::search::{CellRect, ScrollbackMatch, SearchOptions, TextQuery};
+use crate::render::structured::{self, StructuredData, StructuredKind};
+use crate::shell::args::{ShellArgTemplate, ShellArgs};
+use crate::shell::capabilities;
+use crate::shell::command_line::CommandLine;
+use crate::shell::cwd::{self, NewSessionCwd};
+use crate::shell::{remote, QueuedCommand, ViewportBlock};
+use crate::terminal::device::DeviceAttributes;
+use crate::terminal::grid::ScrollbackUsage;
+use crate::terminal::mouse::MouseEvent;
+use crate::terminal::selection::{SelectionMode, SelectionPoint, SelectionRange};
+use crate::terminal::selftest::{self, SelftestResult};
+use crate::terminal::TerminalState;
+use crate::tmux::controller::TMUX_EVENT_TYPES;
 
 /// Result of creating a new terminal session.
 #[derive(serde::Serialize, Clone)]
@@ -12,12 +48,19 @@ pub struct CreateSessionResult {
     pub session_id: String,
     /// True when Rain itself is running inside an existing tmux session.
     pub inside_tmux: bool,
+    /// Render frames come as MessagePack over the channel passed in,
+    /// rather than as JSON `render-frame` events.
+    pub binary_frames: bool,
+    /// Render frames go through a frame ring, announced by `frame-ready`
+    /// events.
+    pub frame_ring: bool,
 }
 
 /// Create a new terminal session. Returns the session ID and env info.
 #[tauri::command]
 pub fn create_session(
     app: AppHandle,
+    webview: Webview,
     state: State<'_, AppState>,
     shell: Option<String>,
     cwd: Option<String>,
@@ -25,47 +68,80 @@ pub fn create_session(
     cols: Option<u16>,
     env: Option<HashMap<String, String>>,
     tmux_mode: Option<String>,
+    ambiguous_wide: Option<bool>,
+    scrollback_limit: Option<usize>,
+    frame_channel: Option<JavaScriptChannelId>,
+    frame_ring: Option<bool>,
+    command: Option<Vec<String>>,
 ) -> Result<CreateSessionResult, String> {
     let rows = rows.unwrap_or(24);
     let cols = cols.unwrap_or(80);
 
     let session_id = Uuid::new_v4().to_string();
 
-    let spawn_result = state
-        .pty_manager
-        .spawn_session(
-            shell.as_deref(),
-            cwd.as_deref(),
-            rows,
-            cols,
-            env.as_ref(),
-            tmux_mode.as_deref(),
-        )
-        .map_err(|e| format!("Failed to spawn session: {}", e))?;
+    let warm_key = WarmKey::new(shell.as_deref(), env.as_ref(), tmux_mode.as_deref());
+    // Warm shells are shells; a command session starts its own process.
+    let warm = match command {
+        Some(_) => None,
+        None => state.warm_pool.lock().take(&warm_key),
+    };
+    let spawn_result = match warm.and_then(|warm| warm.into_spawn(cwd.as_deref(), rows, cols)) {
+        Some(spawned) => {
+            tracing::debug!("Using warm shell for session {}", &session_id[..8]);
+            spawned
+        }
+        None => state
+            .pty_manager
+            .spawn_session(
+                shell.as_deref(),
+                cwd.as_deref(),
+                rows,
+                cols,
+                env.as_ref(),
+                tmux_mode.as_deref(),
+                command.as_deref(),
+            )
+            .map_err(|e| format!("Failed to spawn session: {}", e))?,
+    };
+    refill_warm_pool(&app, warm_key);
 
     let mut session = spawn_result.session;
-    let reader = spawn_result.reader;
-
-    // Start parser/render threads (with shared writer for DSR/DA responses)
-    let terminal_state = session.state();
-    let writer = session.writer();
-    let child = session.child();
-    let exit_code = session.exit_code();
-    let running = session.running();
-    let handles = spawn_pty_threads(
-        reader,
-        terminal_state,
-        writer,
-        child,
-        exit_code,
-        app.clone(),
-        session_id.clone(),
-        running,
-    );
-    session.set_thread_handles(handles.parser, handles.render_pump, handles.render_waker);
+    session.set_launch(LaunchSpec {
+        shell: shell.clone(),
+        cwd,
+        env,
+        tmux_mode,
+        command,
+    });
+    {
+        let mut ts = session.state.lock();
+        if let Some(wide) = ambiguous_wide {
+            ts.set_ambiguous_wide(wide);
+        }
+        if let Some(lines) = scrollback_limit {
+            ts.set_scrollback_limit(lines);
+        }
+    }
+    let binary_frames = frame_channel.is_some();
+    if let Some(channel) = frame_channel {
+        state
+            .frame_channels
+            .lock()
+            .insert(session_id.clone(), channel.channel_on(webview));
+    }
+    let frame_ring = frame_ring.unwrap_or(false);
+    if frame_ring {
+        let ring = FrameRing::new(frame_ring::RING_CAPACITY);
+        state
+            .frame_rings
+            .lock()
+            .insert(session_id.clone(), Arc::new(Mutex::new(ring)));
+    }
+    start_session(&app, &state, &session_id, &mut session, spawn_result.reader);
 
     tracing::info!("Created session {} ({}x{})", &session_id[..8], cols, rows);
     state.sessions.lock().insert(session_id.clone(), session);
+    state.session_journal.lock().launched(&session_id, shell);
 
     // Detect if Rain is running inside an existing tmux session
     let inside_tmux = std::env::var("TMUX").is_ok();
@@ -73,154 +149,1699 @@ pub fn create_session(
     Ok(CreateSessionResult {
         session_id,
         inside_tmux,
+        binary_frames,
+        frame_ring,
     })
 }
 
-/// Write input bytes to a terminal session (keyboard input).
+/// Move a session's frame channel to the calling webview and redraw the
+/// session in full. A channel belongs to the webview that opened it, so a
+/// window adopting a session (tab tear-off, drag between windows) passes
+/// its own; sessions without a channel keep their transport and only get
+/// the redraw.
 #[tauri::command]
-pub fn write_input(
+pub fn attach_frame_channel(
+    webview: Webview,
     state: State<'_, AppState>,
     session_id: String,
-    data: Vec<u8>,
+    frame_channel: JavaScriptChannelId,
 ) -> Result<(), String> {
     let sessions = state.sessions.lock();
     let session = sessions
         .get(&session_id)
         .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    if let Some(channel) = state.frame_channels.lock().get_mut(&session_id) {
+        *channel = frame_channel.channel_on(webview);
+    }
+    session.request_full_redraw();
+    Ok(())
+}
 
-    session
-        .write_input(&data)
-        .map_err(|e| format!("Write error: {}", e))
+/// Start a warm shell for `key` in the background, unless the pool is off
+/// or already has one.
+fn refill_warm_pool(app: &AppHandle, key: WarmKey) {
+    if !app.state::<AppState>().warm_pool.lock().wants(&key) {
+        return;
+    }
+    let app = app.clone();
+    let spawned = std::thread::Builder::new()
+        .name("warm-shell".to_string())
+        .spawn(move || {
+            let state = app.state::<AppState>();
+            let shell = match WarmShell::spawn(&state.pty_manager, &key) {
+                Ok(Some(shell)) => shell,
+                Ok(None) => return,
+                Err(e) => {
+                    tracing::warn!("{}", e);
+                    return;
+                }
+            };
+            let discarded = state.warm_pool.lock().insert(key, shell);
+            for shell in discarded {
+                shell.discard();
+            }
+        });
+    if let Err(e) = spawned {
+        tracing::warn!("Failed to spawn warm shell thread: {}", e);
+    }
 }
 
-/// Lightweight acknowledgment sent after a resize (no line data).
-#[derive(serde::Serialize, Clone)]
-pub struct ResizeAckPayload {
-    pub session_id: String,
-    pub rows: u16,
-    pub cols: u16,
-    pub frame_seq: u64,
-    pub resize_epoch: u64,
+/// Turn the pool of pre-spawned shells for new tabs on or off.
+#[tauri::command]
+pub fn set_warm_pool_enabled(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    let discarded = state.warm_pool.lock().set_enabled(enabled);
+    for shell in discarded {
+        shell.discard();
+    }
+    tracing::info!(
+        "Warm shell pool {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
 }
 
-/// Resize a terminal session.
-///
-/// Resizes the grid and PTY, then emits a lightweight `resize-ack` event so the
-/// frontend can confirm the new viewport dimensions. No render frame is emitted
-/// here -- the reader thread will emit a proper frame when the child process
-/// responds to SIGWINCH with actual content changes.
+/// Kill the warm shells, e.g. on exit.
+pub fn clear_warm_pool(app: &AppHandle) {
+    let discarded = app.state::<AppState>().warm_pool.lock().clear();
+    for shell in discarded {
+        shell.discard();
+    }
+}
+
+/// Apply the configured terminal settings to a freshly spawned session and
+/// start its parser and render threads.
+fn start_session(
+    app: &AppHandle,
+    state: &AppState,
+    session_id: &str,
+    session: &mut Session,
+    reader: Box<dyn std::io::Read + Send>,
+) {
+    {
+        let mut ts = session.state.lock();
+        ts.set_custom_osc_codes(state.custom_osc_codes.lock().clone());
+        ts.set_device_attributes(state.device_attributes.lock().clone());
+        let (colors, resolve) = *state.theme.lock();
+        ts.set_theme(&colors, resolve);
+        ts.set_asset_sink(AssetSink::new(session_id, state.assets.clone()));
+        ts.set_clipboard_history(state.clipboard_history.clone());
+    }
+
+    // Start parser/render threads (with shared writer for DSR/DA responses)
+    let config = PtyThreadConfig {
+        session_id: session_id.to_string(),
+        command: session.launch().command.is_some(),
+        state: session.state(),
+        writer: session.writer(),
+        input: session.input(),
+        child: session.child(),
+        exit_code: session.exit_code(),
+        io: session.io_counters(),
+        running: session.running(),
+        visible: session.visibility(),
+        read_only: session.read_only_flag(),
+        watch: session.watch(),
+    };
+    let handles = spawn_pty_threads(reader, config, app.clone());
+    session.set_thread_handles(handles.parser, handles.render_pump, handles.render_waker);
+}
+
+/// Start the shell of a session whose process has exited again, under the
+/// same session ID. The new shell starts in the last known working
+/// directory; the old screen and scrollback stay above a marker line.
 #[tauri::command]
-pub fn resize_terminal(
+pub fn restart_session(
     app: AppHandle,
     state: State<'_, AppState>,
     session_id: String,
-    rows: u16,
-    cols: u16,
 ) -> Result<(), String> {
-    let sessions = state.sessions.lock();
+    let (launch, cwd, rows, cols) = {
+        let sessions = state.sessions.lock();
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| format!("Session not found: {}", session_id))?;
+        if session.running().load(Ordering::Acquire) {
+            return Err(format!("Session is still running: {}", session_id));
+        }
+        let ts = session.state.lock();
+        let cwd = ts
+            .shell
+            .local_cwd()
+            .map(|cwd| cwd.to_string_lossy().into_owned())
+            .or_else(|| session.launch().cwd.clone());
+        let (rows, cols) = ts.size();
+        (session.launch().clone(), cwd, rows, cols)
+    };
+
+    let spawn_result = state
+        .pty_manager
+        .spawn_session(
+            launch.shell.as_deref(),
+            cwd.as_deref(),
+            rows,
+            cols,
+            launch.env.as_ref(),
+            launch.tmux_mode.as_deref(),
+            launch.command.as_deref(),
+        )
+        .map_err(|e| format!("Failed to spawn session: {}", e))?;
+
+    let mut old = state
+        .sessions
+        .lock()
+        .remove(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    old.kill();
+
+    let mut session = spawn_result.session;
+    {
+        let mut restored = std::mem::replace(&mut *old.state.lock(), TerminalState::new(1, 1));
+        restored.restart();
+        *session.state.lock() = restored;
+    }
+    session.set_read_only(old.is_read_only());
+    session.set_launch(launch);
+    start_session(&app, &state, &session_id, &mut session, spawn_result.reader);
+
+    tracing::info!("Restarted session {}", &session_id[..8]);
+    state.sessions.lock().insert(session_id, session);
+    Ok(())
+}
+
+/// Move a session whose parser thread is stuck or has crashed onto new
+/// threads and a blank terminal state. The shell keeps running; resizing
+/// the PTY away and back makes the foreground program repaint the blank
+/// screen, as it would after `tput clear`.
+#[tauri::command]
+pub fn recover_session(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<(), String> {
+    let mut sessions = state.sessions.lock();
     let session = sessions
-        .get(&session_id)
+        .get_mut(&session_id)
         .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    let health = session.parser_health();
+    if health == ParserHealth::Ok {
+        return Err(format!("Session is not stalled: {}", session_id));
+    }
 
+    let reader = session
+        .detach_threads()
+        .map_err(|e| format!("Failed to recover session: {}", e))?;
+    start_session(&app, &state, &session_id, session, reader);
+    let (rows, cols) = session.state.lock().size();
     session
-        .resize(rows, cols)
+        .resize(rows, cols.saturating_sub(1).max(1))
+        .and_then(|_| session.resize(rows, cols))
         .map_err(|e| format!("Resize error: {}", e))?;
 
-    // Send lightweight ack with new dimensions (no line data).
-    // The reader thread handles frame emission when content actually changes.
-    let terminal_state = session.state();
-    let ts = terminal_state.lock();
-    let payload = ResizeAckPayload {
-        session_id,
-        rows,
-        cols,
-        frame_seq: ts.frame_seq(),
-        resize_epoch: ts.resize_epoch(),
-    };
-    drop(ts);
-    tracing::debug!(
-        session = %&payload.session_id[..8],
-        rows = payload.rows,
-        cols = payload.cols,
-        frame_seq = payload.frame_seq,
-        resize_epoch = payload.resize_epoch,
-        "Emitting resize-ack"
+    tracing::warn!(
+        "Recovered {:?} parser of session {}",
+        health,
+        &session_id[..8]
     );
-    let _ = app.emit("resize-ack", &payload);
-
     Ok(())
 }
 
-/// Destroy a terminal session.
+/// Working directory of a session: as the shell reports it through OSC 7,
+/// or else read from the OS on every call, since the last value read (or a
+/// warm shell's seed) goes stale with each `cd`. A changed value is reported
+/// to the frontend as `CwdChanged`. The OS is asked without the parser lock.
+fn session_cwd(session: &Session) -> Option<PathBuf> {
+    {
+        let ts = session.state.lock();
+        if ts.shell.reports_cwd() {
+            return ts.shell.local_cwd().map(Path::to_path_buf);
+        }
+    }
+    let cwd = process::process_cwd(session.shell_pid()?)?;
+    let changed = session
+        .state
+        .lock()
+        .shell
+        .adopt_os_cwd(cwd.to_string_lossy().into_owned());
+    if changed {
+        session.notify_render();
+    }
+    Some(cwd)
+}
+
+/// Starting directory for a new tab or split opened from `source_session_id`,
+/// chosen by `policy`. Without a source session only the home directory is
+/// a candidate. Returns `None` when nothing suitable exists.
 #[tauri::command]
-pub fn destroy_session(state: State<'_, AppState>, session_id: String) -> Result<(), String> {
-    state.session_transfer_state.lock().remove(&session_id);
-    let mut sessions = state.sessions.lock();
-    if let Some(mut session) = sessions.remove(&session_id) {
-        session.kill();
-        tracing::info!("Destroyed session {}", &session_id[..8]);
-        Ok(())
-    } else {
-        Err(format!("Session not found: {}", session_id))
+pub fn resolve_new_session_cwd(
+    state: State<'_, AppState>,
+    source_session_id: Option<String>,
+    policy: NewSessionCwd,
+) -> Result<Option<String>, String> {
+    let (current, original) = match source_session_id {
+        Some(id) => {
+            let sessions = state.sessions.lock();
+            let session = sessions
+                .get(&id)
+                .ok_or_else(|| format!("Session not found: {}", id))?;
+            (session_cwd(session), session.launch().cwd.as_ref().map(PathBuf::from))
+        }
+        None => (None, None),
+    };
+    let home = dirs::home_dir();
+    let cwd = cwd::resolve(
+        policy,
+        current.as_deref(),
+        original.as_deref(),
+        home.as_deref(),
+    );
+    Ok(cwd.map(|dir| dir.to_string_lossy().into_owned()))
+}
+
+/// Error from the commands that write user input to a session. Serialized as
+/// `{ kind, sessionId | message }` so the UI can tell a locked pane apart
+/// from a failed write.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum InputError {
+    SessionNotFound { session_id: String },
+    ReadOnly { session_id: String },
+    Write { message: String },
+}
+
+impl std::fmt::Display for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputError::SessionNotFound { session_id } => {
+                write!(f, "Session not found: {}", session_id)
+            }
+            InputError::ReadOnly { session_id } => {
+                write!(f, "Session is read-only: {}", session_id)
+            }
+            InputError::Write { message } => write!(f, "Write error: {}", message),
+        }
     }
 }
 
-/// Get the text content of terminal output for a row range.
+/// The session to write user input to, unless it is missing or read-only.
+pub(crate) fn writable_session<'a>(
+    sessions: &'a HashMap<String, Session>,
+    session_id: &str,
+) -> Result<&'a Session, InputError> {
+    let session = sessions
+        .get(session_id)
+        .ok_or_else(|| InputError::SessionNotFound {
+            session_id: session_id.to_string(),
+        })?;
+    if session.is_read_only() {
+        return Err(InputError::ReadOnly {
+            session_id: session_id.to_string(),
+        });
+    }
+    Ok(session)
+}
+
+/// Write input bytes to a terminal session (keyboard input).
 #[tauri::command]
-pub fn get_block_output(
+pub fn write_input(
     state: State<'_, AppState>,
     session_id: String,
-    start_row: usize,
-    end_row: usize,
-) -> Result<String, String> {
+    data: Vec<u8>,
+) -> Result<(), InputError> {
+    let sessions = state.sessions.lock();
+    writable_session(&sessions, &session_id)?
+        .write_input(&data)
+        .map_err(|e| InputError::Write {
+            message: e.to_string(),
+        })
+}
+
+/// Report a mouse event to the program in the session, encoded for the
+/// mouse modes it enabled. Returns whether anything was written (false when
+/// the program doesn't track this kind of event).
+#[tauri::command]
+pub fn send_mouse_event(
+    state: State<'_, AppState>,
+    session_id: String,
+    event: MouseEvent,
+) -> Result<bool, InputError> {
+    let sessions = state.sessions.lock();
+    let session = writable_session(&sessions, &session_id)?;
+    // Writing input takes the state lock too.
+    let report = session.state().lock().encode_mouse(&event);
+    let Some(report) = report else {
+        return Ok(false);
+    };
+    session
+        .write_input(&report)
+        .map(|_| true)
+        .map_err(|e| InputError::Write {
+            message: e.to_string(),
+        })
+}
+
+/// Paste text into a session: control characters and escape sequences are
+/// stripped, the rest is bracketed when the program enabled mode 2004, and
+/// large pastes are written in pieces from a background thread.
+#[tauri::command]
+pub fn paste_text(
+    state: State<'_, AppState>,
+    session_id: String,
+    text: String,
+) -> Result<(), InputError> {
+    let sessions = state.sessions.lock();
+    writable_session(&sessions, &session_id)?
+        .write_paste(&text)
+        .map_err(|e| InputError::Write {
+            message: e.to_string(),
+        })
+}
+
+/// Lock a session against accidental keystrokes (e.g. a pane tailing
+/// production logs). Input commands fail with `InputError::ReadOnly` until
+/// it is unlocked; output and terminal query replies are unaffected. Queued
+/// commands wait too, and the next one is typed on unlock if the shell is
+/// idle at its prompt.
+#[tauri::command]
+pub fn set_session_read_only(
+    state: State<'_, AppState>,
+    session_id: String,
+    read_only: bool,
+) -> Result<(), String> {
     let sessions = state.sessions.lock();
     let session = sessions
         .get(&session_id)
         .ok_or_else(|| format!("Session not found: {}", session_id))?;
-
-    let terminal_state = session.state();
-    let ts = terminal_state.lock();
-    Ok(ts.get_text_range(start_row, end_row))
+    session.set_read_only(read_only);
+    if !read_only {
+        let ready = {
+            let mut ts = session.state.lock();
+            if ts.using_alt {
+                None
+            } else {
+                ts.shell.take_queued_command()
+            }
+        };
+        if let Some(ready) = ready {
+            session
+                .write_input(format!("{}\r", ready.command).as_bytes())
+                .map_err(|e| format!("Write error: {}", e))?;
+            session.notify_render();
+        }
+    }
+    tracing::info!(
+        "Session {} is now {}",
+        session_id,
+        if read_only { "read-only" } else { "writable" }
+    );
+    Ok(())
 }
 
-/// Force a full redraw. Marks all visible grid lines as dirty and generates
-/// a complete render frame. Used when the frontend connects and needs to
-/// catch up with terminal state that was rendered while it wasn't listening.
+/// Tell the render pump whether the session is on screen. Sessions in
+/// background tabs or hidden windows get a few frames per second.
 #[tauri::command]
-pub fn request_full_redraw(
-    _app: AppHandle,
+pub fn set_session_visible(
     state: State<'_, AppState>,
     session_id: String,
+    visible: bool,
 ) -> Result<(), String> {
-    // Check regular PTY sessions first
-    {
-        let sessions = state.sessions.lock();
-        if let Some(session) = sessions.get(&session_id) {
-            session.request_full_redraw();
-            return Ok(());
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    session.set_visible(visible);
+    Ok(())
+}
+
+/// Comparable form of a directory: symlinks resolved where possible, trailing
+/// separators dropped otherwise.
+fn dir_key(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.components().collect())
+}
+
+/// Find a session sitting at an empty prompt in `cwd`, so "open here" style
+/// actions can reuse its tab instead of spawning another shell. `among`
+/// restricts the search to the caller's sessions; the most recently used
+/// match wins.
+#[tauri::command]
+pub fn find_idle_session(
+    state: State<'_, AppState>,
+    cwd: String,
+    among: Option<Vec<String>>,
+) -> Result<Option<String>, String> {
+    let target = dir_key(Path::new(&cwd));
+    let sessions = state.sessions.lock();
+    let mut best: Option<(Duration, &String)> = None;
+    for (id, session) in sessions.iter() {
+        if among.as_ref().is_some_and(|ids| !ids.contains(id)) {
+            continue;
+        }
+        let (dir, idle_for) = {
+            let ts = session.state.lock();
+            if ts.using_alt || !ts.shell.is_idle_at_prompt() {
+                continue;
+            }
+            match ts.shell.local_cwd() {
+                Some(dir) => (dir.to_path_buf(), ts.idle_for()),
+                None => continue,
+            }
+        };
+        if dir_key(&dir) == target && best.is_none_or(|(b, _)| idle_for < b) {
+            best = Some((idle_for, id));
         }
     }
+    Ok(best.map(|(_, id)| id.clone()))
+}
 
-    // Check tmux pane handles
-    {
-        let ctrl = state.tmux_controller.lock();
-        if let Some(ref controller) = *ctrl {
-            let handles = controller.pane_handles.lock();
-            if let Some(handle) = handles.get(&session_id) {
-                let mut ts = handle.state.lock();
-                if ts.using_alt {
-                    if let Some(ref mut alt) = ts.alt_grid {
-                        alt.mark_all_dirty();
-                    }
-                } else {
-                    ts.grid.mark_all_dirty();
-                }
-                drop(ts);
-                let _ = handle.render_waker.try_send(());
-                return Ok(());
-            }
+/// Queue a command to run once the shell reports an idle prompt (OSC 133;A
+/// with nothing typed and no command running). Runs right away if the
+/// session is already idle.
+#[tauri::command]
+pub fn queue_command(
+    state: State<'_, AppState>,
+    session_id: String,
+    command: String,
+) -> Result<QueuedCommand, String> {
+    let command = command.trim_end_matches(['\r', '\n']).to_string();
+    if command.is_empty() {
+        return Err("Command is empty".to_string());
+    }
+    let sessions = state.sessions.lock();
+    let session = writable_session(&sessions, &session_id).map_err(|e| e.to_string())?;
+    let (queued, ready) = {
+        let mut ts = session.state.lock();
+        if !ts.shell.active {
+            return Err("Queued commands need shell integration".to_string());
         }
+        let queued = ts.shell.queue_command(command);
+        let ready = if ts.using_alt {
+            None
+        } else {
+            ts.shell.take_queued_command()
+        };
+        (queued, ready)
+    };
+    if let Some(ready) = ready {
+        session
+            .write_input(format!("{}\r", ready.command).as_bytes())
+            .map_err(|e| format!("Write error: {}", e))?;
     }
+    session.notify_render();
+    Ok(queued)
+}
 
-    Err(format!("Session not found: {}", session_id))
+#[tauri::command]
+pub fn cancel_queued_command(
+    state: State<'_, AppState>,
+    session_id: String,
+    id: String,
+) -> Result<bool, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    let removed = session.state.lock().shell.cancel_queued(&id);
+    session.notify_render();
+    Ok(removed)
+}
+
+#[tauri::command]
+pub fn list_queued_commands(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<QueuedCommand>, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    let queued = session.state.lock().shell.queued_commands();
+    Ok(queued)
+}
+
+/// Bytes read from and written to a session's PTY, with the transfer rates
+/// of the last `session-io` sample.
+#[tauri::command]
+pub fn get_session_io_stats(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<IoStats, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    Ok(session.io_stats())
+}
+
+/// Name, pid and arguments of the process in the foreground of a session's
+/// PTY, so the frontend can title tabs after it and warn before closing a
+/// busy pane. `None` where the OS can't tell.
+#[tauri::command]
+pub fn get_foreground_process(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<Option<ForegroundProcess>, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    let Some(pid) = session.foreground_pid() else {
+        return Ok(None);
+    };
+    let is_shell = session.shell_pid() == Some(pid);
+    Ok(process::process_info(pid).map(|process| ForegroundProcess { process, is_shell }))
+}
+
+/// Jobs still running under a session's shell, e.g. `ssh` and `npm`, so
+/// closing it can be confirmed first. Empty when the shell is idle.
+#[tauri::command]
+pub fn session_has_running_children(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<ProcessInfo>, String> {
+    let shell_pid = {
+        let sessions = state.sessions.lock();
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| format!("Session not found: {}", session_id))?;
+        session.shell_pid()
+    };
+    // Walking the process tree is slow; other commands need the sessions.
+    Ok(shell_pid.map(process::running_children).unwrap_or_default())
+}
+
+/// Send `signal` to the foreground job of a session's PTY, e.g. to stop a
+/// running block without focusing its pane.
+#[tauri::command]
+pub fn send_signal(
+    state: State<'_, AppState>,
+    session_id: String,
+    signal: Signal,
+) -> Result<(), String> {
+    let sessions = state.sessions.lock();
+    let session = writable_session(&sessions, &session_id).map_err(|e| e.to_string())?;
+    session
+        .signal_foreground(signal)
+        .map_err(|e| format!("Failed to send {:?}: {}", signal, e))
+}
+
+/// Type Rain's shell hooks into the remote shell of a running ssh block, so
+/// blocks and cwd tracking work there until the connection closes.
+#[tauri::command]
+pub fn inject_remote_hooks(state: State<'_, AppState>, session_id: String) -> Result<(), String> {
+    let sessions = state.sessions.lock();
+    let session = writable_session(&sessions, &session_id).map_err(|e| e.to_string())?;
+    if session.state.lock().using_alt {
+        return Err("A full-screen program is running".to_string());
+    }
+    session
+        .write_input(format!("{}\r", remote::bootstrap_command()).as_bytes())
+        .map_err(|e| format!("Write error: {}", e))
+}
+
+/// Running commands across all sessions, with CPU usage from the last
+/// jobs monitor pass.
+#[tauri::command]
+pub fn list_active_jobs(state: State<'_, AppState>) -> Vec<Job> {
+    let mut jobs = active_jobs(&state);
+    let sampled = state.jobs.lock();
+    for job in jobs.iter_mut() {
+        job.cpu_percent = sampled
+            .iter()
+            .find(|s| s.block_id == job.block_id)
+            .and_then(|s| s.cpu_percent);
+    }
+    jobs
+}
+
+/// Each session's running block, oldest first.
+fn active_jobs(state: &AppState) -> Vec<Job> {
+    let sessions = state.sessions.lock();
+    let mut jobs: Vec<Job> = sessions
+        .iter()
+        .filter_map(|(session_id, session)| {
+            let job = {
+                let ts = session.state.lock();
+                let block = ts.shell.running_block()?;
+                Job {
+                    session_id: session_id.clone(),
+                    block_id: block.id.clone(),
+                    command: block.command.clone().unwrap_or_default(),
+                    cwd: ts.shell.cwd.clone(),
+                    started_at: block
+                        .started_at
+                        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                        .map_or(0, |d| d.as_millis() as u64),
+                    pid: None,
+                    cpu_percent: None,
+                }
+            };
+            Some(Job {
+                pid: session.foreground_pid(),
+                ..job
+            })
+        })
+        .collect();
+    jobs.sort_by(|a, b| (a.started_at, &a.session_id).cmp(&(b.started_at, &b.session_id)));
+    jobs
+}
+
+/// Write several input chunks in one IPC call; the frontend coalesces rapid
+/// writes (macro replay, chunked pastes) into these.
+#[tauri::command]
+pub fn write_input_batched(
+    state: State<'_, AppState>,
+    session_id: String,
+    chunks: Vec<Vec<u8>>,
+) -> Result<(), InputError> {
+    let sessions = state.sessions.lock();
+    writable_session(&sessions, &session_id)?
+        .write_input_batched(&chunks)
+        .map_err(|e| InputError::Write {
+            message: e.to_string(),
+        })
+}
+
+/// Move dragged text from one pane into another session's PTY.
+///
+/// The transfer happens entirely in the backend so the OS clipboard is left
+/// untouched. With `as_paste` the destination's bracketed-paste mode is
+/// honored; otherwise the text is written as if typed.
+#[tauri::command]
+pub fn transfer_text_between_sessions(
+    state: State<'_, AppState>,
+    source_session_id: String,
+    target_session_id: String,
+    text: String,
+    as_paste: bool,
+) -> Result<(), String> {
+    if text.is_empty() {
+        return Ok(());
+    }
+
+    let sessions = state.sessions.lock();
+    if !sessions.contains_key(&source_session_id) {
+        return Err(format!("Session not found: {}", source_session_id));
+    }
+    let target = writable_session(&sessions, &target_session_id).map_err(|e| e.to_string())?;
+
+    let result = if as_paste {
+        target.write_paste(&text)
+    } else {
+        target.write_input(text.as_bytes())
+    };
+    result.map_err(|e| format!("Write error: {}", e))?;
+
+    tracing::debug!(
+        "Transferred {} bytes from session {} to {}",
+        text.len(),
+        &source_session_id[..8.min(source_session_id.len())],
+        &target_session_id[..8.min(target_session_id.len())]
+    );
+    Ok(())
+}
+
+/// Lightweight acknowledgment sent after a resize (no line data).
+#[derive(serde::Serialize, Clone)]
+pub struct ResizeAckPayload {
+    pub session_id: String,
+    pub rows: u16,
+    pub cols: u16,
+    pub frame_seq: u64,
+    pub resize_epoch: u64,
+}
+
+/// Resize a terminal session.
+///
+/// Resizes the grid and PTY, records the cell size in pixels when given (for
+/// XTWINOPS size reports), then emits a lightweight `resize-ack` event so the
+/// frontend can confirm the new viewport dimensions. No render frame is emitted
+/// here -- the reader thread will emit a proper frame when the child process
+/// responds to SIGWINCH with actual content changes.
+#[tauri::command]
+pub fn resize_terminal(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    session_id: String,
+    rows: u16,
+    cols: u16,
+    cell_width: Option<u16>,
+    cell_height: Option<u16>,
+) -> Result<(), String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    session
+        .resize(rows, cols)
+        .map_err(|e| format!("Resize error: {}", e))?;
+    if let (Some(width), Some(height)) = (cell_width, cell_height) {
+        session.state.lock().set_cell_pixels(width, height);
+    }
+
+    // Send lightweight ack with new dimensions (no line data).
+    // The reader thread handles frame emission when content actually changes.
+    let terminal_state = session.state();
+    let ts = terminal_state.lock();
+    let payload = ResizeAckPayload {
+        session_id,
+        rows,
+        cols,
+        frame_seq: ts.frame_seq(),
+        resize_epoch: ts.resize_epoch(),
+    };
+    drop(ts);
+    tracing::debug!(
+        session = %&payload.session_id[..8],
+        rows = payload.rows,
+        cols = payload.cols,
+        frame_seq = payload.frame_seq,
+        resize_epoch = payload.resize_epoch,
+        "Emitting resize-ack"
+    );
+    let _ = app.emit("resize-ack", &payload);
+
+    Ok(())
+}
+
+/// Drop what AppState keeps about a session apart from the session itself:
+/// its transfer state, journal entry, images and frame transports.
+pub fn forget_session(state: &AppState, session_id: &str) {
+    state.session_transfer_state.lock().remove(session_id);
+    state.session_journal.lock().ended(session_id);
+    state.assets.lock().remove_session(session_id);
+    state.frame_channels.lock().remove(session_id);
+    state.frame_rings.lock().remove(session_id);
+}
+
+/// Destroy a terminal session.
+#[tauri::command]
+pub fn destroy_session(state: State<'_, AppState>, session_id: String) -> Result<(), String> {
+    forget_session(&state, &session_id);
+    let mut sessions = state.sessions.lock();
+    if let Some(mut session) = sessions.remove(&session_id) {
+        session.kill();
+        tracing::info!("Destroyed session {}", &session_id[..8]);
+        Ok(())
+    } else {
+        Err(format!("Session not found: {}", session_id))
+    }
+}
+
+/// Get the text content of terminal output for a row range.
+#[tauri::command]
+pub fn get_block_output(
+    state: State<'_, AppState>,
+    session_id: String,
+    start_row: usize,
+    end_row: usize,
+) -> Result<String, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let terminal_state = session.state();
+    let mut ts = terminal_state.lock();
+    ts.ensure_resident();
+    Ok(ts.get_text_range(start_row, end_row))
+}
+
+/// Get the text of a block by its stable line IDs (inclusive). Unlike
+/// [`get_block_output`], the range stays valid across scrolling and resizes.
+#[tauri::command]
+pub fn get_line_range_output(
+    state: State<'_, AppState>,
+    session_id: String,
+    start_line_id: u64,
+    end_line_id: u64,
+    options: Option<CopyOptions>,
+) -> Result<String, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let terminal_state = session.state();
+    let mut ts = terminal_state.lock();
+    ts.ensure_resident();
+    match options {
+        Some(options) => ts.copy_line_range(start_line_id, end_line_id, options),
+        None => ts.get_line_range_text(start_line_id, end_line_id),
+    }
+    .ok_or_else(|| "Requested lines are no longer in scrollback".to_string())
+}
+
+/// Normalized text of a block for the clipboard, optionally with its prompt
+/// and command.
+#[tauri::command]
+pub fn get_block_text(
+    state: State<'_, AppState>,
+    session_id: String,
+    block_id: String,
+    include_command: bool,
+    options: CopyOptions,
+) -> Result<String, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let terminal_state = session.state();
+    let mut ts = terminal_state.lock();
+    ts.ensure_resident();
+    ts.copy_block(&block_id, include_command, options)
+        .ok_or_else(|| "Block is no longer in scrollback".to_string())
+}
+
+/// A block as a ready-to-paste Markdown snippet: command and output in a
+/// `console` fence, output capped at `max_output_lines` (0 for no limit).
+#[tauri::command]
+pub fn copy_block_as_markdown(
+    state: State<'_, AppState>,
+    session_id: String,
+    block_id: String,
+    max_output_lines: usize,
+) -> Result<String, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let terminal_state = session.state();
+    let mut ts = terminal_state.lock();
+    ts.ensure_resident();
+    ts.block_markdown(&block_id, max_output_lines)
+        .ok_or_else(|| "Block is no longer in scrollback".to_string())
+}
+
+/// Output of a finished block parsed as `format`, or as whatever it was
+/// detected to be when `format` is omitted.
+#[tauri::command]
+pub fn get_block_structured(
+    state: State<'_, AppState>,
+    session_id: String,
+    block_id: String,
+    format: Option<StructuredKind>,
+) -> Result<StructuredData, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let terminal_state = session.state();
+    let mut ts = terminal_state.lock();
+    ts.ensure_resident();
+    let text = ts
+        .block_output_text(&block_id)
+        .ok_or_else(|| "Block is no longer in scrollback".to_string())?;
+    let kind = format
+        .or_else(|| structured::detect(&text))
+        .ok_or_else(|| "Block output is not JSON or a table".to_string())?;
+    structured::parse(&text, kind)
+}
+
+/// The command line being typed at the session's prompt, or `None` while a
+/// command runs or without shell integration.
+#[tauri::command]
+pub fn get_current_command_line(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<Option<CommandLine>, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let terminal_state = session.state();
+    let mut ts = terminal_state.lock();
+    ts.ensure_resident();
+    Ok(ts.current_command_line())
+}
+
+/// Find `pattern` on the session's active screen (alt screen included).
+/// Matches keep arriving in each frame's `visible_search` until the search
+/// is replaced or ended with an empty pattern.
+#[tauri::command]
+pub fn search_visible(
+    state: State<'_, AppState>,
+    session_id: String,
+    pattern: String,
+) -> Result<Vec<CellRect>, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let terminal_state = session.state();
+    let matches = terminal_state.lock().set_visible_search(&pattern);
+    Ok(matches)
+}
+
+/// Search the session's scrollback and screen for `query`, so the frontend
+/// needn't hold the whole buffer to search it.
+#[tauri::command]
+pub fn search_scrollback(
+    state: State<'_, AppState>,
+    session_id: String,
+    query: String,
+    options: Option<SearchOptions>,
+) -> Result<Vec<ScrollbackMatch>, String> {
+    let query = TextQuery::new(&query, options.unwrap_or_default())
+        .map_err(|e| format!("Invalid search pattern: {}", e))?;
+    let Some(query) = query else {
+        return Ok(Vec::new());
+    };
+
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let terminal_state = session.state();
+    let matches = terminal_state.lock().search_scrollback(&query);
+    Ok(matches)
+}
+
+/// Styled scrollback rows from global row `start_global_row` on, so a
+/// window that adopts a session or attaches late can load its history in
+/// pages instead of waiting for it to stream by.
+#[tauri::command]
+pub fn get_scrollback_range(
+    state: State<'_, AppState>,
+    session_id: String,
+    start_global_row: u64,
+    count: usize,
+) -> Result<ScrollbackPage, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let terminal_state = session.state();
+    let page = terminal_state
+        .lock()
+        .scrollback_range(start_global_row, count);
+    Ok(page)
+}
+
+/// Start a selection at `anchor`, widened to words or lines per `mode`.
+/// Returns the selected range, or `None` when the line is gone.
+#[tauri::command]
+pub fn set_selection(
+    state: State<'_, AppState>,
+    session_id: String,
+    mode: SelectionMode,
+    anchor: SelectionPoint,
+) -> Result<Option<SelectionRange>, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let terminal_state = session.state();
+    let range = terminal_state.lock().set_selection(mode, anchor);
+    Ok(range)
+}
+
+/// Move the free end of the selection to `head`. Returns the new range, or
+/// `None` when there is no selection any more.
+#[tauri::command]
+pub fn extend_selection(
+    state: State<'_, AppState>,
+    session_id: String,
+    head: SelectionPoint,
+) -> Result<Option<SelectionRange>, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let terminal_state = session.state();
+    let range = terminal_state.lock().extend_selection(head);
+    Ok(range)
+}
+
+#[tauri::command]
+pub fn clear_selection(state: State<'_, AppState>, session_id: String) -> Result<(), String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    session.state().lock().clear_selection();
+    Ok(())
+}
+
+/// Text of the selection as plain text (the default), ANSI or HTML.
+#[tauri::command]
+pub fn get_selection_text(
+    state: State<'_, AppState>,
+    session_id: String,
+    format: Option<ExportFormat>,
+) -> Result<Option<String>, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let terminal_state = session.state();
+    let text = terminal_state
+        .lock()
+        .selection_text(format.unwrap_or(ExportFormat::Text));
+    Ok(text)
+}
+
+/// Payload of the `viewport-block-changed` event.
+#[derive(serde::Serialize, Clone)]
+pub struct ViewportBlockPayload {
+    pub session_id: String,
+    #[serde(flatten)]
+    pub block: ViewportBlock,
+}
+
+/// Record the main-grid line at the top of the frontend's viewport as it
+/// scrolls. Emits `viewport-block-changed` when that line belongs to a
+/// different block than before, so a sticky header can show its command.
+#[tauri::command]
+pub fn set_viewport_top(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    session_id: String,
+    line_id: u64,
+) -> Result<(), String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let terminal_state = session.state();
+    let mut ts = terminal_state.lock();
+    ts.ensure_resident();
+    let changed = ts.set_viewport_top(line_id);
+    drop(ts);
+    if let Some(block) = changed {
+        let payload = ViewportBlockPayload { session_id, block };
+        let _ = app.emit("viewport-block-changed", &payload);
+    }
+    Ok(())
+}
+
+/// Event wire format of this backend, for the frontend to check against the
+/// version it was built for.
+#[tauri::command]
+pub fn get_event_schema() -> EventSchema {
+    EventSchema {
+        version: EVENT_SCHEMA_VERSION,
+        terminal_events: TERMINAL_EVENT_TYPES,
+        tmux_events: TMUX_EVENT_TYPES,
+    }
+}
+
+/// Force a full redraw. Marks all visible grid lines as dirty and generates
+/// a complete render frame. Used when the frontend connects and needs to
+/// catch up with terminal state that was rendered while it wasn't listening.
+#[tauri::command]
+pub fn request_full_redraw(
+    _app: AppHandle,
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<(), String> {
+    // Check regular PTY sessions first
+    {
+        let sessions = state.sessions.lock();
+        if let Some(session) = sessions.get(&session_id) {
+            session.request_full_redraw();
+            return Ok(());
+        }
+    }
+
+    // Check tmux pane handles
+    {
+        let ctrl = state.tmux_controller.lock();
+        if let Some(ref controller) = *ctrl {
+            let handles = controller.pane_handles.lock();
+            if let Some(handle) = handles.get(&session_id) {
+                let mut ts = handle.state.lock();
+                if ts.using_alt {
+                    if let Some(ref mut alt) = ts.alt_grid {
+                        alt.mark_all_dirty();
+                    }
+                } else {
+                    ts.grid.mark_all_dirty();
+                }
+                drop(ts);
+                let _ = handle.render_waker.try_send(());
+                return Ok(());
+            }
+        }
+    }
+
+    Err(format!("Session not found: {}", session_id))
+}
+
+/// Acknowledge that the frontend has applied frames up to `frame_seq`. When
+/// acks fall too far behind (e.g. a throttled, minimized webview) the backend
+/// switches to full keyframes until the frontend catches up.
+#[tauri::command]
+pub fn ack_frame(
+    state: State<'_, AppState>,
+    session_id: String,
+    frame_seq: u64,
+) -> Result<(), String> {
+    {
+        let sessions = state.sessions.lock();
+        if let Some(session) = sessions.get(&session_id) {
+            session.state.lock().ack_frame(frame_seq);
+            return Ok(());
+        }
+    }
+
+    {
+        let ctrl = state.tmux_controller.lock();
+        if let Some(ref controller) = *ctrl {
+            if let Some(handle) = controller.pane_handles.lock().get(&session_id) {
+                handle.state.lock().ack_frame(frame_seq);
+                return Ok(());
+            }
+        }
+    }
+
+    Err(format!("Session not found: {}", session_id))
+}
+
+/// Register the OSC numbers (from config) whose payloads are forwarded to the
+/// frontend as `CustomOsc` events. Applies to all running sessions and tmux
+/// panes, and to sessions created later.
+#[tauri::command]
+pub fn set_custom_osc_codes(state: State<'_, AppState>, codes: Vec<u16>) -> Result<(), String> {
+    let mut codes = codes;
+    codes.sort_unstable();
+    codes.dedup();
+    *state.custom_osc_codes.lock() = codes.clone();
+
+    for session in state.sessions.lock().values() {
+        session.state.lock().set_custom_osc_codes(codes.clone());
+    }
+    if let Some(ref controller) = *state.tmux_controller.lock() {
+        for handle in controller.pane_handles.lock().values() {
+            handle.state.lock().set_custom_osc_codes(codes.clone());
+        }
+    }
+    tracing::info!("Custom OSC codes: {:?}", codes);
+    Ok(())
+}
+
+/// Configure the conformance level and feature parameters reported to
+/// applications through DA1/DA2. Applies to all running sessions and tmux
+/// panes, and to sessions created later.
+#[tauri::command]
+pub fn set_device_attributes(
+    state: State<'_, AppState>,
+    attributes: DeviceAttributes,
+) -> Result<(), String> {
+    let attributes = attributes.normalized();
+    *state.device_attributes.lock() = attributes.clone();
+
+    for session in state.sessions.lock().values() {
+        session
+            .state
+            .lock()
+            .set_device_attributes(attributes.clone());
+    }
+    if let Some(ref controller) = *state.tmux_controller.lock() {
+        for handle in controller.pane_handles.lock().values() {
+            handle
+                .state
+                .lock()
+                .set_device_attributes(attributes.clone());
+        }
+    }
+    tracing::info!("Device attributes: {:?}", attributes);
+    Ok(())
+}
+
+/// Set the per-shell argument templates from config. They apply to sessions
+/// spawned afterwards; running shells keep the arguments they started with.
+#[tauri::command]
+pub fn set_shell_args(
+    state: State<'_, AppState>,
+    templates: Vec<ShellArgTemplate>,
+) -> Result<(), String> {
+    let args = ShellArgs::new(templates)?;
+    tracing::info!("Shell argument templates: {:?}", args);
+    if state.pty_manager.set_shell_args(args) {
+        // Warm shells were started with the old arguments.
+        let discarded = state.warm_pool.lock().clear();
+        for shell in discarded {
+            shell.discard();
+        }
+    }
+    Ok(())
+}
+
+/// Shortest idle threshold accepted from config, to avoid churning the disk.
+const MIN_HIBERNATE_IDLE_SECS: u64 = 60;
+/// How often the background sweeper looks for idle sessions.
+const HIBERNATE_SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+/// How often running jobs are listed and their CPU usage sampled.
+const JOBS_SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often the parser watchdog checks every session.
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(2);
+
+fn hibernation_file(app: &AppHandle, session_id: &str) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| format!("App cache dir error: {}", e))?;
+    Ok(dir.join("hibernate").join(format!("{}.bin", session_id)))
+}
+
+/// Move a session's scrollback to disk, keeping the PTY and visible screen
+/// alive. Scrollback is restored transparently on the next output or read.
+/// Returns the number of rows hibernated.
+#[tauri::command]
+pub fn hibernate_session(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<usize, String> {
+    let path = hibernation_file(&app, &session_id)?;
+    // Writing to disk only holds this session's state, not every session.
+    let terminal_state = state
+        .sessions
+        .lock()
+        .get(&session_id)
+        .map(|session| session.state())
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let rows = terminal_state
+        .lock()
+        .hibernate(path)
+        .map_err(|e| format!("Hibernate error: {}", e))?;
+    tracing::info!(
+        "Hibernated {} scrollback rows for session {}",
+        rows,
+        &session_id[..8]
+    );
+    Ok(rows)
+}
+
+/// Apply an accessibility color filter to every frame this session emits.
+#[tauri::command]
+pub fn set_color_filter(
+    state: State<'_, AppState>,
+    session_id: String,
+    filter: ColorFilter,
+) -> Result<(), String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    session.state.lock().set_color_filter(filter);
+    session.notify_render();
+    Ok(())
+}
+
+/// Lighten or darken text that has less than `ratio` contrast against its
+/// background (1 turns it off).
+#[tauri::command]
+pub fn set_min_contrast(
+    state: State<'_, AppState>,
+    session_id: String,
+    ratio: f32,
+) -> Result<(), String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    session.state.lock().set_min_contrast(ratio);
+    session.notify_render();
+    Ok(())
+}
+
+/// Draw bold text in ANSI colors 0-7 in the bright colors, as terminals
+/// that brighten bold do.
+#[tauri::command]
+pub fn set_bold_is_bright(
+    state: State<'_, AppState>,
+    session_id: String,
+    bright: bool,
+) -> Result<(), String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    session.state.lock().set_bold_is_bright(bright);
+    session.notify_render();
+    Ok(())
+}
+
+/// Keep at most `lines` rows of scrollback in this session, and at most
+/// `max_bytes` of memory when given, dropping the oldest rows beyond either.
+/// With `compress`, older scrollback is kept zstd-compressed. Returns the
+/// scrollback's size afterwards.
+#[tauri::command]
+pub fn set_scrollback_limit(
+    state: State<'_, AppState>,
+    session_id: String,
+    lines: usize,
+    max_bytes: Option<usize>,
+    compress: Option<bool>,
+) -> Result<ScrollbackUsage, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let mut terminal = session.state.lock();
+    terminal.set_scrollback_limit(lines);
+    terminal.set_scrollback_max_bytes(max_bytes);
+    Ok(terminal.set_scrollback_compression(compress.unwrap_or(false)))
+}
+
+/// Print East Asian ambiguous-width characters in this session two columns
+/// wide (CJK locales) or one.
+#[tauri::command]
+pub fn set_ambiguous_width(
+    state: State<'_, AppState>,
+    session_id: String,
+    wide: bool,
+) -> Result<(), String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    session.state.lock().set_ambiguous_wide(wide);
+    Ok(())
+}
+
+/// Offer files programs in this session send for download, or drop them.
+#[tauri::command]
+pub fn set_file_downloads(
+    state: State<'_, AppState>,
+    session_id: String,
+    enabled: bool,
+) -> Result<(), String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    session.state.lock().set_file_downloads(enabled);
+    Ok(())
+}
+
+/// Run the built-in escape-sequence self-test against a scratch terminal
+/// with this session's settings. With `render`, the test pattern and results
+/// are also printed into the session so renderer and font problems show up.
+#[tauri::command]
+pub fn run_terminal_selftest(
+    state: State<'_, AppState>,
+    session_id: String,
+    render: bool,
+) -> Result<Vec<SelftestResult>, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+
+    let image_protocols = session.state.lock().image_protocols_enabled();
+    let results = selftest::run(image_protocols);
+    let failed = results.iter().filter(|result| !result.passed).count();
+    tracing::info!(
+        "Terminal self-test for session {}: {} of {} checks failed",
+        &session_id[..8],
+        failed,
+        results.len()
+    );
+    if render {
+        {
+            let mut ts = session.state.lock();
+            ts.ensure_resident();
+            ts.feed(&selftest::render(&results));
+        }
+        session.notify_render();
+    }
+    Ok(results)
+}
+
+/// Update idle hibernation settings from the user config.
+#[tauri::command]
+pub fn set_hibernation_config(
+    state: State<'_, AppState>,
+    config: HibernationConfig,
+) -> Result<(), String> {
+    *state.hibernation.lock() = HibernationConfig {
+        idle_threshold_secs: config.idle_threshold_secs.max(MIN_HIBERNATE_IDLE_SECS),
+        ..config
+    };
+    Ok(())
+}
+
+/// Spawn the background thread that hibernates sessions idle past the
+/// configured threshold. Does nothing per tick while hibernation is disabled.
+pub fn spawn_hibernation_sweeper(app: AppHandle) {
+    let spawned = std::thread::Builder::new()
+        .name("session-hibernate".to_string())
+        .spawn(move || loop {
+            std::thread::sleep(HIBERNATE_SWEEP_INTERVAL);
+            let state = app.state::<AppState>();
+            let config = *state.hibernation.lock();
+            if !config.enabled {
+                continue;
+            }
+            let threshold = Duration::from_secs(config.idle_threshold_secs);
+            let is_idle = |ts: &TerminalState| !ts.is_hibernated() && ts.idle_for() >= threshold;
+
+            // Only picking the idle sessions holds the sessions lock; each is
+            // then written to disk holding nothing but its own state.
+            let idle: Vec<(String, Arc<Mutex<TerminalState>>)> = state
+                .sessions
+                .lock()
+                .iter()
+                .filter(|(_, session)| is_idle(&session.state.lock()))
+                .map(|(session_id, session)| (session_id.clone(), session.state()))
+                .collect();
+            for (session_id, terminal_state) in idle {
+                let mut ts = terminal_state.lock();
+                // Input may have arrived since.
+                if !is_idle(&ts) {
+                    continue;
+                }
+                let path = match hibernation_file(&app, &session_id) {
+                    Ok(path) => path,
+                    Err(e) => {
+                        tracing::warn!("Skipping idle hibernation: {}", e);
+                        break;
+                    }
+                };
+                match ts.hibernate(path) {
+                    Ok(0) => {}
+                    Ok(rows) => tracing::info!(
+                        "Hibernated {} scrollback rows for idle session {}",
+                        rows,
+                        &session_id[..8]
+                    ),
+                    Err(e) => {
+                        tracing::warn!("Failed to hibernate session {}: {}", &session_id[..8], e)
+                    }
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        tracing::error!("Failed to spawn hibernation sweeper: {}", e);
+    }
+}
+
+fn journal_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("App data dir error: {}", e))?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("mkdir error: {}", e))?;
+    Ok(dir.join(journal::JOURNAL_FILE))
+}
+
+/// Current journal entries, in the order the sessions were created.
+fn journal_entries(state: &AppState) -> Vec<JournalEntry> {
+    let sessions = state.sessions.lock();
+    let mut session_journal = state.session_journal.lock();
+    session_journal.retain(|id| sessions.contains_key(id));
+    session_journal
+        .launches()
+        .iter()
+        .filter_map(|(session_id, shell)| {
+            let ts = sessions.get(session_id)?.state.lock();
+            let (rows, cols) = ts.size();
+            Some(JournalEntry {
+                session_id: session_id.clone(),
+                cwd: ts.shell.cwd.clone(),
+                rows,
+                cols,
+                shell: shell.clone(),
+                title: ts.title.clone(),
+                block_id: ts.shell.current_block_id.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Sample running jobs periodically and emit `jobs-changed` whenever the
+/// set of jobs or their CPU usage changes.
+pub fn spawn_jobs_monitor(app: AppHandle) {
+    let spawned = std::thread::Builder::new()
+        .name("jobs-monitor".to_string())
+        .spawn(move || {
+            let mut sampler = CpuSampler::default();
+            loop {
+                std::thread::sleep(JOBS_SAMPLE_INTERVAL);
+                let state = app.state::<AppState>();
+                let mut jobs = active_jobs(&state);
+                for job in jobs.iter_mut() {
+                    job.cpu_percent = job.pid.and_then(|pid| sampler.sample(pid));
+                }
+                let pids: Vec<i32> = jobs.iter().filter_map(|job| job.pid).collect();
+                sampler.retain(&pids);
+                note_job_terms(&state, &jobs);
+
+                let mut last = state.jobs.lock();
+                if jobs::changed(&last, &jobs) {
+                    let _ = app.emit("jobs-changed", &jobs);
+                }
+                *last = jobs;
+            }
+        });
+    if let Err(e) = spawned {
+        tracing::error!("Failed to spawn jobs monitor: {}", e);
+    }
+}
+
+/// Spawn the thread that emits `session-stalled` once for each session
+/// whose parser thread gets stuck or crashes (see `pty::watchdog`), so the
+/// UI can offer `recover_session`.
+pub fn spawn_parser_watchdog(app: AppHandle) {
+    let spawned = std::thread::Builder::new()
+        .name("parser-watchdog".to_string())
+        .spawn(move || {
+            let mut reported: HashMap<String, ParserHealth> = HashMap::new();
+            loop {
+                std::thread::sleep(WATCHDOG_INTERVAL);
+                let state = app.state::<AppState>();
+                // A command waiting on a wedged session's state can hold the
+                // table; check again next tick.
+                let Some(sessions) = state.sessions.try_lock_for(WATCHDOG_INTERVAL) else {
+                    continue;
+                };
+                let unhealthy: Vec<(String, ParserHealth)> = sessions
+                    .iter()
+                    .map(|(id, session)| (id.clone(), session.parser_health()))
+                    .filter(|(_, health)| *health != ParserHealth::Ok)
+                    .collect();
+                drop(sessions);
+
+                reported.retain(|id, _| unhealthy.iter().any(|(other, _)| other == id));
+                for (session_id, health) in unhealthy {
+                    if reported.get(&session_id) == Some(&health) {
+                        continue;
+                    }
+                    tracing::error!("Parser of session {} is {:?}", &session_id[..8], health);
+                    let _ = app.emit(
+                        "session-stalled",
+                        &SessionStalledPayload {
+                            session_id: session_id.clone(),
+                            health,
+                        },
+                    );
+                    reported.insert(session_id, health);
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        tracing::error!("Failed to spawn parser watchdog: {}", e);
+    }
+}
+
+/// Let each running command's own `TERM` limit the features its session
+/// offers (see `shell::capabilities`).
+fn note_job_terms(state: &AppState, jobs: &[Job]) {
+    let terms: Vec<(&str, String)> = jobs
+        .iter()
+        .filter_map(|job| Some((job.session_id.as_str(), capabilities::process_term(job.pid?)?)))
+        .collect();
+    if terms.is_empty() {
+        return;
+    }
+    let sessions = state.sessions.lock();
+    for (session_id, term) in terms {
+        let Some(session) = sessions.get(session_id) else {
+            continue;
+        };
+        let changed = session.state.lock().shell.set_process_term(&term);
+        if changed {
+            session.notify_render();
+        }
+    }
+}
+
+/// Pick up a journal left by a crashed run (for `load_workspace`), then
+/// spawn the thread that journals session state whenever it changes.
+pub fn spawn_session_journal(app: AppHandle) {
+    let path = match journal_path(&app) {
+        Ok(path) => path,
+        Err(e) => {
+            tracing::warn!("Session journal disabled: {}", e);
+            return;
+        }
+    };
+    if let Some(crashed) = journal::take_crashed(&path) {
+        tracing::warn!(
+            "Previous run exited uncleanly with {} sessions",
+            crashed.sessions.len()
+        );
+        *app.state::<AppState>().crashed_journal.lock() = Some(crashed);
+    }
+
+    let spawned = std::thread::Builder::new()
+        .name("session-journal".to_string())
+        .spawn(move || loop {
+            std::thread::sleep(journal::JOURNAL_INTERVAL);
+            let state = app.state::<AppState>();
+            let entries = journal_entries(&state);
+            // Held across the write so a clean exit can't race it.
+            let mut session_journal = state.session_journal.lock();
+            if !session_journal.needs_write(&entries) {
+                continue;
+            }
+            let updated_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_millis() as u64);
+            if let Err(e) = journal::write(&path, &journal::journal(entries, updated_at)) {
+                tracing::warn!("Failed to write session journal: {}", e);
+            }
+        });
+    if let Err(e) = spawned {
+        tracing::error!("Failed to spawn session journal: {}", e);
+    }
+}
+
+/// Clean exit: stop journaling and delete the journal so the next launch
+/// doesn't offer a crash recovery.
+pub fn close_session_journal(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let mut session_journal = state.session_journal.lock();
+    if !session_journal.close() {
+        return;
+    }
+    if let Ok(path) = journal_path(app) {
+        let _ = std::fs::remove_file(path);
+    }
 }
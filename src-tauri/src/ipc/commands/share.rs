@@ -0,0 +1,267 @@
+use std::time::Duration;
+
+use tauri::State;
+
+use crate::credentials;
+use crate::ipc::AppState;
+use crate::render::export::{ExportFormat, ExportRange};
+use crate::share::{self, ShareService};
+use crate::terminal::grid::Row;
+use crate::terminal::iterm;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn http_client() -> Result<reqwest::Client, String> {
+    if rustls::crypto::CryptoProvider::get_default().is_none() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+    }
+    reqwest::Client::builder()
+        .user_agent(concat!("Rain/", env!("CARGO_PKG_VERSION")))
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| format!("HTTP client error: {}", e))
+}
+
+/// Upload a command block, rendered as ANSI or HTML, to GitHub Gist or a paste
+/// endpoint. Returns the URL of the shared document.
+#[tauri::command]
+pub async fn share_block(
+    state: State<'_, AppState>,
+    session_id: String,
+    block_id: String,
+    service: ShareService,
+    format: ExportFormat,
+) -> Result<String, String> {
+    let (command, content) = {
+        let sessions = state.sessions.lock();
+        let session = sessions
+            .get(&session_id)
+            .ok_or_else(|| format!("Session not found: {}", session_id))?;
+        let terminal_state = session.state();
+        let mut ts = terminal_state.lock();
+        ts.ensure_resident();
+        let (command, rows) = ts
+            .block_rows(&block_id)
+            .ok_or_else(|| "Block output is no longer in scrollback".to_string())?;
+        let title = command.as_deref().unwrap_or("Terminal output");
+        let rows: Vec<&Row> = rows.iter().collect();
+        let content = format.render(&rows, title);
+        (command, content)
+    };
+    if content.trim().is_empty() {
+        return Err("Block has no output to share".to_string());
+    }
+    if content.len() > share::MAX_SHARE_BYTES {
+        return Err(format!(
+            "Block output is too large to share ({} KiB, limit {} KiB)",
+            content.len() / 1024,
+            share::MAX_SHARE_BYTES / 1024
+        ));
+    }
+
+    let client = http_client()?;
+    let url = match service {
+        ShareService::Gist { public } => {
+            let token = credentials::get_secret(share::GIST_TOKEN_KEY)?.ok_or_else(|| {
+                format!(
+                    "No GitHub token stored; save one under \"{}\"",
+                    share::GIST_TOKEN_KEY
+                )
+            })?;
+            let file_name = share::file_name(command.as_deref(), format.extension());
+            let payload = share::gist_payload(&file_name, &content, command.as_deref(), public);
+            let response = client
+                .post(share::GIST_API_URL)
+                .bearer_auth(token)
+                .header("Accept", "application/vnd.github+json")
+                .json(&payload)
+                .send()
+                .await
+                .map_err(|e| format!("Gist upload failed: {}", e))?;
+            let status = response.status();
+            let body = response
+                .text()
+                .await
+                .map_err(|e| format!("Gist upload failed: {}", e))?;
+            if !status.is_success() {
+                return Err(format!(
+                    "Gist upload failed ({}): {}",
+                    status,
+                    share::error_message(&body)
+                ));
+            }
+            let json: serde_json::Value =
+                serde_json::from_str(&body).map_err(|e| format!("Invalid gist response: {}", e))?;
+            share::gist_url(&json)?
+        }
+        ShareService::Paste { endpoint } => {
+            share::validate_endpoint(&endpoint)?;
+            let mut request = client
+                .post(endpoint.trim())
+                .header("Content-Type", format.content_type())
+                .body(content);
+            if let Some(token) = credentials::get_secret(share::PASTE_TOKEN_KEY)? {
+                request = request.bearer_auth(token);
+            }
+            let response = request
+                .send()
+                .await
+                .map_err(|e| format!("Paste upload failed: {}", e))?;
+            let status = response.status();
+            let body = response
+                .text()
+                .await
+                .map_err(|e| format!("Paste upload failed: {}", e))?;
+            if !status.is_success() {
+                return Err(format!(
+                    "Paste upload failed ({}): {}",
+                    status,
+                    share::error_message(&body)
+                ));
+            }
+            share::paste_url(&body)?
+        }
+    };
+    tracing::info!("Shared block {} of session {}", block_id, session_id);
+    Ok(url)
+}
+
+/// Save main-grid rows, rendered as ANSI, plain text or HTML, to a file the
+/// user picks. A file type chosen in the dialog overrides `format`. Returns
+/// false when the dialog is cancelled.
+#[tauri::command]
+pub fn export_output(
+    state: State<'_, AppState>,
+    session_id: String,
+    range: ExportRange,
+    format: ExportFormat,
+) -> Result<bool, String> {
+    // Copy the rows out so the dialog doesn't hold the terminal lock.
+    let (command, rows) = export_rows(&state, &session_id, &range)?;
+    let rows: Vec<&Row> = rows.iter().collect();
+
+    let mut dialog = rfd::FileDialog::new()
+        .set_file_name(share::file_name(command.as_deref(), format.extension()))
+        .add_filter(format.description(), &[format.extension()]);
+    for other in [ExportFormat::Ansi, ExportFormat::Text, ExportFormat::Html] {
+        if other != format {
+            dialog = dialog.add_filter(other.description(), &[other.extension()]);
+        }
+    }
+    let Some(path) = dialog.save_file() else {
+        return Ok(false);
+    };
+    let format = path
+        .extension()
+        .and_then(|ext| ExportFormat::from_extension(&ext.to_string_lossy()))
+        .unwrap_or(format);
+    let title = command.as_deref().unwrap_or("Terminal output");
+    std::fs::write(&path, format.render(&rows, title))
+        .map_err(|e| format!("Write error: {}", e))?;
+    tracing::info!("Exported output of session {} to {:?}", session_id, path);
+    Ok(true)
+}
+
+/// Render main-grid rows as an HTML page served over `rain-asset`, for the
+/// frontend to show before the user saves or shares them. Returns its URL,
+/// which stops resolving when the session is destroyed.
+#[tauri::command]
+pub fn preview_export(
+    state: State<'_, AppState>,
+    session_id: String,
+    range: ExportRange,
+) -> Result<String, String> {
+    let (command, rows) = export_rows(&state, &session_id, &range)?;
+    let rows: Vec<&Row> = rows.iter().collect();
+    let html = ExportFormat::Html.render(&rows, command.as_deref().unwrap_or("Terminal output"));
+    let url = state.assets.lock().insert_typed(
+        &session_id,
+        html.into_bytes(),
+        ExportFormat::Html.content_type(),
+    );
+    Ok(url)
+}
+
+/// Copy of the rows `range` names, with the block's command if it is one.
+/// Fails when nothing printable is left to export.
+fn export_rows(
+    state: &AppState,
+    session_id: &str,
+    range: &ExportRange,
+) -> Result<(Option<String>, Vec<Row>), String> {
+    let (command, rows): (Option<String>, Vec<Row>) = {
+        let sessions = state.sessions.lock();
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| format!("Session not found: {}", session_id))?;
+        let terminal_state = session.state();
+        let mut ts = terminal_state.lock();
+        ts.ensure_resident();
+        let (command, rows) = match range {
+            ExportRange::Lines {
+                start_line_id,
+                end_line_id,
+            } => (None, ts.line_range_rows(*start_line_id, *end_line_id)),
+            ExportRange::Block { block_id } => match ts.block_rows(block_id) {
+                Some((command, rows)) => (command, Some(rows)),
+                None => (None, None),
+            },
+        };
+        let rows = rows.ok_or_else(|| "Requested lines are no longer in scrollback".to_string())?;
+        (command, rows)
+    };
+    let refs: Vec<&Row> = rows.iter().collect();
+    if ExportFormat::Text.render(&refs, "").trim().is_empty() {
+        return Err("Nothing to export".to_string());
+    }
+    Ok((command, rows))
+}
+
+/// Save a file a program sent for download (`FileDownload`) where the user
+/// picks. The dialog suggests the name the program gave, without any
+/// directories. Returns false when the dialog is cancelled, which also
+/// discards the file.
+#[tauri::command]
+pub fn save_download(
+    state: State<'_, AppState>,
+    session_id: String,
+    download_id: String,
+) -> Result<bool, String> {
+    let file = take_download(&state, &session_id, &download_id)?;
+    let name = file
+        .args
+        .name
+        .as_deref()
+        .and_then(|name| std::path::Path::new(name).file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "download".to_string());
+    let Some(path) = rfd::FileDialog::new().set_file_name(&name).save_file() else {
+        return Ok(false);
+    };
+    std::fs::write(&path, &file.data).map_err(|e| format!("Write error: {}", e))?;
+    tracing::info!("Saved download from session {} to {:?}", session_id, path);
+    Ok(true)
+}
+
+/// Drop a file offered for download without saving it.
+#[tauri::command]
+pub fn discard_download(
+    state: State<'_, AppState>,
+    session_id: String,
+    download_id: String,
+) -> Result<(), String> {
+    take_download(&state, &session_id, &download_id).map(|_| ())
+}
+
+fn take_download(
+    state: &AppState,
+    session_id: &str,
+    download_id: &str,
+) -> Result<iterm::File, String> {
+    let sessions = state.sessions.lock();
+    let session = sessions
+        .get(session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    let file = session.state().lock().take_download(download_id);
+    file.ok_or_else(|| format!("Download not found: {}", download_id))
+}
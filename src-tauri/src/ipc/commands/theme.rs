@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+
+use tauri::{AppHandle, Manager, State};
+
+use crate::assets;
+use crate::ipc::AppState;
+use crate::theme::{self, ThemeInfo};
+
+fn themes_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("App config dir error: {}", e))?;
+    Ok(dir.join(theme::THEMES_DIR))
+}
+
+/// Built-in themes and the ones in the config directory's `themes` folder,
+/// read again on every call so new files show up without a restart.
+#[tauri::command]
+pub fn list_themes(app: AppHandle) -> Result<Vec<ThemeInfo>, String> {
+    let dir = themes_dir(&app)?;
+    Ok(theme::list_themes(&dir).iter().map(|t| t.info()).collect())
+}
+
+/// Largest theme background image served.
+const MAX_BACKGROUND_IMAGE_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Make `name` the active theme for every session and tmux pane, and for
+/// sessions created later. Returns its colors, and the URL of its background
+/// image, for the frontend to show.
+#[tauri::command]
+pub fn set_theme(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<ThemeInfo, String> {
+    let dir = themes_dir(&app)?;
+    let theme = theme::list_themes(&dir)
+        .into_iter()
+        .find(|t| t.name == name)
+        .ok_or_else(|| format!("Theme not found: {}", name))?;
+    // The frontend has palettes for its own themes only.
+    let active = (theme.colors, !theme.builtin);
+    *state.theme.lock() = active;
+
+    for session in state.sessions.lock().values() {
+        session.state.lock().set_theme(&active.0, active.1);
+        session.notify_render();
+    }
+    if let Some(ref controller) = *state.tmux_controller.lock() {
+        for handle in controller.pane_handles.lock().values() {
+            handle.state.lock().set_theme(&active.0, active.1);
+        }
+    }
+    let mut info = theme.info();
+    let mut registry = state.assets.lock();
+    registry.remove_session(assets::THEME_OWNER);
+    if let Some(path) = &theme.background_image {
+        match read_background_image(path) {
+            Ok(bytes) => info.background_image = Some(registry.insert(assets::THEME_OWNER, bytes)),
+            Err(e) => tracing::warn!("Background image {:?} not loaded: {}", path, e),
+        }
+    }
+    tracing::info!("Theme: {}", theme.name);
+    Ok(info)
+}
+
+fn read_background_image(path: &std::path::Path) -> Result<Vec<u8>, String> {
+    let len = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if len > MAX_BACKGROUND_IMAGE_BYTES {
+        return Err(format!("{} bytes is over the limit", len));
+    }
+    std::fs::read(path).map_err(|e| e.to_string())
+}
@@ -79,6 +79,16 @@ pub fn take_session_transfer_state(
 }
 
 const TAB_TRANSFER_TTL_MS: u64 = 45_000;
+/// Bounds for caller-supplied TTLs. Heavy sessions may need minutes while the
+/// target window loads, but a transfer should never linger indefinitely.
+const TAB_TRANSFER_MIN_TTL_MS: u64 = 5_000;
+const TAB_TRANSFER_MAX_TTL_MS: u64 = 10 * 60_000;
+
+fn clamp_transfer_ttl(ttl_ms: Option<u64>) -> u64 {
+    ttl_ms
+        .unwrap_or(TAB_TRANSFER_TTL_MS)
+        .clamp(TAB_TRANSFER_MIN_TTL_MS, TAB_TRANSFER_MAX_TTL_MS)
+}
 
 fn now_ms() -> u64 {
     SystemTime::now()
@@ -87,14 +97,53 @@ fn now_ms() -> u64 {
         .unwrap_or(0)
 }
 
-fn cleanup_expired_transfers(entries: &mut std::collections::HashMap<String, TabTransferEntry>) {
+/// Drop expired and committed entries. Returns the IDs of transfers that
+/// expired so callers can report them.
+fn cleanup_expired_transfers(
+    entries: &mut std::collections::HashMap<String, TabTransferEntry>,
+) -> Vec<String> {
     let now = now_ms();
-    entries.retain(|_, entry| {
+    let mut expired = Vec::new();
+    entries.retain(|id, entry| {
         if entry.expires_at_ms <= now {
+            expired.push(id.clone());
             return false;
         }
         entry.status != TabTransferStatus::Committed
     });
+    expired
+}
+
+/// Progress/heartbeat payload for the `tab-transfer-progress` event.
+#[derive(serde::Serialize, Clone)]
+pub struct TabTransferProgressPayload {
+    pub transfer_id: String,
+    /// One of: staged, prepared, released, extended, committed, expired.
+    pub stage: &'static str,
+    pub expires_at_ms: Option<u64>,
+}
+
+fn emit_transfer_progress(
+    app: &AppHandle,
+    transfer_id: &str,
+    stage: &'static str,
+    expires_at_ms: Option<u64>,
+) {
+    let _ = app.emit(
+        "tab-transfer-progress",
+        &TabTransferProgressPayload {
+            transfer_id: transfer_id.to_string(),
+            stage,
+            expires_at_ms,
+        },
+    );
+}
+
+fn emit_expired_transfers(app: &AppHandle, expired: Vec<String>) {
+    for transfer_id in expired {
+        tracing::warn!("Tab transfer {} expired before adoption", transfer_id);
+        emit_transfer_progress(app, &transfer_id, "expired", None);
+    }
 }
 
 #[derive(serde::Serialize, Clone)]
@@ -115,48 +164,91 @@ pub struct TabTransferCommitResult {
 
 #[tauri::command]
 pub fn stage_tab_transfer_manifest(
+    app: AppHandle,
     state: State<'_, AppState>,
     transfer_id: String,
     manifest: TabTransferManifest,
+    ttl_ms: Option<u64>,
 ) -> Result<(), String> {
     if manifest.pane_sessions.is_empty() {
         return Err("Manifest must contain at least one pane session".to_string());
     }
     let now = now_ms();
+    let ttl_ms = clamp_transfer_ttl(ttl_ms);
+    let expires_at_ms = now + ttl_ms;
     let mut manifests = state.tab_transfer_manifests.lock();
-    cleanup_expired_transfers(&mut manifests);
+    let expired = cleanup_expired_transfers(&mut manifests);
     manifests.insert(
-        transfer_id,
+        transfer_id.clone(),
         TabTransferEntry {
             manifest,
             status: TabTransferStatus::Staged,
             created_at_ms: now,
-            expires_at_ms: now + TAB_TRANSFER_TTL_MS,
+            expires_at_ms,
+            ttl_ms,
             prepared_for: None,
             ready_token: None,
         },
     );
+    drop(manifests);
+    emit_expired_transfers(&app, expired);
+    emit_transfer_progress(&app, &transfer_id, "staged", Some(expires_at_ms));
     Ok(())
 }
 
 #[tauri::command]
 pub fn take_tab_transfer_manifest(
+    app: AppHandle,
     state: State<'_, AppState>,
     transfer_id: String,
 ) -> Result<Option<TabTransferManifest>, String> {
     let mut manifests = state.tab_transfer_manifests.lock();
-    cleanup_expired_transfers(&mut manifests);
-    Ok(manifests.remove(&transfer_id).map(|entry| entry.manifest))
+    let expired = cleanup_expired_transfers(&mut manifests);
+    let manifest = manifests.remove(&transfer_id).map(|entry| entry.manifest);
+    drop(manifests);
+    emit_expired_transfers(&app, expired);
+    Ok(manifest)
+}
+
+/// Push a transfer's expiry forward while the target window is still loading.
+/// Acts as a heartbeat: the target calls it periodically until it commits.
+/// Returns the new expiry, or `None` if the transfer is already gone.
+#[tauri::command]
+pub fn extend_tab_transfer(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    transfer_id: String,
+    ttl_ms: Option<u64>,
+) -> Result<Option<u64>, String> {
+    let mut manifests = state.tab_transfer_manifests.lock();
+    let expired = cleanup_expired_transfers(&mut manifests);
+    let extended = manifests.get_mut(&transfer_id).map(|entry| {
+        if ttl_ms.is_some() {
+            entry.ttl_ms = clamp_transfer_ttl(ttl_ms);
+        }
+        entry.expires_at_ms = now_ms() + entry.ttl_ms;
+        entry.expires_at_ms
+    });
+    drop(manifests);
+    emit_expired_transfers(&app, expired);
+    if let Some(expires_at_ms) = extended {
+        emit_transfer_progress(&app, &transfer_id, "extended", Some(expires_at_ms));
+    }
+    Ok(extended)
 }
 
 #[tauri::command]
 pub fn prepare_tab_transfer_adopt(
+    app: AppHandle,
     state: State<'_, AppState>,
     transfer_id: String,
     target_label: String,
 ) -> Result<TabTransferPrepareResult, String> {
     let mut manifests = state.tab_transfer_manifests.lock();
-    cleanup_expired_transfers(&mut manifests);
+    let expired = cleanup_expired_transfers(&mut manifests);
+    drop(manifests);
+    emit_expired_transfers(&app, expired);
+    let mut manifests = state.tab_transfer_manifests.lock();
     let Some(entry) = manifests.get_mut(&transfer_id) else {
         return Ok(TabTransferPrepareResult {
             ok: false,
@@ -215,6 +307,9 @@ pub fn prepare_tab_transfer_adopt(
             entry.status = TabTransferStatus::Prepared;
             entry.prepared_for = Some(target_label);
             entry.ready_token = Some(token.clone());
+            // Preparing counts as a heartbeat: the target is alive and loading.
+            entry.expires_at_ms = entry.expires_at_ms.max(now_ms() + entry.ttl_ms);
+            emit_transfer_progress(&app, &transfer_id, "prepared", Some(entry.expires_at_ms));
             Ok(TabTransferPrepareResult {
                 ok: true,
                 reason: None,
@@ -228,13 +323,15 @@ pub fn prepare_tab_transfer_adopt(
 
 #[tauri::command]
 pub fn release_tab_transfer_adopt(
+    app: AppHandle,
     state: State<'_, AppState>,
     transfer_id: String,
     target_label: String,
     ready_token: String,
 ) -> Result<(), String> {
     let mut manifests = state.tab_transfer_manifests.lock();
-    cleanup_expired_transfers(&mut manifests);
+    let expired = cleanup_expired_transfers(&mut manifests);
+    let mut released_expiry = None;
     if let Some(entry) = manifests.get_mut(&transfer_id) {
         if entry.status == TabTransferStatus::Prepared
             && entry.prepared_for.as_deref() == Some(target_label.as_str())
@@ -243,20 +340,30 @@ pub fn release_tab_transfer_adopt(
             entry.status = TabTransferStatus::Staged;
             entry.prepared_for = None;
             entry.ready_token = None;
+            released_expiry = Some(entry.expires_at_ms);
         }
     }
+    drop(manifests);
+    emit_expired_transfers(&app, expired);
+    if released_expiry.is_some() {
+        emit_transfer_progress(&app, &transfer_id, "released", released_expiry);
+    }
     Ok(())
 }
 
 #[tauri::command]
 pub fn commit_tab_transfer_adopt(
+    app: AppHandle,
     state: State<'_, AppState>,
     transfer_id: String,
     target_label: String,
     ready_token: String,
 ) -> Result<TabTransferCommitResult, String> {
     let mut manifests = state.tab_transfer_manifests.lock();
-    cleanup_expired_transfers(&mut manifests);
+    let expired = cleanup_expired_transfers(&mut manifests);
+    drop(manifests);
+    emit_expired_transfers(&app, expired);
+    let mut manifests = state.tab_transfer_manifests.lock();
     let Some(entry) = manifests.get(&transfer_id) else {
         return Ok(TabTransferCommitResult {
             ok: false,
@@ -287,6 +394,10 @@ pub fn commit_tab_transfer_adopt(
         .remove(&transfer_id)
         .ok_or_else(|| "Transfer disappeared before commit".to_string())?;
     entry.status = TabTransferStatus::Committed;
+    drop(manifests);
+    // Binary frame channels stay bound to the source window's webview until
+    // the adopting window attaches its own (`attach_frame_channel`).
+    emit_transfer_progress(&app, &transfer_id, "committed", None);
     Ok(TabTransferCommitResult {
         ok: true,
         reason: None,
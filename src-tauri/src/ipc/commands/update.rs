@@ -0,0 +1,339 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_updater::{Update, UpdaterExt};
+
+use crate::ipc::AppState;
+use crate::updates::{self, ReleaseInfo};
+
+/// Minimum gap between `update-download-progress` events.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The release being offered, its downloaded package, and whether it should
+/// install by itself once every session is idle.
+#[derive(Default)]
+pub struct UpdateState {
+    release: Option<(Update, ReleaseInfo)>,
+    downloading: bool,
+    bytes: Option<Vec<u8>>,
+    install_when_idle: bool,
+    idle_watcher_running: bool,
+}
+
+impl UpdateState {
+    /// Forget the offered release and its download. An idle watcher still
+    /// running finds nothing to install and stops by itself.
+    pub fn reset(&mut self) {
+        self.release = None;
+        self.bytes = None;
+        self.install_when_idle = false;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadProgress {
+    version: String,
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum InstallOutcome {
+    Installing,
+    /// Waiting for busy sessions; installs by itself once they go idle.
+    #[serde(rename_all = "camelCase")]
+    Deferred {
+        busy_sessions: usize,
+    },
+}
+
+/// Dev builds and forks ship without an updater signing key.
+fn updater_configured(app: &AppHandle) -> bool {
+    app.config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|updater| updater.get("pubkey"))
+        .and_then(|key| key.as_str())
+        .is_some_and(|key| !key.trim().is_empty())
+}
+
+fn data_file(app: &AppHandle, name: &str) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("App data dir error: {}", e))?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("mkdir error: {}", e))?;
+    Ok(dir.join(name))
+}
+
+/// Random per-install ID that places this install in a rollout bucket.
+fn install_id(app: &AppHandle) -> Result<String, String> {
+    let path = data_file(app, "install-id")?;
+    if let Ok(id) = std::fs::read_to_string(&path) {
+        if !id.trim().is_empty() {
+            return Ok(id.trim().to_string());
+        }
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    std::fs::write(&path, &id).map_err(|e| format!("write error: {}", e))?;
+    Ok(id)
+}
+
+/// Sessions an install would interrupt. tmux panes are skipped: the tmux
+/// server keeps them running across a restart.
+fn busy_sessions(state: &AppState) -> usize {
+    state
+        .sessions
+        .lock()
+        .values()
+        .filter(|session| {
+            let exited = session.exit_code().lock().is_some();
+            let ts = session.state.lock();
+            updates::session_busy(
+                ts.shell.active,
+                ts.shell.command_running,
+                exited,
+                ts.idle_for(),
+            )
+        })
+        .count()
+}
+
+/// Check the update endpoint. A release this install is part of the rollout
+/// for is announced with `update-available` and downloaded in the
+/// background; it installs once every session is idle or the user confirms.
+#[tauri::command]
+pub async fn check_for_updates(app: AppHandle) -> Result<Option<ReleaseInfo>, String> {
+    if !updater_configured(&app) {
+        return Err("Updates are not configured for this build".to_string());
+    }
+    let update = app
+        .updater()
+        .map_err(|e| format!("Updater error: {}", e))?
+        .check()
+        .await
+        .map_err(|e| format!("Update check failed: {}", e))?;
+    let Some(update) = update else {
+        return Ok(None);
+    };
+
+    let install_id = install_id(&app)?;
+    if !updates::in_rollout(&install_id, &update.version, &update.raw_json) {
+        tracing::info!(
+            "Rain {} is available but not yet rolled out to this install",
+            update.version
+        );
+        return Ok(None);
+    }
+
+    let info = ReleaseInfo {
+        version: update.version.clone(),
+        current_version: update.current_version.clone(),
+        notes: update.body.clone(),
+        date: update
+            .raw_json
+            .get("pub_date")
+            .and_then(|date| date.as_str())
+            .map(String::from),
+        size: updates::release_size(&update.raw_json, &update.target),
+    };
+
+    let start_download = {
+        let state = app.state::<AppState>();
+        let mut current = state.update.lock();
+        let known = current
+            .release
+            .as_ref()
+            .is_some_and(|(_, release)| release.version == info.version);
+        if !known {
+            current.release = Some((update.clone(), info.clone()));
+            current.bytes = None;
+            current.downloading = false;
+        }
+        let start = !current.downloading && current.bytes.is_none();
+        current.downloading |= start;
+        start
+    };
+
+    if let Err(e) = app.emit("update-available", &info) {
+        tracing::warn!("Failed to emit update-available: {}", e);
+    }
+    if start_download {
+        spawn_download(app, update, info.clone());
+    }
+    Ok(Some(info))
+}
+
+fn spawn_download(app: AppHandle, update: Update, info: ReleaseInfo) {
+    tauri::async_runtime::spawn(async move {
+        let mut downloaded = 0u64;
+        let mut last_emit: Option<Instant> = None;
+        let result = update
+            .download(
+                |chunk, total| {
+                    downloaded += chunk as u64;
+                    let finished = total == Some(downloaded);
+                    if finished || last_emit.is_none_or(|t| t.elapsed() >= PROGRESS_INTERVAL) {
+                        last_emit = Some(Instant::now());
+                        let progress = DownloadProgress {
+                            version: info.version.clone(),
+                            downloaded,
+                            total,
+                        };
+                        let _ = app.emit("update-download-progress", &progress);
+                    }
+                },
+                || {},
+            )
+            .await;
+
+        let state = app.state::<AppState>();
+        let mut current = state.update.lock();
+        current.downloading = false;
+        // A newer release may have replaced this one mid-download.
+        let still_current = current
+            .release
+            .as_ref()
+            .is_some_and(|(_, release)| release.version == info.version);
+        if !still_current {
+            return;
+        }
+        match result {
+            Ok(bytes) => {
+                tracing::info!("Rain {} downloaded", info.version);
+                current.bytes = Some(bytes);
+                current.install_when_idle = true;
+                drop(current);
+                if let Err(e) = app.emit("update-ready", &info) {
+                    tracing::warn!("Failed to emit update-ready: {}", e);
+                }
+                spawn_idle_installer(&app);
+            }
+            Err(e) => {
+                drop(current);
+                tracing::warn!("Update download failed: {}", e);
+                let _ = app.emit("update-error", format!("Update download failed: {}", e));
+            }
+        }
+    });
+}
+
+/// Poll until every session is idle, then install.
+fn spawn_idle_installer(app: &AppHandle) {
+    {
+        let state = app.state::<AppState>();
+        let mut current = state.update.lock();
+        if current.idle_watcher_running {
+            return;
+        }
+        current.idle_watcher_running = true;
+    }
+
+    let handle = app.clone();
+    let spawned = std::thread::Builder::new()
+        .name("update-idle-install".to_string())
+        .spawn(move || loop {
+            std::thread::sleep(updates::IDLE_POLL_INTERVAL);
+            let state = handle.state::<AppState>();
+            {
+                let mut current = state.update.lock();
+                if !current.install_when_idle || current.bytes.is_none() {
+                    current.idle_watcher_running = false;
+                    return;
+                }
+            }
+            if busy_sessions(&state) > 0 {
+                continue;
+            }
+            if let Err(e) = install_now(&handle) {
+                tracing::error!("{}", e);
+                let _ = handle.emit("update-error", e);
+                let mut current = state.update.lock();
+                current.install_when_idle = false;
+                current.idle_watcher_running = false;
+                return;
+            }
+        });
+    if let Err(e) = spawned {
+        tracing::error!("Failed to spawn update installer: {}", e);
+        app.state::<AppState>().update.lock().idle_watcher_running = false;
+    }
+}
+
+/// Install the downloaded package and relaunch. Only returns on failure.
+fn install_now(app: &AppHandle) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let (update, info, bytes) = {
+        let mut current = state.update.lock();
+        let (update, info) = current
+            .release
+            .clone()
+            .ok_or("No update has been downloaded")?;
+        let bytes = current
+            .bytes
+            .take()
+            .ok_or("No update has been downloaded")?;
+        current.install_when_idle = false;
+        (update, info, bytes)
+    };
+
+    // Shown by the next launch as "what's new".
+    let saved = data_file(app, "update-changelog.json").and_then(|path| {
+        let json = serde_json::to_string(&info).map_err(|e| format!("serialize error: {}", e))?;
+        std::fs::write(path, json).map_err(|e| format!("write error: {}", e))
+    });
+    if let Err(e) = saved {
+        tracing::warn!("Failed to save update changelog: {}", e);
+    }
+
+    tracing::info!("Installing Rain {}", info.version);
+    if let Err(e) = update.install(&bytes) {
+        state.update.lock().bytes = Some(bytes);
+        return Err(format!("Failed to install update: {}", e));
+    }
+    app.restart()
+}
+
+/// Install the downloaded update. With busy sessions and no `force`, the
+/// install is deferred until they go idle and the busy count is returned so
+/// the UI can ask the user to confirm.
+#[tauri::command]
+pub fn install_update(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    force: bool,
+) -> Result<InstallOutcome, String> {
+    if state.update.lock().bytes.is_none() {
+        return Err("No update has been downloaded".to_string());
+    }
+    let busy = busy_sessions(&state);
+    if busy > 0 && !force {
+        state.update.lock().install_when_idle = true;
+        spawn_idle_installer(&app);
+        return Ok(InstallOutcome::Deferred {
+            busy_sessions: busy,
+        });
+    }
+    install_now(&app)?;
+    Ok(InstallOutcome::Installing)
+}
+
+/// Release notes of the update that was installed before this launch, once.
+#[tauri::command]
+pub fn take_update_changelog(app: AppHandle) -> Result<Option<ReleaseInfo>, String> {
+    let path = data_file(&app, "update-changelog.json")?;
+    let Ok(data) = std::fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    let _ = std::fs::remove_file(&path);
+    let info: ReleaseInfo =
+        serde_json::from_str(&data).map_err(|e| format!("Invalid update changelog: {}", e))?;
+    // A failed install leaves the old version running; nothing to announce.
+    let running = app.package_info().version.to_string();
+    Ok((info.version == running).then_some(info))
+}
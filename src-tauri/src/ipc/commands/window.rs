@@ -1,8 +1,12 @@
 use std::sync::atomic::Ordering;
 
-use tauri::{AppHandle, Manager, State, WebviewWindow};
+use tauri::window::{ProgressBarState, ProgressBarStatus};
+use tauri::{AppHandle, Emitter, Manager, State, UserAttentionType, WebviewWindow};
 use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
 
+use crate::compositor::CompositorCapabilities;
+use crate::focus::FocusChange;
+use crate::hotkey::HotkeyCapabilities;
 use crate::ipc::AppState;
 
 /// Set the native window background blur radius on macOS via CoreGraphics SPI.
@@ -140,6 +144,34 @@ pub fn set_window_opacity(webview: WebviewWindow, opacity: f64) -> Result<(), St
     Ok(())
 }
 
+/// Report whether transparency and blur can render on this display, so the
+/// UI can fall back to an opaque window instead of showing a black one.
+#[tauri::command]
+pub fn get_compositor_capabilities(webview: WebviewWindow) -> Result<CompositorCapabilities, String> {
+    #[cfg(target_os = "macos")]
+    {
+        let _ = webview;
+        Ok(CompositorCapabilities::native("macos"))
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let _ = webview;
+        Ok(CompositorCapabilities::native("windows"))
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        use gtk::prelude::GtkWindowExt;
+
+        let gtk_window = webview
+            .gtk_window()
+            .map_err(|e| format!("Failed to get GTK window: {}", e))?;
+        let composited = gtk_window.screen().is_some_and(|screen| screen.is_composited());
+        Ok(CompositorCapabilities::linux(|key| std::env::var(key).ok(), composited))
+    }
+}
+
 /// Get the system hostname.
 #[tauri::command]
 pub fn get_hostname() -> String {
@@ -237,6 +269,44 @@ pub fn create_child_window(
     Ok(window_label)
 }
 
+/// Open a fresh top-level window that starts its own session (tray "New Window").
+pub(crate) fn open_new_window(app: &AppHandle) -> Result<String, String> {
+    let n = app
+        .state::<AppState>()
+        .window_counter
+        .fetch_add(1, Ordering::Relaxed);
+    let window_label = format!("rain-{}", n);
+    let url = tauri::WebviewUrl::App("index.html".into());
+
+    let builder = tauri::WebviewWindowBuilder::new(app, &window_label, url)
+        .title("")
+        .inner_size(1024.0, 768.0)
+        .resizable(true)
+        .decorations(true)
+        .transparent(true)
+        .min_inner_size(400.0, 300.0);
+
+    #[cfg(target_os = "macos")]
+    let builder = builder.title_bar_style(tauri::TitleBarStyle::Overlay);
+
+    let window = builder
+        .build()
+        .map_err(|e| format!("Failed to create window: {}", e))?;
+
+    #[cfg(target_os = "macos")]
+    crate::configure_macos_window(&window);
+
+    #[cfg(target_os = "windows")]
+    crate::configure_windows_window(&window);
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    crate::configure_linux_window(&window);
+
+    let _ = window.set_focus();
+    tracing::info!("Opened new window '{}'", window_label);
+    Ok(window_label)
+}
+
 /// Ghost window label used for the drag-out pill overlay.
 const GHOST_LABEL: &str = "ghost-drag";
 
@@ -401,7 +471,7 @@ pub fn quit_app(app: AppHandle) {
 }
 
 /// Toggle the main window visibility (for global hotkey).
-fn toggle_main_window(app: &AppHandle) -> Result<(), String> {
+pub(crate) fn toggle_main_window(app: &AppHandle) -> Result<(), String> {
     if let Some(window) = app.get_webview_window("main") {
         if window.is_visible().unwrap_or(false) {
             window.hide().map_err(|e| format!("Hide error: {}", e))?;
@@ -422,11 +492,19 @@ pub fn toggle_window_visibility(app: AppHandle) -> Result<(), String> {
 
 /// Register a global shortcut to toggle the window.
 #[tauri::command]
-pub fn register_global_hotkey(app: AppHandle, accelerator: String) -> Result<(), String> {
+pub fn register_global_hotkey(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    accelerator: String,
+) -> Result<(), String> {
     let shortcut_manager = app.global_shortcut();
     shortcut_manager
         .unregister_all()
         .map_err(|e| format!("Failed to clear previous global hotkeys: {}", e))?;
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    crate::hotkey::portal::unregister(&state.hotkey_portal);
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    let _ = state;
 
     let normalized = accelerator
         .trim()
@@ -436,6 +514,18 @@ pub fn register_global_hotkey(app: AppHandle, accelerator: String) -> Result<(),
         return Ok(());
     }
 
+    // Wayland compositors ignore X11 key grabs; go through the portal.
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    if crate::hotkey::needs_portal() {
+        crate::hotkey::portal::register(app, &state.hotkey_portal, &normalized, |app_handle| {
+            if let Err(error) = toggle_main_window(app_handle) {
+                tracing::warn!("Global hotkey toggle failed: {}", error);
+            }
+        });
+        tracing::info!("Global hotkey requested via portal: {}", normalized);
+        return Ok(());
+    }
+
     shortcut_manager
         .on_shortcut(normalized.as_str(), move |app_handle, _shortcut, event| {
             if event.state == ShortcutState::Pressed {
@@ -450,6 +540,150 @@ pub fn register_global_hotkey(app: AppHandle, accelerator: String) -> Result<(),
     Ok(())
 }
 
+/// Show or remove the tray / menu bar icon.
+#[tauri::command]
+pub fn set_tray_enabled(app: AppHandle, enabled: bool) -> Result<(), String> {
+    if enabled {
+        crate::create_tray(&app).map_err(|e| format!("Failed to create tray icon: {}", e))
+    } else {
+        app.remove_tray_by_id(crate::tray::TRAY_ID);
+        Ok(())
+    }
+}
+
+/// Drain directories queued by "Open Rain here" (file manager, second launch).
+#[tauri::command]
+pub fn take_pending_open_directories(state: State<'_, AppState>) -> Vec<String> {
+    std::mem::take(&mut *state.pending_open_directories.lock())
+}
+
+/// Drain `rain://` links queued for the main window (commands already confirmed).
+#[tauri::command]
+pub fn take_pending_deep_links(state: State<'_, AppState>) -> Vec<crate::deep_link::DeepLink> {
+    std::mem::take(&mut *state.pending_deep_links.lock())
+}
+
+/// Show a desktop notification a program in `session_id` asked for (OSC 9
+/// or 777). Clicking it brings the calling window forward and emits
+/// `notification-clicked` to it with the session ID. Where there is no
+/// notifier the taskbar entry flashes instead.
+#[tauri::command]
+pub fn show_notification(
+    window: WebviewWindow,
+    session_id: String,
+    title: String,
+    body: String,
+) -> Result<(), String> {
+    let target = window.clone();
+    let shown = crate::notification::show(&title, &body, move || {
+        let _ = target.show();
+        let _ = target.unminimize();
+        let _ = target.set_focus();
+        if let Err(e) = target.emit_to(target.label(), "notification-clicked", &session_id) {
+            tracing::warn!("Failed to emit notification click: {}", e);
+        }
+    });
+    if let Err(e) = shown {
+        tracing::debug!("Falling back to user attention: {}", e);
+        window
+            .request_user_attention(Some(UserAttentionType::Informational))
+            .map_err(|e| format!("Failed to request attention: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Show progress on the window's dock icon (macOS) or taskbar button
+/// (Windows, and Linux docks that support it). `value` is a percentage;
+/// the `none` state hides the bar.
+#[tauri::command]
+pub fn set_window_progress(
+    window: WebviewWindow,
+    state: ProgressBarStatus,
+    value: u8,
+) -> Result<(), String> {
+    window
+        .set_progress_bar(ProgressBarState {
+            status: Some(state),
+            progress: Some(value.min(100) as u64),
+        })
+        .map_err(|e| format!("Failed to set progress: {}", e))
+}
+
+/// Install or remove the Windows Explorer "Open Rain here" verb.
+#[tauri::command]
+pub fn set_explorer_integration(enabled: bool) -> Result<(), String> {
+    crate::open_here::set_explorer_integration(enabled)
+}
+
+/// The session with keyboard focus in the calling window changed. Together
+/// with native window focus this drives focus in/out reports (mode 1004).
+#[tauri::command]
+pub fn set_session_focus(
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+    session_id: String,
+    focused: bool,
+) {
+    let change = state
+        .focus
+        .lock()
+        .set_session_focus(window.label(), &session_id, focused);
+    report_focus_change(&state, change);
+}
+
+/// Native focus of a window changed, or the window closed.
+pub fn window_focus_changed(window: &tauri::Window, focused: Option<bool>) {
+    let state = window.state::<AppState>();
+    let change = match focused {
+        Some(focused) => state.focus.lock().set_window_focus(window.label(), focused),
+        None => state.focus.lock().remove_window(window.label()),
+    };
+    report_focus_change(&state, change);
+}
+
+fn report_focus_change(state: &AppState, change: FocusChange) {
+    let sessions = state.sessions.lock();
+    let reports = [(change.lost, false), (change.gained, true)];
+    for (session_id, focused) in reports {
+        let Some(session) = session_id.and_then(|id| sessions.get(&id)) else {
+            continue;
+        };
+        if let Err(e) = session.report_focus(focused) {
+            tracing::warn!("Failed to report focus: {}", e);
+        }
+    }
+}
+
+/// Report which hotkey backend applies and whether it can work here.
+#[tauri::command]
+pub async fn get_global_hotkey_capabilities() -> HotkeyCapabilities {
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    if crate::hotkey::needs_portal() {
+        return if crate::hotkey::portal::is_available().await {
+            HotkeyCapabilities {
+                backend: "portal",
+                supported: true,
+                reason: Some(
+                    "Your desktop will ask you to confirm the shortcut the first time.".to_string(),
+                ),
+                bound_trigger: None,
+            }
+        } else {
+            HotkeyCapabilities {
+                backend: "none",
+                supported: false,
+                reason: Some(
+                    "This Wayland desktop has no GlobalShortcuts portal; bind a system shortcut \
+                     to `rain` instead."
+                        .to_string(),
+                ),
+                bound_trigger: None,
+            }
+        };
+    }
+    HotkeyCapabilities::native()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -7,6 +7,11 @@
 pub const RENDER_FRAME: &str = "render-frame";
 
 /// Session has ended (shell exited).
-/// Payload: SessionEndPayload { session_id, exit_code }
+/// Payload: SessionEndPayload { session_id, exit_code, command }
 #[allow(dead_code)]
 pub const SESSION_ENDED: &str = "session-ended";
+
+/// A session's frame ring holds new frames, up to `head`.
+/// Payload: FrameReadyPayload { session_id, url, head }
+#[allow(dead_code)]
+pub const FRAME_READY: &str = "frame-ready";
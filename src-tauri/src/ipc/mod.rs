@@ -3,9 +3,12 @@ pub mod events;
 
 use std::collections::HashMap;
 use std::sync::atomic::AtomicU32;
+use std::sync::Arc;
 
 use parking_lot::Mutex;
+use tauri::ipc::Channel;
 
+use crate::frame_ring::FrameRing;
 use crate::pty::Session;
 use crate::tmux::TmuxController;
 
@@ -103,19 +106,85 @@ pub struct TabTransferEntry {
     pub status: TabTransferStatus,
     pub created_at_ms: u64,
     pub expires_at_ms: u64,
+    /// TTL applied on each heartbeat/extension of this transfer.
+    pub ttl_ms: u64,
     pub prepared_for: Option<String>,
     pub ready_token: Option<String>,
 }
 
+/// Idle-session hibernation settings, pushed from the user config.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HibernationConfig {
+    /// Automatically hibernate sessions that stay idle past the threshold.
+    pub enabled: bool,
+    /// Seconds without PTY output or user input before a session hibernates.
+    pub idle_threshold_secs: u64,
+}
+
+impl Default for HibernationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_threshold_secs: 30 * 60,
+        }
+    }
+}
+
 /// Application-wide state managed by Tauri.
 pub struct AppState {
     pub sessions: Mutex<HashMap<String, Session>>,
     pub session_transfer_state: Mutex<HashMap<String, SessionTransferState>>,
     pub tab_transfer_manifests: Mutex<HashMap<String, TabTransferEntry>>,
     pub pty_manager: crate::pty::PtyManager,
+    /// Shells started ahead of time for new tabs, when enabled in config.
+    pub warm_pool: Mutex<crate::pty::warm::WarmPool>,
     pub tmux_controller: Mutex<Option<TmuxController>>,
     /// Counter for generating unique child window labels.
     pub window_counter: AtomicU32,
+    pub hibernation: Mutex<HibernationConfig>,
+    /// User-registered OSC numbers, applied to every new and existing session.
+    pub custom_osc_codes: Mutex<Vec<u16>>,
+    /// DA1/DA2 conformance level and features, applied like the OSC codes.
+    pub device_attributes: Mutex<crate::terminal::device::DeviceAttributes>,
+    /// Disk-backed cache of executables on PATH for the command palette.
+    pub path_command_cache: Mutex<commands::filesystem::PathCommandCache>,
+    /// Native terminal context menu awaiting a selection.
+    pub context_menu: Mutex<Option<commands::menu::PendingContextMenu>>,
+    /// Directories from "Open Rain here" waiting for the main window.
+    pub pending_open_directories: Mutex<Vec<String>>,
+    /// Confirmed `rain://` links waiting for the main window.
+    pub pending_deep_links: Mutex<Vec<crate::deep_link::DeepLink>>,
+    /// Release being offered by the updater and its download.
+    pub update: Mutex<commands::update::UpdateState>,
+    /// Launch order and last write of the crash-recovery journal.
+    pub session_journal: Mutex<crate::journal::SessionJournal>,
+    /// Journal left behind by a crashed run, offered once by `load_workspace`.
+    pub crashed_journal: Mutex<Option<crate::journal::Journal>>,
+    /// Whether this launch runs in safe mode after repeated crashes.
+    pub startup_mode: Mutex<crate::safe_mode::StartupMode>,
+    /// Running commands across sessions as of the last jobs monitor pass.
+    pub jobs: Mutex<Vec<crate::jobs::Job>>,
+    /// Decoded images served over `rain-asset://`, shared with every
+    /// session's terminal.
+    pub assets: Arc<Mutex<crate::assets::AssetRegistry>>,
+    /// Text copied through the UI or OSC 52, newest first.
+    pub clipboard_history: crate::clipboard::SharedHistory,
+    /// Global hotkey portal session, cancelled when the hotkey changes.
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    pub hotkey_portal: crate::hotkey::portal::ActiveSession,
+    /// Session with keyboard focus in each window, for focus reports.
+    pub focus: Mutex<crate::focus::FocusTracker>,
+    /// Active theme's colors, and whether spans carry its ANSI colors as
+    /// RGB; applied to every new and existing session.
+    pub theme: Mutex<(crate::terminal::color::ThemeColors, bool)>,
+    /// Channels for MessagePack render frames (and the session's end), by
+    /// session, bound to the webview showing it; the rest get JSON
+    /// `render-frame` events.
+    pub frame_channels: Mutex<HashMap<String, Channel>>,
+    /// Frame rings, by session, for sessions created with one. They take
+    /// precedence over frame channels.
+    pub frame_rings: Mutex<HashMap<String, Arc<Mutex<FrameRing>>>>,
 }
 
 impl AppState {
@@ -125,8 +194,29 @@ impl AppState {
             session_transfer_state: Mutex::new(HashMap::new()),
             tab_transfer_manifests: Mutex::new(HashMap::new()),
             pty_manager: crate::pty::PtyManager::new(),
+            warm_pool: Mutex::new(Default::default()),
             tmux_controller: Mutex::new(None),
             window_counter: AtomicU32::new(0),
+            hibernation: Mutex::new(HibernationConfig::default()),
+            custom_osc_codes: Mutex::new(Vec::new()),
+            device_attributes: Mutex::new(Default::default()),
+            path_command_cache: Mutex::new(Default::default()),
+            context_menu: Mutex::new(None),
+            pending_open_directories: Mutex::new(Vec::new()),
+            pending_deep_links: Mutex::new(Vec::new()),
+            update: Mutex::new(Default::default()),
+            session_journal: Mutex::new(Default::default()),
+            crashed_journal: Mutex::new(None),
+            startup_mode: Mutex::new(Default::default()),
+            jobs: Mutex::new(Vec::new()),
+            assets: Arc::new(Mutex::new(Default::default())),
+            clipboard_history: Arc::new(Mutex::new(Default::default())),
+            #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+            hotkey_portal: Mutex::new(None),
+            focus: Mutex::new(Default::default()),
+            theme: Mutex::new(Default::default()),
+            frame_channels: Mutex::new(HashMap::new()),
+            frame_rings: Mutex::new(HashMap::new()),
         }
     }
 }
@@ -0,0 +1,130 @@
+//! Commands currently running in any session, for the global jobs drawer.
+//!
+//! A job is a session's running block (OSC 133 command start without an end
+//! yet). The monitor in `ipc::commands::session` rebuilds the list
+//! periodically and emits `jobs-changed` when it differs from the last one.
+//! CPU usage is sampled from the PTY's foreground process group leader
+//! where the OS exposes per-process times (Linux `/proc`); elsewhere it is
+//! left empty.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    pub session_id: String,
+    pub block_id: String,
+    pub command: String,
+    pub cwd: String,
+    /// Unix time in milliseconds when the command started.
+    pub started_at: u64,
+    /// Foreground process group leader of the session's PTY.
+    pub pid: Option<i32>,
+    /// CPU usage of `pid` since the previous sample, in percent of one core.
+    pub cpu_percent: Option<f32>,
+}
+
+/// Whether the drawer needs a refresh: jobs were added or removed, or a CPU
+/// reading moved by at least a percentage point.
+pub fn changed(prev: &[Job], next: &[Job]) -> bool {
+    prev.len() != next.len()
+        || prev.iter().zip(next).any(|(a, b)| {
+            a.block_id != b.block_id
+                || a.pid != b.pid
+                || match (a.cpu_percent, b.cpu_percent) {
+                    (Some(a), Some(b)) => (a - b).abs() >= 1.0,
+                    (a, b) => a.is_some() != b.is_some(),
+                }
+        })
+}
+
+/// User plus system clock ticks from the contents of `/proc/<pid>/stat`.
+fn parse_stat_ticks(stat: &str) -> Option<u64> {
+    // The command name may contain spaces and parentheses; fields after it
+    // start at `state` (field 3), so utime (14) and stime (15) are 11 and 12.
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
+#[cfg(target_os = "linux")]
+fn cpu_seconds(pid: i32) -> Option<f64> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // SAFETY: sysconf has no preconditions.
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_sec <= 0 {
+        return None;
+    }
+    Some(parse_stat_ticks(&stat)? as f64 / ticks_per_sec as f64)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cpu_seconds(_pid: i32) -> Option<f64> {
+    None
+}
+
+/// Turns cumulative process CPU time into usage between samples.
+#[derive(Debug, Default)]
+pub struct CpuSampler {
+    last: HashMap<i32, (f64, Instant)>,
+}
+
+impl CpuSampler {
+    /// Usage since the previous call for `pid`; `None` on the first sample
+    /// or where CPU times are unavailable.
+    pub fn sample(&mut self, pid: i32) -> Option<f32> {
+        let now = Instant::now();
+        let seconds = cpu_seconds(pid)?;
+        let previous = self.last.insert(pid, (seconds, now));
+        let (prev_seconds, prev_at) = previous?;
+        let elapsed = now.duration_since(prev_at).as_secs_f64();
+        if elapsed <= 0.0 || seconds < prev_seconds {
+            return None;
+        }
+        Some(((seconds - prev_seconds) / elapsed * 100.0) as f32)
+    }
+
+    /// Forget processes that are no longer sampled.
+    pub fn retain(&mut self, pids: &[i32]) {
+        self.last.retain(|pid, _| pids.contains(pid));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(block_id: &str, cpu_percent: Option<f32>) -> Job {
+        Job {
+            session_id: "s".to_string(),
+            block_id: block_id.to_string(),
+            command: "make".to_string(),
+            cwd: "/src".to_string(),
+            started_at: 0,
+            pid: Some(42),
+            cpu_percent,
+        }
+    }
+
+    #[test]
+    fn parses_stat_with_odd_command_names() {
+        let stat = "42 (my (odd) cmd) R 1 42 42 0 -1 4194304 100 0 0 0 250 30 0 0 20 0 1 0";
+        assert_eq!(parse_stat_ticks(stat), Some(280));
+        assert_eq!(parse_stat_ticks("42 (short) R 1"), None);
+    }
+
+    #[test]
+    fn small_cpu_jitter_is_not_a_change() {
+        let prev = vec![job("a", Some(10.0))];
+        assert!(!changed(&prev, &[job("a", Some(10.4))]));
+        assert!(changed(&prev, &[job("a", Some(12.0))]));
+        assert!(changed(&prev, &[job("b", Some(10.0))]));
+        assert!(changed(&prev, &[]));
+        assert!(changed(&[], &prev));
+    }
+}
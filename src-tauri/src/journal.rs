@@ -0,0 +1,219 @@
+//! Crash-resilient session journal.
+//!
+//! Workspace saves only happen on a clean quit, so a crash used to lose the
+//! whole layout. A background thread journals the minimal state of every
+//! session whenever it changes; a clean exit deletes the journal. A journal
+//! still on disk at startup therefore means the last run crashed, and
+//! `load_workspace` offers its sessions back as tabs (shells restart).
+
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+pub const JOURNAL_FILE: &str = "session-journal.json";
+
+/// How often the journal thread looks for changes.
+pub const JOURNAL_INTERVAL: Duration = Duration::from_secs(1);
+
+const JOURNAL_VERSION: u32 = 1;
+
+/// What survives a crash for one session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    pub session_id: String,
+    pub cwd: String,
+    pub rows: u16,
+    pub cols: u16,
+    /// Shell the session was launched with; `None` for the default shell.
+    pub shell: Option<String>,
+    pub title: String,
+    /// Command block in progress when the entry was written.
+    pub block_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Journal {
+    pub version: u32,
+    /// Unix time in milliseconds.
+    pub updated_at: u64,
+    pub sessions: Vec<JournalEntry>,
+}
+
+/// Sessions in launch order, plus what was last written to disk.
+#[derive(Debug, Default)]
+pub struct SessionJournal {
+    launches: Vec<(String, Option<String>)>,
+    last_written: Option<Vec<JournalEntry>>,
+    /// Set on clean exit so the journal thread doesn't recreate the file.
+    closed: bool,
+}
+
+impl SessionJournal {
+    pub fn launched(&mut self, session_id: &str, shell: Option<String>) {
+        self.launches.push((session_id.to_string(), shell));
+    }
+
+    pub fn ended(&mut self, session_id: &str) {
+        self.launches.retain(|(id, _)| id != session_id);
+    }
+
+    /// Drop sessions that went away without `ended` (e.g. a backend reset).
+    pub fn retain(&mut self, mut live: impl FnMut(&str) -> bool) {
+        self.launches.retain(|(id, _)| live(id));
+    }
+
+    /// Session IDs in launch order with their requested shells.
+    pub fn launches(&self) -> &[(String, Option<String>)] {
+        &self.launches
+    }
+
+    /// Whether `entries` differ from the last write (and the journal is
+    /// still open). Records them as written when they do.
+    pub fn needs_write(&mut self, entries: &[JournalEntry]) -> bool {
+        if self.closed || self.last_written.as_deref() == Some(entries) {
+            return false;
+        }
+        self.last_written = Some(entries.to_vec());
+        true
+    }
+
+    /// Stop journaling; returns false if already closed.
+    pub fn close(&mut self) -> bool {
+        !std::mem::replace(&mut self.closed, true)
+    }
+}
+
+pub fn journal(sessions: Vec<JournalEntry>, updated_at: u64) -> Journal {
+    Journal {
+        version: JOURNAL_VERSION,
+        updated_at,
+        sessions,
+    }
+}
+
+/// Write via a temp file and rename, so a crash mid-write can't leave a
+/// truncated journal behind.
+pub fn write(path: &Path, journal: &Journal) -> std::io::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    let data = serde_json::to_vec(journal)?;
+    {
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+    }
+    std::fs::rename(&tmp, path)
+}
+
+/// Read and remove a journal left behind by a crashed run.
+pub fn take_crashed(path: &Path) -> Option<Journal> {
+    let data = std::fs::read(path).ok()?;
+    let _ = std::fs::remove_file(path);
+    let journal: Journal = serde_json::from_slice(&data).ok()?;
+    (journal.version == JOURNAL_VERSION).then_some(journal)
+}
+
+/// A session-restore workspace (the frontend's `SavedWorkspace` format) with
+/// one tab per journaled session, marked with `recoveredSessions`.
+pub fn recovered_workspace(journal: &Journal) -> Option<Value> {
+    if journal.sessions.is_empty() {
+        return None;
+    }
+    let tabs: Vec<Value> = journal
+        .sessions
+        .iter()
+        .map(|entry| {
+            let label = if entry.title.trim().is_empty() {
+                "Shell"
+            } else {
+                entry.title.trim()
+            };
+            json!({
+                "label": label,
+                "customLabel": null,
+                "cwd": entry.cwd,
+                "paneTree": { "type": "leaf", "cwd": entry.cwd },
+                "activeLeafIndex": 0,
+            })
+        })
+        .collect();
+    Some(json!({
+        "version": 1,
+        "tabs": tabs,
+        "activeTabIndex": 0,
+        "savedAt": journal.updated_at,
+        "recoveredSessions": journal.sessions.len(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, cwd: &str) -> JournalEntry {
+        JournalEntry {
+            session_id: id.into(),
+            cwd: cwd.into(),
+            rows: 24,
+            cols: 80,
+            shell: None,
+            title: String::new(),
+            block_id: None,
+        }
+    }
+
+    #[test]
+    fn writes_only_on_change_and_never_after_close() {
+        let mut journal = SessionJournal::default();
+        let entries = vec![entry("a", "/tmp")];
+        assert!(journal.needs_write(&entries));
+        assert!(!journal.needs_write(&entries));
+        assert!(journal.needs_write(&[entry("a", "/srv")]));
+        assert!(journal.close());
+        assert!(!journal.close());
+        assert!(!journal.needs_write(&[]));
+    }
+
+    #[test]
+    fn launches_keep_order() {
+        let mut journal = SessionJournal::default();
+        journal.launched("a", None);
+        journal.launched("b", Some("/bin/zsh".into()));
+        journal.launched("c", None);
+        journal.ended("b");
+        journal.retain(|id| id != "missing");
+        let ids: Vec<&str> = journal
+            .launches()
+            .iter()
+            .map(|(id, _)| id.as_str())
+            .collect();
+        assert_eq!(ids, ["a", "c"]);
+    }
+
+    #[test]
+    fn crashed_journal_round_trips_once() {
+        let path = std::env::temp_dir().join(format!("rain-journal-{}.json", std::process::id()));
+        write(&path, &journal(vec![entry("a", "/tmp")], 42)).unwrap();
+        let recovered = take_crashed(&path).unwrap();
+        assert_eq!(recovered.sessions, vec![entry("a", "/tmp")]);
+        assert!(take_crashed(&path).is_none());
+    }
+
+    #[test]
+    fn recovered_workspace_has_a_tab_per_session() {
+        let mut titled = entry("b", "/srv");
+        titled.title = "htop".into();
+        let workspace =
+            recovered_workspace(&journal(vec![entry("a", "/tmp"), titled], 42)).unwrap();
+        assert_eq!(workspace["recoveredSessions"], 2);
+        assert_eq!(workspace["savedAt"], 42);
+        assert_eq!(workspace["tabs"][0]["label"], "Shell");
+        assert_eq!(workspace["tabs"][1]["label"], "htop");
+        assert_eq!(workspace["tabs"][1]["paneTree"]["cwd"], "/srv");
+        assert!(recovered_workspace(&journal(vec![], 42)).is_none());
+    }
+}
@@ -1,15 +1,85 @@
+//! Rain's backend. The `app` feature (on by default) builds the Tauri
+//! desktop app around the terminal engine; without it only the engine
+//! modules are built, for `rain-headless`.
+
+// Much of the engine is only reached from the app's commands.
+#![cfg_attr(not(feature = "app"), allow(dead_code))]
+
+mod assets;
+#[cfg(feature = "app")]
+mod backup;
+mod clipboard;
+#[cfg(feature = "app")]
+mod compositor;
+#[cfg(feature = "app")]
+mod context_menu;
+#[cfg(feature = "app")]
+mod credentials;
+#[cfg(feature = "app")]
+mod deep_link;
+#[cfg(feature = "app")]
+mod focus;
+#[cfg(feature = "app")]
+mod frame_ring;
+#[cfg(feature = "headless")]
+pub mod headless;
+#[cfg(feature = "app")]
+mod hotkey;
+#[cfg(feature = "app")]
 mod ipc;
+#[cfg(feature = "app")]
+mod jobs;
+#[cfg(feature = "app")]
+mod journal;
+#[cfg(feature = "app")]
+mod notification;
+#[cfg(feature = "app")]
+mod open_here;
+mod process;
 mod pty;
 mod render;
+#[cfg(feature = "app")]
+mod safe_mode;
+#[cfg(feature = "app")]
+mod share;
 mod shell;
 mod terminal;
+#[cfg(feature = "app")]
+mod theme;
+#[cfg(feature = "app")]
 mod tmux;
+#[cfg(feature = "app")]
+mod tray;
+#[cfg(feature = "app")]
+mod updates;
 
+#[cfg(feature = "app")]
+use std::path::PathBuf;
+#[cfg(feature = "app")]
+use std::time::Duration;
+
+#[cfg(feature = "app")]
+use deep_link::DeepLink;
+#[cfg(feature = "app")]
 use ipc::AppState;
-use tauri::Manager;
+#[cfg(feature = "app")]
+use tauri::menu::{Menu, MenuBuilder};
+#[cfg(feature = "app")]
+use tauri::tray::TrayIconBuilder;
+#[cfg(feature = "app")]
+use tauri::{AppHandle, Emitter, Manager};
+#[cfg(feature = "app")]
+use tauri_plugin_deep_link::DeepLinkExt;
+#[cfg(feature = "app")]
+use tray::{TrayAction, TraySession, TrayWindow};
+
+/// How often the tray menu is checked against current session state.
+#[cfg(feature = "app")]
+const TRAY_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
 
 /// Apply macOS-specific transparent window configuration.
 /// Called for both the main window at startup and dynamically created child windows.
+#[cfg(feature = "app")]
 #[cfg(target_os = "macos")]
 pub fn configure_macos_window(window: &tauri::WebviewWindow) {
     if let Err(err) = window.with_webview(|webview| unsafe {
@@ -48,12 +118,7 @@ pub fn configure_macos_window(window: &tauri::WebviewWindow) {
 
         extern "C" {
             fn CGSMainConnectionID() -> i32;
-            fn CGSSetWindowTags(
-                cid: i32,
-                wid: i32,
-                tags: *const i32,
-                max_tag_size: i32,
-            ) -> i32;
+            fn CGSSetWindowTags(cid: i32, wid: i32, tags: *const i32, max_tag_size: i32) -> i32;
         }
 
         let ns_window_ptr: *mut NSWindow = webview.ns_window().cast();
@@ -71,6 +136,7 @@ pub fn configure_macos_window(window: &tauri::WebviewWindow) {
 }
 
 /// Apply Windows-specific DWM configuration for dark title bar and transparent backdrop.
+#[cfg(feature = "app")]
 #[cfg(target_os = "windows")]
 pub fn configure_windows_window(window: &tauri::WebviewWindow) {
     #[repr(C)]
@@ -127,13 +193,427 @@ pub fn configure_windows_window(window: &tauri::WebviewWindow) {
 /// the GDK window visual to RGBA. CSS `backdrop-filter` provides the blur
 /// effect on composited desktops; the fallback in base-layout.css renders a
 /// solid background when the compositor doesn't support it.
+#[cfg(feature = "app")]
 #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-pub fn configure_linux_window(_window: &tauri::WebviewWindow) {
-    tracing::info!(
-        "Linux window configured (transparency via compositor + tauri.conf.json)"
-    );
+pub fn configure_linux_window(window: &tauri::WebviewWindow) {
+    use gtk::prelude::GtkWindowExt;
+    use tauri::Emitter;
+
+    match ipc::commands::window::get_compositor_capabilities(window.clone()) {
+        Ok(caps) => {
+            if let Some(advice) = &caps.advice {
+                tracing::warn!("{}", advice);
+            }
+            tracing::info!(
+                "Linux window configured ({} session, compositor {:?}, composited: {})",
+                caps.display_server,
+                caps.compositor,
+                caps.composited
+            );
+        }
+        Err(e) => tracing::warn!("Compositor detection failed: {}", e),
+    }
+
+    // Compositors can start or stop while Rain runs (e.g. toggling picom);
+    // let the frontend re-evaluate window effects when that happens.
+    let Some(screen) = window.gtk_window().ok().and_then(|w| w.screen()) else {
+        return;
+    };
+    let window = window.clone();
+    screen.connect_composited_changed(move |screen| {
+        let caps = compositor::CompositorCapabilities::linux(
+            |key| std::env::var(key).ok(),
+            screen.is_composited(),
+        );
+        if let Err(e) = window.emit("compositor-capabilities-changed", &caps) {
+            tracing::warn!("Failed to emit compositor change: {}", e);
+        }
+    });
 }
 
+/// Open windows (main first) and live sessions, as shown in the tray menu.
+#[cfg(feature = "app")]
+fn tray_snapshot(app: &AppHandle) -> (Vec<TrayWindow>, Vec<TraySession>) {
+    let mut windows: Vec<TrayWindow> = app
+        .webview_windows()
+        .into_iter()
+        .filter(|(label, _)| !label.starts_with("ghost-"))
+        .map(|(label, window)| TrayWindow {
+            title: window.title().unwrap_or_default(),
+            label,
+        })
+        .collect();
+    windows.sort_by(|a, b| (a.label != "main", &a.label).cmp(&(b.label != "main", &b.label)));
+
+    let state = app.state::<AppState>();
+    let mut sessions: Vec<TraySession> = state
+        .sessions
+        .lock()
+        .iter()
+        .map(|(session_id, session)| {
+            let exited = session.exit_code().lock().is_some();
+            let ts = session.state.lock();
+            TraySession {
+                session_id: session_id.clone(),
+                title: ts.title.clone(),
+                cwd: ts.shell.cwd.clone(),
+                active: ts.idle_for() < tray::ACTIVE_WINDOW,
+                exited,
+            }
+        })
+        .collect();
+    sessions.sort_by(|a, b| {
+        a.title
+            .cmp(&b.title)
+            .then_with(|| a.session_id.cmp(&b.session_id))
+    });
+    (windows, sessions)
+}
+
+#[cfg(feature = "app")]
+fn build_tray_menu(
+    app: &AppHandle,
+    windows: &[TrayWindow],
+    sessions: &[TraySession],
+) -> tauri::Result<Menu<tauri::Wry>> {
+    let mut builder = MenuBuilder::new(app);
+    for (index, window) in windows.iter().enumerate() {
+        builder = builder.text(
+            TrayAction::FocusWindow(window.label.clone()).id(),
+            tray::window_label(window, index),
+        );
+    }
+    if !sessions.is_empty() {
+        builder = builder.separator();
+        for session in sessions {
+            builder = builder.text(
+                TrayAction::FocusSession(session.session_id.clone()).id(),
+                tray::session_label(session),
+            );
+        }
+    }
+    builder
+        .separator()
+        .text(TrayAction::NewWindow.id(), "New Window")
+        .text(TrayAction::ToggleVisibility.id(), "Show/Hide Rain")
+        .separator()
+        .text(TrayAction::Quit.id(), "Quit Rain")
+        .build()
+}
+
+#[cfg(feature = "app")]
+fn handle_tray_action(app: &AppHandle, id: &str) {
+    let Some(action) = TrayAction::from_id(id) else {
+        return;
+    };
+    let result = match action {
+        TrayAction::NewWindow => ipc::commands::window::open_new_window(app).map(|_| ()),
+        TrayAction::ToggleVisibility => ipc::commands::window::toggle_main_window(app),
+        TrayAction::Quit => {
+            app.exit(0);
+            Ok(())
+        }
+        TrayAction::FocusWindow(label) => match app.get_webview_window(&label) {
+            Some(window) => window
+                .show()
+                .and_then(|_| window.set_focus())
+                .map_err(|e| format!("Failed to focus window {}: {}", label, e)),
+            None => Err(format!("Window not found: {}", label)),
+        },
+        // Only the frontend knows which window and tab hold a session; the
+        // owning window focuses itself when it sees the event.
+        TrayAction::FocusSession(session_id) => app
+            .emit("tray-focus-session", &session_id)
+            .map_err(|e| format!("Failed to emit tray focus: {}", e)),
+    };
+    if let Err(e) = result {
+        tracing::warn!("Tray action failed: {}", e);
+    }
+}
+
+/// Create the tray / menu bar icon if it isn't shown yet.
+#[cfg(feature = "app")]
+pub fn create_tray(app: &AppHandle) -> tauri::Result<()> {
+    if app.tray_by_id(tray::TRAY_ID).is_some() {
+        return Ok(());
+    }
+    let (windows, sessions) = tray_snapshot(app);
+    let menu = build_tray_menu(app, &windows, &sessions)?;
+    let mut builder = TrayIconBuilder::with_id(tray::TRAY_ID)
+        .menu(&menu)
+        .tooltip(tray::tooltip(&sessions))
+        .on_menu_event(|app, event| handle_tray_action(app, event.id().as_ref()));
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+    tracing::info!("Tray icon created");
+    Ok(())
+}
+
+/// Keep the tray menu in step with open windows, sessions and activity.
+/// Rebuilds only when something visible changed.
+#[cfg(feature = "app")]
+fn spawn_tray_refresher(app: AppHandle) {
+    let spawned = std::thread::Builder::new()
+        .name("tray-refresh".to_string())
+        .spawn(move || {
+            let mut last = None;
+            loop {
+                std::thread::sleep(TRAY_REFRESH_INTERVAL);
+                let Some(tray_icon) = app.tray_by_id(tray::TRAY_ID) else {
+                    last = None;
+                    continue;
+                };
+                let snapshot = tray_snapshot(&app);
+                if last.as_ref() == Some(&snapshot) {
+                    continue;
+                }
+                let (windows, sessions) = &snapshot;
+                let updated = build_tray_menu(&app, windows, sessions).and_then(|menu| {
+                    tray_icon.set_menu(Some(menu))?;
+                    tray_icon.set_tooltip(Some(tray::tooltip(sessions)))
+                });
+                match updated {
+                    Ok(()) => last = Some(snapshot),
+                    Err(e) => tracing::warn!("Failed to refresh tray menu: {}", e),
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        tracing::error!("Failed to spawn tray refresher: {}", e);
+    }
+}
+
+/// Queue a directory from the file manager and bring the main window
+/// forward; the frontend drains the queue and opens a tab per directory.
+#[cfg(feature = "app")]
+pub fn open_directory_in_rain(app: &AppHandle, dir: PathBuf) {
+    tracing::info!("Open Rain here: {}", dir.display());
+    app.state::<AppState>()
+        .pending_open_directories
+        .lock()
+        .push(dir.to_string_lossy().into_owned());
+
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+    if let Err(e) = app.emit_to("main", "open-directory-requested", ()) {
+        tracing::warn!("Failed to emit open-directory request: {}", e);
+    }
+}
+
+/// Queue a validated deep link for the main window, as with "Open Rain here".
+#[cfg(feature = "app")]
+fn queue_deep_link(app: &AppHandle, link: DeepLink) {
+    app.state::<AppState>().pending_deep_links.lock().push(link);
+
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+    if let Err(e) = app.emit_to("main", "deep-link-requested", ()) {
+        tracing::warn!("Failed to emit deep link request: {}", e);
+    }
+}
+
+/// Act on a `rain://` link. Links can come from any web page, so commands
+/// are only queued after the user confirms them in a native dialog.
+#[cfg(feature = "app")]
+fn handle_deep_link(app: &AppHandle, url: &str) {
+    let link = match deep_link::parse(url) {
+        Ok(link) => link,
+        Err(e) => {
+            tracing::warn!("Ignoring deep link {}: {}", url, e);
+            return;
+        }
+    };
+    tracing::info!("Deep link: {:?}", link);
+
+    match link {
+        DeepLink::Open { path } => {
+            if path.is_dir() {
+                open_directory_in_rain(app, path);
+            } else {
+                tracing::warn!("Deep link directory does not exist: {}", path.display());
+            }
+        }
+        DeepLink::Run {
+            ref command,
+            ref cwd,
+        } => {
+            if let Some(cwd) = cwd.as_ref().filter(|cwd| !cwd.is_dir()) {
+                tracing::warn!("Deep link directory does not exist: {}", cwd.display());
+                return;
+            }
+            let location = cwd
+                .as_ref()
+                .map(|cwd| format!("\n\nin {}", cwd.display()))
+                .unwrap_or_default();
+            let mut dialog = rfd::AsyncMessageDialog::new()
+                .set_level(rfd::MessageLevel::Warning)
+                .set_title("Run command from link?")
+                .set_description(format!(
+                    "A link wants to run this command in a new Rain tab:\n\n{}{}\n\nOnly run commands from sources you trust.",
+                    command, location
+                ))
+                .set_buttons(rfd::MessageButtons::OkCancelCustom(
+                    "Run".to_string(),
+                    "Cancel".to_string(),
+                ));
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.show();
+                let _ = window.set_focus();
+                dialog = dialog.set_parent(&window);
+            }
+
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                match dialog.show().await {
+                    rfd::MessageDialogResult::Ok => queue_deep_link(&app, link),
+                    rfd::MessageDialogResult::Custom(label) if label == "Run" => {
+                        queue_deep_link(&app, link)
+                    }
+                    _ => tracing::info!("Deep link command declined"),
+                }
+            });
+        }
+        DeepLink::Tmux { .. } => queue_deep_link(app, link),
+    }
+}
+
+/// Answer a `rain-asset://localhost/<owner>/<token>/<asset>` request from
+/// the asset registry.
+#[cfg(feature = "app")]
+fn serve_asset(
+    app: &AppHandle,
+    request: &tauri::http::Request<Vec<u8>>,
+) -> tauri::http::Response<Vec<u8>> {
+    use tauri::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+
+    let asset = app
+        .state::<AppState>()
+        .assets
+        .lock()
+        .resolve(request.uri().path());
+    let response = match asset {
+        // Asset URLs are never reused for other content.
+        Some(asset) => tauri::http::Response::builder()
+            .header(CONTENT_TYPE, asset.mime)
+            .header(CACHE_CONTROL, "private, max-age=31536000, immutable")
+            .body(asset.bytes.to_vec()),
+        None => tauri::http::Response::builder()
+            .status(404)
+            .header(CACHE_CONTROL, "no-store")
+            .body(Vec::new()),
+    };
+    response.unwrap_or_default()
+}
+
+/// Origins of the app's own pages: the bundled frontend (Windows serves it
+/// from `http://tauri.localhost`) and the dev server.
+#[cfg(feature = "app")]
+const APP_ORIGINS: [&str; 3] = [
+    "tauri://localhost",
+    "http://tauri.localhost",
+    "http://localhost:1420",
+];
+
+/// Answer a `rain-frames://localhost/<session>/<token>?from=<cursor>`
+/// request with the records its frame ring holds from `cursor` on. 404
+/// unless the token is the ring's; 410 Gone when the records were already
+/// overwritten, and the frontend then asks for a full redraw.
+#[cfg(feature = "app")]
+fn serve_frames(
+    app: &AppHandle,
+    request: &tauri::http::Request<Vec<u8>>,
+) -> tauri::http::Response<Vec<u8>> {
+    use tauri::http::header::{ACCESS_CONTROL_ALLOW_ORIGIN, CACHE_CONTROL, CONTENT_TYPE, ORIGIN};
+
+    let uri = request.uri();
+    let read = frame_ring::parse_request(uri.path(), uri.query()).and_then(|wanted| {
+        let ring = app
+            .state::<AppState>()
+            .frame_rings
+            .lock()
+            .get(wanted.session_id)
+            .cloned()?;
+        let ring = ring.lock();
+        ring.accepts(wanted.token)
+            .then(|| ring.read_from(wanted.cursor))
+    });
+    let mut response = tauri::http::Response::builder().header(CACHE_CONTROL, "no-store");
+    // Only the app's own pages may read frames.
+    if let Some(origin) = request
+        .headers()
+        .get(ORIGIN)
+        .filter(|origin| APP_ORIGINS.contains(&origin.to_str().unwrap_or_default()))
+    {
+        response = response.header(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    }
+    let response = match read {
+        Some(frame_ring::RingRead::Frames(bytes)) => response
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(bytes),
+        Some(frame_ring::RingRead::Overrun) => response.status(410).body(Vec::new()),
+        None => response.status(404).body(Vec::new()),
+    };
+    response.unwrap_or_default()
+}
+
+/// A second `rain` launch hands its arguments to this instance and exits.
+#[cfg(feature = "app")]
+fn handle_second_instance(app: &AppHandle, argv: Vec<String>, cwd: String) {
+    match open_here::directory_from_args(&argv, std::path::Path::new(&cwd)) {
+        Some(dir) => open_directory_in_rain(app, dir),
+        None => {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+        }
+    }
+}
+
+/// Native window events the backend follows.
+#[cfg(feature = "app")]
+fn handle_window_event(window: &tauri::Window, event: &tauri::WindowEvent) {
+    match event {
+        tauri::WindowEvent::Focused(focused) => {
+            ipc::commands::window::window_focus_changed(window, Some(*focused));
+        }
+        tauri::WindowEvent::Destroyed => {
+            ipc::commands::window::window_focus_changed(window, None);
+        }
+        _ => {}
+    }
+}
+
+/// App lifecycle events that aren't tied to a window.
+#[cfg(feature = "app")]
+fn handle_run_event(app: &AppHandle, event: tauri::RunEvent) {
+    match event {
+        tauri::RunEvent::Exit => {
+            ipc::commands::session::close_session_journal(app);
+            ipc::commands::session::clear_warm_pool(app);
+            ipc::commands::config::clear_launch_marker(app);
+        }
+        // Finder "Open With" and folders dropped on the Dock icon arrive as
+        // kAEOpenDocuments Apple events.
+        #[cfg(target_os = "macos")]
+        tauri::RunEvent::Opened { urls } => {
+            for url in urls {
+                if let Some(dir) = open_here::directory_from_url(url.as_str()) {
+                    open_directory_in_rain(app, dir);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(feature = "app")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tracing_subscriber::fmt()
@@ -145,7 +625,12 @@ pub fn run() {
 
     tracing::info!("Starting Rain terminal");
 
-    let builder = tauri::Builder::default();
+    // Single-instance must be the first plugin so a second launch exits
+    // before it touches anything else.
+    let builder = tauri::Builder::default()
+        .plugin(tauri_plugin_single_instance::init(handle_second_instance))
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(tauri_plugin_updater::Builder::new().build());
 
     #[cfg(target_os = "macos")]
     let builder = builder.plugin(tauri_plugin_liquid_glass::init());
@@ -153,15 +638,77 @@ pub fn run() {
     builder
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
         .manage(AppState::new())
+        .register_uri_scheme_protocol(assets::SCHEME, |ctx, request| {
+            serve_asset(ctx.app_handle(), &request)
+        })
+        .register_uri_scheme_protocol(frame_ring::SCHEME, |ctx, request| {
+            serve_frames(ctx.app_handle(), &request)
+        })
         .invoke_handler(tauri::generate_handler![
             ipc::commands::session::create_session,
+            ipc::commands::session::attach_frame_channel,
             ipc::commands::session::write_input,
+            ipc::commands::session::write_input_batched,
+            ipc::commands::session::send_mouse_event,
+            ipc::commands::session::paste_text,
             ipc::commands::session::resize_terminal,
             ipc::commands::session::destroy_session,
+            ipc::commands::session::restart_session,
+            ipc::commands::session::recover_session,
+            ipc::commands::session::resolve_new_session_cwd,
+            ipc::commands::session::find_idle_session,
+            ipc::commands::session::list_active_jobs,
+            ipc::commands::session::queue_command,
+            ipc::commands::session::cancel_queued_command,
+            ipc::commands::session::list_queued_commands,
+            ipc::commands::session::inject_remote_hooks,
             ipc::commands::session::get_block_output,
+            ipc::commands::session::get_line_range_output,
+            ipc::commands::session::get_session_io_stats,
+            ipc::commands::session::get_foreground_process,
+            ipc::commands::session::send_signal,
+            ipc::commands::session::session_has_running_children,
+            ipc::commands::session::get_block_text,
+            ipc::commands::session::copy_block_as_markdown,
+            ipc::commands::session::get_block_structured,
+            ipc::commands::session::search_scrollback,
+            ipc::commands::session::get_scrollback_range,
+            ipc::commands::session::set_selection,
+            ipc::commands::session::extend_selection,
+            ipc::commands::session::clear_selection,
+            ipc::commands::session::get_selection_text,
+            ipc::commands::session::search_visible,
+            ipc::commands::session::set_viewport_top,
+            ipc::commands::session::get_current_command_line,
+            ipc::commands::session::get_event_schema,
             ipc::commands::session::request_full_redraw,
+            ipc::commands::session::ack_frame,
+            ipc::commands::session::transfer_text_between_sessions,
+            ipc::commands::session::hibernate_session,
+            ipc::commands::session::set_hibernation_config,
+            ipc::commands::session::set_custom_osc_codes,
+            ipc::commands::session::set_device_attributes,
+            ipc::commands::session::set_shell_args,
+            ipc::commands::session::set_warm_pool_enabled,
+            ipc::commands::session::set_session_read_only,
+            ipc::commands::session::set_session_visible,
+            ipc::commands::session::run_terminal_selftest,
+            ipc::commands::session::set_color_filter,
+            ipc::commands::session::set_min_contrast,
+            ipc::commands::session::set_bold_is_bright,
+            ipc::commands::session::set_ambiguous_width,
+            ipc::commands::session::set_file_downloads,
+            ipc::commands::session::set_scrollback_limit,
+            ipc::commands::share::share_block,
+            ipc::commands::share::export_output,
+            ipc::commands::share::preview_export,
+            ipc::commands::share::save_download,
+            ipc::commands::share::discard_download,
+            ipc::commands::theme::list_themes,
+            ipc::commands::theme::set_theme,
             ipc::commands::window::set_window_blur_radius,
             ipc::commands::window::set_window_opacity,
+            ipc::commands::window::get_compositor_capabilities,
             ipc::commands::window::get_hostname,
             ipc::commands::window::create_child_window,
             ipc::commands::window::create_drag_ghost,
@@ -182,6 +729,7 @@ pub fn run() {
             ipc::commands::transfer::take_session_transfer_state,
             ipc::commands::transfer::stage_tab_transfer_manifest,
             ipc::commands::transfer::take_tab_transfer_manifest,
+            ipc::commands::transfer::extend_tab_transfer,
             ipc::commands::transfer::prepare_tab_transfer_adopt,
             ipc::commands::transfer::release_tab_transfer_adopt,
             ipc::commands::transfer::commit_tab_transfer_adopt,
@@ -192,16 +740,44 @@ pub fn run() {
             ipc::commands::window::quit_app,
             ipc::commands::window::toggle_window_visibility,
             ipc::commands::window::register_global_hotkey,
+            ipc::commands::window::get_global_hotkey_capabilities,
             ipc::commands::config::save_text_to_file,
             ipc::commands::config::get_app_version,
+            ipc::commands::config::get_startup_mode,
+            ipc::commands::config::export_app_state,
+            ipc::commands::config::import_app_state,
+            ipc::commands::credentials::store_secret,
+            ipc::commands::credentials::get_secret,
+            ipc::commands::credentials::delete_secret,
             ipc::commands::filesystem::list_directory,
             ipc::commands::filesystem::scan_project_commands,
             ipc::commands::filesystem::scan_path_commands,
             ipc::commands::filesystem::snoop_path_context,
+            ipc::commands::automation::reset_backend_state,
+            ipc::commands::clipboard::set_primary_selection,
+            ipc::commands::clipboard::paste_primary,
+            ipc::commands::clipboard::record_clipboard_copy,
+            ipc::commands::clipboard::list_clipboard_history,
+            ipc::commands::clipboard::pin_clipboard_entry,
+            ipc::commands::clipboard::recopy_clipboard_entry,
+            ipc::commands::clipboard::remove_clipboard_entry,
+            ipc::commands::clipboard::clear_clipboard_history,
+            ipc::commands::menu::show_terminal_context_menu,
+            ipc::commands::window::set_tray_enabled,
+            ipc::commands::window::take_pending_open_directories,
+            ipc::commands::window::set_explorer_integration,
+            ipc::commands::window::take_pending_deep_links,
+            ipc::commands::window::show_notification,
+            ipc::commands::window::set_window_progress,
+            ipc::commands::window::set_session_focus,
+            ipc::commands::update::check_for_updates,
+            ipc::commands::update::install_update,
+            ipc::commands::update::take_update_changelog,
         ])
         .setup(|app| {
             #[cfg(target_os = "macos")]
             {
+                open_here::services::register(app.handle());
                 if let Some(window) = app.get_webview_window("main") {
                     configure_macos_window(&window);
                 } else {
@@ -225,11 +801,47 @@ pub fn run() {
                 }
             }
 
+            app.on_menu_event(ipc::commands::menu::handle_menu_event);
+
+            // Links opened while Rain runs (second launches are forwarded
+            // here by the single-instance plugin), then the launch link.
+            let handle = app.handle().clone();
+            app.deep_link().on_open_url(move |event| {
+                for url in event.urls() {
+                    handle_deep_link(&handle, url.as_str());
+                }
+            });
+            #[cfg(not(target_os = "macos"))]
+            if let Err(e) = app.deep_link().register_all() {
+                tracing::warn!("Failed to register rain:// links: {}", e);
+            }
+            if let Ok(Some(urls)) = app.deep_link().get_current() {
+                for url in urls {
+                    handle_deep_link(app.handle(), url.as_str());
+                }
+            }
+
+            ipc::commands::config::record_launch(app.handle());
+            ipc::commands::session::spawn_hibernation_sweeper(app.handle().clone());
+            ipc::commands::session::spawn_session_journal(app.handle().clone());
+            ipc::commands::session::spawn_jobs_monitor(app.handle().clone());
+            ipc::commands::session::spawn_parser_watchdog(app.handle().clone());
+            ipc::commands::filesystem::spawn_path_cache_warmup(app.handle().clone());
+            spawn_tray_refresher(app.handle().clone());
+
+            let args: Vec<String> = std::env::args().collect();
+            let cwd = std::env::current_dir().unwrap_or_default();
+            if let Some(dir) = open_here::directory_from_args(&args, &cwd) {
+                open_directory_in_rain(app.handle(), dir);
+            }
+
             // Don't apply vibrancy by default. The frontend controls this
             // based on user appearance preferences.
             tracing::info!("Rain setup complete. Waiting for frontend to create session.");
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("Failed to run Rain");
+        .on_window_event(handle_window_event)
+        .build(tauri::generate_context!())
+        .expect("Failed to build Rain")
+        .run(handle_run_event);
 }
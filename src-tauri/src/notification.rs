@@ -0,0 +1,119 @@
+//! Desktop notifications requested by programs in a session (OSC 9 and
+//! OSC 777), shown through the platform's own notifier.
+//!
+//! - macOS: Notification Center via `osascript`
+//! - Linux/BSD: the freedesktop notification daemon via `notify-send`
+//! - Windows: no notifier; the caller flashes the taskbar instead
+//!
+//! Only `notify-send` reports clicks back, so `on_click` never runs on macOS.
+
+/// Show a notification. `on_click` runs (on a background thread) if the user
+/// clicks it.
+pub fn show(
+    title: &str,
+    body: &str,
+    on_click: impl FnOnce() + Send + 'static,
+) -> Result<(), String> {
+    platform::show(title, body, on_click)
+}
+
+/// `text` as an AppleScript string literal.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::process::{Command, Stdio};
+
+    use super::applescript_string;
+
+    pub fn show(title: &str, body: &str, _on_click: impl FnOnce()) -> Result<(), String> {
+        let script = format!(
+            "display notification {} with title {}",
+            applescript_string(body),
+            applescript_string(title)
+        );
+        Command::new("osascript")
+            .args(["-e", &script])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to run osascript: {}", e))
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    pub fn show(_title: &str, _body: &str, _on_click: impl FnOnce()) -> Result<(), String> {
+        Err("Desktop notifications are not supported on Windows".to_string())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod platform {
+    use std::process::{Command, Stdio};
+
+    fn notify_send(
+        title: &str,
+        body: &str,
+        with_action: bool,
+    ) -> std::io::Result<std::process::Child> {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name", "Rain"]);
+        if with_action {
+            // notify-send prints the action's name and exits when clicked.
+            command.args(["--action", "default=Show", "--wait"]);
+        }
+        command
+            .args(["--", title, body])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+    }
+
+    pub fn show(
+        title: &str,
+        body: &str,
+        on_click: impl FnOnce() + Send + 'static,
+    ) -> Result<(), String> {
+        let child = notify_send(title, body, true).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                "notify-send not found; install libnotify to get notifications".to_string()
+            } else {
+                format!("Failed to run notify-send: {}", e)
+            }
+        })?;
+        let (title, body) = (title.to_string(), body.to_string());
+        std::thread::spawn(move || match child.wait_with_output() {
+            Ok(output) if output.status.success() => {
+                if String::from_utf8_lossy(&output.stdout).trim() == "default" {
+                    on_click();
+                }
+            }
+            // notify-send before libnotify 0.7.10 has no --action.
+            _ => {
+                if let Err(e) = notify_send(&title, &body, false) {
+                    tracing::warn!("Failed to run notify-send: {}", e);
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applescript_strings_escape_quotes_and_backslashes() {
+        assert_eq!(applescript_string("done"), "\"done\"");
+        assert_eq!(
+            applescript_string(r#"say "hi" \o/"#),
+            r#""say \"hi\" \\o/""#
+        );
+    }
+}
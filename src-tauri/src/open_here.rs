@@ -0,0 +1,274 @@
+//! "Open Rain here" from the system file manager.
+//!
+//! Every entry point boils down to a directory that the main window opens a
+//! new tab in:
+//! - Second launches (`rain --open-dir <dir>` or a bare directory argument)
+//!   reach the running instance through the single-instance plugin.
+//! - macOS: the "New Rain Tab Here" Service declared in `Info.plist`, and
+//!   folders opened with Rain from Finder (`kAEOpenDocuments`).
+//! - Windows: an Explorer context-menu verb under `HKCU\Software\Classes`,
+//!   installed on request because per-user installs have no shell hook.
+
+use std::path::{Path, PathBuf};
+
+/// Command-line flag the file manager integrations launch Rain with.
+pub const OPEN_DIR_FLAG: &str = "--open-dir";
+
+/// Resolve `path` to a directory; files resolve to their parent.
+fn resolve_directory(path: PathBuf) -> Option<PathBuf> {
+    if path.is_dir() {
+        Some(path)
+    } else if path.is_file() {
+        path.parent().map(Path::to_path_buf)
+    } else {
+        None
+    }
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Directory named by a `file://` URL (Apple events and pasteboards use these).
+pub fn directory_from_url(url: &str) -> Option<PathBuf> {
+    let rest = url.strip_prefix("file://")?;
+    // Only local files: `file:///path` or `file://localhost/path`.
+    let path = rest.strip_prefix("localhost").unwrap_or(rest);
+    if !path.starts_with('/') {
+        return None;
+    }
+    resolve_directory(PathBuf::from(percent_decode(path)))
+}
+
+/// Directory requested on a command line, relative paths resolved against
+/// `cwd` (the launching process's directory). `args[0]` is the executable.
+pub fn directory_from_args(args: &[String], cwd: &Path) -> Option<PathBuf> {
+    let mut requested = None;
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        if arg == OPEN_DIR_FLAG {
+            requested = iter.next().cloned();
+        } else if let Some(value) = arg.strip_prefix("--open-dir=") {
+            requested = Some(value.to_string());
+        } else if !arg.starts_with('-') && requested.is_none() {
+            requested = Some(arg.clone());
+        }
+    }
+
+    let requested = requested?;
+    if requested.starts_with("file://") {
+        return directory_from_url(&requested);
+    }
+    let path = PathBuf::from(requested);
+    let path = if path.is_absolute() {
+        path
+    } else {
+        cwd.join(path)
+    };
+    resolve_directory(path)
+}
+
+/// Add or remove the "Open Rain here" verb on folders and folder backgrounds.
+#[cfg(target_os = "windows")]
+pub fn set_explorer_integration(enabled: bool) -> Result<(), String> {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    // Folder items pass their path as %1; the folder background as %V.
+    const VERBS: [(&str, &str); 2] = [
+        (r"HKCU\Software\Classes\Directory\shell\Rain", "%1"),
+        (
+            r"HKCU\Software\Classes\Directory\Background\shell\Rain",
+            "%V",
+        ),
+    ];
+
+    fn reg(args: &[&str]) -> Result<std::process::Output, String> {
+        Command::new("reg")
+            .args(args)
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .map_err(|e| format!("Failed to run reg.exe: {}", e))
+    }
+
+    let exe =
+        std::env::current_exe().map_err(|e| format!("Failed to locate Rain executable: {}", e))?;
+    let exe = exe.to_string_lossy();
+
+    for (key, placeholder) in VERBS {
+        if !enabled {
+            // Deleting a key that was never added is fine.
+            reg(&["delete", key, "/f"])?;
+            continue;
+        }
+        let command_key = format!(r"{}\command", key);
+        let command = format!("\"{}\" {} \"{}\"", exe, OPEN_DIR_FLAG, placeholder);
+        for args in [
+            vec!["add", key, "/ve", "/d", "Open Rain here", "/f"],
+            vec!["add", key, "/v", "Icon", "/d", &exe, "/f"],
+            vec!["add", &command_key, "/ve", "/d", &command, "/f"],
+        ] {
+            let output = reg(&args)?;
+            if !output.status.success() {
+                return Err(format!(
+                    "reg add {} failed: {}",
+                    key,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn set_explorer_integration(_enabled: bool) -> Result<(), String> {
+    Err("Explorer integration is only available on Windows".to_string())
+}
+
+/// macOS Services provider backing the `openDirectory` NSServices entry.
+#[cfg(target_os = "macos")]
+pub mod services {
+    use objc2::rc::Retained;
+    use objc2::runtime::{AnyObject, NSObject};
+    use objc2::{define_class, msg_send, DefinedClass, MainThreadMarker, MainThreadOnly};
+    use objc2_app_kit::{NSApplication, NSPasteboard, NSPasteboardTypeFileURL};
+    use objc2_foundation::NSString;
+    use tauri::AppHandle;
+
+    pub struct ProviderIvars {
+        app: AppHandle,
+    }
+
+    define_class!(
+        #[unsafe(super(NSObject))]
+        #[thread_kind = MainThreadOnly]
+        #[name = "RainServicesProvider"]
+        #[ivars = ProviderIvars]
+        struct ServicesProvider;
+
+        impl ServicesProvider {
+            #[unsafe(method(openDirectory:userData:error:))]
+            fn open_directory(
+                &self,
+                pboard: &NSPasteboard,
+                _user_data: Option<&NSString>,
+                _error: *mut *mut NSString,
+            ) {
+                let url = pboard.stringForType(unsafe { NSPasteboardTypeFileURL });
+                match url.and_then(|url| super::directory_from_url(&url.to_string())) {
+                    Some(dir) => crate::open_directory_in_rain(&self.ivars().app, dir),
+                    None => tracing::warn!("Rain service invoked without a folder"),
+                }
+            }
+        }
+    );
+
+    impl ServicesProvider {
+        fn new(mtm: MainThreadMarker, app: AppHandle) -> Retained<Self> {
+            let this = Self::alloc(mtm).set_ivars(ProviderIvars { app });
+            unsafe { msg_send![super(this), init] }
+        }
+    }
+
+    /// Register the provider; must run on the main thread (app setup).
+    pub fn register(app: &AppHandle) {
+        let Some(mtm) = MainThreadMarker::new() else {
+            tracing::warn!("Services provider must be registered on the main thread");
+            return;
+        };
+        let provider = ServicesProvider::new(mtm, app.clone());
+        let provider_object: &AnyObject = &provider;
+        let ns_app = NSApplication::sharedApplication(mtm);
+        unsafe { ns_app.setServicesProvider(Some(provider_object)) };
+        objc2_app_kit::NSUpdateDynamicServices();
+        // NSApp does not retain its services provider.
+        std::mem::forget(provider);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("rain-open-here-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(dir.join("sub dir")).unwrap();
+        std::fs::write(dir.join("file.txt"), "x").unwrap();
+        dir
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        std::iter::once("rain")
+            .chain(list.iter().copied())
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn open_dir_flag_and_bare_paths() {
+        let dir = scratch_dir("args");
+        let sub = dir.join("sub dir");
+        let sub_str = sub.to_string_lossy().into_owned();
+
+        assert_eq!(
+            directory_from_args(&args(&["--open-dir", &sub_str]), &dir),
+            Some(sub.clone())
+        );
+        assert_eq!(
+            directory_from_args(&args(&[&format!("--open-dir={}", sub_str)]), &dir),
+            Some(sub.clone())
+        );
+        assert_eq!(
+            directory_from_args(&args(&["sub dir"]), &dir),
+            Some(sub.clone())
+        );
+        assert_eq!(
+            directory_from_args(&args(&["file.txt"]), &dir),
+            Some(dir.clone())
+        );
+        assert_eq!(directory_from_args(&args(&[]), &dir), None);
+        assert_eq!(directory_from_args(&args(&["--verbose"]), &dir), None);
+        assert_eq!(directory_from_args(&args(&["missing"]), &dir), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_urls_are_decoded() {
+        let dir = scratch_dir("url");
+        let url = format!("file://{}/sub%20dir/", dir.to_string_lossy());
+        assert_eq!(directory_from_url(&url), Some(dir.join("sub dir")));
+        let localhost = format!("file://localhost{}", dir.to_string_lossy());
+        assert_eq!(directory_from_url(&localhost), Some(dir.clone()));
+        assert_eq!(directory_from_url("file://server/share"), None);
+        assert_eq!(directory_from_url("https://example.com"), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn percent_decoding_leaves_malformed_escapes() {
+        assert_eq!(percent_decode("a%20b"), "a b");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+        assert_eq!(percent_decode("%C3%A9"), "é");
+    }
+}
@@ -0,0 +1,460 @@
+//! What runs in a session's PTY, read from the OS: the foreground process
+//! for tab titles and stop buttons, the jobs under the shell for close
+//! confirmations, and the shell's working directory for shells without
+//! integration.
+//!
+//! Linux answers through `/proc`, macOS through libproc and `sysctl`;
+//! elsewhere nothing is reported.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// A process as the OS reports it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProcessInfo {
+    pub pid: i32,
+    /// Executable name, e.g. `vim`.
+    pub name: String,
+    /// Arguments it was started with, program first; empty where the OS
+    /// doesn't say.
+    pub argv: Vec<String>,
+}
+
+/// A session's foreground process.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ForegroundProcess {
+    #[serde(flatten)]
+    pub process: ProcessInfo,
+    /// The foreground job is the session's shell, so nothing is running.
+    pub is_shell: bool,
+}
+
+/// A signal for a session's foreground job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Signal {
+    /// SIGINT, or CTRL_C_EVENT on Windows.
+    Interrupt,
+    /// SIGTERM.
+    Terminate,
+    /// SIGHUP.
+    Hangup,
+    /// SIGKILL.
+    Kill,
+}
+
+impl Signal {
+    #[cfg(unix)]
+    pub fn number(self) -> libc::c_int {
+        match self {
+            Signal::Interrupt => libc::SIGINT,
+            Signal::Terminate => libc::SIGTERM,
+            Signal::Hangup => libc::SIGHUP,
+            Signal::Kill => libc::SIGKILL,
+        }
+    }
+}
+
+/// Details of process `pid`, or `None` once it is gone or where processes
+/// can't be inspected.
+pub fn process_info(pid: i32) -> Option<ProcessInfo> {
+    let argv = process_argv(pid);
+    let name = process_name(pid).or_else(|| {
+        let program = argv.first()?;
+        Some(program.rsplit('/').next().unwrap_or(program).to_string())
+    })?;
+    Some(ProcessInfo { pid, name, argv })
+}
+
+/// Shells that may only wrap what they run (`sh -c`, subshells); the close
+/// confirmation names what runs under them instead.
+const WRAPPER_SHELLS: &[&str] = &["sh", "bash", "zsh", "fish", "dash", "ksh"];
+
+/// Deepest chain of wrapper shells looked through.
+const MAX_TREE_DEPTH: usize = 8;
+
+/// Where a process sits: its parent, job and terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ProcStat {
+    ppid: i32,
+    /// Process group; each job the shell starts gets its own.
+    pgrp: i32,
+    /// Controlling terminal device, 0 for none.
+    tty: i64,
+}
+
+impl ProcStat {
+    /// Whether this process is part of one of `shell`'s jobs: on the same
+    /// terminal, in a process group other than the shell's. Helpers the
+    /// shell or its prompt start, and daemons that left the terminal, are not.
+    fn is_job_of(&self, shell: &ProcStat) -> bool {
+        shell.tty != 0 && self.tty == shell.tty && self.pgrp != shell.pgrp
+    }
+}
+
+/// The jobs the shell `pid` is running, in the foreground or background,
+/// looking through wrapper shells to the commands under them. Empty at an
+/// idle prompt or where the process tree can't be read.
+pub fn running_children(pid: i32) -> Vec<ProcessInfo> {
+    let table = process_table(pid);
+    let Some(shell) = table.get(&pid).copied() else {
+        return Vec::new();
+    };
+    let mut children: HashMap<i32, Vec<i32>> = HashMap::new();
+    for (&child, stat) in &table {
+        children.entry(stat.ppid).or_default().push(child);
+    }
+    let children = |pid: i32| children.get(&pid).cloned().unwrap_or_default();
+    let mut found = Vec::new();
+    collect_running(pid, 0, &children, &process_name, &mut found);
+    found
+        .into_iter()
+        .filter(|child| table.get(child).is_some_and(|stat| stat.is_job_of(&shell)))
+        .filter_map(process_info)
+        .collect()
+}
+
+fn collect_running(
+    pid: i32,
+    depth: usize,
+    children: &dyn Fn(i32) -> Vec<i32>,
+    name: &dyn Fn(i32) -> Option<String>,
+    out: &mut Vec<i32>,
+) {
+    let mut kids = children(pid);
+    kids.sort_unstable();
+    for child in kids {
+        let wrapper = depth < MAX_TREE_DEPTH
+            && name(child).is_some_and(|name| WRAPPER_SHELLS.contains(&name.as_str()));
+        if wrapper && !children(child).is_empty() {
+            collect_running(child, depth + 1, children, name, out);
+        } else {
+            out.push(child);
+        }
+    }
+}
+
+/// Parent, process group and terminal from the contents of `/proc/<pid>/stat`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_stat(stat: &str) -> Option<ProcStat> {
+    // The command name may contain spaces and parentheses; `state`, `ppid`,
+    // `pgrp`, `session` and `tty_nr` follow it.
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().take(5).collect();
+    Some(ProcStat {
+        ppid: fields.get(1)?.parse().ok()?,
+        pgrp: fields.get(2)?.parse().ok()?,
+        tty: fields.get(4)?.parse().ok()?,
+    })
+}
+
+/// Every process, from one pass over `/proc`.
+#[cfg(target_os = "linux")]
+fn process_table(_root: i32) -> HashMap<i32, ProcStat> {
+    let mut table = HashMap::new();
+    for entry in std::fs::read_dir("/proc").into_iter().flatten().flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        else {
+            continue;
+        };
+        let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        if let Some(stat) = parse_stat(&stat) {
+            table.insert(pid, stat);
+        }
+    }
+    table
+}
+
+/// `root` and its descendants.
+#[cfg(target_os = "macos")]
+fn process_table(root: i32) -> HashMap<i32, ProcStat> {
+    let mut table = HashMap::new();
+    let mut pending = vec![root];
+    while let Some(pid) = pending.pop() {
+        if table.contains_key(&pid) {
+            continue;
+        }
+        let Some(stat) = bsd_stat(pid) else {
+            continue;
+        };
+        table.insert(pid, stat);
+        pending.extend(child_pids(pid));
+    }
+    table
+}
+
+#[cfg(target_os = "macos")]
+fn bsd_stat(pid: i32) -> Option<ProcStat> {
+    let mut info = std::mem::MaybeUninit::<libc::proc_bsdinfo>::zeroed();
+    let size = std::mem::size_of::<libc::proc_bsdinfo>() as libc::c_int;
+    // SAFETY: `info` is writable for `size` bytes.
+    let read = unsafe {
+        libc::proc_pidinfo(
+            pid,
+            libc::PROC_PIDTBSDINFO,
+            0,
+            info.as_mut_ptr().cast(),
+            size,
+        )
+    };
+    if read != size {
+        return None;
+    }
+    // SAFETY: proc_pidinfo filled the whole struct.
+    let info = unsafe { info.assume_init() };
+    Some(ProcStat {
+        ppid: info.pbi_ppid as i32,
+        pgrp: info.pbi_pgid as i32,
+        // NODEV (all ones) when there is no controlling terminal.
+        tty: if info.e_tdev == u32::MAX {
+            0
+        } else {
+            i64::from(info.e_tdev)
+        },
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn child_pids(pid: i32) -> Vec<i32> {
+    let mut pids: Vec<libc::pid_t> = vec![0; 256];
+    let size = std::mem::size_of_val(pids.as_slice()) as libc::c_int;
+    // SAFETY: `pids` is writable for `size` bytes.
+    let bytes = unsafe {
+        libc::proc_listpids(
+            libc::PROC_PPID_ONLY,
+            pid as u32,
+            pids.as_mut_ptr().cast(),
+            size,
+        )
+    };
+    let count = usize::try_from(bytes).unwrap_or(0) / std::mem::size_of::<libc::pid_t>();
+    pids.truncate(count);
+    pids.retain(|&pid| pid > 0);
+    pids
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn process_table(_root: i32) -> HashMap<i32, ProcStat> {
+    HashMap::new()
+}
+
+/// Working directory of process `pid`.
+#[cfg(target_os = "linux")]
+pub fn process_cwd(pid: i32) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+}
+
+#[cfg(target_os = "macos")]
+pub fn process_cwd(pid: i32) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut info = std::mem::MaybeUninit::<libc::proc_vnodepathinfo>::zeroed();
+    let size = std::mem::size_of::<libc::proc_vnodepathinfo>() as libc::c_int;
+    // SAFETY: `info` is writable for `size` bytes.
+    let read = unsafe {
+        libc::proc_pidinfo(
+            pid,
+            libc::PROC_PIDVNODEPATHINFO,
+            0,
+            info.as_mut_ptr().cast(),
+            size,
+        )
+    };
+    if read != size {
+        return None;
+    }
+    // SAFETY: proc_pidinfo filled the whole struct.
+    let info = unsafe { info.assume_init() };
+    // The path is a NUL-terminated C string, declared as nested arrays.
+    let path = &info.pvi_cdir.vip_path;
+    // SAFETY: the arrays are contiguous `c_char`s of the given total size.
+    let bytes = unsafe {
+        std::slice::from_raw_parts(path.as_ptr().cast::<u8>(), std::mem::size_of_val(path))
+    };
+    let len = bytes.iter().position(|&b| b == 0)?;
+    (len > 0).then(|| PathBuf::from(std::ffi::OsStr::from_bytes(&bytes[..len])))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn process_cwd(_pid: i32) -> Option<PathBuf> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn process_name(pid: i32) -> Option<String> {
+    let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    Some(comm.trim_end_matches('\n').to_string()).filter(|name| !name.is_empty())
+}
+
+#[cfg(target_os = "linux")]
+fn process_argv(pid: i32) -> Vec<String> {
+    std::fs::read(format!("/proc/{}/cmdline", pid))
+        .map(|cmdline| split_nul(&cmdline))
+        .unwrap_or_default()
+}
+
+#[cfg(target_os = "macos")]
+fn process_name(pid: i32) -> Option<String> {
+    let mut buf = [0u8; 256];
+    // SAFETY: `buf` is writable for the length passed.
+    let len = unsafe { libc::proc_name(pid, buf.as_mut_ptr().cast(), buf.len() as u32) };
+    (len > 0).then(|| String::from_utf8_lossy(&buf[..len as usize]).into_owned())
+}
+
+#[cfg(target_os = "macos")]
+fn process_argv(pid: i32) -> Vec<String> {
+    let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid];
+    let mut size: libc::size_t = 0;
+    // SAFETY: a null buffer asks sysctl for the size only.
+    let sized = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            3,
+            std::ptr::null_mut(),
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if sized != 0 || size == 0 {
+        return Vec::new();
+    }
+    let mut buf = vec![0u8; size];
+    // SAFETY: `buf` is writable for `size` bytes.
+    let read = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            3,
+            buf.as_mut_ptr().cast(),
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if read != 0 {
+        return Vec::new();
+    }
+    buf.truncate(size);
+    parse_procargs2(&buf)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn process_name(_pid: i32) -> Option<String> {
+    None
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn process_argv(_pid: i32) -> Vec<String> {
+    Vec::new()
+}
+
+/// Strings of a NUL-separated list such as `/proc/<pid>/cmdline`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn split_nul(data: &[u8]) -> Vec<String> {
+    data.split(|&b| b == 0)
+        .filter(|part| !part.is_empty())
+        .map(|part| String::from_utf8_lossy(part).into_owned())
+        .collect()
+}
+
+/// Arguments from a macOS `KERN_PROCARGS2` buffer: `argc`, the executable
+/// path, NUL padding, then `argc` NUL-terminated arguments (and the
+/// environment, which is skipped).
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_procargs2(data: &[u8]) -> Vec<String> {
+    let Some(argc) = data.get(..4).and_then(|b| b.try_into().ok()) else {
+        return Vec::new();
+    };
+    let argc = usize::try_from(i32::from_ne_bytes(argc)).unwrap_or(0);
+    let rest = &data[4..];
+    let Some(path_end) = rest.iter().position(|&b| b == 0) else {
+        return Vec::new();
+    };
+    let args = &rest[path_end..];
+    let start = args.iter().position(|&b| b != 0).unwrap_or(args.len());
+    args[start..]
+        .split(|&b| b == 0)
+        .take(argc)
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_argument_lists() {
+        assert_eq!(split_nul(b"vim\0-O\0a.rs\0"), ["vim", "-O", "a.rs"]);
+        assert!(split_nul(b"").is_empty());
+
+        let mut procargs = 2i32.to_ne_bytes().to_vec();
+        procargs.extend_from_slice(b"/usr/bin/vim\0\0\0\0vim\0a.rs\0HOME=/Users/me\0");
+        assert_eq!(parse_procargs2(&procargs), ["vim", "a.rs"]);
+        assert!(parse_procargs2(&[1, 0]).is_empty());
+    }
+
+    #[test]
+    fn looks_through_wrapper_shells() {
+        // 1 is the session's shell: `ssh` runs directly, `sh -c` wraps
+        // `npm`, and an idle subshell is reported as itself.
+        let children = |pid: i32| match pid {
+            1 => vec![4, 2, 3],
+            3 => vec![5],
+            _ => Vec::new(),
+        };
+        let name = |pid: i32| {
+            let name = match pid {
+                2 => "ssh",
+                3 => "sh",
+                4 => "bash",
+                5 => "npm",
+                _ => return None,
+            };
+            Some(name.to_string())
+        };
+        let mut found = Vec::new();
+        collect_running(1, 0, &children, &name, &mut found);
+        assert_eq!(found, [2, 5, 4]);
+    }
+
+    #[test]
+    fn only_jobs_on_the_shells_terminal_count() {
+        let stat = "42 (my (odd) cmd) S 7 40 40 34817 -1";
+        let job = parse_stat(stat).unwrap();
+        assert_eq!(
+            job,
+            ProcStat {
+                ppid: 7,
+                pgrp: 40,
+                tty: 34817
+            }
+        );
+        let shell = ProcStat {
+            ppid: 1,
+            pgrp: 7,
+            tty: 34817,
+        };
+        assert!(job.is_job_of(&shell));
+        // The shell's own helpers, and daemons without the terminal.
+        assert!(!ProcStat { pgrp: 7, ..job }.is_job_of(&shell));
+        assert!(!ProcStat { tty: 0, ..job }.is_job_of(&shell));
+        assert!(!job.is_job_of(&ProcStat { tty: 0, ..shell }));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn describes_this_process() {
+        let info = process_info(std::process::id() as i32).unwrap();
+        assert!(!info.name.is_empty());
+        assert!(!info.argv.is_empty());
+        assert_eq!(process_cwd(info.pid), std::env::current_dir().ok());
+    }
+}
@@ -0,0 +1,215 @@
+//! Ordered user input for a session's PTY.
+//!
+//! Keystrokes, pastes, replayed input and queued commands all go through one
+//! [`InputQueue`], so the shell sees them in the order they were made: a key
+//! pressed while a large paste is still being written lands after the paste,
+//! not in the middle of it. Terminal query replies bypass the queue and use
+//! the shared writer directly; they answer the program, not the user.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use super::session::SharedWriter;
+
+/// Largest single PTY write. ConPTY stalls on very large writes, so bigger
+/// input goes out in pieces of this size.
+pub const MAX_INPUT_FLUSH: usize = 16 * 1024;
+
+/// Input larger than this is written from a background thread, one
+/// [`MAX_INPUT_FLUSH`] piece at a time. A program that reads slowly then
+/// blocks neither the caller nor, for longer than one piece, query replies.
+pub const BACKGROUND_WRITE: usize = 64 * 1024;
+
+#[derive(Default)]
+struct Backlog {
+    /// Pieces not yet written, oldest first.
+    pending: VecDeque<Vec<u8>>,
+    /// Whether a background thread is writing `pending`.
+    draining: bool,
+}
+
+/// One session's input, written to the PTY in order.
+#[derive(Clone)]
+pub struct InputQueue {
+    writer: SharedWriter,
+    backlog: Arc<Mutex<Backlog>>,
+}
+
+impl InputQueue {
+    pub fn new(writer: SharedWriter) -> Self {
+        Self {
+            writer,
+            backlog: Arc::default(),
+        }
+    }
+
+    /// Write `data` after all input queued before it.
+    pub fn write(&self, data: &[u8]) -> io::Result<()> {
+        self.write_batch(&[data.to_vec()])
+    }
+
+    /// Write input chunks after all input queued before them, merged into
+    /// writes of at most [`MAX_INPUT_FLUSH`] bytes. With nothing queued,
+    /// input up to [`BACKGROUND_WRITE`] bytes is written right away and
+    /// reports its errors; anything else joins the backlog and finishes in
+    /// the background, where write errors are only logged.
+    pub fn write_batch(&self, chunks: &[Vec<u8>]) -> io::Result<()> {
+        let pieces = coalesce_input(chunks, MAX_INPUT_FLUSH);
+        if pieces.is_empty() {
+            return Ok(());
+        }
+        let mut backlog = self.backlog.lock();
+        let len: usize = pieces.iter().map(Vec::len).sum();
+        if !backlog.draining && len <= BACKGROUND_WRITE {
+            let mut writer = self.writer.lock();
+            for piece in &pieces {
+                writer.write_all(piece)?;
+                writer.flush()?;
+            }
+            return Ok(());
+        }
+
+        backlog.pending.extend(pieces);
+        if !backlog.draining {
+            backlog.draining = true;
+            let queue = self.clone();
+            std::thread::spawn(move || queue.drain());
+        }
+        Ok(())
+    }
+
+    /// Write the backlog until it is empty. New input keeps queueing behind
+    /// it until then, since `draining` stays set.
+    fn drain(&self) {
+        loop {
+            let Some(piece) = ({
+                let mut backlog = self.backlog.lock();
+                let next = backlog.pending.pop_front();
+                if next.is_none() {
+                    backlog.draining = false;
+                }
+                next
+            }) else {
+                return;
+            };
+            let mut writer = self.writer.lock();
+            if let Err(e) = writer.write_all(&piece).and_then(|_| writer.flush()) {
+                tracing::warn!("Dropping queued input after a write error: {}", e);
+                let mut backlog = self.backlog.lock();
+                backlog.pending.clear();
+                backlog.draining = false;
+                return;
+            }
+        }
+    }
+}
+
+/// Merge input chunks into buffers of at most `cap` bytes, in order. Chunks
+/// larger than `cap` are split; the PTY sees one byte stream either way.
+pub fn coalesce_input(chunks: &[Vec<u8>], cap: usize) -> Vec<Vec<u8>> {
+    let mut out: Vec<Vec<u8>> = Vec::new();
+    let mut current = Vec::new();
+    for chunk in chunks {
+        let mut rest = chunk.as_slice();
+        while !rest.is_empty() {
+            let take = rest.len().min(cap - current.len());
+            current.extend_from_slice(&rest[..take]);
+            rest = &rest[take..];
+            if current.len() == cap {
+                out.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    /// A PTY stand-in that records writes, optionally pausing on each.
+    struct Recorder {
+        written: Arc<Mutex<Vec<u8>>>,
+        delay: Duration,
+    }
+
+    impl Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            std::thread::sleep(self.delay);
+            self.written.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn is_busy(input: &InputQueue) -> bool {
+        input.backlog.lock().draining
+    }
+
+    fn queue(delay: Duration) -> (InputQueue, Arc<Mutex<Vec<u8>>>) {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Recorder {
+            written: Arc::clone(&written),
+            delay,
+        };
+        let writer: SharedWriter = Arc::new(Mutex::new(Box::new(recorder)));
+        (InputQueue::new(writer), written)
+    }
+
+    #[test]
+    fn keys_typed_during_a_large_paste_land_after_it() {
+        let (input, written) = queue(Duration::from_millis(2));
+        let paste = vec![b'p'; BACKGROUND_WRITE * 2];
+        input.write(&paste).unwrap();
+        assert!(is_busy(&input));
+        input.write(b"k").unwrap();
+        input.write(b"\r").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while is_busy(&input) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let written = written.lock();
+        assert_eq!(written.len(), paste.len() + 2);
+        assert!(written[..paste.len()].iter().all(|&b| b == b'p'));
+        assert_eq!(&written[paste.len()..], b"k\r");
+    }
+
+    #[test]
+    fn small_input_is_written_right_away() {
+        let (input, written) = queue(Duration::ZERO);
+        input
+            .write_batch(&[b"ls".to_vec(), Vec::new(), b"\r".to_vec()])
+            .unwrap();
+        assert!(!is_busy(&input));
+        assert_eq!(written.lock().as_slice(), b"ls\r");
+    }
+
+    #[test]
+    fn coalesce_merges_small_chunks_and_splits_large_ones() {
+        let keys: Vec<Vec<u8>> = b"hello".iter().map(|b| vec![*b]).collect();
+        assert_eq!(coalesce_input(&keys, 16), vec![b"hello".to_vec()]);
+
+        let chunks = vec![
+            b"abc".to_vec(),
+            Vec::new(),
+            b"defghij".to_vec(),
+            b"k".to_vec(),
+        ];
+        assert_eq!(
+            coalesce_input(&chunks, 4),
+            vec![b"abcd".to_vec(), b"efgh".to_vec(), b"ijk".to_vec()]
+        );
+        assert!(coalesce_input(&[Vec::new()], 4).is_empty());
+    }
+}
@@ -0,0 +1,175 @@
+//! Bytes moved through a session's PTY.
+//!
+//! The parser thread counts what it reads and every write to the PTY goes
+//! through a [`CountingWriter`], so keyboard input, pastes and terminal query
+//! replies are all included. The render pump samples the counters and emits
+//! `session-io` at most once per [`IO_EVENT_INTERVAL`] while they move (plus
+//! once more when they stop), which is enough for an activity indicator.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// Shortest gap between two `session-io` events for one session.
+pub const IO_EVENT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default)]
+pub struct IoCounters {
+    read: AtomicU64,
+    written: AtomicU64,
+    sampler: Mutex<IoSampler>,
+}
+
+impl IoCounters {
+    pub fn add_read(&self, bytes: usize) {
+        self.read.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_written(&self, bytes: usize) {
+        self.written.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn totals(&self) -> (u64, u64) {
+        (
+            self.read.load(Ordering::Relaxed),
+            self.written.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Stats for a `session-io` event, when one is due.
+    pub fn poll(&self, now: Instant) -> Option<IoStats> {
+        self.sampler.lock().poll(self.totals(), now)
+    }
+
+    /// Current totals with the rates of the last sample.
+    pub fn stats(&self) -> IoStats {
+        self.sampler.lock().current(self.totals())
+    }
+}
+
+/// Counts bytes written through the wrapped PTY writer.
+pub struct CountingWriter {
+    inner: Box<dyn Write + Send>,
+    counters: Arc<IoCounters>,
+}
+
+impl CountingWriter {
+    pub fn new(inner: Box<dyn Write + Send>, counters: Arc<IoCounters>) -> Self {
+        Self { inner, counters }
+    }
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.counters.add_written(written);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Totals and rates for one session, as returned by `get_session_io_stats`
+/// and carried by the `session-io` event.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IoStats {
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Bytes per second over the last sampling interval.
+    pub read_rate: f64,
+    pub write_rate: f64,
+}
+
+/// Turns counter totals into rates and decides when an event is due.
+#[derive(Debug, Default)]
+pub struct IoSampler {
+    last: Option<((u64, u64), Instant)>,
+    last_stats: IoStats,
+}
+
+impl IoSampler {
+    /// Stats for `totals` if the previous sample is at least
+    /// [`IO_EVENT_INTERVAL`] old and something changed since it: new bytes,
+    /// or rates that should drop back to zero.
+    pub fn poll(&mut self, totals: (u64, u64), now: Instant) -> Option<IoStats> {
+        let Some((prev, at)) = self.last else {
+            self.last = Some((totals, now));
+            return None;
+        };
+        let elapsed = now.saturating_duration_since(at);
+        if elapsed < IO_EVENT_INTERVAL {
+            return None;
+        }
+        let idle = totals == prev;
+        if idle && self.last_stats.read_rate == 0.0 && self.last_stats.write_rate == 0.0 {
+            // Restart the interval so the first rate after a pause isn't
+            // averaged over the whole pause.
+            self.last = Some((totals, now));
+            return None;
+        }
+        let seconds = elapsed.as_secs_f64();
+        let stats = IoStats {
+            bytes_read: totals.0,
+            bytes_written: totals.1,
+            read_rate: totals.0.saturating_sub(prev.0) as f64 / seconds,
+            write_rate: totals.1.saturating_sub(prev.1) as f64 / seconds,
+        };
+        self.last = Some((totals, now));
+        self.last_stats = stats;
+        Some(stats)
+    }
+
+    /// The most recent sample, with current totals.
+    pub fn current(&self, totals: (u64, u64)) -> IoStats {
+        IoStats {
+            bytes_read: totals.0,
+            bytes_written: totals.1,
+            ..self.last_stats
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counting_writer_counts_accepted_bytes() {
+        let counters = Arc::new(IoCounters::default());
+        let mut writer = CountingWriter::new(Box::new(Vec::new()), Arc::clone(&counters));
+        writer.write_all(b"ls -la\r").unwrap();
+        counters.add_read(4096);
+        assert_eq!(counters.totals(), (4096, 7));
+    }
+
+    #[test]
+    fn sampler_is_rate_limited_and_reports_the_stop() {
+        let start = Instant::now();
+        let mut sampler = IoSampler::default();
+        assert_eq!(sampler.poll((0, 0), start), None);
+        assert_eq!(sampler.poll((500, 0), start + IO_EVENT_INTERVAL / 2), None);
+
+        let busy = sampler
+            .poll((2000, 10), start + IO_EVENT_INTERVAL * 2)
+            .unwrap();
+        assert_eq!(busy.bytes_read, 2000);
+        assert_eq!(busy.read_rate, 1000.0);
+        assert_eq!(busy.write_rate, 5.0);
+
+        let stopped = sampler
+            .poll((2000, 10), start + IO_EVENT_INTERVAL * 3)
+            .unwrap();
+        assert_eq!((stopped.read_rate, stopped.write_rate), (0.0, 0.0));
+        assert_eq!(
+            sampler.poll((2000, 10), start + IO_EVENT_INTERVAL * 9),
+            None
+        );
+    }
+}
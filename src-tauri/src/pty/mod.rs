@@ -1,33 +1,61 @@
+pub mod input;
+pub mod io_stats;
 pub mod reader;
 pub mod session;
+pub mod warm;
+pub mod watchdog;
 
 pub use session::Session;
 
+use parking_lot::Mutex;
 use portable_pty::{native_pty_system, CommandBuilder, PtySize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use uuid::Uuid;
 
-use crate::shell::{detect::detect_shell, hooks::shell_init_command};
+use crate::shell::{
+    args::{default_login, login_flag, login_shell_command, ShellArgs},
+    detect::detect_shell,
+    hooks::shell_init_command,
+};
 
 /// Result of spawning a session: the session itself plus the reader handle
 /// which must be passed to the reader thread.
 pub struct SpawnResult {
     pub session: Session,
     pub reader: Box<dyn std::io::Read + Send>,
+    /// Path of the shell that was started, or the program of a command.
+    pub shell: String,
 }
 
 /// Manages PTY creation and shell spawning.
-/// Stateless: creates a new PtySystem for each spawn to avoid Sync issues.
-pub struct PtyManager;
+/// Creates a new PtySystem for each spawn to avoid Sync issues; the only
+/// state kept is the configured shell arguments.
+pub struct PtyManager {
+    shell_args: Mutex<ShellArgs>,
+}
 
 impl PtyManager {
     pub fn new() -> Self {
-        Self
+        Self {
+            shell_args: Mutex::new(ShellArgs::default()),
+        }
+    }
+
+    /// Replace the shell argument templates used by later spawns. Returns
+    /// whether they changed.
+    pub fn set_shell_args(&self, args: ShellArgs) -> bool {
+        let mut current = self.shell_args.lock();
+        let changed = *current != args;
+        *current = args;
+        changed
     }
 
     /// Spawn a new terminal session with the given shell and dimensions.
+    /// With a `command` (program first) the session runs just that instead
+    /// of a shell, started through the login shell, and ends when it exits.
+    #[allow(clippy::too_many_arguments)]
     pub fn spawn_session(
         &self,
         shell_path: Option<&str>,
@@ -36,18 +64,14 @@ impl PtyManager {
         cols: u16,
         env: Option<&HashMap<String, String>>,
         tmux_mode: Option<&str>,
+        command: Option<&[String]>,
     ) -> Result<SpawnResult, Box<dyn std::error::Error + Send + Sync>> {
         let pty_system = native_pty_system();
-        let shell = match shell_path {
-            Some(p) if std::path::Path::new(p).exists() => p.to_string(),
-            Some(p) => {
-                tracing::warn!(
-                    "Configured shell '{}' not found; falling back to default",
-                    p
-                );
-                detect_shell()
-            }
-            None => detect_shell(),
+        let command = command.filter(|argv| !argv.is_empty());
+        let shell = resolve_shell(shell_path);
+        let program = match command {
+            Some(argv) => login_shell_command(&shell, argv),
+            None => (shell.clone(), Vec::new()),
         };
 
         let pair = pty_system.openpty(PtySize {
@@ -57,7 +81,7 @@ impl PtyManager {
             pixel_height: 0,
         })?;
 
-        let mut cmd = CommandBuilder::new(&shell);
+        let mut cmd = CommandBuilder::new(&program.0);
 
         if let Some(dir) = cwd {
             cmd.cwd(dir);
@@ -96,16 +120,14 @@ impl PtyManager {
         };
         cmd.env("RAIN_TMUX_MODE", tmux_mode);
 
-        let shell_name = crate::shell::detect::shell_name(&shell);
-        let mut temp_dir: Option<PathBuf> = None;
-        if let Some(init_cmd) = shell_init_command(shell_name) {
-            cmd.env("RAIN_SHELL_INIT", &init_cmd);
-            temp_dir = apply_shell_init(&mut cmd, shell_name, &init_cmd)?;
-        } else {
-            // Login shell flag is Unix-specific; Windows shells don't support it
-            #[cfg(unix)]
-            cmd.arg("--login");
-        }
+        // A command runs without the user's shell arguments or integration.
+        let temp_dir = match command {
+            Some(_) => {
+                cmd.args(&program.1);
+                None
+            }
+            None => self.add_shell_args(&mut cmd, &shell)?,
+        };
 
         let child = pair.slave.spawn_command(cmd)?;
         let reader = pair.master.try_clone_reader()?;
@@ -116,7 +138,66 @@ impl PtyManager {
             session.set_temp_dir(dir);
         }
 
-        Ok(SpawnResult { session, reader })
+        Ok(SpawnResult {
+            session,
+            reader,
+            shell: command.map_or(shell, |argv| argv[0].clone()),
+        })
+    }
+
+    /// Add the login flag, the user's arguments and shell integration for
+    /// `shell` to `cmd`. Returns the temp directory holding init files.
+    fn add_shell_args(
+        &self,
+        cmd: &mut CommandBuilder,
+        shell: &str,
+    ) -> Result<Option<PathBuf>, Box<dyn std::error::Error + Send + Sync>> {
+        let shell_name = crate::shell::detect::shell_name(shell);
+        let init_cmd = shell_init_command(shell_name);
+        let integrated = init_cmd.is_some();
+
+        // Login flag first (PowerShell requires -Login to lead), then the
+        // user's arguments, then the integration arguments.
+        let template = self
+            .shell_args
+            .lock()
+            .for_shell(shell_name, std::env::consts::OS)
+            .cloned();
+        let login = template
+            .as_ref()
+            .and_then(|t| t.login)
+            .unwrap_or_else(|| default_login(shell_name, integrated));
+        if login {
+            if let Some(flag) = login_flag(shell_name, integrated) {
+                cmd.arg(flag);
+            }
+        }
+        if let Some(template) = &template {
+            cmd.args(&template.args);
+        }
+
+        let mut temp_dir: Option<PathBuf> = None;
+        if let Some(init_cmd) = init_cmd {
+            cmd.env("RAIN_SHELL_INIT", &init_cmd);
+            temp_dir = apply_shell_init(cmd, shell_name, &init_cmd)?;
+        }
+        Ok(temp_dir)
+    }
+}
+
+/// The shell `spawn_session` starts for `shell_path`: the path itself when
+/// it exists, the detected default otherwise.
+pub fn resolve_shell(shell_path: Option<&str>) -> String {
+    match shell_path {
+        Some(p) if std::path::Path::new(p).exists() => p.to_string(),
+        Some(p) => {
+            tracing::warn!(
+                "Configured shell '{}' not found; falling back to default",
+                p
+            );
+            detect_shell()
+        }
+        None => detect_shell(),
     }
 }
 
@@ -130,27 +211,7 @@ fn apply_shell_init(
     match shell_name {
         "zsh" => {
             let dir = create_shell_init_dir("zsh")?;
-            let zshrc = r#"
-if [ -n "$RAIN_ORIG_ZDOTDIR" ] && [ -f "$RAIN_ORIG_ZDOTDIR/.zshrc" ]; then
-  source "$RAIN_ORIG_ZDOTDIR/.zshrc"
-elif [ -f "$HOME/.zshrc" ]; then
-  source "$HOME/.zshrc"
-fi
-
-if [ -n "$RAIN_SHELL_INIT" ]; then
-  eval "$RAIN_SHELL_INIT"
-fi
-"#;
-            let zprofile = r#"
-if [ -n "$RAIN_ORIG_ZDOTDIR" ] && [ -f "$RAIN_ORIG_ZDOTDIR/.zprofile" ]; then
-  source "$RAIN_ORIG_ZDOTDIR/.zprofile"
-elif [ -f "$HOME/.zprofile" ]; then
-  source "$HOME/.zprofile"
-fi
-"#;
-
-            fs::write(dir.join(".zshrc"), zshrc)?;
-            fs::write(dir.join(".zprofile"), zprofile)?;
+            crate::shell::zdotdir::write_wrapper(&dir)?;
 
             if let Ok(orig) = std::env::var("ZDOTDIR") {
                 if !orig.is_empty() {
@@ -158,7 +219,6 @@ fi
                 }
             }
             cmd.env("ZDOTDIR", dir.clone());
-            cmd.arg("--login");
             Ok(Some(dir))
         }
         "bash" => {
@@ -205,11 +265,7 @@ fi
             // /D disables AutoRun registry commands for a clean start.
             Ok(None)
         }
-        _ => {
-            #[cfg(unix)]
-            cmd.arg("--login");
-            Ok(None)
-        }
+        _ => Ok(None),
     }
 }
 
@@ -1,17 +1,24 @@
 use std::io::{Read, Write};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::mpsc::{sync_channel, RecvTimeoutError, SyncSender};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use parking_lot::Mutex;
-use tauri::AppHandle;
-use tauri::Emitter;
+#[cfg(feature = "app")]
+use tauri::ipc::InvokeResponseBody;
+#[cfg(feature = "app")]
+use tauri::{AppHandle, Emitter, Manager};
 
+#[cfg(feature = "app")]
+use crate::ipc::AppState;
 use crate::render::RenderFrame;
 use crate::terminal::TerminalState;
 
+use super::input::InputQueue;
+use super::io_stats::{IoCounters, IoStats};
 use super::session::{SharedChild, SharedExitCode, SharedWriter};
+use super::watchdog::{ParserHealth, ThreadWatch};
 
 /// Payload sent to the frontend for each render frame.
 #[derive(serde::Serialize, Clone)]
@@ -20,11 +27,165 @@ pub struct RenderFramePayload {
     pub frame: RenderFrame,
 }
 
+/// Where a session's render pump sends what it produces: the webview,
+/// through the app's `AppHandle`, or `rain-headless`.
+pub trait FrameSink: Send + 'static {
+    fn render_frame(&self, payload: &RenderFramePayload);
+    /// Sent once, after the session's last frame.
+    fn session_ended(&self, payload: &SessionEndPayload);
+    /// Rate-limited PTY transfer totals.
+    fn session_io(&self, _payload: &SessionIoPayload) {}
+}
+
+#[cfg(feature = "app")]
+impl FrameSink for AppHandle {
+    fn render_frame(&self, payload: &RenderFramePayload) {
+        emit_render_frame(self, payload);
+    }
+
+    fn session_ended(&self, payload: &SessionEndPayload) {
+        emit_session_ended(self, payload);
+    }
+
+    fn session_io(&self, payload: &SessionIoPayload) {
+        let _ = self.emit("session-io", payload);
+    }
+}
+
+/// What a session's frame ring or channel carries, in order: its render frames and,
+/// last, its end. Sending the end over the channel too keeps it from
+/// overtaking frames still on their way.
+#[cfg(feature = "app")]
+#[derive(serde::Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum ChannelMessage<'a> {
+    RenderFrame(&'a RenderFramePayload),
+    SessionEnded(&'a SessionEndPayload),
+}
+
+/// Send `message` as MessagePack over the frame channel of `session_id`.
+/// False when the session has no channel or sending failed.
+#[cfg(feature = "app")]
+fn send_on_frame_channel(app: &AppHandle, session_id: &str, message: &ChannelMessage) -> bool {
+    let channel = app
+        .state::<AppState>()
+        .frame_channels
+        .lock()
+        .get(session_id)
+        .cloned();
+    let Some(channel) = channel else {
+        return false;
+    };
+    match rmp_serde::to_vec_named(message) {
+        Ok(bytes) => match channel.send(InvokeResponseBody::Raw(bytes)) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("Failed to send on frame channel: {}", e);
+                false
+            }
+        },
+        Err(e) => {
+            tracing::warn!("Failed to encode frame channel message: {}", e);
+            false
+        }
+    }
+}
+
+/// Payload of `frame-ready`: the frame ring at `url` holds frames up to
+/// `head`.
+#[cfg(feature = "app")]
+#[derive(serde::Serialize, Clone)]
+pub struct FrameReadyPayload {
+    pub session_id: String,
+    pub url: String,
+    pub head: u64,
+}
+
+/// Append `message`, as MessagePack, to the frame ring of `session_id` and
+/// announce the new head. False when the session has no ring or the message
+/// doesn't fit in one.
+#[cfg(feature = "app")]
+fn push_to_frame_ring(app: &AppHandle, session_id: &str, message: &ChannelMessage) -> bool {
+    let ring = app
+        .state::<AppState>()
+        .frame_rings
+        .lock()
+        .get(session_id)
+        .cloned();
+    let Some(ring) = ring else {
+        return false;
+    };
+    let bytes = match rmp_serde::to_vec_named(message) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!("Failed to encode frame ring message: {}", e);
+            return false;
+        }
+    };
+    let payload = {
+        let mut ring = ring.lock();
+        if !ring.push(&bytes) {
+            tracing::warn!("{} byte frame doesn't fit in the frame ring", bytes.len());
+            return false;
+        }
+        FrameReadyPayload {
+            session_id: session_id.to_string(),
+            url: ring.url(session_id),
+            head: ring.head(),
+        }
+    };
+    let _ = app.emit("frame-ready", &payload);
+    true
+}
+
+/// Send `message` through the session's frame ring or channel, whichever
+/// it has. False when it has neither or both failed.
+#[cfg(feature = "app")]
+fn send_binary(app: &AppHandle, session_id: &str, message: &ChannelMessage) -> bool {
+    push_to_frame_ring(app, session_id, message) || send_on_frame_channel(app, session_id, message)
+}
+
+/// Send a render frame to the frontend: through the session's frame ring,
+/// as MessagePack over the channel its window gave it, or else as a JSON
+/// `render-frame` event.
+#[cfg(feature = "app")]
+pub fn emit_render_frame(app: &AppHandle, payload: &RenderFramePayload) {
+    let message = ChannelMessage::RenderFrame(payload);
+    if !send_binary(app, &payload.session_id, &message) {
+        let _ = app.emit("render-frame", payload);
+    }
+}
+
+/// Tell the frontend a session ended, on the same path as its frames.
+#[cfg(feature = "app")]
+pub fn emit_session_ended(app: &AppHandle, payload: &SessionEndPayload) {
+    let message = ChannelMessage::SessionEnded(payload);
+    if !send_binary(app, &payload.session_id, &message) {
+        let _ = app.emit("session-ended", payload);
+    }
+}
+
 /// Payload sent when a session ends.
 #[derive(serde::Serialize, Clone)]
 pub struct SessionEndPayload {
     pub session_id: String,
     pub exit_code: Option<i32>,
+    /// The session ran a single command instead of a shell.
+    pub command: bool,
+}
+
+/// Payload sent when a session's parser thread is stuck or has crashed.
+#[derive(serde::Serialize, Clone)]
+pub struct SessionStalledPayload {
+    pub session_id: String,
+    pub health: ParserHealth,
+}
+
+/// Payload of the rate-limited `session-io` event.
+#[derive(serde::Serialize, Clone)]
+pub struct SessionIoPayload {
+    pub session_id: String,
+    pub stats: IoStats,
 }
 
 /// Handles for the parser and render-pump threads.
@@ -34,24 +195,62 @@ pub struct PtyThreadHandles {
     pub render_waker: SyncSender<()>,
 }
 
+/// A session's shared handles, as the parser and render-pump threads use them.
+pub struct PtyThreadConfig {
+    pub session_id: String,
+    /// The session runs a single command instead of a shell.
+    pub command: bool,
+    pub state: Arc<Mutex<TerminalState>>,
+    pub writer: SharedWriter,
+    pub input: InputQueue,
+    pub child: SharedChild,
+    pub exit_code: SharedExitCode,
+    pub io: Arc<IoCounters>,
+    pub running: Arc<AtomicBool>,
+    pub visible: Arc<AtomicBool>,
+    pub read_only: Arc<AtomicBool>,
+    pub watch: Arc<ThreadWatch>,
+}
+
 /// Spawn PTY parser and render-pump threads.
 ///
 /// - Parser thread: reads PTY bytes and mutates terminal state.
 /// - Render-pump thread: emits at most one frame per tick from accumulated damage.
+///
+/// Once `watch` is retired the threads exit quietly: the session has moved to
+/// new ones, so the render pump neither drains nor ends it.
 pub fn spawn_pty_threads(
     mut reader: Box<dyn Read + Send>,
-    state: Arc<Mutex<TerminalState>>,
-    writer: SharedWriter,
-    child: SharedChild,
-    exit_code: SharedExitCode,
-    app_handle: AppHandle,
-    session_id: String,
-    running: Arc<AtomicBool>,
+    config: PtyThreadConfig,
+    sink: impl FrameSink,
 ) -> PtyThreadHandles {
     fn notify_render(waker: &SyncSender<()>) {
         let _ = waker.try_send(());
     }
 
+    fn emit_io(sink: &impl FrameSink, session_id: &str, io: &IoCounters) {
+        if let Some(stats) = io.poll(Instant::now()) {
+            sink.session_io(&SessionIoPayload {
+                session_id: session_id.to_string(),
+                stats,
+            });
+        }
+    }
+
+    let PtyThreadConfig {
+        session_id,
+        command,
+        state,
+        writer,
+        input,
+        child,
+        exit_code,
+        io,
+        running,
+        visible,
+        read_only,
+        watch,
+    } = config;
     let (render_waker, render_rx) = sync_channel::<()>(1);
     let parser_state = Arc::clone(&state);
     let parser_writer = Arc::clone(&writer);
@@ -60,15 +259,46 @@ pub fn spawn_pty_threads(
     let parser_session = session_id.clone();
     let parser_running = Arc::clone(&running);
     let parser_waker = render_waker.clone();
+    let parser_io = Arc::clone(&io);
+    let parser_watch = Arc::clone(&watch);
+    let parser_visible = Arc::clone(&visible);
 
     let parser = std::thread::Builder::new()
         .name(format!("pty-parser-{}", &session_id[..8]))
         .spawn(move || {
+            // Longest a read waits for a lagging frontend, so one that
+            // stopped acking can't stall the shell for good.
+            const MAX_READ_PAUSE: Duration = Duration::from_secs(1);
+            const READ_PAUSE_POLL: Duration = Duration::from_millis(10);
             let mut parser = vte::Parser::new();
             let mut buf = [0u8; 4096];
 
             while parser_running.load(Ordering::Acquire) {
-                match reader.read(&mut buf) {
+                // Flow control: while a visible session's frontend is far
+                // behind on frames, stop reading so the program blocks on
+                // its writes instead of output piling up here. Hidden
+                // windows don't ack, and their sessions keep running. A
+                // frontend still behind after a whole pause is left to
+                // catch up on keyframes while reading goes on.
+                let paused_at = Instant::now();
+                while parser_visible.load(Ordering::Relaxed)
+                    && parser_running.load(Ordering::Acquire)
+                    && parser_state.lock().frontend_backlogged()
+                {
+                    if paused_at.elapsed() >= MAX_READ_PAUSE {
+                        parser_state.lock().expire_read_pause();
+                        break;
+                    }
+                    std::thread::sleep(READ_PAUSE_POLL);
+                }
+
+                let read = reader.read(&mut buf);
+                // Recovered onto new threads: the bytes and the session are
+                // theirs now, so don't parse, reply or type anything.
+                if parser_watch.is_retired() {
+                    break;
+                }
+                match read {
                     Ok(0) => {
                         // EOF: shell exited -- capture exit code via try_wait
                         tracing::info!("PTY reader EOF for session {}", &parser_session[..8]);
@@ -91,9 +321,14 @@ pub fn spawn_pty_threads(
                         break;
                     }
                     Ok(n) => {
+                        parser_watch.begin();
+                        parser_io.add_read(n);
                         let mut state = parser_state.lock();
-                        for &byte in &buf[..n] {
-                            parser.advance(&mut *state, byte);
+                        state.ensure_resident();
+                        state.touch_activity();
+                        state.advance(&mut parser, &buf[..n]);
+                        if parser_watch.is_retired() {
+                            break;
                         }
 
                         // Flush any DSR/DA response bytes back to the PTY
@@ -105,6 +340,23 @@ pub fn spawn_pty_threads(
                             }
                             let _ = w.flush();
                         }
+
+                        // Type the next queued command once the shell is
+                        // back at an idle prompt. A read-only session keeps
+                        // it queued until it is unlocked.
+                        let queued = if !state.using_alt && !read_only.load(Ordering::Relaxed) {
+                            state.shell.take_queued_command()
+                        } else {
+                            None
+                        };
+                        drop(state);
+                        if let Some(queued) = queued {
+                            let line = format!("{}\r", queued.command);
+                            if let Err(e) = input.write(line.as_bytes()) {
+                                tracing::warn!("Failed to type queued command: {}", e);
+                            }
+                        }
+                        parser_watch.end();
                         notify_render(&parser_waker);
                     }
                     Err(e) => {
@@ -135,7 +387,7 @@ pub fn spawn_pty_threads(
 
     let render_state = Arc::clone(&state);
     let render_exit_code = Arc::clone(&exit_code);
-    let render_app = app_handle;
+    let render_sink = sink;
     let render_session = session_id;
     let render_running = Arc::clone(&running);
     let render_retry_waker = render_waker.clone();
@@ -144,19 +396,50 @@ pub fn spawn_pty_threads(
         .name(format!("pty-render-{}", &render_session[..8]))
         .spawn(move || {
             const FRAME_TICK: Duration = Duration::from_millis(16);
+            // Sessions out of sight accumulate damage between 4 fps frames.
+            const HIDDEN_FRAME_TICK: Duration = Duration::from_millis(250);
+            const IDLE_POLL: Duration = Duration::from_millis(500);
             let mut last_emit = Instant::now() - FRAME_TICK;
+            // Set while a synchronized update holds frames back: the pump
+            // wakes when it times out even if no more output arrives.
+            let mut held_for: Option<Duration> = None;
 
             while render_running.load(Ordering::Acquire) {
-                if render_rx.recv().is_err() {
-                    break;
+                match render_rx.recv_timeout(held_for.unwrap_or(IDLE_POLL)) {
+                    Ok(()) => {}
+                    Err(RecvTimeoutError::Timeout) => {
+                        // Input alone doesn't wake the pump; the idle poll
+                        // reports it, and the end of a transfer.
+                        emit_io(&render_sink, &render_session, &io);
+                        // No output; only wake to flush a debounced session name
+                        // or a frame the synchronized update timed out on.
+                        if held_for.is_none() && !render_state.lock().shell.session_name_pending() {
+                            continue;
+                        }
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
                 }
                 if !render_running.load(Ordering::Acquire) {
                     break;
                 }
 
-                let elapsed = last_emit.elapsed();
-                if elapsed < FRAME_TICK {
-                    std::thread::sleep(FRAME_TICK - elapsed);
+                // Wait out the frame tick. A wake-up re-checks visibility,
+                // so a session coming into view cuts a hidden tick short.
+                loop {
+                    let tick = if visible.load(Ordering::Relaxed) {
+                        FRAME_TICK
+                    } else {
+                        HIDDEN_FRAME_TICK
+                    };
+                    let elapsed = last_emit.elapsed();
+                    if elapsed >= tick || !render_running.load(Ordering::Acquire) {
+                        break;
+                    }
+                    if let Err(RecvTimeoutError::Disconnected) =
+                        render_rx.recv_timeout(tick - elapsed)
+                    {
+                        break;
+                    }
                 }
 
                 // Coalesce bursty parser notifications into one frame build.
@@ -165,6 +448,7 @@ pub fn spawn_pty_threads(
                 let mut emitted = false;
                 if let Some(mut state) = render_state.try_lock() {
                     let snapshot = state.take_render_snapshot();
+                    held_for = state.synchronized_update_remaining();
                     drop(state); // keep parser lock hold minimal
                     if let Some(snapshot) = snapshot {
                         let frame = snapshot.into_frame();
@@ -183,7 +467,7 @@ pub fn spawn_pty_threads(
                             session_id: render_session.clone(),
                             frame,
                         };
-                        let _ = render_app.emit("render-frame", &payload);
+                        render_sink.render_frame(&payload);
                         emitted = true;
                     }
                 } else {
@@ -195,10 +479,15 @@ pub fn spawn_pty_threads(
                 if emitted {
                     last_emit = Instant::now();
                 }
+                emit_io(&render_sink, &render_session, &io);
+            }
+            if watch.is_retired() {
+                return;
             }
 
             // Final drain for any remaining dirty state after shutdown.
             let mut state = render_state.lock();
+            state.release_synchronized_update();
             let snapshot = state.take_render_snapshot();
             drop(state);
             if let Some(snapshot) = snapshot {
@@ -218,17 +507,15 @@ pub fn spawn_pty_threads(
                     session_id: render_session.clone(),
                     frame,
                 };
-                let _ = render_app.emit("render-frame", &payload);
+                render_sink.render_frame(&payload);
             }
 
             let captured_exit_code = render_exit_code.lock().take();
-            let _ = render_app.emit(
-                "session-ended",
-                &SessionEndPayload {
-                    session_id: render_session,
-                    exit_code: captured_exit_code,
-                },
-            );
+            render_sink.session_ended(&SessionEndPayload {
+                session_id: render_session,
+                exit_code: captured_exit_code,
+                command,
+            });
         })
         .expect("Failed to spawn PTY render thread");
 
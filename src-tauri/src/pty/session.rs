@@ -1,11 +1,18 @@
+use std::collections::HashMap;
 use std::io::Write;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::SyncSender;
 use std::sync::Arc;
+use std::time::Duration;
 
 use parking_lot::Mutex;
 use portable_pty::{Child, MasterPty, PtySize};
 
+use super::input::InputQueue;
+use super::io_stats::{CountingWriter, IoCounters, IoStats};
+use super::watchdog::{ParserHealth, ThreadWatch};
+use crate::process::{self, Signal};
+use crate::shell::naming;
 use crate::terminal::TerminalState;
 
 /// Shared writer handle so both the Session (keyboard input) and the reader
@@ -17,6 +24,18 @@ pub type SharedWriter = Arc<Mutex<Box<dyn Write + Send>>>;
 /// `session-ended` event.
 pub type SharedExitCode = Arc<Mutex<Option<i32>>>;
 
+/// What a session's shell was spawned with, kept so `restart_session` can
+/// start the same shell again.
+#[derive(Debug, Clone, Default)]
+pub struct LaunchSpec {
+    pub shell: Option<String>,
+    pub cwd: Option<String>,
+    pub env: Option<HashMap<String, String>>,
+    pub tmux_mode: Option<String>,
+    /// Program and arguments run instead of the shell.
+    pub command: Option<Vec<String>>,
+}
+
 /// Shared child handle so the parser thread can call `try_wait()` after EOF.
 pub type SharedChild = Arc<Mutex<Box<dyn Child + Send + Sync>>>;
 
@@ -38,10 +57,24 @@ pub struct Session {
     parser_handle: Option<std::thread::JoinHandle<()>>,
     /// Render-pump thread handle
     render_handle: Option<std::thread::JoinHandle<()>>,
+    /// Parser heartbeat for the stall watchdog.
+    watch: Arc<ThreadWatch>,
     /// Temp directory used for shell init files; cleaned up on kill.
     temp_dir: Option<std::path::PathBuf>,
     /// Shared exit code slot written by the parser thread on EOF.
     exit_code: SharedExitCode,
+    /// Input commands refuse to write while set; the PTY still answers
+    /// terminal queries through the shared writer.
+    read_only: Arc<AtomicBool>,
+    /// Whether the session is on screen; hidden ones render at a few
+    /// frames per second.
+    visible: Arc<AtomicBool>,
+    /// Bytes read from and written to the PTY.
+    io: Arc<IoCounters>,
+    /// User input, written to the PTY in order.
+    input: InputQueue,
+    /// Arguments the shell was spawned with.
+    launch: LaunchSpec,
 }
 
 impl Session {
@@ -53,18 +86,28 @@ impl Session {
         cols: u16,
     ) -> Self {
         let state = Arc::new(Mutex::new(TerminalState::new(rows, cols)));
+        let io = Arc::new(IoCounters::default());
+        let writer: Box<dyn Write + Send> =
+            Box::new(CountingWriter::new(writer, Arc::clone(&io)));
+        let writer: SharedWriter = Arc::new(Mutex::new(writer));
 
         Self {
             master: Some(master),
             child: Arc::new(Mutex::new(child)),
-            writer: Arc::new(Mutex::new(writer)),
+            input: InputQueue::new(Arc::clone(&writer)),
+            writer,
             state,
             running: Arc::new(AtomicBool::new(true)),
             render_waker: None,
             parser_handle: None,
             render_handle: None,
+            watch: Arc::new(ThreadWatch::default()),
             temp_dir: None,
             exit_code: Arc::new(Mutex::new(None)),
+            read_only: Arc::new(AtomicBool::new(false)),
+            visible: Arc::new(AtomicBool::new(true)),
+            io,
+            launch: LaunchSpec::default(),
         }
     }
 
@@ -78,16 +121,116 @@ impl Session {
         Arc::clone(&self.writer)
     }
 
+    /// Get the session's input queue, for input typed on the user's behalf.
+    pub fn input(&self) -> InputQueue {
+        self.input.clone()
+    }
+
     /// Get the shared running flag for PTY worker threads.
     pub fn running(&self) -> Arc<AtomicBool> {
         Arc::clone(&self.running)
     }
 
+    /// Get the shared visibility flag for the render pump.
+    pub fn visibility(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.visible)
+    }
+
+    /// Get the parser heartbeat for the PTY worker threads.
+    pub fn watch(&self) -> Arc<ThreadWatch> {
+        Arc::clone(&self.watch)
+    }
+
+    /// Whether the parser thread is keeping up. Never waits on the terminal
+    /// state, so it is safe to ask about a session that is wedged.
+    pub fn parser_health(&self) -> ParserHealth {
+        let finished = self
+            .parser_handle
+            .as_ref()
+            .is_some_and(|handle| handle.is_finished());
+        self.watch
+            .health(finished, self.running.load(Ordering::Acquire))
+    }
+
+    /// Abandon the current parser and render threads and the terminal state
+    /// they share, for a session whose parser is stuck or gone. The shell
+    /// keeps running; the returned reader and the fresh state, sized like
+    /// the PTY, are for new threads. The fresh state keeps the session's
+    /// settings (theme, color filter, scrollback limits, ...) unless the
+    /// stuck parser still holds the old one. A stuck thread can't be
+    /// stopped, only told to leave without touching the session if it ever
+    /// gets unstuck.
+    pub fn detach_threads(&mut self) -> Result<Box<dyn std::io::Read + Send>, String> {
+        /// How long to wait for a stuck parser to let go of the old state.
+        const STATE_LOCK_WAIT: Duration = Duration::from_millis(200);
+
+        let master = self.master.as_ref().ok_or("PTY master already closed")?;
+        let size = master.get_size().map_err(|e| e.to_string())?;
+        let reader = master.try_clone_reader().map_err(|e| e.to_string())?;
+
+        self.watch.retire();
+        self.running.store(false, Ordering::Release);
+        self.notify_render();
+        self.parser_handle = None;
+        self.render_handle = None;
+        self.render_waker = None;
+
+        let mut state = match self.state.try_lock_for(STATE_LOCK_WAIT) {
+            Some(mut old) => old.take_for_recovery(),
+            None => {
+                tracing::warn!(
+                    "Stuck parser holds the terminal state; recovering without its settings"
+                );
+                TerminalState::new(size.rows, size.cols)
+            }
+        };
+        if state.size() != (size.rows, size.cols) {
+            state.resize(size.rows, size.cols);
+        }
+        self.watch = Arc::new(ThreadWatch::default());
+        self.running = Arc::new(AtomicBool::new(true));
+        self.state = Arc::new(Mutex::new(state));
+        Ok(reader)
+    }
+
     /// Get the shared exit code slot for reader/render threads.
     pub fn exit_code(&self) -> SharedExitCode {
         Arc::clone(&self.exit_code)
     }
 
+    /// Get the shared PTY byte counters for the reader/render threads.
+    pub fn io_counters(&self) -> Arc<IoCounters> {
+        Arc::clone(&self.io)
+    }
+
+    pub fn io_stats(&self) -> IoStats {
+        self.io.stats()
+    }
+
+    /// Lock or unlock the session against user input.
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::Relaxed);
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    /// Get the shared read-only flag, so the parser thread holds queued
+    /// commands back while it is set.
+    pub fn read_only_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.read_only)
+    }
+
+    /// Record whether the session is on screen. Coming back into view
+    /// flushes what accumulated while it was hidden right away.
+    pub fn set_visible(&self, visible: bool) {
+        let was_visible = self.visible.swap(visible, Ordering::Relaxed);
+        if visible && !was_visible {
+            self.notify_render();
+        }
+    }
+
     /// Signal render-pump that terminal state may have changed.
     pub fn notify_render(&self) {
         if let Some(waker) = &self.render_waker {
@@ -109,12 +252,110 @@ impl Session {
         self.notify_render();
     }
 
-    /// Write input bytes to the shell via the PTY.
+    /// Process ID of the shell (or command) the session spawned.
+    pub fn shell_pid(&self) -> Option<i32> {
+        self.child.lock().process_id().map(|pid| pid as i32)
+    }
+
+    /// Process group leader of the PTY's foreground job: the running
+    /// command, or the shell itself at a prompt.
+    #[cfg(unix)]
+    pub fn foreground_pid(&self) -> Option<i32> {
+        self.master.as_ref()?.process_group_leader()
+    }
+
+    #[cfg(not(unix))]
+    pub fn foreground_pid(&self) -> Option<i32> {
+        None
+    }
+
+    /// Deliver `signal` to the PTY's foreground job. The shell itself is
+    /// never signalled: with nothing but the shell in the foreground this
+    /// fails. A run-command session's command took over the shell's process
+    /// (see `login_shell_command`), so there it is the job. Interrupting a
+    /// remote-login client types Ctrl+C instead, which it passes on to the
+    /// remote command; SIGINT would end the connection.
+    #[cfg(unix)]
+    pub fn signal_foreground(&self, signal: Signal) -> std::io::Result<()> {
+        let pgid = signal_target(
+            self.foreground_pid(),
+            self.shell_pid(),
+            self.launch.command.is_some(),
+        )
+        .ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no job in the foreground")
+        })?;
+        if signal == Signal::Interrupt
+            && process::process_info(pgid)
+                .is_some_and(|process| naming::is_remote_program(&process.name))
+        {
+            return self.write_input(b"\x03");
+        }
+        // SAFETY: killpg has no memory-safety preconditions.
+        if unsafe { libc::killpg(pgid, signal.number()) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// ConPTY has no process groups to signal; typing Ctrl+C makes it raise
+    /// CTRL_C_EVENT in the attached processes, the only signal it offers.
+    #[cfg(not(unix))]
+    pub fn signal_foreground(&self, signal: Signal) -> std::io::Result<()> {
+        match signal {
+            Signal::Interrupt => self.write_input(b"\x03"),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("{:?} is not supported on this platform", signal),
+            )),
+        }
+    }
+
+    /// Write input bytes to the shell via the PTY, after any input still
+    /// being written.
     pub fn write_input(&self, data: &[u8]) -> Result<(), std::io::Error> {
+        self.note_input([data]);
+        self.input.write(data)
+    }
+
+    /// Write many input chunks (replayed keystrokes, paste pieces) as one
+    /// batch through the input queue.
+    pub fn write_input_batched(&self, chunks: &[Vec<u8>]) -> Result<(), std::io::Error> {
+        if chunks.iter().all(|chunk| chunk.is_empty()) {
+            return Ok(());
+        }
+        self.note_input(chunks.iter().map(Vec::as_slice));
+        self.input.write_batch(chunks)
+    }
+
+    fn note_input<'a>(&self, chunks: impl IntoIterator<Item = &'a [u8]>) {
+        let mut state = self.state.lock();
+        state.touch_activity();
+        for chunk in chunks {
+            state.shell.note_input(chunk);
+        }
+    }
+
+    /// Report a focus change (`CSI I` / `CSI O`) if the running program
+    /// enabled mode 1004. Not user input: the prompt stays idle.
+    pub fn report_focus(&self, focused: bool) -> Result<(), std::io::Error> {
+        if !self.state.lock().modes.focus_events {
+            return Ok(());
+        }
         let mut writer = self.writer.lock();
-        writer.write_all(data)?;
-        writer.flush()?;
-        Ok(())
+        writer.write_all(if focused { b"\x1b[I" } else { b"\x1b[O" })?;
+        writer.flush()
+    }
+
+    /// Write text as a paste, sanitized and wrapped in bracketed-paste
+    /// markers when the running program has enabled mode 2004. Large pastes
+    /// (or ones made while earlier input is still being written) finish in
+    /// the background; their write errors are only logged.
+    pub fn write_paste(&self, text: &str) -> Result<(), std::io::Error> {
+        let bracketed = self.state.lock().modes.bracketed_paste;
+        let data = encode_paste(text, bracketed);
+        self.note_input([data.as_slice()]);
+        self.input.write(&data)
     }
 
     /// Resize the terminal.
@@ -147,6 +388,15 @@ impl Session {
         self.temp_dir = Some(path);
     }
 
+    /// Record what the shell was spawned with.
+    pub fn set_launch(&mut self, launch: LaunchSpec) {
+        self.launch = launch;
+    }
+
+    pub fn launch(&self) -> &LaunchSpec {
+        &self.launch
+    }
+
     /// Set parser and render-pump thread handles.
     pub fn set_thread_handles(
         &mut self,
@@ -233,7 +483,6 @@ impl Session {
     }
 
     /// Check if the child process has exited.
-    #[allow(dead_code)]
     pub fn try_wait(&self) -> Option<portable_pty::ExitStatus> {
         self.child.lock().try_wait().ok().flatten()
     }
@@ -244,8 +493,97 @@ impl Session {
     }
 }
 
+/// Build the byte stream for a paste. The text is sanitized, and when
+/// bracketed paste is active wrapped in `ESC [200~` / `ESC [201~`; with no
+/// escapes left in it, the payload cannot terminate the paste early.
+pub fn encode_paste(text: &str, bracketed: bool) -> Vec<u8> {
+    let body = sanitize_paste(text);
+    if !bracketed {
+        return body.into_bytes();
+    }
+    let mut out = Vec::with_capacity(body.len() + 12);
+    out.extend_from_slice(b"\x1b[200~");
+    out.extend_from_slice(body.as_bytes());
+    out.extend_from_slice(b"\x1b[201~");
+    out
+}
+
+/// Pasted text without escape sequences and control characters other than
+/// tab, CR and LF, so a paste can't drive the program (or the terminal it
+/// echoes to) the way typed control keys would.
+pub fn sanitize_paste(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters and intermediates up to the final byte.
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC, DCS, SOS, PM and APC strings end at BEL or ST.
+                Some(']' | 'P' | 'X' | '^' | '_') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\t' | '\r' | '\n' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
 impl Drop for Session {
     fn drop(&mut self) {
         self.kill();
     }
 }
+
+/// The process group `signal_foreground` may signal: the foreground one,
+/// unless that is the shell's and the session runs a shell rather than a
+/// command.
+#[cfg(unix)]
+fn signal_target(foreground: Option<i32>, shell: Option<i32>, runs_command: bool) -> Option<i32> {
+    foreground.filter(|&pgid| runs_command || Some(pgid) != shell)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn only_run_command_sessions_signal_their_own_process() {
+        assert_eq!(signal_target(Some(200), Some(100), false), Some(200));
+        assert_eq!(signal_target(Some(100), Some(100), false), None);
+        // `htop` exec'd over the login shell keeps its PID.
+        assert_eq!(signal_target(Some(100), Some(100), true), Some(100));
+        assert_eq!(signal_target(None, Some(100), true), None);
+    }
+
+    #[test]
+    fn pastes_lose_escapes_and_control_characters() {
+        assert_eq!(
+            sanitize_paste("ls\t-la\r\n\x1b[31mred\x1b[0m\x03\x7f\u{9b}é"),
+            "ls\t-la\r\nredé"
+        );
+        assert_eq!(
+            sanitize_paste("a\x1b]52;c;aGk=\x07b\x1bPq#0\x1b\\c\x1b7d"),
+            "abcd"
+        );
+        assert_eq!(
+            encode_paste("x\x1b[201~rm -rf ~\n", true),
+            b"\x1b[200~xrm -rf ~\n\x1b[201~"
+        );
+    }
+}
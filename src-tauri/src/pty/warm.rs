@@ -0,0 +1,437 @@
+//! Pre-spawned shells for new tabs.
+//!
+//! Shell startup can take most of a second (oh-my-zsh and friends), so with
+//! the warm pool enabled the backend keeps one shell per launch profile
+//! (shell, environment and tmux mode) running in the home directory, and
+//! `create_session` takes it instead of spawning. Its output is buffered
+//! from the start so startup never blocks on a full PTY. When the tab wants
+//! another directory, a `cd` is typed first and everything up to the prompt
+//! after it is dropped, so the tab opens at a clean prompt in that directory.
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+
+use super::{resolve_shell, PtyManager, Session, SpawnResult};
+use crate::shell::detect::shell_name;
+use crate::shell::hooks::shell_init_command;
+
+/// Warm shells kept at once, across all profiles.
+pub const MAX_WARM_SHELLS: usize = 4;
+
+/// Size a warm shell starts with; it is resized when taken.
+const WARM_ROWS: u16 = 24;
+const WARM_COLS: u16 = 80;
+
+/// How long to wait for the prompt after the initial `cd` before showing
+/// whatever the shell printed instead.
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Launch profile of a warm shell: the arguments of `create_session` apart
+/// from the directory and size.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WarmKey {
+    shell: Option<String>,
+    env: Vec<(String, String)>,
+    tmux_mode: Option<String>,
+}
+
+impl WarmKey {
+    pub fn new(
+        shell: Option<&str>,
+        env: Option<&HashMap<String, String>>,
+        tmux_mode: Option<&str>,
+    ) -> Self {
+        let mut env: Vec<(String, String)> = env
+            .map(|env| env.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+            .unwrap_or_default();
+        env.sort();
+        Self {
+            shell: shell.map(str::to_string),
+            env,
+            tmux_mode: tmux_mode.map(str::to_string),
+        }
+    }
+
+    fn env_map(&self) -> HashMap<String, String> {
+        self.env.iter().cloned().collect()
+    }
+}
+
+/// Command that changes the directory of `shell` to `dir`, with a leading
+/// space to keep it out of the history. `None` for shells without one.
+pub fn cd_command(shell: &str, dir: &Path) -> Option<String> {
+    let dir = dir.to_str()?;
+    match shell {
+        "bash" | "zsh" => Some(format!(" cd -- '{}'\r", dir.replace('\'', r"'\''"))),
+        "fish" => Some(format!(
+            " cd '{}'\r",
+            dir.replace('\\', r"\\").replace('\'', r"\'")
+        )),
+        "pwsh" | "powershell" => Some(format!(
+            " Set-Location -LiteralPath '{}'\r",
+            dir.replace('\'', "''")
+        )),
+        _ => None,
+    }
+}
+
+const COMMAND_START: &[u8] = b"\x1b]133;C";
+const PROMPT_START: &[u8] = b"\x1b]133;A";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GateState {
+    Open,
+    AwaitCommand,
+    AwaitPrompt,
+}
+
+/// Holds back output until the prompt drawn after the initial `cd`: the
+/// command start (OSC 133;C) the hooks print for it, then the next prompt
+/// start (OSC 133;A), which is passed on with everything after it.
+#[derive(Debug)]
+struct OutputGate {
+    state: GateState,
+    held: Vec<u8>,
+}
+
+impl Default for OutputGate {
+    fn default() -> Self {
+        Self {
+            state: GateState::Open,
+            held: Vec::new(),
+        }
+    }
+}
+
+impl OutputGate {
+    /// Start holding, beginning with output that wasn't read yet.
+    fn close(&mut self, unread: Vec<u8>) {
+        self.state = GateState::AwaitCommand;
+        self.held = unread;
+    }
+
+    fn is_open(&self) -> bool {
+        self.state == GateState::Open
+    }
+
+    /// Add `chunk`, moving what may be shown into `out`.
+    fn feed(&mut self, chunk: &[u8], out: &mut Vec<u8>) {
+        if self.is_open() {
+            out.extend_from_slice(chunk);
+            return;
+        }
+        self.held.extend_from_slice(chunk);
+        loop {
+            let marker = match self.state {
+                GateState::Open => return,
+                GateState::AwaitCommand => COMMAND_START,
+                GateState::AwaitPrompt => PROMPT_START,
+            };
+            let Some(at) = find(&self.held, marker) else {
+                return;
+            };
+            if self.state == GateState::AwaitCommand {
+                self.held.drain(..at + marker.len());
+                self.state = GateState::AwaitPrompt;
+            } else {
+                out.extend(self.held.drain(at..));
+                self.held.clear();
+                self.state = GateState::Open;
+            }
+        }
+    }
+
+    /// Stop waiting and pass on everything held.
+    fn open(&mut self, out: &mut Vec<u8>) {
+        out.append(&mut self.held);
+        self.state = GateState::Open;
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[derive(Debug, Default)]
+struct Pipe {
+    out: Vec<u8>,
+    gate: OutputGate,
+    deadline: Option<Instant>,
+    eof: bool,
+}
+
+/// The PTY output of a warm shell, read on a thread of its own from spawn
+/// on. Handed to the parser thread like the PTY reader itself.
+pub struct WarmReader {
+    pipe: Arc<(Mutex<Pipe>, Condvar)>,
+}
+
+impl WarmReader {
+    fn start(mut reader: Box<dyn Read + Send>) -> io::Result<Self> {
+        let pipe = Arc::new((Mutex::new(Pipe::default()), Condvar::new()));
+        let drain = Arc::clone(&pipe);
+        std::thread::Builder::new()
+            .name("pty-warm".to_string())
+            .spawn(move || {
+                let (lock, ready) = &*drain;
+                let mut buf = [0u8; 8192];
+                loop {
+                    let n = reader.read(&mut buf).unwrap_or(0);
+                    let mut pipe = lock.lock();
+                    if n == 0 {
+                        // Whatever a shell that died before its prompt said.
+                        let Pipe { out, gate, .. } = &mut *pipe;
+                        gate.open(out);
+                        pipe.eof = true;
+                        ready.notify_all();
+                        return;
+                    }
+                    let Pipe { out, gate, .. } = &mut *pipe;
+                    gate.feed(&buf[..n], out);
+                    ready.notify_all();
+                }
+            })?;
+        Ok(Self { pipe })
+    }
+
+    /// Drop output up to the prompt that follows the next command.
+    fn hold_until_prompt(&self) {
+        let mut pipe = self.pipe.0.lock();
+        let unread = std::mem::take(&mut pipe.out);
+        pipe.gate.close(unread);
+        pipe.deadline = Some(Instant::now() + PROMPT_TIMEOUT);
+    }
+}
+
+impl Read for WarmReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let (lock, ready) = &*self.pipe;
+        let mut pipe = lock.lock();
+        loop {
+            if !pipe.out.is_empty() {
+                let n = buf.len().min(pipe.out.len());
+                buf[..n].copy_from_slice(&pipe.out[..n]);
+                pipe.out.drain(..n);
+                return Ok(n);
+            }
+            if pipe.eof {
+                return Ok(0);
+            }
+            match pipe.deadline {
+                Some(deadline) if !pipe.gate.is_open() => {
+                    if Instant::now() >= deadline {
+                        tracing::warn!("No prompt from warm shell after cd; showing its output");
+                        let Pipe { out, gate, .. } = &mut *pipe;
+                        gate.open(out);
+                    } else {
+                        ready.wait_until(&mut pipe, deadline);
+                    }
+                }
+                _ => ready.wait(&mut pipe),
+            }
+        }
+    }
+}
+
+/// A shell started ahead of time, waiting in the home directory.
+pub struct WarmShell {
+    session: Session,
+    reader: WarmReader,
+    shell: String,
+    cwd: Option<PathBuf>,
+}
+
+impl WarmShell {
+    /// Start a warm shell for `key`, or `None` for shells Rain has no hooks
+    /// or `cd` command for, which couldn't be moved to another directory.
+    pub fn spawn(manager: &PtyManager, key: &WarmKey) -> Result<Option<Self>, String> {
+        let shell = resolve_shell(key.shell.as_deref());
+        let name = shell_name(&shell);
+        if shell_init_command(name).is_none() || cd_command(name, Path::new("/")).is_none() {
+            return Ok(None);
+        }
+        let env = key.env_map();
+        let spawned = manager
+            .spawn_session(
+                key.shell.as_deref(),
+                None,
+                WARM_ROWS,
+                WARM_COLS,
+                Some(&env),
+                key.tmux_mode.as_deref(),
+                None,
+            )
+            .map_err(|e| format!("Failed to spawn warm shell: {}", e))?;
+        let mut session = spawned.session;
+        let reader = match WarmReader::start(spawned.reader) {
+            Ok(reader) => reader,
+            Err(e) => {
+                session.kill();
+                return Err(format!("Failed to start warm shell reader: {}", e));
+            }
+        };
+        Ok(Some(Self {
+            session,
+            reader,
+            shell: spawned.shell,
+            cwd: dirs::home_dir(),
+        }))
+    }
+
+    /// Resize the shell to `rows`x`cols`, move it to `cwd` (the home
+    /// directory when `None`) and hand it out. `None` when the shell has
+    /// exited or can't get there; it is killed then.
+    pub fn into_spawn(self, cwd: Option<&str>, rows: u16, cols: u16) -> Option<SpawnResult> {
+        if self.session.try_wait().is_some() {
+            self.discard();
+            return None;
+        }
+        let target = cwd.map(PathBuf::from).or_else(dirs::home_dir);
+        if target.as_ref().is_some_and(|dir| !dir.is_dir()) {
+            self.discard();
+            return None;
+        }
+        if let Err(e) = self.session.resize(rows, cols) {
+            tracing::warn!("Failed to resize warm shell: {}", e);
+            self.discard();
+            return None;
+        }
+        if let Some(dir) = target.filter(|dir| Some(dir) != self.cwd.as_ref()) {
+            let Some(command) = cd_command(shell_name(&self.shell), &dir) else {
+                self.discard();
+                return None;
+            };
+            self.reader.hold_until_prompt();
+            if self.session.write_input(command.as_bytes()).is_err() {
+                self.discard();
+                return None;
+            }
+            self.session
+                .state
+                .lock()
+                .shell
+                .set_cwd(dir.to_string_lossy().into_owned(), true);
+        }
+        Some(SpawnResult {
+            session: self.session,
+            reader: Box::new(self.reader),
+            shell: self.shell,
+        })
+    }
+
+    /// Kill the shell.
+    pub fn discard(mut self) {
+        self.session.kill();
+    }
+}
+
+/// Warm shells by profile, oldest first.
+#[derive(Default)]
+pub struct WarmPool {
+    enabled: bool,
+    shells: Vec<(WarmKey, WarmShell)>,
+}
+
+impl WarmPool {
+    /// Turn the pool on or off. Returns the shells to discard.
+    #[must_use]
+    pub fn set_enabled(&mut self, enabled: bool) -> Vec<WarmShell> {
+        self.enabled = enabled;
+        if enabled {
+            Vec::new()
+        } else {
+            self.clear()
+        }
+    }
+
+    /// Whether a shell for `key` should be started.
+    pub fn wants(&self, key: &WarmKey) -> bool {
+        self.enabled && !self.shells.iter().any(|(k, _)| k == key)
+    }
+
+    pub fn take(&mut self, key: &WarmKey) -> Option<WarmShell> {
+        let index = self.shells.iter().position(|(k, _)| k == key)?;
+        Some(self.shells.remove(index).1)
+    }
+
+    /// Keep `shell` for `key`. Returns the shells to discard: `shell` itself
+    /// when it isn't wanted, or the oldest one when the pool is full.
+    #[must_use]
+    pub fn insert(&mut self, key: WarmKey, shell: WarmShell) -> Vec<WarmShell> {
+        if !self.wants(&key) {
+            return vec![shell];
+        }
+        let mut evicted = Vec::new();
+        if self.shells.len() >= MAX_WARM_SHELLS {
+            evicted.push(self.shells.remove(0).1);
+        }
+        self.shells.push((key, shell));
+        evicted
+    }
+
+    /// Remove every shell, e.g. when the launch arguments change. Returns
+    /// the shells to discard.
+    #[must_use]
+    pub fn clear(&mut self) -> Vec<WarmShell> {
+        self.shells.drain(..).map(|(_, shell)| shell).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn gate_passes_output_from_the_prompt_after_the_command() {
+        let mut gate = OutputGate::default();
+        let mut out = Vec::new();
+        gate.feed(b"motd", &mut out);
+        gate.close(std::mem::take(&mut out));
+        gate.feed(b"\x1b]133;A\x07$  cd -- '/tmp'\r\n\x1b]1", &mut out);
+        gate.feed(b"33;B; cd -- '/tmp'\x07\x1b]13", &mut out);
+        gate.feed(b"3;C\x07\x1b]133;D;0\x07\x1b]133", &mut out);
+        assert!(out.is_empty());
+        gate.feed(b";A\x07/tmp $ ", &mut out);
+        assert_eq!(out, b"\x1b]133;A\x07/tmp $ ");
+        gate.feed(b"ls", &mut out);
+        assert!(out.ends_with(b"$ ls"));
+
+        let mut gate = OutputGate::default();
+        let mut out = Vec::new();
+        gate.close(b"Update oh-my-zsh? [Y/n] ".to_vec());
+        gate.open(&mut out);
+        assert_eq!(out, b"Update oh-my-zsh? [Y/n] ");
+    }
+
+    #[test]
+    fn cd_command_quotes_the_directory() {
+        let dir =
+            std::env::temp_dir().join(format!("rain-warm-{}-it's here", uuid::Uuid::new_v4()));
+        assert_eq!(
+            cd_command("pwsh", Path::new("/tmp/it's here")).unwrap(),
+            " Set-Location -LiteralPath '/tmp/it''s here'\r"
+        );
+        assert_eq!(cd_command("nu", &dir), None);
+
+        std::fs::create_dir_all(&dir).unwrap();
+        let command = cd_command("bash", &dir).unwrap();
+        let script = format!("{} && pwd", command.trim_end_matches('\r'));
+        let output = Command::new("bash").args(["-c", &script]).output();
+        std::fs::remove_dir_all(&dir).ok();
+        let Ok(output) = output else {
+            return;
+        };
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim_end(),
+            dir.to_string_lossy()
+        );
+    }
+}
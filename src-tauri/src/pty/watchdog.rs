@@ -0,0 +1,125 @@
+//! Heartbeat of a session's parser thread, for spotting one that has wedged.
+//!
+//! The parser marks itself busy from the moment a PTY read returns until
+//! the bytes are applied to the terminal state. Blocking in `read` is idle
+//! time, so a parser busy for longer than [`STALL_THRESHOLD`] had output to
+//! handle and is stuck on it: deadlocked on the state lock or looping in
+//! escape handling. A parser thread that exits while its session is still
+//! running has panicked. Neither check takes the terminal state lock, which
+//! a stuck parser may be holding.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// How long the parser may spend on one chunk of output before its session
+/// counts as stalled.
+pub const STALL_THRESHOLD: Duration = Duration::from_secs(5);
+
+/// `busy_since` while the parser is waiting for output.
+const IDLE: u64 = u64::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParserHealth {
+    Ok,
+    /// Busy on one chunk for longer than [`STALL_THRESHOLD`].
+    Stalled,
+    /// The thread ended while the session was still running.
+    Crashed,
+}
+
+/// Shared between a parser thread and its session.
+#[derive(Debug)]
+pub struct ThreadWatch {
+    epoch: Instant,
+    /// Milliseconds after `epoch` the current chunk was read, or [`IDLE`].
+    busy_since: AtomicU64,
+    /// Set when the session moves to new threads; the old ones then leave
+    /// without touching the session.
+    retired: AtomicBool,
+}
+
+impl Default for ThreadWatch {
+    fn default() -> Self {
+        Self {
+            epoch: Instant::now(),
+            busy_since: AtomicU64::new(IDLE),
+            retired: AtomicBool::new(false),
+        }
+    }
+}
+
+impl ThreadWatch {
+    /// The parser read output and starts applying it.
+    pub fn begin(&self) {
+        let now = self.epoch.elapsed().as_millis() as u64;
+        self.busy_since.store(now, Ordering::Release);
+    }
+
+    /// The parser is done with the chunk and goes back to reading.
+    pub fn end(&self) {
+        self.busy_since.store(IDLE, Ordering::Release);
+    }
+
+    /// How long the parser has been on its current chunk.
+    pub fn busy_for(&self) -> Option<Duration> {
+        let since = self.busy_since.load(Ordering::Acquire);
+        if since == IDLE {
+            return None;
+        }
+        let now = self.epoch.elapsed().as_millis() as u64;
+        Some(Duration::from_millis(now.saturating_sub(since)))
+    }
+
+    pub fn retire(&self) {
+        self.retired.store(true, Ordering::Release);
+    }
+
+    pub fn is_retired(&self) -> bool {
+        self.retired.load(Ordering::Acquire)
+    }
+
+    /// Health of the parser, given whether its thread has finished and
+    /// whether the session should still be running.
+    pub fn health(&self, finished: bool, running: bool) -> ParserHealth {
+        if !running {
+            return ParserHealth::Ok;
+        }
+        if finished {
+            return ParserHealth::Crashed;
+        }
+        match self.busy_for() {
+            Some(busy) if busy >= STALL_THRESHOLD => ParserHealth::Stalled,
+            _ => ParserHealth::Ok,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsers_stall_only_while_busy_on_output() {
+        let watch = ThreadWatch::default();
+        assert_eq!(watch.busy_for(), None);
+        assert_eq!(watch.health(false, true), ParserHealth::Ok);
+
+        // Read a chunk long ago and never finished it.
+        watch.busy_since.store(0, Ordering::Release);
+        let watch = ThreadWatch {
+            epoch: Instant::now() - STALL_THRESHOLD,
+            ..watch
+        };
+        assert_eq!(watch.health(false, true), ParserHealth::Stalled);
+        assert_eq!(watch.health(false, false), ParserHealth::Ok);
+
+        watch.end();
+        assert_eq!(watch.health(false, true), ParserHealth::Ok);
+        assert_eq!(watch.health(true, true), ParserHealth::Crashed);
+        // EOF clears the running flag before the thread ends.
+        assert_eq!(watch.health(true, false), ParserHealth::Ok);
+    }
+}
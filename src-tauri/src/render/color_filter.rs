@@ -0,0 +1,207 @@
+//! Accessibility color filters applied to spans before they leave the backend.
+//!
+//! Filtering here means every frame arrives already adjusted, so the
+//! renderers need no per-frame color work. The red–green filters move the 16
+//! ANSI colors onto a palette that stays distinguishable with deuteranopia or
+//! protanopia (based on Okabe–Ito) and daltonize 256-color and truecolor
+//! values. High contrast forces theme foreground/background, keeping only a
+//! black-on-white highlight for text that had its own background.
+
+use serde::{Deserialize, Serialize};
+
+use super::frame::StyledSpan;
+use crate::terminal::color::{indexed_to_rgb, SerializableColor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorFilter {
+    #[default]
+    None,
+    Deuteranopia,
+    Protanopia,
+    HighContrast,
+}
+
+/// ANSI red/green/yellow/blue/magenta/cyan, normal then bright, for each
+/// red–green filter. Black, white and their bright variants are kept.
+const DEUTERANOPIA_ANSI: [(u8, u8, u8); 12] = [
+    (0xd5, 0x5e, 0x00),
+    (0x00, 0x9e, 0x73),
+    (0xf0, 0xe4, 0x42),
+    (0x00, 0x72, 0xb2),
+    (0xcc, 0x79, 0xa7),
+    (0x56, 0xb4, 0xe9),
+    (0xff, 0x8c, 0x42),
+    (0x3d, 0xd6, 0xa8),
+    (0xff, 0xf5, 0x80),
+    (0x4d, 0xa3, 0xe6),
+    (0xe8, 0xa6, 0xc8),
+    (0x9a, 0xd6, 0xf5),
+];
+
+/// Protanopes see long-wavelength red as dark, so "red" becomes a bright
+/// orange instead of vermillion.
+const PROTANOPIA_ANSI: [(u8, u8, u8); 12] = [
+    (0xe6, 0x9f, 0x00),
+    (0x00, 0x9e, 0x73),
+    (0xf0, 0xe4, 0x42),
+    (0x00, 0x72, 0xb2),
+    (0xcc, 0x79, 0xa7),
+    (0x56, 0xb4, 0xe9),
+    (0xff, 0xc2, 0x4d),
+    (0x3d, 0xd6, 0xa8),
+    (0xff, 0xf5, 0x80),
+    (0x4d, 0xa3, 0xe6),
+    (0xe8, 0xa6, 0xc8),
+    (0x9a, 0xd6, 0xf5),
+];
+
+impl ColorFilter {
+    pub fn is_none(self) -> bool {
+        self == ColorFilter::None
+    }
+
+    pub fn apply(self, spans: &mut [StyledSpan]) {
+        match self {
+            ColorFilter::None => {}
+            ColorFilter::Deuteranopia | ColorFilter::Protanopia => {
+                for span in spans {
+                    span.fg = self.remap(&span.fg);
+                    span.bg = self.remap(&span.bg);
+                    span.underline_color = span.underline_color.as_ref().map(|c| self.remap(c));
+                }
+            }
+            ColorFilter::HighContrast => {
+                for span in spans {
+                    let highlighted = !matches!(span.bg, SerializableColor::Default);
+                    if highlighted {
+                        span.fg = SerializableColor::Indexed { index: 0 };
+                        span.bg = SerializableColor::Indexed { index: 15 };
+                    } else {
+                        span.fg = SerializableColor::Default;
+                    }
+                    span.underline_color = None;
+                    span.dim = false;
+                }
+            }
+        }
+    }
+
+    fn remap(self, color: &SerializableColor) -> SerializableColor {
+        let (r, g, b) = match *color {
+            SerializableColor::Default => return SerializableColor::Default,
+            SerializableColor::Indexed { index } => match index {
+                1..=6 | 9..=14 => {
+                    let slot = if index < 8 { index - 1 } else { index - 3 };
+                    let table = if self == ColorFilter::Protanopia {
+                        &PROTANOPIA_ANSI
+                    } else {
+                        &DEUTERANOPIA_ANSI
+                    };
+                    let (r, g, b) = table[slot as usize];
+                    return SerializableColor::Rgb { r, g, b };
+                }
+                16..=231 => indexed_to_rgb(index),
+                // Black, white and the grayscale ramp are already safe.
+                _ => return color.clone(),
+            },
+            SerializableColor::Rgb { r, g, b } => (r, g, b),
+        };
+        let (r, g, b) = self.daltonize(r, g, b);
+        SerializableColor::Rgb { r, g, b }
+    }
+
+    /// Shift the detail a red–green deficiency loses into the green and blue
+    /// channels (Fidaner et al.), so distinct colors stay distinct.
+    fn daltonize(self, r: u8, g: u8, b: u8) -> (u8, u8, u8) {
+        let (r, g, b) = (f32::from(r), f32::from(g), f32::from(b));
+        let l = 17.8824 * r + 43.5161 * g + 4.11935 * b;
+        let m = 3.45565 * r + 27.1554 * g + 3.86714 * b;
+        let s = 0.0299566 * r + 0.184309 * g + 1.46709 * b;
+        let (l, m) = if self == ColorFilter::Protanopia {
+            (2.02344 * m - 2.52581 * s, m)
+        } else {
+            (l, 0.494207 * l + 1.24827 * s)
+        };
+        let sim_r = 0.0809444 * l - 0.130504 * m + 0.116721 * s;
+        let sim_g = -0.0102485 * l + 0.0540193 * m - 0.113615 * s;
+        let sim_b = -0.000365297 * l - 0.00412161 * m + 0.693511 * s;
+        let (err_r, err_g, err_b) = (r - sim_r, g - sim_g, b - sim_b);
+        let clamp = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+        (
+            clamp(r),
+            clamp(g + 0.7 * err_r + err_g),
+            clamp(b + 0.7 * err_r + err_b),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::color::Color;
+    use crate::terminal::cursor::CellAttrs;
+
+    fn span(fg: Color, bg: Color) -> StyledSpan {
+        StyledSpan::new("x", 1, fg, bg, Color::Default, CellAttrs::DIM)
+    }
+
+    fn rgb(color: &SerializableColor) -> (u8, u8, u8) {
+        match *color {
+            SerializableColor::Rgb { r, g, b } => (r, g, b),
+            ref other => panic!("expected rgb, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn red_green_filters_remap_ansi_and_keep_neutrals() {
+        let mut spans = vec![
+            span(Color::Indexed(1), Color::Indexed(2)),
+            span(Color::Default, Color::Indexed(232)),
+            span(Color::Indexed(7), Color::Rgb(128, 128, 128)),
+        ];
+        ColorFilter::Deuteranopia.apply(&mut spans);
+        assert_eq!(rgb(&spans[0].fg), DEUTERANOPIA_ANSI[0]);
+        assert_eq!(rgb(&spans[0].bg), DEUTERANOPIA_ANSI[1]);
+        assert!(matches!(spans[1].fg, SerializableColor::Default));
+        assert!(matches!(
+            spans[1].bg,
+            SerializableColor::Indexed { index: 232 }
+        ));
+        assert!(matches!(
+            spans[2].fg,
+            SerializableColor::Indexed { index: 7 }
+        ));
+        let (r, g, b) = rgb(&spans[2].bg);
+        assert!(r.abs_diff(128) <= 2 && g.abs_diff(128) <= 2 && b.abs_diff(128) <= 2);
+    }
+
+    #[test]
+    fn daltonize_separates_red_and_green() {
+        for filter in [ColorFilter::Deuteranopia, ColorFilter::Protanopia] {
+            let red = filter.daltonize(220, 40, 40);
+            let green = filter.daltonize(40, 180, 40);
+            // Red picks up blue, which red–green deficiencies still see.
+            assert!(red.2 > green.2, "{:?}: {:?} vs {:?}", filter, red, green);
+        }
+    }
+
+    #[test]
+    fn high_contrast_forces_theme_colors() {
+        let mut spans = vec![
+            span(Color::Indexed(8), Color::Default),
+            span(Color::Rgb(90, 90, 90), Color::Rgb(60, 60, 60)),
+        ];
+        ColorFilter::HighContrast.apply(&mut spans);
+        assert!(matches!(spans[0].fg, SerializableColor::Default));
+        assert!(!spans[0].dim);
+        assert!(matches!(
+            spans[1].fg,
+            SerializableColor::Indexed { index: 0 }
+        ));
+        assert!(matches!(
+            spans[1].bg,
+            SerializableColor::Indexed { index: 15 }
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+//! Minimum contrast between text and its background.
+//!
+//! Output colored for a different theme can end up unreadable, like dark
+//! blue on a black background. With a minimum ratio set, a span whose
+//! foreground falls short is lightened or darkened until it meets the ratio
+//! against its background, as WezTerm and kitty do. Contrast is the WCAG
+//! ratio, from 1 (no contrast) to 21 (black on white).
+//!
+//! Default and ANSI colors are judged by the backend's palette, which
+//! matches the dark theme; lighter themes may see slightly different ratios.
+
+use super::frame::StyledSpan;
+use crate::terminal::color::{DynamicColors, Palette, SerializableColor};
+
+/// Lowest ratio a session can ask for; it leaves every color as it is.
+pub const MIN_CONTRAST_OFF: f32 = 1.0;
+/// Highest ratio there is: black on white.
+pub const MAX_CONTRAST: f32 = 21.0;
+
+type Rgb = (u8, u8, u8);
+
+/// WCAG relative luminance of an sRGB color.
+fn luminance((r, g, b): Rgb) -> f32 {
+    let channel = |v: u8| {
+        let v = f32::from(v) / 255.0;
+        if v <= 0.04045 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(r) + 0.7152 * channel(g) + 0.0722 * channel(b)
+}
+
+pub fn contrast_ratio(a: Rgb, b: Rgb) -> f32 {
+    let (la, lb) = (luminance(a), luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+fn mix(from: Rgb, to: Rgb, t: f32) -> Rgb {
+    let lerp = |a: u8, b: u8| (f32::from(a) + (f32::from(b) - f32::from(a)) * t).round() as u8;
+    (lerp(from.0, to.0), lerp(from.1, to.1), lerp(from.2, to.2))
+}
+
+/// `fg` moved toward white or black, whichever gets there sooner, just far
+/// enough to reach `min` against `bg`. When neither can, the one with more
+/// contrast.
+pub fn adjust(fg: Rgb, bg: Rgb, min: f32) -> Option<Rgb> {
+    if contrast_ratio(fg, bg) >= min {
+        return None;
+    }
+    let reach = |target: Rgb| -> Option<(f32, Rgb)> {
+        if contrast_ratio(target, bg) < min {
+            return None;
+        }
+        // Contrast grows monotonically along the way, so bisect for the
+        // smallest step that is enough.
+        let (mut lo, mut hi) = (0.0_f32, 1.0_f32);
+        for _ in 0..12 {
+            let mid = (lo + hi) / 2.0;
+            if contrast_ratio(mix(fg, target, mid), bg) >= min {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+        Some((hi, mix(fg, target, hi)))
+    };
+    const WHITE: Rgb = (255, 255, 255);
+    const BLACK: Rgb = (0, 0, 0);
+    match (reach(WHITE), reach(BLACK)) {
+        (Some((up, lighter)), Some((down, darker))) => {
+            Some(if up <= down { lighter } else { darker })
+        }
+        (Some((_, lighter)), None) => Some(lighter),
+        (None, Some((_, darker))) => Some(darker),
+        (None, None) if contrast_ratio(WHITE, bg) >= contrast_ratio(BLACK, bg) => Some(WHITE),
+        (None, None) => Some(BLACK),
+    }
+}
+
+fn rgb(color: &SerializableColor, default: Rgb, palette: &Palette) -> Rgb {
+    match *color {
+        SerializableColor::Default => default,
+        SerializableColor::Indexed { index } => palette.rgb(index),
+        SerializableColor::Rgb { r, g, b } => (r, g, b),
+    }
+}
+
+/// Give every span's text at least `min` contrast against its background.
+/// Spans left in the theme's own colors, blank spans and hidden text (drawn
+/// in its background color) are left alone.
+pub fn apply(spans: &mut [StyledSpan], min: f32, palette: &Palette, colors: &DynamicColors) {
+    if min <= MIN_CONTRAST_OFF {
+        return;
+    }
+    for span in spans {
+        let themed = matches!(
+            (&span.fg, &span.bg),
+            (SerializableColor::Default, SerializableColor::Default)
+        );
+        if themed || span.text.trim().is_empty() {
+            continue;
+        }
+        let fg = rgb(&span.fg, colors.rgb(0), palette);
+        let bg = rgb(&span.bg, colors.rgb(1), palette);
+        if fg == bg {
+            continue;
+        }
+        if let Some((r, g, b)) = adjust(fg, bg, min) {
+            span.fg = SerializableColor::Rgb { r, g, b };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::color::Color;
+    use crate::terminal::cursor::CellAttrs;
+
+    fn span(text: &str, fg: Color, bg: Color) -> StyledSpan {
+        StyledSpan::new(text, 1, fg, bg, Color::Default, CellAttrs::empty())
+    }
+
+    fn fg_rgb(span: &StyledSpan) -> Rgb {
+        match span.fg {
+            SerializableColor::Rgb { r, g, b } => (r, g, b),
+            ref other => panic!("expected an RGB foreground, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn dark_text_on_dark_backgrounds_is_lifted_to_the_ratio() {
+        let palette = Palette::default();
+        let colors = DynamicColors::default();
+        let mut spans = vec![
+            span("ls", Color::Rgb(0, 0, 0x80), Color::Default),
+            span("ok", Color::Rgb(0xff, 0xff, 0xff), Color::Rgb(0, 0, 0)),
+            span("plain", Color::Default, Color::Default),
+            span("secret", Color::Rgb(9, 9, 9), Color::Rgb(9, 9, 9)),
+        ];
+        apply(&mut spans, 4.5, &palette, &colors);
+
+        let lifted = fg_rgb(&spans[0]);
+        let ratio = contrast_ratio(lifted, colors.rgb(1));
+        assert!((4.5..5.0).contains(&ratio), "ratio {ratio}");
+        // Lifted toward white, keeping its blue cast.
+        assert!(lifted.2 > lifted.0);
+        assert_eq!(fg_rgb(&spans[1]), (0xff, 0xff, 0xff));
+        assert!(matches!(spans[2].fg, SerializableColor::Default));
+        assert_eq!(fg_rgb(&spans[3]), (9, 9, 9));
+    }
+
+    #[test]
+    fn light_backgrounds_darken_text_and_off_changes_nothing() {
+        assert_eq!(adjust((0, 0, 0), (255, 255, 255), 20.0), None);
+        let darker = adjust((0xee, 0xee, 0x00), (255, 255, 255), 3.0).unwrap();
+        assert!(contrast_ratio(darker, (255, 255, 255)) >= 3.0);
+        assert!(darker.0 < 0xee);
+
+        let mut spans = vec![span("x", Color::Rgb(1, 1, 1), Color::Rgb(0, 0, 0))];
+        apply(
+            &mut spans,
+            MIN_CONTRAST_OFF,
+            &Palette::default(),
+            &DynamicColors::default(),
+        );
+        assert_eq!(fg_rgb(&spans[0]), (1, 1, 1));
+    }
+}
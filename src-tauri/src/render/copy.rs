@@ -0,0 +1,241 @@
+//! Plain-text renderings of grid rows for the clipboard, normalized from the
+//! cell data rather than from what the frontend happened to render.
+
+use serde::Deserialize;
+
+use crate::terminal::grid::{Cell, CellFlags, Row};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CopyOptions {
+    /// Strip trailing whitespace from every line.
+    pub trim_trailing_whitespace: bool,
+    /// Drop prompt decorations (OSC 133 prompt regions), keeping the command.
+    pub strip_prompts: bool,
+    /// Collapse into a Markdown fenced code block, commands as `$ command`.
+    /// Implies `strip_prompts` and `trim_trailing_whitespace`.
+    pub fenced: bool,
+}
+
+/// A prompt within the copied rows, as indices into them. Rows from `first`
+/// up to `command_row` are decoration; `command_row` holds the prompt's tail
+/// followed by the typed command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptRegion {
+    pub first: usize,
+    pub command_row: usize,
+    /// Column where typed input starts, from a bare `OSC 133;B`.
+    pub input_col: Option<u16>,
+    pub command: Option<String>,
+}
+
+fn cells_text(row: &Row, from_col: usize) -> String {
+    row.cells
+        .iter()
+        .skip(from_col)
+        .filter(|c| !c.flags.contains(CellFlags::WIDE_SPACER))
+        .flat_map(Cell::chars)
+        .collect()
+}
+
+/// The typed command on a prompt's last row, without the prompt in front.
+fn command_input(row: &Row, region: &PromptRegion) -> String {
+    if let Some(col) = region.input_col {
+        return cells_text(row, col as usize).trim_end().to_string();
+    }
+    let line = cells_text(row, 0);
+    match region.command.as_deref() {
+        Some(command) => match line.rfind(command) {
+            Some(idx) => line[idx..].trim_end().to_string(),
+            // Wrapped or continued input: the recorded command is exact.
+            None => command.to_string(),
+        },
+        None => line.trim_end().to_string(),
+    }
+}
+
+/// Backtick fence longer than any backtick run in `text`.
+fn fence_for(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+pub fn rows_to_text(rows: &[&Row], prompts: &[PromptRegion], options: CopyOptions) -> String {
+    let strip = options.strip_prompts || options.fenced;
+    let mut lines: Vec<String> = Vec::with_capacity(rows.len());
+    for (idx, row) in rows.iter().enumerate() {
+        let region = strip
+            .then(|| {
+                prompts
+                    .iter()
+                    .find(|r| (r.first..=r.command_row).contains(&idx))
+            })
+            .flatten();
+        let line = match region {
+            Some(region) if idx < region.command_row => continue,
+            Some(region) => {
+                let input = command_input(row, region);
+                if input.is_empty() {
+                    continue;
+                }
+                if options.fenced {
+                    format!("$ {}", input)
+                } else {
+                    input
+                }
+            }
+            None => cells_text(row, 0),
+        };
+        lines.push(if options.trim_trailing_whitespace || options.fenced {
+            line.trim_end().to_string()
+        } else {
+            line
+        });
+    }
+    while lines.last().is_some_and(|l| l.trim().is_empty()) {
+        lines.pop();
+    }
+
+    let text = lines.join("\n");
+    if options.fenced {
+        let fence = fence_for(&text);
+        format!("{}\n{}\n{}\n", fence, text, fence)
+    } else {
+        text
+    }
+}
+
+/// A block as a ready-to-paste Markdown snippet for docs: the command and
+/// its output in a `console` fence, output cut after `max_output_lines`
+/// (0 for no limit) with a note, and the exit status as a trailing comment.
+pub fn block_markdown(
+    command: Option<&str>,
+    output: &[&Row],
+    exit_code: Option<i32>,
+    max_output_lines: usize,
+) -> String {
+    let mut output: Vec<String> = output
+        .iter()
+        .map(|row| cells_text(row, 0).trim_end().to_string())
+        .collect();
+    while output.last().is_some_and(|l| l.is_empty()) {
+        output.pop();
+    }
+
+    let mut lines = Vec::with_capacity(output.len().min(max_output_lines) + 3);
+    if let Some(command) = command {
+        lines.push(format!("$ {}", command));
+    }
+    if max_output_lines > 0 && output.len() > max_output_lines {
+        let omitted = output.len() - max_output_lines;
+        output.truncate(max_output_lines);
+        lines.extend(output);
+        lines.push(format!(
+            "… ({} more line{})",
+            omitted,
+            if omitted == 1 { "" } else { "s" }
+        ));
+    } else {
+        lines.extend(output);
+    }
+    if let Some(code) = exit_code {
+        lines.push(format!("# exit status: {}", code));
+    }
+
+    let text = lines.join("\n");
+    let fence = fence_for(&text);
+    format!("{}console\n{}\n{}\n", fence, text, fence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::grid::Cell;
+
+    fn row(text: &str) -> Row {
+        let mut row = Row::new(20);
+        for (cell, c) in row.cells.iter_mut().zip(text.chars()) {
+            *cell = Cell {
+                c,
+                ..Cell::default()
+            };
+        }
+        row
+    }
+
+    #[test]
+    fn trailing_whitespace_is_optional() {
+        let rows = [row("a  "), row("b")];
+        let refs: Vec<&Row> = rows.iter().collect();
+        let raw = rows_to_text(&refs, &[], CopyOptions::default());
+        assert_eq!(raw.lines().next().unwrap().len(), 20);
+        let trimmed = CopyOptions {
+            trim_trailing_whitespace: true,
+            ..Default::default()
+        };
+        assert_eq!(rows_to_text(&refs, &[], trimmed), "a\nb");
+    }
+
+    #[test]
+    fn prompts_are_stripped_to_the_command() {
+        let rows = [row("~/src (main)"), row("❯ ls -la"), row("total 0")];
+        let refs: Vec<&Row> = rows.iter().collect();
+        let prompt = PromptRegion {
+            first: 0,
+            command_row: 1,
+            input_col: None,
+            command: Some("ls -la".to_string()),
+        };
+        let options = CopyOptions {
+            trim_trailing_whitespace: true,
+            strip_prompts: true,
+            fenced: false,
+        };
+        assert_eq!(
+            rows_to_text(&refs, std::slice::from_ref(&prompt), options),
+            "ls -la\ntotal 0"
+        );
+
+        // A bare OSC 133;B marks the input column exactly.
+        let marked = PromptRegion {
+            input_col: Some(2),
+            command: None,
+            ..prompt
+        };
+        assert_eq!(rows_to_text(&refs, &[marked], options), "ls -la\ntotal 0");
+    }
+
+    #[test]
+    fn fenced_output_uses_a_longer_fence_than_the_content() {
+        let rows = [row("$ echo"), row("```")];
+        let refs: Vec<&Row> = rows.iter().collect();
+        let prompt = PromptRegion {
+            first: 0,
+            command_row: 0,
+            input_col: None,
+            command: Some("echo".to_string()),
+        };
+        let options = CopyOptions {
+            fenced: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            rows_to_text(&refs, &[prompt], options),
+            "````\n$ echo\n```\n````\n"
+        );
+    }
+
+    #[test]
+    fn block_markdown_truncates_and_reports_exit_status() {
+        let rows = [row("a"), row("b"), row("c"), row("")];
+        let refs: Vec<&Row> = rows.iter().collect();
+        assert_eq!(
+            block_markdown(Some("make"), &refs, Some(2), 2),
+            "```console\n$ make\na\nb\n… (1 more line)\n# exit status: 2\n```\n"
+        );
+        assert_eq!(
+            block_markdown(None, &refs, None, 0),
+            "```console\na\nb\nc\n```\n"
+        );
+    }
+}
@@ -0,0 +1,318 @@
+//! Standalone ANSI and HTML renderings of grid rows, for output that leaves
+//! the app (sharing, saving) with its formatting intact.
+
+use serde::Deserialize;
+
+use crate::terminal::color::{indexed_to_rgb, Color};
+use crate::terminal::cursor::CellAttrs;
+use crate::terminal::grid::{Cell, CellFlags, Row};
+
+/// Default colors of exported HTML (the default dark theme).
+const HTML_FG: (u8, u8, u8) = (0xc0, 0xca, 0xf5);
+const HTML_BG: (u8, u8, u8) = (0x1a, 0x1b, 0x26);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Ansi,
+    Text,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Ansi => "ans",
+            ExportFormat::Text => "txt",
+            ExportFormat::Html => "html",
+        }
+    }
+
+    /// Format implied by a file extension, for a save dialog where the user
+    /// may pick another file type than the one offered first.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "ans" | "ansi" => Some(ExportFormat::Ansi),
+            "txt" | "log" => Some(ExportFormat::Text),
+            "html" | "htm" => Some(ExportFormat::Html),
+            _ => None,
+        }
+    }
+
+    /// Name of the format in save dialog filters.
+    pub fn description(self) -> &'static str {
+        match self {
+            ExportFormat::Ansi => "ANSI text",
+            ExportFormat::Text => "Plain text",
+            ExportFormat::Html => "HTML page",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Ansi | ExportFormat::Text => "text/plain; charset=utf-8",
+            ExportFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+
+    pub fn render(self, rows: &[&Row], title: &str) -> String {
+        match self {
+            ExportFormat::Ansi => rows_to_ansi(rows),
+            ExportFormat::Text => rows_to_text(rows),
+            ExportFormat::Html => rows_to_html(rows, title),
+        }
+    }
+}
+
+/// Main-grid rows to export: a line range by stable line IDs (inclusive),
+/// or the command and output of a block.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExportRange {
+    #[serde(rename_all = "camelCase")]
+    Lines {
+        start_line_id: u64,
+        end_line_id: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    Block { block_id: String },
+}
+
+/// A run of cells sharing one style.
+struct Run {
+    text: String,
+    fg: Color,
+    bg: Color,
+    attrs: CellAttrs,
+}
+
+/// Style runs of a row, without trailing blank cells.
+fn runs(row: &Row) -> Vec<Run> {
+    let blank = Cell::default();
+    let len = row
+        .cells
+        .iter()
+        .rposition(|cell| *cell != blank)
+        .map_or(0, |idx| idx + 1);
+    let mut runs: Vec<Run> = Vec::new();
+    for cell in &row.cells[..len] {
+        if cell.flags.contains(CellFlags::WIDE_SPACER) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if run.fg == cell.fg && run.bg == cell.bg && run.attrs == cell.attrs => {
+                run.text.extend(cell.chars());
+            }
+            _ => runs.push(Run {
+                text: cell.chars().collect(),
+                fg: cell.fg,
+                bg: cell.bg,
+                attrs: cell.attrs,
+            }),
+        }
+    }
+    runs
+}
+
+/// Rows with trailing blank rows dropped.
+fn trimmed(rows: &[&Row]) -> Vec<Vec<Run>> {
+    let mut rendered: Vec<Vec<Run>> = rows.iter().map(|row| runs(row)).collect();
+    while rendered.last().is_some_and(Vec::is_empty) {
+        rendered.pop();
+    }
+    rendered
+}
+
+fn sgr_color(out: &mut Vec<String>, color: Color, base: u8) {
+    match color {
+        Color::Default => {}
+        Color::Indexed(i) if i < 8 => out.push((base + i).to_string()),
+        Color::Indexed(i) if i < 16 => out.push((base + 60 + i - 8).to_string()),
+        Color::Indexed(i) => out.push(format!("{};5;{}", base + 8, i)),
+        Color::Rgb(r, g, b) => out.push(format!("{};2;{};{};{}", base + 8, r, g, b)),
+    }
+}
+
+/// Text with SGR escapes, one line per row.
+pub fn rows_to_ansi(rows: &[&Row]) -> String {
+    const ATTR_CODES: [(CellAttrs, &str); 9] = [
+        (CellAttrs::BOLD, "1"),
+        (CellAttrs::DIM, "2"),
+        (CellAttrs::ITALIC, "3"),
+        (CellAttrs::UNDERLINE, "4"),
+        (CellAttrs::BLINK, "5"),
+        (CellAttrs::RAPID_BLINK, "6"),
+        (CellAttrs::REVERSE, "7"),
+        (CellAttrs::HIDDEN, "8"),
+        (CellAttrs::STRIKETHROUGH, "9"),
+    ];
+    let mut lines = Vec::new();
+    for runs in trimmed(rows) {
+        let mut line = String::new();
+        for run in runs {
+            let mut codes = vec!["0".to_string()];
+            for (attr, code) in ATTR_CODES {
+                if run.attrs.contains(attr) {
+                    codes.push(code.to_string());
+                }
+            }
+            sgr_color(&mut codes, run.fg, 30);
+            sgr_color(&mut codes, run.bg, 40);
+            if codes.len() > 1 {
+                line.push_str(&format!("\x1b[{}m{}\x1b[0m", codes.join(";"), run.text));
+            } else {
+                line.push_str(&run.text);
+            }
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+/// Text without styling, one line per row.
+pub fn rows_to_text(rows: &[&Row]) -> String {
+    trimmed(rows)
+        .into_iter()
+        .map(|runs| runs.into_iter().map(|run| run.text).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn css_rgb(color: Color, default: (u8, u8, u8)) -> (u8, u8, u8) {
+    match color {
+        Color::Default => default,
+        Color::Indexed(i) => indexed_to_rgb(i),
+        Color::Rgb(r, g, b) => (r, g, b),
+    }
+}
+
+fn escape_html(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+/// A self-contained HTML page with the rows in a styled `<pre>`.
+pub fn rows_to_html(rows: &[&Row], title: &str) -> String {
+    let mut body = String::new();
+    for (i, runs) in trimmed(rows).into_iter().enumerate() {
+        if i > 0 {
+            body.push('\n');
+        }
+        for run in runs {
+            let mut fg = css_rgb(run.fg, HTML_FG);
+            let mut bg = (run.bg != Color::Default).then(|| css_rgb(run.bg, HTML_BG));
+            if run.attrs.contains(CellAttrs::REVERSE) {
+                (fg, bg) = (bg.unwrap_or(HTML_BG), Some(fg));
+            }
+            let mut style = Vec::new();
+            if run.attrs.contains(CellAttrs::HIDDEN) {
+                style.push("visibility:hidden".to_string());
+            }
+            if fg != HTML_FG {
+                style.push(format!("color:#{:02x}{:02x}{:02x}", fg.0, fg.1, fg.2));
+            }
+            if let Some(bg) = bg {
+                style.push(format!("background:#{:02x}{:02x}{:02x}", bg.0, bg.1, bg.2));
+            }
+            if run.attrs.contains(CellAttrs::BOLD) {
+                style.push("font-weight:bold".to_string());
+            }
+            if run.attrs.contains(CellAttrs::DIM) {
+                style.push("opacity:0.6".to_string());
+            }
+            if run.attrs.contains(CellAttrs::ITALIC) {
+                style.push("font-style:italic".to_string());
+            }
+            let decorations: Vec<&str> = [
+                (CellAttrs::UNDERLINE, "underline"),
+                (CellAttrs::STRIKETHROUGH, "line-through"),
+            ]
+            .into_iter()
+            .filter(|(attr, _)| run.attrs.contains(*attr))
+            .map(|(_, name)| name)
+            .collect();
+            if !decorations.is_empty() {
+                style.push(format!("text-decoration:{}", decorations.join(" ")));
+            }
+
+            if style.is_empty() {
+                escape_html(&run.text, &mut body);
+            } else {
+                body.push_str(&format!("<span style=\"{}\">", style.join(";")));
+                escape_html(&run.text, &mut body);
+                body.push_str("</span>");
+            }
+        }
+    }
+
+    let mut escaped_title = String::new();
+    escape_html(title, &mut escaped_title);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n\
+         <body style=\"margin:0;background:#{bg}\">\n\
+         <pre style=\"margin:0;padding:12px;color:#{fg};background:#{bg};\
+         font-family:'JetBrains Mono',Menlo,Consolas,monospace;font-size:13px\">{body}</pre>\n\
+         </body>\n</html>\n",
+        title = escaped_title,
+        fg = format_args!("{:02x}{:02x}{:02x}", HTML_FG.0, HTML_FG.1, HTML_FG.2),
+        bg = format_args!("{:02x}{:02x}{:02x}", HTML_BG.0, HTML_BG.1, HTML_BG.2),
+        body = body,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::TerminalState;
+
+    fn rows_of(input: &[u8]) -> Vec<Row> {
+        let mut state = TerminalState::new(4, 20);
+        state.feed(input);
+        (0..4).map(|r| state.grid.visible_row(r).clone()).collect()
+    }
+
+    #[test]
+    fn ansi_keeps_styles_and_trims_blank_space() {
+        let rows = rows_of("\x1b[1;31mok\x1b[0m done  \r\n漢\x1b[38;2;1;2;3mx".as_bytes());
+        let refs: Vec<&Row> = rows.iter().collect();
+        assert_eq!(
+            rows_to_ansi(&refs),
+            "\x1b[0;1;31mok\x1b[0m done\n漢\x1b[0;38;2;1;2;3mx\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn text_drops_styles_and_formats_follow_extensions() {
+        let rows = rows_of("\x1b[1;31mok\x1b[0m done  \r\n漢\x1b[38;2;1;2;3mx\r\n\r\n".as_bytes());
+        let refs: Vec<&Row> = rows.iter().collect();
+        assert_eq!(ExportFormat::Text.render(&refs, ""), "ok done\n漢x");
+        assert_eq!(
+            ExportFormat::from_extension("HTM"),
+            Some(ExportFormat::Html)
+        );
+        assert_eq!(
+            ExportFormat::from_extension("log"),
+            Some(ExportFormat::Text)
+        );
+        assert_eq!(ExportFormat::from_extension("png"), None);
+    }
+
+    #[test]
+    fn html_escapes_and_colors() {
+        let rows = rows_of(b"<a>&\x1b[7m!\x1b[0m\x1b[44m \x1b[0m");
+        let refs: Vec<&Row> = rows.iter().collect();
+        let html = rows_to_html(&refs, "ls <dir>");
+        assert!(html.contains("<title>ls &lt;dir&gt;</title>"));
+        assert!(html.contains("&lt;a&gt;&amp;"));
+        // Reverse video swaps the theme colors.
+        assert!(html.contains("<span style=\"color:#1a1b26;background:#c0caf5\">!</span>"));
+        assert!(html.contains("background:#7aa2f7\"> </span>"));
+    }
+}
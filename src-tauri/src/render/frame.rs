@@ -1,11 +1,71 @@
+use std::collections::HashMap;
+
+use serde::ser::{SerializeStruct, Serializer};
 use serde::Serialize;
 
-use crate::terminal::color::{Color, SerializableColor};
-use crate::terminal::cursor::CellAttrs;
+use super::links::{LinkTarget, PathLink};
+use super::search::CellRect;
+use super::structured::StructuredKind;
+use crate::shell::capabilities::Capabilities;
+use crate::shell::git::GitRef;
+use crate::shell::QueuedCommand;
+use crate::terminal::color::{Color, PaletteEntry, SerializableColor};
+use crate::terminal::cursor::{BlinkRate, CellAttrs, UnderlineStyle};
+use crate::terminal::grid::LineSize;
+
+/// Version of the event wire format. Bump it when an event variant or field
+/// is added, removed or changes meaning, and keep the type lists below (and
+/// `FRONTEND_EVENT_SCHEMA` in the frontend) in step.
+pub const EVENT_SCHEMA_VERSION: u32 = 19;
+
+/// `type` tags of every [`TerminalEvent`] variant.
+pub const TERMINAL_EVENT_TYPES: &[&str] = &[
+    "BlockStarted",
+    "BlockCommand",
+    "BlockCompleted",
+    "TitleChanged",
+    "AltScreenEntered",
+    "AltScreenExited",
+    "Bell",
+    "CwdChanged",
+    "SessionNameChanged",
+    "CustomOsc",
+    "MouseModeChanged",
+    "ScrollbackCleared",
+    "ScrollbackRewrapped",
+    "InlineImage",
+    "FileDownload",
+    "SixelImage",
+    "KittyImage",
+    "TmuxRequested",
+    "CommandQueueChanged",
+    "RemotePromptDetected",
+    "ColumnModeChanged",
+    "BlockStructuredData",
+    "PaletteChanged",
+    "DynamicColorsChanged",
+    "Notification",
+    "ProgressChanged",
+    "TerminalCapabilitiesChanged",
+    "SelectionCleared",
+];
+
+/// What this backend emits, so a frontend built against a different version
+/// can tell which events to expect. Frontends must skip (and may pass on to
+/// generic listeners) event types they don't know rather than fail on them.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventSchema {
+    pub version: u32,
+    pub terminal_events: &'static [&'static str],
+    pub tmux_events: &'static [&'static str],
+}
 
 /// A complete render frame sent to the frontend via IPC.
 #[derive(Debug, Clone, Serialize)]
 pub struct RenderFrame {
+    /// [`EVENT_SCHEMA_VERSION`] of the backend that produced the frame.
+    pub event_schema: u32,
     /// Monotonic sequence for frame ordering (newer frames have larger values).
     pub frame_seq: u64,
     /// Monotonic resize generation. Increments on each terminal resize.
@@ -26,6 +86,45 @@ pub struct RenderFrame {
     pub cursor: CursorRender,
     /// Terminal events (block changes, title, mode switches)
     pub events: Vec<TerminalEvent>,
+    /// True when `lines` holds the full viewport because the frontend fell
+    /// behind on frame acknowledgments.
+    pub keyframe: bool,
+    /// Kitty keyboard enhancement flags of the active screen, for the
+    /// frontend's key encoder.
+    pub keyboard_flags: u8,
+    /// DECSCNM: the frontend swaps the default foreground and background.
+    pub reverse_video: bool,
+    /// Matches of the `search_visible` pattern on the active screen,
+    /// refreshed whenever lines changed; omitted while no search is active
+    /// or the screen didn't change.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visible_search: Option<Vec<CellRect>>,
+    /// Distinct span styles of this frame; spans name theirs by index in
+    /// `style` instead of repeating it. Filled by [`RenderFrame::intern_styles`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub styles: Vec<SpanStyle>,
+}
+
+impl RenderFrame {
+    /// Move every span's style into [`RenderFrame::styles`], so colorful
+    /// output (`ls --color`, diffs) sends each style once per frame.
+    pub fn intern_styles(&mut self) {
+        let mut index: HashMap<SpanStyle, u32> = HashMap::new();
+        let spans = self
+            .lines
+            .iter_mut()
+            .chain(self.scrolled_lines.iter_mut())
+            .flat_map(|line| line.spans.iter_mut());
+        for span in spans {
+            let style = span.style();
+            let next = self.styles.len() as u32;
+            let id = *index.entry(style).or_insert_with_key(|style| {
+                self.styles.push(style.clone());
+                next
+            });
+            span.style = Some(id);
+        }
+    }
 }
 
 /// A single rendered line with pre-segmented styled spans.
@@ -33,12 +132,43 @@ pub struct RenderFrame {
 pub struct RenderedLine {
     /// Screen-relative row index (0 = top of visible area)
     pub index: u32,
+    /// Stable line identity; unlike `index` and global rows it does not
+    /// shift when the grid scrolls, resizes or resets.
+    pub line_id: u64,
+    /// DEC double-width/double-height attribute; omitted for normal lines.
+    #[serde(skip_serializing_if = "LineSize::is_single")]
+    pub line_size: LineSize,
+    /// Set for a partial update: `spans` replace the cells from this column
+    /// on, as many as they cover, and the rest of the line is unchanged.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_col: Option<u32>,
     /// Styled text segments
     pub spans: Vec<StyledSpan>,
 }
 
-/// A contiguous run of text sharing the same style.
+/// Most rows one `get_scrollback_range` call returns.
+pub const MAX_SCROLLBACK_PAGE: usize = 5_000;
+
+/// A page of main-grid scrollback for hydrating history that was never
+/// streamed to a frontend in `scrolled_lines`.
 #[derive(Debug, Clone, Serialize)]
+pub struct ScrollbackPage {
+    /// Global row of `lines[0]`; later than the requested row when older
+    /// rows were already trimmed.
+    pub start_global_row: u64,
+    /// Global row of the oldest row still in scrollback.
+    pub first_global_row: u64,
+    /// Global row of visible row 0; scrollback ends just before it.
+    pub visible_base_global: u64,
+    /// Rows in order; `index` is the offset from `start_global_row`.
+    pub lines: Vec<RenderedLine>,
+}
+
+/// A contiguous run of text sharing the same style.
+///
+/// Serialized with its style inline, or as `style`, an index into the
+/// frame's [`RenderFrame::styles`], once the frame interned its styles.
+#[derive(Debug, Clone)]
 pub struct StyledSpan {
     pub text: String,
     pub cols: u32,
@@ -48,13 +178,83 @@ pub struct StyledSpan {
     pub dim: bool,
     pub italic: bool,
     pub underline: bool,
+    /// Underline variant; omitted for plain underlines.
+    pub underline_style: UnderlineStyle,
+    /// SGR 58 underline color; omitted when the underline follows `fg`.
+    pub underline_color: Option<SerializableColor>,
     pub strikethrough: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// SGR 5/6 blinking; omitted for steady text.
+    pub blink: Option<BlinkRate>,
+    /// OSC 8 hyperlink or a URL found in the text.
     pub url: Option<String>,
+    /// File path found in the text, with any `:line:col` after it.
+    pub path: Option<PathLink>,
+    /// Index of this span's style in its frame's style table.
+    pub style: Option<u32>,
+}
+
+/// The look of a span: everything but its text and links.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct SpanStyle {
+    pub fg: SerializableColor,
+    pub bg: SerializableColor,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    #[serde(skip_serializing_if = "UnderlineStyle::is_single")]
+    pub underline_style: UnderlineStyle,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub underline_color: Option<SerializableColor>,
+    pub strikethrough: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blink: Option<BlinkRate>,
+}
+
+impl Serialize for StyledSpan {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut out = serializer.serialize_struct("StyledSpan", 15)?;
+        out.serialize_field("text", &self.text)?;
+        out.serialize_field("cols", &self.cols)?;
+        if let Some(style) = self.style {
+            out.serialize_field("style", &style)?;
+        } else {
+            out.serialize_field("fg", &self.fg)?;
+            out.serialize_field("bg", &self.bg)?;
+            out.serialize_field("bold", &self.bold)?;
+            out.serialize_field("dim", &self.dim)?;
+            out.serialize_field("italic", &self.italic)?;
+            out.serialize_field("underline", &self.underline)?;
+            if !self.underline_style.is_single() {
+                out.serialize_field("underline_style", &self.underline_style)?;
+            }
+            if let Some(color) = &self.underline_color {
+                out.serialize_field("underline_color", color)?;
+            }
+            out.serialize_field("strikethrough", &self.strikethrough)?;
+            if let Some(blink) = &self.blink {
+                out.serialize_field("blink", blink)?;
+            }
+        }
+        if let Some(url) = &self.url {
+            out.serialize_field("url", url)?;
+        }
+        if let Some(path) = &self.path {
+            out.serialize_field("path", path)?;
+        }
+        out.end()
+    }
 }
 
 impl StyledSpan {
-    pub fn new(text: &str, cols: u32, fg: Color, bg: Color, attrs: CellAttrs) -> Self {
+    pub fn new(
+        text: &str,
+        cols: u32,
+        fg: Color,
+        bg: Color,
+        underline_color: Color,
+        attrs: CellAttrs,
+    ) -> Self {
         // SGR 7 (REVERSE): swap foreground and background colors
         let (fg, bg) = if attrs.contains(CellAttrs::REVERSE) {
             (bg, fg)
@@ -68,6 +268,11 @@ impl StyledSpan {
         } else {
             fg
         };
+        let underline_style = attrs.underline_style();
+        let underline_color = (underline_style.is_some()
+            && underline_color != Color::Default
+            && !attrs.contains(CellAttrs::HIDDEN))
+        .then(|| underline_color.into());
 
         Self {
             text: text.to_string(),
@@ -77,10 +282,55 @@ impl StyledSpan {
             bold: attrs.contains(CellAttrs::BOLD),
             dim: attrs.contains(CellAttrs::DIM),
             italic: attrs.contains(CellAttrs::ITALIC),
-            underline: attrs.contains(CellAttrs::UNDERLINE),
+            underline: underline_style.is_some(),
+            underline_style: underline_style.unwrap_or_default(),
+            underline_color,
             strikethrough: attrs.contains(CellAttrs::STRIKETHROUGH),
+            blink: attrs.blink_rate(),
             url: None,
+            path: None,
+            style: None,
+        }
+    }
+
+    /// This span's style, for interning.
+    pub fn style(&self) -> SpanStyle {
+        SpanStyle {
+            fg: self.fg.clone(),
+            bg: self.bg.clone(),
+            bold: self.bold,
+            dim: self.dim,
+            italic: self.italic,
+            underline: self.underline,
+            underline_style: self.underline_style,
+            underline_color: self.underline_color.clone(),
+            strikethrough: self.strikethrough,
+            blink: self.blink,
+        }
+    }
+
+    /// Draw bold text in ANSI colors 0-7 in their bright variants, as
+    /// terminals without a bold font did. Hidden text stays hidden.
+    pub fn brighten_bold(&mut self) {
+        if !self.bold {
+            return;
+        }
+        if let SerializableColor::Indexed { index } = self.fg {
+            let hidden = matches!(self.bg, SerializableColor::Indexed { index: bg } if bg == index);
+            if index < 8 && !hidden {
+                self.fg = SerializableColor::Indexed { index: index + 8 };
+            }
+        }
+    }
+
+    /// Attach a detected link to this span.
+    pub fn with_link(mut self, target: Option<&LinkTarget>) -> Self {
+        match target {
+            Some(LinkTarget::Url(url)) => self.url = Some(url.clone()),
+            Some(LinkTarget::Path(path)) => self.path = Some(path.clone()),
+            None => {}
         }
+        self
     }
 }
 
@@ -104,6 +354,11 @@ pub enum TerminalEvent {
         cwd: String,
         /// Global cursor row at the time of the event
         global_row: u64,
+        /// Line ID of the cursor row at the time of the event
+        line_id: u64,
+        /// Git branch/commit of `cwd`, when it is inside a repository
+        #[serde(skip_serializing_if = "Option::is_none")]
+        git: Option<GitRef>,
     },
     /// The command within a block has been identified
     BlockCommand {
@@ -111,6 +366,8 @@ pub enum TerminalEvent {
         command: String,
         /// Global cursor row at the time of the event
         global_row: u64,
+        /// Line ID of the cursor row at the time of the event
+        line_id: u64,
     },
     /// A command block has completed execution
     BlockCompleted {
@@ -118,6 +375,8 @@ pub enum TerminalEvent {
         exit_code: i32,
         /// Global cursor row at the time of the event
         global_row: u64,
+        /// Line ID of the cursor row at the time of the event
+        line_id: u64,
     },
     /// Terminal title changed (via OSC 0 or OSC 2)
     TitleChanged { title: String },
@@ -129,6 +388,11 @@ pub enum TerminalEvent {
     Bell,
     /// Working directory changed
     CwdChanged { path: String },
+    /// Friendly session name derived from the long-running foreground command
+    /// (or tmux window name). `None` means fall back to the default label.
+    SessionNameChanged { name: Option<String> },
+    /// Payload of a user-registered OSC sequence, forwarded verbatim.
+    CustomOsc { code: u16, payload: String },
     /// Mouse mode flags changed
     MouseModeChanged {
         tracking: bool,
@@ -141,41 +405,154 @@ pub enum TerminalEvent {
         synchronized_output: bool,
         bracketed_paste: bool,
         cursor_keys_application: bool,
+        /// xterm modifyOtherKeys level (0-2).
+        modify_other_keys: u8,
     },
     /// Scrollback buffer was cleared (CSI 3J)
     ScrollbackCleared,
-    /// Inline image data (iTerm2 OSC 1337 protocol)
+    /// Scrollback was re-wrapped to a new width and its global rows
+    /// renumbered; held copies should be refetched.
+    ScrollbackRewrapped,
+    /// Inline image data (iTerm2 OSC 1337 protocol), to be shown `width` x
+    /// `height` pixels large over `columns` x `rows` cells; stretched when
+    /// `preserve_aspect_ratio` is off. Image events carry a `rain-asset`
+    /// `url` and an empty `data_base64` in sessions that store their images
+    /// in the asset registry, and the `line_id` of the row they start on,
+    /// which keeps them anchored as the row scrolls.
     InlineImage {
         id: String,
         data_base64: String,
-        width: u16,
-        height: u16,
+        url: Option<String>,
+        name: Option<String>,
+        width: u32,
+        height: u32,
+        columns: u16,
+        rows: u16,
+        preserve_aspect_ratio: bool,
         row: u16,
         col: u16,
+        line_id: u64,
     },
-    /// Sixel image data (experimental; only emitted when
-    /// RAIN_ENABLE_EXPERIMENTAL_IMAGE_PROTOCOLS=1).
+    /// A program sent a file that isn't meant to be shown (iTerm2 OSC 1337
+    /// without `inline=1`). `save_download` writes it where the user picks.
+    FileDownload {
+        id: String,
+        name: Option<String>,
+        size: u64,
+    },
+    /// Sixel image decoded to PNG, `width` x `height` pixels covering
+    /// `columns` x `rows` cells from `row`/`col` (experimental; only emitted
+    /// when RAIN_ENABLE_EXPERIMENTAL_IMAGE_PROTOCOLS=1).
     SixelImage {
         id: String,
         data_base64: String,
+        url: Option<String>,
         width: u32,
         height: u32,
+        columns: u16,
+        rows: u16,
         row: u16,
         col: u16,
+        line_id: u64,
     },
-    /// Kitty graphics protocol image (experimental scaffold).
+    /// Kitty graphics protocol placement (`action` "place", PNG data) or
+    /// removal ("delete", where a 0 ID matches every image or placement).
+    /// Experimental; only emitted when image protocols are enabled.
     KittyImage {
         id: String,
         action: String,
         data_base64: String,
+        url: Option<String>,
         width: u32,
         height: u32,
         row: u16,
         col: u16,
+        line_id: u64,
         image_id: u32,
         placement_id: u32,
     },
     /// The shell hook intercepted a `tmux` command and requests Rain handle it
     /// via control mode. `args` contains the raw arguments (e.g. "attach -t main").
     TmuxRequested { args: String },
+    /// Commands waiting for an idle prompt were added, run or cancelled.
+    CommandQueueChanged { queued: Vec<QueuedCommand> },
+    /// The prompt of a remote shell reached by the running block's ssh (or
+    /// mosh, ...) command appeared. Rain's hooks can be injected into it
+    /// with `inject_remote_hooks`.
+    RemotePromptDetected { block_id: String, host: String },
+    /// DECCOLM switched the grid to `cols` (132 or 80) columns. The frontend
+    /// may resize the PTY to match or resize back to the pane's width.
+    ColumnModeChanged { cols: u16 },
+    /// The output of completed block `id` is a JSON document or a CSV/TSV
+    /// table; `get_block_structured` returns it parsed. Follows the block's
+    /// `BlockCompleted`.
+    BlockStructuredData {
+        id: String,
+        kind: StructuredKind,
+        sample: String,
+    },
+    /// OSC 4 or 104 changed the 256-color palette. `colors` lists every
+    /// entry that now differs from the default; spans already carry them
+    /// as RGB.
+    PaletteChanged { colors: Vec<PaletteEntry> },
+    /// OSC 10/11/12 (or 110/111/112) changed the default foreground,
+    /// background or cursor color; `None` means the theme's color.
+    DynamicColorsChanged {
+        foreground: Option<SerializableColor>,
+        background: Option<SerializableColor>,
+        cursor: Option<SerializableColor>,
+    },
+    /// OSC 9 or OSC 777 `notify` asked for a desktop notification. Only
+    /// OSC 777 carries a title.
+    Notification { title: Option<String>, body: String },
+    /// ConEmu's OSC 9;4 progress report. `value` is a percentage and only
+    /// meaningful for `normal`, `error` and `paused`.
+    ProgressChanged { state: ProgressState, value: u8 },
+    /// The running command's `TERM` limits what it can use, or (with an
+    /// empty `degraded` list) a limited command ended.
+    TerminalCapabilitiesChanged {
+        #[serde(flatten)]
+        capabilities: Capabilities,
+    },
+    /// The backend selection was dropped because its text changed or
+    /// scrolled out of the grid.
+    SelectionCleared,
+}
+
+/// Drop all but the last `TitleChanged` and `CwdChanged` of a frame. Only
+/// the final value matters to the frontend, and prompts that update them on
+/// every keystroke would otherwise flood the event stream. Other events keep
+/// their order. Returns how many events were dropped.
+pub fn coalesce_events(events: &mut Vec<TerminalEvent>) -> usize {
+    let last_title = events
+        .iter()
+        .rposition(|event| matches!(event, TerminalEvent::TitleChanged { .. }));
+    let last_cwd = events
+        .iter()
+        .rposition(|event| matches!(event, TerminalEvent::CwdChanged { .. }));
+    let before = events.len();
+    let mut idx = 0;
+    events.retain(|event| {
+        let keep = match event {
+            TerminalEvent::TitleChanged { .. } => Some(idx) == last_title,
+            TerminalEvent::CwdChanged { .. } => Some(idx) == last_cwd,
+            _ => true,
+        };
+        idx += 1;
+        keep
+    });
+    before - events.len()
+}
+
+/// State of a progress report, named like the window progress bar states.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressState {
+    /// No progress is shown.
+    #[default]
+    None,
+    Normal,
+    Error,
+    Indeterminate,
+    Paused,
 }
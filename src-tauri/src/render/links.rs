@@ -0,0 +1,221 @@
+//! Links found in plain output: URLs, and file paths with the `:line:col`
+//! suffix compilers and linters print. They reach the frontend on spans,
+//! like OSC 8 hyperlinks, so it needn't scan every frame itself.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::terminal::grid::{CellFlags, Row};
+
+/// A file path in the output, as printed (relative paths stay relative).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PathLink {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub col: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkTarget {
+    Url(String),
+    Path(PathLink),
+}
+
+/// A link over cells `start..end` of a row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedLink {
+    pub start: usize,
+    pub end: usize,
+    pub target: LinkTarget,
+}
+
+fn url_regex() -> &'static Regex {
+    static URL: OnceLock<Regex> = OnceLock::new();
+    URL.get_or_init(|| {
+        Regex::new(r#"\b(?:https?|ftp|file)://[^\s<>"'`{}|\\^\[\]]+"#).expect("valid URL regex")
+    })
+}
+
+fn path_regex() -> &'static Regex {
+    static PATH: OnceLock<Regex> = OnceLock::new();
+    PATH.get_or_init(|| {
+        Regex::new(
+            r#"(?:^|[\s"'(\[<=])(?P<path>(?:~|\.\.?)?/?[\w.@+-]+(?:/[\w.@+-]+)*)(?::(?P<line>\d+)(?::(?P<col>\d+))?)?"#,
+        )
+        .expect("valid path regex")
+    })
+}
+
+/// Drop sentence punctuation after a URL, keeping a closing parenthesis
+/// that belongs to it (as in Wikipedia links).
+fn trim_url(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let Some(last) = url.chars().last() else {
+            return url;
+        };
+        let keep = match last {
+            ')' => url.matches('(').count() >= url.matches(')').count(),
+            '.' | ',' | ';' | ':' | '!' | '?' | '\'' | '"' | ']' | '}' => false,
+            _ => true,
+        };
+        if keep {
+            return url;
+        }
+        url = &url[..url.len() - last.len_utf8()];
+    }
+}
+
+/// Whether `path` looks like a file path rather than a word, number or
+/// fraction: it has a slash, or a file extension when followed by a line.
+fn is_plausible_path(path: &str, has_line: bool) -> bool {
+    if !path.chars().any(char::is_alphabetic) {
+        return false;
+    }
+    if path.contains('/') {
+        return path.len() > 1;
+    }
+    has_line
+        && path
+            .rsplit_once('.')
+            .is_some_and(|(stem, ext)| !stem.is_empty() && ext.chars().any(char::is_alphabetic))
+}
+
+/// URLs and file paths in `row`, in column order.
+pub fn detect(row: &Row) -> Vec<DetectedLink> {
+    // Byte offset in `text` where each non-spacer cell starts, with its column.
+    let mut text = String::new();
+    let mut starts: Vec<(usize, usize)> = Vec::new();
+    for (col, cell) in row.cells.iter().enumerate() {
+        if cell.flags.contains(CellFlags::WIDE_SPACER) {
+            continue;
+        }
+        starts.push((text.len(), col));
+        text.extend(cell.chars());
+    }
+    if !text.contains('/') && !text.contains(':') {
+        return Vec::new();
+    }
+    let col_at = |byte: usize| -> usize {
+        let idx = starts.partition_point(|&(start, _)| start < byte);
+        starts.get(idx).map_or(row.cells.len(), |&(_, col)| col)
+    };
+    let cell_start = |byte: usize| -> usize {
+        let idx = starts.partition_point(|&(start, _)| start <= byte);
+        starts[idx.saturating_sub(1)].1
+    };
+
+    let mut links: Vec<DetectedLink> = Vec::new();
+    for found in url_regex().find_iter(&text) {
+        let url = trim_url(found.as_str());
+        links.push(DetectedLink {
+            start: cell_start(found.start()),
+            end: col_at(found.start() + url.len()),
+            target: LinkTarget::Url(url.to_string()),
+        });
+    }
+    let urls = links.len();
+    for caps in path_regex().captures_iter(&text) {
+        let path = caps.name("path").expect("path group");
+        let line = caps.name("line").and_then(|m| m.as_str().parse().ok());
+        let col = caps.name("col").and_then(|m| m.as_str().parse().ok());
+        let trimmed = path.as_str().trim_end_matches('.');
+        if !is_plausible_path(trimmed, line.is_some()) {
+            continue;
+        }
+        let end = match (line, caps.get(0)) {
+            (Some(_), Some(all)) => all.end(),
+            _ => path.start() + trimmed.len(),
+        };
+        let (start, end) = (cell_start(path.start()), col_at(end));
+        if links[..urls]
+            .iter()
+            .any(|url| start < url.end && url.start < end)
+        {
+            continue;
+        }
+        links.push(DetectedLink {
+            start,
+            end,
+            target: LinkTarget::Path(PathLink {
+                path: trimmed.to_string(),
+                line,
+                col,
+            }),
+        });
+    }
+    links.sort_by_key(|link| link.start);
+    links
+}
+
+/// The link covering cell `col`, if any.
+pub fn link_at(links: &[DetectedLink], col: usize) -> Option<usize> {
+    links
+        .iter()
+        .position(|link| (link.start..link.end).contains(&col))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::grid::Cell;
+
+    fn row_of(text: &str) -> Row {
+        let mut row = Row::new(text.chars().count() as u16);
+        for (cell, c) in row.cells.iter_mut().zip(text.chars()) {
+            *cell = Cell {
+                c,
+                ..Cell::default()
+            };
+        }
+        row
+    }
+
+    fn targets(text: &str) -> Vec<(usize, usize, LinkTarget)> {
+        detect(&row_of(text))
+            .into_iter()
+            .map(|link| (link.start, link.end, link.target))
+            .collect()
+    }
+
+    fn path(path: &str, line: Option<u32>, col: Option<u32>) -> LinkTarget {
+        LinkTarget::Path(PathLink {
+            path: path.to_string(),
+            line,
+            col,
+        })
+    }
+
+    #[test]
+    fn finds_urls_and_compiler_locations() {
+        assert_eq!(
+            targets("see https://example.com/a_(b). now"),
+            vec![(
+                4,
+                29,
+                LinkTarget::Url("https://example.com/a_(b)".to_string())
+            )]
+        );
+        assert_eq!(
+            targets("error at src/main.rs:12:5: oops"),
+            vec![(9, 25, path("src/main.rs", Some(12), Some(5)))]
+        );
+        assert_eq!(
+            targets("lib.rs:40 and ~/notes.md."),
+            vec![
+                (0, 9, path("lib.rs", Some(40), None)),
+                (14, 24, path("~/notes.md", None, None)),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_times_fractions_and_plain_words() {
+        assert!(targets("at 12:30, 1/2 done: ok").is_empty());
+        assert!(targets("README.md is fine").is_empty());
+    }
+}
@@ -1,3 +1,10 @@
+pub mod color_filter;
+pub mod contrast;
+pub mod copy;
+pub mod export;
 pub mod frame;
+pub mod links;
+pub mod search;
+pub mod structured;
 
 pub use frame::{RenderFrame, TerminalEvent};
@@ -0,0 +1,243 @@
+//! Text search over grid rows, reporting matches as cell ranges so
+//! highlights line up with wide characters. [`Pattern`] is the literal,
+//! smart-case search kept live over the screen of full-screen programs;
+//! [`TextQuery`] runs one-off literal or regex searches over scrollback.
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::terminal::grid::{CellFlags, Row};
+
+/// Most matches one scrollback search returns, oldest first.
+pub const MAX_SCROLLBACK_MATCHES: usize = 10_000;
+
+/// Cells `col..col + cols` of screen row `row`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CellRect {
+    pub row: u16,
+    pub col: u16,
+    pub cols: u16,
+}
+
+/// A search pattern. Matching ignores case unless the pattern has an
+/// uppercase letter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    chars: Vec<char>,
+    case_sensitive: bool,
+}
+
+impl Pattern {
+    /// `None` for an empty pattern.
+    pub fn new(pattern: &str) -> Option<Self> {
+        let case_sensitive = pattern.chars().any(char::is_uppercase);
+        let chars: Vec<char> = pattern.chars().map(|c| fold(c, case_sensitive)).collect();
+        (!chars.is_empty()).then_some(Self {
+            chars,
+            case_sensitive,
+        })
+    }
+
+    /// Non-overlapping matches within `row`, left to right. Matches don't
+    /// continue onto the next row.
+    pub fn find_in_row(&self, row: &Row, row_index: u16) -> Vec<CellRect> {
+        // (column, char) of every char of the cells that start a character,
+        // so combining marks and other grapheme parts can match too.
+        let cells: Vec<(usize, char)> = row
+            .cells
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| !cell.flags.contains(CellFlags::WIDE_SPACER))
+            .flat_map(|(col, cell)| cell.chars().map(move |c| (col, c)))
+            .map(|(col, c)| (col, fold(c, self.case_sensitive)))
+            .collect();
+
+        let mut matches = Vec::new();
+        let mut start = 0;
+        while start + self.chars.len() <= cells.len() {
+            let window = &cells[start..start + self.chars.len()];
+            if window
+                .iter()
+                .map(|&(_, c)| c)
+                .eq(self.chars.iter().copied())
+            {
+                let first = window[0].0;
+                let last = window[window.len() - 1].0;
+                let width = if row.cells[last].flags.contains(CellFlags::WIDE_CHAR) {
+                    2
+                } else {
+                    1
+                };
+                matches.push(CellRect {
+                    row: row_index,
+                    col: first as u16,
+                    cols: (last + width - first) as u16,
+                });
+                start += self.chars.len();
+            } else {
+                start += 1;
+            }
+        }
+        matches
+    }
+}
+
+/// How a [`TextQuery`] matches.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchOptions {
+    /// The query is a regular expression rather than literal text.
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+}
+
+/// A literal or regex query, matched against the text of one row at a time.
+#[derive(Debug, Clone)]
+pub struct TextQuery {
+    regex: Regex,
+}
+
+impl TextQuery {
+    /// `Ok(None)` for an empty query, `Err` for an invalid regex.
+    pub fn new(query: &str, options: SearchOptions) -> Result<Option<Self>, regex::Error> {
+        if query.is_empty() {
+            return Ok(None);
+        }
+        let source = if options.regex {
+            query.to_string()
+        } else {
+            regex::escape(query)
+        };
+        let regex = RegexBuilder::new(&source)
+            .case_insensitive(!options.case_sensitive)
+            .build()?;
+        Ok(Some(Self { regex }))
+    }
+
+    /// Non-empty, non-overlapping matches within `row` as `(col, cols)`
+    /// cell ranges, left to right. Matches don't continue onto the next row.
+    pub fn find_in_row(&self, row: &Row) -> Vec<(u16, u16)> {
+        let mut text = String::new();
+        // (byte offset, column, width) of every cell that starts a character.
+        let mut cells: Vec<(usize, usize, usize)> = Vec::new();
+        for (col, cell) in row.cells.iter().enumerate() {
+            if cell.flags.contains(CellFlags::WIDE_SPACER) {
+                continue;
+            }
+            let width = if cell.flags.contains(CellFlags::WIDE_CHAR) {
+                2
+            } else {
+                1
+            };
+            cells.push((text.len(), col, width));
+            text.extend(cell.chars());
+        }
+        let cell_at = |byte: usize| cells[cells.partition_point(|&(start, ..)| start <= byte) - 1];
+
+        self.regex
+            .find_iter(&text)
+            .filter(|m| !m.is_empty())
+            .map(|m| {
+                let (_, first, _) = cell_at(m.start());
+                let (_, last, width) = cell_at(m.end() - 1);
+                (first as u16, (last + width - first) as u16)
+            })
+            .collect()
+    }
+}
+
+/// Cells `col..col + cols` of a main-grid row matched by a scrollback search.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrollbackMatch {
+    /// Row in the numbering of `RenderFrame::visible_base_global`.
+    pub global_row: u64,
+    pub line_id: u64,
+    pub col: u16,
+    pub cols: u16,
+    /// Text of the whole row, trailing blanks trimmed.
+    pub context: String,
+}
+
+fn fold(c: char, case_sensitive: bool) -> char {
+    if case_sensitive {
+        c
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::grid::Cell;
+
+    fn row(text: &str) -> Row {
+        let mut row = Row::new(12);
+        let mut col = 0;
+        for c in text.chars() {
+            row.cells[col].set_char(c);
+            if c == '界' {
+                row.cells[col].flags = CellFlags::WIDE_CHAR;
+                row.cells[col + 1] = Cell::wide_spacer();
+                col += 1;
+            }
+            col += 1;
+        }
+        row
+    }
+
+    fn spans(pattern: &str, text: &str) -> Vec<(u16, u16)> {
+        Pattern::new(pattern)
+            .unwrap()
+            .find_in_row(&row(text), 0)
+            .into_iter()
+            .map(|rect| (rect.col, rect.cols))
+            .collect()
+    }
+
+    #[test]
+    fn matches_are_cell_ranges_with_smart_case() {
+        assert_eq!(spans("ab", "xAbab aba"), vec![(1, 2), (3, 2), (6, 2)]);
+        assert!(spans("AB", "ab").is_empty());
+        assert_eq!(spans("界x", "a界x"), vec![(1, 3)]);
+        assert_eq!(spans("a界", "a界"), vec![(0, 3)]);
+        assert!(Pattern::new("").is_none());
+
+        let rects = Pattern::new("b").unwrap().find_in_row(&row("ab"), 7);
+        assert_eq!(rects[0].row, 7);
+
+        // A grapheme cluster in one cell matches as a whole.
+        let mut accented = row("ex");
+        accented.cells[0].combining = Some("\u{301}".into());
+        let found = Pattern::new("e\u{301}x").unwrap().find_in_row(&accented, 0);
+        assert_eq!((found[0].col, found[0].cols), (0, 2));
+    }
+
+    #[test]
+    fn text_queries_match_literally_or_by_regex() {
+        let find = |query: &str, options: SearchOptions, text: &str| {
+            TextQuery::new(query, options)
+                .unwrap()
+                .unwrap()
+                .find_in_row(&row(text))
+        };
+        let plain = SearchOptions::default();
+        assert_eq!(find("a.", plain, "xA.a.ab"), vec![(1, 2), (3, 2)]);
+        let exact = SearchOptions {
+            case_sensitive: true,
+            ..plain
+        };
+        assert_eq!(find("a.", exact, "xA.a.ab"), vec![(3, 2)]);
+
+        let regex = SearchOptions {
+            regex: true,
+            ..plain
+        };
+        assert_eq!(find("a.", regex, "a界ab"), vec![(0, 3), (3, 2)]);
+        assert_eq!(find("b*", regex, "abb"), vec![(1, 2)]);
+        assert!(TextQuery::new("", regex).unwrap().is_none());
+        assert!(TextQuery::new("(", regex).is_err());
+    }
+}
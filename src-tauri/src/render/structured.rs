@@ -0,0 +1,255 @@
+//! Recognizes command output that is a JSON document or a CSV/TSV table, so
+//! the frontend can offer a table or tree view of a finished block.
+//!
+//! Detection runs on the PTY reader thread when a block completes, so it only
+//! looks at modest outputs and gives up at the first inconsistency. Parsing
+//! the full data happens on demand through `get_block_structured`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::terminal::grid::{CellFlags, Row};
+
+/// Blocks with more output rows than this are not inspected.
+pub const MAX_STRUCTURED_ROWS: usize = 5000;
+
+/// Table lines (header included) in a block's sample.
+const SAMPLE_LINES: usize = 4;
+
+/// Characters of a JSON document in a block's sample.
+const SAMPLE_CHARS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StructuredKind {
+    Json,
+    Csv,
+    Tsv,
+}
+
+impl StructuredKind {
+    fn delimiter(self) -> Option<char> {
+        match self {
+            StructuredKind::Json => None,
+            StructuredKind::Csv => Some(','),
+            StructuredKind::Tsv => Some('\t'),
+        }
+    }
+}
+
+/// Parsed block output, as returned by `get_block_structured`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum StructuredData {
+    Json {
+        value: Value,
+    },
+    /// CSV or TSV; `columns` is the header row.
+    Table {
+        columns: Vec<String>,
+        rows: Vec<Vec<String>>,
+    },
+}
+
+/// Text of output rows for parsing. A row filled to the last column is taken
+/// as soft-wrapped and joined with the next, so long JSON strings and CSV
+/// records survive the terminal width.
+pub fn rows_text(rows: &[&Row]) -> String {
+    let mut text = String::new();
+    for row in rows {
+        let line: String = row
+            .cells
+            .iter()
+            .filter(|c| !c.flags.contains(CellFlags::WIDE_SPACER))
+            .flat_map(|c| c.chars())
+            .collect();
+        let wrapped = row.cells.last().is_some_and(|last| {
+            last.flags.contains(CellFlags::WRAP)
+                || (last.chars().ne([' ']) && !last.flags.contains(CellFlags::WIDE_SPACER))
+        });
+        if wrapped {
+            text.push_str(&line);
+        } else {
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// What `text` looks like, if it is entirely one JSON object or array, or a
+/// table of at least two columns and two lines with the same column count
+/// on every line.
+pub fn detect(text: &str) -> Option<StructuredKind> {
+    let text = text.trim();
+    if text.starts_with('{') || text.starts_with('[') {
+        return serde_json::from_str::<Value>(text)
+            .ok()
+            .map(|_| StructuredKind::Json);
+    }
+    let header = text.lines().next()?;
+    let kind = if header.contains('\t') {
+        StructuredKind::Tsv
+    } else if header.contains(',') {
+        StructuredKind::Csv
+    } else {
+        return None;
+    };
+    let records = records(text, kind.delimiter()?).ok()?;
+    let columns = records[0].len();
+    let consistent = records.len() >= 2
+        && columns >= 2
+        && records[0].iter().all(|name| !name.trim().is_empty())
+        && records.iter().all(|record| record.len() == columns);
+    consistent.then_some(kind)
+}
+
+/// A short preview of `text` as `kind`, for the block's metadata.
+pub fn sample(text: &str, kind: StructuredKind) -> String {
+    let text = text.trim();
+    match kind {
+        StructuredKind::Json => {
+            let compact = serde_json::from_str::<Value>(text)
+                .map(|value| value.to_string())
+                .unwrap_or_else(|_| text.to_string());
+            if compact.chars().count() > SAMPLE_CHARS {
+                let cut: String = compact.chars().take(SAMPLE_CHARS).collect();
+                format!("{}…", cut)
+            } else {
+                compact
+            }
+        }
+        StructuredKind::Csv | StructuredKind::Tsv => text
+            .lines()
+            .take(SAMPLE_LINES)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+/// Parse `text` as `kind`. Tables may be ragged here; only detection
+/// insists on a consistent column count.
+pub fn parse(text: &str, kind: StructuredKind) -> Result<StructuredData, String> {
+    let text = text.trim();
+    match kind.delimiter() {
+        None => serde_json::from_str(text)
+            .map(|value| StructuredData::Json { value })
+            .map_err(|e| format!("Output is not valid JSON: {}", e)),
+        Some(delimiter) => {
+            let mut records = records(text, delimiter)?.into_iter();
+            let columns = records
+                .next()
+                .ok_or_else(|| "Output is empty".to_string())?;
+            Ok(StructuredData::Table {
+                columns,
+                rows: records.collect(),
+            })
+        }
+    }
+}
+
+/// Non-empty lines of `text` split into fields. Fields may be quoted with
+/// `"` (doubled to escape); a quoted field can't span lines.
+fn records(text: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            split_record(line, delimiter)
+                .ok_or_else(|| format!("Unterminated quote on line {}", idx + 1))
+        })
+        .collect()
+}
+
+fn split_record(line: &str, delimiter: char) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    fields.push(field);
+    Some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_json_and_consistent_tables_only() {
+        assert_eq!(
+            detect("{\n  \"name\": \"rain\",\n  \"tags\": [1, 2]\n}\n"),
+            Some(StructuredKind::Json)
+        );
+        assert_eq!(
+            detect("name,size\n\"a, b\",1\nc,2\n"),
+            Some(StructuredKind::Csv)
+        );
+        assert_eq!(detect("PID\tCMD\n1\tinit\n"), Some(StructuredKind::Tsv));
+        assert_eq!(detect("{ not json"), None);
+        assert_eq!(detect("Hello, world\nBye, now, friend\n"), None);
+        assert_eq!(detect("just, one line"), None);
+        assert_eq!(detect("total 8\ndrwxr-xr-x 2 me me 4096 ."), None);
+    }
+
+    #[test]
+    fn parses_tables_with_quotes_and_json() {
+        let table = parse(
+            "name,note\nx,\"said \"\"hi\"\", left\"\n",
+            StructuredKind::Csv,
+        );
+        assert_eq!(
+            table,
+            Ok(StructuredData::Table {
+                columns: vec!["name".into(), "note".into()],
+                rows: vec![vec!["x".into(), "said \"hi\", left".into()]],
+            })
+        );
+        assert!(parse("a,\"b\n", StructuredKind::Csv).is_err());
+        assert_eq!(
+            parse("[1,2]", StructuredKind::Json),
+            Ok(StructuredData::Json {
+                value: serde_json::json!([1, 2]),
+            })
+        );
+        assert_eq!(
+            sample("a\tb\n1\t2\n3\t4\n5\t6\n7\t8\n", StructuredKind::Tsv),
+            "a\tb\n1\t2\n3\t4\n5\t6"
+        );
+    }
+
+    #[test]
+    fn full_width_rows_join_the_next_row() {
+        let row = |text: &str| {
+            let mut row = Row::new(6);
+            for (cell, c) in row.cells.iter_mut().zip(text.chars()) {
+                cell.set_char(c);
+            }
+            row
+        };
+        let mut rows = [row("{\"key\""), row(":1}"), row("x")];
+        rows[2].cells[0].combining = Some("\u{308}".into());
+        let refs: Vec<&Row> = rows.iter().collect();
+        assert_eq!(rows_text(&refs), "{\"key\":1}\nx\u{308}\n");
+    }
+}
@@ -0,0 +1,103 @@
+//! Safe mode after repeated crashes.
+//!
+//! A marker file is written at startup and removed on clean exit, so a marker
+//! still present at the next launch means that run died. The marker carries
+//! the number of consecutive abnormal exits; once it reaches
+//! [`SAFE_MODE_THRESHOLD`] Rain starts with the default theme, no window
+//! effects, no plugins and no workspace restore, letting users locked out by
+//! a bad config or GPU interaction recover without editing files by hand.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+pub const MARKER_FILE: &str = "running.marker";
+
+/// Consecutive abnormal exits before the next launch starts in safe mode.
+pub const SAFE_MODE_THRESHOLD: u32 = 3;
+
+/// Forces safe mode regardless of the crash count.
+pub const SAFE_MODE_ENV: &str = "RAIN_SAFE_MODE";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupMode {
+    pub safe_mode: bool,
+    /// Abnormal exits in a row before this launch.
+    pub consecutive_crashes: u32,
+    /// Safe mode was requested through [`SAFE_MODE_ENV`].
+    pub forced: bool,
+}
+
+impl StartupMode {
+    pub fn new(consecutive_crashes: u32, forced: bool) -> Self {
+        Self {
+            safe_mode: forced || consecutive_crashes >= SAFE_MODE_THRESHOLD,
+            consecutive_crashes,
+            forced,
+        }
+    }
+}
+
+/// Whether the environment asks for safe mode (any value but empty/`0`).
+pub fn forced_by_env(value: Option<&str>) -> bool {
+    value.is_some_and(|v| !v.trim().is_empty() && v.trim() != "0")
+}
+
+/// Record this launch: read the crash count left by the previous run, then
+/// leave a marker counting it. Returns the consecutive abnormal exits.
+pub fn record_launch(dir: &Path) -> Result<u32, String> {
+    let path = dir.join(MARKER_FILE);
+    let crashes = match std::fs::read_to_string(&path) {
+        // A marker without a readable count still means one crash.
+        Ok(previous) => previous
+            .trim()
+            .parse::<u32>()
+            .unwrap_or(0)
+            .saturating_add(1),
+        Err(_) => 0,
+    };
+    std::fs::create_dir_all(dir).map_err(|e| format!("create dir error: {}", e))?;
+    std::fs::write(&path, crashes.to_string()).map_err(|e| format!("write marker error: {}", e))?;
+    Ok(crashes)
+}
+
+/// Clean shutdown: the next launch starts with a zero crash count.
+pub fn clear(dir: &Path) {
+    let _ = std::fs::remove_file(dir.join(MARKER_FILE));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crashes_accumulate_until_a_clean_exit() {
+        let dir = std::env::temp_dir().join(format!("rain-safe-mode-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(record_launch(&dir).unwrap(), 0);
+        assert_eq!(record_launch(&dir).unwrap(), 1);
+        assert_eq!(record_launch(&dir).unwrap(), 2);
+        assert_eq!(record_launch(&dir).unwrap(), 3);
+        assert!(StartupMode::new(3, false).safe_mode);
+
+        clear(&dir);
+        assert_eq!(record_launch(&dir).unwrap(), 0);
+
+        std::fs::write(dir.join(MARKER_FILE), "garbage").unwrap();
+        assert_eq!(record_launch(&dir).unwrap(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn threshold_and_env_override() {
+        assert!(!StartupMode::new(SAFE_MODE_THRESHOLD - 1, false).safe_mode);
+        assert!(StartupMode::new(SAFE_MODE_THRESHOLD, false).safe_mode);
+        assert!(StartupMode::new(0, true).safe_mode);
+        assert!(forced_by_env(Some("1")));
+        assert!(!forced_by_env(Some("0")));
+        assert!(!forced_by_env(Some("")));
+        assert!(!forced_by_env(None));
+    }
+}
@@ -0,0 +1,180 @@
+//! Sharing command blocks to GitHub Gist or a paste service.
+//!
+//! The HTTP round trip lives in `ipc::commands::share`; this module builds
+//! the requests and reads the resulting URL out of the responses. Tokens come
+//! from the OS credential store under [`GIST_TOKEN_KEY`] and
+//! [`PASTE_TOKEN_KEY`].
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+pub const GIST_API_URL: &str = "https://api.github.com/gists";
+
+/// Credential store key of the GitHub token (needs the `gist` scope).
+pub const GIST_TOKEN_KEY: &str = "share.gist-token";
+/// Credential store key of an optional bearer token for the paste endpoint.
+pub const PASTE_TOKEN_KEY: &str = "share.paste-token";
+
+/// Largest rendering we upload; gists truncate bigger files anyway.
+pub const MAX_SHARE_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShareService {
+    Gist {
+        #[serde(default)]
+        public: bool,
+    },
+    /// Any endpoint that accepts the raw document as a POST body and answers
+    /// with its URL, as plain text or JSON (`url`, `link` or `html_url`).
+    Paste { endpoint: String },
+}
+
+/// File name for the shared block, derived from its command.
+pub fn file_name(command: Option<&str>, extension: &str) -> String {
+    let stem: String = command
+        .and_then(|command| command.split_whitespace().next())
+        .map(|program| program.rsplit('/').next().unwrap_or(program))
+        .unwrap_or("")
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .take(40)
+        .collect();
+    let stem = stem.trim_matches('.');
+    if stem.is_empty() {
+        format!("rain-output.{}", extension)
+    } else {
+        format!("rain-{}.{}", stem, extension)
+    }
+}
+
+pub fn gist_payload(file_name: &str, content: &str, command: Option<&str>, public: bool) -> Value {
+    let description = match command {
+        Some(command) => format!("$ {}", command),
+        None => "Terminal output shared from Rain".to_string(),
+    };
+    json!({
+        "description": description,
+        "public": public,
+        "files": { file_name: { "content": content } },
+    })
+}
+
+pub fn gist_url(response: &Value) -> Result<String, String> {
+    response
+        .get("html_url")
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| "Gist response has no URL".to_string())
+}
+
+/// Tokens must not travel in clear text; plain HTTP is only allowed for a
+/// paste service on this machine.
+pub fn validate_endpoint(endpoint: &str) -> Result<(), String> {
+    let endpoint = endpoint.trim();
+    if endpoint.starts_with("https://") {
+        return Ok(());
+    }
+    let local = ["http://localhost", "http://127.0.0.1", "http://[::1]"]
+        .iter()
+        .any(|prefix| {
+            endpoint
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with([':', '/']))
+        });
+    if local {
+        Ok(())
+    } else {
+        Err(format!("Paste endpoint must use https: {}", endpoint))
+    }
+}
+
+fn is_web_url(text: &str) -> bool {
+    (text.starts_with("https://") || text.starts_with("http://"))
+        && !text.contains(char::is_whitespace)
+}
+
+/// The shared document's URL from a paste service response body.
+pub fn paste_url(body: &str) -> Result<String, String> {
+    let body = body.trim();
+    if is_web_url(body) {
+        return Ok(body.to_string());
+    }
+    let json: Value =
+        serde_json::from_str(body).map_err(|_| "Paste service did not return a URL".to_string())?;
+    ["url", "link", "html_url"]
+        .iter()
+        .filter_map(|key| json.get(key).and_then(Value::as_str))
+        .find(|url| is_web_url(url))
+        .map(String::from)
+        .ok_or_else(|| "Paste service did not return a URL".to_string())
+}
+
+/// Best error text from a failed API response.
+pub fn error_message(body: &str) -> String {
+    serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|json| {
+            json.get("message")
+                .and_then(Value::as_str)
+                .map(String::from)
+        })
+        .unwrap_or_else(|| body.trim().chars().take(200).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_come_from_the_program() {
+        assert_eq!(
+            file_name(Some("cargo test -p rain"), "ans"),
+            "rain-cargo.ans"
+        );
+        assert_eq!(file_name(Some("/usr/bin/ls -la"), "html"), "rain-ls.html");
+        assert_eq!(file_name(Some("../.."), "ans"), "rain-output.ans");
+        assert_eq!(file_name(None, "ans"), "rain-output.ans");
+    }
+
+    #[test]
+    fn gist_payload_and_response() {
+        let payload = gist_payload("rain-ls.ans", "out", Some("ls"), false);
+        assert_eq!(payload["files"]["rain-ls.ans"]["content"], "out");
+        assert_eq!(payload["description"], "$ ls");
+        assert_eq!(payload["public"], false);
+        assert_eq!(
+            gist_url(&json!({ "html_url": "https://gist.github.com/abc" })).unwrap(),
+            "https://gist.github.com/abc"
+        );
+        assert!(gist_url(&json!({})).is_err());
+    }
+
+    #[test]
+    fn endpoints_must_be_https_unless_local() {
+        assert!(validate_endpoint("https://paste.example.com/api").is_ok());
+        assert!(validate_endpoint("http://localhost:8080/paste").is_ok());
+        assert!(validate_endpoint("http://127.0.0.1").is_ok());
+        assert!(validate_endpoint("http://localhost.evil.com").is_err());
+        assert!(validate_endpoint("http://paste.example.com").is_err());
+        assert!(validate_endpoint("file:///etc/passwd").is_err());
+    }
+
+    #[test]
+    fn paste_responses_in_text_or_json() {
+        assert_eq!(
+            paste_url("https://p.example/abc\n").unwrap(),
+            "https://p.example/abc"
+        );
+        assert_eq!(
+            paste_url(r#"{"id":"abc","link":"https://p.example/abc"}"#).unwrap(),
+            "https://p.example/abc"
+        );
+        assert!(paste_url("created").is_err());
+        assert!(paste_url(r#"{"url":"javascript:alert(1)"}"#).is_err());
+        assert_eq!(
+            error_message(r#"{"message":"Bad credentials"}"#),
+            "Bad credentials"
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! User-configured shell arguments.
+//!
+//! Rain adds its own flags when spawning a shell: the integration arguments
+//! (bash's `--rcfile`, fish's `-C`, PowerShell's `-NoExit -Command`) and a
+//! login flag for zsh and shells it has no hooks for. A template from config
+//! can turn the login flag on or off and add arguments for one shell, on
+//! every platform or just one, e.g. `nu --config ~/.config/nu/rain.nu` or
+//! `pwsh -NoLogo`.
+
+use serde::Deserialize;
+
+use super::detect::shell_name;
+
+/// Arguments accepted per template.
+const MAX_ARGS: usize = 32;
+
+/// Platforms a template may be limited to, as in `std::env::consts::OS`.
+const PLATFORMS: &[&str] = &["macos", "linux", "windows"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ShellArgTemplate {
+    /// Shell name as detected from its path ("fish", "nu", "pwsh").
+    pub shell: String,
+    /// Only apply on this platform; `None` applies everywhere.
+    pub platform: Option<String>,
+    /// Start as a login shell; `None` keeps Rain's default for the shell.
+    pub login: Option<bool>,
+    /// Passed before Rain's integration arguments.
+    pub args: Vec<String>,
+}
+
+/// Arguments Rain passes itself for shell integration, which a template
+/// must not repeat or override.
+fn reserved_args(shell: &str) -> &'static [&'static str] {
+    match shell {
+        "bash" => &["-c", "--rcfile", "--init-file", "--norc", "--noprofile"],
+        "zsh" => &["-c"],
+        "fish" => &["-c", "--command", "-C", "--init-command"],
+        "pwsh" | "powershell" => &["-c", "-command", "-f", "-file", "-noexit", "-login"],
+        _ => &["-c"],
+    }
+}
+
+impl ShellArgTemplate {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.shell.trim().is_empty() {
+            return Err("Shell argument template has no shell name".to_string());
+        }
+        if let Some(platform) = &self.platform {
+            if !PLATFORMS.contains(&platform.as_str()) {
+                return Err(format!(
+                    "Unknown platform '{}' for {} (expected one of {})",
+                    platform,
+                    self.shell,
+                    PLATFORMS.join(", ")
+                ));
+            }
+        }
+        if self.args.len() > MAX_ARGS {
+            return Err(format!(
+                "Too many arguments for {} ({}, at most {})",
+                self.shell,
+                self.args.len(),
+                MAX_ARGS
+            ));
+        }
+        let reserved = reserved_args(&self.shell);
+        let powershell = matches!(self.shell.as_str(), "pwsh" | "powershell");
+        for arg in &self.args {
+            if arg.contains('\0') {
+                return Err(format!("Argument for {} contains a NUL byte", self.shell));
+            }
+            let flag = arg.split('=').next().unwrap_or(arg);
+            let clashes = if powershell {
+                reserved.contains(&flag.to_ascii_lowercase().as_str())
+            } else {
+                reserved.contains(&flag)
+            };
+            if clashes {
+                return Err(format!(
+                    "'{}' is used by Rain's shell integration and can't be set for {}",
+                    arg, self.shell
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn applies_to(&self, shell: &str, platform: &str) -> bool {
+        self.shell == shell && self.platform.as_deref().is_none_or(|p| p == platform)
+    }
+}
+
+/// All configured templates. For a given shell, a platform-specific template
+/// wins over one that applies everywhere.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShellArgs {
+    templates: Vec<ShellArgTemplate>,
+}
+
+impl ShellArgs {
+    pub fn new(templates: Vec<ShellArgTemplate>) -> Result<Self, String> {
+        for template in &templates {
+            template.validate()?;
+        }
+        Ok(Self { templates })
+    }
+
+    pub fn for_shell(&self, shell: &str, platform: &str) -> Option<&ShellArgTemplate> {
+        let mut matching = self
+            .templates
+            .iter()
+            .filter(|t| t.applies_to(shell, platform));
+        let first = matching.next()?;
+        if first.platform.is_some() {
+            return Some(first);
+        }
+        Some(matching.find(|t| t.platform.is_some()).unwrap_or(first))
+    }
+}
+
+/// Whether Rain starts `shell` as a login shell unless configured otherwise.
+/// Bash is made to read its profile by Rain's rcfile instead, and fish and
+/// PowerShell load theirs regardless; without hooks every shell gets the
+/// flag on Unix.
+pub fn default_login(shell: &str, integrated: bool) -> bool {
+    if !cfg!(unix) {
+        return false;
+    }
+    !integrated || !matches!(shell, "bash" | "fish" | "pwsh" | "powershell" | "cmd")
+}
+
+/// How `shell` spells the login flag, if it has one. Bash's `--login` would
+/// make it ignore `--rcfile`, so it only applies without integration.
+pub fn login_flag(shell: &str, integrated: bool) -> Option<&'static str> {
+    match shell {
+        "bash" if integrated => None,
+        "cmd" => None,
+        "pwsh" | "powershell" if cfg!(unix) => Some("-Login"),
+        "pwsh" | "powershell" => None,
+        _ => Some("--login"),
+    }
+}
+
+/// Program and arguments that run `argv` through the login shell at
+/// `shell_path`, so the program is found on the PATH the user's profile sets
+/// up rather than on Rain's own; `exec` then leaves only the program running.
+/// Shells without POSIX `"$@"`, other than fish, hand it to `/bin/sh`.
+/// Windows has no login shells and runs `argv` directly.
+pub fn login_shell_command(shell_path: &str, argv: &[String]) -> (String, Vec<String>) {
+    if !cfg!(unix) {
+        return (argv[0].clone(), argv[1..].to_vec());
+    }
+    let posix = r#"exec "$@""#;
+    let (program, script) = match shell_name(shell_path) {
+        "fish" => (shell_path, "exec $argv"),
+        "bash" | "zsh" | "sh" | "dash" | "ksh" => (shell_path, posix),
+        _ => ("/bin/sh", posix),
+    };
+    let mut args = vec!["-l".to_string(), "-c".to_string(), script.to_string()];
+    if script == posix {
+        // `$0` of the script.
+        args.push(argv[0].clone());
+    }
+    args.extend(argv.iter().cloned());
+    (program.to_string(), args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(shell: &str, platform: Option<&str>, args: &[&str]) -> ShellArgTemplate {
+        ShellArgTemplate {
+            shell: shell.to_string(),
+            platform: platform.map(str::to_string),
+            login: None,
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn platform_specific_template_wins() {
+        let args = ShellArgs::new(vec![
+            template("nu", None, &["--config", "a.nu"]),
+            template("nu", Some("macos"), &["--config", "b.nu"]),
+            template("pwsh", None, &["-NoLogo"]),
+        ])
+        .unwrap();
+        assert_eq!(args.for_shell("nu", "macos").unwrap().args[1], "b.nu");
+        assert_eq!(args.for_shell("nu", "linux").unwrap().args[1], "a.nu");
+        assert_eq!(args.for_shell("pwsh", "windows").unwrap().args, ["-NoLogo"]);
+        assert!(args.for_shell("fish", "linux").is_none());
+    }
+
+    #[test]
+    fn integration_arguments_are_rejected() {
+        assert!(template("bash", None, &["--rcfile=x"]).validate().is_err());
+        assert!(template("pwsh", None, &["-noexit"]).validate().is_err());
+        assert!(template("fish", None, &["--init-command"])
+            .validate()
+            .is_err());
+        assert!(template("fish", None, &["--private"]).validate().is_ok());
+        assert!(template("zsh", Some("bsd"), &[]).validate().is_err());
+        assert!(template(" ", None, &[]).validate().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commands_run_through_the_login_shell() {
+        let argv = vec!["ssh".to_string(), "-t".to_string(), "host".to_string()];
+        let (program, args) = login_shell_command("/bin/zsh", &argv);
+        assert_eq!(program, "/bin/zsh");
+        assert_eq!(
+            args,
+            ["-l", "-c", r#"exec "$@""#, "ssh", "ssh", "-t", "host"]
+        );
+
+        let (program, args) = login_shell_command("/usr/bin/fish", &argv);
+        assert_eq!(program, "/usr/bin/fish");
+        assert_eq!(args, ["-l", "-c", "exec $argv", "ssh", "-t", "host"]);
+
+        let (program, args) = login_shell_command("/usr/bin/nu", &argv);
+        assert_eq!(program, "/bin/sh");
+        assert_eq!(args[3], "ssh");
+    }
+}
@@ -0,0 +1,191 @@
+//! What a session's foreground program can use, judged from the `TERM` it
+//! runs under.
+//!
+//! Rain starts shells with `TERM=xterm-256color`, but a command can run
+//! under a more limited one: set on its command line (`TERM=dumb make`),
+//! by a multiplexer it starts (`screen`, `tmux`), or found in its process
+//! environment. Such a program won't emit what its terminfo entry lacks, so
+//! the frontend stops offering those features until the command ends.
+
+use serde::Serialize;
+
+/// Colors of a direct-color `TERM`.
+pub const TRUECOLOR: u32 = 1 << 24;
+
+/// Capabilities of the foreground program, as sent in
+/// `TerminalCapabilitiesChanged`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    /// `None` while the program runs under Rain's own `TERM`.
+    pub term: Option<String>,
+    /// Colors the program will use: 2, 8, 16, 88, 256 or [`TRUECOLOR`].
+    pub colors: u32,
+    pub hyperlinks: bool,
+    pub images: bool,
+    pub mouse: bool,
+    /// Features lost compared to Rain's own `TERM`.
+    pub degraded: Vec<&'static str>,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            term: None,
+            colors: TRUECOLOR,
+            hyperlinks: true,
+            images: true,
+            mouse: true,
+            degraded: Vec::new(),
+        }
+    }
+}
+
+impl Capabilities {
+    /// Capabilities under `term`; the default when nothing is lost.
+    pub fn for_term(term: &str) -> Self {
+        let family = term.split('-').next().unwrap_or(term);
+        let (colors, hyperlinks, images, mouse) = match family {
+            "" | "dumb" | "vt52" | "vt100" | "vt102" | "vt220" | "vt320" => {
+                (2, false, false, false)
+            }
+            "ansi" | "linux" | "cons25" => (8, false, false, false),
+            // Multiplexers drop OSC 8 and image data unless configured to
+            // pass them through.
+            "screen" | "tmux" => (8, false, false, true),
+            _ => (TRUECOLOR, true, true, true),
+        };
+        let colors = if term.ends_with("-direct") || term.ends_with("-truecolor") {
+            TRUECOLOR
+        } else if term.contains("256color") {
+            256
+        } else if term.contains("88color") {
+            88
+        } else if term.contains("16color") {
+            16
+        } else {
+            colors
+        };
+
+        let mut degraded = Vec::new();
+        if colors < TRUECOLOR {
+            degraded.push("truecolor");
+        }
+        if colors < 256 {
+            degraded.push("256color");
+        }
+        for (available, feature) in [
+            (hyperlinks, "hyperlinks"),
+            (images, "images"),
+            (mouse, "mouse"),
+        ] {
+            if !available {
+                degraded.push(feature);
+            }
+        }
+        // A 256-color TERM loses nothing users would notice.
+        if degraded.iter().all(|&feature| feature == "truecolor") {
+            return Self::default();
+        }
+        Self {
+            term: Some(term.to_string()),
+            colors,
+            hyperlinks,
+            images,
+            mouse,
+            degraded,
+        }
+    }
+}
+
+/// `TERM` a command line sets for its program (`TERM=dumb make`), or the
+/// one a multiplexer it starts gives its panes.
+pub fn command_term(command: &str) -> Option<String> {
+    for token in command.split_whitespace() {
+        if let Some(value) = token.strip_prefix("TERM=") {
+            return Some(value.trim_matches(['\'', '"']).to_string());
+        }
+        let is_assignment = token
+            .split_once('=')
+            .is_some_and(|(key, _)| !key.is_empty() && !key.starts_with('-'));
+        if is_assignment || token == "env" {
+            continue;
+        }
+        return match token.rsplit('/').next() {
+            Some("screen") => Some("screen".to_string()),
+            Some("tmux") => Some("tmux-256color".to_string()),
+            _ => None,
+        };
+    }
+    None
+}
+
+/// `TERM` in the environment process `pid` started with, where the OS
+/// exposes it (Linux `/proc`).
+#[cfg(target_os = "linux")]
+pub fn process_term(pid: i32) -> Option<String> {
+    let environ = std::fs::read(format!("/proc/{}/environ", pid)).ok()?;
+    environ_var(&environ, "TERM")
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn process_term(_pid: i32) -> Option<String> {
+    None
+}
+
+/// Value of `name` in a NUL-separated `KEY=value` block.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn environ_var(environ: &[u8], name: &str) -> Option<String> {
+    environ.split(|&b| b == 0).find_map(|entry| {
+        let entry = std::str::from_utf8(entry).ok()?;
+        let value = entry.strip_prefix(name)?.strip_prefix('=')?;
+        Some(value.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limited_terms_report_what_they_lose() {
+        assert_eq!(
+            Capabilities::for_term("xterm-256color"),
+            Capabilities::default()
+        );
+        assert_eq!(
+            Capabilities::for_term("xterm-kitty"),
+            Capabilities::default()
+        );
+
+        let dumb = Capabilities::for_term("dumb");
+        assert_eq!(dumb.colors, 2);
+        assert_eq!(
+            dumb.degraded,
+            vec!["truecolor", "256color", "hyperlinks", "images", "mouse"]
+        );
+
+        let tmux = Capabilities::for_term("tmux-256color");
+        assert_eq!((tmux.colors, tmux.mouse), (256, true));
+        assert_eq!(tmux.degraded, vec!["truecolor", "hyperlinks", "images"]);
+        assert_eq!(tmux.term.as_deref(), Some("tmux-256color"));
+    }
+
+    #[test]
+    fn terms_come_from_commands_and_environments() {
+        assert_eq!(command_term("TERM=dumb make -j8").as_deref(), Some("dumb"));
+        assert_eq!(
+            command_term("env CI=1 /usr/bin/screen -r").as_deref(),
+            Some("screen")
+        );
+        assert_eq!(
+            command_term("tmux new -s main").as_deref(),
+            Some("tmux-256color")
+        );
+        assert_eq!(command_term("echo TERM=dumb"), None);
+        assert_eq!(
+            environ_var(b"HOME=/root\0TERM=screen\0TERMINFO=/x\0", "TERM").as_deref(),
+            Some("screen")
+        );
+        assert_eq!(environ_var(b"TERMINFO=/x\0", "TERM"), None);
+    }
+}
@@ -0,0 +1,186 @@
+//! The command line being edited at a prompt, for features that need what
+//! is typed before Enter (explanations, highlighting, suggestions).
+//!
+//! Input written to the PTY is mirrored with basic line editing, but the
+//! shell's echo is the truth: completion, history recall and cursor
+//! movement only show up there. The mirror strips continuation prompts from
+//! multi-line echoes, and stands in for the echo when the shell doesn't
+//! mark where input starts (no bare OSC 133;B).
+
+use serde::Serialize;
+
+/// Input mirrored per prompt, beyond which further typing is dropped.
+const MAX_MIRROR_BYTES: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandLineSource {
+    /// Read from the screen after the prompt.
+    Echo,
+    /// Rebuilt from the input alone.
+    Typed,
+}
+
+/// As returned by `get_current_command_line`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommandLine {
+    /// Lines of a multi-line command are joined with `\n`.
+    pub text: String,
+    /// Character offset of the cursor in `text`, when it is known.
+    pub cursor: Option<usize>,
+    pub source: CommandLineSource,
+}
+
+impl CommandLine {
+    pub fn typed(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            cursor: None,
+            source: CommandLineSource::Typed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Escape {
+    #[default]
+    None,
+    Start,
+    Csi,
+    Ss3,
+}
+
+/// Text typed since the prompt was drawn. Escape sequences (arrow keys,
+/// paste brackets) are skipped rather than interpreted.
+#[derive(Debug, Default)]
+pub struct InputMirror {
+    text: String,
+    escape: Escape,
+}
+
+impl InputMirror {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.escape = Escape::None;
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for c in String::from_utf8_lossy(bytes).chars() {
+            match (self.escape, c) {
+                (Escape::Start, '[') => self.escape = Escape::Csi,
+                (Escape::Start, 'O') => self.escape = Escape::Ss3,
+                (Escape::Start | Escape::Ss3, _) => self.escape = Escape::None,
+                (Escape::Csi, '\x40'..='\x7e') => self.escape = Escape::None,
+                (Escape::Csi, _) => {}
+                (Escape::None, '\x1b') => self.escape = Escape::Start,
+                (Escape::None, '\x7f' | '\x08') => {
+                    self.text.pop();
+                }
+                // ^C abandons the line, ^U kills it.
+                (Escape::None, '\x03' | '\x15') => self.text.clear(),
+                // ^W kills the previous word.
+                (Escape::None, '\x17') => {
+                    let kept = self
+                        .text
+                        .trim_end()
+                        .trim_end_matches(|c: char| !c.is_whitespace())
+                        .len();
+                    self.text.truncate(kept);
+                }
+                // Enter either runs the command (whose start clears the
+                // mirror) or continues it on the next line.
+                (Escape::None, '\r' | '\n') => self.push('\n'),
+                (Escape::None, c) if !c.is_control() => self.push(c),
+                _ => {}
+            }
+        }
+    }
+
+    fn push(&mut self, c: char) {
+        if self.text.len() < MAX_MIRROR_BYTES {
+            self.text.push(c);
+        }
+    }
+}
+
+/// Join the echoed lines of the input region, each with the cursor's
+/// character offset when it is on that line. Continuation lines that end
+/// with the matching typed line lose whatever precedes it (the shell's
+/// continuation prompt).
+pub fn reconcile(echo: Vec<(String, Option<usize>)>, typed: &str) -> CommandLine {
+    let typed_lines: Vec<&str> = typed.split('\n').collect();
+    let same_shape = typed_lines.len() == echo.len();
+
+    let mut text = String::new();
+    let mut cursor = None;
+    for (idx, (line, line_cursor)) in echo.into_iter().enumerate() {
+        let (line, line_cursor) = match typed_lines.get(idx) {
+            Some(typed_line)
+                if idx > 0
+                    && same_shape
+                    && !typed_line.is_empty()
+                    && line.ends_with(typed_line) =>
+            {
+                let prompt_chars = line.chars().count() - typed_line.chars().count();
+                (
+                    typed_line.to_string(),
+                    line_cursor.map(|c| c.saturating_sub(prompt_chars)),
+                )
+            }
+            _ => (line, line_cursor),
+        };
+        if idx > 0 {
+            text.push('\n');
+        }
+        if let Some(col) = line_cursor {
+            cursor = Some(text.chars().count() + col);
+        }
+        text.push_str(&line);
+    }
+    CommandLine {
+        text,
+        cursor,
+        source: CommandLineSource::Echo,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mirrored(input: &[u8]) -> String {
+        let mut mirror = InputMirror::default();
+        mirror.feed(input);
+        mirror.text().to_string()
+    }
+
+    #[test]
+    fn mirror_applies_line_editing_and_skips_escapes() {
+        assert_eq!(mirrored(b"git stauts\x7f\x7f\x7f\x7fatus"), "git status");
+        assert_eq!(mirrored(b"echo one two\x17three"), "echo one three");
+        assert_eq!(mirrored(b"rm -rf /\x15ls\x1b[Dx\x1bOA"), "lsx");
+        assert_eq!(
+            mirrored(b"for f in *\rdo\x1b[200~ echo\x1b[201~"),
+            "for f in *\ndo echo"
+        );
+    }
+
+    #[test]
+    fn reconcile_strips_continuation_prompts_and_places_the_cursor() {
+        let echo = vec![
+            ("for f in *".to_string(), None),
+            ("for> do echo".to_string(), Some(9)),
+        ];
+        let line = reconcile(echo, "for f in *\ndo echo");
+        assert_eq!(line.text, "for f in *\ndo echo");
+        assert_eq!(line.cursor, Some(11 + 4));
+
+        // Completion changed the line; the echo wins.
+        let line = reconcile(vec![("git status".to_string(), Some(10))], "git st\t");
+        assert_eq!((line.text.as_str(), line.cursor), ("git status", Some(10)));
+    }
+}
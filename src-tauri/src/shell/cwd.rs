@@ -0,0 +1,93 @@
+//! Working directory for a new tab or split opened from an existing session.
+//!
+//! Every "new session from here" path goes through [`resolve`] so they agree
+//! on what "here" means. The current directory comes from shell integration
+//! (OSC 7); directories that no longer exist are skipped and the next
+//! candidate is used, ending at the home directory.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use super::git;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NewSessionCwd {
+    /// Where the source session's shell is now.
+    #[default]
+    Inherit,
+    /// Where the source session was started.
+    Original,
+    Home,
+    /// Top of the git working tree around the current directory.
+    ProjectRoot,
+}
+
+/// Directory a new session should start in, or `None` when no candidate
+/// exists (the shell then starts wherever the spawner defaults to).
+pub fn resolve(
+    policy: NewSessionCwd,
+    current: Option<&Path>,
+    original: Option<&Path>,
+    home: Option<&Path>,
+) -> Option<PathBuf> {
+    let project = current.and_then(git::work_tree);
+    let candidates = match policy {
+        NewSessionCwd::Inherit => [current, original, home, None],
+        NewSessionCwd::Original => [original, home, None, None],
+        NewSessionCwd::Home => [home, None, None, None],
+        NewSessionCwd::ProjectRoot => [project, current, original, home],
+    };
+    candidates
+        .into_iter()
+        .flatten()
+        .find(|dir| dir.is_dir())
+        .map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn policies_fall_back_to_existing_directories() {
+        let root = std::env::temp_dir().join(format!("rain-cwd-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let deep = root.join("repo/src/deep");
+        fs::create_dir_all(&deep).unwrap();
+        fs::create_dir_all(root.join("repo/.git")).unwrap();
+        let home = root.join("home");
+        fs::create_dir_all(&home).unwrap();
+        let gone = root.join("gone");
+
+        let resolve_with = |policy, current: &Path, original: &Path| {
+            resolve(policy, Some(current), Some(original), Some(&home))
+        };
+        assert_eq!(
+            resolve_with(NewSessionCwd::Inherit, &deep, &home),
+            Some(deep.clone())
+        );
+        assert_eq!(
+            resolve_with(NewSessionCwd::Original, &deep, &root),
+            Some(root.clone())
+        );
+        assert_eq!(
+            resolve_with(NewSessionCwd::ProjectRoot, &deep, &home),
+            Some(root.join("repo"))
+        );
+        // Outside a repository the project root is the current directory.
+        assert_eq!(
+            resolve_with(NewSessionCwd::ProjectRoot, &home, &root),
+            Some(home.clone())
+        );
+        assert_eq!(
+            resolve_with(NewSessionCwd::Inherit, &gone, &gone),
+            Some(home.clone())
+        );
+        assert_eq!(resolve(NewSessionCwd::Home, Some(&deep), None, None), None);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
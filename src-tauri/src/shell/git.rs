@@ -0,0 +1,199 @@
+//! Git branch/commit of a working directory, read straight from the
+//! repository files.
+//!
+//! Resolved once per prompt on the PTY reader thread, so it must stay cheap:
+//! no `git` process, just `HEAD`, the loose ref and `packed-refs`. Worktrees
+//! and submodules (`.git` files pointing elsewhere) are followed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GitRef {
+    /// Checked-out branch; during a rebase, the branch being rebased.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Full commit hash of `HEAD`; absent in a repository without commits.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// A rebase is in progress.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub rebasing: bool,
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+fn is_hash(s: &str) -> bool {
+    matches!(s.len(), 40 | 64) && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// The git directory for `cwd`, searching parent directories.
+fn find_git_dir(cwd: &Path) -> Option<PathBuf> {
+    for dir in cwd.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git);
+        }
+        if dot_git.is_file() {
+            let target = read_trimmed(&dot_git)?;
+            let target = target.strip_prefix("gitdir:")?.trim();
+            return Some(dir.join(target));
+        }
+    }
+    None
+}
+
+/// Top of the working tree containing `cwd`: the nearest ancestor with a
+/// `.git` directory or file.
+pub fn work_tree(cwd: &Path) -> Option<&Path> {
+    cwd.ancestors().find(|dir| dir.join(".git").exists())
+}
+
+/// Refs live in the main repository's directory; a linked worktree's git
+/// directory names it in `commondir`.
+fn common_dir(git_dir: &Path) -> PathBuf {
+    match read_trimmed(&git_dir.join("commondir")) {
+        Some(common) => git_dir.join(common),
+        None => git_dir.to_path_buf(),
+    }
+}
+
+fn resolve_ref(common: &Path, name: &str) -> Option<String> {
+    if let Some(hash) = read_trimmed(&common.join(name)).filter(|h| is_hash(h)) {
+        return Some(hash);
+    }
+    let packed = fs::read_to_string(common.join("packed-refs")).ok()?;
+    packed.lines().find_map(|line| {
+        let (hash, ref_name) = line.split_once(' ')?;
+        (ref_name == name && is_hash(hash)).then(|| hash.to_string())
+    })
+}
+
+/// Branch being rebased, from an interactive or `am`-based rebase.
+fn rebase_head_name(git_dir: &Path) -> Option<String> {
+    ["rebase-merge", "rebase-apply"]
+        .iter()
+        .find_map(|dir| read_trimmed(&git_dir.join(dir).join("head-name")))
+}
+
+/// Git state of the repository containing `cwd`, or `None` outside one.
+pub fn resolve(cwd: &Path) -> Option<GitRef> {
+    let git_dir = find_git_dir(cwd)?;
+    let head = read_trimmed(&git_dir.join("HEAD"))?;
+    let common = common_dir(&git_dir);
+    let rebase_head = rebase_head_name(&git_dir);
+
+    let (branch, commit) = match head.strip_prefix("ref:") {
+        Some(name) => {
+            let name = name.trim();
+            let branch = name.strip_prefix("refs/heads/").unwrap_or(name);
+            (Some(branch.to_string()), resolve_ref(&common, name))
+        }
+        None if is_hash(&head) => {
+            let branch = rebase_head
+                .as_deref()
+                .map(|name| name.strip_prefix("refs/heads/").unwrap_or(name).to_string());
+            (branch, Some(head))
+        }
+        None => return None,
+    };
+    Some(GitRef {
+        branch,
+        commit,
+        rebasing: rebase_head.is_some(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "0123456789abcdef0123456789abcdef01234567";
+    const OTHER: &str = "89abcdef0123456789abcdef0123456789abcdef";
+
+    struct TempRepo(PathBuf);
+
+    impl TempRepo {
+        fn new(name: &str) -> Self {
+            let root =
+                std::env::temp_dir().join(format!("rain-git-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(root.join(".git/refs/heads")).unwrap();
+            fs::create_dir_all(root.join("src/deep")).unwrap();
+            TempRepo(root)
+        }
+
+        fn write(&self, path: &str, contents: &str) {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+    }
+
+    impl Drop for TempRepo {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn branch_from_loose_and_packed_refs() {
+        let repo = TempRepo::new("refs");
+        repo.write(".git/HEAD", "ref: refs/heads/feature/x\n");
+        repo.write(".git/refs/heads/feature/x", &format!("{}\n", HASH));
+        let git = resolve(&repo.0.join("src/deep")).unwrap();
+        assert_eq!(git.branch.as_deref(), Some("feature/x"));
+        assert_eq!(git.commit.as_deref(), Some(HASH));
+        assert!(!git.rebasing);
+
+        repo.write(".git/HEAD", "ref: refs/heads/main\n");
+        repo.write(
+            ".git/packed-refs",
+            &format!("# pack-refs with: peeled\n{} refs/heads/main\n", OTHER),
+        );
+        assert_eq!(resolve(&repo.0).unwrap().commit.as_deref(), Some(OTHER));
+
+        // Unborn branch: no commit yet.
+        repo.write(".git/HEAD", "ref: refs/heads/empty\n");
+        let git = resolve(&repo.0).unwrap();
+        assert_eq!(git.branch.as_deref(), Some("empty"));
+        assert_eq!(git.commit, None);
+    }
+
+    #[test]
+    fn detached_head_during_rebase_names_the_branch() {
+        let repo = TempRepo::new("rebase");
+        repo.write(".git/HEAD", HASH);
+        assert_eq!(resolve(&repo.0).unwrap().branch, None);
+
+        repo.write(".git/rebase-merge/head-name", "refs/heads/feature/x\n");
+        let git = resolve(&repo.0).unwrap();
+        assert_eq!(git.branch.as_deref(), Some("feature/x"));
+        assert_eq!(git.commit.as_deref(), Some(HASH));
+        assert!(git.rebasing);
+    }
+
+    #[test]
+    fn linked_worktree_reads_refs_from_common_dir() {
+        let repo = TempRepo::new("worktree");
+        repo.write(".git/refs/heads/wt", HASH);
+        repo.write(".git/worktrees/wt/HEAD", "ref: refs/heads/wt\n");
+        repo.write(".git/worktrees/wt/commondir", "../..\n");
+        repo.write(
+            "wt/.git",
+            &format!("gitdir: {}\n", repo.0.join(".git/worktrees/wt").display()),
+        );
+        let git = resolve(&repo.0.join("wt")).unwrap();
+        assert_eq!(git.branch.as_deref(), Some("wt"));
+        assert_eq!(git.commit.as_deref(), Some(HASH));
+        assert_eq!(
+            work_tree(&repo.0.join("wt")),
+            Some(repo.0.join("wt").as_path())
+        );
+        assert_eq!(work_tree(&repo.0.join("src/deep")), Some(repo.0.as_path()));
+    }
+}
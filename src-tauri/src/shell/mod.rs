@@ -1,9 +1,68 @@
+pub mod args;
+pub mod capabilities;
+pub mod command_line;
+pub mod cwd;
 pub mod detect;
+pub mod git;
 pub mod hooks;
+pub mod naming;
+pub mod remote;
+pub mod zdotdir;
 
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Instant, SystemTime};
+
+use serde::Serialize;
 use uuid::Uuid;
 
 use crate::render::TerminalEvent;
+use capabilities::Capabilities;
+use command_line::InputMirror;
+use git::GitRef;
+use naming::SessionNamer;
+
+/// Finished and in-progress blocks remembered for backend lookups by ID.
+const MAX_BLOCK_RECORDS: usize = 1000;
+
+/// Where a command block lives in the grid, by stable line IDs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockRecord {
+    pub id: String,
+    pub command: Option<String>,
+    /// Line of the prompt (OSC 133;A).
+    pub prompt_line_id: u64,
+    /// Line and column where typed input starts, when the shell marks the
+    /// end of its prompt with a bare OSC 133;B.
+    pub input_start: Option<(u64, u16)>,
+    /// First output line (OSC 133;C), once the command started.
+    pub output_line_id: Option<u64>,
+    /// Line the block ended on (OSC 133;D), once it finished.
+    pub end_line_id: Option<u64>,
+    pub exit_code: Option<i32>,
+    /// When the command started (OSC 133;B with a command).
+    pub started_at: Option<SystemTime>,
+    /// Git branch/commit of the working directory when the block started.
+    pub git: Option<GitRef>,
+    /// Working directory at the block's prompt, as last reported by OSC 7.
+    pub cwd: String,
+}
+
+/// The block at the top of the frontend's viewport, for a sticky header
+/// over scrolled-back output. All `None` above the first block.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ViewportBlock {
+    pub block_id: Option<String>,
+    pub command: Option<String>,
+    pub cwd: Option<String>,
+}
+
+/// A command waiting to be run at the next idle prompt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueuedCommand {
+    pub id: String,
+    pub command: String,
+}
 
 /// Tracks shell integration state for command block detection.
 /// Receives events from OSC 133 (FinalTerm protocol) sequences.
@@ -13,10 +72,32 @@ pub struct ShellIntegration {
     pub active: bool,
     /// Current block ID (if a block is in progress)
     pub current_block_id: Option<String>,
+    /// A command has started (OSC 133;C) and not yet finished
+    pub command_running: bool,
+    /// Input was written to the PTY since the last prompt was drawn.
+    input_since_prompt: bool,
+    /// What was typed at the current prompt.
+    typed: InputMirror,
+    /// What the running command can use, from the `TERM` it runs under.
+    capabilities: Capabilities,
     /// Current working directory
     pub cwd: String,
+    /// The working directory is on this machine (not reported over SSH).
+    cwd_local: bool,
+    /// The shell reports its working directory through OSC 7, so `cwd`
+    /// follows it; otherwise `cwd` is only a seed or a value read from the OS.
+    cwd_reported: bool,
+    /// Derives the session's display name from the foreground command
+    namer: SessionNamer,
     /// Pending events to be sent to the frontend
     pending_events: Vec<TerminalEvent>,
+    /// Most recent blocks, oldest first.
+    blocks: VecDeque<BlockRecord>,
+    /// Commands to type once the shell is idle at a prompt, oldest first.
+    queue: VecDeque<QueuedCommand>,
+    /// Block and host of a running remote-login command whose prompt hasn't
+    /// been seen yet.
+    remote_pending: Option<(String, String)>,
 }
 
 impl ShellIntegration {
@@ -24,53 +105,290 @@ impl ShellIntegration {
         Self {
             active: false,
             cwd: String::new(),
+            cwd_local: true,
+            cwd_reported: false,
             current_block_id: None,
+            command_running: false,
+            input_since_prompt: false,
+            typed: InputMirror::default(),
+            capabilities: Capabilities::default(),
+            namer: SessionNamer::new(),
             pending_events: Vec::new(),
+            blocks: VecDeque::new(),
+            queue: VecDeque::new(),
+            remote_pending: None,
         }
     }
 
+    /// Look up a recent block by ID.
+    pub fn block(&self, id: &str) -> Option<&BlockRecord> {
+        self.blocks.iter().rev().find(|block| block.id == id)
+    }
+
+    /// Recent blocks, oldest first.
+    pub fn blocks(&self) -> impl Iterator<Item = &BlockRecord> {
+        self.blocks.iter()
+    }
+
+    /// The block whose command is running, if any.
+    pub fn running_block(&self) -> Option<&BlockRecord> {
+        if !self.command_running {
+            return None;
+        }
+        self.block(self.current_block_id.as_deref()?)
+    }
+
+    fn current_block_mut(&mut self) -> Option<&mut BlockRecord> {
+        let id = self.current_block_id.as_deref()?;
+        self.blocks.iter_mut().rev().find(|block| block.id == id)
+    }
+
     /// Called when OSC 133;A is received (prompt start).
     /// This marks the beginning of a new command block.
-    pub fn prompt_start(&mut self, global_row: u64) {
+    pub fn prompt_start(&mut self, global_row: u64, line_id: u64) {
         self.active = true;
+        self.command_running = false;
+        self.input_since_prompt = false;
+        self.typed.clear();
+        self.remote_pending = None;
         let id = Uuid::new_v4().to_string();
         self.current_block_id = Some(id.clone());
+        let git = self.git_ref();
+        if self.blocks.len() == MAX_BLOCK_RECORDS {
+            self.blocks.pop_front();
+        }
+        self.blocks.push_back(BlockRecord {
+            id: id.clone(),
+            command: None,
+            prompt_line_id: line_id,
+            input_start: None,
+            output_line_id: None,
+            end_line_id: None,
+            exit_code: None,
+            started_at: None,
+            git: git.clone(),
+            cwd: self.cwd.clone(),
+        });
         self.pending_events.push(TerminalEvent::BlockStarted {
             id,
             cwd: self.cwd.clone(),
             global_row,
+            line_id,
+            git,
         });
     }
 
+    /// Called when a bare OSC 133;B is received (end of prompt, input
+    /// starts at the cursor).
+    pub fn prompt_end(&mut self, line_id: u64, col: u16) {
+        if let Some(block) = self.current_block_mut() {
+            block.input_start = Some((line_id, col));
+        }
+    }
+
     /// Called when OSC 133;C is received (command output start).
     /// The command text has been identified and execution begins.
-    pub fn command_start(&mut self, command: String, global_row: u64) {
+    pub fn command_start(&mut self, command: String, global_row: u64, line_id: u64) {
+        self.command_running = true;
+        self.typed.clear();
+        self.namer.command_started(&command, Instant::now());
+        if let Some(term) = capabilities::command_term(&command) {
+            self.set_capabilities(Capabilities::for_term(&term));
+        }
+        self.remote_pending = naming::remote_host(&command)
+            .zip(self.current_block_id.clone())
+            .map(|(host, id)| (id, host));
+        if let Some(block) = self.current_block_mut() {
+            block.command = Some(command.clone());
+            block.output_line_id = Some(line_id);
+            block.started_at = Some(SystemTime::now());
+        }
         if let Some(id) = &self.current_block_id {
             self.pending_events.push(TerminalEvent::BlockCommand {
                 id: id.clone(),
                 command,
                 global_row,
+                line_id,
             });
         }
     }
 
     /// Called when OSC 133;D;<exit_code> is received (command finished).
-    pub fn command_end(&mut self, exit_code: i32, global_row: u64) {
+    pub fn command_end(&mut self, exit_code: i32, global_row: u64, line_id: u64) {
+        self.command_running = false;
+        self.namer.command_finished(Instant::now());
+        self.set_capabilities(Capabilities::default());
+        self.remote_pending = None;
+        if let Some(block) = self.current_block_mut() {
+            block.end_line_id = Some(line_id);
+            block.exit_code = Some(exit_code);
+        }
         if let Some(id) = self.current_block_id.take() {
             self.pending_events.push(TerminalEvent::BlockCompleted {
                 id,
                 exit_code,
                 global_row,
+                line_id,
             });
         }
     }
 
-    /// Called when OSC 7 is received (working directory update).
-    pub fn set_cwd(&mut self, path: String) {
+    /// Note the `TERM` in the running command's environment. A `TERM` from
+    /// the command line takes precedence. Returns whether the capabilities
+    /// changed.
+    pub fn set_process_term(&mut self, term: &str) -> bool {
+        if !self.command_running || self.capabilities.term.is_some() {
+            return false;
+        }
+        self.set_capabilities(Capabilities::for_term(term))
+    }
+
+    fn set_capabilities(&mut self, capabilities: Capabilities) -> bool {
+        if self.capabilities == capabilities {
+            return false;
+        }
+        self.capabilities = capabilities.clone();
+        self.pending_events
+            .push(TerminalEvent::TerminalCapabilitiesChanged { capabilities });
+        true
+    }
+
+    /// Record that the user sent input to the shell.
+    pub fn note_input(&mut self, bytes: &[u8]) {
+        self.input_since_prompt = true;
+        if !self.command_running {
+            self.typed.feed(bytes);
+        }
+    }
+
+    /// Text typed at the current prompt, with line editing applied.
+    pub fn typed_input(&self) -> &str {
+        self.typed.text()
+    }
+
+    /// Sitting at a prompt with nothing typed into it yet.
+    pub fn is_idle_at_prompt(&self) -> bool {
+        self.active && !self.command_running && !self.input_since_prompt
+    }
+
+    /// Queue a command to run at the next idle prompt.
+    pub fn queue_command(&mut self, command: String) -> QueuedCommand {
+        let queued = QueuedCommand {
+            id: Uuid::new_v4().to_string(),
+            command,
+        };
+        self.queue.push_back(queued.clone());
+        self.push_queue_changed();
+        queued
+    }
+
+    /// Drop a queued command. Returns whether it was still waiting.
+    pub fn cancel_queued(&mut self, id: &str) -> bool {
+        let before = self.queue.len();
+        self.queue.retain(|queued| queued.id != id);
+        let removed = self.queue.len() != before;
+        if removed {
+            self.push_queue_changed();
+        }
+        removed
+    }
+
+    pub fn queued_commands(&self) -> Vec<QueuedCommand> {
+        self.queue.iter().cloned().collect()
+    }
+
+    /// The next queued command, if the shell is idle at its prompt. Taking
+    /// it counts as input, so the same prompt never receives a second one.
+    pub fn take_queued_command(&mut self) -> Option<QueuedCommand> {
+        if !self.is_idle_at_prompt() {
+            return None;
+        }
+        let queued = self.queue.pop_front()?;
+        self.input_since_prompt = true;
+        self.push_queue_changed();
+        Some(queued)
+    }
+
+    fn push_queue_changed(&mut self) {
+        self.pending_events.push(TerminalEvent::CommandQueueChanged {
+            queued: self.queued_commands(),
+        });
+    }
+
+    /// A remote-login command is running and its prompt hasn't shown yet.
+    pub fn awaiting_remote_prompt(&self) -> bool {
+        self.remote_pending.is_some()
+    }
+
+    /// The remote shell's prompt appeared; offer hooks for it once.
+    pub fn remote_prompt_seen(&mut self) {
+        if let Some((block_id, host)) = self.remote_pending.take() {
+            self.pending_events
+                .push(TerminalEvent::RemotePromptDetected { block_id, host });
+        }
+    }
+
+    /// The working directory, if it is an absolute path on this machine.
+    pub fn local_cwd(&self) -> Option<&Path> {
+        let cwd = Path::new(&self.cwd);
+        (self.cwd_local && cwd.is_absolute()).then_some(cwd)
+    }
+
+    /// Git state of the working directory; remote paths are never looked up
+    /// on the local disk.
+    fn git_ref(&self) -> Option<GitRef> {
+        git::resolve(self.local_cwd()?)
+    }
+
+    /// Set the working directory without the shell having reported it, e.g.
+    /// to where a pre-started shell was sent. `local` is false when the path
+    /// belongs to another host.
+    pub fn set_cwd(&mut self, path: String, local: bool) {
         self.cwd = path.clone();
+        self.cwd_local = local;
         self.pending_events.push(TerminalEvent::CwdChanged { path });
     }
 
+    /// Called when OSC 7 is received (working directory update). From then
+    /// on `cwd` is the shell's own report.
+    pub fn report_cwd(&mut self, path: String, local: bool) {
+        self.cwd_reported = true;
+        self.set_cwd(path, local);
+    }
+
+    /// Whether the shell reports its working directory (OSC 7 was seen).
+    /// Otherwise `cwd` can be stale and the OS has the real one.
+    pub fn reports_cwd(&self) -> bool {
+        self.cwd_reported
+    }
+
+    /// Take a working directory read from the OS, for shells that don't
+    /// report theirs through OSC 7. Returns whether it changed.
+    pub fn adopt_os_cwd(&mut self, path: String) -> bool {
+        if self.cwd_reported || (self.cwd_local && self.cwd == path) {
+            return false;
+        }
+        self.set_cwd(path, true);
+        true
+    }
+
+    /// Called when tmux reports the name of the window hosting this pane.
+    pub fn set_tmux_window_name(&mut self, name: &str) {
+        self.namer.set_tmux_window_name(name, Instant::now());
+    }
+
+    /// Whether a session name change is waiting on its debounce.
+    pub fn session_name_pending(&self) -> bool {
+        self.namer.is_pending()
+    }
+
+    /// Queue a SessionNameChanged event if the debounced name has settled.
+    pub fn poll_session_name(&mut self) {
+        if let Some(name) = self.namer.poll(Instant::now()) {
+            self.pending_events.push(TerminalEvent::SessionNameChanged { name });
+        }
+    }
+
     /// Check if there are pending events to send.
     #[allow(dead_code)]
     pub fn has_pending_events(&self) -> bool {
@@ -0,0 +1,275 @@
+use std::time::{Duration, Instant};
+
+/// How long a candidate name must stay stable before it is published.
+/// Keeps quick commands like `ls` or `git status` from flickering the tab label.
+pub const SESSION_NAME_DEBOUNCE: Duration = Duration::from_millis(1000);
+
+/// Longest name we publish; anything longer is truncated with an ellipsis.
+const MAX_NAME_CHARS: usize = 40;
+
+/// Programs that only wrap the real command and should be skipped.
+const WRAPPER_COMMANDS: &[&str] = &[
+    "sudo",
+    "doas",
+    "exec",
+    "time",
+    "nohup",
+    "env",
+    "nice",
+    "command",
+    "builtin",
+    "caffeinate",
+];
+
+/// Remote-login programs where the first positional argument is a host.
+const REMOTE_COMMANDS: &[&str] = &["ssh", "mosh", "et", "autossh"];
+
+/// ssh flags that consume the following argument.
+const SSH_FLAGS_WITH_VALUE: &str = "BbcDEeFIiJLlmOopQRSWw";
+
+/// Derives a friendly session name from the foreground command and publishes
+/// it once it has been stable for [`SESSION_NAME_DEBOUNCE`].
+#[derive(Debug, Default)]
+pub struct SessionNamer {
+    /// Name derived from the currently running command, if any.
+    command_name: Option<String>,
+    /// Name reported by tmux for the window hosting this pane.
+    tmux_window_name: Option<String>,
+    /// When the effective candidate last changed.
+    candidate_since: Option<Instant>,
+    /// Name last published to the frontend.
+    published: Option<String>,
+}
+
+impl SessionNamer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A command started running in the foreground.
+    pub fn command_started(&mut self, command: &str, now: Instant) {
+        self.command_name = derive_session_name(command);
+        self.candidate_since = Some(now);
+    }
+
+    /// The foreground command finished; fall back to the tmux window name.
+    pub fn command_finished(&mut self, now: Instant) {
+        if self.command_name.take().is_some() {
+            self.candidate_since = Some(now);
+        }
+    }
+
+    /// tmux reported a (new) name for the window hosting this pane.
+    pub fn set_tmux_window_name(&mut self, name: &str, now: Instant) {
+        let name = truncate_name(name.trim());
+        self.tmux_window_name = (!name.is_empty()).then_some(name);
+        self.candidate_since = Some(now);
+    }
+
+    /// The name that would be published once the debounce elapses.
+    fn candidate(&self) -> Option<&String> {
+        self.command_name
+            .as_ref()
+            .or(self.tmux_window_name.as_ref())
+    }
+
+    /// Whether a name change is waiting for its debounce to elapse.
+    pub fn is_pending(&self) -> bool {
+        self.candidate() != self.published.as_ref()
+    }
+
+    /// Publish the candidate if it has been stable long enough. Returns
+    /// `Some(name)` when the published name changed (`None` inside clears it).
+    pub fn poll(&mut self, now: Instant) -> Option<Option<String>> {
+        if !self.is_pending() {
+            return None;
+        }
+        let since = self.candidate_since?;
+        if now.saturating_duration_since(since) < SESSION_NAME_DEBOUNCE {
+            return None;
+        }
+        self.published = self.candidate().cloned();
+        Some(self.published.clone())
+    }
+}
+
+/// Split off leading environment assignments and wrapper commands (plus
+/// their flags), returning the real program's name and its arguments.
+fn split_program(command: &str) -> Option<(&str, impl Iterator<Item = &str>)> {
+    let mut tokens = command.split_whitespace().peekable();
+    while let Some(&token) = tokens.peek() {
+        let is_assignment = token
+            .split_once('=')
+            .is_some_and(|(key, _)| !key.is_empty() && !key.starts_with('-'));
+        if is_assignment || WRAPPER_COMMANDS.contains(&token) || token.starts_with('-') {
+            tokens.next();
+        } else {
+            break;
+        }
+    }
+
+    let program = tokens.next()?;
+    let program = program.rsplit('/').next().unwrap_or(program);
+    (!program.is_empty()).then_some((program, tokens))
+}
+
+/// The first positional argument of a remote-login command, without a user.
+fn host_argument<'a>(args: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let mut skip_value = false;
+    for token in args {
+        if skip_value {
+            skip_value = false;
+            continue;
+        }
+        if let Some(flags) = token.strip_prefix('-') {
+            // `-p 22` consumes the next token, `-p22` does not.
+            skip_value = flags.len() == 1 && SSH_FLAGS_WITH_VALUE.contains(flags);
+            continue;
+        }
+        return Some(token.rsplit('@').next().unwrap_or(token));
+    }
+    None
+}
+
+/// The host a remote-login command (`ssh`, `mosh`, ...) connects to.
+/// Whether a process named `name` is a remote-login client, whose terminal
+/// input goes to a shell on another machine.
+pub fn is_remote_program(name: &str) -> bool {
+    REMOTE_COMMANDS.contains(&name) || name == "mosh-client"
+}
+
+pub fn remote_host(command: &str) -> Option<String> {
+    let (program, args) = split_program(command)?;
+    if !REMOTE_COMMANDS.contains(&program) {
+        return None;
+    }
+    host_argument(args).map(str::to_string)
+}
+
+/// Turn a command line into a short label, e.g. `FOO=1 sudo npm run dev -- --port 3000`
+/// becomes `npm run dev` and `ssh -p 2222 admin@prod-db` becomes `ssh prod-db`.
+pub fn derive_session_name(command: &str) -> Option<String> {
+    let (program, args) = split_program(command)?;
+
+    let mut parts = vec![program.to_string()];
+    if REMOTE_COMMANDS.contains(&program) {
+        parts.extend(host_argument(args).map(str::to_string));
+    } else {
+        parts.extend(
+            args.take_while(|token| *token != "--" && !token.contains(['|', ';', '&', '>', '<']))
+                .filter(|token| !token.starts_with('-'))
+                .take(2)
+                .map(str::to_string),
+        );
+    }
+
+    Some(truncate_name(&parts.join(" ")))
+}
+
+fn truncate_name(name: &str) -> String {
+    if name.chars().count() <= MAX_NAME_CHARS {
+        return name.to_string();
+    }
+    let mut truncated: String = name.chars().take(MAX_NAME_CHARS - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_names_from_common_commands() {
+        assert_eq!(
+            derive_session_name("npm run dev").as_deref(),
+            Some("npm run dev")
+        );
+        assert_eq!(
+            derive_session_name("FOO=1 sudo -E npm run dev -- --port 3000").as_deref(),
+            Some("npm run dev")
+        );
+        assert_eq!(
+            derive_session_name("ssh -p 2222 -A admin@prod-db uptime").as_deref(),
+            Some("ssh prod-db")
+        );
+        assert_eq!(
+            derive_session_name("/usr/bin/vim src/main.rs").as_deref(),
+            Some("vim src/main.rs")
+        );
+        assert_eq!(
+            derive_session_name("tail -f log.txt | grep err").as_deref(),
+            Some("tail log.txt")
+        );
+        assert_eq!(derive_session_name("   ").as_deref(), None);
+    }
+
+    #[test]
+    fn remote_host_skips_flags_and_users() {
+        assert_eq!(
+            remote_host("TERM=xterm ssh -i ~/.ssh/id -A deploy@web-1 -t tmux").as_deref(),
+            Some("web-1")
+        );
+        assert_eq!(remote_host("mosh box").as_deref(), Some("box"));
+        assert_eq!(remote_host("ssh -v").as_deref(), None);
+        assert_eq!(remote_host("scp a b:c").as_deref(), None);
+        assert!(is_remote_program("ssh") && is_remote_program("mosh-client"));
+        assert!(!is_remote_program("scp"));
+    }
+
+    #[test]
+    fn long_names_are_truncated() {
+        let name = derive_session_name(&format!("cat {}", "x".repeat(80))).unwrap();
+        assert_eq!(name.chars().count(), MAX_NAME_CHARS);
+        assert!(name.ends_with('…'));
+    }
+
+    #[test]
+    fn short_commands_do_not_publish_a_name() {
+        let start = Instant::now();
+        let mut namer = SessionNamer::new();
+        namer.command_started("ls -la", start);
+        namer.command_finished(start + Duration::from_millis(20));
+        assert_eq!(namer.poll(start + SESSION_NAME_DEBOUNCE * 2), None);
+        assert!(!namer.is_pending());
+    }
+
+    #[test]
+    fn long_running_command_publishes_after_debounce_and_clears_on_exit() {
+        let start = Instant::now();
+        let mut namer = SessionNamer::new();
+        namer.command_started("cargo run --release", start);
+        assert_eq!(namer.poll(start + Duration::from_millis(100)), None);
+        assert_eq!(
+            namer.poll(start + SESSION_NAME_DEBOUNCE),
+            Some(Some("cargo run".to_string()))
+        );
+
+        let end = start + Duration::from_secs(30);
+        namer.command_finished(end);
+        assert_eq!(namer.poll(end + SESSION_NAME_DEBOUNCE), Some(None));
+    }
+
+    #[test]
+    fn tmux_window_name_is_the_fallback() {
+        let start = Instant::now();
+        let mut namer = SessionNamer::new();
+        namer.set_tmux_window_name("editor", start);
+        assert_eq!(
+            namer.poll(start + SESSION_NAME_DEBOUNCE),
+            Some(Some("editor".to_string()))
+        );
+
+        let later = start + Duration::from_secs(5);
+        namer.command_started("htop", later);
+        assert_eq!(
+            namer.poll(later + SESSION_NAME_DEBOUNCE),
+            Some(Some("htop".to_string()))
+        );
+        namer.command_finished(later + Duration::from_secs(10));
+        assert_eq!(
+            namer.poll(later + Duration::from_secs(20)),
+            Some(Some("editor".to_string()))
+        );
+    }
+}
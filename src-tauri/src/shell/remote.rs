@@ -0,0 +1,85 @@
+//! Shell hooks for remote shells reached over ssh.
+//!
+//! Rain's hooks are only installed in shells it spawns itself, so blocks and
+//! cwd tracking stop at an ssh connection. When the remote prompt shows up,
+//! the frontend may offer to type [`bootstrap_command`] into it: a one-line
+//! version of the bash and zsh hooks that lives only as long as that remote
+//! shell and writes nothing to the server.
+
+/// Characters remote prompts commonly end with.
+const PROMPT_ENDINGS: &[char] = &['$', '#', '%', '>', '❯'];
+
+/// Longer lines are output, not a prompt.
+const MAX_PROMPT_CHARS: usize = 200;
+
+/// Whether `line`, the cursor row up to the cursor, looks like a shell
+/// prompt waiting for input. Password and host key questions don't qualify.
+pub fn looks_like_prompt(line: &str) -> bool {
+    let line = line.trim_end();
+    line.chars().count() <= MAX_PROMPT_CHARS && line.ends_with(PROMPT_ENDINGS)
+}
+
+/// zsh: the same sequences as `rain.zsh`, without the tmux interception.
+const ZSH_HOOKS: &str = "__rain_a=; \
+__rain_p() { local s=$?; [ -n \"$__rain_a\" ] && printf '\\033]133;D;%d\\007' $s; __rain_a=; \
+printf '\\033]7;file://%s%s\\007\\033]133;A\\007' \"$HOST\" \"$PWD\"; }; \
+__rain_x() { printf '\\033]133;B;%s\\007\\033]133;C\\007' \"$1\"; __rain_a=1; }; \
+autoload -Uz add-zsh-hook; add-zsh-hook precmd __rain_p; add-zsh-hook preexec __rain_x";
+
+/// bash: the same sequences as `rain.bash`, without the tmux interception.
+const BASH_HOOKS: &str = "__rain_a=; \
+__rain_p() { local s=$?; [ -n \"$__rain_a\" ] && printf '\\033]133;D;%d\\007' $s; __rain_a=; \
+printf '\\033]7;file://%s%s\\007\\033]133;A\\007' \"$HOSTNAME\" \"$PWD\"; }; \
+__rain_x() { [ -n \"$COMP_LINE\" ] || [ -n \"$__rain_a\" ] && return; \
+case \"$BASH_COMMAND\" in __rain_*) return;; esac; \
+printf '\\033]133;B;%s\\007\\033]133;C\\007' \"$BASH_COMMAND\"; __rain_a=1; }; \
+PROMPT_COMMAND=\"__rain_p;${PROMPT_COMMAND:-}\"; trap __rain_x DEBUG";
+
+/// The line typed into the remote shell to install the hooks. It starts
+/// with a space so `HISTCONTROL=ignorespace` and zsh's `HIST_IGNORE_SPACE`
+/// keep it out of the history; shells other than bash and zsh skip it.
+pub fn bootstrap_command() -> String {
+    format!(
+        " if [ -n \"$ZSH_VERSION\" ]; then {}; elif [ -n \"$BASH_VERSION\" ]; then {}; fi",
+        ZSH_HOOKS, BASH_HOOKS
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn recognises_prompts_but_not_questions() {
+        assert!(looks_like_prompt("deploy@web-1:~$ "));
+        assert!(looks_like_prompt("[root@db /var/log]# "));
+        assert!(looks_like_prompt("web-1% "));
+        assert!(looks_like_prompt("~/src ❯ "));
+        assert!(!looks_like_prompt("deploy@web-1's password: "));
+        assert!(!looks_like_prompt(
+            "Are you sure you want to continue connecting (yes/no)? "
+        ));
+        assert!(!looks_like_prompt(""));
+        assert!(!looks_like_prompt(&format!(
+            "{}$",
+            "x".repeat(MAX_PROMPT_CHARS)
+        )));
+    }
+
+    #[test]
+    fn bootstrap_is_one_line_that_bash_accepts() {
+        let command = bootstrap_command();
+        assert!(command.starts_with(' '));
+        assert!(!command.contains('\n'));
+        // `bash -n` only parses, which covers the zsh branch too.
+        let Ok(output) = Command::new("bash").args(["-n", "-c", &command]).output() else {
+            return;
+        };
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
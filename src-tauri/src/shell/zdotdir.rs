@@ -0,0 +1,193 @@
+//! ZDOTDIR wrapper used to inject Rain's zsh hooks.
+//!
+//! zsh reads its startup files from `$ZDOTDIR`, so Rain points it at a
+//! temporary directory holding one wrapper per startup file. Each wrapper
+//! switches `ZDOTDIR` back to the user's value while it sources the user's
+//! own file, so frameworks that probe `${ZDOTDIR:-$HOME}` for their files
+//! (oh-my-zsh, zinit, powerlevel10k) find them, and a `.zshenv` that moves
+//! `ZDOTDIR` elsewhere is followed for the remaining files. After the last
+//! startup file of the session the user's `ZDOTDIR` is restored for good,
+//! so `.zlogout`, history and completion dumps resolve as without Rain.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Restore the user's ZDOTDIR (unset if they had none). Exported, since an
+/// earlier `unset` dropped the attribute.
+const RESTORE_USER_ZDOTDIR: &str = r#"if [ -n "$RAIN_ORIG_ZDOTDIR" ]; then
+  export ZDOTDIR="$RAIN_ORIG_ZDOTDIR"
+else
+  unset ZDOTDIR
+fi
+"#;
+
+/// Leave ZDOTDIR with the user for the rest of the session.
+fn finish() -> String {
+    format!(
+        "{}unset RAIN_ORIG_ZDOTDIR RAIN_WRAPPER_ZDOTDIR\n",
+        RESTORE_USER_ZDOTDIR
+    )
+}
+
+/// Source the user's `name` with their ZDOTDIR in place, then note any
+/// change the file made to it.
+fn source_user_file(name: &str) -> String {
+    format!(
+        r#"{restore}if [ -f "${{ZDOTDIR:-$HOME}}/{name}" ]; then
+  source "${{ZDOTDIR:-$HOME}}/{name}"
+fi
+RAIN_ORIG_ZDOTDIR="$ZDOTDIR"
+"#,
+        restore = RESTORE_USER_ZDOTDIR,
+        name = name,
+    )
+}
+
+const BACK_TO_WRAPPER: &str = "ZDOTDIR=\"$RAIN_WRAPPER_ZDOTDIR\"\n";
+
+/// Wrapper startup files, in the order zsh reads them: `.zshenv` always,
+/// `.zprofile` for login shells, `.zshrc` for interactive ones and
+/// `.zlogin` for login shells again.
+pub fn wrapper_files() -> [(&'static str, String); 4] {
+    let zshenv = format!(
+        "RAIN_WRAPPER_ZDOTDIR=\"$ZDOTDIR\"\n{source}\
+         if [[ ! -o interactive && ! -o login ]]; then\n{finish}else\n  {back}fi\n",
+        source = source_user_file(".zshenv"),
+        finish = finish(),
+        back = BACK_TO_WRAPPER,
+    );
+    let zprofile = format!("{}{}", source_user_file(".zprofile"), BACK_TO_WRAPPER);
+    // Hooks go in last, over whatever the user's .zshrc set up.
+    let zshrc = format!(
+        "{source}if [ -n \"$RAIN_SHELL_INIT\" ]; then\n  eval \"$RAIN_SHELL_INIT\"\nfi\n\
+         if [[ ! -o login ]]; then\n{finish}else\n  {back}fi\n",
+        source = source_user_file(".zshrc"),
+        finish = finish(),
+        back = BACK_TO_WRAPPER,
+    );
+    let zlogin = format!("{}{}", source_user_file(".zlogin"), finish());
+    [
+        (".zshenv", zshenv),
+        (".zprofile", zprofile),
+        (".zshrc", zshrc),
+        (".zlogin", zlogin),
+    ]
+}
+
+/// Write the wrapper startup files into `dir`, the ZDOTDIR zsh is started
+/// with.
+pub fn write_wrapper(dir: &Path) -> io::Result<()> {
+    for (name, contents) in wrapper_files() {
+        fs::write(dir.join(name), contents)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "rain-zdotdir-test-{}-{}",
+            name,
+            uuid::Uuid::new_v4()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Run an interactive login zsh through the wrapper and return what it
+    /// printed, or `None` (cleaning up `home`) when zsh isn't installed.
+    fn run_zsh(home: &Path, script: &str) -> Option<String> {
+        let wrapper = scratch_dir("wrapper");
+        write_wrapper(&wrapper).unwrap();
+        let output = Command::new("zsh")
+            .args(["--login", "--interactive", "-c", script])
+            .env("HOME", home)
+            .env("ZDOTDIR", &wrapper)
+            .env("RAIN_SHELL_INIT", "print -r -- hooks:$ZDOTDIR >> $HOME/log")
+            .env_remove("RAIN_ORIG_ZDOTDIR")
+            .output();
+        fs::remove_dir_all(&wrapper).ok();
+        if output.is_err() {
+            fs::remove_dir_all(home).ok();
+        }
+        Some(String::from_utf8_lossy(&output.ok()?.stdout).into_owned())
+    }
+
+    #[test]
+    fn wrappers_cover_every_startup_file_in_order() {
+        let names: Vec<&str> = wrapper_files().iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, [".zshenv", ".zprofile", ".zshrc", ".zlogin"]);
+        for (name, contents) in wrapper_files() {
+            assert!(contents.contains(&format!("${{ZDOTDIR:-$HOME}}/{}", name)));
+        }
+    }
+
+    #[test]
+    fn zshenv_moving_zdotdir_is_followed() {
+        // A common layout: ~/.zshenv points ZDOTDIR at ~/.config/zsh, where
+        // the framework keeps .zshrc and its own files.
+        let home = scratch_dir("home");
+        let config = home.join(".config/zsh");
+        fs::create_dir_all(&config).unwrap();
+        fs::write(
+            home.join(".zshenv"),
+            "ZDOTDIR=$HOME/.config/zsh\nprint -r -- zshenv >> $HOME/log\n",
+        )
+        .unwrap();
+        fs::write(
+            config.join(".zprofile"),
+            "print -r -- zprofile >> $HOME/log\n",
+        )
+        .unwrap();
+        fs::write(
+            config.join(".zshrc"),
+            "[[ -f $ZDOTDIR/.p10k.zsh ]] && source $ZDOTDIR/.p10k.zsh\n\
+             print -r -- zshrc >> $HOME/log\n",
+        )
+        .unwrap();
+        fs::write(config.join(".p10k.zsh"), "print -r -- p10k >> $HOME/log\n").unwrap();
+        fs::write(config.join(".zlogin"), "print -r -- zlogin >> $HOME/log\n").unwrap();
+
+        let Some(out) = run_zsh(&home, "print -r -- $ZDOTDIR") else {
+            return;
+        };
+        assert_eq!(out.trim(), config.to_string_lossy());
+        let log = fs::read_to_string(home.join("log")).unwrap();
+        assert_eq!(
+            log.lines().collect::<Vec<_>>(),
+            [
+                "zshenv".to_string(),
+                "zprofile".to_string(),
+                "p10k".to_string(),
+                "zshrc".to_string(),
+                format!("hooks:{}", config.display()),
+                "zlogin".to_string(),
+            ]
+        );
+        fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn zdotdir_is_unset_again_without_a_user_value() {
+        let home = scratch_dir("home");
+        fs::write(
+            home.join(".zshrc"),
+            "print -r -- zshrc:${ZDOTDIR-unset} >> $HOME/log\n",
+        )
+        .unwrap();
+        let script = "print -r -- ${ZDOTDIR-unset}:${RAIN_WRAPPER_ZDOTDIR-gone}";
+        let Some(out) = run_zsh(&home, script) else {
+            return;
+        };
+        assert_eq!(out.trim(), "unset:gone");
+        let log = fs::read_to_string(home.join("log")).unwrap();
+        assert_eq!(log.lines().next(), Some("zshrc:unset"));
+        fs::remove_dir_all(&home).ok();
+    }
+}
@@ -15,7 +15,7 @@ impl Default for Color {
 }
 
 /// Serializable color for IPC transport to the frontend.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 #[serde(tag = "type")]
 pub enum SerializableColor {
     Default,
@@ -72,3 +72,257 @@ pub fn indexed_to_rgb(index: u8) -> (u8, u8, u8) {
         }
     }
 }
+
+/// A palette entry that differs from the default, as reported to the
+/// frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PaletteEntry {
+    pub index: u8,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+/// Colors a theme gives the terminal: the 16 ANSI colors and the default
+/// foreground, background and cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThemeColors {
+    pub foreground: (u8, u8, u8),
+    pub background: (u8, u8, u8),
+    pub cursor: (u8, u8, u8),
+    pub ansi: [(u8, u8, u8); 16],
+}
+
+impl Default for ThemeColors {
+    fn default() -> Self {
+        let [foreground, background, cursor] = DEFAULT_DYNAMIC_COLORS;
+        Self {
+            foreground,
+            background,
+            cursor,
+            ansi: std::array::from_fn(|i| indexed_to_rgb(i as u8)),
+        }
+    }
+}
+
+/// A session's 256-color palette: the theme's ANSI colors and the defaults
+/// of [`indexed_to_rgb`], with the entries programs changed through OSC 4.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    overrides: Box<[Option<(u8, u8, u8)>; 256]>,
+    ansi: [(u8, u8, u8); 16],
+    /// Whether the frontend lacks the theme's ANSI colors, so spans carry
+    /// them as RGB.
+    resolve_ansi: bool,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            overrides: Box::new([None; 256]),
+            ansi: ThemeColors::default().ansi,
+            resolve_ansi: false,
+        }
+    }
+}
+
+impl Palette {
+    pub fn rgb(&self, index: u8) -> (u8, u8, u8) {
+        self.overrides[index as usize].unwrap_or_else(|| match self.ansi.get(index as usize) {
+            Some(&rgb) => rgb,
+            None => indexed_to_rgb(index),
+        })
+    }
+
+    /// Use a theme's ANSI colors. `resolve` sends them to the frontend as
+    /// RGB, for themes it has no palette of its own for.
+    pub fn set_theme(&mut self, ansi: [(u8, u8, u8); 16], resolve: bool) {
+        self.ansi = ansi;
+        self.resolve_ansi = resolve;
+    }
+
+    /// Whether [`Palette::resolve`] changes any color.
+    pub fn resolves(&self) -> bool {
+        self.resolve_ansi || !self.is_default()
+    }
+
+    /// Returns whether the entry changed.
+    pub fn set(&mut self, index: u8, rgb: (u8, u8, u8)) -> bool {
+        let slot = &mut self.overrides[index as usize];
+        let changed = *slot != Some(rgb);
+        *slot = Some(rgb);
+        changed
+    }
+
+    /// Restore one entry to its default. Returns whether it had been set.
+    pub fn reset(&mut self, index: u8) -> bool {
+        self.overrides[index as usize].take().is_some()
+    }
+
+    /// Restore every entry. Returns whether any had been set.
+    pub fn reset_all(&mut self) -> bool {
+        let changed = !self.is_default();
+        *self.overrides = [None; 256];
+        changed
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.overrides.iter().all(Option::is_none)
+    }
+
+    pub fn entries(&self) -> Vec<PaletteEntry> {
+        self.overrides
+            .iter()
+            .enumerate()
+            .filter_map(|(index, rgb)| {
+                rgb.map(|(r, g, b)| PaletteEntry {
+                    index: index as u8,
+                    r,
+                    g,
+                    b,
+                })
+            })
+            .collect()
+    }
+
+    /// Replace an indexed color whose entry was changed with its RGB value,
+    /// so renderers that only know the theme palette draw it correctly.
+    pub fn resolve(&self, color: &mut SerializableColor) {
+        if let SerializableColor::Indexed { index } = *color {
+            let theme = (self.resolve_ansi && index < 16).then(|| self.ansi[index as usize]);
+            if let Some((r, g, b)) = self.overrides[index as usize].or(theme) {
+                *color = SerializableColor::Rgb { r, g, b };
+            }
+        }
+    }
+}
+
+/// Default foreground, background and cursor colors reported to OSC
+/// 10/11/12 queries until a theme or program sets its own (the dark
+/// theme's).
+const DEFAULT_DYNAMIC_COLORS: [(u8, u8, u8); 3] =
+    [(0xd4, 0xd4, 0xd4), (0x0e, 0x0e, 0x0e), (0xd4, 0xd4, 0xd4)];
+
+/// The default foreground, background and cursor colors, which OSC 10, 11
+/// and 12 set and OSC 110, 111 and 112 reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DynamicColors {
+    /// Overrides by slot: 0 foreground, 1 background, 2 cursor.
+    overrides: [Option<(u8, u8, u8)>; 3],
+    /// The theme's colors, used where nothing is overridden.
+    defaults: [(u8, u8, u8); 3],
+}
+
+impl Default for DynamicColors {
+    fn default() -> Self {
+        Self {
+            overrides: [None; 3],
+            defaults: DEFAULT_DYNAMIC_COLORS,
+        }
+    }
+}
+
+impl DynamicColors {
+    pub fn rgb(&self, slot: usize) -> (u8, u8, u8) {
+        self.overrides[slot].unwrap_or(self.defaults[slot])
+    }
+
+    pub fn set_theme(&mut self, theme: &ThemeColors) {
+        self.defaults = [theme.foreground, theme.background, theme.cursor];
+    }
+
+    /// Returns whether the color changed.
+    pub fn set(&mut self, slot: usize, rgb: (u8, u8, u8)) -> bool {
+        let changed = self.overrides[slot] != Some(rgb);
+        self.overrides[slot] = Some(rgb);
+        changed
+    }
+
+    /// Returns whether the color had been set.
+    pub fn reset(&mut self, slot: usize) -> bool {
+        self.overrides[slot].take().is_some()
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.overrides.iter().all(Option::is_none)
+    }
+
+    /// The color in `slot` if a program set it; `None` follows the theme.
+    pub fn get(&self, slot: usize) -> Option<SerializableColor> {
+        self.overrides[slot].map(|(r, g, b)| SerializableColor::Rgb { r, g, b })
+    }
+}
+
+/// Parse an X11 color specification as used by OSC 4/10/11/12:
+/// `rgb:R/G/B` with 1–4 hex digits per component, or `#RGB`, `#RRGGBB`,
+/// `#RRRGGGBBB` and `#RRRRGGGGBBBB`.
+pub fn parse_color_spec(spec: &str) -> Option<(u8, u8, u8)> {
+    // Scale an n-digit hex component to 8 bits.
+    fn component(hex: &str) -> Option<u8> {
+        if hex.is_empty() || hex.len() > 4 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        let max = (1u32 << (4 * hex.len())) - 1;
+        Some(((value * 255 + max / 2) / max) as u8)
+    }
+
+    if let Some(rest) = spec.strip_prefix("rgb:") {
+        let mut parts = rest.split('/');
+        let rgb = (
+            component(parts.next()?)?,
+            component(parts.next()?)?,
+            component(parts.next()?)?,
+        );
+        return parts.next().is_none().then_some(rgb);
+    }
+    let hex = spec.strip_prefix('#')?;
+    if hex.is_empty() || hex.len() % 3 != 0 || hex.len() > 12 || !hex.is_ascii() {
+        return None;
+    }
+    let n = hex.len() / 3;
+    // `#` forms give the most significant digits, not a scaled value.
+    let top = |i: usize| {
+        let digits = &hex[i * n..(i + 1) * n];
+        let padded = format!("{:0<2}", digits);
+        u8::from_str_radix(&padded[..2], 16).ok()
+    };
+    Some((top(0)?, top(1)?, top(2)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_x11_color_specs() {
+        assert_eq!(parse_color_spec("rgb:ff/80/00"), Some((0xff, 0x80, 0x00)));
+        assert_eq!(
+            parse_color_spec("rgb:ffff/8080/0000"),
+            Some((0xff, 0x80, 0x00))
+        );
+        assert_eq!(parse_color_spec("rgb:f/8/0"), Some((0xff, 0x88, 0x00)));
+        assert_eq!(parse_color_spec("#1a2b3c"), Some((0x1a, 0x2b, 0x3c)));
+        assert_eq!(parse_color_spec("#abc"), Some((0xa0, 0xb0, 0xc0)));
+        assert_eq!(parse_color_spec("rgb:ff/80"), None);
+        assert_eq!(parse_color_spec("red"), None);
+        assert_eq!(parse_color_spec("#12345"), None);
+    }
+
+    #[test]
+    fn palette_overrides_resolve_indexed_colors() {
+        let mut palette = Palette::default();
+        assert!(palette.set(1, (1, 2, 3)));
+        assert!(!palette.set(1, (1, 2, 3)));
+        assert_eq!(palette.rgb(1), (1, 2, 3));
+        assert_eq!(palette.rgb(2), indexed_to_rgb(2));
+
+        let mut color = SerializableColor::Indexed { index: 1 };
+        palette.resolve(&mut color);
+        assert!(matches!(color, SerializableColor::Rgb { r: 1, g: 2, b: 3 }));
+
+        assert!(palette.reset(1));
+        assert!(!palette.reset_all());
+        assert!(palette.is_default());
+    }
+}
@@ -1,5 +1,6 @@
 use super::color::Color;
 use bitflags::bitflags;
+use serde::Serialize;
 
 bitflags! {
     /// Cell text attributes as a compact bitflag set.
@@ -13,6 +14,91 @@ bitflags! {
         const REVERSE       = 1 << 5;
         const HIDDEN        = 1 << 6;
         const STRIKETHROUGH = 1 << 7;
+        /// Underline variants from SGR `4:x`; set together with `UNDERLINE`.
+        const DOUBLE_UNDERLINE = 1 << 8;
+        const CURLY_UNDERLINE  = 1 << 9;
+        const DOTTED_UNDERLINE = 1 << 10;
+        const DASHED_UNDERLINE = 1 << 11;
+        /// SGR 6; replaces `BLINK` and is cleared with it by SGR 25.
+        const RAPID_BLINK      = 1 << 12;
+        const UNDERLINE_STYLES = Self::DOUBLE_UNDERLINE.bits()
+            | Self::CURLY_UNDERLINE.bits()
+            | Self::DOTTED_UNDERLINE.bits()
+            | Self::DASHED_UNDERLINE.bits();
+    }
+}
+
+/// How an underlined cell's underline is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnderlineStyle {
+    #[default]
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+impl UnderlineStyle {
+    pub fn is_single(&self) -> bool {
+        *self == UnderlineStyle::Single
+    }
+}
+
+/// How fast blinking text blinks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlinkRate {
+    /// SGR 5, under 150 blinks per minute.
+    Slow,
+    /// SGR 6, 150 or more per minute.
+    Rapid,
+}
+
+impl CellAttrs {
+    /// Apply SGR `4:x`: 0 removes the underline, 1-5 select single, double,
+    /// curly, dotted or dashed. Unknown styles fall back to single.
+    pub fn set_underline(&mut self, style: u16) {
+        self.remove(Self::UNDERLINE | Self::UNDERLINE_STYLES);
+        let variant = match style {
+            0 => return,
+            2 => Self::DOUBLE_UNDERLINE,
+            3 => Self::CURLY_UNDERLINE,
+            4 => Self::DOTTED_UNDERLINE,
+            5 => Self::DASHED_UNDERLINE,
+            _ => Self::empty(),
+        };
+        self.insert(Self::UNDERLINE | variant);
+    }
+
+    /// Blink rate, or `None` when the cell doesn't blink.
+    pub fn blink_rate(self) -> Option<BlinkRate> {
+        if self.contains(Self::RAPID_BLINK) {
+            Some(BlinkRate::Rapid)
+        } else if self.contains(Self::BLINK) {
+            Some(BlinkRate::Slow)
+        } else {
+            None
+        }
+    }
+
+    /// Style of the underline, or `None` when the cell isn't underlined.
+    pub fn underline_style(self) -> Option<UnderlineStyle> {
+        if !self.contains(Self::UNDERLINE) {
+            return None;
+        }
+        Some(if self.contains(Self::DOUBLE_UNDERLINE) {
+            UnderlineStyle::Double
+        } else if self.contains(Self::CURLY_UNDERLINE) {
+            UnderlineStyle::Curly
+        } else if self.contains(Self::DOTTED_UNDERLINE) {
+            UnderlineStyle::Dotted
+        } else if self.contains(Self::DASHED_UNDERLINE) {
+            UnderlineStyle::Dashed
+        } else {
+            UnderlineStyle::Single
+        })
     }
 }
 
@@ -38,8 +124,12 @@ pub struct CursorState {
     pub fg: Color,
     pub bg: Color,
     pub attrs: CellAttrs,
+    /// SGR 58 underline color; `Default` draws underlines in the foreground.
+    pub underline_color: Color,
     pub shape: CursorShape,
     pub visible: bool,
+    /// DECSCA: printed cells are protected from selective erase.
+    pub protected: bool,
     /// Saved cursor for DECSC/DECRC
     saved: Option<SavedCursor>,
 }
@@ -51,6 +141,8 @@ struct SavedCursor {
     fg: Color,
     bg: Color,
     attrs: CellAttrs,
+    underline_color: Color,
+    protected: bool,
 }
 
 impl Default for CursorState {
@@ -61,8 +153,10 @@ impl Default for CursorState {
             fg: Color::Default,
             bg: Color::Default,
             attrs: CellAttrs::empty(),
+            underline_color: Color::Default,
             shape: CursorShape::Block,
             visible: true,
+            protected: false,
             saved: None,
         }
     }
@@ -80,9 +174,24 @@ impl CursorState {
             fg: self.fg,
             bg: self.bg,
             attrs: self.attrs,
+            underline_color: self.underline_color,
+            protected: self.protected,
         });
     }
 
+    /// Back to the default rendition (SGR 0).
+    pub fn reset_rendition(&mut self) {
+        self.attrs = CellAttrs::empty();
+        self.fg = Color::Default;
+        self.bg = Color::Default;
+        self.underline_color = Color::Default;
+    }
+
+    /// Forget the position and rendition saved by DECSC.
+    pub fn clear_saved(&mut self) {
+        self.saved = None;
+    }
+
     pub fn restore(&mut self) {
         if let Some(saved) = self.saved.take() {
             self.row = saved.row;
@@ -90,6 +199,8 @@ impl CursorState {
             self.fg = saved.fg;
             self.bg = saved.bg;
             self.attrs = saved.attrs;
+            self.underline_color = saved.underline_color;
+            self.protected = saved.protected;
         }
     }
 }
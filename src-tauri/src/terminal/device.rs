@@ -0,0 +1,197 @@
+//! Device Attributes reports (DA1 `CSI c`, DA2 `CSI > c`, DA3 `CSI = c`) and
+//! the XTVERSION name/version report (`CSI > q`).
+//!
+//! Applications use these for feature detection, so the DA1 feature list is
+//! derived from what the emulator currently implements (e.g. Sixel only while
+//! image protocols are enabled). Users can pick the advertised conformance
+//! level and add or hide individual feature parameters on top of that. Until
+//! they pick one, the reports stay what Rain always sent: VT220 in DA1 and
+//! xterm's VT100 terminal ID in DA2.
+
+use serde::{Deserialize, Serialize};
+
+/// Firmware version reported in DA2.
+const FIRMWARE_VERSION: u16 = 10;
+
+/// Unit ID reported in DA3: "RAIN" in hex, as terminals without a real
+/// unit number conventionally pick.
+const UNIT_ID: &str = "5241494E";
+
+/// DA1 feature parameters, as numbered by the VT510/VT520 manuals.
+pub mod feature {
+    pub const SIXEL: u16 = 4;
+    pub const ANSI_COLOR: u16 = 22;
+    /// DECCRA, DECFRA, DECERA and DECSERA; a VT400-class feature.
+    pub const RECTANGULAR_EDITING: u16 = 28;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConformanceLevel {
+    /// No level configured.
+    #[default]
+    Default,
+    Vt220,
+    Vt420,
+    Vt520,
+}
+
+impl ConformanceLevel {
+    /// Operating level in the DA1 reply (`CSI ? 6x ; ... c`).
+    fn da1_class(self) -> u16 {
+        match self {
+            ConformanceLevel::Default | ConformanceLevel::Vt220 => 62,
+            ConformanceLevel::Vt420 => 64,
+            ConformanceLevel::Vt520 => 65,
+        }
+    }
+
+    /// Terminal type in the DA2 reply, using xterm's `decTerminalID` codes.
+    fn da2_terminal_id(self) -> u16 {
+        match self {
+            ConformanceLevel::Default => 0,
+            ConformanceLevel::Vt220 => 1,
+            ConformanceLevel::Vt420 => 41,
+            ConformanceLevel::Vt520 => 64,
+        }
+    }
+}
+
+/// Optional emulator features that have a DA1 parameter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub sixel: bool,
+}
+
+/// User configuration of the device attribute reports.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DeviceAttributes {
+    pub conformance: ConformanceLevel,
+    /// DA1 parameters advertised in addition to the implemented features.
+    pub extra_features: Vec<u16>,
+    /// DA1 parameters never advertised, even when implemented.
+    pub hidden_features: Vec<u16>,
+}
+
+impl DeviceAttributes {
+    /// Sort and dedupe the feature lists; 0 is not a feature parameter.
+    pub fn normalized(mut self) -> Self {
+        for list in [&mut self.extra_features, &mut self.hidden_features] {
+            list.retain(|&p| p != 0);
+            list.sort_unstable();
+            list.dedup();
+        }
+        self
+    }
+
+    /// DA1 feature parameters for the given capabilities, ascending.
+    pub fn features(&self, caps: Capabilities) -> Vec<u16> {
+        let mut features = vec![feature::ANSI_COLOR];
+        if caps.sixel {
+            features.push(feature::SIXEL);
+        }
+        if matches!(
+            self.conformance,
+            ConformanceLevel::Vt420 | ConformanceLevel::Vt520
+        ) {
+            features.push(feature::RECTANGULAR_EDITING);
+        }
+        features.extend_from_slice(&self.extra_features);
+        features.retain(|p| !self.hidden_features.contains(p));
+        features.sort_unstable();
+        features.dedup();
+        features
+    }
+
+    pub fn primary_response(&self, caps: Capabilities) -> Vec<u8> {
+        let mut response = format!("\x1b[?{}", self.conformance.da1_class());
+        for p in self.features(caps) {
+            response.push_str(&format!(";{}", p));
+        }
+        response.push('c');
+        response.into_bytes()
+    }
+
+    pub fn secondary_response(&self) -> Vec<u8> {
+        format!(
+            "\x1b[>{};{};0c",
+            self.conformance.da2_terminal_id(),
+            FIRMWARE_VERSION
+        )
+        .into_bytes()
+    }
+}
+
+/// DA3 reply, `DCS ! | unit-id ST`.
+pub fn tertiary_response() -> Vec<u8> {
+    format!("\x1bP!|{}\x1b\\", UNIT_ID).into_bytes()
+}
+
+/// XTVERSION reply, `DCS > | name(version) ST`, which tmux, neovim and
+/// notcurses match on by name rather than trusting DA2.
+pub fn version_response() -> Vec<u8> {
+    format!("\x1bP>|Rain({})\x1b\\", env!("CARGO_PKG_VERSION")).into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_reports_vt220_with_color() {
+        let da = DeviceAttributes::default();
+        let caps = Capabilities::default();
+        assert_eq!(da.primary_response(caps), b"\x1b[?62;22c");
+        assert_eq!(da.secondary_response(), b"\x1b[>0;10;0c");
+        assert_eq!(
+            da.primary_response(Capabilities { sixel: true }),
+            b"\x1b[?62;4;22c"
+        );
+        assert_eq!(tertiary_response(), b"\x1bP!|5241494E\x1b\\");
+
+        let vt220 = DeviceAttributes {
+            conformance: ConformanceLevel::Vt220,
+            ..Default::default()
+        };
+        assert_eq!(vt220.primary_response(caps), b"\x1b[?62;22c");
+        assert_eq!(vt220.secondary_response(), b"\x1b[>1;10;0c");
+    }
+
+    #[test]
+    fn vt400_levels_advertise_rectangular_editing() {
+        let da = DeviceAttributes {
+            conformance: ConformanceLevel::Vt520,
+            ..Default::default()
+        };
+        assert_eq!(
+            da.primary_response(Capabilities::default()),
+            b"\x1b[?65;22;28c"
+        );
+    }
+
+    #[test]
+    fn configured_level_and_features() {
+        let da = DeviceAttributes {
+            conformance: ConformanceLevel::Vt420,
+            extra_features: vec![28, 0, 1, 28],
+            hidden_features: vec![feature::SIXEL],
+        }
+        .normalized();
+        assert_eq!(da.extra_features, vec![1, 28]);
+        assert_eq!(
+            da.primary_response(Capabilities { sixel: true }),
+            b"\x1b[?64;1;22;28c"
+        );
+        assert_eq!(da.secondary_response(), b"\x1b[>41;10;0c");
+    }
+
+    #[test]
+    fn deserializes_partial_config() {
+        let da: DeviceAttributes =
+            serde_json::from_str(r#"{"conformance":"vt520","extraFeatures":[6]}"#).unwrap();
+        assert_eq!(da.conformance, ConformanceLevel::Vt520);
+        assert_eq!(da.extra_features, vec![6]);
+        assert!(da.hidden_features.is_empty());
+    }
+}
@@ -1,10 +1,15 @@
 use std::collections::VecDeque;
+use std::ops::{Range, RangeInclusive};
+use std::path::PathBuf;
 
 use bitflags::bitflags;
+use serde::Serialize;
 
 use super::color::Color;
 use super::cursor::CellAttrs;
+use super::hibernate::{self, HibernatedScrollback};
 use crate::render::frame::{RenderedLine, StyledSpan};
+use crate::render::links::{self, DetectedLink};
 
 bitflags! {
     /// Per-cell flags for wide character tracking and line wrapping.
@@ -16,6 +21,8 @@ bitflags! {
         const WIDE_SPACER = 1 << 1;
         /// Line wrapped at this position
         const WRAP        = 1 << 2;
+        /// Written while DECSCA protection was on; selective erase skips it
+        const PROTECTED   = 1 << 3;
     }
 }
 
@@ -25,8 +32,13 @@ pub struct Cell {
     pub c: char,
     pub fg: Color,
     pub bg: Color,
+    /// SGR 58 underline color; `Default` follows `fg`.
+    pub underline_color: Color,
     pub attrs: CellAttrs,
     pub flags: CellFlags,
+    /// Characters after `c` in its grapheme cluster: combining marks,
+    /// variation selectors, the rest of a ZWJ sequence or flag.
+    pub combining: Option<Box<str>>,
 }
 
 impl Default for Cell {
@@ -35,8 +47,10 @@ impl Default for Cell {
             c: ' ',
             fg: Color::Default,
             bg: Color::Default,
+            underline_color: Color::Default,
             attrs: CellAttrs::empty(),
             flags: CellFlags::empty(),
+            combining: None,
         }
     }
 }
@@ -51,30 +65,100 @@ impl Cell {
         }
     }
 
+    /// The characters of the cell's grapheme cluster.
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        std::iter::once(self.c).chain(self.combining.iter().flat_map(|rest| rest.chars()))
+    }
+
+    /// Replace the character, dropping anything combined with the old one.
+    pub fn set_char(&mut self, c: char) {
+        self.c = c;
+        self.combining = None;
+    }
+
     /// Reset cell to default blank state.
     pub fn clear(&mut self) {
-        self.c = ' ';
+        self.set_char(' ');
         self.fg = Color::Default;
         self.bg = Color::Default;
+        self.underline_color = Color::Default;
         self.attrs = CellAttrs::empty();
         self.flags = CellFlags::empty();
     }
 
     /// Erase cell using the cursor's current background color (per ECMA-48).
     pub fn erase(&mut self, bg: Color) {
-        self.c = ' ';
+        self.set_char(' ');
         self.fg = Color::Default;
         self.bg = bg;
+        self.underline_color = Color::Default;
         self.attrs = CellAttrs::empty();
         self.flags = CellFlags::empty();
     }
 }
 
+/// DEC line attribute (DECSWL/DECDWL/DECDHL), set per row by `ESC # 3..6`.
+/// Double-size lines show only the first half of their cells, each drawn
+/// twice as wide; double-height lines come in top/bottom pairs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineSize {
+    #[default]
+    Single,
+    DoubleWidth,
+    DoubleHeightTop,
+    DoubleHeightBottom,
+}
+
+impl LineSize {
+    pub fn is_single(&self) -> bool {
+        *self == LineSize::Single
+    }
+
+    /// Columns usable on a line of this size in a grid `cols` wide.
+    pub fn usable_cols(self, cols: u16) -> u16 {
+        if self.is_single() {
+            cols
+        } else {
+            (cols / 2).max(1)
+        }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        match self {
+            LineSize::Single => 0,
+            LineSize::DoubleWidth => 1,
+            LineSize::DoubleHeightTop => 2,
+            LineSize::DoubleHeightBottom => 3,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Self {
+        match byte {
+            1 => LineSize::DoubleWidth,
+            2 => LineSize::DoubleHeightTop,
+            3 => LineSize::DoubleHeightBottom,
+            _ => LineSize::Single,
+        }
+    }
+}
+
 /// A single row in the terminal grid.
 #[derive(Debug, Clone)]
 pub struct Row {
     pub cells: Vec<Cell>,
     pub dirty: bool,
+    /// Columns `start..end` changed since the row was last rendered, when
+    /// it isn't wholly `dirty`; sent as a partial line.
+    pub damage: Option<(u16, u16)>,
+    /// The last render of this row carried links. Their extent can change
+    /// outside the damaged columns, so such rows are always sent whole.
+    pub linked: bool,
+    /// Stable identity of this line, assigned by the owning [`Grid`] and kept
+    /// as the row moves through scrollback, resizes and hibernation. 0 means
+    /// unassigned.
+    pub line_id: u64,
+    pub line_size: LineSize,
 }
 
 impl Row {
@@ -82,13 +166,29 @@ impl Row {
         Self {
             cells: vec![Cell::default(); cols as usize],
             dirty: true,
+            damage: None,
+            linked: false,
+            line_id: 0,
+            line_size: LineSize::Single,
+        }
+    }
+
+    /// Mark columns `start..end` changed. Rows already wholly dirty stay so.
+    pub fn mark_damaged(&mut self, start: u16, end: u16) {
+        if self.dirty || start >= end {
+            return;
         }
+        self.damage = Some(match self.damage {
+            Some((from, to)) => (from.min(start), to.max(end)),
+            None => (start, end),
+        });
     }
 
     pub fn clear(&mut self) {
         for cell in &mut self.cells {
             cell.clear();
         }
+        self.line_size = LineSize::Single;
         self.dirty = true;
     }
 
@@ -100,18 +200,82 @@ impl Row {
         self.dirty = true;
     }
 
+    /// Erase the whole line as ED does, which also drops any double-size
+    /// attribute (EL keeps it).
+    pub fn erase_line_with_bg(&mut self, bg: Color) {
+        self.erase_with_bg(bg);
+        self.line_size = LineSize::Single;
+    }
+
     pub fn resize(&mut self, cols: u16) {
         let new_len = cols as usize;
         if self.cells.len() != new_len {
+            self.set_wrapped(false);
             self.cells.resize(new_len, Cell::default());
             self.dirty = true;
         }
     }
 
+    /// Heap bytes the row holds: its cells and their grapheme clusters.
+    pub fn heap_bytes(&self) -> usize {
+        let combining: usize = self
+            .cells
+            .iter()
+            .filter_map(|cell| cell.combining.as_deref())
+            .map(str::len)
+            .sum();
+        self.cells.capacity() * std::mem::size_of::<Cell>() + combining
+    }
+
+    /// Whether text wrapped off the right edge of this row onto the next.
+    pub fn is_wrapped(&self) -> bool {
+        self.cells
+            .last()
+            .is_some_and(|cell| cell.flags.contains(CellFlags::WRAP))
+    }
+
+    pub fn set_wrapped(&mut self, wrapped: bool) {
+        if let Some(cell) = self.cells.last_mut() {
+            cell.flags.set(CellFlags::WRAP, wrapped);
+        }
+    }
+
     /// Convert this row into styled spans for the render pipeline.
     /// Adjacent cells with matching styles are coalesced into a single span.
+    /// Wide and narrow cells never share a span, so a span's `cols` divided
+    /// among its grapheme clusters gives each its width. URLs and file paths
+    /// found in the text get spans of their own carrying the link.
     pub fn to_styled_spans(&self) -> Vec<StyledSpan> {
-        if self.cells.is_empty() {
+        self.spans_in(0..self.cells.len(), &links::detect(self))
+    }
+
+    /// Spans for the damaged columns alone, with the column they start at.
+    /// `None` when the row has to be sent whole: nothing is damaged, all of
+    /// it is, or links on the row may have moved.
+    fn damaged_spans(&self) -> Option<(u16, Vec<StyledSpan>)> {
+        let (start, end) = self.damage?;
+        let len = self.cells.len();
+        let (mut start, mut end) = (start as usize, (end as usize).min(len));
+        if start == 0 && end == len {
+            return None;
+        }
+        let links = links::detect(self);
+        if self.linked || !links.is_empty() {
+            return None;
+        }
+        // Never split a wide character from its spacer.
+        if start > 0 && self.cells[start].flags.contains(CellFlags::WIDE_SPACER) {
+            start -= 1;
+        }
+        if end < len && self.cells[end].flags.contains(CellFlags::WIDE_SPACER) {
+            end += 1;
+        }
+        Some((start as u16, self.spans_in(start..end, &links)))
+    }
+
+    /// Styled spans for `cols`, given the links detected on the row.
+    fn spans_in(&self, cols: Range<usize>, links: &[DetectedLink]) -> Vec<StyledSpan> {
+        if cols.is_empty() {
             return vec![];
         }
 
@@ -120,35 +284,57 @@ impl Row {
         let mut cur_cols: u32 = 0;
         let mut cur_fg = Color::Default;
         let mut cur_bg = Color::Default;
+        let mut cur_ul = Color::Default;
         let mut cur_attrs = CellAttrs::empty();
+        let mut cur_wide = false;
+        let mut cur_link = None;
         let mut initialized = false;
+        let target = |link: Option<usize>| link.map(|i| &links[i].target);
 
-        for cell in &self.cells {
+        for col in cols {
+            let cell = &self.cells[col];
             // Skip spacer cells for wide characters
             if cell.flags.contains(CellFlags::WIDE_SPACER) {
                 continue;
             }
 
+            let wide = cell.flags.contains(CellFlags::WIDE_CHAR);
+            let link = links::link_at(links, col);
             if !initialized {
                 // Initialize style from the first non-spacer cell
                 cur_fg = cell.fg;
                 cur_bg = cell.bg;
+                cur_ul = cell.underline_color;
                 cur_attrs = cell.attrs;
+                cur_wide = wide;
+                cur_link = link;
                 initialized = true;
-            } else if cell.fg != cur_fg || cell.bg != cur_bg || cell.attrs != cur_attrs {
+            } else if cell.fg != cur_fg
+                || cell.bg != cur_bg
+                || cell.underline_color != cur_ul
+                || cell.attrs != cur_attrs
+                || wide != cur_wide
+                || link != cur_link
+            {
                 // Style changed, flush current span
                 if !text.is_empty() {
-                    spans.push(StyledSpan::new(&text, cur_cols, cur_fg, cur_bg, cur_attrs));
+                    spans.push(
+                        StyledSpan::new(&text, cur_cols, cur_fg, cur_bg, cur_ul, cur_attrs)
+                            .with_link(target(cur_link)),
+                    );
                     text.clear();
                     cur_cols = 0;
                 }
                 cur_fg = cell.fg;
                 cur_bg = cell.bg;
+                cur_ul = cell.underline_color;
                 cur_attrs = cell.attrs;
+                cur_wide = wide;
+                cur_link = link;
             }
 
-            text.push(cell.c);
-            if cell.flags.contains(CellFlags::WIDE_CHAR) {
+            text.extend(cell.chars());
+            if wide {
                 cur_cols += 2;
             } else {
                 cur_cols += 1;
@@ -156,13 +342,64 @@ impl Row {
         }
 
         if !text.is_empty() {
-            spans.push(StyledSpan::new(&text, cur_cols, cur_fg, cur_bg, cur_attrs));
+            spans.push(
+                StyledSpan::new(&text, cur_cols, cur_fg, cur_bg, cur_ul, cur_attrs)
+                    .with_link(target(cur_link)),
+            );
         }
 
         spans
     }
 }
 
+/// A cell showing nothing: a space with default colors and attributes.
+fn is_blank(cell: &Cell) -> bool {
+    cell.c == ' '
+        && cell.combining.is_none()
+        && cell.bg == Color::Default
+        && cell.attrs.is_empty()
+        && !cell
+            .flags
+            .intersects(CellFlags::WIDE_CHAR | CellFlags::WIDE_SPACER)
+}
+
+/// Scrollback rows a grid keeps unless its session sets a limit.
+pub const DEFAULT_SCROLLBACK_LIMIT: usize = 10_000;
+/// Most scrollback rows a session may keep.
+pub const MAX_SCROLLBACK_LIMIT: usize = 1_000_000;
+
+/// Scrollback rows compressed together, when compression is on.
+const COMPRESSED_CHUNK_ROWS: usize = 1024;
+/// Newest scrollback rows never compressed, so the history just above the
+/// screen stays selectable and its blocks exportable without inflating.
+const UNCOMPRESSED_SCROLLBACK_ROWS: usize = 2048;
+/// zstd level for scrollback chunks. Terminal text compresses well at
+/// fast levels already.
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Scrollback rows in the hibernation format, compressed with zstd.
+struct CompressedChunk {
+    data: Vec<u8>,
+    rows: usize,
+    /// Lowest through highest line ID of the rows, so lookups only
+    /// decompress chunks that can hold the line.
+    line_ids: RangeInclusive<u64>,
+}
+
+/// Size of a session's scrollback, as reported to the frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ScrollbackUsage {
+    /// Rows above the screen.
+    pub rows: usize,
+    pub limit: usize,
+    /// Approximate memory held by all rows, screen included; compressed
+    /// scrollback counts at its compressed size.
+    pub bytes: usize,
+    /// Most memory the scrollback rows may hold, if limited.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<usize>,
+}
+
 /// The terminal grid holding visible rows and scrollback history.
 pub struct Grid {
     /// All rows: scrollback + visible. The visible area is the last `visible_rows` entries.
@@ -170,20 +407,111 @@ pub struct Grid {
     pub cols: u16,
     pub visible_rows: u16,
     pub scrollback_limit: usize,
+    /// Most heap bytes the scrollback rows may hold; the oldest rows beyond
+    /// it are dropped even below `scrollback_limit`.
+    pub scrollback_max_bytes: Option<usize>,
+    /// Whether scrollback older than [`UNCOMPRESSED_SCROLLBACK_ROWS`] is
+    /// kept compressed.
+    pub compress_scrollback: bool,
+    /// The oldest scrollback rows, compressed, in front of `rows`.
+    compressed: VecDeque<CompressedChunk>,
+    compressed_rows: usize,
+    compressed_bytes: usize,
+    /// Heap bytes held by the scrollback part of `rows`.
+    scrollback_heap: usize,
+    /// Scrollback rows moved to disk while the session is idle.
+    hibernated: Option<HibernatedScrollback>,
+    /// Line ID handed to the next row this grid creates.
+    next_line_id: u64,
 }
 
 impl Grid {
     pub fn new(visible_rows: u16, cols: u16) -> Self {
-        let mut rows = VecDeque::with_capacity(visible_rows as usize);
-        for _ in 0..visible_rows {
-            rows.push_back(Row::new(cols));
-        }
-        Self {
-            rows,
+        Self::with_first_line_id(visible_rows, cols, 1)
+    }
+
+    /// Create a grid whose rows are numbered starting at `first_line_id`, so
+    /// line IDs stay unique across alt-screen grids and full resets.
+    pub fn with_first_line_id(visible_rows: u16, cols: u16, first_line_id: u64) -> Self {
+        let mut grid = Self {
+            rows: VecDeque::with_capacity(visible_rows as usize),
             cols,
             visible_rows,
-            scrollback_limit: 10_000,
+            scrollback_limit: DEFAULT_SCROLLBACK_LIMIT,
+            scrollback_max_bytes: None,
+            compress_scrollback: false,
+            compressed: VecDeque::new(),
+            compressed_rows: 0,
+            compressed_bytes: 0,
+            scrollback_heap: 0,
+            hibernated: None,
+            next_line_id: first_line_id.max(1),
+        };
+        for _ in 0..visible_rows {
+            let row = grid.new_row(cols);
+            grid.rows.push_back(row);
         }
+        grid
+    }
+
+    /// Create a blank row with a fresh line ID.
+    fn new_row(&mut self, cols: u16) -> Row {
+        let mut row = Row::new(cols);
+        row.line_id = self.next_line_id;
+        self.next_line_id += 1;
+        row
+    }
+
+    /// The line ID the next created row will receive.
+    pub fn next_line_id(&self) -> u64 {
+        self.next_line_id
+    }
+
+    /// Never hand out IDs below `next`, e.g. after an alt-screen grid that
+    /// borrowed this grid's ID range is discarded.
+    pub fn reserve_line_ids(&mut self, next: u64) {
+        self.next_line_id = self.next_line_id.max(next);
+    }
+
+    /// Line ID of a visible row.
+    pub fn visible_line_id(&self, row: u16) -> u64 {
+        self.rows
+            .get(self.visible_offset() + row as usize)
+            .map_or(0, |r| r.line_id)
+    }
+
+    /// Index into `rows` of the row carrying `line_id`, if it is resident.
+    pub fn find_line(&self, line_id: u64) -> Option<usize> {
+        if line_id == 0 {
+            return None;
+        }
+        self.rows.iter().rposition(|row| row.line_id == line_id)
+    }
+
+    /// Number in the whole history (see [`Grid::history_rows`]) of the row
+    /// carrying `line_id`, compressed scrollback included.
+    pub fn find_history_line(&self, line_id: u64) -> Option<usize> {
+        if line_id == 0 {
+            return None;
+        }
+        if let Some(idx) = self.find_line(line_id) {
+            return Some(self.compressed_rows + idx);
+        }
+        let mut start = self.compressed_rows;
+        for chunk in self.compressed.iter().rev() {
+            start -= chunk.rows;
+            if !chunk.line_ids.contains(&line_id) {
+                continue;
+            }
+            if let Some(idx) = self
+                .inflate(chunk)
+                .iter()
+                .rposition(|row| row.line_id == line_id)
+            {
+                return Some(start + idx);
+            }
+        }
+        None
     }
 
     /// Get the offset where the visible area starts.
@@ -192,7 +520,6 @@ impl Grid {
     }
 
     /// Get a reference to a visible row by its screen-relative index (0 = top of screen).
-    #[allow(dead_code)]
     pub fn visible_row(&self, row: u16) -> &Row {
         let idx = self.visible_offset() + row as usize;
         &self.rows[idx]
@@ -209,7 +536,7 @@ impl Grid {
         if col < self.cols && row < self.visible_rows {
             let r = self.visible_row_mut(row);
             r.cells[col as usize] = cell;
-            r.dirty = true;
+            r.mark_damaged(col, col + 1);
         }
     }
 
@@ -219,7 +546,7 @@ impl Grid {
         if col < self.cols && row < self.visible_rows {
             let r = self.visible_row_mut(row);
             r.cells[col as usize].clear();
-            r.dirty = true;
+            r.mark_damaged(col, col + 1);
         }
     }
 
@@ -242,20 +569,25 @@ impl Grid {
             let spans = self.rows[top_idx].to_styled_spans();
             scrolled_line = Some(RenderedLine {
                 index: 0, // index doesn't matter for scrolled-off lines
+                line_id: self.rows[top_idx].line_id,
+                line_size: self.rows[top_idx].line_size,
+                start_col: None,
                 spans,
             });
 
             // Top line goes into scrollback; insert a new blank at the bottom position
-            self.rows.insert(bottom_idx + 1, Row::new(self.cols));
+            let blank = self.new_row(self.cols);
+            self.rows.insert(bottom_idx + 1, blank);
+            self.scrollback_heap += self.rows[top_idx].heap_bytes();
 
-            // Trim scrollback if over limit
-            while self.rows.len() > self.visible_rows as usize + self.scrollback_limit {
-                self.rows.pop_front();
-            }
+            // Compress the coldest rows, then trim scrollback if over limit
+            self.compress_old_rows();
+            self.trim_scrollback();
         } else {
             // Remove the top line of the scroll region and insert blank at bottom
             self.rows.remove(top_idx);
-            self.rows.insert(bottom_idx, Row::new(self.cols));
+            let blank = self.new_row(self.cols);
+            self.rows.insert(bottom_idx, blank);
         }
 
         // Mark visible rows as dirty
@@ -278,13 +610,133 @@ impl Grid {
         }
 
         self.rows.remove(bottom_idx);
-        self.rows.insert(top_idx, Row::new(self.cols));
+        let blank = self.new_row(self.cols);
+        self.rows.insert(top_idx, blank);
 
         for i in top..=bottom {
             self.visible_row_mut(i).dirty = true;
         }
     }
 
+    /// Scroll columns [left, right] of the region [top, bottom] up by one
+    /// line, for scrolling inside left/right margins. Cells outside the
+    /// columns stay put, so nothing moves into scrollback.
+    pub fn scroll_rect_up(&mut self, top: u16, bottom: u16, left: u16, right: u16) {
+        if top > bottom || bottom >= self.visible_rows {
+            return;
+        }
+        let (start, end) = (left as usize, right as usize + 1);
+        for row in top..bottom {
+            let below = self.visible_row(row + 1).cells[start..end].to_vec();
+            let line = self.visible_row_mut(row);
+            line.cells[start..end].clone_from_slice(&below);
+            line.dirty = true;
+        }
+        let line = self.visible_row_mut(bottom);
+        line.cells[start..end].iter_mut().for_each(Cell::clear);
+        line.dirty = true;
+    }
+
+    /// Scroll columns [left, right] of the region [top, bottom] down by one
+    /// line.
+    pub fn scroll_rect_down(&mut self, top: u16, bottom: u16, left: u16, right: u16) {
+        if top > bottom || bottom >= self.visible_rows {
+            return;
+        }
+        let (start, end) = (left as usize, right as usize + 1);
+        for row in (top + 1..=bottom).rev() {
+            let above = self.visible_row(row - 1).cells[start..end].to_vec();
+            let line = self.visible_row_mut(row);
+            line.cells[start..end].clone_from_slice(&above);
+            line.dirty = true;
+        }
+        let line = self.visible_row_mut(top);
+        line.cells[start..end].iter_mut().for_each(Cell::clear);
+        line.dirty = true;
+    }
+
+    /// Copy rows [top, bottom] x columns [left, right] so the top-left corner
+    /// lands on (dst_row, dst_col), dropping whatever falls off the grid.
+    /// Overlapping areas copy as if through a buffer.
+    pub fn copy_rect(
+        &mut self,
+        top: u16,
+        bottom: u16,
+        left: u16,
+        right: u16,
+        dst_row: u16,
+        dst_col: u16,
+    ) {
+        if top > bottom || left > right || bottom >= self.visible_rows {
+            return;
+        }
+        let (start, end) = (left as usize, right as usize + 1);
+        let source: Vec<Vec<Cell>> = (top..=bottom)
+            .map(|row| self.visible_row(row).cells[start..end].to_vec())
+            .collect();
+        for (offset, cells) in source.into_iter().enumerate() {
+            let row = dst_row as usize + offset;
+            if row >= self.visible_rows as usize {
+                break;
+            }
+            let line = self.visible_row_mut(row as u16);
+            let col = (dst_col as usize).min(line.cells.len());
+            let len = cells.len().min(line.cells.len() - col);
+            line.cells[col..col + len].clone_from_slice(&cells[..len]);
+            repair_wide_pairs(&mut line.cells, col, col + len);
+            line.dirty = true;
+        }
+    }
+
+    /// Overwrite rows [top, bottom] x columns [left, right] with `cell`.
+    pub fn fill_rect(&mut self, top: u16, bottom: u16, left: u16, right: u16, cell: &Cell) {
+        if top > bottom || left > right || bottom >= self.visible_rows {
+            return;
+        }
+        for row in top..=bottom {
+            let line = self.visible_row_mut(row);
+            let end = (right as usize + 1).min(line.cells.len());
+            line.cells[left as usize..end].fill(cell.clone());
+            repair_wide_pairs(&mut line.cells, left as usize, end);
+            line.dirty = true;
+        }
+    }
+
+    /// Replace the characters in rows [top, bottom] x columns [left, right]
+    /// with spaces, keeping colors and attributes. Protected cells are left
+    /// alone, as for DECSERA.
+    pub fn blank_rect(&mut self, top: u16, bottom: u16, left: u16, right: u16) {
+        if top > bottom || left > right || bottom >= self.visible_rows {
+            return;
+        }
+        for row in top..=bottom {
+            self.selective_erase_cells(row, left, right + 1);
+        }
+    }
+
+    /// Selective erase (DECSED/DECSEL) of a row from start_col to end_col
+    /// (exclusive): unprotected characters become spaces, keeping their
+    /// colors and attributes.
+    pub fn selective_erase_cells(&mut self, row: u16, start_col: u16, end_col: u16) {
+        if row >= self.visible_rows {
+            return;
+        }
+        let line = self.visible_row_mut(row);
+        let start = start_col as usize;
+        let end = (end_col as usize).min(line.cells.len());
+        if start >= end {
+            return;
+        }
+        for cell in &mut line.cells[start..end] {
+            if !cell.flags.contains(CellFlags::PROTECTED) {
+                cell.set_char(' ');
+                cell.flags -= CellFlags::WIDE_CHAR | CellFlags::WIDE_SPACER;
+            }
+        }
+        repair_wide_pairs(&mut line.cells, start, end);
+        line.mark_damaged(start as u16, end as u16);
+    }
+
     /// Resize the grid to new dimensions. Existing content is preserved where possible.
     /// When shrinking, excess rows become scrollback (appropriate for the main grid).
     /// After the resize commit, mark the full visible viewport dirty so the
@@ -304,16 +756,185 @@ impl Grid {
             // New rows are created with dirty=true by Row::new().
             let needed = new_visible - current_visible;
             for _ in 0..needed {
-                self.rows.push_back(Row::new(new_cols));
+                let row = self.new_row(new_cols);
+                self.rows.push_back(row);
             }
         }
         // If shrinking, we don't remove rows - they become scrollback
 
         self.visible_rows = new_rows;
         self.cols = new_cols;
+        self.recount_scrollback_heap();
         self.mark_all_dirty();
     }
 
+    /// Re-wrap every row to `new_cols`. Rows continued with
+    /// [`CellFlags::WRAP`] are joined into logical lines and split again at
+    /// the new width, so narrowing a window and widening it again gives the
+    /// original lines back. Double-size rows are only truncated or padded.
+    ///
+    /// `cursor` is the screen position to carry along (the cursor, on the
+    /// main screen); blank rows below it stay below it. Returns where it
+    /// ends up. The number of screen rows doesn't change; lines that no
+    /// longer fit on it move into scrollback. Compressed scrollback is
+    /// decompressed for this and compressed again at the new width.
+    pub fn reflow(&mut self, new_cols: u16, cursor: Option<(u16, u16)>) -> Option<(u16, u16)> {
+        let old_cols = self.cols as usize;
+        let width = new_cols as usize;
+        if width == old_cols || width == 0 || self.rows.is_empty() {
+            return cursor;
+        }
+        self.inflate_compressed();
+        let old_offset = self.visible_offset();
+        let cursor_at = cursor.map(|(row, col)| (old_offset + row as usize, col as usize));
+
+        // Blank rows below the cursor (or the content) are put back after.
+        let content_end = match cursor_at {
+            Some((row, _)) => row + 1,
+            None => 0,
+        };
+        let mut trailing = 0;
+        while self.rows.len() > content_end
+            && self
+                .rows
+                .back()
+                .is_some_and(|row| !row.is_wrapped() && row.cells.iter().all(is_blank))
+        {
+            self.rows.pop_back();
+            trailing += 1;
+        }
+
+        let old_rows: Vec<Row> = self.rows.drain(..).collect();
+        // New rows whose logical line started in scrollback.
+        let mut from_scrollback = 0;
+        let mut new_cursor = None;
+        let mut i = 0;
+        while i < old_rows.len() {
+            let start = i;
+            if old_rows[start].line_size != LineSize::Single {
+                let mut row = old_rows[start].clone();
+                row.resize(new_cols);
+                row.dirty = true;
+                if let Some((_, col)) = cursor_at.filter(|&(row, _)| row == start) {
+                    new_cursor = Some((self.rows.len(), col.min(width - 1)));
+                }
+                self.rows.push_back(row);
+                from_scrollback += usize::from(start < old_offset);
+                i += 1;
+                continue;
+            }
+            while i + 1 < old_rows.len()
+                && old_rows[i].is_wrapped()
+                && old_rows[i + 1].line_size == LineSize::Single
+            {
+                i += 1;
+            }
+            let line = &old_rows[start..=i];
+            i += 1;
+
+            let mut cells: Vec<Cell> = line.iter().flat_map(|row| row.cells.clone()).collect();
+            for cell in &mut cells {
+                cell.flags.remove(CellFlags::WRAP);
+            }
+            let cursor_offset = cursor_at
+                .filter(|&(row, _)| (start..start + line.len()).contains(&row))
+                .map(|(row, col)| (row - start) * old_cols + col);
+            let mut len = cells
+                .iter()
+                .rposition(|cell| !is_blank(cell))
+                .map_or(0, |last| last + 1);
+            if let Some(offset) = cursor_offset {
+                len = len.max(offset);
+            }
+            cells.truncate(len);
+
+            let mut pieces: Vec<Vec<Cell>> = vec![Vec::with_capacity(width)];
+            let mut line_cursor = None;
+            let mut k = 0;
+            while k < cells.len() {
+                let wide = cells[k].flags.contains(CellFlags::WIDE_CHAR)
+                    && cells
+                        .get(k + 1)
+                        .is_some_and(|next| next.flags.contains(CellFlags::WIDE_SPACER));
+                let span = if wide && width > 1 { 2 } else { 1 };
+                let piece = pieces.last_mut().expect("pieces start non-empty");
+                if piece.len() + span > width {
+                    // A wide character that doesn't fit moves to the next row.
+                    piece.resize(width, Cell::default());
+                    pieces.push(Vec::with_capacity(width));
+                }
+                let n = pieces.len() - 1;
+                let piece = &mut pieces[n];
+                if cursor_offset == Some(k) || (cursor_offset == Some(k + 1) && span == 2) {
+                    line_cursor = Some((n, piece.len()));
+                }
+                piece.push(cells[k].clone());
+                if span == 2 {
+                    piece.push(cells[k + 1].clone());
+                } else if wide || cells[k].flags.contains(CellFlags::WIDE_SPACER) {
+                    // Half of a pair at a one-column width.
+                    let last = piece.last_mut().expect("just pushed");
+                    last.flags -= CellFlags::WIDE_CHAR | CellFlags::WIDE_SPACER;
+                }
+                k += if wide { 2 } else { 1 };
+            }
+            if line_cursor.is_none() && cursor_offset.is_some() {
+                if pieces.last().is_some_and(|piece| piece.len() == width) {
+                    pieces.push(Vec::with_capacity(width));
+                }
+                line_cursor = Some((pieces.len() - 1, pieces.last().map_or(0, Vec::len)));
+            }
+
+            let count = pieces.len();
+            for (n, mut cells) in pieces.into_iter().enumerate() {
+                cells.resize(width, Cell::default());
+                // Rows keep the IDs of the rows the line had, so blocks
+                // still find their lines; extra rows get new ones.
+                let line_id = match line.get(n) {
+                    Some(old) => old.line_id,
+                    None => {
+                        self.next_line_id += 1;
+                        self.next_line_id - 1
+                    }
+                };
+                let mut row = Row {
+                    cells,
+                    dirty: true,
+                    damage: None,
+                    linked: false,
+                    line_id,
+                    line_size: LineSize::Single,
+                };
+                row.set_wrapped(n + 1 < count);
+                if let Some((_, col)) = line_cursor.filter(|&(piece, _)| piece == n) {
+                    new_cursor = Some((self.rows.len(), col));
+                }
+                self.rows.push_back(row);
+                from_scrollback += usize::from(start < old_offset);
+            }
+        }
+
+        // Scrollback rows stay in scrollback; the screen is filled out with
+        // the blank rows taken off.
+        let visible = self.visible_rows as usize;
+        let padding = trailing.max((from_scrollback + visible).saturating_sub(self.rows.len()));
+        for _ in 0..padding {
+            let row = self.new_row(new_cols);
+            self.rows.push_back(row);
+        }
+        self.cols = new_cols;
+        self.recount_scrollback_heap();
+        while self.compress_old_rows() {}
+        self.trim_scrollback();
+        self.mark_all_dirty();
+
+        let offset = self.visible_offset();
+        new_cursor.map(|(index, col)| {
+            let row = index.saturating_sub(offset).min(visible.saturating_sub(1));
+            (row as u16, col.min(width - 1) as u16)
+        })
+    }
+
     /// Resize for the alternate screen buffer.
     /// Alt-screen content is disposable: TUIs always repaint from scratch on
     /// SIGWINCH. We clear the grid entirely and let the child redraw into a
@@ -321,7 +942,8 @@ impl Grid {
     pub fn resize_no_scrollback(&mut self, new_rows: u16, new_cols: u16) {
         self.rows.clear();
         for _ in 0..new_rows as usize {
-            self.rows.push_back(Row::new(new_cols));
+            let row = self.new_row(new_cols);
+            self.rows.push_back(row);
         }
         self.visible_rows = new_rows;
         self.cols = new_cols;
@@ -335,14 +957,30 @@ impl Grid {
 
         for i in 0..self.visible_rows {
             let idx = offset + i as usize;
-            if idx < self.rows.len() && self.rows[idx].dirty {
-                let spans = self.rows[idx].to_styled_spans();
-                result.push(RenderedLine {
-                    index: i as u32,
-                    spans,
-                });
-                self.rows[idx].dirty = false;
+            let Some(row) = self.rows.get_mut(idx) else {
+                continue;
+            };
+            if !row.dirty && row.damage.is_none() {
+                continue;
             }
+            let partial = if row.dirty { None } else { row.damaged_spans() };
+            let (start_col, spans) = match partial {
+                Some((start, spans)) => (Some(u32::from(start)), spans),
+                None => {
+                    let links = links::detect(row);
+                    row.linked = !links.is_empty();
+                    (None, row.spans_in(0..row.cells.len(), &links))
+                }
+            };
+            result.push(RenderedLine {
+                index: i as u32,
+                line_id: row.line_id,
+                line_size: row.line_size,
+                start_col,
+                spans,
+            });
+            row.dirty = false;
+            row.damage = None;
         }
 
         result
@@ -364,10 +1002,270 @@ impl Grid {
         self.rows.len()
     }
 
-    /// Get the number of scrollback lines above the visible area.
-    #[allow(dead_code)]
+    /// Get the number of scrollback lines above the visible area, the
+    /// compressed ones included.
     pub fn scrollback_len(&self) -> usize {
-        self.visible_offset()
+        self.compressed_rows + self.visible_offset()
+    }
+
+    /// Keep at most `limit` scrollback rows, capped at
+    /// [`MAX_SCROLLBACK_LIMIT`]. Returns how many of the oldest rows were
+    /// dropped to get under it.
+    pub fn set_scrollback_limit(&mut self, limit: usize) -> usize {
+        self.scrollback_limit = limit.min(MAX_SCROLLBACK_LIMIT);
+        self.trim_scrollback()
+    }
+
+    /// Keep scrollback rows within `max_bytes` of memory (or only the row
+    /// limit when `None`). Returns how many of the oldest rows were dropped.
+    pub fn set_scrollback_max_bytes(&mut self, max_bytes: Option<usize>) -> usize {
+        self.scrollback_max_bytes = max_bytes;
+        self.trim_scrollback()
+    }
+
+    /// Keep scrollback older than [`UNCOMPRESSED_SCROLLBACK_ROWS`]
+    /// compressed, or inflate it all again. Returns how many of the oldest
+    /// rows were dropped to stay within the limits afterwards.
+    pub fn set_scrollback_compression(&mut self, compress: bool) -> usize {
+        self.compress_scrollback = compress;
+        if compress {
+            while self.compress_old_rows() {}
+        } else {
+            self.inflate_compressed();
+        }
+        self.trim_scrollback()
+    }
+
+    /// Move every compressed chunk back in front of `rows`.
+    fn inflate_compressed(&mut self) {
+        if self.compressed.is_empty() {
+            return;
+        }
+        while let Some(chunk) = self.compressed.pop_back() {
+            self.compressed_rows -= chunk.rows;
+            self.compressed_bytes -= chunk.data.len();
+            for row in self.inflate(&chunk).into_iter().rev() {
+                self.rows.push_front(row);
+            }
+        }
+        self.recount_scrollback_heap();
+    }
+
+    /// Compress the oldest chunk of resident scrollback once more than
+    /// [`UNCOMPRESSED_SCROLLBACK_ROWS`] rows are held beyond it. Returns
+    /// whether a chunk was compressed.
+    fn compress_old_rows(&mut self) -> bool {
+        if !self.compress_scrollback
+            || self.hibernated.is_some()
+            || self.visible_offset() < UNCOMPRESSED_SCROLLBACK_ROWS + COMPRESSED_CHUNK_ROWS
+        {
+            return false;
+        }
+        let mut encoded = Vec::new();
+        let compressed =
+            hibernate::encode_rows(&mut encoded, self.rows.range(..COMPRESSED_CHUNK_ROWS))
+                .and_then(|_| zstd::encode_all(encoded.as_slice(), COMPRESSION_LEVEL));
+        let data = match compressed {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::warn!("Keeping scrollback uncompressed: {}", e);
+                self.compress_scrollback = false;
+                return false;
+            }
+        };
+        let mut heap = 0;
+        let (mut low, mut high) = (u64::MAX, 0);
+        for row in self.rows.drain(..COMPRESSED_CHUNK_ROWS) {
+            heap += row.heap_bytes();
+            low = low.min(row.line_id);
+            high = high.max(row.line_id);
+        }
+        self.scrollback_heap = self.scrollback_heap.saturating_sub(heap);
+        self.compressed_rows += COMPRESSED_CHUNK_ROWS;
+        self.compressed_bytes += data.len();
+        self.compressed.push_back(CompressedChunk {
+            data,
+            rows: COMPRESSED_CHUNK_ROWS,
+            line_ids: low..=high,
+        });
+        true
+    }
+
+    /// The rows of a compressed chunk at the current width. A chunk that
+    /// fails to decode comes back as blank rows, so row numbers still hold.
+    fn inflate(&self, chunk: &CompressedChunk) -> Vec<Row> {
+        let decoded = zstd::decode_all(chunk.data.as_slice())
+            .and_then(|data| hibernate::decode_rows(&data))
+            .and_then(|rows| {
+                if rows.len() == chunk.rows {
+                    Ok(rows)
+                } else {
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(
+                            "expected {} compressed rows, found {}",
+                            chunk.rows,
+                            rows.len()
+                        ),
+                    ))
+                }
+            });
+        match decoded {
+            Ok(mut rows) => {
+                for row in &mut rows {
+                    row.resize(self.cols);
+                    row.dirty = false;
+                }
+                rows
+            }
+            Err(e) => {
+                tracing::error!("Failed to decompress scrollback, rows lost: {}", e);
+                (0..chunk.rows).map(|_| Row::new(self.cols)).collect()
+            }
+        }
+    }
+
+    /// Rows `range` of the whole history, numbered from the oldest
+    /// scrollback row, compressed or not, through the screen. Only the
+    /// compressed chunks the range reaches are decompressed.
+    pub fn history_rows(&self, range: Range<usize>) -> Vec<Row> {
+        let mut out = Vec::with_capacity(range.len());
+        let mut start = 0;
+        for chunk in &self.compressed {
+            let end = start + chunk.rows;
+            if range.start < end && start < range.end {
+                let rows = self.inflate(chunk);
+                let from = range.start.max(start) - start;
+                let to = range.end.min(end) - start;
+                out.extend(rows.into_iter().take(to).skip(from));
+            }
+            start = end;
+        }
+        let from = range.start.max(start) - start;
+        let to = range.end.saturating_sub(start).min(self.rows.len());
+        if from < to {
+            out.extend(self.rows.range(from..to).cloned());
+        }
+        out
+    }
+
+    /// Call `visit` with every history row and its number, oldest first,
+    /// until it returns false. Compressed chunks are decompressed one at a
+    /// time.
+    pub fn visit_history(&self, mut visit: impl FnMut(usize, &Row) -> bool) {
+        let mut index = 0;
+        for chunk in &self.compressed {
+            for row in &self.inflate(chunk) {
+                if !visit(index, row) {
+                    return;
+                }
+                index += 1;
+            }
+        }
+        for row in &self.rows {
+            if !visit(index, row) {
+                return;
+            }
+            index += 1;
+        }
+    }
+
+    /// Whether the scrollback holds more rows than its limit or more bytes
+    /// than its budget.
+    fn scrollback_over_budget(&self) -> bool {
+        self.scrollback_len() > self.scrollback_limit
+            || self
+                .scrollback_max_bytes
+                .is_some_and(|max| self.scrollback_heap + self.compressed_bytes > max)
+    }
+
+    /// Drop the oldest scrollback while it is over its limit or budget:
+    /// whole compressed chunks first, then resident rows. Returns how many
+    /// rows were dropped.
+    fn trim_scrollback(&mut self) -> usize {
+        let mut dropped = 0;
+        while self.scrollback_over_budget() {
+            if let Some(chunk) = self.compressed.pop_front() {
+                self.compressed_rows -= chunk.rows;
+                self.compressed_bytes -= chunk.data.len();
+                dropped += chunk.rows;
+            } else if self.visible_offset() > 0 {
+                let row = self.rows.pop_front().expect("scrollback rows are resident");
+                self.scrollback_heap = self.scrollback_heap.saturating_sub(row.heap_bytes());
+                dropped += 1;
+            } else {
+                break;
+            }
+        }
+        dropped
+    }
+
+    fn recount_scrollback_heap(&mut self) {
+        self.scrollback_heap = self
+            .rows
+            .range(..self.visible_offset())
+            .map(Row::heap_bytes)
+            .sum();
+    }
+
+    /// Approximate heap bytes held by the rows, compressed scrollback at
+    /// its compressed size.
+    pub fn memory_bytes(&self) -> usize {
+        self.rows.iter().map(Row::heap_bytes).sum::<usize>() + self.compressed_bytes
+    }
+
+    /// Whether scrollback rows are currently hibernated on disk.
+    pub fn is_hibernated(&self) -> bool {
+        self.hibernated.is_some()
+    }
+
+    /// Move all scrollback rows to `path`, compressed ones included,
+    /// keeping only the visible rows in memory. Returns the number of rows
+    /// written (0 if there was nothing to hibernate or the grid is already
+    /// hibernated).
+    pub fn hibernate_scrollback(&mut self, path: PathBuf) -> std::io::Result<usize> {
+        if self.hibernated.is_some() || self.scrollback_len() == 0 {
+            return Ok(0);
+        }
+        self.inflate_compressed();
+        let count = self.visible_offset();
+        if let Err(e) = hibernate::write_rows_to_file(&path, self.rows.range(..count)) {
+            while self.compress_old_rows() {}
+            return Err(e);
+        }
+        self.rows.drain(..count);
+        self.rows.shrink_to_fit();
+        self.scrollback_heap = 0;
+        self.hibernated = Some(HibernatedScrollback { path, rows: count });
+        Ok(count)
+    }
+
+    /// Load hibernated scrollback back in front of the resident rows. Rows
+    /// are resized to the current width and the scrollback limit re-applied.
+    pub fn rehydrate_scrollback(&mut self) -> std::io::Result<usize> {
+        let Some(hibernated) = self.hibernated.take() else {
+            return Ok(0);
+        };
+        let restored = hibernate::read_rows_from_file(&hibernated.path)?;
+        let count = restored.len();
+        if count != hibernated.rows {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "expected {} hibernated rows, found {}",
+                    hibernated.rows, count
+                ),
+            ));
+        }
+        for mut row in restored.into_iter().rev() {
+            row.resize(self.cols);
+            row.dirty = false;
+            self.rows.push_front(row);
+        }
+        self.recount_scrollback_heap();
+        while self.compress_old_rows() {}
+        self.trim_scrollback();
+        Ok(count)
     }
 
     /// Erase cells in a row from start_col to end_col (exclusive),
@@ -382,42 +1280,66 @@ impl Grid {
         for i in start..end {
             r.cells[i].erase(bg);
         }
-        r.dirty = true;
+        r.mark_damaged(start as u16, end as u16);
     }
 
-    /// Insert blank cells at position, shifting existing cells right.
-    pub fn insert_cells(&mut self, row: u16, col: u16, count: u16) {
+    /// Insert blank cells at position, shifting existing cells right. Cells
+    /// pushed past `end` (exclusive; the right margin) are dropped.
+    pub fn insert_cells(&mut self, row: u16, col: u16, count: u16, end: u16) {
         if row >= self.visible_rows {
             return;
         }
         let r = self.visible_row_mut(row);
         let col = col as usize;
-        let count = count as usize;
-        let len = r.cells.len();
-
-        for _ in 0..count.min(len - col) {
-            r.cells.pop();
-            r.cells.insert(col, Cell::default());
+        let end = (end as usize).min(r.cells.len());
+        if col >= end {
+            return;
         }
-        r.dirty = true;
+        let count = (count as usize).min(end - col);
+
+        r.cells[col..end].rotate_right(count);
+        r.cells[col..col + count].fill(Cell::default());
+        r.mark_damaged(col as u16, end as u16);
     }
 
-    /// Delete cells at position, shifting remaining cells left.
-    pub fn delete_cells(&mut self, row: u16, col: u16, count: u16) {
+    /// Delete cells at position, shifting the cells up to `end` (exclusive;
+    /// the right margin) left and filling the gap with blanks.
+    pub fn delete_cells(&mut self, row: u16, col: u16, count: u16, end: u16) {
         if row >= self.visible_rows {
             return;
         }
         let r = self.visible_row_mut(row);
         let col = col as usize;
-        let count = count as usize;
+        let end = (end as usize).min(r.cells.len());
+        if col >= end {
+            return;
+        }
+        let count = (count as usize).min(end - col);
 
-        let to_remove = count.min(r.cells.len().saturating_sub(col));
-        for _ in 0..to_remove {
-            if col < r.cells.len() {
-                r.cells.remove(col);
-                r.cells.push(Cell::default());
-            }
+        r.cells[col..end].rotate_left(count);
+        r.cells[end - count..end].fill(Cell::default());
+        r.mark_damaged(col as u16, end as u16);
+    }
+}
+
+/// Blank wide-character halves left without their partner after cells in
+/// `[start, end)` were overwritten.
+fn repair_wide_pairs(cells: &mut [Cell], start: usize, end: usize) {
+    let from = start.saturating_sub(1);
+    let to = (end + 1).min(cells.len());
+    for i in from..to {
+        let orphaned = if cells[i].flags.contains(CellFlags::WIDE_CHAR) {
+            !cells
+                .get(i + 1)
+                .is_some_and(|next| next.flags.contains(CellFlags::WIDE_SPACER))
+        } else if cells[i].flags.contains(CellFlags::WIDE_SPACER) {
+            i == 0 || !cells[i - 1].flags.contains(CellFlags::WIDE_CHAR)
+        } else {
+            false
+        };
+        if orphaned {
+            cells[i].set_char(' ');
+            cells[i].flags -= CellFlags::WIDE_CHAR | CellFlags::WIDE_SPACER;
         }
-        r.dirty = true;
     }
 }
@@ -0,0 +1,236 @@
+//! On-disk format for hibernated scrollback.
+//!
+//! Idle sessions can move their scrollback rows out of memory while the PTY
+//! stays alive. Rows are written in a compact binary layout (trailing blank
+//! cells are not stored) and read back the next time the session is touched.
+
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use super::color::Color;
+use super::cursor::CellAttrs;
+use super::grid::{Cell, CellFlags, LineSize, Row};
+
+const MAGIC: &[u8; 8] = b"RAINHIB5";
+
+/// Scrollback rows that currently live on disk instead of in the grid.
+/// The backing file is removed when this handle is dropped.
+#[derive(Debug)]
+pub struct HibernatedScrollback {
+    pub path: PathBuf,
+    pub rows: usize,
+}
+
+impl Drop for HibernatedScrollback {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Serialize rows to `path`, creating parent directories as needed.
+pub fn write_rows_to_file<'a>(
+    path: &Path,
+    rows: impl ExactSizeIterator<Item = &'a Row>,
+) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+    encode_rows(&mut out, rows)?;
+    out.flush()
+}
+
+/// Read rows previously written by [`write_rows_to_file`].
+pub fn read_rows_from_file(path: &Path) -> io::Result<Vec<Row>> {
+    let mut data = Vec::new();
+    std::fs::File::open(path)?.read_to_end(&mut data)?;
+    decode_rows(&data)
+}
+
+pub fn encode_rows<'a>(
+    out: &mut impl Write,
+    rows: impl ExactSizeIterator<Item = &'a Row>,
+) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&(rows.len() as u32).to_le_bytes())?;
+    for row in rows {
+        let stored = row
+            .cells
+            .iter()
+            .rposition(|cell| *cell != Cell::default())
+            .map_or(0, |idx| idx + 1);
+        out.write_all(&row.line_id.to_le_bytes())?;
+        out.write_all(&[row.line_size.to_byte()])?;
+        out.write_all(&(row.cells.len() as u16).to_le_bytes())?;
+        out.write_all(&(stored as u16).to_le_bytes())?;
+        for cell in &row.cells[..stored] {
+            out.write_all(&(cell.c as u32).to_le_bytes())?;
+            write_color(out, cell.fg)?;
+            write_color(out, cell.bg)?;
+            write_color(out, cell.underline_color)?;
+            out.write_all(&cell.attrs.bits().to_le_bytes())?;
+            out.write_all(&[cell.flags.bits()])?;
+            let combining = cell.combining.as_deref().unwrap_or("");
+            out.write_all(&(combining.len() as u16).to_le_bytes())?;
+            out.write_all(combining.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+pub fn decode_rows(data: &[u8]) -> io::Result<Vec<Row>> {
+    let mut reader = ByteReader { data, pos: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(invalid("bad hibernation file header"));
+    }
+    let count = reader.u32()? as usize;
+    let mut rows = Vec::with_capacity(count);
+    for _ in 0..count {
+        let line_id = reader.u64()?;
+        let line_size = LineSize::from_byte(reader.u8()?);
+        let len = reader.u16()? as usize;
+        let stored = reader.u16()? as usize;
+        if stored > len {
+            return Err(invalid("stored cells exceed row length"));
+        }
+        let mut cells = Vec::with_capacity(len);
+        for _ in 0..stored {
+            let c = char::from_u32(reader.u32()?).unwrap_or(' ');
+            let fg = reader.color()?;
+            let bg = reader.color()?;
+            let underline_color = reader.color()?;
+            let attrs = CellAttrs::from_bits_truncate(reader.u16()?);
+            let flags = CellFlags::from_bits_truncate(reader.u8()?);
+            let combining_len = reader.u16()? as usize;
+            let combining = match reader.take(combining_len)? {
+                [] => None,
+                bytes => Some(String::from_utf8_lossy(bytes).into()),
+            };
+            cells.push(Cell {
+                c,
+                fg,
+                bg,
+                underline_color,
+                attrs,
+                flags,
+                combining,
+            });
+        }
+        cells.resize(len, Cell::default());
+        rows.push(Row {
+            cells,
+            dirty: false,
+            damage: None,
+            linked: false,
+            line_id,
+            line_size,
+        });
+    }
+    Ok(rows)
+}
+
+fn write_color(out: &mut impl Write, color: Color) -> io::Result<()> {
+    let bytes = match color {
+        Color::Default => [0, 0, 0, 0],
+        Color::Indexed(i) => [1, i, 0, 0],
+        Color::Rgb(r, g, b) => [2, r, g, b],
+    };
+    out.write_all(&bytes)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let end = self.pos + n;
+        let slice = self
+            .data
+            .get(self.pos..end)
+            .ok_or_else(|| invalid("truncated hibernation file"))?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn color(&mut self) -> io::Result<Color> {
+        let b = self.take(4)?;
+        match b[0] {
+            0 => Ok(Color::Default),
+            1 => Ok(Color::Indexed(b[1])),
+            2 => Ok(Color::Rgb(b[1], b[2], b[3])),
+            _ => Err(invalid("unknown color tag")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_round_trip_through_encoding() {
+        let mut styled = Row::new(6);
+        styled.cells[0] = Cell {
+            c: '漢',
+            fg: Color::Rgb(1, 2, 3),
+            bg: Color::Indexed(4),
+            underline_color: Color::Rgb(5, 6, 7),
+            attrs: CellAttrs::BOLD | CellAttrs::UNDERLINE | CellAttrs::CURLY_UNDERLINE,
+            flags: CellFlags::WIDE_CHAR,
+            combining: None,
+        };
+        styled.cells[1] = Cell::wide_spacer();
+        styled.cells[2].c = 'x';
+        styled.cells[3].c = 'e';
+        styled.cells[3].combining = Some("\u{301}".into());
+        styled.line_id = 42;
+        styled.line_size = LineSize::DoubleWidth;
+        let blank = Row::new(6);
+
+        let mut buf = Vec::new();
+        encode_rows(&mut buf, [&styled, &blank].into_iter()).unwrap();
+        let decoded = decode_rows(&buf).unwrap();
+
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].cells, styled.cells);
+        assert_eq!(decoded[0].line_id, 42);
+        assert_eq!(decoded[0].line_size, LineSize::DoubleWidth);
+        assert!(decoded[1].line_size.is_single());
+        assert_eq!(decoded[1].cells, blank.cells);
+        assert!(!decoded[0].dirty);
+    }
+
+    #[test]
+    fn truncated_data_is_rejected() {
+        let mut buf = Vec::new();
+        encode_rows(&mut buf, [&Row::new(3)].into_iter()).unwrap();
+        assert!(decode_rows(&buf[..buf.len() - 1]).is_err());
+        assert!(decode_rows(b"garbage!").is_err());
+    }
+}
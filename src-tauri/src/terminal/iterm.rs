@@ -0,0 +1,306 @@
+//! iTerm2 file transfer: `OSC 1337 ; File=<args> : <base64> ST`, and the
+//! multipart form (`MultipartFile=<args>`, then `FilePart=<base64>` chunks
+//! and `FileEnd`) that tmux and slow links use for large files.
+//!
+//! Inline files are images shown in the grid, sized by `width`/`height` in
+//! cells (`N`), pixels (`Npx`), percent of the terminal (`N%`) or `auto`.
+//! Other files are offered to the user as downloads.
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
+
+/// Largest base64 payload kept for one file; larger transfers are dropped.
+const MAX_PAYLOAD_BYTES: usize = 64 * 1024 * 1024;
+
+/// One side of an image's size, as the program asked for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dimension {
+    #[default]
+    Auto,
+    Cells(u32),
+    Pixels(u32),
+    Percent(u32),
+}
+
+impl Dimension {
+    pub fn parse(value: &str) -> Self {
+        let number = |s: &str| s.trim().parse::<u32>().ok();
+        let parsed = if let Some(px) = value.strip_suffix("px") {
+            number(px).map(Dimension::Pixels)
+        } else if let Some(percent) = value.strip_suffix('%') {
+            number(percent).map(|p| Dimension::Percent(p.min(100)))
+        } else {
+            number(value).map(Dimension::Cells)
+        };
+        parsed.unwrap_or(Dimension::Auto)
+    }
+
+    /// In pixels, for cells `cell` pixels long on a terminal `total` pixels
+    /// long. `None` for `auto`.
+    fn pixels(self, cell: u32, total: u32) -> Option<u32> {
+        match self {
+            Dimension::Auto => None,
+            Dimension::Cells(n) => Some(n.saturating_mul(cell)),
+            Dimension::Pixels(n) => Some(n),
+            Dimension::Percent(p) => Some(total * p / 100),
+        }
+        .map(|n| n.max(1))
+    }
+}
+
+/// The `key=value` arguments of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileArgs {
+    /// File name, base64-decoded.
+    pub name: Option<String>,
+    pub size: Option<u64>,
+    pub width: Dimension,
+    pub height: Dimension,
+    pub preserve_aspect_ratio: bool,
+    /// Show the file in the terminal rather than download it.
+    pub inline: bool,
+}
+
+impl Default for FileArgs {
+    fn default() -> Self {
+        Self {
+            name: None,
+            size: None,
+            width: Dimension::Auto,
+            height: Dimension::Auto,
+            preserve_aspect_ratio: true,
+            inline: false,
+        }
+    }
+}
+
+impl FileArgs {
+    pub fn parse(args: &str) -> Self {
+        let mut out = FileArgs::default();
+        for part in args.split(';') {
+            let Some((key, value)) = part.split_once('=') else {
+                continue;
+            };
+            match key {
+                "name" => {
+                    out.name = BASE64_STANDARD
+                        .decode(value.trim())
+                        .ok()
+                        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                        .filter(|name| !name.is_empty());
+                }
+                "size" => out.size = value.parse().ok(),
+                "width" => out.width = Dimension::parse(value),
+                "height" => out.height = Dimension::parse(value),
+                "preserveAspectRatio" => out.preserve_aspect_ratio = value != "0",
+                "inline" => out.inline = value == "1",
+                _ => {}
+            }
+        }
+        out
+    }
+
+    /// Display size in pixels of an image `intrinsic` pixels large (when
+    /// its format is known), with cells `cell` pixels large on a terminal
+    /// `grid` cells large. An `auto` width never exceeds the terminal's.
+    pub fn display_size(
+        &self,
+        intrinsic: Option<(u32, u32)>,
+        cell: (u32, u32),
+        grid: (u16, u16),
+    ) -> (u32, u32) {
+        let total = (u32::from(grid.0) * cell.0, u32::from(grid.1) * cell.1);
+        // An image of unknown format gets the full width and half the height.
+        let (iw, ih) = intrinsic
+            .filter(|&(w, h)| w > 0 && h > 0)
+            .unwrap_or((total.0.max(1), (total.1 / 2).max(1)));
+        let scale = |n: u32, to: u32, from: u32| {
+            (u64::from(n) * u64::from(to) / u64::from(from)).max(1) as u32
+        };
+        let width = self.width.pixels(cell.0, total.0);
+        let height = self.height.pixels(cell.1, total.1);
+        let preserve = self.preserve_aspect_ratio;
+        let (w, h) = match (width, height) {
+            (Some(w), Some(h)) if preserve => {
+                if u64::from(iw) * u64::from(h) > u64::from(ih) * u64::from(w) {
+                    (w, scale(ih, w, iw))
+                } else {
+                    (scale(iw, h, ih), h)
+                }
+            }
+            (Some(w), Some(h)) => (w, h),
+            (Some(w), None) => (w, if preserve { scale(ih, w, iw) } else { ih }),
+            (None, Some(h)) => (if preserve { scale(iw, h, ih) } else { iw }, h),
+            (None, None) => (iw, ih),
+        };
+        if width.is_none() && w > total.0 && total.0 > 0 {
+            let h = if preserve { scale(h, total.0, w) } else { h };
+            return (total.0, h);
+        }
+        (w, h)
+    }
+}
+
+/// A complete file, decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct File {
+    pub args: FileArgs,
+    pub data: Vec<u8>,
+}
+
+/// Width and height of a PNG, GIF or JPEG image.
+pub fn image_size(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") && data.len() >= 24 {
+        let be =
+            |at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+        return Some((be(16), be(20)));
+    }
+    if data.starts_with(b"GIF8") && data.len() >= 10 {
+        let le = |at: usize| u32::from(u16::from_le_bytes([data[at], data[at + 1]]));
+        return Some((le(6), le(8)));
+    }
+    if data.starts_with(&[0xff, 0xd8]) {
+        // Walk the markers to the first start-of-frame.
+        let mut at = 2;
+        while at + 9 < data.len() {
+            if data[at] != 0xff {
+                return None;
+            }
+            let marker = data[at + 1];
+            let len = usize::from(u16::from_be_bytes([data[at + 2], data[at + 3]]));
+            let is_sof = matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc);
+            if is_sof {
+                let be = |at: usize| u32::from(u16::from_be_bytes([data[at], data[at + 1]]));
+                return Some((be(at + 7), be(at + 5)));
+            }
+            at += 2 + len;
+        }
+    }
+    None
+}
+
+/// A multipart transfer in progress.
+#[derive(Debug)]
+struct Pending {
+    args: FileArgs,
+    payload: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+pub struct FileTransfers {
+    pending: Option<Pending>,
+}
+
+impl FileTransfers {
+    /// Handle an OSC 1337 payload (what follows `1337;`). Returns a file
+    /// once all of it arrived; payloads that aren't file transfers, bad
+    /// base64 and oversized files yield nothing.
+    pub fn handle(&mut self, payload: &[u8]) -> Option<File> {
+        if let Some(rest) = payload.strip_prefix(b"File=") {
+            let colon = rest.iter().position(|&b| b == b':')?;
+            let args = FileArgs::parse(&String::from_utf8_lossy(&rest[..colon]));
+            return decode(args, &rest[colon + 1..]);
+        }
+        if let Some(args) = payload.strip_prefix(b"MultipartFile=") {
+            self.pending = Some(Pending {
+                args: FileArgs::parse(&String::from_utf8_lossy(args)),
+                payload: Vec::new(),
+            });
+            return None;
+        }
+        if let Some(part) = payload.strip_prefix(b"FilePart=") {
+            let pending = self.pending.as_mut()?;
+            if pending.payload.len() + part.len() > MAX_PAYLOAD_BYTES {
+                tracing::warn!(
+                    "iTerm2 multipart file exceeds {} bytes; dropped",
+                    MAX_PAYLOAD_BYTES
+                );
+                self.pending = None;
+                return None;
+            }
+            pending.payload.extend_from_slice(part);
+            return None;
+        }
+        if payload == b"FileEnd" {
+            let pending = self.pending.take()?;
+            return decode(pending.args, &pending.payload);
+        }
+        None
+    }
+}
+
+fn decode(args: FileArgs, payload: &[u8]) -> Option<File> {
+    if payload.len() > MAX_PAYLOAD_BYTES {
+        tracing::warn!("iTerm2 file exceeds {} bytes; dropped", MAX_PAYLOAD_BYTES);
+        return None;
+    }
+    let compact: Vec<u8> = payload
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    match BASE64_STANDARD.decode(&compact) {
+        Ok(data) if !data.is_empty() => Some(File { args, data }),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::warn!("iTerm2 file has bad base64: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_arguments_and_dimensions() {
+        let args = FileArgs::parse(
+            "name=YS5wbmc=;size=10;width=50%;height=8px;preserveAspectRatio=0;inline=1",
+        );
+        assert_eq!(args.name.as_deref(), Some("a.png"));
+        assert_eq!(args.size, Some(10));
+        assert_eq!(args.width, Dimension::Percent(50));
+        assert_eq!(args.height, Dimension::Pixels(8));
+        assert!(!args.preserve_aspect_ratio);
+        assert!(args.inline);
+        assert_eq!(Dimension::parse("12"), Dimension::Cells(12));
+        assert_eq!(Dimension::parse("auto"), Dimension::Auto);
+    }
+
+    #[test]
+    fn sizes_images_in_cells_pixels_and_percent() {
+        let cell = (10, 20);
+        let grid = (80, 24);
+        let size = |args: &str| FileArgs::parse(args).display_size(Some((400, 200)), cell, grid);
+        assert_eq!(size(""), (400, 200));
+        // One side given: the other follows the aspect ratio, unless told not to.
+        assert_eq!(size("width=20"), (200, 100));
+        assert_eq!(size("width=20;preserveAspectRatio=0"), (200, 200));
+        // Both given: fit inside the box.
+        assert_eq!(size("width=100px;height=100px"), (100, 50));
+        assert_eq!(size("width=50%;height=10"), (400, 200));
+        // Auto widths shrink to the terminal.
+        assert_eq!(
+            FileArgs::parse("").display_size(Some((1600, 400)), cell, grid),
+            (800, 200)
+        );
+    }
+
+    #[test]
+    fn multipart_transfers_are_joined() {
+        let mut transfers = FileTransfers::default();
+        assert_eq!(
+            transfers.handle(b"MultipartFile=name=eC50eHQ=;size=5"),
+            None
+        );
+        assert_eq!(transfers.handle(b"FilePart=aGVs"), None);
+        assert_eq!(transfers.handle(b"FilePart=bG8="), None);
+        let file = transfers.handle(b"FileEnd").unwrap();
+        assert_eq!(file.data, b"hello");
+        assert_eq!(file.args.name.as_deref(), Some("x.txt"));
+        assert!(!file.args.inline);
+        // A part without a started transfer is ignored.
+        assert_eq!(transfers.handle(b"FilePart=aGVs"), None);
+        assert_eq!(transfers.handle(b"FileEnd"), None);
+    }
+}
@@ -0,0 +1,98 @@
+//! Kitty keyboard protocol (progressive enhancement) flag stacks.
+//!
+//! Programs push the enhancements they want with `CSI > flags u`, restore the
+//! previous set with `CSI < n u`, modify the current set with
+//! `CSI = flags ; mode u` and query it with `CSI ? u`. The main and alternate
+//! screens keep independent stacks. Key encoding happens in the frontend;
+//! the backend only tracks which flags are active.
+
+/// Enhancement flags defined by the protocol.
+pub const ALL_FLAGS: u8 = 0b1_1111;
+
+/// Entries kept per stack; pushing past this drops the oldest.
+const MAX_STACK_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyboardStack {
+    /// Pushed flag sets, oldest first. Empty means no enhancements.
+    entries: Vec<u8>,
+}
+
+impl KeyboardStack {
+    pub fn flags(&self) -> u8 {
+        self.entries.last().copied().unwrap_or(0)
+    }
+
+    pub fn push(&mut self, flags: u8) {
+        if self.entries.len() == MAX_STACK_DEPTH {
+            self.entries.remove(0);
+        }
+        self.entries.push(flags & ALL_FLAGS);
+    }
+
+    /// Pop `count` entries; popping more than were pushed empties the stack.
+    pub fn pop(&mut self, count: usize) {
+        let keep = self.entries.len().saturating_sub(count);
+        self.entries.truncate(keep);
+    }
+
+    /// `CSI = flags ; mode u`: 1 replaces, 2 sets the given bits, 3 clears
+    /// them. Other modes are ignored.
+    pub fn set(&mut self, flags: u8, mode: u16) {
+        let flags = flags & ALL_FLAGS;
+        let current = self.flags();
+        let next = match mode {
+            1 => flags,
+            2 => current | flags,
+            3 => current & !flags,
+            _ => return,
+        };
+        match self.entries.last_mut() {
+            Some(top) => *top = next,
+            None => self.entries.push(next),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Reply to `CSI ? u`.
+    pub fn report(&self) -> Vec<u8> {
+        format!("\x1b[?{}u", self.flags()).into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_pop_and_set() {
+        let mut stack = KeyboardStack::default();
+        assert_eq!(stack.report(), b"\x1b[?0u");
+        stack.push(1);
+        stack.push(0b1011);
+        assert_eq!(stack.flags(), 0b1011);
+        stack.set(0b0010, 3);
+        assert_eq!(stack.flags(), 0b1001);
+        stack.set(0b0100, 2);
+        assert_eq!(stack.flags(), 0b1101);
+        stack.pop(1);
+        assert_eq!(stack.flags(), 1);
+        stack.pop(5);
+        assert_eq!(stack.flags(), 0);
+        stack.set(0xff, 1);
+        assert_eq!(stack.report(), b"\x1b[?31u");
+    }
+
+    #[test]
+    fn overflow_drops_the_oldest_entry() {
+        let mut stack = KeyboardStack::default();
+        for flags in 0..=MAX_STACK_DEPTH as u8 {
+            stack.push(flags);
+        }
+        stack.pop(MAX_STACK_DEPTH - 1);
+        assert_eq!(stack.flags(), 1);
+    }
+}
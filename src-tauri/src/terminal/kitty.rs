@@ -0,0 +1,632 @@
+//! Kitty graphics protocol (`ESC _ G <control> ; <payload> ESC \`).
+//!
+//! vte drops APC strings, so [`ApcScanner`] lifts them out of the byte
+//! stream before it reaches the parser. [`KittyGraphics`] then handles
+//! transmission (direct, optionally chunked), placement, queries and
+//! deletion. Images are stored as PNG whatever format they arrived in, so
+//! the frontend can show every placement as a plain data URI.
+//!
+//! Not supported: file, temp-file and shared-memory transmission (a remote
+//! program must not make Rain read local files), zlib compression and
+//! animation frames. Those requests are answered with `EINVAL`.
+
+use std::collections::{HashMap, VecDeque};
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
+
+/// Largest APC string kept; longer ones are dropped when they end.
+const MAX_APC_BYTES: usize = 16 * 1024 * 1024;
+/// PNG bytes kept across all images; the oldest images go first.
+const MAX_STORED_BYTES: usize = 64 * 1024 * 1024;
+/// Largest decoded image, in pixels.
+const MAX_PIXELS: u64 = 10_000 * 10_000;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// What to do with one byte offered to [`ApcScanner::feed`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Feed {
+    /// Hand the byte to the parser.
+    Pass,
+    /// The scanner keeps the byte.
+    Held,
+    /// Hand a held ESC to the parser; the current byte is held.
+    ReleaseEsc,
+    /// Hand a held ESC and then the current byte to the parser.
+    ReleaseEscAndPass,
+    /// An APC string ended; its contents, without `ESC _` and `ESC \`.
+    Apc(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum ScanState {
+    #[default]
+    Ground,
+    Escape,
+    Apc,
+    ApcEscape,
+}
+
+#[derive(Debug, Default)]
+pub struct ApcScanner {
+    state: ScanState,
+    buf: Vec<u8>,
+    overflow: bool,
+}
+
+impl ApcScanner {
+    pub fn feed(&mut self, byte: u8) -> Feed {
+        match (self.state, byte) {
+            (ScanState::Ground, 0x1b) => {
+                self.state = ScanState::Escape;
+                Feed::Held
+            }
+            (ScanState::Ground, _) => Feed::Pass,
+            (ScanState::Escape, b'_') => {
+                self.state = ScanState::Apc;
+                self.buf.clear();
+                self.overflow = false;
+                Feed::Held
+            }
+            (ScanState::Escape, 0x1b) => Feed::ReleaseEsc,
+            (ScanState::Escape, _) => {
+                self.state = ScanState::Ground;
+                Feed::ReleaseEscAndPass
+            }
+            (ScanState::Apc, 0x1b) => {
+                self.state = ScanState::ApcEscape;
+                Feed::Held
+            }
+            (ScanState::Apc, _) => {
+                if self.buf.len() < MAX_APC_BYTES {
+                    self.buf.push(byte);
+                } else {
+                    self.overflow = true;
+                }
+                Feed::Held
+            }
+            (ScanState::ApcEscape, b'\\') => {
+                self.state = ScanState::Ground;
+                let data = std::mem::take(&mut self.buf);
+                if self.overflow {
+                    Feed::Held
+                } else {
+                    Feed::Apc(data)
+                }
+            }
+            // Any other escape cancels the string and starts a new sequence.
+            (ScanState::ApcEscape, 0x1b) => {
+                self.buf.clear();
+                self.state = ScanState::Escape;
+                Feed::Held
+            }
+            (ScanState::ApcEscape, _) => {
+                self.buf.clear();
+                self.state = ScanState::Ground;
+                Feed::ReleaseEscAndPass
+            }
+        }
+    }
+}
+
+/// Control keys of a graphics command, with the protocol's defaults.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Control {
+    /// `a`: t(ransmit), T (transmit and place), p(lace), d(elete), q(uery).
+    pub action: u8,
+    /// `f`: 24 (RGB), 32 (RGBA) or 100 (PNG).
+    pub format: u32,
+    /// `t`: d(irect), f(ile), t(emp file), s(hared memory).
+    pub medium: u8,
+    /// `o`: compression of the payload.
+    pub compression: Option<u8>,
+    /// `s`, `v`: pixel size of raw RGB(A) data.
+    pub width: u32,
+    pub height: u32,
+    /// `i`: client-chosen image ID.
+    pub image_id: u32,
+    /// `I`: image number; Rain picks the ID and reports it.
+    pub image_number: u32,
+    /// `p`: placement ID.
+    pub placement_id: u32,
+    /// `m`: more chunks follow.
+    pub more: bool,
+    /// `q`: 1 suppresses OK replies, 2 suppresses errors too.
+    pub quiet: u8,
+    /// `d`: what a delete command removes.
+    pub delete: u8,
+    /// `c`, `r`: cells the placement spans.
+    pub columns: u32,
+    pub rows: u32,
+    /// `C=1`: leave the cursor where it is after placing.
+    pub no_cursor_move: bool,
+}
+
+impl Default for Control {
+    fn default() -> Self {
+        Self {
+            action: b't',
+            format: 32,
+            medium: b'd',
+            compression: None,
+            width: 0,
+            height: 0,
+            image_id: 0,
+            image_number: 0,
+            placement_id: 0,
+            more: false,
+            quiet: 0,
+            delete: b'a',
+            columns: 0,
+            rows: 0,
+            no_cursor_move: false,
+        }
+    }
+}
+
+pub fn parse_control(control: &str) -> Control {
+    let mut out = Control::default();
+    for pair in control.split(',') {
+        let Some((key, value)) = pair.split_once('=') else {
+            continue;
+        };
+        let number = || value.parse::<u32>().unwrap_or(0);
+        let letter = value.bytes().next();
+        match key {
+            "a" => out.action = letter.unwrap_or(b't'),
+            "f" => out.format = number(),
+            "t" => out.medium = letter.unwrap_or(b'd'),
+            "o" => out.compression = letter,
+            "s" => out.width = number(),
+            "v" => out.height = number(),
+            "i" => out.image_id = number(),
+            "I" => out.image_number = number(),
+            "p" => out.placement_id = number(),
+            "m" => out.more = number() == 1,
+            "q" => out.quiet = number().min(2) as u8,
+            "d" => out.delete = letter.unwrap_or(b'a'),
+            "c" => out.columns = number(),
+            "r" => out.rows = number(),
+            "C" => out.no_cursor_move = number() == 1,
+            _ => {}
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub png: Vec<u8>,
+}
+
+/// What the terminal should do after a graphics command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Effect {
+    Place {
+        image_id: u32,
+        placement_id: u32,
+        width: u32,
+        height: u32,
+        png_base64: String,
+        columns: u32,
+        rows: u32,
+        move_cursor: bool,
+    },
+    /// Remove placements; 0 matches every image or placement.
+    Delete { image_id: u32, placement_id: u32 },
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Outcome {
+    /// Reply to write back to the PTY.
+    pub response: Option<Vec<u8>>,
+    pub effect: Option<Effect>,
+}
+
+#[derive(Debug, Default)]
+pub struct KittyGraphics {
+    images: HashMap<u32, Image>,
+    /// Image IDs, oldest first, for eviction.
+    order: VecDeque<u32>,
+    /// Image number (`I`) to the ID it was given.
+    numbers: HashMap<u32, u32>,
+    /// First chunk's control and the base64 payload so far.
+    pending: Option<(Control, Vec<u8>)>,
+    next_id: u32,
+    stored_bytes: usize,
+}
+
+impl KittyGraphics {
+    /// Handle the contents of one APC string. Non-graphics APCs are ignored.
+    pub fn handle(&mut self, apc: &[u8]) -> Outcome {
+        let Some(body) = apc.strip_prefix(b"G") else {
+            return Outcome::default();
+        };
+        let (control, payload) = match body.iter().position(|&b| b == b';') {
+            Some(idx) => (&body[..idx], &body[idx + 1..]),
+            None => (body, &body[body.len()..]),
+        };
+        let control = parse_control(&String::from_utf8_lossy(control));
+
+        // Later chunks only carry `m` (and maybe `q`); the first chunk's
+        // keys apply to the whole image.
+        let (control, payload) = match self.pending.take() {
+            Some((mut first, mut data)) => {
+                data.extend_from_slice(payload);
+                if control.more {
+                    self.pending = Some((first, data));
+                    return Outcome::default();
+                }
+                first.quiet = first.quiet.max(control.quiet);
+                (first, data)
+            }
+            None if control.more => {
+                self.pending = Some((control, payload.to_vec()));
+                return Outcome::default();
+            }
+            None => (control, payload.to_vec()),
+        };
+
+        match self.dispatch(&control, &payload) {
+            Ok((id, effect)) => Outcome {
+                response: reply(&control, id, None),
+                effect,
+            },
+            Err(error) => Outcome {
+                response: reply(&control, control.image_id, Some(error)),
+                effect: None,
+            },
+        }
+    }
+
+    fn dispatch(
+        &mut self,
+        control: &Control,
+        payload: &[u8],
+    ) -> Result<(u32, Option<Effect>), String> {
+        match control.action {
+            b'q' => {
+                decode_image(control, payload)?;
+                Ok((control.image_id, None))
+            }
+            b't' | b'T' => {
+                let image = decode_image(control, payload)?;
+                let id = self.store(control, image);
+                let effect = (control.action == b'T')
+                    .then(|| self.place(control, id))
+                    .flatten();
+                Ok((id, effect))
+            }
+            b'p' => {
+                let id = self.resolve(control).ok_or("ENOENT:image not found")?;
+                Ok((id, self.place(control, id)))
+            }
+            b'd' => Ok((control.image_id, self.delete(control))),
+            _ => Err("EINVAL:unsupported action".to_string()),
+        }
+    }
+
+    fn resolve(&self, control: &Control) -> Option<u32> {
+        let id = if control.image_id != 0 {
+            control.image_id
+        } else {
+            *self.numbers.get(&control.image_number)?
+        };
+        self.images.contains_key(&id).then_some(id)
+    }
+
+    fn store(&mut self, control: &Control, image: Image) -> u32 {
+        let id = if control.image_id != 0 {
+            control.image_id
+        } else {
+            self.allocate_id()
+        };
+        if control.image_number != 0 {
+            self.numbers.insert(control.image_number, id);
+        }
+        self.remove(id);
+        self.stored_bytes += image.png.len();
+        self.images.insert(id, image);
+        self.order.push_back(id);
+        while self.stored_bytes > MAX_STORED_BYTES && self.order.len() > 1 {
+            let oldest = self.order[0];
+            self.remove(oldest);
+        }
+        id
+    }
+
+    /// IDs Rain assigns count down from the top so they never collide with
+    /// the small IDs programs pick themselves.
+    fn allocate_id(&mut self) -> u32 {
+        loop {
+            self.next_id = self.next_id.wrapping_add(1);
+            let id = u32::MAX - self.next_id;
+            if id != 0 && !self.images.contains_key(&id) {
+                return id;
+            }
+        }
+    }
+
+    fn remove(&mut self, id: u32) {
+        if let Some(image) = self.images.remove(&id) {
+            self.stored_bytes -= image.png.len();
+            self.order.retain(|&other| other != id);
+            self.numbers.retain(|_, &mut other| other != id);
+        }
+    }
+
+    fn place(&self, control: &Control, id: u32) -> Option<Effect> {
+        let image = self.images.get(&id)?;
+        Some(Effect::Place {
+            image_id: id,
+            placement_id: control.placement_id,
+            width: image.width,
+            height: image.height,
+            png_base64: BASE64_STANDARD.encode(&image.png),
+            columns: control.columns,
+            rows: control.rows,
+            move_cursor: !control.no_cursor_move,
+        })
+    }
+
+    /// Lowercase targets only remove placements; uppercase also frees the
+    /// image data. Position-based targets (cursor, cell, z-index) are not
+    /// tracked and are ignored.
+    fn delete(&mut self, control: &Control) -> Option<Effect> {
+        let free = control.delete.is_ascii_uppercase();
+        match control.delete.to_ascii_lowercase() {
+            b'a' => {
+                if free {
+                    self.images.clear();
+                    self.order.clear();
+                    self.numbers.clear();
+                    self.stored_bytes = 0;
+                }
+                Some(Effect::Delete {
+                    image_id: 0,
+                    placement_id: 0,
+                })
+            }
+            b'i' | b'n' => {
+                let id = self.resolve(control)?;
+                if free {
+                    self.remove(id);
+                }
+                Some(Effect::Delete {
+                    image_id: id,
+                    placement_id: control.placement_id,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// `ESC _ G i=<id>[,I=<n>][,p=<p>];<message> ESC \`, unless the client gave
+/// no ID or asked for quiet.
+fn reply(control: &Control, id: u32, error: Option<String>) -> Option<Vec<u8>> {
+    if control.image_id == 0 && control.image_number == 0 {
+        return None;
+    }
+    let suppressed = match error {
+        None => control.quiet >= 1,
+        Some(_) => control.quiet >= 2,
+    };
+    if suppressed {
+        return None;
+    }
+    let mut keys = format!("i={}", id);
+    if control.image_number != 0 {
+        keys.push_str(&format!(",I={}", control.image_number));
+    }
+    if control.placement_id != 0 {
+        keys.push_str(&format!(",p={}", control.placement_id));
+    }
+    let message = error.unwrap_or_else(|| "OK".to_string());
+    Some(format!("\x1b_G{};{}\x1b\\", keys, message).into_bytes())
+}
+
+fn decode_image(control: &Control, payload: &[u8]) -> Result<Image, String> {
+    if control.medium != b'd' {
+        return Err("EINVAL:only direct transmission is supported".to_string());
+    }
+    if control.compression.is_some() {
+        return Err("EINVAL:compression is not supported".to_string());
+    }
+    let data = BASE64_STANDARD
+        .decode(payload.trim_ascii())
+        .map_err(|_| "EINVAL:bad base64 payload".to_string())?;
+    match control.format {
+        100 => {
+            if !data.starts_with(PNG_SIGNATURE) || data.len() < 24 {
+                return Err("EBADPNG:not a PNG image".to_string());
+            }
+            let width = u32::from_be_bytes([data[16], data[17], data[18], data[19]]);
+            let height = u32::from_be_bytes([data[20], data[21], data[22], data[23]]);
+            Ok(Image {
+                width,
+                height,
+                png: data,
+            })
+        }
+        24 | 32 => {
+            let channels = if control.format == 24 { 3 } else { 4 };
+            let (width, height) = (control.width, control.height);
+            let pixels = width as u64 * height as u64;
+            if pixels == 0 || pixels > MAX_PIXELS {
+                return Err("EINVAL:bad image size".to_string());
+            }
+            if data.len() as u64 != pixels * channels as u64 {
+                return Err("ENODATA:payload does not match image size".to_string());
+            }
+            Ok(Image {
+                width,
+                height,
+                png: encode_png(width, height, channels, &data),
+            })
+        }
+        _ => Err("EINVAL:unsupported format".to_string()),
+    }
+}
+
+/// Uncompressed 8-bit RGB or RGBA PNG (stored deflate blocks).
+pub fn encode_png(width: u32, height: u32, channels: usize, pixels: &[u8]) -> Vec<u8> {
+    let stride = width as usize * channels;
+    let mut raw = Vec::with_capacity((stride + 1) * height as usize);
+    for row in pixels.chunks(stride) {
+        raw.push(0); // filter: none
+        raw.extend_from_slice(row);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        zlib.push(u8::from(blocks.peek().is_none()));
+        let len = block.len() as u16;
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    let color_type = if channels == 4 { 6 } else { 2 };
+    ihdr.extend_from_slice(&[8, color_type, 0, 0, 0]);
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    png_chunk(&mut png, b"IHDR", &ihdr);
+    png_chunk(&mut png, b"IDAT", &zlib);
+    png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(scanner: &mut ApcScanner, bytes: &[u8]) -> (Vec<u8>, Vec<Vec<u8>>) {
+        let mut passed = Vec::new();
+        let mut apcs = Vec::new();
+        for &b in bytes {
+            match scanner.feed(b) {
+                Feed::Pass => passed.push(b),
+                Feed::Held => {}
+                Feed::ReleaseEsc => passed.push(0x1b),
+                Feed::ReleaseEscAndPass => passed.extend_from_slice(&[0x1b, b]),
+                Feed::Apc(data) => apcs.push(data),
+            }
+        }
+        (passed, apcs)
+    }
+
+    #[test]
+    fn scanner_lifts_apc_strings_out_of_the_stream() {
+        let mut scanner = ApcScanner::default();
+        let (passed, apcs) = scan(&mut scanner, b"a\x1b[1mb\x1b_Gi=1;AAAA\x1b\\c\x1b\x1b[0m");
+        assert_eq!(passed, b"a\x1b[1mbc\x1b\x1b[0m");
+        assert_eq!(apcs, vec![b"Gi=1;AAAA".to_vec()]);
+
+        // Split across reads.
+        let (_, apcs) = scan(&mut scanner, b"\x1b_Gq=2");
+        assert!(apcs.is_empty());
+        let (passed, apcs) = scan(&mut scanner, b";\x1b\\x");
+        assert_eq!((passed, apcs), (b"x".to_vec(), vec![b"Gq=2;".to_vec()]));
+    }
+
+    #[test]
+    fn chunked_rgb_transmission_and_placement() {
+        let mut kitty = KittyGraphics::default();
+        let pixels = BASE64_STANDARD.encode([255u8, 0, 0, 0, 255, 0]);
+        let (first, rest) = pixels.split_at(4);
+
+        let outcome = kitty.handle(format!("Ga=T,f=24,s=2,v=1,i=7,p=3,m=1;{}", first).as_bytes());
+        assert_eq!(outcome, Outcome::default());
+        let outcome = kitty.handle(format!("Gm=0;{}", rest).as_bytes());
+        assert_eq!(outcome.response, Some(b"\x1b_Gi=7,p=3;OK\x1b\\".to_vec()));
+        let Some(Effect::Place {
+            image_id,
+            placement_id,
+            width,
+            png_base64,
+            ..
+        }) = outcome.effect
+        else {
+            panic!("expected a placement: {:?}", outcome.effect);
+        };
+        assert_eq!((image_id, placement_id, width), (7, 3, 2));
+        let png = BASE64_STANDARD.decode(png_base64).unwrap();
+        assert!(png.starts_with(PNG_SIGNATURE));
+        assert_eq!(kitty.images.get(&7).unwrap().png, png);
+    }
+
+    #[test]
+    fn queries_errors_and_deletion() {
+        let mut kitty = KittyGraphics::default();
+        // The support probe TUIs send before using the protocol.
+        let outcome = kitty.handle(b"Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA");
+        assert_eq!(outcome.response, Some(b"\x1b_Gi=31;OK\x1b\\".to_vec()));
+        assert!(!kitty.images.contains_key(&31));
+
+        let outcome = kitty.handle(b"Ga=p,i=9");
+        assert_eq!(
+            outcome.response,
+            Some(b"\x1b_Gi=9;ENOENT:image not found\x1b\\".to_vec())
+        );
+        let outcome = kitty.handle(b"Ga=t,t=f,i=9,q=2;L2V0Yy9wYXNzd2Q=");
+        assert_eq!(outcome, Outcome::default());
+
+        kitty.handle(b"Ga=t,f=32,s=1,v=1,I=4;AAAAAA==");
+        let id = *kitty.numbers.get(&4).unwrap();
+        let outcome = kitty.handle(b"Ga=d,d=N,I=4,q=1");
+        assert_eq!(
+            outcome.effect,
+            Some(Effect::Delete {
+                image_id: id,
+                placement_id: 0
+            })
+        );
+        assert!(!kitty.images.contains_key(&id));
+        assert_eq!(kitty.stored_bytes, 0);
+    }
+}
@@ -1,7 +1,16 @@
 pub mod color;
 pub mod cursor;
+pub mod device;
 pub mod grid;
+pub mod hibernate;
+pub mod iterm;
+pub mod keyboard;
+pub mod kitty;
 pub mod modes;
+pub mod mouse;
+pub mod selection;
+pub mod selftest;
+pub mod sixel;
 pub mod state;
 
 pub use state::TerminalState;
@@ -3,6 +3,10 @@
 pub struct TerminalModes {
     /// DECCKM: cursor key mode (application vs normal)
     pub cursor_keys_application: bool,
+    /// DECCOLM: 132-column mode (mode 3)
+    pub column_132: bool,
+    /// DECSCNM: reverse video for the whole screen (mode 5)
+    pub reverse_video: bool,
     /// DECOM: origin mode
     pub origin: bool,
     /// DECAWM: auto-wrap mode
@@ -19,6 +23,8 @@ pub struct TerminalModes {
     pub sgr_mouse: bool,
     /// UTF-8 mouse reporting (mode 1005)
     pub utf8_mouse: bool,
+    /// SGR mouse reporting in pixels (mode 1016)
+    pub sgr_pixels: bool,
     /// Alternate scroll mode (mode 1007)
     pub alternate_scroll: bool,
     /// Bracketed paste mode (mode 2004)
@@ -33,12 +39,19 @@ pub struct TerminalModes {
     pub insert: bool,
     /// Line feed / new line mode (LNM)
     pub linefeed_newline: bool,
+    /// DECLRMM: left/right margin mode (mode 69), enables DECSLRM
+    pub left_right_margin: bool,
+    /// xterm modifyOtherKeys level set with `CSI > 4 ; Pv m`: 0 (off), 1
+    /// (keys whose modifiers would otherwise be lost) or 2 (all modified keys)
+    pub modify_other_keys: u8,
 }
 
 impl Default for TerminalModes {
     fn default() -> Self {
         Self {
             cursor_keys_application: false,
+            column_132: false,
+            reverse_video: false,
             origin: false,
             autowrap: true,
             cursor_visible: true,
@@ -47,6 +60,7 @@ impl Default for TerminalModes {
             mouse_all_motion: false,
             sgr_mouse: false,
             utf8_mouse: false,
+            sgr_pixels: false,
             alternate_scroll: false,
             bracketed_paste: false,
             synchronized_output: false,
@@ -54,6 +68,8 @@ impl Default for TerminalModes {
             alt_screen: false,
             insert: false,
             linefeed_newline: false,
+            left_right_margin: false,
+            modify_other_keys: 0,
         }
     }
 }
@@ -0,0 +1,213 @@
+//! Mouse reports written to the PTY while a program has mouse tracking on.
+//!
+//! The frontend sends what happened (button, action, cell, pixel offset and
+//! modifiers) and the encoding follows the modes the program set, most
+//! specific first:
+//!
+//! - SGR-Pixels (1016): `CSI < b ; x ; y M/m` with pixel coordinates
+//! - SGR (1006): the same with cell coordinates
+//! - UTF-8 (1005): `CSI M` with each value as a UTF-8 character
+//! - X10/normal: `CSI M` with each value as one byte, up to column 223
+
+use serde::Deserialize;
+
+use super::modes::TerminalModes;
+
+/// Largest value a UTF-8 report can carry (two-byte characters).
+const MAX_UTF8_VALUE: u32 = 2047 - 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    /// Motion with no button held.
+    None,
+    WheelUp,
+    WheelDown,
+    WheelLeft,
+    WheelRight,
+}
+
+impl MouseButton {
+    fn code(self) -> u32 {
+        match self {
+            MouseButton::Left => 0,
+            MouseButton::Middle => 1,
+            MouseButton::Right => 2,
+            MouseButton::None => 3,
+            MouseButton::WheelUp => 64,
+            MouseButton::WheelDown => 65,
+            MouseButton::WheelLeft => 66,
+            MouseButton::WheelRight => 67,
+        }
+    }
+
+    fn is_wheel(self) -> bool {
+        self.code() >= 64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MouseAction {
+    Press,
+    Release,
+    Motion,
+}
+
+/// A mouse event over the grid, as sent to `send_mouse_event`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MouseEvent {
+    pub action: MouseAction,
+    pub button: MouseButton,
+    /// 0-based cell under the pointer.
+    pub row: u16,
+    pub col: u16,
+    /// 0-based pixel offset from the grid's top-left corner, for SGR-Pixels.
+    #[serde(default)]
+    pub x: u32,
+    #[serde(default)]
+    pub y: u32,
+    #[serde(default)]
+    pub shift: bool,
+    #[serde(default)]
+    pub alt: bool,
+    #[serde(default)]
+    pub ctrl: bool,
+}
+
+/// Bytes reporting `event`, or `None` when the active tracking modes don't
+/// report it (no tracking, motion without 1002/1003, wheel releases).
+pub fn encode(event: &MouseEvent, modes: &TerminalModes) -> Option<Vec<u8>> {
+    if !(modes.mouse_tracking || modes.mouse_motion || modes.mouse_all_motion) {
+        return None;
+    }
+    let button = event.button;
+    let mut code = match event.action {
+        MouseAction::Press => button.code(),
+        MouseAction::Release if button.is_wheel() => return None,
+        // Only SGR says which button was released.
+        MouseAction::Release if modes.sgr_mouse || modes.sgr_pixels => button.code(),
+        MouseAction::Release => 3,
+        MouseAction::Motion if button.is_wheel() => return None,
+        MouseAction::Motion if modes.mouse_all_motion => button.code() + 32,
+        MouseAction::Motion if modes.mouse_motion && button != MouseButton::None => {
+            button.code() + 32
+        }
+        MouseAction::Motion => return None,
+    };
+    if event.shift {
+        code += 4;
+    }
+    if event.alt {
+        code += 8;
+    }
+    if event.ctrl {
+        code += 16;
+    }
+
+    let (col, row) = (event.col as u32 + 1, event.row as u32 + 1);
+    if modes.sgr_pixels || modes.sgr_mouse {
+        let (x, y) = if modes.sgr_pixels {
+            (event.x + 1, event.y + 1)
+        } else {
+            (col, row)
+        };
+        let suffix = if event.action == MouseAction::Release {
+            'm'
+        } else {
+            'M'
+        };
+        return Some(format!("\x1b[<{};{};{}{}", code, x, y, suffix).into_bytes());
+    }
+
+    let mut report = b"\x1b[M".to_vec();
+    if modes.utf8_mouse {
+        for value in [code, col, row] {
+            let c = char::from_u32(value.min(MAX_UTF8_VALUE) + 32)?;
+            let mut buf = [0u8; 4];
+            report.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+    } else {
+        for value in [code, col, row] {
+            report.push((value + 32).min(255) as u8);
+        }
+    }
+    Some(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(action: MouseAction, button: MouseButton, row: u16, col: u16) -> MouseEvent {
+        MouseEvent {
+            action,
+            button,
+            row,
+            col,
+            x: 0,
+            y: 0,
+            shift: false,
+            alt: false,
+            ctrl: false,
+        }
+    }
+
+    #[test]
+    fn encodes_in_the_most_specific_active_format() {
+        let mut modes = TerminalModes::default();
+        let press = event(MouseAction::Press, MouseButton::Left, 4, 9);
+        assert_eq!(encode(&press, &modes), None);
+
+        modes.mouse_tracking = true;
+        assert_eq!(encode(&press, &modes).unwrap(), b"\x1b[M *%");
+        let release = event(MouseAction::Release, MouseButton::Right, 4, 9);
+        assert_eq!(encode(&release, &modes).unwrap(), b"\x1b[M#*%");
+        let far = event(MouseAction::Press, MouseButton::Left, 0, 299);
+        assert_eq!(encode(&far, &modes).unwrap(), b"\x1b[M \xff!");
+
+        modes.utf8_mouse = true;
+        assert_eq!(encode(&far, &modes).unwrap(), "\x1b[M \u{14c}!".as_bytes());
+
+        modes.sgr_mouse = true;
+        assert_eq!(encode(&release, &modes).unwrap(), b"\x1b[<2;10;5m");
+        let ctrl_wheel = MouseEvent {
+            ctrl: true,
+            ..event(MouseAction::Press, MouseButton::WheelDown, 4, 9)
+        };
+        assert_eq!(encode(&ctrl_wheel, &modes).unwrap(), b"\x1b[<81;10;5M");
+
+        modes.sgr_pixels = true;
+        let pixels = MouseEvent {
+            x: 75,
+            y: 41,
+            ..press
+        };
+        assert_eq!(encode(&pixels, &modes).unwrap(), b"\x1b[<0;76;42M");
+    }
+
+    #[test]
+    fn motion_follows_the_tracking_mode() {
+        let mut modes = TerminalModes {
+            mouse_tracking: true,
+            sgr_mouse: true,
+            ..TerminalModes::default()
+        };
+        let drag = event(MouseAction::Motion, MouseButton::Left, 0, 0);
+        let hover = event(MouseAction::Motion, MouseButton::None, 0, 0);
+        assert_eq!(encode(&drag, &modes), None);
+
+        modes.mouse_motion = true;
+        assert_eq!(encode(&drag, &modes).unwrap(), b"\x1b[<32;1;1M");
+        assert_eq!(encode(&hover, &modes), None);
+
+        modes.mouse_all_motion = true;
+        assert_eq!(encode(&hover, &modes).unwrap(), b"\x1b[<35;1;1M");
+        let wheel_release = event(MouseAction::Release, MouseButton::WheelUp, 0, 0);
+        assert_eq!(encode(&wheel_release, &modes), None);
+    }
+}
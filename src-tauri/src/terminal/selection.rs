@@ -0,0 +1,393 @@
+//! Text selection kept in the backend, so copying reads the cells rather
+//! than whatever the frontend happened to render.
+//!
+//! Both ends are anchored to stable line IDs, so scrolling carries the
+//! selection along with its text. It is dropped once that text can no
+//! longer be trusted: an end leaves the grid (trimmed scrollback, reset), a
+//! selected row that was on screen gets rewritten (a reflow to a new width
+//! included), or rows are inserted inside it.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use super::grid::{Cell, CellFlags, Grid, Row};
+
+/// Characters that count as part of a word besides letters and digits, so
+/// paths and URLs select whole. Matches the frontend's word selection.
+const WORD_PUNCTUATION: &str = "_-./~";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SelectionMode {
+    /// Cells from one end to the other in reading order.
+    Char,
+    /// Like `Char`, widened to whole words at both ends.
+    Word,
+    /// Whole logical lines, rows they wrapped onto included.
+    Line,
+    /// The rectangle between the two ends.
+    Block,
+}
+
+/// A cell by the stable ID of its line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionPoint {
+    pub line_id: u64,
+    pub col: u16,
+}
+
+/// The selected cells after word and line widening: rows from `start` to
+/// `end`, and for `Block` columns `start.col..=end.col` of each; otherwise
+/// from `start.col` on the first row through `end.col` on the last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionRange {
+    pub mode: SelectionMode,
+    pub start: SelectionPoint,
+    pub end: SelectionPoint,
+}
+
+/// Row indices and columns of a selection in one grid.
+#[derive(Debug, Clone, Copy)]
+struct Resolved {
+    start: (usize, usize),
+    end: (usize, usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextSelection {
+    pub mode: SelectionMode,
+    pub anchor: SelectionPoint,
+    pub head: SelectionPoint,
+    /// Made on the alternate screen; switching screens drops it.
+    pub alt_screen: bool,
+    /// Offset from the first selected row of the first one that was on
+    /// screen when the selection was made, and a hash of the text from
+    /// there to the end. Rows above it are scrollback and can't change.
+    watch: (usize, u64),
+}
+
+impl TextSelection {
+    /// A selection of `grid`, or `None` when either end isn't resident.
+    pub fn new(
+        grid: &Grid,
+        mode: SelectionMode,
+        anchor: SelectionPoint,
+        head: SelectionPoint,
+        alt_screen: bool,
+    ) -> Option<Self> {
+        let mut selection = Self {
+            mode,
+            anchor,
+            head,
+            alt_screen,
+            watch: (0, 0),
+        };
+        let resolved = selection.resolve(grid)?;
+        let on_screen = grid.rows.len().saturating_sub(grid.visible_rows as usize);
+        let offset = on_screen.saturating_sub(resolved.start.0);
+        selection.watch = (offset, text_hash(grid, &resolved, offset));
+        Some(selection)
+    }
+
+    /// Whether the selected text is still what was selected.
+    pub fn is_intact(&self, grid: &Grid) -> bool {
+        let Some(resolved) = self.resolve(grid) else {
+            return false;
+        };
+        text_hash(grid, &resolved, self.watch.0) == self.watch.1
+    }
+
+    pub fn range(&self, grid: &Grid) -> Option<SelectionRange> {
+        let resolved = self.resolve(grid)?;
+        let point = |(idx, col): (usize, usize)| SelectionPoint {
+            line_id: grid.rows[idx].line_id,
+            col: col as u16,
+        };
+        Some(SelectionRange {
+            mode: self.mode,
+            start: point(resolved.start),
+            end: point(resolved.end),
+        })
+    }
+
+    /// Selected text, rows joined with newlines except where a line wrapped,
+    /// trailing blanks of each line dropped.
+    pub fn text(&self, grid: &Grid) -> Option<String> {
+        let resolved = self.resolve(grid)?;
+        let mut text = String::new();
+        for idx in resolved.start.0..=resolved.end.0 {
+            let row = &grid.rows[idx];
+            let (from, to) = self.columns(&resolved, idx, row);
+            let piece = cells_text(&row.cells[from..to]);
+            let last = idx == resolved.end.0;
+            if self.mode != SelectionMode::Block && row.is_wrapped() && !last {
+                text.push_str(&piece);
+            } else {
+                text.push_str(piece.trim_end());
+                if !last {
+                    text.push('\n');
+                }
+            }
+        }
+        Some(text)
+    }
+
+    /// The selected part of each row, for styled renderings.
+    pub fn rows(&self, grid: &Grid) -> Option<Vec<Row>> {
+        let resolved = self.resolve(grid)?;
+        let rows = (resolved.start.0..=resolved.end.0)
+            .map(|idx| {
+                let row = &grid.rows[idx];
+                let (from, to) = self.columns(&resolved, idx, row);
+                Row {
+                    cells: row.cells[from..to].to_vec(),
+                    ..row.clone()
+                }
+            })
+            .collect();
+        Some(rows)
+    }
+
+    /// Selected cells `from..to` of row `idx`.
+    fn columns(&self, resolved: &Resolved, idx: usize, row: &Row) -> (usize, usize) {
+        let len = row.cells.len();
+        let (from, to) = if self.mode == SelectionMode::Block {
+            (resolved.start.1, resolved.end.1 + 1)
+        } else {
+            let from = if idx == resolved.start.0 {
+                resolved.start.1
+            } else {
+                0
+            };
+            let to = if idx == resolved.end.0 {
+                resolved.end.1 + 1
+            } else {
+                len
+            };
+            (from, to)
+        };
+        (from.min(len), to.min(len).max(from.min(len)))
+    }
+
+    fn resolve(&self, grid: &Grid) -> Option<Resolved> {
+        let locate = |point: SelectionPoint| -> Option<(usize, usize)> {
+            let idx = grid.find_line(point.line_id)?;
+            let last = grid.rows[idx].cells.len().saturating_sub(1);
+            Some((idx, (point.col as usize).min(last)))
+        };
+        let anchor = locate(self.anchor)?;
+        let head = locate(self.head)?;
+
+        if self.mode == SelectionMode::Block {
+            return Some(Resolved {
+                start: (anchor.0.min(head.0), anchor.1.min(head.1)),
+                end: (anchor.0.max(head.0), anchor.1.max(head.1)),
+            });
+        }
+        let (mut start, mut end) = if head < anchor {
+            (head, anchor)
+        } else {
+            (anchor, head)
+        };
+        match self.mode {
+            SelectionMode::Word => {
+                start = word_start(grid, start);
+                end = word_end(grid, end);
+            }
+            SelectionMode::Line => {
+                while start.0 > 0 && grid.rows[start.0 - 1].is_wrapped() {
+                    start.0 -= 1;
+                }
+                while end.0 + 1 < grid.rows.len() && grid.rows[end.0].is_wrapped() {
+                    end.0 += 1;
+                }
+                start.1 = 0;
+                end.1 = grid.rows[end.0].cells.len().saturating_sub(1);
+            }
+            SelectionMode::Char | SelectionMode::Block => {}
+        }
+        Some(Resolved { start, end })
+    }
+}
+
+fn cells_text(cells: &[Cell]) -> String {
+    cells
+        .iter()
+        .filter(|c| !c.flags.contains(CellFlags::WIDE_SPACER))
+        .flat_map(Cell::chars)
+        .collect()
+}
+
+fn is_spacer(cell: &Cell) -> bool {
+    cell.flags.contains(CellFlags::WIDE_SPACER)
+}
+
+fn is_word_cell(cell: &Cell) -> bool {
+    !is_spacer(cell) && (cell.c.is_alphanumeric() || WORD_PUNCTUATION.contains(cell.c))
+}
+
+/// First cell of the word at `pos`, or `pos` itself off a word. Words
+/// continue across wrapped rows.
+fn word_start(grid: &Grid, (mut idx, col): (usize, usize)) -> (usize, usize) {
+    let cells = &grid.rows[idx].cells;
+    let mut start = col;
+    if start > 0 && is_spacer(&cells[start]) {
+        start -= 1;
+    }
+    if !is_word_cell(&cells[start]) {
+        return (idx, start);
+    }
+    loop {
+        let cells = &grid.rows[idx].cells;
+        while start > 0 {
+            let prev = if is_spacer(&cells[start - 1]) && start >= 2 {
+                start - 2
+            } else {
+                start - 1
+            };
+            if !is_word_cell(&cells[prev]) {
+                return (idx, start);
+            }
+            start = prev;
+        }
+        let continues = idx > 0
+            && grid.rows[idx - 1].is_wrapped()
+            && grid.rows[idx - 1].cells.last().is_some_and(is_word_cell);
+        if !continues {
+            return (idx, start);
+        }
+        idx -= 1;
+        start = grid.rows[idx].cells.len() - 1;
+    }
+}
+
+/// Last cell of the word at `pos`, including a trailing wide spacer.
+fn word_end(grid: &Grid, (mut idx, col): (usize, usize)) -> (usize, usize) {
+    let mut end = col;
+    if end > 0 && is_spacer(&grid.rows[idx].cells[end]) {
+        end -= 1;
+    }
+    if is_word_cell(&grid.rows[idx].cells[end]) {
+        loop {
+            let row = &grid.rows[idx];
+            while end + 1 < row.cells.len()
+                && (is_word_cell(&row.cells[end + 1]) || is_spacer(&row.cells[end + 1]))
+            {
+                end += 1;
+            }
+            let continues = end + 1 == row.cells.len()
+                && row.is_wrapped()
+                && grid
+                    .rows
+                    .get(idx + 1)
+                    .and_then(|next| next.cells.first())
+                    .is_some_and(is_word_cell);
+            if !continues {
+                break;
+            }
+            idx += 1;
+            end = 0;
+        }
+    }
+    let cells = &grid.rows[idx].cells;
+    if end + 1 < cells.len() && is_spacer(&cells[end + 1]) {
+        end += 1;
+    }
+    (idx, end)
+}
+
+/// Hash of the row count and of the text from `offset` rows into the
+/// selection to its end.
+fn text_hash(grid: &Grid, resolved: &Resolved, offset: usize) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (resolved.end.0 - resolved.start.0).hash(&mut hasher);
+    for row in grid
+        .rows
+        .range(resolved.start.0..=resolved.end.0)
+        .skip(offset)
+    {
+        row.line_id.hash(&mut hasher);
+        for c in row.cells.iter().flat_map(Cell::chars) {
+            c.hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::TerminalState;
+
+    fn point(state: &TerminalState, row: u16, col: u16) -> SelectionPoint {
+        SelectionPoint {
+            line_id: state.grid.visible_line_id(row),
+            col,
+        }
+    }
+
+    #[test]
+    fn modes_widen_and_join_as_expected() {
+        let mut state = TerminalState::new(4, 10);
+        state.feed(b"cd ~/src/app\r\nls -la\r\nx");
+        let select = |mode, anchor, head| {
+            TextSelection::new(&state.grid, mode, anchor, head, false)
+                .unwrap()
+                .text(&state.grid)
+                .unwrap()
+        };
+
+        // "cd ~/src/a" wrapped onto "pp": one line without a newline.
+        let word = select(
+            SelectionMode::Word,
+            point(&state, 0, 5),
+            point(&state, 0, 5),
+        );
+        assert_eq!(word, "~/src/app");
+        let line = select(
+            SelectionMode::Line,
+            point(&state, 1, 3),
+            point(&state, 0, 1),
+        );
+        assert_eq!(line, "cd ~/src/app");
+        let chars = select(
+            SelectionMode::Char,
+            point(&state, 2, 3),
+            point(&state, 1, 0),
+        );
+        assert_eq!(chars, "pp\nls -");
+        let block = select(
+            SelectionMode::Block,
+            point(&state, 0, 0),
+            point(&state, 2, 1),
+        );
+        assert_eq!(block, "cd\npp\nls");
+    }
+
+    #[test]
+    fn rewriting_selected_rows_breaks_the_selection() {
+        let mut state = TerminalState::new(3, 10);
+        state.feed(b"one\r\ntwo\r\nthree");
+        let selection = TextSelection::new(
+            &state.grid,
+            SelectionMode::Char,
+            point(&state, 0, 0),
+            point(&state, 1, 2),
+            false,
+        )
+        .unwrap();
+        assert!(selection.is_intact(&state.grid));
+
+        // Scrolling moves the text but leaves it as it was.
+        state.feed(b"\r\nfour");
+        assert!(selection.is_intact(&state.grid));
+        assert_eq!(selection.text(&state.grid).unwrap(), "one\ntwo");
+
+        state.feed(b"\x1b[Htw0");
+        assert!(!selection.is_intact(&state.grid));
+    }
+}
@@ -0,0 +1,327 @@
+//! Built-in terminal self-test.
+//!
+//! Each check feeds a short escape-sequence snippet into a scratch
+//! [`TerminalState`] and inspects the resulting grid, so a bug report can say
+//! exactly which feature misbehaves. [`render`] turns the results into a
+//! visual test pattern that can be shown in a real session, where problems
+//! with fonts or the renderer (rather than the parser) become visible.
+
+use serde::Serialize;
+
+use super::color::Color;
+use super::cursor::CellAttrs;
+use super::grid::{CellFlags, LineSize};
+use super::TerminalState;
+use crate::render::frame::TerminalEvent;
+
+const ROWS: u16 = 8;
+const COLS: u16 = 40;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelftestResult {
+    pub feature: &'static str,
+    pub passed: bool,
+    /// What went wrong, for failed checks.
+    pub detail: Option<String>,
+}
+
+struct Check {
+    feature: &'static str,
+    input: &'static [u8],
+    verify: fn(&mut TerminalState) -> Result<(), String>,
+    /// Only run when image protocols are enabled.
+    images: bool,
+}
+
+fn row_text(state: &TerminalState, row: u16, len: usize) -> String {
+    state
+        .grid
+        .visible_row(row)
+        .cells
+        .iter()
+        .take(len)
+        .map(|cell| cell.c)
+        .collect()
+}
+
+fn expect<T: PartialEq + std::fmt::Debug>(what: &str, got: T, want: T) -> Result<(), String> {
+    if got == want {
+        Ok(())
+    } else {
+        Err(format!("{}: expected {:?}, got {:?}", what, want, got))
+    }
+}
+
+const CHECKS: &[Check] = &[
+    Check {
+        feature: "sgr-colors",
+        input: b"\x1b[31mA\x1b[92mB\x1b[38;5;208mC\x1b[38;2;1;2;3mD\x1b[44mE\x1b[0mF",
+        verify: |state| {
+            let cells = &state.grid.visible_row(0).cells;
+            expect("ANSI red", cells[0].fg, Color::Indexed(1))?;
+            expect("bright green", cells[1].fg, Color::Indexed(10))?;
+            expect("256-color", cells[2].fg, Color::Indexed(208))?;
+            expect("truecolor", cells[3].fg, Color::Rgb(1, 2, 3))?;
+            expect("background", cells[4].bg, Color::Indexed(4))?;
+            expect(
+                "reset",
+                (cells[5].fg, cells[5].bg),
+                (Color::Default, Color::Default),
+            )
+        },
+        images: false,
+    },
+    Check {
+        feature: "text-attributes",
+        input: b"\x1b[1;2;3;4;7;9mA\x1b[22;23;24;27;29mB",
+        verify: |state| {
+            let cells = &state.grid.visible_row(0).cells;
+            let all = CellAttrs::BOLD
+                | CellAttrs::DIM
+                | CellAttrs::ITALIC
+                | CellAttrs::UNDERLINE
+                | CellAttrs::REVERSE
+                | CellAttrs::STRIKETHROUGH;
+            expect("attributes set", cells[0].attrs, all)?;
+            expect("attributes cleared", cells[1].attrs, CellAttrs::empty())
+        },
+        images: false,
+    },
+    Check {
+        feature: "wide-chars",
+        input: "漢字x".as_bytes(),
+        verify: |state| {
+            let cells = &state.grid.visible_row(0).cells;
+            expect("wide cell", cells[0].flags, CellFlags::WIDE_CHAR)?;
+            expect("spacer cell", cells[1].flags, CellFlags::WIDE_SPACER)?;
+            expect("text", row_text(state, 0, 5), "漢 字 x".to_string())?;
+            expect("cursor column", state.cursor.col, 5)
+        },
+        images: false,
+    },
+    Check {
+        feature: "line-drawing",
+        input: b"\x1b(0lqk\r\nmqj\x1b(Bq",
+        verify: |state| {
+            expect("top", row_text(state, 0, 3), "┌─┐".to_string())?;
+            expect("bottom", row_text(state, 1, 4), "└─┘q".to_string())
+        },
+        images: false,
+    },
+    Check {
+        feature: "cursor-movement",
+        input: b"\x1b[3;5HX\x1b[2AY\x1b[10GZ\x1b[1;1H\x1b[2BW",
+        verify: |state| {
+            expect("CUP", state.grid.visible_row(2).cells[4].c, 'X')?;
+            expect("CUU", state.grid.visible_row(0).cells[5].c, 'Y')?;
+            expect("CHA", state.grid.visible_row(0).cells[9].c, 'Z')?;
+            expect("CUD", state.grid.visible_row(2).cells[0].c, 'W')
+        },
+        images: false,
+    },
+    Check {
+        feature: "erase",
+        input: b"abcdef\x1b[3D\x1b[K\r\nxyz\r\nuvw\x1b[2;1H\x1b[J",
+        verify: |state| {
+            expect("EL", row_text(state, 0, 6), "abc   ".to_string())?;
+            expect(
+                "ED",
+                row_text(state, 1, 3) + &row_text(state, 2, 3),
+                "      ".to_string(),
+            )
+        },
+        images: false,
+    },
+    Check {
+        feature: "scroll-region",
+        input: b"1\r\n2\r\n3\r\n4\x1b[2;4r\x1b[4;1H\n5",
+        verify: |state| {
+            expect("outside region", row_text(state, 0, 1), "1".to_string())?;
+            expect("scrolled", row_text(state, 1, 1), "3".to_string())?;
+            expect("new line", row_text(state, 3, 1), "5".to_string())
+        },
+        images: false,
+    },
+    Check {
+        feature: "alt-screen",
+        input: b"main\x1b[?1049halt\x1b[?1049l",
+        verify: |state| {
+            expect("back on main screen", state.using_alt, false)?;
+            expect(
+                "main content kept",
+                row_text(state, 0, 4),
+                "main".to_string(),
+            )
+        },
+        images: false,
+    },
+    Check {
+        feature: "double-size-lines",
+        input: b"\x1b#3Top\r\n\x1b#4Top\r\n\x1b#6Wide",
+        verify: |state| {
+            expect(
+                "DECDHL top",
+                state.grid.visible_row(0).line_size,
+                LineSize::DoubleHeightTop,
+            )?;
+            expect(
+                "DECDHL bottom",
+                state.grid.visible_row(1).line_size,
+                LineSize::DoubleHeightBottom,
+            )?;
+            expect(
+                "DECDWL",
+                state.grid.visible_row(2).line_size,
+                LineSize::DoubleWidth,
+            )
+        },
+        images: false,
+    },
+    Check {
+        feature: "device-reports",
+        input: b"\x1b[2;3H\x1b[6n\x1b[c",
+        verify: |state| {
+            let responses = state.take_pending_responses();
+            expect(
+                "DSR",
+                responses.first().map(Vec::as_slice),
+                Some(&b"\x1b[2;3R"[..]),
+            )?;
+            expect("DA1", responses.len(), 2)
+        },
+        images: false,
+    },
+    Check {
+        feature: "hyperlinks",
+        input: b"\x1b]8;;https://example.com\x07link\x1b]8;;\x07",
+        verify: |state| expect("link text", row_text(state, 0, 4), "link".to_string()),
+        images: false,
+    },
+    Check {
+        feature: "inline-images",
+        input: b"\x1b]1337;File=inline=1:iVBORw0KGgo=\x07",
+        verify: |state| {
+            let events = state
+                .take_render_snapshot()
+                .map(|snapshot| snapshot.events)
+                .unwrap_or_default();
+            let found = events
+                .iter()
+                .any(|event| matches!(event, TerminalEvent::InlineImage { .. }));
+            expect("OSC 1337 image event", found, true)
+        },
+        images: true,
+    },
+];
+
+/// Run every check against its own scratch terminal.
+pub fn run(image_protocols: bool) -> Vec<SelftestResult> {
+    CHECKS
+        .iter()
+        .filter(|check| image_protocols || !check.images)
+        .map(|check| {
+            let mut state = TerminalState::new(ROWS, COLS);
+            state.feed(check.input);
+            let outcome = (check.verify)(&mut state);
+            SelftestResult {
+                feature: check.feature,
+                passed: outcome.is_ok(),
+                detail: outcome.err(),
+            }
+        })
+        .collect()
+}
+
+/// A visual test pattern followed by the check results, ready to be fed to a
+/// session as if the shell had printed it.
+pub fn render(results: &[SelftestResult]) -> Vec<u8> {
+    let mut out = String::from("\r\n\x1b[0m");
+    out.push_str("\x1b#3Rain self-test\r\n\x1b#4Rain self-test\r\n\r\n");
+
+    out.push_str("ANSI     ");
+    for i in 0..8 {
+        out.push_str(&format!("\x1b[4{}m  ", i));
+    }
+    out.push_str("\x1b[0m\r\n         ");
+    for i in 0..8 {
+        out.push_str(&format!("\x1b[10{}m  ", i));
+    }
+    out.push_str("\x1b[0m\r\n256      ");
+    for i in (16..232).step_by(6) {
+        out.push_str(&format!("\x1b[48;5;{}m ", i));
+    }
+    out.push_str("\x1b[0m\r\n         ");
+    for i in 232..=255 {
+        out.push_str(&format!("\x1b[48;5;{}m ", i));
+    }
+    out.push_str("\x1b[0m\r\nRGB      ");
+    for i in 0..48u32 {
+        let v = i * 255 / 47;
+        out.push_str(&format!("\x1b[48;2;{};{};{}m ", v, 96, 255 - v));
+    }
+    out.push_str("\x1b[0m\r\n\r\n");
+
+    out.push_str(
+        "Styles   \x1b[1mbold\x1b[0m \x1b[2mdim\x1b[0m \x1b[3mitalic\x1b[0m \
+         \x1b[4munderline\x1b[0m \x1b[7mreverse\x1b[0m \x1b[9mstrike\x1b[0m\r\n",
+    );
+    out.push_str("Wide     漢字 かな 한글 😀|\r\n");
+    out.push_str("Drawing  \x1b(0lqqwqqk\x1b(B\r\n");
+    out.push_str("         \x1b(0x  x  x\x1b(B\r\n");
+    out.push_str("         \x1b(0mqqvqqj\x1b(B\r\n");
+    out.push_str("\x1b#6Double width\r\n\r\n");
+
+    for result in results {
+        if result.passed {
+            out.push_str(&format!("\x1b[32m  ✓ {}\x1b[0m\r\n", result.feature));
+        } else {
+            out.push_str(&format!(
+                "\x1b[31m  ✗ {}: {}\x1b[0m\r\n",
+                result.feature,
+                result.detail.as_deref().unwrap_or("failed")
+            ));
+        }
+    }
+    let failed = results.iter().filter(|result| !result.passed).count();
+    out.push_str(&format!(
+        "\r\n{} of {} checks passed\r\n",
+        results.len() - failed,
+        results.len()
+    ));
+    out.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_check_passes() {
+        let results = run(true);
+        assert_eq!(results.len(), CHECKS.len());
+        for result in &results {
+            assert!(result.passed, "{}: {:?}", result.feature, result.detail);
+        }
+        assert!(run(false)
+            .iter()
+            .all(|result| result.feature != "inline-images"));
+    }
+
+    #[test]
+    fn rendered_pattern_reports_failures() {
+        let results = [SelftestResult {
+            feature: "wide-chars",
+            passed: false,
+            detail: Some("bad width".into()),
+        }];
+        let text = String::from_utf8(render(&results)).unwrap();
+        assert!(text.contains("wide-chars: bad width"));
+        assert!(text.contains("0 of 1 checks passed"));
+
+        // The pattern itself must parse cleanly.
+        let mut state = TerminalState::new(40, 80);
+        state.feed(text.as_bytes());
+        assert!(state.cursor.col == 0 && state.cursor.row > 15);
+    }
+}
@@ -0,0 +1,285 @@
+//! Sixel graphics (`DCS P1 ; P2 ; P3 q <data> ST`).
+//!
+//! The DCS payload is decoded into RGBA here: color register definitions
+//! (`#Pc;Pu;Px;Py;Pz`, RGB or DEC's HLS), selections (`#Pc`), repeat runs
+//! (`!Pn`), raster attributes (`"Pan;Pad;Ph;Pv`), graphics carriage
+//! return (`$`) and new line (`-`). Pixel aspect ratios are ignored;
+//! every sixel is one pixel wide and six tall.
+
+/// Color registers a program can define, as in xterm.
+const REGISTERS: usize = 1024;
+/// Largest image kept, in pixels; sixels beyond it are clipped.
+const MAX_PIXELS: u64 = 4096 * 4096;
+/// Longest side kept, in pixels.
+const MAX_SIDE: u32 = 8192;
+
+type Rgb = (u8, u8, u8);
+
+/// VT340 default color registers 0-15, in percent.
+const VT340_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (20, 20, 80),
+    (80, 13, 13),
+    (20, 80, 20),
+    (80, 20, 80),
+    (20, 80, 80),
+    (80, 80, 20),
+    (53, 53, 53),
+    (26, 26, 26),
+    (33, 33, 60),
+    (60, 26, 26),
+    (33, 60, 33),
+    (60, 33, 60),
+    (33, 60, 60),
+    (60, 60, 33),
+    (80, 80, 80),
+];
+
+/// A decoded image, `rgba` row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SixelImage {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    /// `"Pan;Pad;Ph;Pv`: the image size the program announced.
+    Raster {
+        width: u32,
+        height: u32,
+    },
+    /// `#Pc`, with `Pu;Px;Py;Pz` when it defines the register.
+    Color {
+        register: usize,
+        define: Option<(u32, u32, u32, u32)>,
+    },
+    /// Six pixels stacked vertically, bit 0 on top, `repeat` times.
+    Sixel {
+        bits: u8,
+        repeat: u32,
+    },
+    CarriageReturn,
+    NextLine,
+}
+
+fn percent(v: u32) -> u8 {
+    (v.min(100) * 255 / 100) as u8
+}
+
+/// DEC HLS, where hue 0 is blue and 120 is red, to RGB.
+fn hls_to_rgb(hue: u32, lightness: u32, saturation: u32) -> Rgb {
+    let h = ((hue + 240) % 360) as f32 / 360.0;
+    let l = lightness.min(100) as f32 / 100.0;
+    let s = saturation.min(100) as f32 / 100.0;
+    if s == 0.0 {
+        let v = (l * 255.0).round() as u8;
+        return (v, v, v);
+    }
+    let q = if l < 0.5 {
+        l * (1.0 + s)
+    } else {
+        l + s - l * s
+    };
+    let p = 2.0 * l - q;
+    let channel = |t: f32| {
+        let t = t.rem_euclid(1.0);
+        let v = if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        } else if t < 0.5 {
+            q
+        } else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        } else {
+            p
+        };
+        (v * 255.0).round() as u8
+    };
+    (channel(h + 1.0 / 3.0), channel(h), channel(h - 1.0 / 3.0))
+}
+
+/// Numeric parameters starting at `data[*i]`, separated by `;`.
+fn params(data: &[u8], i: &mut usize) -> Vec<u32> {
+    let mut out = vec![0u32];
+    while let Some(&b) = data.get(*i) {
+        match b {
+            b'0'..=b'9' => {
+                let last = out.last_mut().expect("at least one parameter");
+                *last = last.saturating_mul(10).saturating_add(u32::from(b - b'0'));
+            }
+            b';' => out.push(0),
+            _ => break,
+        }
+        *i += 1;
+    }
+    out
+}
+
+fn parse(data: &[u8], mut on: impl FnMut(Op)) {
+    let mut i = 0;
+    while let Some(&b) = data.get(i) {
+        i += 1;
+        match b {
+            b'?'..=b'~' => on(Op::Sixel {
+                bits: b - b'?',
+                repeat: 1,
+            }),
+            b'!' => {
+                let count = params(data, &mut i)[0].max(1);
+                if let Some(&c @ b'?'..=b'~') = data.get(i) {
+                    i += 1;
+                    on(Op::Sixel {
+                        bits: c - b'?',
+                        repeat: count,
+                    });
+                }
+            }
+            b'#' => {
+                let p = params(data, &mut i);
+                let register = p[0] as usize;
+                if register >= REGISTERS {
+                    continue;
+                }
+                let define = (p.len() >= 5).then(|| (p[1], p[2], p[3], p[4]));
+                on(Op::Color { register, define });
+            }
+            b'"' => {
+                let p = params(data, &mut i);
+                if let (Some(&width), Some(&height)) = (p.get(2), p.get(3)) {
+                    on(Op::Raster { width, height });
+                }
+            }
+            b'$' => on(Op::CarriageReturn),
+            b'-' => on(Op::NextLine),
+            _ => {}
+        }
+    }
+}
+
+/// Decode a Sixel payload (the bytes after the `q`). Pixels no sixel
+/// covers are `background`, or transparent when it is `None` (`P2` = 1).
+/// Returns `None` for an empty image.
+pub fn decode(data: &[u8], background: Option<Rgb>) -> Option<SixelImage> {
+    // First pass: the size, from the raster attributes and the sixels drawn.
+    let (mut width, mut height) = (0u32, 0u32);
+    let (mut x, mut y) = (0u32, 0u32);
+    parse(data, |op| match op {
+        Op::Raster {
+            width: w,
+            height: h,
+        } => {
+            width = width.max(w);
+            height = height.max(h);
+        }
+        Op::Sixel { bits, repeat } => {
+            x = x.saturating_add(repeat);
+            width = width.max(x);
+            if bits != 0 {
+                height = height.max(y.saturating_add(8 - bits.leading_zeros()));
+            }
+        }
+        Op::CarriageReturn => x = 0,
+        Op::NextLine => {
+            x = 0;
+            y = y.saturating_add(6);
+        }
+        Op::Color { .. } => {}
+    });
+    let width = width.min(MAX_SIDE);
+    let mut height = height.min(MAX_SIDE);
+    if width as u64 * height as u64 > MAX_PIXELS {
+        height = (MAX_PIXELS / width as u64) as u32;
+    }
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    // Second pass: paint.
+    let fill = match background {
+        Some((r, g, b)) => [r, g, b, 0xff],
+        None => [0, 0, 0, 0],
+    };
+    let mut rgba = fill.repeat(width as usize * height as usize);
+    let mut palette = vec![(0u8, 0u8, 0u8); REGISTERS];
+    for (slot, &(r, g, b)) in palette.iter_mut().zip(VT340_PALETTE.iter()) {
+        *slot = (
+            percent(u32::from(r)),
+            percent(u32::from(g)),
+            percent(u32::from(b)),
+        );
+    }
+    let mut color = palette[0];
+    let (mut x, mut y) = (0u32, 0u32);
+    parse(data, |op| match op {
+        Op::Color { register, define } => {
+            match define {
+                Some((1, h, l, s)) => palette[register] = hls_to_rgb(h, l, s),
+                Some((2, r, g, b)) => palette[register] = (percent(r), percent(g), percent(b)),
+                _ => {}
+            }
+            color = palette[register];
+        }
+        Op::Sixel { bits, repeat } => {
+            let end = x.saturating_add(repeat).min(width);
+            for bit in 0..6u32 {
+                let row = y.saturating_add(bit);
+                if bits & (1 << bit) == 0 || row >= height {
+                    continue;
+                }
+                let line = row as usize * width as usize;
+                for col in x.min(end)..end {
+                    let at = (line + col as usize) * 4;
+                    rgba[at..at + 4].copy_from_slice(&[color.0, color.1, color.2, 0xff]);
+                }
+            }
+            x = x.saturating_add(repeat);
+        }
+        Op::CarriageReturn => x = 0,
+        Op::NextLine => {
+            x = 0;
+            y = y.saturating_add(6);
+        }
+        Op::Raster { .. } => {}
+    });
+    Some(SixelImage {
+        width,
+        height,
+        rgba,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(image: &SixelImage, x: u32, y: u32) -> [u8; 4] {
+        let at = ((y * image.width + x) * 4) as usize;
+        image.rgba[at..at + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn decodes_registers_repeats_and_bands() {
+        // Register 1 red, 2 green by HLS (hue 240 is green); a 3-wide red
+        // run over the first band, then one green sixel on the second.
+        let image = decode(b"\"1;1;4;8#1;2;100;0;0#2;1;240;50;100#1!3~-#2@", None).unwrap();
+        assert_eq!((image.width, image.height), (4, 8));
+        assert_eq!(pixel(&image, 0, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 2, 5), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 3, 0), [0, 0, 0, 0]);
+        // `@` sets only bit 0: the first row of the second band.
+        assert_eq!(pixel(&image, 0, 6), [0, 255, 0, 255]);
+        assert_eq!(pixel(&image, 0, 7), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn sizes_without_raster_attributes_and_fills_the_background() {
+        // `$` returns to the start of the band to overlay a second color.
+        let image = decode(b"\"1;1;2;1#0;2;0;0;100A$#15@", Some((9, 9, 9))).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(pixel(&image, 0, 0), [204, 204, 204, 255]);
+        assert_eq!(pixel(&image, 0, 1), [0, 0, 255, 255]);
+        assert_eq!(pixel(&image, 1, 0), [9, 9, 9, 255]);
+        assert_eq!(decode(b"#1;2;0;0;0", None), None);
+    }
+}
@@ -1,12 +1,62 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
 use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
+use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthChar;
 
-use super::color::{Color, indexed_to_rgb};
+use super::color::{parse_color_spec, Color, DynamicColors, Palette, ThemeColors};
 use super::cursor::{CellAttrs, CursorShape, CursorState};
-use super::grid::{Cell, CellFlags, Grid};
+use super::device::{self, Capabilities, DeviceAttributes};
+use super::grid::{Cell, CellFlags, Grid, LineSize, Row, ScrollbackUsage};
+use super::iterm::{self, FileTransfers};
+use super::keyboard::KeyboardStack;
+use super::kitty::{self, ApcScanner, Effect, Feed, KittyGraphics};
 use super::modes::TerminalModes;
-use crate::render::frame::{CursorRender, RenderFrame, RenderedLine, TerminalEvent};
-use crate::shell::ShellIntegration;
+use super::mouse::{self, MouseEvent};
+use super::selection::{SelectionMode, SelectionPoint, SelectionRange, TextSelection};
+use super::sixel::{self, SixelImage};
+use crate::assets::AssetSink;
+use crate::clipboard::{self, Selection};
+use crate::render::color_filter::ColorFilter;
+use crate::render::contrast;
+use crate::render::copy::{self, CopyOptions, PromptRegion};
+use crate::render::export::ExportFormat;
+use crate::render::frame::{
+    coalesce_events, CursorRender, ProgressState, RenderFrame, RenderedLine, ScrollbackPage,
+    TerminalEvent, EVENT_SCHEMA_VERSION, MAX_SCROLLBACK_PAGE,
+};
+use crate::render::search::{
+    CellRect, Pattern, ScrollbackMatch, TextQuery, MAX_SCROLLBACK_MATCHES,
+};
+use crate::render::structured;
+use crate::shell::command_line::{self, CommandLine};
+use crate::shell::{remote, BlockRecord, ShellIntegration, ViewportBlock};
+
+/// Unacknowledged frames tolerated before switching to keyframe mode.
+const FRAME_ACK_KEYFRAME_GAP: u64 = 120;
+/// Gap the frontend must catch up to before deltas resume.
+const FRAME_ACK_RESUME_GAP: u64 = 8;
+/// Unacknowledged frames at which the PTY reader pauses until the frontend
+/// catches up.
+const FRAME_ACK_PAUSE_GAP: u64 = 240;
+/// Scrolled-off lines held for the next frame. Older ones are dropped from
+/// it; they stay in scrollback for the frontend to fetch.
+const MAX_PENDING_SCROLLED_LINES: usize = 5_000;
+/// iTerm2 downloads kept until the user saves or discards them.
+const MAX_PENDING_DOWNLOADS: usize = 4;
+/// Titles kept by `CSI 22 t`, as in xterm.
+const MAX_TITLE_STACK: usize = 10;
+/// Cell size assumed for the footprint of Sixel images until the frontend
+/// reports the real one.
+const FALLBACK_CELL_PIXELS: (u16, u16) = (10, 20);
+/// Longest a synchronized update (mode 2026) holds frames back, so a
+/// program that never ends one doesn't freeze the screen.
+const SYNC_UPDATE_TIMEOUT: Duration = Duration::from_millis(150);
+/// Dim line written between the output of an exited shell and its
+/// replacement by `restart`.
+const RESTART_MARKER: &str = "\x1b[2m── Shell restarted ──\x1b[0m";
 
 /// Full terminal state. Implements `vte::Perform` to process escape sequences.
 pub struct TerminalState {
@@ -17,9 +67,15 @@ pub struct TerminalState {
     pub modes: TerminalModes,
     pub scroll_top: u16,
     pub scroll_bottom: u16,
+    /// Left/right margins (DECSLRM), inclusive. Full width unless DECLRMM
+    /// is set.
+    pub scroll_left: u16,
+    pub scroll_right: u16,
     pub tab_stops: Vec<bool>,
     pub title: String,
     pub title_changed: bool,
+    /// `TitleChanged`/`CwdChanged` events dropped by frame coalescing.
+    coalesced_events: u64,
     pub shell: ShellIntegration,
     cols: u16,
     rows: u16,
@@ -52,6 +108,8 @@ pub struct TerminalState {
     sixel_active: bool,
     /// Accumulated Sixel data buffer
     sixel_buffer: Vec<u8>,
+    /// The Sixel DCS asked for a transparent background (`P2` = 1).
+    sixel_transparent: bool,
     /// Gate for image protocols (OSC 1337 / Sixel / Kitty scaffolding).
     experimental_image_protocols_enabled: bool,
     /// One-shot warning guard when image protocol data is ignored.
@@ -62,6 +120,87 @@ pub struct TerminalState {
     /// cursor-only changes (position, visibility, shape) that should
     /// trigger a frame even when no grid lines are dirty.
     last_emitted_cursor: (u16, u16, bool, CursorShape),
+    /// Last time the session saw PTY output or user input. Drives idle
+    /// hibernation of scrollback.
+    last_activity: Instant,
+    /// OSC numbers registered from config whose payloads are forwarded to
+    /// the frontend as `CustomOsc` events. Built-in OSC handlers take priority.
+    custom_osc_codes: Vec<u16>,
+    /// Conformance level and feature list reported by DA1/DA2.
+    device_attributes: DeviceAttributes,
+    /// Registry that decoded images go to; without one they are sent
+    /// inline as base64.
+    assets: Option<AssetSink>,
+    /// Clipboard history that OSC 52 copies are recorded in.
+    clipboard_history: Option<clipboard::SharedHistory>,
+    /// Highest frame sequence the frontend has acknowledged. `None` until the
+    /// first ack so consumers that never ack are unaffected.
+    acked_frame_seq: Option<u64>,
+    /// While the frontend lags too far behind, every frame is a full
+    /// keyframe so a stalled webview never applies stale deltas out of order.
+    keyframe_mode: bool,
+    /// Set once the frontend stayed backlogged for a whole read pause. The
+    /// reader then stops pausing and frames stay keyframes until the
+    /// frontend catches up, rather than throttling the program read by read.
+    read_pause_expired: bool,
+    /// Accessibility color transform applied to every emitted span.
+    color_filter: ColorFilter,
+    /// Contrast every span's text must have against its background; 1 is
+    /// off.
+    min_contrast: f32,
+    /// Bold text in ANSI colors 0-7 is sent in the bright variants.
+    bold_is_bright: bool,
+    /// Print East Asian ambiguous-width characters two columns wide, as
+    /// CJK locales expect.
+    ambiguous_wide: bool,
+    /// Text selected through `set_selection`, checked against the grid
+    /// before every frame.
+    selection: Option<TextSelection>,
+    /// 256-color palette as changed by OSC 4; overridden entries are
+    /// emitted as RGB.
+    palette: Palette,
+    /// Default fg/bg/cursor colors as changed by OSC 10/11/12.
+    dynamic_colors: DynamicColors,
+    /// Active theme, and whether the frontend needs its ANSI colors as RGB.
+    theme: (ThemeColors, bool),
+    /// Last OSC 9;4 progress report (state, percent).
+    progress: (ProgressState, u8),
+    /// Block last reported at the top of the frontend's viewport.
+    viewport_block: ViewportBlock,
+    /// Pattern of `search_visible`, re-run on the active screen for every
+    /// frame that changes it.
+    visible_search: Option<Pattern>,
+    /// Kitty keyboard enhancement flags of the main screen.
+    keyboard_main: KeyboardStack,
+    /// Kitty keyboard enhancement flags of the alternate screen.
+    keyboard_alt: KeyboardStack,
+    /// Keyboard flags in the last emitted frame; a change alone triggers one.
+    last_emitted_keyboard_flags: u8,
+    /// DECSCNM state in the last emitted frame; a change alone triggers one.
+    last_emitted_reverse_video: bool,
+    /// Lifts APC strings (kitty graphics) out of the stream before vte,
+    /// which drops them.
+    apc_scanner: ApcScanner,
+    /// Images and chunked transfers of the kitty graphics protocol.
+    kitty_graphics: KittyGraphics,
+    /// Multipart iTerm2 file transfers.
+    file_transfers: FileTransfers,
+    /// Non-inline iTerm2 files waiting to be saved, oldest first.
+    pending_downloads: Vec<(String, iterm::File)>,
+    /// Whether non-inline iTerm2 files are offered as downloads at all;
+    /// off by default, when they are dropped.
+    file_downloads_enabled: bool,
+    /// Titles saved by `CSI 22 t` for `CSI 23 t` to restore.
+    title_stack: Vec<String>,
+    /// Cell size in pixels (width, height) as rendered by the frontend, for
+    /// the XTWINOPS size reports.
+    cell_pixels: Option<(u16, u16)>,
+    /// The cursor sits just past the right margin after printing there; the
+    /// next character wraps to the left margin.
+    margin_wrap_pending: bool,
+    /// When the synchronized update in progress began; frames are held
+    /// until it ends or `SYNC_UPDATE_TIMEOUT` passes.
+    sync_update_started: Option<Instant>,
 }
 
 /// Snapshot of terminal render data extracted under lock.
@@ -76,11 +215,16 @@ pub struct RenderSnapshot {
     pub visible_cols: u16,
     pub cursor: CursorRender,
     pub events: Vec<TerminalEvent>,
+    pub keyframe: bool,
+    pub keyboard_flags: u8,
+    pub reverse_video: bool,
+    pub visible_search: Option<Vec<CellRect>>,
 }
 
 impl RenderSnapshot {
     pub fn into_frame(self) -> RenderFrame {
-        RenderFrame {
+        let mut frame = RenderFrame {
+            event_schema: EVENT_SCHEMA_VERSION,
             frame_seq: self.frame_seq,
             resize_epoch: self.resize_epoch,
             lines: self.lines,
@@ -90,7 +234,14 @@ impl RenderSnapshot {
             visible_cols: self.visible_cols,
             cursor: self.cursor,
             events: self.events,
-        }
+            keyframe: self.keyframe,
+            keyboard_flags: self.keyboard_flags,
+            reverse_video: self.reverse_video,
+            visible_search: self.visible_search,
+            styles: Vec::new(),
+        };
+        frame.intern_styles();
+        frame
     }
 }
 
@@ -111,9 +262,12 @@ impl TerminalState {
             modes: TerminalModes::default(),
             scroll_top: 0,
             scroll_bottom: rows.saturating_sub(1),
+            scroll_left: 0,
+            scroll_right: cols.saturating_sub(1),
             tab_stops,
             title: String::new(),
             title_changed: false,
+            coalesced_events: 0,
             shell: ShellIntegration::new(),
             cols,
             rows,
@@ -132,10 +286,210 @@ impl TerminalState {
             bell_pending: false,
             sixel_active: false,
             sixel_buffer: Vec::new(),
+            sixel_transparent: false,
             experimental_image_protocols_enabled: image_protocols_enabled,
             image_protocol_drop_notified: false,
             last_printed_char: ' ',
             last_emitted_cursor: (0, 0, true, CursorShape::Block),
+            last_activity: Instant::now(),
+            custom_osc_codes: Vec::new(),
+            device_attributes: DeviceAttributes::default(),
+            assets: None,
+            clipboard_history: None,
+            acked_frame_seq: None,
+            keyframe_mode: false,
+            read_pause_expired: false,
+            color_filter: ColorFilter::None,
+            min_contrast: contrast::MIN_CONTRAST_OFF,
+            bold_is_bright: false,
+            ambiguous_wide: false,
+            selection: None,
+            palette: Palette::default(),
+            dynamic_colors: DynamicColors::default(),
+            theme: (ThemeColors::default(), false),
+            progress: (ProgressState::None, 0),
+            visible_search: None,
+            viewport_block: ViewportBlock::default(),
+            keyboard_main: KeyboardStack::default(),
+            keyboard_alt: KeyboardStack::default(),
+            last_emitted_keyboard_flags: 0,
+            last_emitted_reverse_video: false,
+            apc_scanner: ApcScanner::default(),
+            kitty_graphics: KittyGraphics::default(),
+            file_transfers: FileTransfers::default(),
+            pending_downloads: Vec::new(),
+            file_downloads_enabled: false,
+            title_stack: Vec::new(),
+            cell_pixels: None,
+            margin_wrap_pending: false,
+            sync_update_started: None,
+        }
+    }
+
+    /// Kitty keyboard enhancement flags of the active screen.
+    pub fn keyboard_flags(&self) -> u8 {
+        if self.using_alt {
+            self.keyboard_alt.flags()
+        } else {
+            self.keyboard_main.flags()
+        }
+    }
+
+    fn keyboard_stack_mut(&mut self) -> &mut KeyboardStack {
+        if self.using_alt {
+            &mut self.keyboard_alt
+        } else {
+            &mut self.keyboard_main
+        }
+    }
+
+    /// Whether the frontend is so far behind on frames that reading more
+    /// output should wait for it.
+    pub fn frontend_backlogged(&self) -> bool {
+        !self.read_pause_expired
+            && self
+                .acked_frame_seq
+                .is_some_and(|acked| self.frame_seq.saturating_sub(acked) > FRAME_ACK_PAUSE_GAP)
+    }
+
+    /// Record that the frontend has applied frames up to `frame_seq`.
+    pub fn ack_frame(&mut self, frame_seq: u64) {
+        let acked = self.acked_frame_seq.map_or(frame_seq, |prev| prev.max(frame_seq));
+        self.acked_frame_seq = Some(acked);
+        if self.keyframe_mode && self.frame_seq.saturating_sub(acked) <= FRAME_ACK_RESUME_GAP {
+            self.keyframe_mode = false;
+            self.read_pause_expired = false;
+            tracing::debug!("Frontend caught up at frame {}; resuming delta frames", acked);
+        }
+    }
+
+    /// Give up waiting for a frontend that stayed backlogged through a whole
+    /// read pause: read at full speed and send only keyframes until it
+    /// catches up.
+    pub fn expire_read_pause(&mut self) {
+        if !self.read_pause_expired {
+            tracing::debug!("Frontend stalled; reading on with keyframes only");
+        }
+        self.read_pause_expired = true;
+        self.keyframe_mode = true;
+    }
+
+    /// Replace the set of user-registered OSC numbers.
+    pub fn set_custom_osc_codes(&mut self, codes: Vec<u16>) {
+        self.custom_osc_codes = codes;
+    }
+
+    /// Replace the configured DA1/DA2 conformance level and features.
+    pub fn set_device_attributes(&mut self, attributes: DeviceAttributes) {
+        self.device_attributes = attributes;
+    }
+
+    /// Store decoded images in the asset registry from now on.
+    pub fn set_asset_sink(&mut self, sink: AssetSink) {
+        self.assets = Some(sink);
+    }
+
+    /// Record OSC 52 clipboard writes in `history` from now on.
+    pub fn set_clipboard_history(&mut self, history: clipboard::SharedHistory) {
+        self.clipboard_history = Some(history);
+    }
+
+    /// Set the rendered cell size in pixels; zero clears it.
+    pub fn set_cell_pixels(&mut self, width: u16, height: u16) {
+        self.cell_pixels = (width > 0 && height > 0).then_some((width, height));
+    }
+
+    /// XTWINOPS (`CSI Ps t`): size reports and the title stack. Window
+    /// manipulation (move, resize, raise) is ignored.
+    fn window_op(&mut self, raw: &[u16]) {
+        let response = match param(raw, 0, 0) {
+            14 => self.cell_pixels.map(|(w, h)| {
+                let height = h as u32 * self.rows as u32;
+                let width = w as u32 * self.cols as u32;
+                format!("\x1b[4;{};{}t", height, width)
+            }),
+            16 => self
+                .cell_pixels
+                .map(|(w, h)| format!("\x1b[6;{};{}t", h, w)),
+            18 => Some(format!("\x1b[8;{};{}t", self.rows, self.cols)),
+            // Rain has one title for both the window and the icon.
+            22 => {
+                if self.title_stack.len() == MAX_TITLE_STACK {
+                    self.title_stack.remove(0);
+                }
+                self.title_stack.push(self.title.clone());
+                None
+            }
+            23 => {
+                if let Some(title) = self.title_stack.pop() {
+                    if title != self.title {
+                        self.title = title;
+                        self.title_changed = true;
+                    }
+                }
+                None
+            }
+            _ => None,
+        };
+        if let Some(response) = response {
+            self.pending_responses.push(response.into_bytes());
+        }
+    }
+
+    /// Optional features currently implemented, as advertised in DA1.
+    fn device_capabilities(&self) -> Capabilities {
+        Capabilities {
+            sixel: self.experimental_image_protocols_enabled,
+        }
+    }
+
+    /// Record PTY output or user input for idle tracking.
+    pub fn touch_activity(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// Time since the last PTY output or user input.
+    pub fn idle_for(&self) -> Duration {
+        self.last_activity.elapsed()
+    }
+
+    /// Visible size as (rows, cols).
+    pub fn size(&self) -> (u16, u16) {
+        (self.rows, self.cols)
+    }
+
+    /// Whether the main grid's scrollback currently lives on disk.
+    pub fn is_hibernated(&self) -> bool {
+        self.grid.is_hibernated()
+    }
+
+    /// Move the main grid's scrollback to `path`. The alternate screen has
+    /// no scrollback, so only the main grid is affected.
+    pub fn hibernate(&mut self, path: PathBuf) -> std::io::Result<usize> {
+        self.grid.hibernate_scrollback(path)
+    }
+
+    /// Bring hibernated scrollback back into memory. Called before anything
+    /// that reads or mutates the grid so hibernation stays transparent.
+    pub fn ensure_resident(&mut self) {
+        if !self.grid.is_hibernated() {
+            return;
+        }
+        match self.grid.rehydrate_scrollback() {
+            Ok(rows) => tracing::debug!("Rehydrated {} scrollback rows", rows),
+            Err(e) => tracing::error!("Failed to rehydrate scrollback, history lost: {}", e),
+        }
+    }
+
+    /// `data_base64` and `url` of an image event: the decoded image goes to
+    /// the asset registry when the session has one.
+    fn image_payload(&self, data_base64: String) -> (String, Option<String>) {
+        let Some(sink) = &self.assets else {
+            return (data_base64, None);
+        };
+        match BASE64_STANDARD.decode(data_base64.trim()) {
+            Ok(bytes) => (String::new(), Some(sink.store(bytes))),
+            Err(_) => (data_base64, None),
         }
     }
 
@@ -145,6 +499,450 @@ impl TerminalState {
         std::mem::take(&mut self.pending_responses)
     }
 
+    /// The report for a mouse event under the current tracking modes, if
+    /// the program asked for it.
+    pub fn encode_mouse(&self, event: &MouseEvent) -> Option<Vec<u8>> {
+        mouse::encode(event, &self.modes)
+    }
+
+    /// Change the color filter and resend the viewport with it applied.
+    pub fn set_color_filter(&mut self, filter: ColorFilter) {
+        if self.color_filter != filter {
+            self.color_filter = filter;
+            self.active_grid_mut().mark_all_dirty();
+        }
+    }
+
+    /// Set the contrast text needs against its background (1 to 21, 1 for
+    /// off) and resend the viewport with it applied.
+    pub fn set_min_contrast(&mut self, ratio: f32) {
+        let ratio = ratio.clamp(contrast::MIN_CONTRAST_OFF, contrast::MAX_CONTRAST);
+        if self.min_contrast != ratio {
+            self.min_contrast = ratio;
+            self.active_grid_mut().mark_all_dirty();
+        }
+    }
+
+    /// Send bold text in ANSI colors 0-7 in the bright colors 8-15 (or stop)
+    /// and resend the viewport.
+    pub fn set_bold_is_bright(&mut self, bright: bool) {
+        if self.bold_is_bright != bright {
+            self.bold_is_bright = bright;
+            self.active_grid_mut().mark_all_dirty();
+        }
+    }
+
+    /// Answer color queries with a theme's colors and judge contrast by
+    /// them. `resolve` also sends its ANSI colors as RGB, for themes the
+    /// frontend has no palette for. Resends the viewport.
+    pub fn set_theme(&mut self, colors: &ThemeColors, resolve: bool) {
+        if self.theme != (*colors, resolve) {
+            self.theme = (*colors, resolve);
+            self.palette.set_theme(colors.ansi, resolve);
+            self.dynamic_colors.set_theme(colors);
+            self.active_grid_mut().mark_all_dirty();
+        }
+    }
+
+    /// Print ambiguous-width characters wide (or narrow again). Only output
+    /// printed from now on is affected.
+    pub fn set_ambiguous_wide(&mut self, wide: bool) {
+        self.ambiguous_wide = wide;
+    }
+
+    /// Keep at most `limit` rows of main-screen scrollback, up to
+    /// [`super::grid::MAX_SCROLLBACK_LIMIT`], dropping the oldest rows now
+    /// if there are more.
+    pub fn set_scrollback_limit(&mut self, limit: usize) -> ScrollbackUsage {
+        self.ensure_resident();
+        let dropped = self.grid.set_scrollback_limit(limit);
+        if dropped > 0 {
+            tracing::debug!("Dropped {} scrollback rows over the new limit", dropped);
+        }
+        self.scrollback_usage()
+    }
+
+    /// Keep main-screen scrollback within `max_bytes` of memory as well as
+    /// the row limit, dropping the oldest rows beyond it; `None` lifts it.
+    pub fn set_scrollback_max_bytes(&mut self, max_bytes: Option<usize>) -> ScrollbackUsage {
+        self.ensure_resident();
+        let dropped = self.grid.set_scrollback_max_bytes(max_bytes);
+        if dropped > 0 {
+            tracing::debug!("Dropped {} scrollback rows over the memory budget", dropped);
+        }
+        self.scrollback_usage()
+    }
+
+    /// Keep main-screen scrollback beyond the newest rows compressed, or
+    /// decompress it all again.
+    pub fn set_scrollback_compression(&mut self, compress: bool) -> ScrollbackUsage {
+        self.ensure_resident();
+        let dropped = self.grid.set_scrollback_compression(compress);
+        if dropped > 0 {
+            tracing::debug!("Dropped {} scrollback rows over the limits", dropped);
+        }
+        self.scrollback_usage()
+    }
+
+    pub fn scrollback_usage(&self) -> ScrollbackUsage {
+        ScrollbackUsage {
+            rows: self.grid.scrollback_len(),
+            limit: self.grid.scrollback_limit,
+            bytes: self.grid.memory_bytes(),
+            max_bytes: self.grid.scrollback_max_bytes,
+        }
+    }
+
+    /// Columns `c` takes, with ambiguous-width characters as configured.
+    fn char_width(&self, c: char) -> Option<usize> {
+        if self.ambiguous_wide {
+            UnicodeWidthChar::width_cjk(c)
+        } else {
+            UnicodeWidthChar::width(c)
+        }
+    }
+
+    /// Reset for a new shell started in this session after the previous one
+    /// exited. Unlike RIS the main screen and its scrollback stay: the
+    /// cursor moves below the last output and a marker line separates the
+    /// two shells.
+    pub fn restart(&mut self) {
+        self.ensure_resident();
+        self.exit_alt_screen();
+        let rows = self.rows;
+        let scrolled_off_buffer = std::mem::take(&mut self.scrolled_off_buffer);
+        let mut pending_terminal_events = std::mem::take(&mut self.pending_terminal_events);
+        let scrollback_seq = self.scrollback_seq;
+        let resize_epoch = self.resize_epoch;
+        let acked_frame_seq = self.acked_frame_seq;
+        let keyframe_mode = self.keyframe_mode;
+        let read_pause_expired = self.read_pause_expired;
+        self.preserve_session_settings(|grid| grid);
+        self.scrolled_off_buffer = scrolled_off_buffer;
+        pending_terminal_events.append(&mut self.pending_terminal_events);
+        self.pending_terminal_events = pending_terminal_events;
+        self.scrollback_seq = scrollback_seq;
+        self.resize_epoch = resize_epoch;
+        self.acked_frame_seq = acked_frame_seq;
+        self.keyframe_mode = keyframe_mode;
+        self.read_pause_expired = read_pause_expired;
+
+        let last_output = (0..rows).rev().find(|&row| {
+            let row = self.grid.visible_row(row);
+            row.cells.iter().any(|cell| cell.c != ' ')
+        });
+        let mut marker = Vec::new();
+        if let Some(row) = last_output {
+            self.cursor.row = row;
+            marker.extend_from_slice(b"\r\n");
+        }
+        marker.extend_from_slice(RESTART_MARKER.as_bytes());
+        marker.extend_from_slice(b"\r\n");
+        self.feed(&marker);
+    }
+
+    /// Blank state, sized like this one and with its session settings, for
+    /// parser threads that take over from stuck ones. Line IDs keep counting.
+    pub fn take_for_recovery(&mut self) -> TerminalState {
+        let (rows, cols) = (self.rows, self.cols);
+        let mut next = std::mem::replace(self, TerminalState::new(rows, cols));
+        next.preserve_session_settings(|old| {
+            Grid::with_first_line_id(rows, cols, old.next_line_id())
+        });
+        next
+    }
+
+    /// Start over from a blank state on the grid `next_grid` makes of the
+    /// old main grid, keeping what belongs to the session rather than to the
+    /// program in it: frame numbering, user settings such as the theme,
+    /// color filter and scrollback limits, the asset sink and the selection.
+    /// Palette, colors and progress the program changed are reset, and the
+    /// frontend is told. Shared by RIS and [`Self::restart`].
+    fn preserve_session_settings(&mut self, next_grid: impl FnOnce(Grid) -> Grid) {
+        let (rows, cols) = (self.rows, self.cols);
+        let frame_seq = self.frame_seq;
+        let custom_osc_codes = std::mem::take(&mut self.custom_osc_codes);
+        let device_attributes = std::mem::take(&mut self.device_attributes);
+        let assets = self.assets.take();
+        let clipboard_history = self.clipboard_history.take();
+        let color_filter = self.color_filter;
+        let min_contrast = self.min_contrast;
+        let bold_is_bright = self.bold_is_bright;
+        let ambiguous_wide = self.ambiguous_wide;
+        let file_downloads_enabled = self.file_downloads_enabled;
+        let theme = self.theme;
+        // Dropped by the next frame's check if it no longer fits.
+        let selection = self.selection.take();
+        let scrollback_limit = self.grid.scrollback_limit;
+        let scrollback_max_bytes = self.grid.scrollback_max_bytes;
+        let compress_scrollback = self.grid.compress_scrollback;
+        let cell_pixels = self.cell_pixels;
+        let palette_was_set = !self.palette.is_default();
+        let colors_were_set = !self.dynamic_colors.is_default();
+        let progress_was_shown = self.progress.0 != ProgressState::None;
+        let grid = std::mem::replace(&mut self.grid, Grid::new(1, 1));
+
+        *self = TerminalState::new(rows, cols);
+        self.grid = next_grid(grid);
+        self.grid.scrollback_limit = scrollback_limit;
+        self.grid.scrollback_max_bytes = scrollback_max_bytes;
+        self.grid.compress_scrollback = compress_scrollback;
+        self.frame_seq = frame_seq;
+        self.custom_osc_codes = custom_osc_codes;
+        self.device_attributes = device_attributes;
+        self.assets = assets;
+        self.clipboard_history = clipboard_history;
+        self.color_filter = color_filter;
+        self.min_contrast = min_contrast;
+        self.bold_is_bright = bold_is_bright;
+        self.ambiguous_wide = ambiguous_wide;
+        self.file_downloads_enabled = file_downloads_enabled;
+        self.set_theme(&theme.0, theme.1);
+        self.selection = selection;
+        self.cell_pixels = cell_pixels;
+        self.grid.mark_all_dirty();
+        if palette_was_set {
+            self.pending_terminal_events
+                .push(TerminalEvent::PaletteChanged { colors: Vec::new() });
+        }
+        if colors_were_set {
+            self.dynamic_colors_changed();
+        }
+        if progress_was_shown {
+            self.pending_terminal_events.push(TerminalEvent::ProgressChanged {
+                state: ProgressState::None,
+                value: 0,
+            });
+        }
+    }
+
+    /// Process PTY output. APC strings are handled here; everything else
+    /// goes through `parser`.
+    pub fn advance(&mut self, parser: &mut vte::Parser, bytes: &[u8]) {
+        for &b in bytes {
+            match self.apc_scanner.feed(b) {
+                Feed::Pass => parser.advance(self, b),
+                Feed::Held => {}
+                Feed::ReleaseEsc => parser.advance(self, 0x1b),
+                Feed::ReleaseEscAndPass => {
+                    parser.advance(self, 0x1b);
+                    parser.advance(self, b);
+                }
+                Feed::Apc(data) => self.handle_apc(&data),
+            }
+        }
+        let pending = self.scrolled_off_buffer.len();
+        if pending > MAX_PENDING_SCROLLED_LINES {
+            self.scrolled_off_buffer
+                .drain(..pending - MAX_PENDING_SCROLLED_LINES);
+        }
+        if self.shell.awaiting_remote_prompt() && !self.using_alt {
+            self.detect_remote_prompt();
+        }
+    }
+
+    /// Offer hooks for a remote shell once its prompt is at the cursor.
+    fn detect_remote_prompt(&mut self) {
+        let row = self.grid.visible_row(self.cursor.row);
+        let end = (self.cursor.col as usize).min(row.cells.len());
+        let line: String = row.cells[..end].iter().flat_map(Cell::chars).collect();
+        if remote::looks_like_prompt(&line) {
+            self.shell.remote_prompt_seen();
+        }
+    }
+
+    /// Run bytes through a fresh parser, as if the PTY had printed them.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        let mut parser = vte::Parser::new();
+        self.advance(&mut parser, bytes);
+    }
+
+    fn handle_apc(&mut self, data: &[u8]) {
+        if !data.starts_with(b"G") {
+            return;
+        }
+        if !self.experimental_image_protocols_enabled {
+            if !self.image_protocol_drop_notified {
+                tracing::info!("Kitty graphics received but experimental rendering is disabled");
+                self.image_protocol_drop_notified = true;
+            }
+            return;
+        }
+        let outcome = self.kitty_graphics.handle(data);
+        if let Some(response) = outcome.response {
+            self.pending_responses.push(response);
+        }
+        match outcome.effect {
+            Some(Effect::Place {
+                image_id,
+                placement_id,
+                width,
+                height,
+                png_base64,
+                columns,
+                rows,
+                move_cursor,
+            }) => {
+                let (data_base64, url) = self.image_payload(png_base64);
+                self.pending_terminal_events.push(TerminalEvent::KittyImage {
+                    id: format!("kitty-{}-{}", image_id, placement_id),
+                    action: "place".to_string(),
+                    data_base64,
+                    url,
+                    width,
+                    height,
+                    row: self.cursor.row,
+                    col: self.cursor.col,
+                    line_id: self.cursor_line_id(),
+                    image_id,
+                    placement_id,
+                });
+                // Cursor ends after the image on its last row. The cell size
+                // is only known when the program gave one.
+                if move_cursor && rows > 0 {
+                    for _ in 1..rows {
+                        self.linefeed();
+                    }
+                    let col = self.cursor.col as u32 + columns;
+                    self.cursor.col = col.min(self.cols.saturating_sub(1) as u32) as u16;
+                }
+            }
+            Some(Effect::Delete {
+                image_id,
+                placement_id,
+            }) => {
+                self.pending_terminal_events.push(TerminalEvent::KittyImage {
+                    id: format!("kitty-{}-{}", image_id, placement_id),
+                    action: "delete".to_string(),
+                    data_base64: String::new(),
+                    url: None,
+                    width: 0,
+                    height: 0,
+                    row: self.cursor.row,
+                    col: self.cursor.col,
+                    line_id: self.cursor_line_id(),
+                    image_id,
+                    placement_id,
+                });
+            }
+            None => {}
+        }
+    }
+
+    /// Show a decoded Sixel image at the cursor. As in xterm, the cursor
+    /// then sits on the last text row the image covers, in the same column.
+    fn place_sixel(&mut self, image: SixelImage) {
+        let (cell_width, cell_height) = self.cell_pixels.unwrap_or(FALLBACK_CELL_PIXELS);
+        let columns = image.width.div_ceil(u32::from(cell_width));
+        let rows = image.height.div_ceil(u32::from(cell_height));
+        let png = kitty::encode_png(image.width, image.height, 4, &image.rgba);
+        self.image_counter += 1;
+        let (data_base64, url) = match &self.assets {
+            Some(sink) => (String::new(), Some(sink.store(png))),
+            None => (BASE64_STANDARD.encode(&png), None),
+        };
+        self.pending_terminal_events
+            .push(TerminalEvent::SixelImage {
+                id: format!("sixel-{}", self.image_counter),
+                data_base64,
+                url,
+                width: image.width,
+                height: image.height,
+                columns: columns.min(u32::from(u16::MAX)) as u16,
+                rows: rows.min(u32::from(u16::MAX)) as u16,
+                row: self.cursor.row,
+                col: self.cursor.col,
+                line_id: self.cursor_line_id(),
+            });
+        for _ in 1..rows.min(u32::from(self.rows)) {
+            self.linefeed();
+        }
+    }
+
+    /// Show an inline iTerm2 file at the cursor, which then moves past it as
+    /// for kitty images, or offer any other file as a download.
+    fn receive_file(&mut self, file: iterm::File) {
+        self.image_counter += 1;
+        if !file.args.inline {
+            if !self.file_downloads_enabled {
+                tracing::debug!("Dropping a file sent for download; downloads are off");
+                return;
+            }
+            let id = format!("download-{}", self.image_counter);
+            self.pending_terminal_events
+                .push(TerminalEvent::FileDownload {
+                    id: id.clone(),
+                    name: file.args.name.clone(),
+                    size: file.data.len() as u64,
+                });
+            if self.pending_downloads.len() >= MAX_PENDING_DOWNLOADS {
+                self.pending_downloads.remove(0);
+            }
+            self.pending_downloads.push((id, file));
+            return;
+        }
+        if !self.experimental_image_protocols_enabled {
+            if !self.image_protocol_drop_notified {
+                tracing::info!(
+                    "Image protocol payload received but experimental rendering is disabled"
+                );
+                self.image_protocol_drop_notified = true;
+            }
+            return;
+        }
+        let (cell_width, cell_height) = self.cell_pixels.unwrap_or(FALLBACK_CELL_PIXELS);
+        let cell = (u32::from(cell_width), u32::from(cell_height));
+        let (width, height) =
+            file.args
+                .display_size(iterm::image_size(&file.data), cell, (self.cols, self.rows));
+        let columns = width.div_ceil(cell.0).min(u32::from(self.cols)) as u16;
+        let rows = height.div_ceil(cell.1).min(u32::from(u16::MAX)) as u16;
+        let (data_base64, url) = match &self.assets {
+            Some(sink) => (String::new(), Some(sink.store(file.data))),
+            None => (BASE64_STANDARD.encode(&file.data), None),
+        };
+        self.pending_terminal_events
+            .push(TerminalEvent::InlineImage {
+                id: format!("img-{}", self.image_counter),
+                data_base64,
+                url,
+                name: file.args.name,
+                width,
+                height,
+                columns,
+                rows,
+                preserve_aspect_ratio: file.args.preserve_aspect_ratio,
+                row: self.cursor.row,
+                col: self.cursor.col,
+                line_id: self.cursor_line_id(),
+            });
+        for _ in 1..rows.min(self.rows) {
+            self.linefeed();
+        }
+        self.cursor.col = (self.cursor.col + columns).min(self.cols.saturating_sub(1));
+    }
+
+    /// Offer files programs send (iTerm2 OSC 1337 without `inline=1`) as
+    /// downloads, or drop them. Turning this off drops the files already
+    /// offered too.
+    pub fn set_file_downloads(&mut self, enabled: bool) {
+        self.file_downloads_enabled = enabled;
+        if !enabled {
+            self.pending_downloads.clear();
+        }
+    }
+
+    /// Take a file offered by a `FileDownload` event, to save it.
+    pub fn take_download(&mut self, id: &str) -> Option<iterm::File> {
+        let idx = self.pending_downloads.iter().position(|(d, _)| d == id)?;
+        Some(self.pending_downloads.remove(idx).1)
+    }
+
+    /// Whether OSC 1337 / Sixel images are decoded.
+    pub fn image_protocols_enabled(&self) -> bool {
+        self.experimental_image_protocols_enabled
+    }
+
     /// Current frame sequence number.
     pub fn frame_seq(&self) -> u64 {
         self.frame_seq
@@ -156,6 +954,26 @@ impl TerminalState {
     }
 
     pub fn resize(&mut self, rows: u16, cols: u16) {
+        // Re-wrap the main grid's lines to the new width. That changes how
+        // many rows the whole history takes, so global rows are renumbered
+        // from the first one held and the frontend refetches its scrollback
+        // instead of appending to rows wrapped at the old width.
+        if cols != self.cols {
+            self.ensure_resident();
+            let first_global = self
+                .scrollback_seq
+                .saturating_sub(self.grid.scrollback_len() as u64);
+            let cursor = (!self.using_alt).then_some((self.cursor.row, self.cursor.col));
+            if let Some((row, col)) = self.grid.reflow(cols, cursor) {
+                self.cursor.row = row;
+                self.cursor.col = col;
+            }
+            self.scrollback_seq = first_global + self.grid.scrollback_len() as u64;
+            self.scrolled_off_buffer.clear();
+            self.pending_terminal_events
+                .push(TerminalEvent::ScrollbackRewrapped);
+        }
+
         // Main grid shrink moves top visible rows into scrollback. Capture those
         // rows explicitly so the frontend scrollback stays in sync with global
         // row accounting used for block slicing.
@@ -171,8 +989,13 @@ impl TerminalState {
                 let idx = visible_offset + i;
                 if idx < self.grid.rows.len() {
                     let spans = self.grid.rows[idx].to_styled_spans();
-                    self.scrolled_off_buffer
-                        .push(RenderedLine { index: 0, spans });
+                    self.scrolled_off_buffer.push(RenderedLine {
+                        index: 0,
+                        line_id: self.grid.rows[idx].line_id,
+                        line_size: self.grid.rows[idx].line_size,
+                        start_col: None,
+                        spans,
+                    });
                     self.scrollback_seq = self.scrollback_seq.saturating_add(1);
                 }
             }
@@ -187,6 +1010,7 @@ impl TerminalState {
         self.cols = cols;
         self.scroll_top = 0;
         self.scroll_bottom = rows.saturating_sub(1);
+        self.reset_lr_margins();
         self.tab_stops = vec![false; cols as usize];
         for i in (0..cols as usize).step_by(8) {
             self.tab_stops[i] = true;
@@ -196,9 +1020,39 @@ impl TerminalState {
         self.resize_epoch = self.resize_epoch.saturating_add(1);
     }
 
+    /// Time left before the synchronized update in progress stops holding
+    /// frames back; `None` when no update holds them.
+    pub fn synchronized_update_remaining(&self) -> Option<Duration> {
+        let started = self.sync_update_started?;
+        SYNC_UPDATE_TIMEOUT
+            .checked_sub(started.elapsed())
+            .filter(|left| !left.is_zero())
+    }
+
+    /// Stop holding frames for the synchronized update in progress, so a
+    /// session that exits mid-update still shows its last screen.
+    pub fn release_synchronized_update(&mut self) {
+        self.sync_update_started = None;
+    }
+
     /// Extract a render snapshot from current terminal state.
-    /// Returns None if there are no dirty lines/events/scrolled lines.
+    /// Returns None if there are no dirty lines/events/scrolled lines, or
+    /// while a synchronized update is in progress.
     pub fn take_render_snapshot(&mut self) -> Option<RenderSnapshot> {
+        if self.synchronized_update_remaining().is_some() {
+            return None;
+        }
+        if let Some(acked) = self.acked_frame_seq {
+            if !self.keyframe_mode && self.frame_seq.saturating_sub(acked) > FRAME_ACK_KEYFRAME_GAP {
+                self.keyframe_mode = true;
+                tracing::debug!(
+                    "Frontend is {} frames behind; switching to keyframes",
+                    self.frame_seq - acked
+                );
+            }
+        }
+
+        self.check_selection();
         let grid = if self.using_alt {
             self.alt_grid.as_mut()?
         } else {
@@ -209,6 +1063,7 @@ impl TerminalState {
         let visible_cols = grid.cols;
         let dirty_lines: Vec<RenderedLine> = grid.collect_dirty_lines();
         let scrolled_lines = std::mem::take(&mut self.scrolled_off_buffer);
+        self.shell.poll_session_name();
         let events = self.shell.take_pending_events();
 
         let mut all_events = events;
@@ -223,16 +1078,89 @@ impl TerminalState {
             all_events.push(TerminalEvent::Bell);
             self.bell_pending = false;
         }
+        let coalesced = coalesce_events(&mut all_events);
+        if coalesced > 0 {
+            self.coalesced_events += coalesced as u64;
+            tracing::trace!(
+                "Coalesced {} title/cwd events ({} in total)",
+                coalesced,
+                self.coalesced_events
+            );
+        }
 
         let cursor_visible = self.cursor.visible && self.modes.cursor_visible;
         let current_cursor = (self.cursor.row, self.cursor.col, cursor_visible, self.cursor.shape);
         let cursor_changed = current_cursor != self.last_emitted_cursor;
 
-        if dirty_lines.is_empty() && all_events.is_empty() && scrolled_lines.is_empty() && !cursor_changed {
+        let keyboard_flags = self.keyboard_flags();
+        let keyboard_changed = keyboard_flags != self.last_emitted_keyboard_flags;
+        let reverse_video = self.modes.reverse_video;
+        let reverse_video_changed = reverse_video != self.last_emitted_reverse_video;
+
+        if dirty_lines.is_empty()
+            && all_events.is_empty()
+            && scrolled_lines.is_empty()
+            && !cursor_changed
+            && !keyboard_changed
+            && !reverse_video_changed
+        {
             return None;
         }
 
         self.last_emitted_cursor = current_cursor;
+        self.last_emitted_keyboard_flags = keyboard_flags;
+        self.last_emitted_reverse_video = reverse_video;
+
+        // Keyframe: resend the whole viewport so this frame stands on its own.
+        let keyframe = self.keyframe_mode;
+        let dirty_lines = if keyframe {
+            let grid = self.active_grid_mut();
+            grid.mark_all_dirty();
+            grid.collect_dirty_lines()
+        } else {
+            dirty_lines
+        };
+
+        let mut dirty_lines = dirty_lines;
+        let mut scrolled_lines = scrolled_lines;
+        // Before the palette, so a program's own bright colors apply.
+        if self.bold_is_bright {
+            for span in dirty_lines
+                .iter_mut()
+                .chain(scrolled_lines.iter_mut())
+                .flat_map(|line| line.spans.iter_mut())
+            {
+                span.brighten_bold();
+            }
+        }
+        if self.palette.resolves() {
+            for span in dirty_lines
+                .iter_mut()
+                .chain(scrolled_lines.iter_mut())
+                .flat_map(|line| line.spans.iter_mut())
+            {
+                self.palette.resolve(&mut span.fg);
+                self.palette.resolve(&mut span.bg);
+                if let Some(color) = span.underline_color.as_mut() {
+                    self.palette.resolve(color);
+                }
+            }
+        }
+        if !self.color_filter.is_none() {
+            for line in dirty_lines.iter_mut().chain(scrolled_lines.iter_mut()) {
+                self.color_filter.apply(&mut line.spans);
+            }
+        }
+        if self.min_contrast > contrast::MIN_CONTRAST_OFF {
+            for line in dirty_lines.iter_mut().chain(scrolled_lines.iter_mut()) {
+                contrast::apply(
+                    &mut line.spans,
+                    self.min_contrast,
+                    &self.palette,
+                    &self.dynamic_colors,
+                );
+            }
+        }
 
         let shape_str = match self.cursor.shape {
             CursorShape::Block => "block",
@@ -247,6 +1175,13 @@ impl TerminalState {
         };
         self.frame_seq = self.frame_seq.saturating_add(1);
         let frame_seq = self.frame_seq;
+        let visible_search = if dirty_lines.is_empty() && scrolled_lines.is_empty() {
+            None
+        } else {
+            self.visible_search
+                .as_ref()
+                .map(|pattern| self.find_visible(pattern))
+        };
 
         Some(RenderSnapshot {
             frame_seq,
@@ -263,74 +1198,477 @@ impl TerminalState {
                 shape: shape_str.to_string(),
             },
             events: all_events,
+            keyframe,
+            keyboard_flags,
+            reverse_video,
+            visible_search,
         })
     }
 
-    // Helper: get the active grid mutably. Callers must copy any self.* values
-    // they need BEFORE calling this, because it borrows &mut self.
-    fn active_grid_mut(&mut self) -> &mut Grid {
-        if self.using_alt {
-            self.alt_grid.as_mut().unwrap()
+    /// Search the active screen for `pattern` (an empty pattern ends the
+    /// search) and keep reporting matches in frames until it is replaced.
+    pub fn set_visible_search(&mut self, pattern: &str) -> Vec<CellRect> {
+        self.visible_search = Pattern::new(pattern);
+        self.visible_search
+            .as_ref()
+            .map(|pattern| self.find_visible(pattern))
+            .unwrap_or_default()
+    }
+
+    fn find_visible(&self, pattern: &Pattern) -> Vec<CellRect> {
+        let grid = if self.using_alt {
+            self.alt_grid.as_ref().unwrap_or(&self.grid)
         } else {
-            &mut self.grid
-        }
+            &self.grid
+        };
+        (0..grid.visible_rows)
+            .flat_map(|row| pattern.find_in_row(grid.visible_row(row), row))
+            .collect()
     }
 
-    fn linefeed(&mut self) {
-        if self.cursor.row == self.scroll_bottom {
-            let top = self.scroll_top;
-            let bottom = self.scroll_bottom;
-            if let Some(scrolled) = self.active_grid_mut().scroll_up(top, bottom) {
-                // Don't capture scrolled lines in alt screen mode (vim, less, etc.)
-                if !self.using_alt {
-                    self.scrolled_off_buffer.push(scrolled);
-                    self.scrollback_seq = self.scrollback_seq.saturating_add(1);
+    /// Matches of `query` in the main grid, scrollback included, oldest
+    /// first. Rows already trimmed from scrollback are not searched.
+    pub fn search_scrollback(&mut self, query: &TextQuery) -> Vec<ScrollbackMatch> {
+        self.ensure_resident();
+        let first_global = self
+            .scrollback_seq
+            .saturating_sub(self.grid.scrollback_len() as u64);
+        let mut matches = Vec::new();
+        self.grid.visit_history(|index, row| {
+            let found = query.find_in_row(row);
+            if found.is_empty() {
+                return true;
+            }
+            let context: String = row
+                .cells
+                .iter()
+                .filter(|c| !c.flags.contains(CellFlags::WIDE_SPACER))
+                .flat_map(Cell::chars)
+                .collect();
+            let context = context.trim_end();
+            for (col, cols) in found {
+                if matches.len() == MAX_SCROLLBACK_MATCHES {
+                    return false;
                 }
+                matches.push(ScrollbackMatch {
+                    global_row: first_global + index as u64,
+                    line_id: row.line_id,
+                    col,
+                    cols,
+                    context: context.to_string(),
+                });
             }
-        } else if self.cursor.row < self.rows.saturating_sub(1) {
-            self.cursor.row += 1;
+            true
+        });
+        matches
+    }
+
+    /// Up to `count` scrollback rows from global row `start_global_row`
+    /// on, capped at [`MAX_SCROLLBACK_PAGE`]. Rows already trimmed are
+    /// skipped and the page never reaches into the visible screen.
+    pub fn scrollback_range(&mut self, start_global_row: u64, count: usize) -> ScrollbackPage {
+        self.ensure_resident();
+        let first = self
+            .scrollback_seq
+            .saturating_sub(self.grid.scrollback_len() as u64);
+        let start = start_global_row.clamp(first, self.scrollback_seq);
+        let end = start_global_row
+            .saturating_add(count.min(MAX_SCROLLBACK_PAGE) as u64)
+            .clamp(start, self.scrollback_seq);
+        let lines = self
+            .grid
+            .history_rows((start - first) as usize..(end - first) as usize)
+            .iter()
+            .enumerate()
+            .map(|(i, row)| RenderedLine {
+                index: i as u32,
+                line_id: row.line_id,
+                line_size: row.line_size,
+                start_col: None,
+                spans: row.to_styled_spans(),
+            })
+            .collect();
+        ScrollbackPage {
+            start_global_row: start,
+            first_global_row: first,
+            visible_base_global: self.scrollback_seq,
+            lines,
         }
     }
 
-    fn global_row(&self) -> u64 {
-        self.scrollback_seq + self.cursor.row as u64
+    /// Start a selection of the active screen at `anchor`, widened per
+    /// `mode`. `None` when the line is no longer resident.
+    pub fn set_selection(
+        &mut self,
+        mode: SelectionMode,
+        anchor: SelectionPoint,
+    ) -> Option<SelectionRange> {
+        self.ensure_resident();
+        self.selection =
+            TextSelection::new(self.active_grid(), mode, anchor, anchor, self.using_alt);
+        self.selection_range()
     }
 
-    fn reverse_index(&mut self) {
-        if self.cursor.row == self.scroll_top {
-            let top = self.scroll_top;
-            let bottom = self.scroll_bottom;
-            self.active_grid_mut().scroll_down(top, bottom);
-        } else if self.cursor.row > 0 {
-            self.cursor.row -= 1;
-        }
+    /// Move the free end of the selection to `head`.
+    pub fn extend_selection(&mut self, head: SelectionPoint) -> Option<SelectionRange> {
+        self.ensure_resident();
+        let current = self.selection.as_ref()?;
+        let (mode, anchor) = (current.mode, current.anchor);
+        self.selection = TextSelection::new(self.active_grid(), mode, anchor, head, self.using_alt);
+        self.selection_range()
     }
 
-    fn carriage_return(&mut self) {
-        self.cursor.col = 0;
+    pub fn clear_selection(&mut self) {
+        self.selection = None;
     }
 
-    fn backspace(&mut self) {
-        if self.cursor.col > 0 {
-            self.cursor.col -= 1;
-        }
+    pub fn selection_range(&self) -> Option<SelectionRange> {
+        self.selection.as_ref()?.range(self.active_grid())
     }
 
-    fn tab(&mut self) {
-        let col = self.cursor.col as usize + 1;
-        for i in col..self.cols as usize {
-            if self.tab_stops.get(i).copied().unwrap_or(false) {
-                self.cursor.col = i as u16;
-                return;
-            }
+    /// The selected text, plain or rendered with its styles.
+    pub fn selection_text(&mut self, format: ExportFormat) -> Option<String> {
+        self.ensure_resident();
+        let selection = self.selection.as_ref()?;
+        let grid = self.active_grid();
+        if format == ExportFormat::Text {
+            return selection.text(grid);
         }
-        self.cursor.col = self.cols.saturating_sub(1);
+        let rows = selection.rows(grid)?;
+        let rows: Vec<&Row> = rows.iter().collect();
+        Some(format.render(&rows, "Selection"))
     }
 
-    fn cursor_up(&mut self, n: u16) {
-        let min_row = if self.cursor.row >= self.scroll_top && self.cursor.row <= self.scroll_bottom
-        {
-            self.scroll_top
+    /// Drop a selection whose text changed or left the grid, and tell the
+    /// frontend. Hibernated scrollback is left alone until it is back.
+    fn check_selection(&mut self) {
+        let Some(selection) = &self.selection else {
+            return;
+        };
+        if self.grid.is_hibernated() {
+            return;
+        }
+        if selection.alt_screen != self.using_alt || !selection.is_intact(self.active_grid()) {
+            self.selection = None;
+            self.pending_terminal_events
+                .push(TerminalEvent::SelectionCleared);
+        }
+    }
+
+    /// Write `c` at the cursor, wrapping first if the cursor is past the
+    /// right edge, and advance the cursor.
+    fn put_char(&mut self, c: char) {
+        let width = self.char_width(c).unwrap_or(1) as u16;
+
+        let line_cols = self.cursor_line_cols();
+        let edge =
+            if self.has_lr_margins() && (self.margin_wrap_pending || self.cursor_in_lr_margins()) {
+                (self.scroll_right + 1).min(line_cols)
+            } else {
+                line_cols
+            };
+        self.margin_wrap_pending = false;
+        if self.cursor.col >= edge {
+            if self.modes.autowrap {
+                if edge == self.cols {
+                    // Remembered so a resize can re-wrap the line.
+                    let row = self.cursor.row;
+                    self.active_grid_mut()
+                        .visible_row_mut(row)
+                        .set_wrapped(true);
+                }
+                self.carriage_return();
+                self.linefeed();
+            } else {
+                self.cursor.col = edge.saturating_sub(1);
+            }
+        }
+        let wraps_at_margin = self.has_lr_margins() && self.cursor_in_lr_margins();
+
+        if self.modes.insert {
+            let row = self.cursor.row;
+            let col = self.cursor.col;
+            let end = self.shift_end();
+            self.active_grid_mut().insert_cells(row, col, width, end);
+        }
+
+        let row = self.cursor.row;
+        let col = self.cursor.col;
+        let fg = self.cursor.fg;
+        let bg = self.cursor.bg;
+        let underline_color = self.cursor.underline_color;
+        let attrs = self.cursor.attrs;
+        let cols = self.cols;
+        let protected = if self.cursor.protected {
+            CellFlags::PROTECTED
+        } else {
+            CellFlags::empty()
+        };
+
+        let cell = Cell {
+            c,
+            fg,
+            bg,
+            underline_color,
+            attrs,
+            flags: if width == 2 {
+                CellFlags::WIDE_CHAR | protected
+            } else {
+                protected
+            },
+            combining: None,
+        };
+
+        let grid = self.active_grid_mut();
+        grid.set_cell(row, col, cell);
+
+        if width == 2 && col + 1 < cols {
+            let mut spacer = Cell::wide_spacer();
+            spacer.flags |= protected;
+            grid.set_cell(row, col + 1, spacer);
+        }
+
+        self.cursor.col += width;
+        self.margin_wrap_pending = wraps_at_margin && self.cursor.col > self.scroll_right;
+    }
+
+    /// Add `c` to the grapheme cluster in the cell before the cursor when
+    /// the two form one cluster (combining accents, ZWJ emoji sequences,
+    /// variation selectors, flags), so a cluster takes one cell. An emoji
+    /// presentation selector or a completed flag makes the cell wide.
+    fn extend_cluster(&mut self, c: char) -> bool {
+        // Only characters from the combining marks up can extend a cluster.
+        if (c as u32) < 0x300 {
+            return false;
+        }
+        let Some(mut col) = self.cursor.col.checked_sub(1) else {
+            return false;
+        };
+        let zero_width = self.char_width(c) == Some(0);
+        let line_cols = self.cursor_line_cols();
+        let row = self.cursor.row;
+        let line = self.active_grid_mut().visible_row_mut(row);
+        let Some(cell) = line.cells.get(col as usize) else {
+            return false;
+        };
+        if cell.flags.contains(CellFlags::WIDE_SPACER) {
+            if col == 0 {
+                return false;
+            }
+            col -= 1;
+        }
+        let cell = &mut line.cells[col as usize];
+        // Of the characters that take columns of their own, only those
+        // completing an emoji join, so that spacing marks keep the widths
+        // programs count with wcwidth.
+        let widens = c == '\u{fe0f}' || ('\u{1f1e6}'..='\u{1f1ff}').contains(&c);
+        let joins = zero_width
+            || widens
+            || ('\u{1f3fb}'..='\u{1f3ff}').contains(&c)
+            || cell
+                .combining
+                .as_deref()
+                .is_some_and(|rest| rest.ends_with('\u{200d}'));
+        if !joins {
+            return false;
+        }
+        let mut cluster: String = cell.chars().collect();
+        cluster.push(c);
+        if cluster.graphemes(true).nth(1).is_some() {
+            return false;
+        }
+        cell.combining = Some(cluster[cell.c.len_utf8()..].into());
+
+        let widened = widens && !cell.flags.contains(CellFlags::WIDE_CHAR) && col + 1 < line_cols;
+        if widened {
+            cell.flags |= CellFlags::WIDE_CHAR;
+            line.cells[col as usize + 1] = Cell::wide_spacer();
+        }
+        line.mark_damaged(col, col + 1 + u16::from(widened));
+        if widened && self.cursor.col == col + 1 {
+            self.cursor.col += 1;
+        }
+        true
+    }
+
+    fn active_grid(&self) -> &Grid {
+        if self.using_alt {
+            self.alt_grid.as_ref().unwrap_or(&self.grid)
+        } else {
+            &self.grid
+        }
+    }
+
+    // Helper: get the active grid mutably. Callers must copy any self.* values
+    // they need BEFORE calling this, because it borrows &mut self.
+    fn active_grid_mut(&mut self) -> &mut Grid {
+        if self.using_alt {
+            self.alt_grid.as_mut().unwrap()
+        } else {
+            &mut self.grid
+        }
+    }
+
+    /// Columns usable on the cursor's row: half the width on double-size
+    /// lines.
+    fn cursor_line_cols(&mut self) -> u16 {
+        let row = self.cursor.row;
+        let cols = self.cols;
+        self.active_grid_mut()
+            .visible_row_mut(row)
+            .line_size
+            .usable_cols(cols)
+    }
+
+    /// DECSWL/DECDWL/DECDHL: set the cursor row's line size.
+    fn set_line_size(&mut self, size: LineSize) {
+        let row = self.cursor.row;
+        let line = self.active_grid_mut().visible_row_mut(row);
+        if line.line_size != size {
+            line.line_size = size;
+            line.dirty = true;
+        }
+        let max_col = self.cursor_line_cols().saturating_sub(1);
+        self.cursor.col = self.cursor.col.min(max_col);
+    }
+
+    /// Whether DECSLRM margins narrower than the screen are in effect.
+    fn has_lr_margins(&self) -> bool {
+        self.modes.left_right_margin
+            && (self.scroll_left > 0 || self.scroll_right < self.cols.saturating_sub(1))
+    }
+
+    fn cursor_in_lr_margins(&self) -> bool {
+        (self.scroll_left..=self.scroll_right).contains(&self.cursor.col)
+    }
+
+    fn reset_lr_margins(&mut self) {
+        self.scroll_left = 0;
+        self.scroll_right = self.cols.saturating_sub(1);
+        self.margin_wrap_pending = false;
+    }
+
+    /// DECSLRM (`CSI Pl ; Pr s`, only while DECLRMM is set). Homes the
+    /// cursor like DECSTBM.
+    fn set_lr_margins(&mut self, raw: &[u16]) {
+        let left = param(raw, 0, 1) - 1;
+        let right = param(raw, 1, self.cols).min(self.cols) - 1;
+        if left >= right {
+            return;
+        }
+        self.scroll_left = left;
+        self.scroll_right = right;
+        if self.modes.origin {
+            self.cursor.row = self.scroll_top;
+            self.cursor.col = self.scroll_left;
+        } else {
+            self.cursor.row = 0;
+            self.cursor.col = 0;
+        }
+    }
+
+    /// Column for CUP/CHA: relative to the left margin in origin mode.
+    fn origin_col(&self, col: u16) -> u16 {
+        if self.modes.origin {
+            (self.scroll_left + col).min(self.scroll_right)
+        } else {
+            col.min(self.cols.saturating_sub(1))
+        }
+    }
+
+    /// Scroll rows [top, bottom] up one line, only between the left and
+    /// right margins when those are set.
+    fn scroll_region_up(&mut self, top: u16, bottom: u16) {
+        if self.has_lr_margins() {
+            let (left, right) = (self.scroll_left, self.scroll_right);
+            self.active_grid_mut()
+                .scroll_rect_up(top, bottom, left, right);
+            return;
+        }
+        if let Some(scrolled) = self.active_grid_mut().scroll_up(top, bottom) {
+            // Don't capture scrolled lines in alt screen mode (vim, less, etc.)
+            if !self.using_alt {
+                self.scrolled_off_buffer.push(scrolled);
+                self.scrollback_seq = self.scrollback_seq.saturating_add(1);
+            }
+        }
+    }
+
+    fn scroll_region_down(&mut self, top: u16, bottom: u16) {
+        if self.has_lr_margins() {
+            let (left, right) = (self.scroll_left, self.scroll_right);
+            self.active_grid_mut()
+                .scroll_rect_down(top, bottom, left, right);
+        } else {
+            self.active_grid_mut().scroll_down(top, bottom);
+        }
+    }
+
+    fn linefeed(&mut self) {
+        if self.cursor.row == self.scroll_bottom {
+            // Outside the left/right margins the bottom margin doesn't scroll.
+            if !self.has_lr_margins() || self.cursor_in_lr_margins() {
+                self.scroll_region_up(self.scroll_top, self.scroll_bottom);
+            }
+        } else if self.cursor.row < self.rows.saturating_sub(1) {
+            self.cursor.row += 1;
+        }
+    }
+
+    fn global_row(&self) -> u64 {
+        self.scrollback_seq + self.cursor.row as u64
+    }
+
+    /// Stable ID of the line under the cursor.
+    fn cursor_line_id(&self) -> u64 {
+        let grid = if self.using_alt {
+            self.alt_grid.as_ref().unwrap_or(&self.grid)
+        } else {
+            &self.grid
+        };
+        grid.visible_line_id(self.cursor.row)
+    }
+
+    fn reverse_index(&mut self) {
+        if self.cursor.row == self.scroll_top {
+            if !self.has_lr_margins() || self.cursor_in_lr_margins() {
+                self.scroll_region_down(self.scroll_top, self.scroll_bottom);
+            }
+        } else if self.cursor.row > 0 {
+            self.cursor.row -= 1;
+        }
+    }
+
+    /// To the left margin, or column 0 when the cursor is left of it.
+    fn carriage_return(&mut self) {
+        self.cursor.col = if self.has_lr_margins() && self.cursor.col >= self.scroll_left {
+            self.scroll_left
+        } else {
+            0
+        };
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor.col > 0 {
+            self.cursor.col -= 1;
+        }
+    }
+
+    fn tab(&mut self) {
+        let col = self.cursor.col as usize + 1;
+        for i in col..self.cols as usize {
+            if self.tab_stops.get(i).copied().unwrap_or(false) {
+                self.cursor.col = i as u16;
+                return;
+            }
+        }
+        self.cursor.col = self.cols.saturating_sub(1);
+    }
+
+    fn cursor_up(&mut self, n: u16) {
+        let min_row = if self.cursor.row >= self.scroll_top && self.cursor.row <= self.scroll_bottom
+        {
+            self.scroll_top
         } else {
             0
         };
@@ -348,11 +1686,21 @@ impl TerminalState {
     }
 
     fn cursor_forward(&mut self, n: u16) {
-        self.cursor.col = (self.cursor.col + n).min(self.cols.saturating_sub(1));
+        let max_col = if self.has_lr_margins() && self.cursor_in_lr_margins() {
+            self.scroll_right
+        } else {
+            self.cols.saturating_sub(1)
+        };
+        self.cursor.col = (self.cursor.col + n).min(max_col);
     }
 
     fn cursor_backward(&mut self, n: u16) {
-        self.cursor.col = self.cursor.col.saturating_sub(n);
+        let min_col = if self.has_lr_margins() && self.cursor_in_lr_margins() {
+            self.scroll_left
+        } else {
+            0
+        };
+        self.cursor.col = self.cursor.col.saturating_sub(n).max(min_col);
     }
 
     fn erase_display(&mut self, mode: u16) {
@@ -366,18 +1714,18 @@ impl TerminalState {
             0 => {
                 grid.erase_cells(crow, ccol, cols, bg);
                 for r in (crow + 1)..rows {
-                    grid.visible_row_mut(r).erase_with_bg(bg);
+                    grid.visible_row_mut(r).erase_line_with_bg(bg);
                 }
             }
             1 => {
                 for r in 0..crow {
-                    grid.visible_row_mut(r).erase_with_bg(bg);
+                    grid.visible_row_mut(r).erase_line_with_bg(bg);
                 }
                 grid.erase_cells(crow, 0, ccol + 1, bg);
             }
             2 => {
                 for r in 0..rows {
-                    grid.visible_row_mut(r).erase_with_bg(bg);
+                    grid.visible_row_mut(r).erase_line_with_bg(bg);
                 }
             }
             3 => {
@@ -405,30 +1753,161 @@ impl TerminalState {
         }
     }
 
+    /// DECSED (`CSI ? Ps J`): ED that only blanks unprotected characters,
+    /// leaving their rendition.
+    fn selective_erase_display(&mut self, mode: u16) {
+        let crow = self.cursor.row;
+        let ccol = self.cursor.col;
+        let cols = self.cols;
+        let rows = self.rows;
+        let grid = self.active_grid_mut();
+        let (first, last) = match mode {
+            0 => {
+                grid.selective_erase_cells(crow, ccol, cols);
+                (crow + 1, rows)
+            }
+            1 => {
+                grid.selective_erase_cells(crow, 0, ccol + 1);
+                (0, crow)
+            }
+            2 => (0, rows),
+            _ => return,
+        };
+        for r in first..last {
+            grid.selective_erase_cells(r, 0, cols);
+        }
+    }
+
+    /// DECSEL (`CSI ? Ps K`): EL that only blanks unprotected characters.
+    fn selective_erase_line(&mut self, mode: u16) {
+        let crow = self.cursor.row;
+        let ccol = self.cursor.col;
+        let cols = self.cols;
+        let grid = self.active_grid_mut();
+        match mode {
+            0 => grid.selective_erase_cells(crow, ccol, cols),
+            1 => grid.selective_erase_cells(crow, 0, ccol + 1),
+            2 => grid.selective_erase_cells(crow, 0, cols),
+            _ => {}
+        }
+    }
+
+    /// Page bounds for the rectangular area operations, as inclusive
+    /// (top, left, bottom, right): the margins in origin mode, else the
+    /// whole page.
+    fn rect_bounds(&self) -> (u16, u16, u16, u16) {
+        if self.modes.origin {
+            (
+                self.scroll_top,
+                self.scroll_left,
+                self.scroll_bottom,
+                self.scroll_right,
+            )
+        } else {
+            (0, 0, self.rows.saturating_sub(1), self.cols.saturating_sub(1))
+        }
+    }
+
+    /// The 1-based `row ; col` at `raw[first..]`, as a 0-based position
+    /// clipped to [`Self::rect_bounds`]. Missing values take `default`.
+    fn rect_point(&self, raw: &[u16], first: usize, default: u16) -> (u16, u16) {
+        let (top, left, bottom, right) = self.rect_bounds();
+        let row = top.saturating_add(param(raw, first, default) - 1);
+        let col = left.saturating_add(param(raw, first + 1, default) - 1);
+        (row.min(bottom), col.min(right))
+    }
+
+    /// The `Pt ; Pl ; Pb ; Pr` area at `raw[first..]` as inclusive
+    /// (top, left, bottom, right); the bottom-right corner defaults to the
+    /// end of the page. `None` when the area is empty.
+    fn rect_area(&self, raw: &[u16], first: usize) -> Option<(u16, u16, u16, u16)> {
+        let (top, left) = self.rect_point(raw, first, 1);
+        let (bottom, right) = self.rect_point(raw, first + 2, u16::MAX);
+        (top <= bottom && left <= right).then_some((top, left, bottom, right))
+    }
+
+    /// DECCRA (`CSI Pts ; Pls ; Pbs ; Prs ; Pps ; Ptd ; Pld ; Ppd $ v`).
+    /// There is a single page, so the page numbers are ignored.
+    fn copy_rect_area(&mut self, raw: &[u16]) {
+        let Some((top, left, bottom, right)) = self.rect_area(raw, 0) else {
+            return;
+        };
+        let (dst_row, dst_col) = self.rect_point(raw, 5, 1);
+        let (_, _, last_row, last_col) = self.rect_bounds();
+        let bottom = bottom.min(top + (last_row - dst_row));
+        let right = right.min(left + (last_col - dst_col));
+        self.active_grid_mut()
+            .copy_rect(top, bottom, left, right, dst_row, dst_col);
+    }
+
+    /// DECFRA (`CSI Pch ; Pt ; Pl ; Pb ; Pr $ x`): fill with a printable
+    /// character in the current rendition.
+    fn fill_rect_area(&mut self, raw: &[u16]) {
+        let code = raw.first().copied().unwrap_or(0);
+        if !matches!(code, 32..=126 | 160..=255) {
+            return;
+        }
+        let Some((top, left, bottom, right)) = self.rect_area(raw, 1) else {
+            return;
+        };
+        let cell = Cell {
+            c: char::from(code as u8),
+            fg: self.cursor.fg,
+            bg: self.cursor.bg,
+            underline_color: self.cursor.underline_color,
+            attrs: self.cursor.attrs,
+            flags: CellFlags::empty(),
+            combining: None,
+        };
+        self.active_grid_mut()
+            .fill_rect(top, bottom, left, right, &cell);
+    }
+
+    /// DECERA (`CSI Pt ; Pl ; Pb ; Pr $ z`) erases to blanks with the
+    /// current background; DECSERA (`$ {`) only blanks unprotected
+    /// characters and leaves their rendition.
+    fn erase_rect_area(&mut self, raw: &[u16], selective: bool) {
+        let Some((top, left, bottom, right)) = self.rect_area(raw, 0) else {
+            return;
+        };
+        let bg = self.cursor.bg;
+        let grid = self.active_grid_mut();
+        if selective {
+            grid.blank_rect(top, bottom, left, right);
+        } else {
+            let mut blank = Cell::default();
+            blank.erase(bg);
+            grid.fill_rect(top, bottom, left, right, &blank);
+        }
+    }
+
+    /// Whether IL/DL/ICH/DCH apply at the cursor: inside the scroll region
+    /// (for lines) and between the left/right margins.
+    fn cursor_in_margins(&self, vertical: bool) -> bool {
+        let in_rows = !vertical
+            || (self.cursor.row >= self.scroll_top && self.cursor.row <= self.scroll_bottom);
+        in_rows && (!self.has_lr_margins() || self.cursor_in_lr_margins())
+    }
+
     fn insert_lines(&mut self, n: u16) {
-        if self.cursor.row >= self.scroll_top && self.cursor.row <= self.scroll_bottom {
+        if self.cursor_in_margins(true) {
             let crow = self.cursor.row;
             let bottom = self.scroll_bottom;
             for _ in 0..n {
-                self.active_grid_mut().scroll_down(crow, bottom);
+                self.scroll_region_down(crow, bottom);
             }
-            self.cursor.col = 0;
+            self.cursor.col = self.scroll_left;
         }
     }
 
     fn delete_lines(&mut self, n: u16) {
-        if self.cursor.row >= self.scroll_top && self.cursor.row <= self.scroll_bottom {
+        if self.cursor_in_margins(true) {
             let crow = self.cursor.row;
             let bottom = self.scroll_bottom;
             for _ in 0..n {
-                if let Some(scrolled) = self.active_grid_mut().scroll_up(crow, bottom) {
-                    if !self.using_alt {
-                        self.scrolled_off_buffer.push(scrolled);
-                        self.scrollback_seq = self.scrollback_seq.saturating_add(1);
-                    }
-                }
+                self.scroll_region_up(crow, bottom);
             }
-            self.cursor.col = 0;
+            self.cursor.col = self.scroll_left;
         }
     }
 
@@ -440,36 +1919,42 @@ impl TerminalState {
         self.active_grid_mut().erase_cells(crow, ccol, end, bg);
     }
 
+    /// Right edge (exclusive) for shifting cells on the cursor's row.
+    fn shift_end(&self) -> u16 {
+        if self.has_lr_margins() {
+            self.scroll_right + 1
+        } else {
+            self.cols
+        }
+    }
+
     fn insert_chars(&mut self, n: u16) {
-        let crow = self.cursor.row;
-        let ccol = self.cursor.col;
-        self.active_grid_mut().insert_cells(crow, ccol, n);
+        if self.cursor_in_margins(false) {
+            let crow = self.cursor.row;
+            let ccol = self.cursor.col;
+            let end = self.shift_end();
+            self.active_grid_mut().insert_cells(crow, ccol, n, end);
+        }
     }
 
     fn delete_chars(&mut self, n: u16) {
-        let crow = self.cursor.row;
-        let ccol = self.cursor.col;
-        self.active_grid_mut().delete_cells(crow, ccol, n);
+        if self.cursor_in_margins(false) {
+            let crow = self.cursor.row;
+            let ccol = self.cursor.col;
+            let end = self.shift_end();
+            self.active_grid_mut().delete_cells(crow, ccol, n, end);
+        }
     }
 
     fn scroll_up_n(&mut self, n: u16) {
-        let top = self.scroll_top;
-        let bottom = self.scroll_bottom;
         for _ in 0..n {
-            if let Some(scrolled) = self.active_grid_mut().scroll_up(top, bottom) {
-                if !self.using_alt {
-                    self.scrolled_off_buffer.push(scrolled);
-                    self.scrollback_seq = self.scrollback_seq.saturating_add(1);
-                }
-            }
+            self.scroll_region_up(self.scroll_top, self.scroll_bottom);
         }
     }
 
     fn scroll_down_n(&mut self, n: u16) {
-        let top = self.scroll_top;
-        let bottom = self.scroll_bottom;
         for _ in 0..n {
-            self.active_grid_mut().scroll_down(top, bottom);
+            self.scroll_region_down(self.scroll_top, self.scroll_bottom);
         }
     }
 
@@ -483,14 +1968,15 @@ impl TerminalState {
 
     fn enter_alt_screen(&mut self) {
         if !self.using_alt {
-            self.alt_grid = Some(Grid::new(self.rows, self.cols));
+            // Borrow the main grid's ID range; it is reserved again on exit.
+            let first_line_id = self.grid.next_line_id();
+            self.alt_grid = Some(Grid::with_first_line_id(self.rows, self.cols, first_line_id));
             self.using_alt = true;
             self.modes.alt_screen = true;
+            self.keyboard_alt.clear();
             // Reset cursor attributes to defaults (matching xterm/iTerm2/kitty).
             // TUI apps expect a clean attribute state in alt screen.
-            self.cursor.attrs = CellAttrs::empty();
-            self.cursor.fg = Color::Default;
-            self.cursor.bg = Color::Default;
+            self.cursor.reset_rendition();
             self.pending_terminal_events
                 .push(TerminalEvent::AltScreenEntered);
         }
@@ -500,7 +1986,9 @@ impl TerminalState {
         if self.using_alt {
             self.using_alt = false;
             self.modes.alt_screen = false;
-            self.alt_grid = None;
+            if let Some(alt) = self.alt_grid.take() {
+                self.grid.reserve_line_ids(alt.next_line_id());
+            }
             self.grid.mark_all_dirty();
             self.pending_terminal_events
                 .push(TerminalEvent::AltScreenExited);
@@ -527,73 +2015,52 @@ impl TerminalState {
 
         while i < params.len() {
             match params[i] {
-                0 => {
-                    self.cursor.attrs = CellAttrs::empty();
-                    self.cursor.fg = Color::Default;
-                    self.cursor.bg = Color::Default;
-                }
+                0 => self.cursor.reset_rendition(),
                 1 => self.cursor.attrs.insert(CellAttrs::BOLD),
                 2 => self.cursor.attrs.insert(CellAttrs::DIM),
                 3 => self.cursor.attrs.insert(CellAttrs::ITALIC),
-                4 => self.cursor.attrs.insert(CellAttrs::UNDERLINE),
-                5 => self.cursor.attrs.insert(CellAttrs::BLINK),
+                4 => self.cursor.attrs.set_underline(1),
+                5 => {
+                    self.cursor.attrs.remove(CellAttrs::RAPID_BLINK);
+                    self.cursor.attrs.insert(CellAttrs::BLINK);
+                }
+                6 => {
+                    self.cursor.attrs.remove(CellAttrs::BLINK);
+                    self.cursor.attrs.insert(CellAttrs::RAPID_BLINK);
+                }
                 7 => self.cursor.attrs.insert(CellAttrs::REVERSE),
                 8 => self.cursor.attrs.insert(CellAttrs::HIDDEN),
                 9 => self.cursor.attrs.insert(CellAttrs::STRIKETHROUGH),
+                21 => self.cursor.attrs.set_underline(2),
                 22 => {
                     self.cursor.attrs.remove(CellAttrs::BOLD);
                     self.cursor.attrs.remove(CellAttrs::DIM);
                 }
                 23 => self.cursor.attrs.remove(CellAttrs::ITALIC),
-                24 => self.cursor.attrs.remove(CellAttrs::UNDERLINE),
-                25 => self.cursor.attrs.remove(CellAttrs::BLINK),
+                24 => self.cursor.attrs.set_underline(0),
+                25 => self
+                    .cursor
+                    .attrs
+                    .remove(CellAttrs::BLINK | CellAttrs::RAPID_BLINK),
                 27 => self.cursor.attrs.remove(CellAttrs::REVERSE),
                 28 => self.cursor.attrs.remove(CellAttrs::HIDDEN),
                 29 => self.cursor.attrs.remove(CellAttrs::STRIKETHROUGH),
                 30..=37 => self.cursor.fg = Color::Indexed(params[i] as u8 - 30),
-                38 => {
-                    i += 1;
-                    if i < params.len() {
+                38 | 48 | 58 => {
+                    let (color, used) = extended_color(&params[i + 1..]);
+                    if let Some(color) = color {
                         match params[i] {
-                            2 if i + 3 < params.len() => {
-                                self.cursor.fg = Color::Rgb(
-                                    params[i + 1] as u8,
-                                    params[i + 2] as u8,
-                                    params[i + 3] as u8,
-                                );
-                                i += 3;
-                            }
-                            5 if i + 1 < params.len() => {
-                                self.cursor.fg = Color::Indexed(params[i + 1] as u8);
-                                i += 1;
-                            }
-                            _ => {}
+                            38 => self.cursor.fg = color,
+                            48 => self.cursor.bg = color,
+                            _ => self.cursor.underline_color = color,
                         }
                     }
+                    i += used;
                 }
                 39 => self.cursor.fg = Color::Default,
                 40..=47 => self.cursor.bg = Color::Indexed(params[i] as u8 - 40),
-                48 => {
-                    i += 1;
-                    if i < params.len() {
-                        match params[i] {
-                            2 if i + 3 < params.len() => {
-                                self.cursor.bg = Color::Rgb(
-                                    params[i + 1] as u8,
-                                    params[i + 2] as u8,
-                                    params[i + 3] as u8,
-                                );
-                                i += 3;
-                            }
-                            5 if i + 1 < params.len() => {
-                                self.cursor.bg = Color::Indexed(params[i + 1] as u8);
-                                i += 1;
-                            }
-                            _ => {}
-                        }
-                    }
-                }
                 49 => self.cursor.bg = Color::Default,
+                59 => self.cursor.underline_color = Color::Default,
                 90..=97 => self.cursor.fg = Color::Indexed(params[i] as u8 - 90 + 8),
                 100..=107 => self.cursor.bg = Color::Indexed(params[i] as u8 - 100 + 8),
                 _ => {}
@@ -630,30 +2097,16 @@ impl TerminalState {
                     flat.clear();
                 }
                 match sub[0] {
-                    4 => match sub.get(1).copied().unwrap_or(1) {
-                        0 => self.cursor.attrs.remove(CellAttrs::UNDERLINE),
-                        _ => self.cursor.attrs.insert(CellAttrs::UNDERLINE),
-                    },
-                    38 => match sub.get(1).copied().unwrap_or(0) {
-                        2 if sub.len() >= 5 => {
-                            self.cursor.fg =
-                                Color::Rgb(sub[2] as u8, sub[3] as u8, sub[4] as u8);
-                        }
-                        5 if sub.len() >= 3 => {
-                            self.cursor.fg = Color::Indexed(sub[2] as u8);
-                        }
-                        _ => {}
-                    },
-                    48 => match sub.get(1).copied().unwrap_or(0) {
-                        2 if sub.len() >= 5 => {
-                            self.cursor.bg =
-                                Color::Rgb(sub[2] as u8, sub[3] as u8, sub[4] as u8);
-                        }
-                        5 if sub.len() >= 3 => {
-                            self.cursor.bg = Color::Indexed(sub[2] as u8);
+                    4 => self.cursor.attrs.set_underline(sub[1]),
+                    38 | 48 | 58 => {
+                        if let Some(color) = colon_color(&sub[1..]) {
+                            match sub[0] {
+                                38 => self.cursor.fg = color,
+                                48 => self.cursor.bg = color,
+                                _ => self.cursor.underline_color = color,
+                            }
                         }
-                        _ => {}
-                    },
+                    }
                     other => {
                         // Unknown colon sub-params — use just the primary value
                         flat.push(other);
@@ -684,6 +2137,7 @@ impl TerminalState {
                 synchronized_output: self.modes.synchronized_output,
                 bracketed_paste: self.modes.bracketed_paste,
                 cursor_keys_application: self.modes.cursor_keys_application,
+                modify_other_keys: self.modes.modify_other_keys,
             });
     }
 
@@ -702,9 +2156,17 @@ impl TerminalState {
                     } else {
                         self.cursor.row = 0;
                     }
-                    self.cursor.col = 0;
+                    self.cursor.col = if enable { self.scroll_left } else { 0 };
                 }
+                3 => self.set_column_mode(enable),
+                5 => self.modes.reverse_video = enable,
                 7 => self.modes.autowrap = enable,
+                69 => {
+                    self.modes.left_right_margin = enable;
+                    if !enable {
+                        self.reset_lr_margins();
+                    }
+                }
                 12 => {}
                 25 => self.modes.cursor_visible = enable,
                 47 => {
@@ -755,6 +2217,8 @@ impl TerminalState {
                     self.modes.sgr_mouse = enable;
                     self.emit_mode_changed();
                 }
+                // Only affects report encoding, which happens here.
+                1016 => self.modes.sgr_pixels = enable,
                 1007 => {
                     self.modes.alternate_scroll = enable;
                     self.emit_mode_changed();
@@ -774,6 +2238,11 @@ impl TerminalState {
                     self.emit_mode_changed();
                 }
                 2026 => {
+                    self.sync_update_started = if enable {
+                        self.sync_update_started.or(Some(Instant::now()))
+                    } else {
+                        None
+                    };
                     self.modes.synchronized_output = enable;
                     self.emit_mode_changed();
                 }
@@ -782,6 +2251,49 @@ impl TerminalState {
         }
     }
 
+    /// DECSTR (`CSI ! p`): soft reset. Modes, margins, the character set,
+    /// the rendition and the saved cursor return to their defaults; unlike
+    /// RIS the screen, scrollback and cursor position stay, so a TUI that
+    /// crashed mid-draw can be recovered from without losing history.
+    fn soft_reset(&mut self) {
+        let defaults = TerminalModes::default();
+        self.modes.cursor_visible = defaults.cursor_visible;
+        self.modes.insert = defaults.insert;
+        self.modes.origin = defaults.origin;
+        self.modes.autowrap = defaults.autowrap;
+        self.modes.cursor_keys_application = defaults.cursor_keys_application;
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows.saturating_sub(1);
+        self.reset_lr_margins();
+        self.charset_g0_drawing = false;
+        self.cursor.reset_rendition();
+        self.cursor.protected = false;
+        self.cursor.clear_saved();
+        self.emit_mode_changed();
+    }
+
+    /// DECCOLM: switch the grid to 132 or 80 columns, clearing the screen
+    /// and homing the cursor with the margins reset. The frontend gets a
+    /// `ColumnModeChanged` event and either resizes the PTY to match or
+    /// resizes back to the width of the pane.
+    fn set_column_mode(&mut self, wide: bool) {
+        self.modes.column_132 = wide;
+        let cols = if wide { 132 } else { 80 };
+        if cols != self.cols {
+            self.resize(self.rows, cols);
+        } else {
+            self.scroll_top = 0;
+            self.scroll_bottom = self.rows.saturating_sub(1);
+            self.reset_lr_margins();
+        }
+        self.clear_screen();
+        self.cursor.row = 0;
+        self.cursor.col = 0;
+        self.margin_wrap_pending = false;
+        self.pending_terminal_events
+            .push(TerminalEvent::ColumnModeChanged { cols });
+    }
+
     fn report_mode_state(&mut self, mode: u16, set: Option<bool>, dec_private: bool) {
         let pm = match set {
             Some(true) => 1,
@@ -796,8 +2308,11 @@ impl TerminalState {
     fn dec_mode_state(&self, mode: u16) -> Option<bool> {
         match mode {
             1 => Some(self.modes.cursor_keys_application),
+            3 => Some(self.modes.column_132),
+            5 => Some(self.modes.reverse_video),
             6 => Some(self.modes.origin),
             7 => Some(self.modes.autowrap),
+            69 => Some(self.modes.left_right_margin),
             25 => Some(self.modes.cursor_visible),
             47 | 1047 | 1049 => Some(self.using_alt),
             1000 => Some(self.modes.mouse_tracking),
@@ -806,6 +2321,7 @@ impl TerminalState {
             1004 => Some(self.modes.focus_events),
             1005 => Some(self.modes.utf8_mouse),
             1006 => Some(self.modes.sgr_mouse),
+            1016 => Some(self.modes.sgr_pixels),
             1007 => Some(self.modes.alternate_scroll),
             2004 => Some(self.modes.bracketed_paste),
             2026 => Some(self.modes.synchronized_output),
@@ -841,6 +2357,23 @@ impl TerminalState {
         }
     }
 
+    /// Apply XTMODKEYS to resource `params[0]` (all of them when omitted).
+    /// Only modifyOtherKeys (4) is tracked; a missing value resets it.
+    fn set_key_modifiers(&mut self, params: &[u16], value: Option<u16>) {
+        let all = params.iter().all(|&p| p == 0) && params.len() <= 1;
+        if !all && params.first() != Some(&4) {
+            return;
+        }
+        let level = match value.unwrap_or(0) {
+            level @ 0..=2 => level as u8,
+            _ => return,
+        };
+        if self.modes.modify_other_keys != level {
+            self.modes.modify_other_keys = level;
+            self.emit_mode_changed();
+        }
+    }
+
     fn set_mode(&mut self, params: &[u16], enable: bool) {
         for &p in params {
             match p {
@@ -870,10 +2403,11 @@ impl TerminalState {
                     let uri = String::from_utf8_lossy(params[1]);
                     if let Some(path) = uri.strip_prefix("file://") {
                         if let Some(slash_idx) = path.find('/') {
-                            self.shell.set_cwd(path[slash_idx..].to_string());
+                            let local = is_local_host(&path[..slash_idx]);
+                            self.shell.report_cwd(path[slash_idx..].to_string(), local);
                         }
                     } else {
-                        self.shell.set_cwd(uri.to_string());
+                        self.shell.report_cwd(uri.to_string(), true);
                     }
                 }
             }
@@ -883,12 +2417,10 @@ impl TerminalState {
                     match marker {
                         "A" => {
                             let row = self.global_row();
-                            self.shell.prompt_start(row);
+                            self.shell.prompt_start(row, self.cursor_line_id());
                             // Reset cursor attributes at prompt start so stale
                             // SGR state from a previous command doesn't leak.
-                            self.cursor.attrs = CellAttrs::empty();
-                            self.cursor.fg = Color::Default;
-                            self.cursor.bg = Color::Default;
+                            self.cursor.reset_rendition();
                         }
                         "B" => {
                             let cmd: String = params[2..]
@@ -898,7 +2430,11 @@ impl TerminalState {
                                 .join(";");
                             if !cmd.is_empty() {
                                 let row = self.global_row();
-                                self.shell.command_start(cmd, row);
+                                let line_id = self.cursor_line_id();
+                                self.shell.command_start(cmd, row, line_id);
+                            } else if !self.using_alt {
+                                let line_id = self.cursor_line_id();
+                                self.shell.prompt_end(line_id, self.cursor.col);
                             }
                         }
                         "C" => {}
@@ -920,7 +2456,11 @@ impl TerminalState {
                                 .and_then(|s| s.parse::<i32>().ok())
                                 .unwrap_or(0);
                             let row = self.global_row();
-                            self.shell.command_end(exit_code, row);
+                            let block_id = self.shell.current_block_id.clone();
+                            self.shell.command_end(exit_code, row, self.cursor_line_id());
+                            if let Some(id) = block_id {
+                                self.detect_structured_output(id);
+                            }
                         }
                         _ => {}
                     }
@@ -946,88 +2486,219 @@ impl TerminalState {
                 self.handle_osc_52(params);
             }
             "4" => {
-                if params.len() >= 3 && params[2] == b"?" {
-                    if let Ok(idx_str) = std::str::from_utf8(params[1]) {
-                        if let Ok(index) = idx_str.parse::<u8>() {
-                            let (r, g, b) = indexed_to_rgb(index);
-                            let (r16, g16, b16) =
-                                (r as u16 * 0x0101, g as u16 * 0x0101, b as u16 * 0x0101);
-                            let response = format!(
-                                "\x1b]4;{};rgb:{:04x}/{:04x}/{:04x}\x1b\\",
-                                index, r16, g16, b16
-                            );
-                            self.pending_responses.push(response.into_bytes());
-                        }
+                // OSC 4 ; index ; spec [; index ; spec ...]
+                let mut changed = false;
+                for pair in params[1..].chunks_exact(2) {
+                    let Some(index) = std::str::from_utf8(pair[0])
+                        .ok()
+                        .and_then(|s| s.parse::<u8>().ok())
+                    else {
+                        continue;
+                    };
+                    if pair[1] == b"?" {
+                        let (r, g, b) = self.palette.rgb(index);
+                        let (r16, g16, b16) =
+                            (r as u16 * 0x0101, g as u16 * 0x0101, b as u16 * 0x0101);
+                        let response = format!(
+                            "\x1b]4;{};rgb:{:04x}/{:04x}/{:04x}\x1b\\",
+                            index, r16, g16, b16
+                        );
+                        self.pending_responses.push(response.into_bytes());
+                    } else if let Some(rgb) = std::str::from_utf8(pair[1])
+                        .ok()
+                        .and_then(parse_color_spec)
+                    {
+                        changed |= self.palette.set(index, rgb);
                     }
                 }
+                if changed {
+                    self.palette_changed();
+                }
+            }
+            "104" => {
+                // OSC 104 alone resets the whole palette; otherwise only the
+                // listed entries.
+                let indexes: Vec<u8> = params[1..]
+                    .iter()
+                    .filter_map(|p| std::str::from_utf8(p).ok()?.parse().ok())
+                    .collect();
+                let changed = if params[1..].iter().all(|p| p.is_empty()) {
+                    self.palette.reset_all()
+                } else {
+                    indexes
+                        .into_iter()
+                        .fold(false, |changed, index| self.palette.reset(index) | changed)
+                };
+                if changed {
+                    self.palette_changed();
+                }
             }
             "10" | "11" | "12" => {
-                if params.len() >= 2 && params[1] == b"?" {
-                    let (r, g, b): (u8, u8, u8) = match first {
-                        "10" => (0xd4, 0xd4, 0xd4),
-                        "11" => (0x0e, 0x0e, 0x0e),
-                        _ => (0xd4, 0xd4, 0xd4),
-                    };
-                    let (r16, g16, b16) =
-                        (r as u16 * 0x0101, g as u16 * 0x0101, b as u16 * 0x0101);
-                    let response = format!(
-                        "\x1b]{};rgb:{:04x}/{:04x}/{:04x}\x1b\\",
-                        first, r16, g16, b16
-                    );
-                    self.pending_responses.push(response.into_bytes());
+                // Each further parameter addresses the next color, so
+                // `OSC 10 ; fg ; bg` sets both.
+                let first_slot = match first {
+                    "10" => 0,
+                    "11" => 1,
+                    _ => 2,
+                };
+                let mut changed = false;
+                for (slot, spec) in (first_slot..3).zip(&params[1..]) {
+                    if *spec == b"?" {
+                        let (r, g, b) = self.dynamic_colors.rgb(slot);
+                        let (r16, g16, b16) =
+                            (r as u16 * 0x0101, g as u16 * 0x0101, b as u16 * 0x0101);
+                        let response = format!(
+                            "\x1b]{};rgb:{:04x}/{:04x}/{:04x}\x1b\\",
+                            10 + slot,
+                            r16,
+                            g16,
+                            b16
+                        );
+                        self.pending_responses.push(response.into_bytes());
+                    } else if let Some(rgb) = std::str::from_utf8(spec)
+                        .ok()
+                        .and_then(parse_color_spec)
+                    {
+                        changed |= self.dynamic_colors.set(slot, rgb);
+                    }
+                }
+                if changed {
+                    self.dynamic_colors_changed();
+                }
+            }
+            "110" | "111" | "112" => {
+                let slot = match first {
+                    "110" => 0,
+                    "111" => 1,
+                    _ => 2,
+                };
+                if self.dynamic_colors.reset(slot) {
+                    self.dynamic_colors_changed();
                 }
             }
+            "9" if params.get(1) == Some(&&b"4"[..]) => self.handle_progress(params),
+            // OSC 9 ; message (iTerm2)
+            "9" if params.len() >= 2 => {
+                let body = params[1..]
+                    .iter()
+                    .map(|p| String::from_utf8_lossy(p))
+                    .collect::<Vec<_>>()
+                    .join(";");
+                self.notify(None, body);
+            }
+            // OSC 777 ; notify ; title ; body (urxvt)
+            "777" if params.get(1) == Some(&&b"notify"[..]) => {
+                let title = params
+                    .get(2)
+                    .map(|p| String::from_utf8_lossy(p).to_string())
+                    .filter(|title| !title.is_empty());
+                let body = params[3.min(params.len())..]
+                    .iter()
+                    .map(|p| String::from_utf8_lossy(p))
+                    .collect::<Vec<_>>()
+                    .join(";");
+                self.notify(title, body);
+            }
             "1337" => {
-                // iTerm2 inline image protocol: OSC 1337 ; File=<params>:<base64data> ST
-                if params.len() >= 2 {
-                    let payload = String::from_utf8_lossy(params[1]).to_string();
-                    if let Some(rest) = payload.strip_prefix("File=") {
-                        // Parse key=value pairs before the colon
-                        if let Some(colon_idx) = rest.find(':') {
-                            let param_str = &rest[..colon_idx];
-                            let base64_data = &rest[colon_idx + 1..];
-                            let mut width: u16 = 0;
-                            let mut height: u16 = 0;
-                            let mut is_inline = false;
-
-                            for part in param_str.split(';') {
-                                if let Some((key, val)) = part.split_once('=') {
-                                    match key {
-                                        "width" => width = val.parse().unwrap_or(0),
-                                        "height" => height = val.parse().unwrap_or(0),
-                                        "inline" => is_inline = val == "1",
-                                        _ => {}
-                                    }
-                                }
-                            }
-
-                            if is_inline && !base64_data.is_empty() && self.experimental_image_protocols_enabled {
-                                self.image_counter += 1;
-                                let id = format!("img-{}", self.image_counter);
-                                self.pending_terminal_events
-                                    .push(TerminalEvent::InlineImage {
-                                        id,
-                                        data_base64: base64_data.to_string(),
-                                        width,
-                                        height,
-                                        row: self.cursor.row,
-                                        col: self.cursor.col,
-                                    });
-                            } else if is_inline
-                                && !base64_data.is_empty()
-                                && !self.image_protocol_drop_notified
-                            {
-                                tracing::info!(
-                                    "Image protocol payload received but experimental rendering is disabled"
-                                );
-                                self.image_protocol_drop_notified = true;
-                            }
-                        }
-                    }
+                // iTerm2 file transfer. vte splits the arguments on `;`.
+                let payload = params[1..].join(&b';');
+                if let Some(file) = self.file_transfers.handle(&payload) {
+                    self.receive_file(file);
                 }
             }
-            _ => {}
+            other => self.handle_custom_osc(other, params),
+        }
+    }
+
+    /// Repaint the screen with the new palette and report its entries.
+    fn palette_changed(&mut self) {
+        self.grid.mark_all_dirty();
+        if let Some(alt) = self.alt_grid.as_mut() {
+            alt.mark_all_dirty();
         }
+        self.pending_terminal_events
+            .push(TerminalEvent::PaletteChanged {
+                colors: self.palette.entries(),
+            });
+    }
+
+    fn dynamic_colors_changed(&mut self) {
+        self.pending_terminal_events
+            .push(TerminalEvent::DynamicColorsChanged {
+                foreground: self.dynamic_colors.get(0),
+                background: self.dynamic_colors.get(1),
+                cursor: self.dynamic_colors.get(2),
+            });
+    }
+
+    fn handle_progress(&mut self, params: &[&[u8]]) {
+        // OSC 9 ; 4 ; state ; value
+        let number = |idx: usize| {
+            params
+                .get(idx)
+                .and_then(|p| std::str::from_utf8(p).ok()?.parse::<u16>().ok())
+                .map(|n| n.min(100) as u8)
+        };
+        let state = match number(2).unwrap_or(0) {
+            0 => ProgressState::None,
+            1 => ProgressState::Normal,
+            2 => ProgressState::Error,
+            3 => ProgressState::Indeterminate,
+            4 => ProgressState::Paused,
+            _ => return,
+        };
+        // Error and paused keep the last value unless they bring their own.
+        let value = match state {
+            ProgressState::None | ProgressState::Indeterminate => 0,
+            ProgressState::Normal => number(3).unwrap_or(0),
+            ProgressState::Error | ProgressState::Paused => number(3).unwrap_or(self.progress.1),
+        };
+        self.set_progress(state, value);
+    }
+
+    fn set_progress(&mut self, state: ProgressState, value: u8) {
+        if self.progress != (state, value) {
+            self.progress = (state, value);
+            self.pending_terminal_events
+                .push(TerminalEvent::ProgressChanged { state, value });
+        }
+    }
+
+    fn notify(&mut self, title: Option<String>, body: String) {
+        const MAX_NOTIFICATION_CHARS: usize = 1024;
+
+        let clip = |text: String| text.chars().take(MAX_NOTIFICATION_CHARS).collect::<String>();
+        let title = title.map(clip);
+        let body = clip(body);
+        if title.is_none() && body.trim().is_empty() {
+            return;
+        }
+        self.pending_terminal_events
+            .push(TerminalEvent::Notification { title, body });
+    }
+
+    /// Forward a user-registered OSC verbatim. The payload is everything
+    /// after the first `;`, rejoined since vte splits on `;`.
+    fn handle_custom_osc(&mut self, code: &str, params: &[&[u8]]) {
+        const MAX_CUSTOM_OSC_PAYLOAD: usize = 64 * 1024;
+
+        let Ok(code) = code.parse::<u16>() else {
+            return;
+        };
+        if !self.custom_osc_codes.contains(&code) {
+            return;
+        }
+        let payload = params[1..]
+            .iter()
+            .map(|p| String::from_utf8_lossy(p))
+            .collect::<Vec<_>>()
+            .join(";");
+        if payload.len() > MAX_CUSTOM_OSC_PAYLOAD {
+            tracing::warn!("Dropping oversized OSC {} payload ({} bytes)", code, payload.len());
+            return;
+        }
+        self.pending_terminal_events
+            .push(TerminalEvent::CustomOsc { code, payload });
     }
 
     fn handle_osc_52(&mut self, params: &[&[u8]]) {
@@ -1039,9 +2710,13 @@ impl TerminalState {
 
         let target = std::str::from_utf8(params[1]).unwrap_or("c");
         let payload = std::str::from_utf8(params[2]).unwrap_or("");
+        let selections = Selection::from_osc52_targets(target);
+        let Some(&first) = selections.first() else {
+            return;
+        };
 
         if payload == "?" {
-            let current = read_clipboard_text().unwrap_or_default();
+            let current = clipboard::read_text(first).unwrap_or_default();
             let encoded = BASE64_STANDARD.encode(current.as_bytes());
             let response = format!("\x1b]52;{};{}\x1b\\", target, encoded);
             self.pending_responses.push(response.into_bytes());
@@ -1049,14 +2724,22 @@ impl TerminalState {
         }
 
         // Empty payload clears clipboard selection by convention.
-        if payload.is_empty() {
-            let _ = write_clipboard_text("");
+        let text = if payload.is_empty() {
+            String::new()
+        } else if let Ok(decoded) = BASE64_STANDARD.decode(payload.as_bytes()) {
+            String::from_utf8_lossy(&decoded).to_string()
+        } else {
             return;
+        };
+        if let Some(history) = self
+            .clipboard_history
+            .as_ref()
+            .filter(|_| selections.contains(&Selection::Clipboard))
+        {
+            history.lock().record(&text, clipboard::CopySource::Osc52);
         }
-
-        if let Ok(decoded) = BASE64_STANDARD.decode(payload.as_bytes()) {
-            let text = String::from_utf8_lossy(&decoded).to_string();
-            let _ = write_clipboard_text(&text);
+        for selection in selections {
+            let _ = clipboard::write_text(selection, &text);
         }
     }
 
@@ -1125,12 +2808,20 @@ impl TerminalState {
                 };
                 Some(format!("{} q", cursor_style))
             }
+            // DECSCA (character protection)
+            "\"q" => Some(format!("{}\"q", u8::from(self.cursor.protected))),
             // DECSTBM (scroll region)
             "r" => Some(format!(
                 "{};{}r",
                 self.scroll_top + 1,
                 self.scroll_bottom + 1
             )),
+            // DECSLRM (left/right margins)
+            "s" => Some(format!(
+                "{};{}s",
+                self.scroll_left + 1,
+                self.scroll_right + 1
+            )),
             _ => None,
         };
 
@@ -1164,9 +2855,7 @@ impl TerminalState {
         }
 
         let mut parser = vte::Parser::new();
-        for b in decoded {
-            parser.advance(self, b);
-        }
+        self.advance(&mut parser, &decoded);
     }
 
     pub fn get_text_range(&self, start_row: usize, end_row: usize) -> String {
@@ -1184,7 +2873,7 @@ impl TerminalState {
                 .cells
                 .iter()
                 .filter(|c| !c.flags.contains(CellFlags::WIDE_SPACER))
-                .map(|c| c.c)
+                .flat_map(Cell::chars)
                 .collect::<String>()
                 .trim_end()
                 .to_string();
@@ -1197,6 +2886,279 @@ impl TerminalState {
 
         lines.join("\n")
     }
+
+    /// Command and output rows of a recent block, while they are still in
+    /// scrollback. A block that is still running ends at the cursor.
+    pub fn block_rows(&self, block_id: &str) -> Option<(Option<String>, Vec<Row>)> {
+        let block = self.shell.block(block_id)?;
+        let start_id = block.output_line_id.unwrap_or(block.prompt_line_id);
+        let end_id = block
+            .end_line_id
+            .unwrap_or_else(|| self.grid.visible_line_id(self.cursor.row));
+        let start = self.grid.find_history_line(start_id)?;
+        let end = self.grid.find_history_line(end_id)?.max(start);
+        let rows = self.grid.history_rows(start..end + 1);
+        Some((block.command.clone(), rows))
+    }
+
+    /// Main-grid rows `start_line_id..=end_line_id`, while both are still
+    /// in scrollback.
+    pub fn line_range_rows(&self, start_line_id: u64, end_line_id: u64) -> Option<Vec<Row>> {
+        let start = self.grid.find_history_line(start_line_id)?;
+        let end = self.grid.find_history_line(end_line_id)?.max(start);
+        Some(self.grid.history_rows(start..end + 1))
+    }
+
+    /// Text of the whole main grid, compressed scrollback included.
+    pub fn scrollback_text(&self) -> String {
+        let mut lines = Vec::new();
+        self.grid.visit_history(|_, row| {
+            lines.push(row_line_text(row));
+            true
+        });
+        join_line_texts(lines)
+    }
+
+    /// Prompt regions of recent blocks overlapping history rows
+    /// `start..=end`, relative to `start`.
+    fn prompt_regions(&self, start: usize, end: usize) -> Vec<PromptRegion> {
+        let cursor_line = self.grid.visible_line_id(self.cursor.row);
+        self.shell
+            .blocks()
+            .filter_map(|block| {
+                let first = self.grid.find_history_line(block.prompt_line_id)?;
+                let command_row = match (block.input_start, block.output_line_id) {
+                    (Some((line_id, _)), _) => self.grid.find_history_line(line_id)?,
+                    (None, Some(output)) => self.grid.find_history_line(output)?.saturating_sub(1),
+                    (None, None) => self.grid.find_history_line(cursor_line)?,
+                }
+                .max(first);
+                if command_row < start || first > end {
+                    return None;
+                }
+                Some(PromptRegion {
+                    first: first.saturating_sub(start),
+                    command_row: command_row - start,
+                    input_col: block.input_start.map(|(_, col)| col),
+                    command: block.command.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Normalized text of main-grid lines `start_line_id..=end_line_id`, or
+    /// `None` when either line is no longer in scrollback.
+    pub fn copy_line_range(
+        &self,
+        start_line_id: u64,
+        end_line_id: u64,
+        options: CopyOptions,
+    ) -> Option<String> {
+        let start = self.grid.find_history_line(start_line_id)?;
+        let end = self.grid.find_history_line(end_line_id)?;
+        if end < start {
+            return Some(String::new());
+        }
+        let rows = self.grid.history_rows(start..end + 1);
+        let rows: Vec<&Row> = rows.iter().collect();
+        let prompts = self.prompt_regions(start, end);
+        Some(copy::rows_to_text(&rows, &prompts, options))
+    }
+
+    /// Normalized text of a recent block: its output, preceded by the prompt
+    /// and command with `include_command`.
+    pub fn copy_block(
+        &self,
+        block_id: &str,
+        include_command: bool,
+        options: CopyOptions,
+    ) -> Option<String> {
+        let block = self.shell.block(block_id)?;
+        let start_id = match block.output_line_id {
+            Some(output) if !include_command => output,
+            _ => block.prompt_line_id,
+        };
+        let end_id = block
+            .end_line_id
+            .unwrap_or_else(|| self.grid.visible_line_id(self.cursor.row));
+        self.copy_line_range(start_id, end_id, options)
+    }
+
+    /// A recent block as a Markdown snippet for docs; see
+    /// [`copy::block_markdown`].
+    pub fn block_markdown(&self, block_id: &str, max_output_lines: usize) -> Option<String> {
+        let exit_code = self.shell.block(block_id)?.exit_code;
+        let (command, rows) = self.block_rows(block_id)?;
+        let rows: Vec<&Row> = rows.iter().collect();
+        Some(copy::block_markdown(
+            command.as_deref(),
+            &rows,
+            exit_code,
+            max_output_lines,
+        ))
+    }
+
+    /// Output rows of a finished block joined for parsing (see
+    /// [`structured::rows_text`]), or `None` when the block has no output
+    /// or isn't in scrollback any more. Blocks longer than
+    /// [`structured::MAX_STRUCTURED_ROWS`] are skipped.
+    pub fn block_output_text(&self, block_id: &str) -> Option<String> {
+        let block = self.shell.block(block_id)?;
+        let start = self.grid.find_history_line(block.output_line_id?)?;
+        let end = self.grid.find_history_line(block.end_line_id?)?;
+        if end < start || end - start >= structured::MAX_STRUCTURED_ROWS {
+            return None;
+        }
+        let rows = self.grid.history_rows(start..end + 1);
+        let rows: Vec<&Row> = rows.iter().collect();
+        Some(structured::rows_text(&rows))
+    }
+
+    /// The block whose prompt is nearest above main-grid line `line_id`.
+    pub fn block_at_line(&self, line_id: u64) -> Option<&BlockRecord> {
+        let top = self.grid.find_line(line_id)?;
+        let prompts: HashMap<u64, &BlockRecord> = self
+            .shell
+            .blocks()
+            .map(|block| (block.prompt_line_id, block))
+            .collect();
+        self.grid
+            .rows
+            .range(..=top)
+            .rev()
+            .find_map(|row| prompts.get(&row.line_id).copied())
+    }
+
+    /// The command line being edited at the prompt, read from the screen
+    /// between the end of the prompt and the cursor. `None` while a command
+    /// runs or without shell integration.
+    pub fn current_command_line(&self) -> Option<CommandLine> {
+        if !self.shell.active || self.shell.command_running || self.using_alt {
+            return None;
+        }
+        let typed = self.shell.typed_input();
+        let block = self.shell.block(self.shell.current_block_id.as_deref()?)?;
+        // Without a bare OSC 133;B (or once the prompt has scrolled away)
+        // the typed text is all there is.
+        let Some((line_id, start_col)) = block.input_start else {
+            return Some(CommandLine::typed(typed));
+        };
+        let (Some(start), Some(cursor_idx)) = (
+            self.grid.find_line(line_id),
+            self.grid.find_line(self.grid.visible_line_id(self.cursor.row)),
+        ) else {
+            return Some(CommandLine::typed(typed));
+        };
+        if cursor_idx < start {
+            return Some(CommandLine::typed(typed));
+        }
+
+        // (text, cursor offset) per line; a row filled to the last column
+        // continues on the next, as in `structured::rows_text`.
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        let mut line_cursor = None;
+        for idx in start..self.grid.rows.len() {
+            let row = &self.grid.rows[idx];
+            let first_col = if idx == start { start_col as usize } else { 0 };
+            for (col, cell) in row.cells.iter().enumerate().skip(first_col) {
+                if idx == cursor_idx && col == self.cursor.col as usize {
+                    line_cursor = Some(line.chars().count());
+                }
+                if !cell.flags.contains(CellFlags::WIDE_SPACER) {
+                    line.extend(cell.chars());
+                }
+            }
+            let wrapped = row
+                .cells
+                .last()
+                .is_some_and(|last| last.c != ' ' && !last.flags.contains(CellFlags::WIDE_SPACER));
+            if wrapped && idx + 1 < self.grid.rows.len() {
+                continue;
+            }
+            // Trailing blanks are dropped unless the cursor sits past them.
+            let keep = line.trim_end().chars().count().max(line_cursor.unwrap_or(0));
+            let text: String = line.chars().take(keep).collect();
+            lines.push((text, line_cursor.take()));
+            line.clear();
+            if idx >= cursor_idx {
+                break;
+            }
+        }
+        Some(command_line::reconcile(lines, typed))
+    }
+
+    /// Note `line_id` as the top line of the frontend's viewport. Returns
+    /// the block there when it differs from the one last returned.
+    pub fn set_viewport_top(&mut self, line_id: u64) -> Option<ViewportBlock> {
+        let block = self
+            .block_at_line(line_id)
+            .map(|block| ViewportBlock {
+                block_id: Some(block.id.clone()),
+                command: block.command.clone(),
+                cwd: Some(block.cwd.clone()).filter(|cwd| !cwd.is_empty()),
+            })
+            .unwrap_or_default();
+        if block == self.viewport_block {
+            return None;
+        }
+        self.viewport_block = block.clone();
+        Some(block)
+    }
+
+    /// Tell the frontend when a just-finished block printed JSON or a table.
+    fn detect_structured_output(&mut self, block_id: String) {
+        let Some(text) = self.block_output_text(&block_id) else {
+            return;
+        };
+        if let Some(kind) = structured::detect(&text) {
+            self.pending_terminal_events
+                .push(TerminalEvent::BlockStructuredData {
+                    id: block_id,
+                    kind,
+                    sample: structured::sample(&text, kind),
+                });
+        }
+    }
+
+    /// Text of the main-grid lines from `start_line_id` through
+    /// `end_line_id` (inclusive), compressed scrollback included. Returns
+    /// `None` when either line is gone (trimmed from scrollback or cleared
+    /// by a reset).
+    pub fn get_line_range_text(&self, start_line_id: u64, end_line_id: u64) -> Option<String> {
+        let start = self.grid.find_history_line(start_line_id)?;
+        let end = self.grid.find_history_line(end_line_id)?;
+        if end < start {
+            return Some(String::new());
+        }
+        let lines = self
+            .grid
+            .history_rows(start..end + 1)
+            .iter()
+            .map(row_line_text)
+            .collect();
+        Some(join_line_texts(lines))
+    }
+}
+
+/// Text of one row, wide-character spacers left out and trailing blanks
+/// trimmed.
+fn row_line_text(row: &Row) -> String {
+    row.cells
+        .iter()
+        .filter(|c| !c.flags.contains(CellFlags::WIDE_SPACER))
+        .flat_map(Cell::chars)
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+/// Row texts joined by newlines, without trailing empty ones.
+fn join_line_texts(mut lines: Vec<String>) -> String {
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
 }
 
 fn extract_params(params: &vte::Params) -> Vec<u16> {
@@ -1214,6 +3176,40 @@ fn param(params: &[u16], idx: usize, default: u16) -> u16 {
         .unwrap_or(default)
 }
 
+/// Color from the parameters after a `;`-separated SGR 38/48/58 (`2;r;g;b`
+/// or `5;n`), and how many of them it used.
+fn extended_color(params: &[u16]) -> (Option<Color>, usize) {
+    match *params {
+        [2, r, g, b, ..] => (Some(Color::Rgb(r as u8, g as u8, b as u8)), 4),
+        [5, n, ..] => (Some(Color::Indexed(n as u8)), 2),
+        [] => (None, 0),
+        _ => (None, 1),
+    }
+}
+
+/// Color from the `:`-separated form: `2:r:g:b`, `2:<space>:r:g:b` or `5:n`.
+fn colon_color(sub: &[u16]) -> Option<Color> {
+    match *sub {
+        [2, _, r, g, b, ..] | [2, r, g, b] => Some(Color::Rgb(r as u8, g as u8, b as u8)),
+        [5, n, ..] => Some(Color::Indexed(n as u8)),
+        _ => None,
+    }
+}
+
+/// Whether the host of an OSC 7 `file://` URI is this machine.
+fn is_local_host(host: &str) -> bool {
+    static HOSTNAME: std::sync::OnceLock<Option<String>> = std::sync::OnceLock::new();
+    if host.is_empty() || host.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    // Shells report either the short or the fully qualified name.
+    let short = |name: &str| name.split('.').next().unwrap_or(name).to_ascii_lowercase();
+    HOSTNAME
+        .get_or_init(|| hostname::get().ok().and_then(|h| h.into_string().ok()))
+        .as_deref()
+        .is_some_and(|local| short(local) == short(host))
+}
+
 fn decode_hex_ascii(input: &str) -> Option<String> {
     if input.len() % 2 != 0 {
         return None;
@@ -1254,16 +3250,6 @@ fn tcap_capability_value(name: &str) -> Option<&'static str> {
     }
 }
 
-fn write_clipboard_text(text: &str) -> Result<(), ()> {
-    let mut clipboard = arboard::Clipboard::new().map_err(|_| ())?;
-    clipboard.set_text(text.to_string()).map_err(|_| ())
-}
-
-fn read_clipboard_text() -> Option<String> {
-    let mut clipboard = arboard::Clipboard::new().ok()?;
-    clipboard.get_text().ok()
-}
-
 /// Map ASCII to DEC Special Graphics (line-drawing) character.
 fn dec_line_drawing_char(c: char) -> char {
     match c {
@@ -1300,56 +3286,17 @@ impl vte::Perform for TerminalState {
         let c = if self.charset_g0_drawing {
             dec_line_drawing_char(c)
         } else {
-            c
-        };
-        self.last_printed_char = c;
-        let width = UnicodeWidthChar::width(c).unwrap_or(1) as u16;
-
-        if self.cursor.col >= self.cols {
-            if self.modes.autowrap {
-                self.carriage_return();
-                self.linefeed();
-            } else {
-                self.cursor.col = self.cols.saturating_sub(1);
-            }
-        }
-
-        if self.modes.insert {
-            let row = self.cursor.row;
-            let col = self.cursor.col;
-            self.active_grid_mut().insert_cells(row, col, width);
-        }
-
-        let row = self.cursor.row;
-        let col = self.cursor.col;
-        let fg = self.cursor.fg;
-        let bg = self.cursor.bg;
-        let attrs = self.cursor.attrs;
-        let cols = self.cols;
-
-        let cell = Cell {
-            c,
-            fg,
-            bg,
-            attrs,
-            flags: if width == 2 {
-                CellFlags::WIDE_CHAR
-            } else {
-                CellFlags::empty()
-            },
-        };
-
-        let grid = self.active_grid_mut();
-        grid.set_cell(row, col, cell);
-
-        if width == 2 && col + 1 < cols {
-            grid.set_cell(row, col + 1, Cell::wide_spacer());
+            c
+        };
+        if self.extend_cluster(c) {
+            return;
         }
-
-        self.cursor.col += width;
+        self.last_printed_char = c;
+        self.put_char(c);
     }
 
     fn execute(&mut self, byte: u8) {
+        self.margin_wrap_pending = false;
         match byte {
             0x07 => {
                 // BEL: set flag so the next render frame includes a Bell event
@@ -1379,6 +3326,14 @@ impl vte::Perform for TerminalState {
         let is_private = intermediates.contains(&b'?');
         let has_gt = intermediates.contains(&b'>');
         let has_dollar = intermediates.contains(&b'$');
+        if action != 'm' {
+            self.margin_wrap_pending = false;
+        }
+
+        if action == 'p' && intermediates == [b'!'] {
+            self.soft_reset();
+            return;
+        }
 
         // DECRPM / ANSI RQM mode reports
         if action == 'p' && has_dollar {
@@ -1390,11 +3345,73 @@ impl vte::Perform for TerminalState {
             return;
         }
 
+        // Kitty keyboard protocol: CSI > flags u (push), CSI < n u (pop),
+        // CSI = flags ; mode u (set), CSI ? u (query).
+        if action == 'u' && !intermediates.is_empty() {
+            let flags = param(&raw, 0, 0).min(u8::MAX as u16) as u8;
+            if has_gt {
+                self.keyboard_stack_mut().push(flags);
+            } else if intermediates.contains(&b'<') {
+                self.keyboard_stack_mut().pop(param(&raw, 0, 1) as usize);
+            } else if intermediates.contains(&b'=') {
+                self.keyboard_stack_mut().set(flags, param(&raw, 1, 1));
+            } else if is_private {
+                let response = self.keyboard_stack_mut().report();
+                self.pending_responses.push(response);
+            }
+            return;
+        }
+
+        // Rectangular area operations: DECCRA, DECFRA, DECERA, DECSERA.
+        if has_dollar && !is_private {
+            match action {
+                'v' => return self.copy_rect_area(&raw),
+                'x' => return self.fill_rect_area(&raw),
+                'z' => return self.erase_rect_area(&raw, false),
+                '{' => return self.erase_rect_area(&raw, true),
+                _ => {}
+            }
+        }
+
         // Secondary Device Attributes (DA2): CSI > c
         if action == 'c' && has_gt {
             if param(&raw, 0, 0) == 0 {
-                // Report as xterm-like VT100-class terminal with firmware marker.
-                self.pending_responses.push(b"\x1b[>0;10;0c".to_vec());
+                let response = self.device_attributes.secondary_response();
+                self.pending_responses.push(response);
+            }
+            return;
+        }
+
+        // Tertiary Device Attributes (DA3): CSI = c
+        if action == 'c' && intermediates.contains(&b'=') {
+            if param(&raw, 0, 0) == 0 {
+                self.pending_responses.push(device::tertiary_response());
+            }
+            return;
+        }
+
+        // XTVERSION: CSI > q
+        if action == 'q' && has_gt {
+            if param(&raw, 0, 0) == 0 {
+                self.pending_responses.push(device::version_response());
+            }
+            return;
+        }
+
+        // XTMODKEYS: CSI > Pp ; Pv m sets and CSI > Pp n disables a key
+        // modifier resource; XTQMODKEYS (CSI ? Pp m) reports one.
+        if action == 'm' && has_gt {
+            self.set_key_modifiers(&raw, raw.get(1).copied());
+            return;
+        }
+        if action == 'n' && has_gt {
+            self.set_key_modifiers(&raw, None);
+            return;
+        }
+        if action == 'm' && is_private {
+            if raw.first() == Some(&4) {
+                let response = format!("\x1b[>4;{}m", self.modes.modify_other_keys);
+                self.pending_responses.push(response.into_bytes());
             }
             return;
         }
@@ -1412,9 +3429,7 @@ impl vte::Perform for TerminalState {
                 self.cursor.col = 0;
                 self.cursor_up(param(&raw, 0, 1));
             }
-            ('G', false) => {
-                self.cursor.col = (param(&raw, 0, 1) - 1).min(self.cols.saturating_sub(1));
-            }
+            ('G', false) => self.cursor.col = self.origin_col(param(&raw, 0, 1) - 1),
             ('H' | 'f', false) => {
                 let row = param(&raw, 0, 1) - 1;
                 if self.modes.origin {
@@ -1422,10 +3437,12 @@ impl vte::Perform for TerminalState {
                 } else {
                     self.cursor.row = row.min(self.rows.saturating_sub(1));
                 }
-                self.cursor.col = (param(&raw, 1, 1) - 1).min(self.cols.saturating_sub(1));
+                self.cursor.col = self.origin_col(param(&raw, 1, 1) - 1);
             }
             ('J', false) => self.erase_display(param(&raw, 0, 0)),
             ('K', false) => self.erase_line(param(&raw, 0, 0)),
+            ('J', true) => self.selective_erase_display(param(&raw, 0, 0)),
+            ('K', true) => self.selective_erase_line(param(&raw, 0, 0)),
             ('L', false) => self.insert_lines(param(&raw, 0, 1)),
             ('M', false) => self.delete_lines(param(&raw, 0, 1)),
             ('P', false) => self.delete_chars(param(&raw, 0, 1)),
@@ -1452,7 +3469,11 @@ impl vte::Perform for TerminalState {
                 } else {
                     0
                 };
-                self.cursor.col = 0;
+                self.cursor.col = if self.modes.origin {
+                    self.scroll_left
+                } else {
+                    0
+                };
             }
             ('h', true) => self.set_dec_mode(&raw, true),
             ('l', true) => self.set_dec_mode(&raw, false),
@@ -1475,11 +3496,16 @@ impl vte::Perform for TerminalState {
                 }
             }
             ('c', false) => {
-                // Primary Device Attributes - respond as VT220
+                // Primary Device Attributes: conformance level and features.
                 if param(&raw, 0, 0) == 0 {
-                    self.pending_responses.push(b"\x1b[?62;22c".to_vec());
+                    let response = self
+                        .device_attributes
+                        .primary_response(self.device_capabilities());
+                    self.pending_responses.push(response);
                 }
             }
+            ('t', false) if intermediates.is_empty() => self.window_op(&raw),
+            ('s', false) if self.modes.left_right_margin => self.set_lr_margins(&raw),
             ('s', false) => self.save_cursor(),
             ('u', false) => self.restore_cursor(),
             ('q', false) if intermediates.contains(&b' ') => match param(&raw, 0, 1) {
@@ -1488,47 +3514,17 @@ impl vte::Perform for TerminalState {
                 5 | 6 => self.cursor.shape = CursorShape::Bar,
                 _ => {}
             },
+            // DECSCA: 1 protects what is printed next from selective erase.
+            ('q', false) if intermediates.contains(&b'"') => match param(&raw, 0, 0) {
+                0 | 2 => self.cursor.protected = false,
+                1 => self.cursor.protected = true,
+                _ => {}
+            },
             ('b', false) => {
-                let count = param(&raw, 0, 1) as usize;
+                // REP: repeat the last printed character.
                 let c = self.last_printed_char;
-                let width = UnicodeWidthChar::width(c).unwrap_or(1) as u16;
-                for _ in 0..count.min(2048) {
-                    if self.cursor.col >= self.cols {
-                        if self.modes.autowrap {
-                            self.carriage_return();
-                            self.linefeed();
-                        } else {
-                            self.cursor.col = self.cols.saturating_sub(1);
-                        }
-                    }
-                    if self.modes.insert {
-                        let row = self.cursor.row;
-                        let col = self.cursor.col;
-                        self.active_grid_mut().insert_cells(row, col, width);
-                    }
-                    let row = self.cursor.row;
-                    let col = self.cursor.col;
-                    let fg = self.cursor.fg;
-                    let bg = self.cursor.bg;
-                    let attrs = self.cursor.attrs;
-                    let cols = self.cols;
-                    let cell = Cell {
-                        c,
-                        fg,
-                        bg,
-                        attrs,
-                        flags: if width == 2 {
-                            CellFlags::WIDE_CHAR
-                        } else {
-                            CellFlags::empty()
-                        },
-                    };
-                    let grid = self.active_grid_mut();
-                    grid.set_cell(row, col, cell);
-                    if width == 2 && col + 1 < cols {
-                        grid.set_cell(row, col + 1, Cell::wide_spacer());
-                    }
-                    self.cursor.col += width;
+                for _ in 0..param(&raw, 0, 1).min(2048) {
+                    self.put_char(c);
                 }
             }
             _ => {}
@@ -1540,15 +3536,21 @@ impl vte::Perform for TerminalState {
     }
 
     fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        self.margin_wrap_pending = false;
         match (byte, intermediates) {
             (b'c', []) => {
-                let rows = self.rows;
-                let cols = self.cols;
+                let (rows, cols) = (self.rows, self.cols);
                 let was_using_alt = self.using_alt;
-                let frame_seq = self.frame_seq;
-                *self = TerminalState::new(rows, cols);
-                self.frame_seq = frame_seq;
-                self.grid.mark_all_dirty();
+                let next_line_id = self
+                    .alt_grid
+                    .as_ref()
+                    .map_or(0, Grid::next_line_id)
+                    .max(self.grid.next_line_id());
+                // Line IDs keep counting so frontend references from before
+                // the reset can never alias new lines.
+                self.preserve_session_settings(|_| {
+                    Grid::with_first_line_id(rows, cols, next_line_id)
+                });
                 if was_using_alt {
                     self.pending_terminal_events
                         .push(TerminalEvent::AltScreenExited);
@@ -1580,11 +3582,16 @@ impl vte::Perform for TerminalState {
             (b'0', [b'(']) => self.charset_g0_drawing = true,
             // SCS G0: ASCII
             (b'B', [b'(']) => self.charset_g0_drawing = false,
+            // DECDHL top/bottom half, DECSWL, DECDWL
+            (b'3', [b'#']) => self.set_line_size(LineSize::DoubleHeightTop),
+            (b'4', [b'#']) => self.set_line_size(LineSize::DoubleHeightBottom),
+            (b'5', [b'#']) => self.set_line_size(LineSize::Single),
+            (b'6', [b'#']) => self.set_line_size(LineSize::DoubleWidth),
             _ => {}
         }
     }
 
-    fn hook(&mut self, _params: &vte::Params, intermediates: &[u8], _ignore: bool, action: char) {
+    fn hook(&mut self, params: &vte::Params, intermediates: &[u8], _ignore: bool, action: char) {
         self.dcs_buffer.clear();
         self.dcs_intermediates.clear();
         self.dcs_intermediates.extend_from_slice(intermediates);
@@ -1596,6 +3603,7 @@ impl vte::Perform for TerminalState {
         if action == 'q' && intermediates.is_empty() && self.experimental_image_protocols_enabled {
             self.sixel_active = true;
             self.sixel_buffer.clear();
+            self.sixel_transparent = params.iter().nth(1).and_then(|p| p.first()) == Some(&1);
         } else if action == 'q' && intermediates.is_empty() && !self.image_protocol_drop_notified {
             tracing::info!("Sixel payload received but experimental rendering is disabled");
             self.image_protocol_drop_notified = true;
@@ -1621,19 +3629,9 @@ impl vte::Perform for TerminalState {
         if self.sixel_active {
             self.sixel_active = false;
             let data = std::mem::take(&mut self.sixel_buffer);
-            if !data.is_empty() {
-                self.image_counter += 1;
-                let id = format!("sixel-{}", self.image_counter);
-                let encoded = BASE64_STANDARD.encode(&data);
-                self.pending_terminal_events
-                    .push(TerminalEvent::SixelImage {
-                        id,
-                        data_base64: encoded,
-                        width: 0,
-                        height: 0,
-                        row: self.cursor.row,
-                        col: self.cursor.col,
-                    });
+            let background = (!self.sixel_transparent).then(|| self.dynamic_colors.rgb(1));
+            if let Some(image) = sixel::decode(&data, background) {
+                self.place_sixel(image);
             }
             self.dcs_buffer.clear();
             self.dcs_intermediates.clear();
@@ -1652,12 +3650,13 @@ impl vte::Perform for TerminalState {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::render::search::SearchOptions;
+    use crate::terminal::color::SerializableColor;
+    use crate::terminal::cursor::{BlinkRate, UnderlineStyle};
+    use crate::terminal::grid::MAX_SCROLLBACK_LIMIT;
 
     fn feed_bytes(state: &mut TerminalState, bytes: &[u8]) {
-        let mut parser = vte::Parser::new();
-        for &b in bytes {
-            parser.advance(state, b);
-        }
+        state.feed(bytes);
     }
 
     #[test]
@@ -1828,55 +3827,892 @@ mod tests {
         let cell = &state.grid.visible_row(0).cells[0];
         assert_eq!(cell.c, '─', "DEC line drawing: 'q' should map to '─'");
 
-        // Write 'x' which should become '│'
-        feed_bytes(&mut state, b"x");
-        let cell = &state.grid.visible_row(0).cells[1];
-        assert_eq!(cell.c, '│', "DEC line drawing: 'x' should map to '│'");
+        // Write 'x' which should become '│'
+        feed_bytes(&mut state, b"x");
+        let cell = &state.grid.visible_row(0).cells[1];
+        assert_eq!(cell.c, '│', "DEC line drawing: 'x' should map to '│'");
+
+        // Exit DEC line drawing mode
+        feed_bytes(&mut state, b"\x1b(B");
+        assert!(!state.charset_g0_drawing);
+
+        // Now 'q' should be literal 'q'
+        feed_bytes(&mut state, b"q");
+        let cell = &state.grid.visible_row(0).cells[2];
+        assert_eq!(cell.c, 'q', "ASCII mode: 'q' should be literal 'q'");
+    }
+
+    #[test]
+    fn collect_dirty_lines_has_correct_indices() {
+        let mut state = TerminalState::new(10, 20);
+        // Enter alt screen, clear, and draw at specific rows
+        feed_bytes(&mut state, b"\x1b[?1049h\x1b[2J");
+
+        // Collect the initial "all dirty" set and discard
+        let _ = state.take_render_snapshot();
+
+        // Write to specific rows
+        feed_bytes(&mut state, b"\x1b[3;1HAAA\x1b[7;1HBBB");
+
+        let snapshot = state
+            .take_render_snapshot()
+            .expect("should have dirty lines");
+        // Should have exactly 2 dirty lines (rows 2 and 6, 0-based)
+        assert_eq!(snapshot.lines.len(), 2, "should have 2 dirty lines");
+        assert_eq!(
+            snapshot.lines[0].index, 2,
+            "first dirty line should be row 2"
+        );
+        assert_eq!(
+            snapshot.lines[1].index, 6,
+            "second dirty line should be row 6"
+        );
+    }
+
+    #[test]
+    fn small_updates_send_partial_lines() {
+        let mut state = TerminalState::new(10, 20);
+        feed_bytes(&mut state, b"\x1b[?1049h\x1b[2Jclock 12:00");
+        let _ = state.take_render_snapshot();
+
+        // Overwriting two cells sends just them.
+        feed_bytes(&mut state, b"\x1b[1;10H01");
+        let snapshot = state.take_render_snapshot().expect("dirty line");
+        assert_eq!(snapshot.lines.len(), 1);
+        assert_eq!(snapshot.lines[0].start_col, Some(9));
+        assert_eq!(snapshot.lines[0].spans.len(), 1);
+        assert_eq!(snapshot.lines[0].spans[0].text, "01");
+        assert_eq!(snapshot.lines[0].spans[0].cols, 2);
+
+        // A wide character is never split from its spacer.
+        feed_bytes(&mut state, b"\x1b[2;1H\xe7\x95\x8c");
+        let _ = state.take_render_snapshot();
+        let grid = state.alt_grid.as_mut().unwrap();
+        grid.visible_row_mut(1).mark_damaged(1, 2);
+        let snapshot = state.take_render_snapshot().expect("dirty line");
+        assert_eq!(snapshot.lines[0].start_col, Some(0));
+
+        // Rows with links are sent whole.
+        feed_bytes(&mut state, b"\x1b[3;1Hhttps://example.com");
+        let _ = state.take_render_snapshot();
+        feed_bytes(&mut state, b"\x1b[3;9HX");
+        let snapshot = state.take_render_snapshot().expect("dirty line");
+        assert_eq!(snapshot.lines[0].start_col, None);
+    }
+
+    #[test]
+    fn frames_send_each_span_style_once() {
+        let mut state = TerminalState::new(4, 20);
+        let _ = state.take_render_snapshot();
+        feed_bytes(&mut state, b"\x1b[31ma\x1b[0m b \x1b[31mc\r\n\x1b[31md");
+        let frame = state.take_render_snapshot().unwrap().into_frame();
+        let json = serde_json::to_value(&frame).unwrap();
+        assert_eq!(json["styles"].as_array().unwrap().len(), 2);
+        assert_eq!(json["styles"][0]["fg"]["index"], 1);
+        let first = &json["lines"][0]["spans"];
+        assert_eq!(first[0]["style"], 0);
+        assert_eq!(first[1]["style"], 1);
+        assert_eq!(first[2]["style"], 0);
+        assert!(first[0].get("fg").is_none());
+        assert_eq!(json["lines"][1]["spans"][0]["style"], 0);
+    }
+
+    #[test]
+    fn secondary_device_attributes_reports_da2() {
+        let mut state = TerminalState::new(24, 80);
+        feed_bytes(&mut state, b"\x1b[>c");
+        let responses = state.take_pending_responses();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0], b"\x1b[>0;10;0c".to_vec());
+    }
+
+    #[test]
+    fn xtversion_and_da3_identify_rain() {
+        let mut state = TerminalState::new(24, 80);
+        feed_bytes(&mut state, b"\x1b[>q\x1b[=c\x1b[>1q");
+        let responses = state.take_pending_responses();
+        assert_eq!(responses.len(), 2);
+        let version = format!("\x1bP>|Rain({})\x1b\\", env!("CARGO_PKG_VERSION"));
+        assert_eq!(responses[0], version.into_bytes());
+        assert_eq!(responses[1], b"\x1bP!|5241494E\x1b\\".to_vec());
+    }
+
+    #[test]
+    fn kitty_graphics_placement_moves_the_cursor() {
+        let mut state = TerminalState::new(10, 20);
+        state.take_render_snapshot();
+        feed_bytes(&mut state, b"ab\x1b_Ga=T,f=24,s=1,v=1,i=5,c=3,r=2;AAAA\x1b\\cd");
+        assert_eq!(state.take_pending_responses(), vec![b"\x1b_Gi=5;OK\x1b\\".to_vec()]);
+        assert_eq!((state.cursor.row, state.cursor.col), (1, 7));
+        let frame = state.take_render_snapshot().unwrap().into_frame();
+        let json = serde_json::to_value(&frame).unwrap();
+        let event = &json["events"][0];
+        assert_eq!(event["type"], "KittyImage");
+        assert_eq!(event["id"], "kitty-5-0");
+        assert_eq!((event["row"].as_u64(), event["col"].as_u64()), (Some(0), Some(2)));
+        assert_eq!(state.get_text_range(0, 2), "ab\n     cd");
+    }
+
+    #[test]
+    fn sixel_images_are_decoded_and_cover_their_cells() {
+        let mut state = TerminalState::new(10, 20);
+        state.set_cell_pixels(4, 5);
+        state.take_render_snapshot();
+        // 10x12 pixels: three columns and three rows of 4x5 cells.
+        feed_bytes(&mut state, b"ab\x1bP0;1q\"1;1;10;12#1!10~-!10~\x1b\\cd");
+        assert_eq!((state.cursor.row, state.cursor.col), (2, 4));
+        let frame = state.take_render_snapshot().unwrap().into_frame();
+        let json = serde_json::to_value(&frame).unwrap();
+        let event = &json["events"][0];
+        assert_eq!(event["type"], "SixelImage");
+        assert_eq!(
+            (event["width"].as_u64(), event["height"].as_u64()),
+            (Some(10), Some(12))
+        );
+        assert_eq!(
+            (event["columns"].as_u64(), event["rows"].as_u64()),
+            (Some(3), Some(3))
+        );
+        assert_eq!(
+            (event["row"].as_u64(), event["col"].as_u64()),
+            (Some(0), Some(2))
+        );
+        assert_eq!(event["line_id"].as_u64(), Some(state.grid.visible_line_id(0)));
+        let png = BASE64_STANDARD
+            .decode(event["data_base64"].as_str().unwrap())
+            .unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn iterm_files_are_sized_in_cells_or_offered_as_downloads() {
+        let mut state = TerminalState::new(10, 20);
+        state.set_cell_pixels(4, 5);
+        state.set_file_downloads(true);
+        state.take_render_snapshot();
+        // An 8x10 PNG header shown 3 cells wide: 12x15 pixels, 3x3 cells.
+        let png = BASE64_STANDARD.encode(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x08\0\0\0\x0a");
+        let osc = format!(
+            "ab\x1b]1337;File=name=YS5wbmc=;width=3;inline=1:{}\x07",
+            png
+        );
+        feed_bytes(&mut state, osc.as_bytes());
+        assert_eq!((state.cursor.row, state.cursor.col), (2, 5));
+        feed_bytes(
+            &mut state,
+            b"\x1b]1337;File=name=eC50eHQ=;size=5:aGVsbG8=\x07",
+        );
+
+        let frame = state.take_render_snapshot().unwrap().into_frame();
+        let json = serde_json::to_value(&frame).unwrap();
+        let image = &json["events"][0];
+        assert_eq!(image["type"], "InlineImage");
+        assert_eq!(image["name"], "a.png");
+        assert_eq!(
+            (image["width"].as_u64(), image["height"].as_u64()),
+            (Some(12), Some(15))
+        );
+        assert_eq!(
+            (image["columns"].as_u64(), image["rows"].as_u64()),
+            (Some(3), Some(3))
+        );
+        let download = &json["events"][1];
+        assert_eq!(download["type"], "FileDownload");
+        assert_eq!(download["name"], "x.txt");
+        assert_eq!(download["size"], 5);
+        let id = download["id"].as_str().unwrap();
+        assert_eq!(state.take_download(id).unwrap().data, b"hello");
+        assert!(state.take_download(id).is_none());
+    }
+
+    #[test]
+    fn downloads_are_dropped_unless_enabled() {
+        let mut state = TerminalState::new(10, 20);
+        state.take_render_snapshot();
+        let file = b"\x1b]1337;File=name=eC50eHQ=;size=5:aGVsbG8=\x07";
+        feed_bytes(&mut state, file);
+        assert!(state.pending_terminal_events.is_empty());
+        assert!(state.pending_downloads.is_empty());
+
+        state.set_file_downloads(true);
+        feed_bytes(&mut state, file);
+        let frame = state.take_render_snapshot().unwrap().into_frame();
+        let json = serde_json::to_value(&frame).unwrap();
+        let id = json["events"][0]["id"].as_str().unwrap().to_string();
+        state.set_file_downloads(false);
+        assert!(state.take_download(&id).is_none());
+    }
+
+    #[test]
+    fn images_go_to_the_asset_registry_when_the_session_has_one() {
+        let registry = std::sync::Arc::new(parking_lot::Mutex::new(Default::default()));
+        let mut state = TerminalState::new(10, 20);
+        state.set_asset_sink(AssetSink::new("s1", registry.clone()));
+        state.take_render_snapshot();
+        feed_bytes(&mut state, b"\x1b_Ga=T,f=24,s=1,v=1,i=5;AAAA\x1b\\");
+        let frame = state.take_render_snapshot().unwrap().into_frame();
+        let json = serde_json::to_value(&frame).unwrap();
+        let event = &json["events"][0];
+        assert_eq!(event["data_base64"], "");
+        let url = event["url"].as_str().unwrap();
+        let path = &url[url.find("/s1/").unwrap()..];
+        let asset = registry.lock().resolve(path).unwrap();
+        assert_eq!(asset.mime, "image/png");
+    }
+
+    #[test]
+    fn bold_is_bright_only_moves_bold_ansi_colors() {
+        let mut state = TerminalState::new(4, 20);
+        state.set_bold_is_bright(true);
+        feed_bytes(
+            &mut state,
+            b"\x1b[1;34mls\x1b[22m x \x1b[1;94mhi\x1b[1;38;5;4mok",
+        );
+        let snap = state.take_render_snapshot().unwrap();
+        let fgs: Vec<_> = snap.lines[0]
+            .spans
+            .iter()
+            .take(4)
+            .map(|span| serde_json::to_value(&span.fg).unwrap()["index"].clone())
+            .collect();
+        assert_eq!(fgs, vec![12, 4, 12, 12]);
+
+        state.set_bold_is_bright(false);
+        let snap = state.take_render_snapshot().unwrap();
+        assert_eq!(
+            serde_json::to_value(&snap.lines[0].spans[0].fg).unwrap()["index"],
+            4
+        );
+
+        // The setting outlives a full reset (RIS).
+        state.set_bold_is_bright(true);
+        feed_bytes(&mut state, b"\x1bc\x1b[1;31mx");
+        let snap = state.take_render_snapshot().unwrap();
+        assert_eq!(
+            serde_json::to_value(&snap.lines[0].spans[0].fg).unwrap()["index"],
+            9
+        );
+    }
+
+    #[test]
+    fn kitty_keyboard_flags_per_screen() {
+        let mut state = TerminalState::new(24, 80);
+        state.take_render_snapshot();
+        feed_bytes(&mut state, b"\x1b[>1u");
+        let frame = state.take_render_snapshot().expect("flag change emits a frame");
+        assert_eq!(frame.keyboard_flags, 1);
+        feed_bytes(&mut state, b"\x1b[?u");
+        assert_eq!(state.take_pending_responses(), vec![b"\x1b[?1u".to_vec()]);
+
+        feed_bytes(&mut state, b"\x1b[?1049h\x1b[>31u");
+        assert_eq!(state.keyboard_flags(), 31);
+        feed_bytes(&mut state, b"\x1b[?1049l");
+        assert_eq!(state.keyboard_flags(), 1);
+        feed_bytes(&mut state, b"\x1b[<u\x1b[s");
+        assert_eq!(state.keyboard_flags(), 0);
+    }
+
+    #[test]
+    fn modify_other_keys_is_set_reported_and_not_taken_as_sgr() {
+        let mut state = TerminalState::new(24, 80);
+        state.take_render_snapshot();
+        feed_bytes(&mut state, b"\x1b[>4;2mx");
+        assert_eq!(state.modes.modify_other_keys, 2);
+        assert!(state.cursor.attrs.is_empty());
+        let frame = state.take_render_snapshot().unwrap();
+        assert!(frame.events.iter().any(|e| matches!(
+            e,
+            TerminalEvent::MouseModeChanged {
+                modify_other_keys: 2,
+                ..
+            }
+        )));
+
+        feed_bytes(&mut state, b"\x1b[?4m");
+        assert_eq!(state.take_pending_responses(), vec![b"\x1b[>4;2m".to_vec()]);
+        // Other resources and out-of-range levels are ignored.
+        feed_bytes(&mut state, b"\x1b[>1;0m\x1b[>4;7m");
+        assert_eq!(state.modes.modify_other_keys, 2);
+        feed_bytes(&mut state, b"\x1b[>4n");
+        assert_eq!(state.modes.modify_other_keys, 0);
+        feed_bytes(&mut state, b"\x1b[>4;1m\x1b[>m");
+        assert_eq!(state.modes.modify_other_keys, 0);
+    }
+
+    #[test]
+    fn grapheme_clusters_take_one_cell() {
+        let mut state = TerminalState::new(4, 20);
+        // e + combining acute, family ZWJ sequence, flag, heart + VS16, x.
+        feed_bytes(
+            &mut state,
+            "e\u{301}\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}\u{1f1e9}\u{1f1ea}\u{2764}\u{fe0f}x"
+                .as_bytes(),
+        );
+        let cells = &state.grid.visible_row(0).cells;
+        let text = |col: usize| cells[col].chars().collect::<String>();
+        assert_eq!(text(0), "e\u{301}");
+        assert_eq!(text(1), "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}");
+        assert!(cells[2].flags.contains(CellFlags::WIDE_SPACER));
+        assert_eq!(text(3), "\u{1f1e9}\u{1f1ea}");
+        assert!(cells[3].flags.contains(CellFlags::WIDE_CHAR));
+        assert_eq!(text(5), "\u{2764}\u{fe0f}");
+        assert!(cells[6].flags.contains(CellFlags::WIDE_SPACER));
+        assert_eq!(text(7), "x");
+        assert_eq!(state.cursor.col, 8);
+
+        // Overwriting a cell drops what was combined with it.
+        feed_bytes(&mut state, b"\x1b[1;1Ha");
+        assert_eq!(state.grid.visible_row(0).cells[0].combining, None);
+        // Spacing marks keep their own column.
+        feed_bytes(&mut state, "\r\n\u{915}\u{93f}".as_bytes());
+        assert_eq!(state.cursor.col, 2);
+    }
+
+    #[test]
+    fn scrollback_limit_trims_and_survives_reset() {
+        let mut state = TerminalState::new(2, 10);
+        for _ in 0..10 {
+            feed_bytes(&mut state, b"line\r\n");
+        }
+        assert_eq!(state.scrollback_usage().rows, 9);
+
+        let usage = state.set_scrollback_limit(3);
+        assert_eq!((usage.rows, usage.limit), (3, 3));
+        assert!(usage.bytes >= 5 * 10 * std::mem::size_of::<Cell>());
+        feed_bytes(&mut state, b"more\r\n");
+        assert_eq!(state.scrollback_usage().rows, 3);
+
+        feed_bytes(&mut state, b"\x1bc");
+        assert_eq!(state.scrollback_usage().limit, 3);
+        assert_eq!(
+            state.set_scrollback_limit(usize::MAX).limit,
+            MAX_SCROLLBACK_LIMIT
+        );
+    }
+
+    #[test]
+    fn scrollback_memory_budget_drops_the_oldest_rows() {
+        let mut state = TerminalState::new(2, 10);
+        for i in 0..10 {
+            feed_bytes(&mut state, format!("line {}\r\n", i).as_bytes());
+        }
+        let row_bytes = 10 * std::mem::size_of::<Cell>();
+        let usage = state.set_scrollback_max_bytes(Some(4 * row_bytes));
+        assert_eq!((usage.rows, usage.max_bytes), (4, Some(4 * row_bytes)));
+        let oldest: String = state.grid.rows[0].cells[..6].iter().map(|c| c.c).collect();
+        assert_eq!(oldest, "line 5");
+        feed_bytes(&mut state, b"more\r\n");
+        assert_eq!(state.scrollback_usage().rows, 4);
+
+        feed_bytes(&mut state, b"\x1bc");
+        assert_eq!(state.scrollback_usage().max_bytes, Some(4 * row_bytes));
+        for _ in 0..10 {
+            feed_bytes(&mut state, b"x\r\n");
+        }
+        assert_eq!(state.set_scrollback_max_bytes(None).rows, 4);
+
+        // Grapheme clusters count against the budget as well.
+        state.set_scrollback_max_bytes(Some(4 * row_bytes));
+        feed_bytes(&mut state, "e\u{301}\u{302}\r\n\r\n".as_bytes());
+        assert_eq!(state.scrollback_usage().rows, 3);
+    }
+
+    #[test]
+    fn recovery_starts_blank_with_the_session_settings() {
+        let mut state = TerminalState::new(2, 10);
+        state.set_ambiguous_wide(true);
+        state.set_bold_is_bright(true);
+        state.set_scrollback_limit(1234);
+        feed_bytes(&mut state, b"old\r\nlines\r\nhere");
+        let last_line = state.grid.next_line_id();
+
+        let recovered = state.take_for_recovery();
+        assert!(recovered.ambiguous_wide && recovered.bold_is_bright);
+        assert_eq!(recovered.grid.scrollback_limit, 1234);
+        assert_eq!(recovered.size(), (2, 10));
+        assert_eq!(recovered.grid.scrollback_len(), 0);
+        assert!(recovered.grid.visible_row(0).cells.iter().all(|c| c.c == ' '));
+        assert!(recovered.grid.next_line_id() >= last_line);
+    }
+
+    #[test]
+    fn lagging_frontends_bound_pending_output() {
+        let mut state = TerminalState::new(4, 10);
+        // Consumers that never ack are never considered behind.
+        state.frame_seq = FRAME_ACK_PAUSE_GAP * 2;
+        assert!(!state.frontend_backlogged());
+        state.ack_frame(1);
+        assert!(state.frontend_backlogged());
+        state.ack_frame(state.frame_seq);
+        assert!(!state.frontend_backlogged());
+
+        // A frontend still behind after a whole pause gets keyframes while
+        // reading goes on, until it catches up.
+        state.ack_frame(1);
+        state.frame_seq = FRAME_ACK_PAUSE_GAP * 3;
+        state.expire_read_pause();
+        assert!(!state.frontend_backlogged());
+        assert!(state.keyframe_mode);
+        state.ack_frame(FRAME_ACK_PAUSE_GAP * 2);
+        assert!(!state.frontend_backlogged() && state.keyframe_mode);
+        state.ack_frame(state.frame_seq);
+        assert!(!state.keyframe_mode);
+        state.frame_seq += FRAME_ACK_PAUSE_GAP + 1;
+        assert!(state.frontend_backlogged());
+
+        let output = "x\r\n".repeat(MAX_PENDING_SCROLLED_LINES + 100);
+        feed_bytes(&mut state, output.as_bytes());
+        assert_eq!(state.scrolled_off_buffer.len(), MAX_PENDING_SCROLLED_LINES);
+        assert!(state.grid.scrollback_len() > MAX_PENDING_SCROLLED_LINES);
+    }
+
+    #[test]
+    fn resizing_columns_rewraps_lines() {
+        let mut state = TerminalState::new(4, 10);
+        feed_bytes(&mut state, b"abcdefghijklmno\r\nxy");
+        let lines = |state: &TerminalState| -> Vec<String> {
+            state
+                .grid
+                .rows
+                .iter()
+                .map(|row| row.cells.iter().flat_map(Cell::chars).collect::<String>())
+                .map(|text| text.trim_end().to_string())
+                .collect()
+        };
+        let first_line = state.grid.rows[0].line_id;
+
+        state.resize(4, 5);
+        assert_eq!(lines(&state), ["abcde", "fghij", "klmno", "xy", ""]);
+        assert_eq!((state.cursor.row, state.cursor.col), (2, 2));
+        assert_eq!(state.grid.rows[0].line_id, first_line);
+        // Global rows still line up with the rewrapped scrollback, which the
+        // frontend is told to refetch.
+        assert_eq!(state.scrollback_seq, 1);
+        assert_eq!(state.grid.scrollback_len(), 1);
+        assert!(state.scrolled_off_buffer.is_empty());
+        assert!(state
+            .pending_terminal_events
+            .iter()
+            .any(|event| matches!(event, TerminalEvent::ScrollbackRewrapped)));
+
+        feed_bytes(&mut state, b"z");
+        state.resize(4, 10);
+        assert_eq!(lines(&state), ["abcdefghij", "klmno", "xyz", "", "", ""]);
+        assert_eq!((state.cursor.row, state.cursor.col), (0, 3));
+        assert!(state.grid.rows[0].is_wrapped());
+        assert!(!state.grid.rows[1].is_wrapped());
+        // The screen keeps its blank rows below the cursor, so two rows sit
+        // in scrollback now and are numbered from the same first row.
+        assert_eq!(state.grid.scrollback_len(), 2);
+        assert_eq!(state.scrollback_seq, 2);
+    }
+
+    #[test]
+    fn scrollback_search_reports_global_rows() {
+        let mut state = TerminalState::new(3, 20);
+        feed_bytes(&mut state, b"one\r\ntwo\r\nthree\r\nfour\r\nfive");
+        let query = |text: &str, regex: bool| {
+            let options = SearchOptions {
+                regex,
+                ..SearchOptions::default()
+            };
+            TextQuery::new(text, options).unwrap().unwrap()
+        };
+
+        let found = state.search_scrollback(&query("T", false));
+        let rows: Vec<_> = found.iter().map(|m| (m.global_row, m.col)).collect();
+        assert_eq!(rows, vec![(1, 0), (2, 0)]);
+        assert_eq!(found[1].context, "three");
+        assert_eq!(found[1].line_id, state.grid.rows[2].line_id);
+
+        let found = state.search_scrollback(&query("^f", true));
+        let rows: Vec<_> = found.iter().map(|m| m.global_row).collect();
+        assert_eq!(rows, vec![3, 4]);
+        assert_eq!(state.grid.visible_line_id(2), found[1].line_id);
+    }
+
+    #[test]
+    fn scrollback_pages_skip_trimmed_rows() {
+        let mut state = TerminalState::new(2, 10);
+        state.set_scrollback_limit(3);
+        for n in 0..6 {
+            feed_bytes(&mut state, format!("row{}\r\n", n).as_bytes());
+        }
+        // Five rows scrolled off and only the last three are kept.
+        let page = state.scrollback_range(0, 2);
+        assert_eq!((page.start_global_row, page.first_global_row), (2, 2));
+        assert_eq!(page.visible_base_global, 5);
+        assert_eq!(page.lines.len(), 0);
+
+        let page = state.scrollback_range(3, 100);
+        let text: Vec<String> = page
+            .lines
+            .iter()
+            .map(|line| line.spans.iter().map(|s| s.text.as_str()).collect())
+            .collect();
+        assert_eq!(text.len(), 2);
+        assert!(text[0].starts_with("row3") && text[1].starts_with("row4"));
+        assert_eq!(page.lines[1].index, 1);
+    }
+
+    #[test]
+    fn compressed_scrollback_still_pages_and_searches() {
+        let mut state = TerminalState::new(2, 12);
+        state.set_scrollback_compression(true);
+        for n in 0..3200 {
+            feed_bytes(&mut state, format!("row{}\r\n", n).as_bytes());
+        }
+        // The oldest 1024 of the 3199 scrollback rows are compressed.
+        assert_eq!(state.scrollback_usage().rows, 3199);
+        assert_eq!(state.grid.rows.len(), 3199 - 1024 + 2);
+
+        let text = |page: &ScrollbackPage| -> Vec<String> {
+            page.lines
+                .iter()
+                .map(|line| line.spans.iter().map(|s| s.text.trim_end()).collect())
+                .collect()
+        };
+        let page = state.scrollback_range(1022, 4);
+        assert_eq!(text(&page), ["row1022", "row1023", "row1024", "row1025"]);
+
+        let query = TextQuery::new("row1000", SearchOptions::default())
+            .unwrap()
+            .unwrap();
+        let found = state.search_scrollback(&query);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].global_row, 1000);
+
+        state.set_scrollback_compression(false);
+        assert_eq!(state.grid.rows.len(), 3199 + 2);
+        assert_eq!(text(&state.scrollback_range(0, 1)), ["row0"]);
+    }
+
+    #[test]
+    fn blocks_in_compressed_scrollback_still_copy() {
+        let mut state = TerminalState::new(2, 20);
+        state.set_scrollback_compression(true);
+        feed_bytes(
+            &mut state,
+            b"\x1b]133;A\x07$ \x1b]133;B\x07make\r\n\x1b]133;C\x07",
+        );
+        feed_bytes(&mut state, b"built\r\ndone\x1b]133;D;0\x07\r\n");
+        let make = state.shell.blocks().next().unwrap().clone();
+        for n in 0..3200 {
+            feed_bytes(&mut state, format!("row{}\r\n", n).as_bytes());
+        }
+        assert!(state.grid.find_line(make.prompt_line_id).is_none());
+
+        let options = CopyOptions {
+            trim_trailing_whitespace: true,
+            strip_prompts: true,
+            fenced: false,
+        };
+        assert_eq!(
+            state.copy_block(&make.id, true, options).as_deref(),
+            Some("make\nbuilt\ndone")
+        );
+        let (_, rows) = state.block_rows(&make.id).unwrap();
+        let texts: Vec<String> = rows.iter().map(row_line_text).collect();
+        assert_eq!(texts, ["$ make", "built", "done"]);
+        assert!(state
+            .block_markdown(&make.id, 10)
+            .unwrap()
+            .contains("built"));
+    }
+
+    #[test]
+    fn compressed_scrollback_reflows_and_exports() {
+        let mut state = TerminalState::new(2, 20);
+        state.set_scrollback_compression(true);
+        for n in 0..3200 {
+            feed_bytes(&mut state, format!("line {:04} of text\r\n", n).as_bytes());
+        }
+        state.resize(2, 8);
+        state.resize(2, 20);
+        let text = state.scrollback_text();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3200);
+        assert_eq!(lines[0], "line 0000 of text");
+        assert_eq!(lines[3199], "line 3199 of text");
+    }
+
+    #[test]
+    fn ambiguous_width_characters_follow_the_setting() {
+        let mut state = TerminalState::new(4, 20);
+        feed_bytes(&mut state, "\u{25cb}a".as_bytes());
+        assert_eq!(state.cursor.col, 2);
+
+        state.set_ambiguous_wide(true);
+        feed_bytes(&mut state, "\r\n\u{25cb}\u{2192}a".as_bytes());
+        let row = state.grid.visible_row(1);
+        assert!(row.cells[0].flags.contains(CellFlags::WIDE_CHAR));
+        assert!(row.cells[3].flags.contains(CellFlags::WIDE_SPACER));
+        assert_eq!(state.cursor.col, 5);
+        // Spans split where the width changes, so renderers can size them.
+        let spans = row.to_styled_spans();
+        assert_eq!(
+            (spans[0].text.as_str(), spans[0].cols),
+            ("\u{25cb}\u{2192}", 4)
+        );
+        assert!(spans[1].text.starts_with('a'));
+
+        // A full reset keeps the session setting.
+        feed_bytes(&mut state, "\x1bc\u{25cb}".as_bytes());
+        assert_eq!(state.cursor.col, 2);
+    }
+
+    #[test]
+    fn decstr_resets_modes_but_keeps_the_screen() {
+        let mut state = TerminalState::new(10, 40);
+        feed_bytes(&mut state, b"one\r\ntwo\r\n");
+        feed_bytes(
+            &mut state,
+            b"\x1b[?25l\x1b[4h\x1b[?6h\x1b[?7l\x1b[?1h\x1b[3;8r\x1b(0\x1b[1;31m\x1b7\x1b[5;6H",
+        );
+        state.take_render_snapshot();
 
-        // Exit DEC line drawing mode
-        feed_bytes(&mut state, b"\x1b(B");
+        feed_bytes(&mut state, b"\x1b[!p");
+        assert!(state.modes.cursor_visible);
+        assert!(!state.modes.insert);
+        assert!(!state.modes.origin);
+        assert!(state.modes.autowrap);
+        assert!(!state.modes.cursor_keys_application);
+        assert_eq!((state.scroll_top, state.scroll_bottom), (0, 9));
         assert!(!state.charset_g0_drawing);
+        assert!(state.cursor.attrs.is_empty());
+        assert_eq!(state.cursor.fg, Color::Default);
+        // The cursor stays where origin mode put it.
+        assert_eq!((state.cursor.row, state.cursor.col), (6, 5));
+        assert_eq!(state.grid.visible_row(0).cells[0].c, 'o');
 
-        // Now 'q' should be literal 'q'
-        feed_bytes(&mut state, b"q");
-        let cell = &state.grid.visible_row(0).cells[2];
-        assert_eq!(cell.c, 'q', "ASCII mode: 'q' should be literal 'q'");
+        // The saved cursor is gone, so DECRC leaves the cursor in place.
+        feed_bytes(&mut state, b"\x1b8");
+        assert_eq!((state.cursor.row, state.cursor.col), (6, 5));
+        let frame = state.take_render_snapshot().unwrap();
+        assert!(frame
+            .events
+            .iter()
+            .any(|e| matches!(e, TerminalEvent::MouseModeChanged { .. })));
     }
 
     #[test]
-    fn collect_dirty_lines_has_correct_indices() {
-        let mut state = TerminalState::new(10, 20);
-        // Enter alt screen, clear, and draw at specific rows
-        feed_bytes(&mut state, b"\x1b[?1049h\x1b[2J");
+    fn decscnm_toggles_reverse_video_in_frames() {
+        let mut state = TerminalState::new(24, 80);
+        state.take_render_snapshot();
+        feed_bytes(&mut state, b"\x1b[?5h");
+        let frame = state.take_render_snapshot().expect("mode change emits a frame");
+        assert!(frame.reverse_video);
+        feed_bytes(&mut state, b"\x1b[?5$p");
+        assert_eq!(state.take_pending_responses(), vec![b"\x1b[?5;1$y".to_vec()]);
+        assert!(state.take_render_snapshot().is_none());
+        feed_bytes(&mut state, b"\x1bc");
+        assert!(!state.take_render_snapshot().unwrap().reverse_video);
+    }
 
-        // Collect the initial "all dirty" set and discard
-        let _ = state.take_render_snapshot();
+    #[test]
+    fn deccolm_switches_between_132_and_80_columns() {
+        let mut state = TerminalState::new(24, 100);
+        feed_bytes(&mut state, b"hello\x1b[5;10r\x1b[10;20H");
+        state.take_render_snapshot();
+
+        feed_bytes(&mut state, b"\x1b[?3h");
+        assert_eq!(state.size(), (24, 132));
+        assert!(state.modes.column_132);
+        assert_eq!((state.cursor.row, state.cursor.col), (0, 0));
+        assert_eq!((state.scroll_top, state.scroll_bottom), (0, 23));
+        assert_eq!(state.grid.visible_row(0).cells[0].c, ' ');
+        let frame = state.take_render_snapshot().unwrap();
+        assert_eq!(frame.visible_cols, 132);
+        assert!(frame
+            .events
+            .iter()
+            .any(|e| matches!(e, TerminalEvent::ColumnModeChanged { cols: 132 })));
 
-        // Write to specific rows
-        feed_bytes(&mut state, b"\x1b[3;1HAAA\x1b[7;1HBBB");
+        feed_bytes(&mut state, b"\x1b[?3$p\x1b[?3l\x1b[?3$p");
+        assert_eq!(
+            state.take_pending_responses(),
+            vec![b"\x1b[?3;1$y".to_vec(), b"\x1b[?3;2$y".to_vec()]
+        );
+        assert_eq!(state.size(), (24, 80));
+    }
 
-        let snapshot = state
-            .take_render_snapshot()
-            .expect("should have dirty lines");
-        // Should have exactly 2 dirty lines (rows 2 and 6, 0-based)
-        assert_eq!(snapshot.lines.len(), 2, "should have 2 dirty lines");
+    #[test]
+    fn restart_keeps_output_below_a_marker() {
+        let mut state = TerminalState::new(4, 30);
+        let text = |state: &TerminalState, row: u16| -> String {
+            let row = state.grid.visible_row(row);
+            row.cells.iter().map(|c| c.c).collect::<String>().trim_end().to_string()
+        };
+        feed_bytes(&mut state, b"one\r\ntwo\r\n\x1b[?5h\x1b[?1049hvim");
+        let frame_seq = state.take_render_snapshot().unwrap().frame_seq;
+
+        state.restart();
+        assert!(!state.using_alt);
+        assert!(!state.modes.reverse_video);
+        assert_eq!(text(&state, 0), "one");
+        assert_eq!(text(&state, 1), "two");
+        assert_eq!(text(&state, 2), "── Shell restarted ──");
+        assert_eq!((state.cursor.row, state.cursor.col), (3, 0));
+        assert!(state.grid.visible_row(2).cells[0].attrs.contains(CellAttrs::DIM));
+        assert!(state.cursor.attrs.is_empty());
+        let frame = state.take_render_snapshot().unwrap();
+        assert!(frame.frame_seq > frame_seq);
+        assert!(frame
+            .events
+            .iter()
+            .any(|e| matches!(e, TerminalEvent::AltScreenExited)));
+
+        // A full screen scrolls to make room for the marker.
+        feed_bytes(&mut state, b"$ ");
+        state.restart();
+        assert_eq!(state.scrollback_seq, 2);
+        assert_eq!(text(&state, 1), "$");
+        assert_eq!(text(&state, 2), "── Shell restarted ──");
+        assert_eq!(state.cursor.row, 3);
+    }
+
+    #[test]
+    fn xtwinops_reports_and_title_stack() {
+        let mut state = TerminalState::new(24, 80);
+        feed_bytes(&mut state, b"\x1b[14t\x1b[18t");
         assert_eq!(
-            snapshot.lines[0].index, 2,
-            "first dirty line should be row 2"
+            state.take_pending_responses(),
+            vec![b"\x1b[8;24;80t".to_vec()]
         );
+        state.set_cell_pixels(9, 18);
+        feed_bytes(&mut state, b"\x1b[14t\x1b[16t");
         assert_eq!(
-            snapshot.lines[1].index, 6,
-            "second dirty line should be row 6"
+            state.take_pending_responses(),
+            vec![b"\x1b[4;432;720t".to_vec(), b"\x1b[6;18;9t".to_vec()]
         );
+
+        feed_bytes(&mut state, b"\x1b]2;shell\x07\x1b[22;0t\x1b]2;vim\x07");
+        state.title_changed = false;
+        feed_bytes(&mut state, b"\x1b[23;0t");
+        assert_eq!(state.title, "shell");
+        assert!(state.title_changed);
+        feed_bytes(&mut state, b"\x1b[23;0t");
+        assert_eq!(state.title, "shell");
     }
 
     #[test]
-    fn secondary_device_attributes_reports_da2() {
-        let mut state = TerminalState::new(24, 80);
-        feed_bytes(&mut state, b"\x1b[>c");
-        let responses = state.take_pending_responses();
-        assert_eq!(responses.len(), 1);
-        assert_eq!(responses[0], b"\x1b[>0;10;0c".to_vec());
+    fn left_right_margins_constrain_scrolling_and_wrapping() {
+        let mut state = TerminalState::new(3, 10);
+        let text = |state: &TerminalState, row: u16| -> String {
+            state
+                .grid
+                .visible_row(row)
+                .cells
+                .iter()
+                .map(|c| c.c)
+                .collect()
+        };
+        feed_bytes(&mut state, b"AAAAAAAAAA\r\nBBBBBBBBBB\r\nCCCCCCCCCC");
+        // Without DECLRMM, CSI s still saves the cursor.
+        feed_bytes(&mut state, b"\x1b[3;6s");
+        assert_eq!(state.scroll_right, 9);
+
+        feed_bytes(&mut state, b"\x1b[?69h\x1b[3;6s");
+        assert_eq!((state.scroll_left, state.scroll_right), (2, 5));
+        assert_eq!((state.cursor.row, state.cursor.col), (0, 0));
+
+        // Scrolling moves only the columns between the margins.
+        feed_bytes(&mut state, b"\x1b[3;3H\n");
+        assert_eq!(text(&state, 0), "AABBBBAAAA");
+        assert_eq!(text(&state, 1), "BBCCCCBBBB");
+        assert_eq!(text(&state, 2), "CC    CCCC");
+
+        // Text wraps at the right margin back to the left margin.
+        feed_bytes(&mut state, b"\x1b[1;3Hxyzwv");
+        assert_eq!(text(&state, 0), "AAxyzwAAAA");
+        assert_eq!(text(&state, 1), "BBvCCCBBBB");
+
+        // Printing just right of the margin doesn't wrap.
+        feed_bytes(&mut state, b"\x1b[3;7H|");
+        assert_eq!(text(&state, 2), "CC    |CCC");
+
+        // ICH/DCH shift cells only up to the right margin.
+        feed_bytes(&mut state, b"\x1b[2;3H\x1b[2@");
+        assert_eq!(text(&state, 1), "BB  vCBBBB");
+        feed_bytes(&mut state, b"\x1b[3P");
+        assert_eq!(text(&state, 1), "BBC   BBBB");
+
+        feed_bytes(&mut state, b"\x1b[?69l");
+        assert_eq!((state.scroll_left, state.scroll_right), (0, 9));
+    }
+
+    #[test]
+    fn rectangular_area_operations() {
+        let mut state = TerminalState::new(4, 8);
+        let text = |state: &TerminalState, row: u16| -> String {
+            state
+                .grid
+                .visible_row(row)
+                .cells
+                .iter()
+                .map(|c| c.c)
+                .collect()
+        };
+        feed_bytes(&mut state, b"abcdefgh\r\nijklmnop\r\nqrstuvwx\r\nyz");
+
+        // DECCRA: overlapping copy one cell down and right.
+        feed_bytes(&mut state, b"\x1b[1;1;2;3;1;2;2;1$v");
+        assert_eq!(text(&state, 0), "abcdefgh");
+        assert_eq!(text(&state, 1), "iabcmnop");
+        assert_eq!(text(&state, 2), "qijkuvwx");
+
+        // DECFRA fills with the current rendition.
+        feed_bytes(&mut state, b"\x1b[1m\x1b[42;2;6;3;7$x\x1b[m");
+        assert_eq!(text(&state, 1), "iabcm**p");
+        assert_eq!(text(&state, 2), "qijku**x");
+        assert!(state.grid.visible_row(1).cells[5].attrs.contains(CellAttrs::BOLD));
+        // Control characters aren't fill characters.
+        feed_bytes(&mut state, b"\x1b[10;1;1;1;1$x");
+        assert_eq!(text(&state, 0), "abcdefgh");
+
+        // DECSERA keeps the rendition, DECERA resets it; the bottom-right
+        // corner defaults to the end of the page.
+        feed_bytes(&mut state, b"\x1b[2;6;2;6${");
+        assert_eq!(text(&state, 1), "iabcm *p");
+        assert!(state.grid.visible_row(1).cells[5].attrs.contains(CellAttrs::BOLD));
+        feed_bytes(&mut state, b"\x1b[3;7$z");
+        assert_eq!(text(&state, 2), "qijku*  ");
+        assert_eq!(text(&state, 3), "yz      ");
+        assert!(state.grid.visible_row(2).cells[6].attrs.is_empty());
+        assert_eq!(text(&state, 0), "abcdefgh");
+    }
+
+    #[test]
+    fn selective_erase_skips_protected_cells() {
+        let mut state = TerminalState::new(3, 8);
+        let text = |state: &TerminalState, row: u16| -> String {
+            state
+                .grid
+                .visible_row(row)
+                .cells
+                .iter()
+                .map(|c| c.c)
+                .collect()
+        };
+        // "ab" and "e" protected; the rest isn't.
+        feed_bytes(&mut state, b"\x1b[1\"qab\x1b[0\"qcd\x1b[1\"qe\x1b[2\"qfgh");
+        feed_bytes(&mut state, b"\x1b[31mijklmnop\x1b[m\r\nqrstuvwx");
+
+        // DECSEL from the cursor to the end of the line.
+        feed_bytes(&mut state, b"\x1b[1;2H\x1b[?K");
+        assert_eq!(text(&state, 0), "ab  e   ");
+        // DECSED of the whole screen keeps the rendition of what it blanks.
+        feed_bytes(&mut state, b"\x1b[?2J");
+        assert_eq!(text(&state, 0), "ab  e   ");
+        assert_eq!(text(&state, 1), "        ");
+        assert_eq!(state.grid.visible_row(1).cells[0].fg, Color::Indexed(1));
+        // Plain ED erases protected cells too, and DECRQSS reports DECSCA.
+        feed_bytes(&mut state, b"\x1b[2J\x1bP$q\"q\x1b\\");
+        assert_eq!(text(&state, 0), "        ");
+        assert_eq!(
+            state.take_pending_responses().last(),
+            Some(&b"\x1bP1$r0\"q\x1b\\".to_vec())
+        );
     }
 
     #[test]
@@ -1900,6 +4736,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn synchronized_output_holds_frames_until_it_ends() {
+        let mut state = TerminalState::new(24, 80);
+        let _ = state.take_render_snapshot();
+        feed_bytes(&mut state, b"\x1b[?2026hhalf");
+        assert!(state.synchronized_update_remaining().is_some());
+        assert!(state.take_render_snapshot().is_none());
+        feed_bytes(&mut state, b" done\x1b[?2026l");
+        assert_eq!(state.synchronized_update_remaining(), None);
+        assert!(state.take_render_snapshot().is_some());
+        let row: String = state.grid.visible_row(0).cells[..9]
+            .iter()
+            .map(|c| c.c)
+            .collect();
+        assert_eq!(row, "half done");
+
+        // A program that never ends the update only delays frames.
+        feed_bytes(&mut state, b"\x1b[?2026hstuck");
+        assert!(state.take_render_snapshot().is_none());
+        state.sync_update_started = Some(Instant::now() - SYNC_UPDATE_TIMEOUT);
+        assert!(state.take_render_snapshot().is_some());
+    }
+
     #[test]
     fn xtgettcap_reports_known_capabilities() {
         let mut state = TerminalState::new(24, 80);
@@ -1978,6 +4837,60 @@ mod tests {
         assert_eq!(state.cursor.fg, Color::Rgb(128, 64, 32));
     }
 
+    #[test]
+    fn styled_underlines_carry_their_own_color() {
+        let mut state = TerminalState::new(2, 20);
+        feed_bytes(&mut state, b"\x1b[4:3;58:2::255:0:0mtypo\x1b[4:0m ");
+        feed_bytes(&mut state, b"\x1b[4;58;5;4mok\x1b[59;21mdbl\x1b[0mx");
+        let spans = state.grid.visible_row(0).to_styled_spans();
+        let styles: Vec<_> = spans
+            .iter()
+            .map(|span| (span.text.as_str(), span.underline, span.underline_style))
+            .collect();
+        assert_eq!(
+            &styles[..4],
+            &[
+                ("typo", true, UnderlineStyle::Curly),
+                (" ", false, UnderlineStyle::Single),
+                ("ok", true, UnderlineStyle::Single),
+                ("dbl", true, UnderlineStyle::Double),
+            ]
+        );
+        assert!(matches!(
+            spans[0].underline_color,
+            Some(SerializableColor::Rgb { r: 255, g: 0, b: 0 })
+        ));
+        assert!(spans[1].underline_color.is_none());
+        assert!(matches!(
+            spans[2].underline_color,
+            Some(SerializableColor::Indexed { index: 4 })
+        ));
+        assert!(spans[3].underline_color.is_none());
+        assert_eq!(state.cursor.underline_color, Color::Default);
+    }
+
+    #[test]
+    fn blink_rates_reach_the_spans() {
+        let mut state = TerminalState::new(2, 20);
+        feed_bytes(
+            &mut state,
+            b"\x1b[5mslow\x1b[6mfast\x1b[5;6mboth\x1b[25mnone",
+        );
+        let spans = state.grid.visible_row(0).to_styled_spans();
+        let blinks: Vec<_> = spans
+            .iter()
+            .map(|span| (span.text.trim_end(), span.blink))
+            .collect();
+        assert_eq!(
+            &blinks[..3],
+            &[
+                ("slow", Some(BlinkRate::Slow)),
+                ("fastboth", Some(BlinkRate::Rapid)),
+                ("none", None),
+            ]
+        );
+    }
+
     #[test]
     fn test_cursor_save_restore() {
         let mut state = TerminalState::new(24, 80);
@@ -2073,4 +4986,548 @@ mod tests {
             .collect();
         assert_eq!(text, "AAAA", "1 original + 3 repeated 'A's");
     }
+
+    #[test]
+    fn test_hibernate_and_rehydrate_scrollback() {
+        let mut state = TerminalState::new(3, 10);
+        for i in 0..6 {
+            feed_bytes(&mut state, format!("line{}\r\n", i).as_bytes());
+        }
+        let scrollback = state.grid.scrollback_len();
+        assert!(scrollback > 0);
+
+        let path = std::env::temp_dir().join(format!("rain-hibernate-{}.bin", std::process::id()));
+        assert_eq!(state.hibernate(path.clone()).unwrap(), scrollback);
+        assert!(state.is_hibernated());
+        assert_eq!(state.grid.scrollback_len(), 0);
+        assert!(path.exists());
+
+        state.ensure_resident();
+        assert!(!state.is_hibernated());
+        assert!(!path.exists(), "backing file is removed after rehydration");
+        assert_eq!(state.grid.scrollback_len(), scrollback);
+        let first: String = state.grid.rows[0]
+            .cells
+            .iter()
+            .take(5)
+            .map(|c| c.c)
+            .collect();
+        assert_eq!(first, "line0");
+    }
+
+    #[test]
+    fn hibernation_takes_compressed_scrollback_along() {
+        let mut state = TerminalState::new(2, 12);
+        state.set_scrollback_compression(true);
+        for n in 0..3200 {
+            feed_bytes(&mut state, format!("row{}\r\n", n).as_bytes());
+        }
+        let before = state.scrollback_usage();
+
+        let path =
+            std::env::temp_dir().join(format!("rain-hibernate-z-{}.bin", std::process::id()));
+        assert_eq!(state.hibernate(path.clone()).unwrap(), 3199);
+        assert_eq!(state.scrollback_usage().rows, 0);
+        assert!(state.grid.memory_bytes() < before.bytes / 10);
+
+        state.ensure_resident();
+        assert_eq!(state.scrollback_usage(), before);
+        assert_eq!(state.get_line_range_text(1, 1).as_deref(), Some("row0"));
+    }
+
+    #[test]
+    fn test_custom_osc_forwarded_only_when_registered() {
+        let mut state = TerminalState::new(24, 80);
+        feed_bytes(&mut state, b"\x1b]7770;build;done\x07");
+        assert!(state.pending_terminal_events.is_empty());
+
+        state.set_custom_osc_codes(vec![7770]);
+        feed_bytes(&mut state, b"\x1b]7770;build;done\x07");
+        match state.pending_terminal_events.as_slice() {
+            [TerminalEvent::CustomOsc { code, payload }] => {
+                assert_eq!(*code, 7770);
+                assert_eq!(payload, "build;done");
+            }
+            other => panic!("expected one CustomOsc event, got {}", other.len()),
+        }
+
+        // Registration survives a full reset (RIS).
+        feed_bytes(&mut state, b"\x1bc");
+        state.pending_terminal_events.clear();
+        feed_bytes(&mut state, b"\x1b]7770\x07");
+        assert!(matches!(
+            state.pending_terminal_events.as_slice(),
+            [TerminalEvent::CustomOsc { code: 7770, payload }] if payload.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_frame_ack_gap_switches_to_keyframes() {
+        let mut state = TerminalState::new(4, 10);
+        state.take_render_snapshot();
+
+        // Without any ack, frames stay incremental.
+        for _ in 0..(FRAME_ACK_KEYFRAME_GAP + 5) {
+            feed_bytes(&mut state, b"x");
+            let snap = state.take_render_snapshot().unwrap();
+            assert!(!snap.keyframe);
+        }
+
+        state.ack_frame(1);
+        feed_bytes(&mut state, b"y");
+        let snap = state.take_render_snapshot().unwrap();
+        assert!(snap.keyframe);
+        assert_eq!(snap.lines.len(), 4, "keyframe carries the full viewport");
+
+        state.ack_frame(snap.frame_seq);
+        feed_bytes(&mut state, b"z");
+        let snap = state.take_render_snapshot().unwrap();
+        assert!(!snap.keyframe);
+        assert_eq!(snap.lines.len(), 1);
+    }
+
+    #[test]
+    fn test_idle_at_prompt_until_input() {
+        let mut state = TerminalState::new(4, 20);
+        assert!(!state.shell.is_idle_at_prompt(), "no shell integration yet");
+        feed_bytes(&mut state, b"\x1b]7;file:///tmp/x/\x07\x1b]133;A\x07");
+        assert!(state.shell.is_idle_at_prompt());
+        assert_eq!(state.shell.local_cwd(), Some(std::path::Path::new("/tmp/x/")));
+
+        state.shell.note_input(b"ls");
+        assert!(!state.shell.is_idle_at_prompt());
+        feed_bytes(&mut state, b"\x1b]133;A\x07");
+        assert!(state.shell.is_idle_at_prompt());
+
+        feed_bytes(&mut state, b"\x1b]133;B;sleep 5\x07\x1b]133;C\x07");
+        assert!(!state.shell.is_idle_at_prompt());
+        feed_bytes(&mut state, b"\x1b]7;file://elsewhere.example/tmp\x07");
+        assert_eq!(state.shell.local_cwd(), None);
+    }
+
+    #[test]
+    fn test_os_cwd_is_only_taken_until_the_shell_reports_one() {
+        let mut state = TerminalState::new(4, 20);
+        // A warm shell's seed doesn't count as a report.
+        state.shell.set_cwd("/tmp/seed".to_string(), true);
+        assert!(!state.shell.reports_cwd());
+        assert!(state.shell.adopt_os_cwd("/tmp/a".to_string()));
+        assert!(!state.shell.adopt_os_cwd("/tmp/a".to_string()));
+        assert!(state.shell.adopt_os_cwd("/tmp/b".to_string()));
+
+        feed_bytes(&mut state, b"\x1b]7;file:///tmp/c\x07");
+        assert!(state.shell.reports_cwd());
+        assert!(!state.shell.adopt_os_cwd("/tmp/d".to_string()));
+        assert_eq!(state.shell.local_cwd(), Some(std::path::Path::new("/tmp/c")));
+    }
+
+    #[test]
+    fn test_queued_commands_wait_for_an_idle_prompt() {
+        let mut state = TerminalState::new(4, 20);
+        feed_bytes(&mut state, b"\x1b]133;A\x07\x1b]133;B;make\x07\x1b]133;C\x07");
+        let first = state.shell.queue_command("make test".to_string());
+        let second = state.shell.queue_command("make lint".to_string());
+        assert_eq!(state.shell.take_queued_command(), None, "command running");
+
+        feed_bytes(&mut state, b"\x1b]133;D;0\x07\x1b]133;A\x07");
+        assert_eq!(state.shell.take_queued_command(), Some(first));
+        assert_eq!(state.shell.take_queued_command(), None, "one per prompt");
+
+        assert!(state.shell.cancel_queued(&second.id));
+        assert!(!state.shell.cancel_queued(&second.id));
+        feed_bytes(&mut state, b"\x1b]133;A\x07");
+        assert_eq!(state.shell.take_queued_command(), None);
+    }
+
+    #[test]
+    fn test_frame_events_are_listed_in_event_schema() {
+        use crate::render::frame::TERMINAL_EVENT_TYPES;
+
+        let mut state = TerminalState::new(4, 20);
+        state.take_render_snapshot();
+        feed_bytes(
+            &mut state,
+            b"\x1b]133;A\x07\x1b]133;B;ls\x07\x1b]133;D;0\x07\x1b]0;t\x07\x07\
+              \x1b]7;file:///tmp\x07\x1b[?1049h\x1b[?1049l\x1b[3J",
+        );
+        let frame = state.take_render_snapshot().unwrap().into_frame();
+        let json = serde_json::to_value(&frame).unwrap();
+        assert_eq!(json["event_schema"], EVENT_SCHEMA_VERSION);
+        let types: Vec<&str> = json["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| event["type"].as_str().unwrap())
+            .collect();
+        assert!(types.len() >= 8, "{:?}", types);
+        for ty in types {
+            assert!(TERMINAL_EVENT_TYPES.contains(&ty), "{} missing from schema", ty);
+        }
+    }
+
+    #[test]
+    fn title_and_cwd_spam_is_coalesced_around_block_events() {
+        let mut state = TerminalState::new(4, 20);
+        state.take_render_snapshot();
+        feed_bytes(
+            &mut state,
+            b"\x1b]7;file:///a\x07\x1b]133;A\x07\x1b]7;file:///b\x07\x1b]2;one\x07\
+              \x1b]133;B;ls\x07\x1b]2;two\x07\x1b]7;file:///c\x07\x1b]133;D;0\x07",
+        );
+        let frame = state.take_render_snapshot().unwrap();
+        let events: Vec<String> = frame
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                TerminalEvent::BlockStarted { .. } => Some("started".to_string()),
+                TerminalEvent::BlockCommand { .. } => Some("command".to_string()),
+                TerminalEvent::BlockCompleted { .. } => Some("completed".to_string()),
+                TerminalEvent::CwdChanged { path } => Some(path.clone()),
+                TerminalEvent::TitleChanged { title } => Some(title.clone()),
+                _ => None,
+            })
+            .collect();
+        let blocks: Vec<&str> = events
+            .iter()
+            .map(String::as_str)
+            .filter(|event| ["started", "command", "completed"].contains(event))
+            .collect();
+        assert_eq!(blocks, ["started", "command", "completed"]);
+        assert_eq!(events.iter().filter(|e| e.starts_with('/')).count(), 1);
+        let cwd = events.iter().position(|e| e == "/c").unwrap();
+        assert!(cwd > events.iter().position(|e| e == "command").unwrap());
+        assert!(events.contains(&"two".to_string()));
+        assert!(!events.contains(&"one".to_string()));
+        assert_eq!(state.coalesced_events, 2);
+    }
+
+    #[test]
+    fn json_output_is_flagged_when_its_block_completes() {
+        let mut state = TerminalState::new(6, 20);
+        feed_bytes(&mut state, b"\x1b]133;A\x07$ cat x\r\n\x1b]133;B;cat x\x07");
+        feed_bytes(&mut state, b"{\"name\":\r\n \"rain\"}\r\n\x1b]133;D;0\x07");
+        match state.pending_terminal_events.as_slice() {
+            [TerminalEvent::BlockStructuredData { kind, sample, .. }] => {
+                assert_eq!(*kind, structured::StructuredKind::Json);
+                assert_eq!(sample, "{\"name\":\"rain\"}");
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+
+        feed_bytes(&mut state, b"\x1b]133;A\x07$ ls\r\n\x1b]133;B;ls\x07a b\r\n\x1b]133;D;0\x07");
+        assert_eq!(state.pending_terminal_events.len(), 1);
+    }
+
+    #[test]
+    fn visible_search_follows_the_alt_screen_each_frame() {
+        let mut state = TerminalState::new(3, 10);
+        feed_bytes(&mut state, b"\x1b[?1049hfind me");
+        let matches = state.set_visible_search("me");
+        assert_eq!(matches, vec![CellRect { row: 0, col: 5, cols: 2 }]);
+
+        state.take_render_snapshot();
+        feed_bytes(&mut state, b"\r\nme too");
+        let snapshot = state.take_render_snapshot().unwrap();
+        assert_eq!(snapshot.visible_search.map(|m| m.len()), Some(2));
+
+        state.set_visible_search("");
+        feed_bytes(&mut state, b"\r\nme");
+        assert!(state.take_render_snapshot().unwrap().visible_search.is_none());
+    }
+
+    #[test]
+    fn themes_answer_color_queries_and_resolve_when_the_frontend_lacks_them() {
+        let mut state = TerminalState::new(2, 10);
+        let mut theme = ThemeColors {
+            background: (0x20, 0x20, 0x20),
+            ..ThemeColors::default()
+        };
+        theme.ansi[1] = (0xaa, 0, 0);
+        state.set_theme(&theme, false);
+        feed_bytes(&mut state, b"\x1b]11;?\x07\x1b]4;1;?\x07\x1b[31mx");
+        assert_eq!(
+            state.take_pending_responses(),
+            vec![
+                b"\x1b]11;rgb:2020/2020/2020\x1b\\".to_vec(),
+                b"\x1b]4;1;rgb:aaaa/0000/0000\x1b\\".to_vec(),
+            ]
+        );
+        let snap = state.take_render_snapshot().unwrap();
+        assert!(matches!(
+            snap.lines[0].spans[0].fg,
+            SerializableColor::Indexed { index: 1 }
+        ));
+
+        state.set_theme(&theme, true);
+        let snap = state.take_render_snapshot().unwrap();
+        assert!(matches!(
+            snap.lines[0].spans[0].fg,
+            SerializableColor::Rgb { r: 0xaa, .. }
+        ));
+    }
+
+    #[test]
+    fn osc_10_11_set_colors_that_queries_report() {
+        let mut state = TerminalState::new(2, 10);
+        feed_bytes(&mut state, b"\x1b]11;?\x07");
+        assert_eq!(
+            state.take_pending_responses(),
+            vec![b"\x1b]11;rgb:0e0e/0e0e/0e0e\x1b\\".to_vec()]
+        );
+
+        feed_bytes(&mut state, b"\x1b]10;#102030;rgb:ff/ff/ff\x07\x1b]11;?\x07");
+        assert_eq!(
+            state.take_pending_responses(),
+            vec![b"\x1b]11;rgb:ffff/ffff/ffff\x1b\\".to_vec()]
+        );
+        match state.pending_terminal_events.as_slice() {
+            [TerminalEvent::DynamicColorsChanged { foreground, background, cursor }] => {
+                assert!(matches!(foreground, Some(SerializableColor::Rgb { r: 0x10, .. })));
+                assert!(background.is_some());
+                assert!(cursor.is_none());
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+
+        state.pending_terminal_events.clear();
+        feed_bytes(&mut state, b"\x1b]112\x07");
+        assert!(state.pending_terminal_events.is_empty());
+        feed_bytes(&mut state, b"\x1b]111\x07");
+        assert!(matches!(
+            state.pending_terminal_events.as_slice(),
+            [TerminalEvent::DynamicColorsChanged { background: None, .. }]
+        ));
+    }
+
+    #[test]
+    fn osc_9_and_777_notify_but_progress_does_not() {
+        let mut state = TerminalState::new(2, 10);
+        feed_bytes(&mut state, b"\x1b]9;build done; 0 errors\x07");
+        feed_bytes(&mut state, b"\x1b]777;notify;make;all;tests\x1b\\");
+        feed_bytes(&mut state, b"\x1b]9;4;1;50\x07\x1b]777;preexec\x07\x1b]9;\x07");
+        state
+            .pending_terminal_events
+            .retain(|event| !matches!(event, TerminalEvent::ProgressChanged { .. }));
+        match state.pending_terminal_events.as_slice() {
+            [TerminalEvent::Notification {
+                title: None,
+                body: first,
+            }, TerminalEvent::Notification {
+                title: Some(title),
+                body: second,
+            }] => {
+                assert_eq!(first, "build done; 0 errors");
+                assert_eq!((title.as_str(), second.as_str()), ("make", "all;tests"));
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn osc_9_4_reports_progress_changes() {
+        let mut state = TerminalState::new(2, 10);
+        let mut reports = |bytes: &[u8]| {
+            feed_bytes(&mut state, bytes);
+            state
+                .pending_terminal_events
+                .drain(..)
+                .map(|event| match event {
+                    TerminalEvent::ProgressChanged { state, value } => (state, value),
+                    other => panic!("unexpected event: {:?}", other),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            reports(b"\x1b]9;4;1;40\x07\x1b]9;4;1;40\x07\x1b]9;4;2\x07"),
+            vec![(ProgressState::Normal, 40), (ProgressState::Error, 40)]
+        );
+        assert_eq!(
+            reports(b"\x1b]9;4;3;70\x07\x1b]9;4;1;250\x07\x1b]9;4;9\x07"),
+            vec![(ProgressState::Indeterminate, 0), (ProgressState::Normal, 100)]
+        );
+        assert_eq!(reports(b"\x1bc"), vec![(ProgressState::None, 0)]);
+    }
+
+    #[test]
+    fn osc_4_sets_palette_entries_and_osc_104_resets_them() {
+        let mut state = TerminalState::new(2, 10);
+        feed_bytes(&mut state, b"\x1b]4;1;rgb:12/34/56;300;#ffffff\x07\x1b[31mr");
+        match state.pending_terminal_events.as_slice() {
+            [TerminalEvent::PaletteChanged { colors }] => {
+                assert_eq!(colors.len(), 1);
+                assert_eq!((colors[0].index, colors[0].r, colors[0].b), (1, 0x12, 0x56));
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+        let snapshot = state.take_render_snapshot().unwrap();
+        let span = &snapshot.lines[0].spans[0];
+        assert!(matches!(span.fg, SerializableColor::Rgb { r: 0x12, g: 0x34, b: 0x56 }));
+
+        feed_bytes(&mut state, b"\x1b]4;1;?\x07");
+        assert_eq!(
+            state.take_pending_responses(),
+            vec![b"\x1b]4;1;rgb:1212/3434/5656\x1b\\".to_vec()]
+        );
+
+        feed_bytes(&mut state, b"\x1b]104;2\x07");
+        assert!(state.pending_terminal_events.is_empty());
+        feed_bytes(&mut state, b"\x1b]104\x07");
+        assert!(matches!(
+            state.pending_terminal_events.as_slice(),
+            [TerminalEvent::PaletteChanged { colors }] if colors.is_empty()
+        ));
+    }
+
+    #[test]
+    fn current_command_line_follows_the_echo_after_the_prompt() {
+        use crate::shell::command_line::CommandLineSource;
+
+        let mut state = TerminalState::new(4, 20);
+        feed_bytes(&mut state, b"\x1b]133;A\x07$ \x1b]133;B\x07");
+        state.shell.note_input(b"git st\t");
+        feed_bytes(&mut state, b"git status");
+        let line = state.current_command_line().unwrap();
+        assert_eq!((line.text.as_str(), line.cursor), ("git status", Some(10)));
+        assert_eq!(line.source, CommandLineSource::Echo);
+
+        // The continuation prompt of a multi-line command is stripped.
+        state.shell.note_input(b"\x15echo 'a\rb'");
+        feed_bytes(&mut state, b"\r\x1b[K$ echo 'a\r\n> b'");
+        let line = state.current_command_line().unwrap();
+        assert_eq!((line.text.as_str(), line.cursor), ("echo 'a\nb'", Some(10)));
+
+        feed_bytes(&mut state, b"\x1b]133;B;echo\x07");
+        assert_eq!(state.current_command_line(), None);
+    }
+
+    #[test]
+    fn limited_term_commands_report_capabilities_until_they_end() {
+        fn degraded(state: &mut TerminalState, bytes: &[u8]) -> Vec<Vec<&'static str>> {
+            feed_bytes(state, bytes);
+            state
+                .shell
+                .take_pending_events()
+                .into_iter()
+                .filter_map(|event| match event {
+                    TerminalEvent::TerminalCapabilitiesChanged { capabilities } => {
+                        Some(capabilities.degraded)
+                    }
+                    _ => None,
+                })
+                .collect()
+        }
+
+        let mut state = TerminalState::new(4, 20);
+        assert!(degraded(&mut state, b"\x1b]133;A\x07\x1b]133;B;ls\x07").is_empty());
+        assert_eq!(
+            degraded(
+                &mut state,
+                b"\x1b]133;D;0\x07\x1b]133;A\x07\x1b]133;B;TERM=screen vim\x07"
+            ),
+            vec![vec!["truecolor", "256color", "hyperlinks", "images"]]
+        );
+        assert!(!state.shell.set_process_term("dumb"));
+        assert_eq!(
+            degraded(&mut state, b"\x1b]133;D;0\x07"),
+            vec![Vec::<&str>::new()]
+        );
+    }
+
+    #[test]
+    fn viewport_top_reports_the_enclosing_block_once() {
+        let mut state = TerminalState::new(4, 20);
+        feed_bytes(&mut state, b"\x1b]7;file:///tmp\x07\x1b]133;A\x07$ \x1b]133;B;make\x07\r\n");
+        feed_bytes(&mut state, b"one\r\ntwo\r\n\x1b]133;D;0\x07\x1b]133;A\x07$ ");
+        let make = state.shell.blocks().next().unwrap().clone();
+        let two = state.grid.find_line(make.prompt_line_id).unwrap() + 2;
+        let two = state.grid.rows[two].line_id;
+
+        let block = state.set_viewport_top(two).unwrap();
+        assert_eq!(block.block_id.as_deref(), Some(make.id.as_str()));
+        assert_eq!(block.command.as_deref(), Some("make"));
+        assert_eq!(block.cwd.as_deref(), Some("/tmp"));
+        assert_eq!(state.set_viewport_top(make.prompt_line_id), None);
+        assert_eq!(state.set_viewport_top(999), Some(ViewportBlock::default()));
+    }
+
+    #[test]
+    fn remote_prompt_is_offered_once_per_ssh_block() {
+        let mut state = TerminalState::new(4, 40);
+        feed_bytes(&mut state, b"\x1b]133;A\x07$ \x1b]133;B;ssh -p 22 me@web-1\x07\r\n");
+        feed_bytes(&mut state, b"me@web-1's password: ");
+        state.shell.take_pending_events();
+        feed_bytes(&mut state, b"\r\nLast login: today\r\nme@web-1:~$ ");
+        match state.shell.take_pending_events().as_slice() {
+            [TerminalEvent::RemotePromptDetected { host, .. }] => assert_eq!(host, "web-1"),
+            other => panic!("unexpected events: {:?}", other),
+        }
+        feed_bytes(&mut state, b"ls\r\nfile\r\nme@web-1:~$ ");
+        assert!(state.shell.take_pending_events().is_empty());
+    }
+
+    #[test]
+    fn test_block_line_ids_survive_scroll_resize_and_reset() {
+        let mut state = TerminalState::new(4, 20);
+        feed_bytes(&mut state, b"\x1b]133;A\x07$ ls\r\n");
+        feed_bytes(&mut state, b"\x1b]133;B;ls\x07");
+        let events = state.shell.take_pending_events();
+        let start_id = match events.as_slice() {
+            [TerminalEvent::BlockStarted { line_id, .. }, TerminalEvent::BlockCommand { line_id: cmd_line, .. }] => {
+                assert_ne!(line_id, cmd_line);
+                *line_id
+            }
+            other => panic!("unexpected events: {:?}", other),
+        };
+
+        // Push the prompt line into scrollback, then shrink and grow.
+        feed_bytes(&mut state, b"a\r\nb\r\nc\r\nd\r\ne");
+        state.resize(2, 10);
+        state.resize(6, 30);
+        feed_bytes(&mut state, b"\x1b]133;D;0\x07");
+        let end_id = match state.shell.take_pending_events().as_slice() {
+            [TerminalEvent::BlockCompleted { line_id, .. }] => *line_id,
+            other => panic!("unexpected events: {:?}", other),
+        };
+        assert_eq!(
+            state.get_line_range_text(start_id, end_id).as_deref(),
+            Some("$ ls\na\nb\nc\nd\ne")
+        );
+
+        // IDs keep increasing across a full reset, so old ones never alias.
+        feed_bytes(&mut state, b"\x1bc");
+        assert!(state.get_line_range_text(start_id, end_id).is_none());
+        assert!(state.grid.visible_line_id(0) > end_id);
+    }
+
+    #[test]
+    fn dec_line_size_wraps_at_half_width_and_renders() {
+        let mut state = TerminalState::new(4, 10);
+        feed_bytes(&mut state, b"\x1b#3BIG\r\n\x1b#4BIG\r\n\x1b#6abcdefg");
+        assert_eq!(
+            state.grid.visible_row(0).line_size,
+            LineSize::DoubleHeightTop
+        );
+        assert_eq!(
+            state.grid.visible_row(1).line_size,
+            LineSize::DoubleHeightBottom
+        );
+        // Five columns fit on a double-width line of a 10-column grid.
+        assert_eq!(state.grid.visible_row(2).line_size, LineSize::DoubleWidth);
+        assert_eq!(state.cursor.row, 3);
+        assert_eq!(state.grid.visible_row(3).cells[0].c, 'f');
+
+        let lines = state.grid.collect_dirty_lines();
+        assert_eq!(lines[2].line_size, LineSize::DoubleWidth);
+        assert!(lines[3].line_size.is_single());
+
+        // EL keeps the attribute; ED drops it.
+        feed_bytes(&mut state, b"\x1b[1;1H\x1b[2K");
+        assert_eq!(
+            state.grid.visible_row(0).line_size,
+            LineSize::DoubleHeightTop
+        );
+        feed_bytes(&mut state, b"\x1b[2J");
+        assert!(state.grid.visible_row(0).line_size.is_single());
+        assert!(state.grid.visible_row(2).line_size.is_single());
+    }
 }
@@ -0,0 +1,430 @@
+//! Color themes: the ones built into the UI and ones loaded from the
+//! `themes` folder of the config directory.
+//!
+//! Theme files are TOML or JSON with `foreground`, `background`, an optional
+//! `cursor` and `ansi`, the 16 ANSI colors as `#rrggbb`; or iTerm2
+//! `.itermcolors` property lists. TOML and JSON themes may name a
+//! `background_image`, relative to the themes folder. The active theme answers
+//! OSC 4/10/11/12 color queries. The UI only has palettes for the built-in themes, so a
+//! loaded theme's ANSI colors go out on spans as RGB.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::terminal::color::{parse_color_spec, ThemeColors};
+
+/// Folder of theme files inside the app config directory.
+pub const THEMES_DIR: &str = "themes";
+
+/// A theme as listed to the frontend, with colors as `#rrggbb`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThemeInfo {
+    pub name: String,
+    pub label: String,
+    pub builtin: bool,
+    pub foreground: String,
+    pub background: String,
+    pub cursor: String,
+    pub ansi: Vec<String>,
+    /// `rain-asset` URL of the background image, once the theme is active.
+    pub background_image: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub name: String,
+    pub label: String,
+    pub builtin: bool,
+    pub colors: ThemeColors,
+    pub background_image: Option<PathBuf>,
+}
+
+impl Theme {
+    pub fn info(&self) -> ThemeInfo {
+        let hex = |(r, g, b): (u8, u8, u8)| format!("#{:02x}{:02x}{:02x}", r, g, b);
+        ThemeInfo {
+            name: self.name.clone(),
+            label: self.label.clone(),
+            builtin: self.builtin,
+            foreground: hex(self.colors.foreground),
+            background: hex(self.colors.background),
+            cursor: hex(self.colors.cursor),
+            ansi: self.colors.ansi.iter().copied().map(hex).collect(),
+            background_image: None,
+        }
+    }
+}
+
+/// A theme the frontend ships, mirrored here for color queries.
+struct BuiltinTheme {
+    name: &'static str,
+    label: &'static str,
+    foreground: u32,
+    background: u32,
+    cursor: u32,
+    ansi: [u32; 16],
+}
+
+/// Must match `THEME_LIST`, `THEME_ANSI_PALETTES` and the theme variables
+/// in the frontend.
+const BUILTIN_THEMES: &[BuiltinTheme] = &[
+    BuiltinTheme {
+        name: "dark",
+        label: "Dark",
+        foreground: 0xd4d4d4,
+        background: 0x0e0e0e,
+        cursor: 0xd4d4d4,
+        ansi: [
+            0x0e0e0e, 0xf85149, 0x56d364, 0xe3b341, 0x58a6ff, 0xbc8cff, 0x39d2c0, 0xc9d1d9,
+            0x484f58, 0xff7b72, 0x7ee787, 0xf0c85c, 0x79c0ff, 0xd2a8ff, 0x56d4cf, 0xf0f6fc,
+        ],
+    },
+    BuiltinTheme {
+        name: "light",
+        label: "Light",
+        foreground: 0x1c1917,
+        background: 0xf8f7f5,
+        cursor: 0x1c1917,
+        ansi: [
+            0xe7e7e7, 0xd32f2f, 0x388e3c, 0xf57c00, 0x1976d2, 0x7b1fa2, 0x0097a7, 0x424242,
+            0x9e9e9e, 0xef5350, 0x66bb6a, 0xffa726, 0x42a5f5, 0xab47bc, 0x26c6da, 0x212121,
+        ],
+    },
+    BuiltinTheme {
+        name: "nord",
+        label: "Nord",
+        foreground: 0xeceff4,
+        background: 0x2e3440,
+        cursor: 0xd8dee9,
+        ansi: [
+            0x3b4252, 0xbf616a, 0xa3be8c, 0xebcb8b, 0x81a1c1, 0xb48ead, 0x88c0d0, 0xe5e9f0,
+            0x4c566a, 0xbf616a, 0xa3be8c, 0xebcb8b, 0x81a1c1, 0xb48ead, 0x8fbcbb, 0xeceff4,
+        ],
+    },
+    BuiltinTheme {
+        name: "solarized-dark",
+        label: "Solarized Dark",
+        foreground: 0x839496,
+        background: 0x002b36,
+        cursor: 0x839496,
+        ansi: [
+            0x073642, 0xdc322f, 0x859900, 0xb58900, 0x268bd2, 0xd33682, 0x2aa198, 0xeee8d5,
+            0x586e75, 0xcb4b16, 0x859900, 0xb58900, 0x268bd2, 0x6c71c4, 0x2aa198, 0xfdf6e3,
+        ],
+    },
+    BuiltinTheme {
+        name: "dracula",
+        label: "Dracula",
+        foreground: 0xf8f8f2,
+        background: 0x282a36,
+        cursor: 0xf8f8f2,
+        ansi: [
+            0x21222c, 0xff5555, 0x50fa7b, 0xf1fa8c, 0xbd93f9, 0xff79c6, 0x8be9fd, 0xf8f8f2,
+            0x6272a4, 0xff6e6e, 0x69ff94, 0xffffa5, 0xd6acff, 0xff92df, 0xa4ffff, 0xffffff,
+        ],
+    },
+    BuiltinTheme {
+        name: "monokai",
+        label: "Monokai",
+        foreground: 0xf8f8f2,
+        background: 0x272822,
+        cursor: 0xf8f8f0,
+        ansi: [
+            0x272822, 0xf92672, 0xa6e22e, 0xf4bf75, 0x66d9ef, 0xae81ff, 0xa1efe4, 0xf8f8f2,
+            0x75715e, 0xf92672, 0xa6e22e, 0xf4bf75, 0x66d9ef, 0xae81ff, 0xa1efe4, 0xf9f8f5,
+        ],
+    },
+    BuiltinTheme {
+        name: "gruvbox",
+        label: "Gruvbox",
+        foreground: 0xebdbb2,
+        background: 0x282828,
+        cursor: 0xebdbb2,
+        ansi: [
+            0x282828, 0xcc241d, 0x98971a, 0xd79921, 0x458588, 0xb16286, 0x689d6a, 0xa89984,
+            0x928374, 0xfb4934, 0xb8bb26, 0xfabd2f, 0x83a598, 0xd3869b, 0x8ec07c, 0xebdbb2,
+        ],
+    },
+    BuiltinTheme {
+        name: "catppuccin",
+        label: "Catppuccin",
+        foreground: 0xcdd6f4,
+        background: 0x1e1e2e,
+        cursor: 0xf5e0dc,
+        ansi: [
+            0x1e1e2e, 0xf38ba8, 0xa6e3a1, 0xf9e2af, 0x89b4fa, 0xcba6f7, 0x94e2d5, 0xcdd6f4,
+            0x585b70, 0xf38ba8, 0xa6e3a1, 0xf9e2af, 0x89b4fa, 0xcba6f7, 0x94e2d5, 0xa6adc8,
+        ],
+    },
+    BuiltinTheme {
+        name: "tokyo-night",
+        label: "Tokyo Night",
+        foreground: 0xc0caf5,
+        background: 0x1a1b26,
+        cursor: 0xc0caf5,
+        ansi: [
+            0x15161e, 0xf7768e, 0x9ece6a, 0xe0af68, 0x7aa2f7, 0xbb9af7, 0x7dcfff, 0xa9b1d6,
+            0x414868, 0xff9e9e, 0xb9f27c, 0xff9e64, 0x82aaff, 0xd4b0ff, 0xa9e1ff, 0xc0caf5,
+        ],
+    },
+    BuiltinTheme {
+        name: "one-dark",
+        label: "One Dark",
+        foreground: 0xabb2bf,
+        background: 0x282c34,
+        cursor: 0xabb2bf,
+        ansi: [
+            0x282c34, 0xe06c75, 0x98c379, 0xe5c07b, 0x61afef, 0xc678dd, 0x56b6c2, 0xabb2bf,
+            0x5c6370, 0xe06c75, 0x98c379, 0xe5c07b, 0x61afef, 0xc678dd, 0x56b6c2, 0xffffff,
+        ],
+    },
+];
+
+fn rgb(hex: u32) -> (u8, u8, u8) {
+    ((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+}
+
+pub fn builtin_themes() -> Vec<Theme> {
+    BUILTIN_THEMES
+        .iter()
+        .map(|theme| Theme {
+            name: theme.name.to_string(),
+            label: theme.label.to_string(),
+            builtin: true,
+            colors: ThemeColors {
+                foreground: rgb(theme.foreground),
+                background: rgb(theme.background),
+                cursor: rgb(theme.cursor),
+                ansi: theme.ansi.map(rgb),
+            },
+            background_image: None,
+        })
+        .collect()
+}
+
+/// Built-in themes followed by the ones in `dir`, sorted by name. Files
+/// that fail to parse are logged and skipped, as are names a built-in
+/// theme already has.
+pub fn list_themes(dir: &Path) -> Vec<Theme> {
+    let mut themes = builtin_themes();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return themes;
+    };
+    let mut loaded = Vec::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let (Some(name), Some(extension)) = (
+            path.file_stem().and_then(|s| s.to_str()),
+            path.extension().and_then(|s| s.to_str()),
+        ) else {
+            continue;
+        };
+        if !matches!(extension, "toml" | "json" | "itermcolors") {
+            continue;
+        }
+        if themes.iter().any(|theme| theme.name == name) {
+            tracing::warn!("Theme {:?} has the name of a built-in theme", path);
+            continue;
+        }
+        let parsed = std::fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| {
+                let image = background_image(extension, &contents).map(|image| dir.join(image));
+                parse_theme(extension, &contents).map(|parsed| (parsed, image))
+            });
+        match parsed {
+            Ok(((label, colors), background_image)) => loaded.push(Theme {
+                name: name.to_string(),
+                label: label.unwrap_or_else(|| name.to_string()),
+                builtin: false,
+                colors,
+                background_image,
+            }),
+            Err(e) => tracing::warn!("Theme {:?} not loaded: {}", path, e),
+        }
+    }
+    loaded.sort_by(|a, b| a.name.cmp(&b.name));
+    themes.extend(loaded);
+    themes
+}
+
+#[derive(Deserialize)]
+struct ThemeFile {
+    name: Option<String>,
+    foreground: String,
+    background: String,
+    cursor: Option<String>,
+    ansi: Vec<String>,
+}
+
+/// The `background_image` a TOML or JSON theme names, if any.
+pub fn background_image(extension: &str, contents: &[u8]) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Background {
+        background_image: Option<String>,
+    }
+    let file: Background = match extension {
+        "toml" => toml::from_str(std::str::from_utf8(contents).ok()?).ok()?,
+        "json" => serde_json::from_slice(contents).ok()?,
+        _ => return None,
+    };
+    file.background_image
+        .filter(|image| !image.trim().is_empty())
+}
+
+/// Parse a theme file by its extension, returning its display name if it
+/// has one.
+pub fn parse_theme(
+    extension: &str,
+    contents: &[u8],
+) -> Result<(Option<String>, ThemeColors), String> {
+    let file: ThemeFile = match extension {
+        "toml" => {
+            let text = std::str::from_utf8(contents).map_err(|e| e.to_string())?;
+            toml::from_str(text).map_err(|e| e.to_string())?
+        }
+        "json" => serde_json::from_slice(contents).map_err(|e| e.to_string())?,
+        "itermcolors" => return parse_itermcolors(contents).map(|colors| (None, colors)),
+        other => return Err(format!("Unknown theme format: {}", other)),
+    };
+    let color = |spec: &str| {
+        parse_color_spec(spec.trim()).ok_or_else(|| format!("Invalid color: {}", spec))
+    };
+    let ansi: Vec<_> = file
+        .ansi
+        .iter()
+        .map(|spec| color(spec))
+        .collect::<Result<_, _>>()?;
+    let ansi: [(u8, u8, u8); 16] = ansi
+        .try_into()
+        .map_err(|ansi: Vec<_>| format!("Expected 16 ANSI colors, found {}", ansi.len()))?;
+    let foreground = color(&file.foreground)?;
+    Ok((
+        file.name,
+        ThemeColors {
+            foreground,
+            background: color(&file.background)?,
+            cursor: file
+                .cursor
+                .as_deref()
+                .map(color)
+                .transpose()?
+                .unwrap_or(foreground),
+            ansi,
+        },
+    ))
+}
+
+/// Read an iTerm2 color preset: a dictionary of colors, each with red,
+/// green and blue components from 0 to 1.
+fn parse_itermcolors(contents: &[u8]) -> Result<ThemeColors, String> {
+    let colors: BTreeMap<String, plist::Value> =
+        plist::from_bytes(contents).map_err(|e| e.to_string())?;
+    let color = |key: &str| -> Result<(u8, u8, u8), String> {
+        let dict = colors
+            .get(key)
+            .and_then(plist::Value::as_dictionary)
+            .ok_or_else(|| format!("Missing {}", key))?;
+        let component = |name: &str| {
+            let value = dict.get(&format!("{} Component", name));
+            let value = value
+                .and_then(plist::Value::as_real)
+                .or_else(|| value?.as_signed_integer().map(|v| v as f64))
+                .ok_or_else(|| format!("Missing {} {} Component", key, name))?;
+            Ok::<_, String>((value.clamp(0.0, 1.0) * 255.0).round() as u8)
+        };
+        Ok((component("Red")?, component("Green")?, component("Blue")?))
+    };
+    let mut ansi = [(0, 0, 0); 16];
+    for (i, slot) in ansi.iter_mut().enumerate() {
+        *slot = color(&format!("Ansi {} Color", i))?;
+    }
+    let foreground = color("Foreground Color")?;
+    Ok(ThemeColors {
+        foreground,
+        background: color("Background Color")?,
+        cursor: color("Cursor Color").unwrap_or(foreground),
+        ansi,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANSI: &str = r##"["#000000", "#800000", "#008000", "#808000", "#000080", "#800080",
+        "#008080", "#c0c0c0", "#808080", "#ff0000", "#00ff00", "#ffff00", "#0000ff",
+        "#ff00ff", "#00ffff", "#ffffff"]"##;
+
+    #[test]
+    fn reads_toml_and_json_themes() {
+        let toml = format!(
+            "name = \"Classic\"\nforeground = \"#c0c0c0\"\nbackground = \"#000000\"\nansi = {}\n",
+            ANSI
+        );
+        let (label, colors) = parse_theme("toml", toml.as_bytes()).unwrap();
+        assert_eq!(label.as_deref(), Some("Classic"));
+        assert_eq!(colors.cursor, (0xc0, 0xc0, 0xc0));
+        assert_eq!(colors.ansi[4], (0, 0, 0x80));
+
+        let json = format!(
+            r##"{{"foreground": "#eeeeee", "background": "rgb:10/10/10", "cursor": "#ff0000", "ansi": {}}}"##,
+            ANSI
+        );
+        let (label, colors) = parse_theme("json", json.as_bytes()).unwrap();
+        assert_eq!(label, None);
+        assert_eq!(background_image("json", json.as_bytes()), None);
+
+        let with_image = format!("{}background_image = \"rain.png\"\n", toml);
+        assert!(parse_theme("toml", with_image.as_bytes()).is_ok());
+        assert_eq!(
+            background_image("toml", with_image.as_bytes()).as_deref(),
+            Some("rain.png")
+        );
+        assert_eq!(colors.background, (0x10, 0x10, 0x10));
+        assert_eq!(colors.cursor, (0xff, 0, 0));
+
+        let short = r##"foreground = "#fff"
+background = "#000"
+ansi = ["#000000"]"##;
+        assert_eq!(
+            parse_theme("toml", short.as_bytes()).unwrap_err(),
+            "Expected 16 ANSI colors, found 1"
+        );
+    }
+
+    #[test]
+    fn reads_itermcolors_presets() {
+        let color = |key: &str, r: &str| {
+            format!(
+                "<key>{key}</key><dict><key>Color Space</key><string>sRGB</string>\
+                 <key>Red Component</key><real>{r}</real>\
+                 <key>Green Component</key><real>0.5</real>\
+                 <key>Blue Component</key><integer>0</integer></dict>"
+            )
+        };
+        let mut body: String = (0..16)
+            .map(|i| color(&format!("Ansi {i} Color"), "0"))
+            .collect();
+        body += &color("Foreground Color", "1");
+        body += &color("Background Color", "0.2");
+        let plist = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <plist version=\"1.0\"><dict>{body}</dict></plist>"
+        );
+        let (_, colors) = parse_theme("itermcolors", plist.as_bytes()).unwrap();
+        assert_eq!(colors.foreground, (255, 128, 0));
+        assert_eq!(colors.background, (51, 128, 0));
+        assert_eq!(colors.cursor, colors.foreground);
+    }
+
+    #[test]
+    fn builtin_dark_theme_is_the_default_background() {
+        let dark = &builtin_themes()[0];
+        assert_eq!(dark.name, "dark");
+        assert_eq!(dark.colors.background, ThemeColors::default().background);
+        assert_eq!(dark.info().ansi[1], "#f85149");
+    }
+}
@@ -1,16 +1,21 @@
 use std::collections::HashMap;
 use std::io::{BufRead, BufReader, Write};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{sync_channel, Sender, SyncSender};
+use std::sync::mpsc::{sync_channel, RecvTimeoutError, Sender, SyncSender};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use parking_lot::Mutex;
 use portable_pty::{native_pty_system, CommandBuilder, PtySize};
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Manager};
 use uuid::Uuid;
 
-use crate::pty::reader::{RenderFramePayload, SessionEndPayload};
+use crate::assets::AssetSink;
+use crate::ipc::commands::session::forget_session;
+use crate::ipc::AppState;
+use crate::pty::reader::{
+    emit_render_frame, emit_session_ended, RenderFramePayload, SessionEndPayload,
+};
 use crate::terminal::TerminalState;
 
 use super::parser::{self, TmuxNotification};
@@ -57,6 +62,20 @@ pub struct TmuxController {
     app_handle: AppHandle,
 }
 
+/// `type` tags of every [`TmuxEvent`] variant; see
+/// [`crate::render::frame::EVENT_SCHEMA_VERSION`].
+pub const TMUX_EVENT_TYPES: &[&str] = &[
+    "Started",
+    "PaneAdded",
+    "PaneRemoved",
+    "WindowAdded",
+    "WindowClosed",
+    "WindowRenamed",
+    "LayoutChanged",
+    "Detached",
+    "Ended",
+];
+
 /// Events emitted to the frontend for tmux lifecycle changes.
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(tag = "type")]
@@ -360,6 +379,7 @@ fi
         let reader_running = Arc::clone(&running);
         let reader_state = Arc::clone(&tmux_state);
         let reader_app = app_handle.clone();
+        let reader_pane_handles = Arc::clone(&pane_handles);
 
         // Channel for the reader thread to send notifications that need
         // pane state creation (which must happen on the controller's side).
@@ -438,6 +458,10 @@ fi
                             let mut state = reader_state.lock();
                             let removed = state.remove_window(window_id);
                             drop(state);
+                            let app_state = reader_app.state::<AppState>();
+                            for session_id in &removed {
+                                forget_session(&app_state, session_id);
+                            }
                             let _ = reader_app.emit(
                                 "tmux-event",
                                 &TmuxEvent::WindowClosed {
@@ -449,7 +473,22 @@ fi
                         TmuxNotification::WindowRenamed { window_id, name } => {
                             let mut state = reader_state.lock();
                             state.set_window(window_id, name.clone());
+                            let pane_sessions: Vec<String> = state
+                                .windows
+                                .get(&window_id)
+                                .map(|w| w.panes.iter().map(|p| p.session_id.clone()).collect())
+                                .unwrap_or_default();
                             drop(state);
+                            // Feed the name into each pane's session naming so
+                            // panes label themselves like regular sessions.
+                            let handles = reader_pane_handles.lock();
+                            for session_id in &pane_sessions {
+                                if let Some(handle) = handles.get(session_id) {
+                                    handle.state.lock().shell.set_tmux_window_name(&name);
+                                    let _ = handle.render_waker.try_send(());
+                                }
+                            }
+                            drop(handles);
                             let _ = reader_app.emit(
                                 "tmux-event",
                                 &TmuxEvent::WindowRenamed { window_id, name },
@@ -537,9 +576,7 @@ fi
                             });
 
                             let mut ts = pstate.terminal_state.lock();
-                            for &byte in &data {
-                                pstate.vte_parser.advance(&mut *ts, byte);
-                            }
+                            ts.advance(&mut pstate.vte_parser, &data);
 
                             // Flush DSR/DA responses (no writer in control mode,
                             // but keep the queue drained to avoid unbounded growth).
@@ -792,7 +829,17 @@ fn create_pane_processor(
     running: &Arc<AtomicBool>,
 ) -> PaneProcessorState {
     let session_id = Uuid::new_v4().to_string();
-    let terminal_state = Arc::new(Mutex::new(TerminalState::new(rows, cols)));
+    let mut pane_terminal = TerminalState::new(rows, cols);
+    pane_terminal.set_custom_osc_codes(app.state::<AppState>().custom_osc_codes.lock().clone());
+    pane_terminal.set_device_attributes(app.state::<AppState>().device_attributes.lock().clone());
+    let (colors, resolve) = *app.state::<AppState>().theme.lock();
+    pane_terminal.set_theme(&colors, resolve);
+    pane_terminal.set_asset_sink(AssetSink::new(
+        &session_id,
+        app.state::<AppState>().assets.clone(),
+    ));
+    pane_terminal.set_clipboard_history(app.state::<AppState>().clipboard_history.clone());
+    let terminal_state = Arc::new(Mutex::new(pane_terminal));
 
     // Register in tmux state
     {
@@ -835,11 +882,23 @@ fn create_pane_processor(
         .name(format!("tmux-render-{}", pane_id))
         .spawn(move || {
             const FRAME_TICK: Duration = Duration::from_millis(16);
+            const IDLE_POLL: Duration = Duration::from_millis(500);
             let mut last_emit = Instant::now() - FRAME_TICK;
+            // Set while a synchronized update holds frames back: the pump
+            // wakes when it times out even if no more output arrives.
+            let mut held_for: Option<Duration> = None;
 
             while render_running.load(Ordering::Acquire) {
-                if render_rx.recv().is_err() {
-                    break;
+                match render_rx.recv_timeout(held_for.unwrap_or(IDLE_POLL)) {
+                    Ok(()) => {}
+                    Err(RecvTimeoutError::Timeout) => {
+                        // No output; only wake to flush a debounced session name
+                        // or a frame the synchronized update timed out on.
+                        if held_for.is_none() && !render_state.lock().shell.session_name_pending() {
+                            continue;
+                        }
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
                 }
                 if !render_running.load(Ordering::Acquire) {
                     break;
@@ -856,6 +915,7 @@ fn create_pane_processor(
                 let mut emitted = false;
                 if let Some(mut state) = render_state.try_lock() {
                     let snapshot = state.take_render_snapshot();
+                    held_for = state.synchronized_update_remaining();
                     drop(state);
                     if let Some(snapshot) = snapshot {
                         let frame = snapshot.into_frame();
@@ -863,7 +923,7 @@ fn create_pane_processor(
                             session_id: render_session.clone(),
                             frame,
                         };
-                        let _ = render_app.emit("render-frame", &payload);
+                        emit_render_frame(&render_app, &payload);
                         emitted = true;
                     }
                 } else {
@@ -878,6 +938,7 @@ fn create_pane_processor(
 
             // Final drain
             let mut state = render_state.lock();
+            state.release_synchronized_update();
             let snapshot = state.take_render_snapshot();
             drop(state);
             if let Some(snapshot) = snapshot {
@@ -886,14 +947,15 @@ fn create_pane_processor(
                     session_id: render_session.clone(),
                     frame,
                 };
-                let _ = render_app.emit("render-frame", &payload);
+                emit_render_frame(&render_app, &payload);
             }
 
-            let _ = render_app.emit(
-                "session-ended",
+            emit_session_ended(
+                &render_app,
                 &SessionEndPayload {
                     session_id: render_session,
                     exit_code: None,
+                    command: false,
                 },
             );
         })
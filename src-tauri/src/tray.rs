@@ -0,0 +1,198 @@
+//! Tray / menu bar menu contents.
+//!
+//! The tray itself is created and refreshed in `lib.rs`; this module only
+//! turns session and window metadata into labels and menu item IDs.
+
+use std::time::Duration;
+
+/// ID of Rain's single tray icon.
+pub const TRAY_ID: &str = "rain-tray";
+
+/// Sessions with output or input this recent get an activity badge.
+pub const ACTIVE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Longest session label before it gets truncated with an ellipsis.
+const MAX_LABEL_CHARS: usize = 48;
+
+/// Backend view of one session, as listed in the tray menu.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraySession {
+    pub session_id: String,
+    pub title: String,
+    pub cwd: String,
+    /// Recent PTY output or input.
+    pub active: bool,
+    /// The shell has exited but the tab is still open.
+    pub exited: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrayWindow {
+    pub label: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrayAction {
+    NewWindow,
+    ToggleVisibility,
+    Quit,
+    FocusWindow(String),
+    FocusSession(String),
+}
+
+impl TrayAction {
+    pub fn id(&self) -> String {
+        match self {
+            TrayAction::NewWindow => "tray:new-window".to_string(),
+            TrayAction::ToggleVisibility => "tray:toggle".to_string(),
+            TrayAction::Quit => "tray:quit".to_string(),
+            TrayAction::FocusWindow(label) => format!("tray:window:{}", label),
+            TrayAction::FocusSession(id) => format!("tray:session:{}", id),
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        let rest = id.strip_prefix("tray:")?;
+        match rest {
+            "new-window" => Some(TrayAction::NewWindow),
+            "toggle" => Some(TrayAction::ToggleVisibility),
+            "quit" => Some(TrayAction::Quit),
+            _ => {
+                if let Some(label) = rest.strip_prefix("window:") {
+                    Some(TrayAction::FocusWindow(label.to_string()))
+                } else {
+                    rest.strip_prefix("session:")
+                        .map(|id| TrayAction::FocusSession(id.to_string()))
+                }
+            }
+        }
+    }
+}
+
+/// Last path component of `cwd`, or `~` for the home directory.
+fn cwd_name(cwd: &str) -> &str {
+    let trimmed = cwd.trim_end_matches('/');
+    if trimmed.is_empty() {
+        return if cwd.is_empty() { "" } else { "/" };
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        if home.to_str() == Some(trimmed) {
+            return "~";
+        }
+    }
+    trimmed.rsplit(['/', '\\']).next().unwrap_or(trimmed)
+}
+
+/// Menu label for a session: its title (or cwd), prefixed with an
+/// activity badge and suffixed when the shell has exited.
+pub fn session_label(session: &TraySession) -> String {
+    let name = if session.title.trim().is_empty() {
+        cwd_name(&session.cwd)
+    } else {
+        session.title.trim()
+    };
+    let name = if name.is_empty() { "Shell" } else { name };
+
+    let mut label: String = name.chars().take(MAX_LABEL_CHARS).collect();
+    if name.chars().count() > MAX_LABEL_CHARS {
+        label.push('…');
+    }
+    // Menu labels treat `&` as a mnemonic marker.
+    let mut label = label.replace('&', "&&");
+    if session.exited {
+        label.push_str(" (exited)");
+    }
+    let badge = if session.active { "● " } else { "   " };
+    format!("{}{}", badge, label)
+}
+
+pub fn window_label(window: &TrayWindow, index: usize) -> String {
+    if window.title.trim().is_empty() {
+        format!("Window {}", index + 1)
+    } else {
+        window.title.trim().replace('&', "&&")
+    }
+}
+
+/// Tooltip summarising open sessions and how many are busy.
+pub fn tooltip(sessions: &[TraySession]) -> String {
+    let active = sessions.iter().filter(|s| s.active).count();
+    match (sessions.len(), active) {
+        (0, _) => "Rain".to_string(),
+        (1, 0) => "Rain — 1 session".to_string(),
+        (n, 0) => format!("Rain — {} sessions", n),
+        (1, _) => "Rain — 1 session, active".to_string(),
+        (n, a) => format!("Rain — {} sessions, {} active", n, a),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(title: &str, cwd: &str) -> TraySession {
+        TraySession {
+            session_id: "s1".into(),
+            title: title.into(),
+            cwd: cwd.into(),
+            active: false,
+            exited: false,
+        }
+    }
+
+    #[test]
+    fn action_ids_round_trip() {
+        for action in [
+            TrayAction::NewWindow,
+            TrayAction::ToggleVisibility,
+            TrayAction::Quit,
+            TrayAction::FocusWindow("rain-2".into()),
+            TrayAction::FocusSession("4f1c-uuid".into()),
+        ] {
+            assert_eq!(TrayAction::from_id(&action.id()), Some(action));
+        }
+        assert_eq!(TrayAction::from_id("terminal-menu:copy"), None);
+        assert_eq!(TrayAction::from_id("tray:unknown"), None);
+    }
+
+    #[test]
+    fn session_labels_fall_back_to_cwd_and_carry_badges() {
+        assert_eq!(session_label(&session("vim", "/tmp")), "   vim");
+        assert_eq!(session_label(&session("", "/usr/local/src/")), "   src");
+        assert_eq!(session_label(&session("", "")), "   Shell");
+
+        let mut busy = session("cargo build", "/tmp");
+        busy.active = true;
+        busy.exited = true;
+        assert_eq!(session_label(&busy), "● cargo build (exited)");
+    }
+
+    #[test]
+    fn session_labels_escape_mnemonics_and_truncate() {
+        assert_eq!(session_label(&session("a & b", "")), "   a && b");
+        let long = "x".repeat(60);
+        let label = session_label(&session(&long, ""));
+        assert!(label.ends_with('…'));
+        assert_eq!(label.chars().count(), 3 + MAX_LABEL_CHARS + 1);
+    }
+
+    #[test]
+    fn tooltip_counts_active_sessions() {
+        let mut sessions = vec![session("a", ""), session("b", "")];
+        assert_eq!(tooltip(&[]), "Rain");
+        assert_eq!(tooltip(&sessions[..1]), "Rain — 1 session");
+        assert_eq!(tooltip(&sessions), "Rain — 2 sessions");
+        sessions[1].active = true;
+        assert_eq!(tooltip(&sessions), "Rain — 2 sessions, 1 active");
+    }
+
+    #[test]
+    fn windows_without_titles_are_numbered() {
+        let window = TrayWindow {
+            label: "rain-0".into(),
+            title: String::new(),
+        };
+        assert_eq!(window_label(&window, 1), "Window 2");
+    }
+}
@@ -0,0 +1,132 @@
+//! Update policy: staged rollout, release metadata and when it is safe to
+//! install.
+//!
+//! The updater plugin does the network and signature work; the commands in
+//! `ipc::commands::update` use these helpers to decide whether this install
+//! takes a release yet and whether installing now would kill running jobs.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A session must have been quiet (no output or input) this long before an
+/// update installs over it, so nobody gets restarted mid-keystroke.
+pub const IDLE_GRACE: Duration = Duration::from_secs(120);
+
+/// How often a deferred install re-checks whether every session is idle.
+pub const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Release metadata shown to the user and in the post-update changelog.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseInfo {
+    pub version: String,
+    pub current_version: String,
+    pub notes: Option<String>,
+    /// RFC 3339 publish date, when the manifest has one.
+    pub date: Option<String>,
+    /// Download size in bytes, when the manifest announces it.
+    pub size: Option<u64>,
+}
+
+/// Share of installs (0–100) offered a release, from the manifest's optional
+/// top-level `rollout` field. Missing or malformed means everyone.
+pub fn rollout_percentage(manifest: &Value) -> u8 {
+    manifest
+        .get("rollout")
+        .and_then(Value::as_u64)
+        .map_or(100, |pct| pct.min(100) as u8)
+}
+
+/// Stable bucket in 0..100 for this install and release. Mixing the version
+/// in means the same installs aren't always first in line.
+pub fn rollout_bucket(install_id: &str, version: &str) -> u8 {
+    // FNV-1a: stable across Rust versions, unlike `DefaultHasher`.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in install_id.bytes().chain([0]).chain(version.bytes()) {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    (hash % 100) as u8
+}
+
+pub fn in_rollout(install_id: &str, version: &str, manifest: &Value) -> bool {
+    rollout_bucket(install_id, version) < rollout_percentage(manifest)
+}
+
+/// Download size for `target`, from `platforms.<target>.size` or a
+/// top-level `size`.
+pub fn release_size(manifest: &Value, target: &str) -> Option<u64> {
+    manifest
+        .get("platforms")
+        .and_then(|platforms| platforms.get(target))
+        .and_then(|platform| platform.get("size"))
+        .or_else(|| manifest.get("size"))
+        .and_then(Value::as_u64)
+}
+
+/// Whether restarting now could interrupt work in a session. Without shell
+/// integration we can't tell a prompt from a quiet job, so only an explicit
+/// confirmation restarts over those sessions.
+pub fn session_busy(
+    shell_integration: bool,
+    command_running: bool,
+    exited: bool,
+    idle_for: Duration,
+) -> bool {
+    if exited {
+        return false;
+    }
+    !shell_integration || command_running || idle_for < IDLE_GRACE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn rollout_defaults_to_everyone_and_clamps() {
+        assert_eq!(rollout_percentage(&json!({ "version": "1.0.0" })), 100);
+        assert_eq!(rollout_percentage(&json!({ "rollout": 25 })), 25);
+        assert_eq!(rollout_percentage(&json!({ "rollout": 400 })), 100);
+        assert_eq!(rollout_percentage(&json!({ "rollout": "half" })), 100);
+
+        assert!(!in_rollout("install", "1.0.0", &json!({ "rollout": 0 })));
+        assert!(in_rollout("install", "1.0.0", &json!({})));
+    }
+
+    #[test]
+    fn rollout_buckets_are_stable_and_spread() {
+        assert_eq!(
+            rollout_bucket("abc", "1.2.0"),
+            rollout_bucket("abc", "1.2.0")
+        );
+        let in_first_half = (0..1000)
+            .filter(|i| rollout_bucket(&format!("install-{}", i), "1.2.0") < 50)
+            .count();
+        assert!((400..600).contains(&in_first_half), "{}", in_first_half);
+    }
+
+    #[test]
+    fn size_prefers_the_platform_entry() {
+        let manifest = json!({
+            "size": 10,
+            "platforms": { "darwin-aarch64": { "url": "x", "size": 42 } }
+        });
+        assert_eq!(release_size(&manifest, "darwin-aarch64"), Some(42));
+        assert_eq!(release_size(&manifest, "linux-x86_64"), Some(10));
+        assert_eq!(release_size(&json!({}), "linux-x86_64"), None);
+    }
+
+    #[test]
+    fn busy_sessions() {
+        let quiet = IDLE_GRACE + Duration::from_secs(1);
+        assert!(!session_busy(true, false, false, quiet));
+        assert!(session_busy(true, true, false, quiet));
+        assert!(session_busy(true, false, false, Duration::from_secs(5)));
+        assert!(session_busy(false, false, false, quiet));
+        assert!(!session_busy(false, true, true, Duration::ZERO));
+    }
+}
@@ -15,23 +15,31 @@ import { Settings } from "./components/Settings";
 import { CommandPalette, type PaletteAction } from "./components/CommandPalette";
 import { HistoryBrowser } from "./components/HistoryBrowser";
 import { createTabsStore } from "./stores/tabs";
-import { useConfig, defaultConfig, type MacosGlassEngine } from "./stores/config";
+import { useConfig, defaultConfig, isSafeMode, type MacosGlassEngine } from "./stores/config";
 import {
   computeBlurProfile,
   computeGlassSurfaceOpacities,
   deriveBackgroundPalette,
   opacityUnitToPercent,
 } from "./lib/glass";
-import { useTheme, THEME_LIST, type ThemeName } from "./stores/theme";
+import { useTheme, THEME_LIST, fileThemeInfo, type ThemeName } from "./stores/theme";
 import {
   createSession,
   destroySession,
+  sessionHasRunningChildren,
   onRenderFrame,
+  watchFrameRings,
+  ackFrame,
   onResizeAck,
   onSessionEnded,
+  onSessionStalled,
   requestFullRedraw,
+  attachFrameChannel,
   setWindowBlurRadius,
   setWindowOpacity,
+  getCompositorCapabilities,
+  onCompositorCapabilitiesChanged,
+  type CompositorCapabilities,
   writeInput,
   emitCrossWindow,
   takeSessionTransferState,
@@ -49,9 +57,52 @@ import {
   onTmuxEvent,
   type TmuxEvent,
   registerGlobalHotkey,
+  setTrayEnabled,
+  takePendingOpenDirectories,
+  findIdleSession,
+  onOpenDirectoryRequested,
+  installUpdate,
+  takeUpdateChangelog,
+  onUpdateDownloadProgress,
+  onUpdateReady,
+  onUpdateError,
+  type ReleaseInfo,
+  type UpdateDownloadProgress,
+  takePendingDeepLinks,
+  onDeepLinkRequested,
+  type DeepLink,
+  onTrayFocusSession,
+  onNotificationClicked,
+  setWindowProgress,
+  onSessionIo,
+  onViewportBlockChanged,
+  setHibernationConfig,
+  setCustomOscCodes,
+  setDeviceAttributes,
+  setShellArgs,
+  setWarmPoolEnabled,
+  resolveNewSessionCwd,
+  type NewSessionCwd,
+  injectRemoteHooks,
+  setSessionReadOnly,
+  runTerminalSelftest,
+  setColorFilter,
+  setAmbiguousWidth,
+  setScrollbackLimit,
+  setMinContrast,
+  setBoldIsBright,
+  type ColorFilter,
   listRainWindows,
   quitApp,
   saveTextToFile,
+  saveDownload,
+  discardDownload,
+  setFileDownloads,
+  listClipboardHistory,
+  recopyClipboardEntry,
+  pinClipboardEntry,
+  clearClipboardHistory,
+  type ClipboardEntry,
 } from "./lib/ipc";
 import {
   disableLiquidGlassEffect,
@@ -66,10 +117,13 @@ import type {
   SerializableColor,
   SessionTransferState,
   TabTransferManifest,
+  FrameTransport,
 } from "./lib/types";
 import { buildSavedWorkspace, persistWorkspace, restoreWorkspace, type SavedWorkspace, type SavedPaneNode } from "./lib/sessionRestore";
 import { matchesKeybinding } from "./lib/keybindings";
-import { getActiveProfile, getProfile } from "./lib/profiles";
+import { isKnownEvent, negotiateEventSchema, passThroughUnknownEvent } from "./lib/eventSchema";
+import { getActiveProfile, getProfile, parseCommandLine } from "./lib/profiles";
+import { describeRunning } from "./lib/processes";
 import { ToastContainer, showToast } from "./components/Toast";
 import {
   checkForUpdates,
@@ -77,16 +131,27 @@ import {
   markUpdateChecked,
   dismissVersion,
   isDismissed,
+  releaseUrlFor,
   type UpdateInfo,
 } from "./lib/updater";
 
-const THEME_BG_MAP = new Map(THEME_LIST.map((entry) => [entry.name, entry.bg]));
-const THEME_ACCENT_MAP = new Map(
+const THEME_BG_MAP = new Map<string, string>(THEME_LIST.map((entry) => [entry.name, entry.bg]));
+const THEME_ACCENT_MAP = new Map<string, string>(
   THEME_LIST.map((entry) => [entry.name, entry.accent]),
 );
 
 const ADOPT_TRANSFER_FALLBACK_MS = 18000;
 const ADOPT_TRANSFER_POLL_MS = 120;
+// At most this many download offers per window; more are dropped.
+const DOWNLOAD_OFFER_LIMIT = 10;
+const DOWNLOAD_OFFER_WINDOW_MS = 60_000;
+
+type DownloadOffer = { sessionId: string; id: string; name: string | null; size: number };
+
+function formatOfferSize(bytes: number): string {
+  if (bytes < 1024 * 1024) return `${Math.max(1, Math.round(bytes / 1024))} KB`;
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+}
 
 function isDevLogEnabled(): boolean {
   if (typeof window === "undefined") return false;
@@ -119,10 +184,33 @@ const App: Component = () => {
   const [adoptProgress, setAdoptProgress] = createSignal<AdoptProgressState | null>(null);
   const [showPalette, setShowPalette] = createSignal(false);
   const [showHistory, setShowHistory] = createSignal(false);
+  const [clipboardEntries, setClipboardEntries] = createSignal<ClipboardEntry[] | null>(null);
   const [updateInfo, setUpdateInfo] = createSignal<UpdateInfo | null>(null);
+  const [updateProgress, setUpdateProgress] = createSignal<UpdateDownloadProgress | null>(null);
+  const [updateReady, setUpdateReady] = createSignal(false);
+  // Notes for the update installed before this launch.
+  const [changelog, setChangelog] = createSignal<ReleaseInfo | null>(null);
   let glassEffectRunSeq = 0;
   const pendingFrames = new Map<string, RenderFramePayload[]>();
 
+  // Frame acks are flushed once per animation frame. A throttled webview stops
+  // acking, which lets the backend switch to keyframes instead of deltas.
+  const pendingAcks = new Map<string, number>();
+  let ackScheduled = false;
+  function noteFrameApplied(payload: RenderFramePayload) {
+    const prev = pendingAcks.get(payload.session_id) ?? 0;
+    pendingAcks.set(payload.session_id, Math.max(prev, payload.frame.frame_seq));
+    if (ackScheduled) return;
+    ackScheduled = true;
+    requestAnimationFrame(() => {
+      ackScheduled = false;
+      for (const [sessionId, frameSeq] of pendingAcks) {
+        ackFrame(sessionId, frameSeq).catch(() => {});
+      }
+      pendingAcks.clear();
+    });
+  }
+
   const deferredFrames = new Map<string, RenderFramePayload[]>();
   let drainScheduled = false;
   function scheduleDrainDeferred() {
@@ -136,6 +224,7 @@ const App: Component = () => {
         if (!store) continue;
         for (const frame of frames) {
           store.applyRenderFrame(frame);
+          noteFrameApplied(frame);
         }
         markSessionRedrawn(sessionId);
       }
@@ -159,6 +248,7 @@ const App: Component = () => {
     if (store) {
       for (const frame of buffered) {
         store.applyRenderFrame(frame);
+        noteFrameApplied(frame);
       }
     }
   }
@@ -271,6 +361,7 @@ const App: Component = () => {
           underline: span.underline,
           strikethrough: span.strikethrough,
           ...(span.url ? { url: span.url } : {}),
+          ...(span.path ? { path: span.path } : {}),
         })),
       })),
       timestamp: snap.timestamp,
@@ -310,7 +401,10 @@ const App: Component = () => {
   function hydrateTransferredSessionState(sessionId: string) {
     takeSessionTransferState(sessionId)
       .then((transfer) => {
-        if (!transfer) return;
+        if (!transfer) {
+          // No snapshot of the old view; rebuild history from the backend.
+          return tabs.getStoreBySessionId(sessionId)?.loadScrollbackHistory();
+        }
         applyTransferredSessionState(sessionId, transfer);
       })
       .catch((error) => {
@@ -322,6 +416,19 @@ const App: Component = () => {
     return manifest.paneSessions.some((pane) => tabs.getStoreBySessionId(pane.sessionId));
   }
 
+  /** How new sessions send render frames, from the transport settings. */
+  function frameTransport(): FrameTransport | undefined {
+    if (config().frameRing) return "ring";
+    return config().binaryRenderFrames ? "channel" : undefined;
+  }
+
+  /** Redraw a session adopted from another window. A session with a frame
+   *  channel gets one of this window's first; the old one went with the
+   *  window it was opened in. */
+  function redrawAdoptedSession(sessionId: string) {
+    attachFrameChannel(sessionId).catch(console.error);
+  }
+
   function adoptManifestIntoTabs(
     manifest: TabTransferManifest,
     insertAt?: number,
@@ -347,7 +454,7 @@ const App: Component = () => {
       applyTransferredSessionState(pane.sessionId, pane.state);
       markSessionApplied(progressId, pane.sessionId);
       flushPendingFrames(pane.sessionId);
-      requestFullRedraw(pane.sessionId).catch(console.error);
+      redrawAdoptedSession(pane.sessionId);
     }
     if (manifest.cwd) {
       tabs.updateTabCwd(added.data.id, manifest.cwd);
@@ -359,6 +466,25 @@ const App: Component = () => {
     tabs.addSettingsTab();
   }
 
+  /**
+   * Starting directory for a session opened from `sourceSessionId`, chosen by
+   * the backend from `policy`. `fallback` is used when there is no source
+   * session or the lookup fails.
+   */
+  async function cwdForNewSession(
+    sourceSessionId: string | null | undefined,
+    policy: NewSessionCwd,
+    fallback?: string,
+  ): Promise<string | undefined> {
+    if (!sourceSessionId) return fallback;
+    try {
+      return (await resolveNewSessionCwd(sourceSessionId, policy)) ?? fallback;
+    } catch (e) {
+      console.warn("[Rain] Failed to resolve new session cwd:", e);
+      return fallback;
+    }
+  }
+
   async function spawnTab(profileId?: string) {
     try {
       const activeStore = tabs.activeStore();
@@ -367,11 +493,18 @@ const App: Component = () => {
       const profile = profileId ? getProfile(profileId) : getActiveProfile();
       const shell = profile?.shell?.trim() || undefined;
       const profileCwd = profile?.cwd?.trim() || undefined;
-      const cwd = profileCwd || activeStore?.state.cwd?.trim() || undefined;
+      const cwd =
+        profileCwd ||
+        (await cwdForNewSession(
+          activeStore?.state.sessionId,
+          config().newSessionCwd,
+          activeStore?.state.cwd?.trim() || undefined,
+        ));
       const env =
         profile?.env && Object.keys(profile.env).length > 0
           ? profile.env
           : undefined;
+      const command = profile?.command ? parseCommandLine(profile.command) : undefined;
 
       const result = await createSession(
         shell,
@@ -380,6 +513,10 @@ const App: Component = () => {
         cols,
         env,
         config().tmuxMode,
+        config().ambiguousWide,
+        config().scrollbackLines,
+        frameTransport(),
+        command?.length ? command : undefined,
       );
       const sessionId = result.session_id;
       devLog("[Rain] Session created:", sessionId, result.inside_tmux ? "(inside tmux)" : "");
@@ -413,7 +550,7 @@ const App: Component = () => {
       const activeStore = tabs.activeStore();
       const rows = activeStore?.state.rows ?? 24;
       const cols = activeStore?.state.cols ?? 80;
-      const result = await createSession(undefined, entry.cwd || undefined, rows, cols, undefined, config().tmuxMode);
+      const result = await createSession(undefined, entry.cwd || undefined, rows, cols, undefined, config().tmuxMode, config().ambiguousWide, config().scrollbackLines, frameTransport());
       const tab = tabs.addTab(result.session_id, entry.label, undefined, entry.cwd);
       if (entry.customLabel) tabs.updateTabCustomLabel(tab.data.id, entry.customLabel);
       if (entry.tabColor) tabs.updateTabColor(tab.data.id, entry.tabColor);
@@ -425,15 +562,59 @@ const App: Component = () => {
     }
   }
 
+  async function openTabAt(cwd?: string): Promise<string | undefined> {
+    if (cwd) {
+      // Reuse a tab already sitting at an empty prompt there.
+      const idle = await findIdleSession(cwd, tabs.sessionIds()).catch(() => null);
+      if (idle && tabs.focusSession(idle)) return idle;
+    }
+    try {
+      const activeStore = tabs.activeStore();
+      const rows = activeStore?.state.rows ?? 24;
+      const cols = activeStore?.state.cols ?? 80;
+      const result = await createSession(undefined, cwd, rows, cols, undefined, config().tmuxMode, config().ambiguousWide, config().scrollbackLines, frameTransport());
+      tabs.addTab(result.session_id, "Shell", undefined, cwd);
+      flushPendingFrames(result.session_id);
+      await requestFullRedraw(result.session_id);
+      return result.session_id;
+    } catch (err) {
+      console.error("[Rain] Failed to open tab at", cwd, err);
+      showToast(`Failed to open ${cwd ?? "tab"}`, "error");
+      return undefined;
+    }
+  }
+
+  async function runDeepLink(link: DeepLink) {
+    switch (link.action) {
+      case "open":
+        await openTabAt(link.path);
+        break;
+      case "run": {
+        const sessionId = await openTabAt(link.cwd ?? undefined);
+        if (!sessionId) return;
+        const bytes = new TextEncoder().encode(link.command + "\r");
+        await writeInput(sessionId, Array.from(bytes)).catch(console.error);
+        break;
+      }
+      case "tmux":
+        await handleTmuxRequested(`attach-session -t ${link.sessionName}`);
+        break;
+    }
+  }
+
   async function duplicateTab() {
     const tab = tabs.activeTab();
     if (!tab || tab.type !== "terminal") return;
     const store = tab.activePaneId ? tabs.stores.get(tab.activePaneId) : undefined;
-    const cwd = store?.state.cwd || tab.cwd || undefined;
+    const cwd = await cwdForNewSession(
+      store?.state.sessionId,
+      "inherit",
+      store?.state.cwd || tab.cwd || undefined,
+    );
     try {
       const rows = store?.state.rows ?? 24;
       const cols = store?.state.cols ?? 80;
-      const result = await createSession(undefined, cwd, rows, cols, undefined, config().tmuxMode);
+      const result = await createSession(undefined, cwd, rows, cols, undefined, config().tmuxMode, config().ambiguousWide, config().scrollbackLines, frameTransport());
       tabs.addTab(result.session_id, tab.customLabel || tab.label, undefined, cwd);
       flushPendingFrames(result.session_id);
       await requestFullRedraw(result.session_id);
@@ -443,6 +624,13 @@ const App: Component = () => {
     }
   }
 
+  /** Ask before closing a session whose shell is still running commands. */
+  async function confirmCloseSession(sessionId: string): Promise<boolean> {
+    const running = await sessionHasRunningChildren(sessionId).catch(() => []);
+    if (running.length === 0) return true;
+    return window.confirm(`${describeRunning(running)}. Close anyway?`);
+  }
+
   async function closeTab(tabId: string) {
     const tabData = tabs.state.tabs.find((t) => t.id === tabId);
     if (!tabData) return;
@@ -456,6 +644,7 @@ const App: Component = () => {
     }
 
     if (tabData.sessionId) {
+      if (!(await confirmCloseSession(tabData.sessionId))) return;
       try {
         await destroySession(tabData.sessionId);
       } catch (e) {
@@ -551,8 +740,12 @@ const App: Component = () => {
           // tmux pane: tell tmux to close it, layout change will rebuild the tree
           tmuxClosePane(store.state.tmuxPaneId).catch(console.error);
         } else if (store?.state.sessionId) {
-          destroySession(store.state.sessionId).catch(console.error);
-          tabs.closePane(active.id, paneId);
+          const sessionId = store.state.sessionId;
+          confirmCloseSession(sessionId).then((close) => {
+            if (!close) return;
+            destroySession(sessionId).catch(console.error);
+            tabs.closePane(active.id, paneId);
+          });
         }
       } else {
         closeTab(active.id);
@@ -612,7 +805,11 @@ const App: Component = () => {
       const rows = paneStore?.state.rows ?? 24;
       const cols = paneStore?.state.cols ?? 80;
       const tab = tabs.activeTab();
-      const cwd = paneStore?.state.cwd || tab?.cwd || undefined;
+      const cwd = await cwdForNewSession(
+        paneStore?.state.sessionId,
+        config().newSessionCwd,
+        paneStore?.state.cwd || tab?.cwd || undefined,
+      );
       const result = await createSession(
         undefined,
         cwd,
@@ -620,6 +817,9 @@ const App: Component = () => {
         cols,
         undefined,
         config().tmuxMode,
+        config().ambiguousWide,
+        config().scrollbackLines,
+        frameTransport(),
       );
       const sessionId = result.session_id;
       tabs.splitPane(tabId, paneId, direction, sessionId);
@@ -670,6 +870,48 @@ const App: Component = () => {
     await writeInput(sid, bytes).catch(console.error);
   };
 
+  function openClipboardHistory() {
+    listClipboardHistory()
+      .then((entries) => {
+        if (entries.length === 0) showToast("Clipboard history is empty", "info");
+        else setClipboardEntries(entries);
+      })
+      .catch((e) => showToast(`Failed to read clipboard history: ${e}`, "error"));
+  }
+
+  // One action per entry to copy it again, and one to pin or unpin it;
+  // pinned entries outlive the history limit.
+  const clipboardActions = (): PaletteAction[] => {
+    const entries = clipboardEntries() ?? [];
+    const preview = (text: string) => {
+      const line = text.trim().split("\n")[0];
+      return line.length > 80 ? `${line.slice(0, 80)}…` : line;
+    };
+    return [
+      ...entries.map((entry): PaletteAction => ({
+        id: `clipboard-${entry.id}`,
+        label: preview(entry.text),
+        category: entry.pinned ? "Pinned" : entry.source === "osc52" ? "From Program" : "Copied",
+        action: () => {
+          recopyClipboardEntry(entry.id)
+            .then(() => showToast("Copied", "success"))
+            .catch((e) => showToast(`Failed to copy: ${e}`, "error"));
+        },
+      })),
+      ...entries.map((entry): PaletteAction => ({
+        id: `clipboard-pin-${entry.id}`,
+        label: `${entry.pinned ? "Unpin" : "Pin"}: ${preview(entry.text)}`,
+        category: "Pin",
+        action: () => {
+          pinClipboardEntry(entry.id, !entry.pinned).catch(console.error);
+        },
+      })),
+      { id: "clipboard-clear", label: "Clear Unpinned Entries", category: "Clipboard", action: () => {
+        clearClipboardHistory().catch(console.error);
+      }},
+    ];
+  };
+
   const paletteActions = (): PaletteAction[] => [
     { id: "new-tab", label: "New Tab", shortcut: "Cmd+T", category: "Tabs", action: () => { spawnTab(); } },
     { id: "close-tab", label: "Close Tab", shortcut: "Cmd+W", category: "Tabs", action: () => {
@@ -702,6 +944,7 @@ const App: Component = () => {
       const cwd = store?.state.cwd;
       if (cwd) navigator.clipboard.writeText(cwd).catch(console.error);
     }},
+    { id: "clipboard-history", label: "Clipboard History", category: "Terminal", action: () => openClipboardHistory() },
     { id: "settings", label: "Open Settings", shortcut: isMac ? "Cmd+," : "Ctrl+,", category: "App", action: () => openSettings() },
     { id: "clear", label: "Clear Terminal", shortcut: isMac ? "Cmd+K" : "Ctrl+K", category: "Terminal", action: () => {
       const store = tabs.activeStore();
@@ -740,6 +983,54 @@ const App: Component = () => {
       const timestamp = new Date().toISOString().replace(/[:.]/g, "-").slice(0, 19);
       saveTextToFile(text, `rain-export-${timestamp}.txt`).catch(console.error);
     }},
+    ...([
+      ["none", "Off"],
+      ["deuteranopia", "Deuteranopia"],
+      ["protanopia", "Protanopia"],
+      ["high_contrast", "High Contrast"],
+    ] as [ColorFilter, string][]).map(([filter, name]): PaletteAction => ({
+      id: `color-filter-${filter}`,
+      label: `Color Filter: ${name}`,
+      category: "Accessibility",
+      action: () => {
+        const sessionId = tabs.activeStore()?.state.sessionId;
+        if (sessionId) setColorFilter(sessionId, filter).catch(console.error);
+      },
+    })),
+    ...([
+      [true, "Wide"],
+      [false, "Narrow"],
+    ] as [boolean, string][]).map(([wide, name]): PaletteAction => ({
+      id: `ambiguous-width-${name.toLowerCase()}`,
+      label: `Ambiguous-Width Characters: ${name}`,
+      category: "Terminal",
+      action: () => {
+        const sessionId = tabs.activeStore()?.state.sessionId;
+        if (sessionId) setAmbiguousWidth(sessionId, wide).catch(console.error);
+      },
+    })),
+    { id: "terminal-selftest", label: "Run Terminal Self-Test", category: "Terminal", action: () => {
+      const sessionId = tabs.activeStore()?.state.sessionId;
+      if (!sessionId) return;
+      runTerminalSelftest(sessionId, true)
+        .then((results) => {
+          const failed = results.filter((r) => !r.passed);
+          if (failed.length > 0) console.warn("Terminal self-test failures:", failed);
+        })
+        .catch(console.error);
+    }},
+    { id: "toggle-read-only", label: "Toggle Read-Only Pane", category: "Panes", action: () => {
+      const store = tabs.activeStore();
+      const sessionId = store?.state.sessionId;
+      if (!store || !sessionId) return;
+      const readOnly = !store.state.readOnly;
+      setSessionReadOnly(sessionId, readOnly)
+        .then(() => {
+          store.setState("readOnly", readOnly);
+          showToast(readOnly ? "Pane locked: input is ignored" : "Pane unlocked", "info");
+        })
+        .catch((e) => showToast(`Failed to change read-only mode: ${e}`, "error"));
+    }},
     { id: "toggle-statusbar", label: "Toggle Status Bar", category: "View", action: () => {
       updateConfig({ showStatusBar: !config().showStatusBar });
     }},
@@ -750,6 +1041,63 @@ const App: Component = () => {
     }},
   ];
 
+  // Offered once per ssh block, when its remote prompt shows up. Deferred so
+  // the frame carrying the prompt is painted before the dialog blocks.
+  function offerRemoteHooks(sessionId: string, host: string) {
+    if (!config().offerRemoteShellIntegration) return;
+    setTimeout(() => {
+      if (!window.confirm(`Enable Rain shell integration on ${host} for this connection?`)) return;
+      injectRemoteHooks(sessionId).catch((e) =>
+        showToast(`Failed to set up shell integration on ${host}: ${e}`, "error"),
+      );
+    }, 0);
+  }
+
+  // Files programs sent (iTerm2 OSC 1337 without inline=1), collected in one
+  // banner. Nothing is written unless the user saves them and picks where.
+  const [downloadOffers, setDownloadOffers] = createSignal<DownloadOffer[]>([]);
+  // When each recent offer arrived, to drop offers past DOWNLOAD_OFFER_LIMIT.
+  let recentOfferTimes: number[] = [];
+  let offersThrottled = false;
+
+  function offerDownload(sessionId: string, download: { id: string; name: string | null; size: number }) {
+    const now = Date.now();
+    recentOfferTimes = recentOfferTimes.filter((t) => now - t < DOWNLOAD_OFFER_WINDOW_MS);
+    if (!config().fileDownloads || recentOfferTimes.length >= DOWNLOAD_OFFER_LIMIT) {
+      if (config().fileDownloads && !offersThrottled) {
+        showToast("Too many files sent by the terminal; dropping more for a minute", "warning");
+        offersThrottled = true;
+      }
+      discardDownload(sessionId, download.id).catch(console.error);
+      return;
+    }
+    offersThrottled = false;
+    recentOfferTimes.push(now);
+    setDownloadOffers((prev) => [...prev, { sessionId, ...download }]);
+  }
+
+  function discardDownloadOffers() {
+    const offers = downloadOffers();
+    setDownloadOffers([]);
+    for (const offer of offers) {
+      discardDownload(offer.sessionId, offer.id).catch(console.error);
+    }
+  }
+
+  // One save dialog per file, in the order they were sent.
+  async function saveDownloadOffers() {
+    const offers = downloadOffers();
+    setDownloadOffers([]);
+    for (const offer of offers) {
+      const name = offer.name ?? "file";
+      try {
+        if (await saveDownload(offer.sessionId, offer.id)) showToast(`Saved ${name}`, "success");
+      } catch (e) {
+        showToast(`Failed to save ${name}: ${e}`, "error");
+      }
+    }
+  }
+
   // Track the session that requested tmux so we can switch back on detach
   const [tmuxOriginTab, setTmuxOriginTab] = createSignal<string | null>(null);
   // Guard to prevent duplicate tmux_start calls from repeated TmuxRequested events
@@ -769,7 +1117,11 @@ const App: Component = () => {
 
       const activePaneId = tab ? tabs.getActivePaneId(tab.id) : undefined;
       const paneStore = activePaneId ? tabs.stores.get(activePaneId) : undefined;
-      const cwd = paneStore?.state.cwd || tab?.cwd || undefined;
+      const cwd = await cwdForNewSession(
+        paneStore?.state.sessionId,
+        config().newSessionCwd,
+        paneStore?.state.cwd || tab?.cwd || undefined,
+      );
 
       await tmuxStart(args || undefined, cwd);
       devLog("[Rain] tmux control mode started");
@@ -797,6 +1149,10 @@ const App: Component = () => {
   }
 
   function handleTmuxEvent(event: TmuxEvent) {
+    if (!isKnownEvent("tmux", event.type)) {
+      passThroughUnknownEvent("tmux", event);
+      return;
+    }
     switch (event.type) {
       case "PaneAdded": {
         // Create a store for this tmux pane and add it as a tab pane
@@ -815,7 +1171,7 @@ const App: Component = () => {
         for (const sid of event.removed_sessions) {
           const store = tabs.getStoreBySessionId(sid);
           if (store) {
-            store.setState({ connected: false });
+            store.setState({ connected: false, stalled: null });
           }
         }
         break;
@@ -847,6 +1203,9 @@ const App: Component = () => {
             cols,
             undefined,
             config().tmuxMode,
+            config().ambiguousWide,
+            config().scrollbackLines,
+            frameTransport(),
           )
             .then(async (result) => {
               tabs.replaceTabSession(tab.id, result.session_id);
@@ -862,9 +1221,17 @@ const App: Component = () => {
 
   onMount(async () => {
     document.addEventListener("keydown", handleGlobalKeyDown);
+    void negotiateEventSchema();
 
     let shutdownInFlight = false;
     const appWindow = getCurrentWindow();
+    if (isSafeMode() && appWindow.label === "main") {
+      showToast(
+        "Rain started in safe mode after repeated crashes: default theme, no window effects, no workspace restore. Settings you change now are saved.",
+        "warning",
+        12000,
+      );
+    }
     const shutdownWindow = async () => {
       if (shutdownInFlight) return;
       shutdownInFlight = true;
@@ -900,12 +1267,16 @@ const App: Component = () => {
     unlisteners.push(unCloseRequested);
 
     // Register all event listeners in parallel for faster startup
-    const [unFrame, unEnd, unResizeAck, unTmux] = await Promise.all([
+    const [unFrame, unEnd, unStalled, unResizeAck, unTmux, unIo, unViewportBlock, unFrameRings] = await Promise.all([
       onRenderFrame((payload) => {
         if (payload.frame?.events) {
           for (const ev of payload.frame.events) {
             if (ev.type === "TmuxRequested" && "args" in ev) {
               handleTmuxRequested((ev as { type: string; args: string }).args);
+            } else if (ev.type === "RemotePromptDetected" && "host" in ev) {
+              offerRemoteHooks(payload.session_id, (ev as { type: string; host: string }).host);
+            } else if (ev.type === "FileDownload") {
+              offerDownload(payload.session_id, ev as { id: string; name: string | null; size: number });
             }
           }
         }
@@ -921,6 +1292,7 @@ const App: Component = () => {
           }
 
           store.applyRenderFrame(payload);
+          noteFrameApplied(payload);
           markSessionRedrawn(payload.session_id);
 
           const buf = deferredFrames.get(payload.session_id) ?? [];
@@ -938,10 +1310,20 @@ const App: Component = () => {
       onSessionEnded((payload) => {
         const store = tabs.getStoreBySessionId(payload.session_id);
         if (store) {
-          store.setState({ connected: false });
+          store.setState({ connected: false, stalled: null });
         }
         deferredFrames.delete(payload.session_id);
         pendingFrames.delete(payload.session_id);
+        // A profile command that exits cleanly closes its tab; one that failed
+        // stays open so its output can be read.
+        if (payload.command && payload.exit_code === 0) {
+          const tab = tabs.state.tabs.find((t) => t.sessionId === payload.session_id);
+          destroySession(payload.session_id).catch(() => {});
+          if (tab) tabs.closeTab(tab.id);
+        }
+      }),
+      onSessionStalled((payload) => {
+        tabs.getStoreBySessionId(payload.session_id)?.setState("stalled", payload.health);
       }),
       onResizeAck((payload) => {
         const store = tabs.getStoreBySessionId(payload.session_id);
@@ -950,8 +1332,15 @@ const App: Component = () => {
         }
       }),
       onTmuxEvent(handleTmuxEvent),
+      onSessionIo((payload) => {
+        tabs.getStoreBySessionId(payload.session_id)?.setState("io", payload.stats);
+      }),
+      onViewportBlockChanged(({ session_id, ...block }) => {
+        tabs.getStoreBySessionId(session_id)?.setState("viewportBlock", block);
+      }),
+      watchFrameRings(),
     ]);
-    unlisteners.push(unFrame, unEnd, unResizeAck, unTmux);
+    unlisteners.push(unFrame, unEnd, unStalled, unResizeAck, unTmux, unIo, unViewportBlock, unFrameRings);
 
     const insertIndexFromX = (insertX?: number): number | undefined => {
       if (insertX == null) return undefined;
@@ -1147,7 +1536,7 @@ const App: Component = () => {
           tabs.addTab(sessionId, label, insertAt, cwd);
           flushPendingFrames(sessionId);
           hydrateTransferredSessionState(sessionId);
-          requestFullRedraw(sessionId).catch(console.error);
+          redrawAdoptedSession(sessionId);
           getCurrentWindow().setFocus().catch(() => {});
         } catch (error) {
           console.error("[Rain] Failed to adopt tab from another window:", error);
@@ -1195,10 +1584,15 @@ const App: Component = () => {
       tabs.addTab(adoptSessionId, label, undefined, cwd);
       flushPendingFrames(adoptSessionId);
       hydrateTransferredSessionState(adoptSessionId);
-      requestFullRedraw(adoptSessionId).catch(console.error);
+      redrawAdoptedSession(adoptSessionId);
     } else {
       // --- Session restore ---
-      const savedWorkspace = await restoreWorkspace();
+      let savedWorkspace = await restoreWorkspace();
+      if (savedWorkspace?.recoveredSessions) {
+        const count = savedWorkspace.tabs.length;
+        const offer = `Rain closed unexpectedly. Restore ${count} ${count === 1 ? "tab" : "tabs"} at their last directories? (shells will restart)`;
+        if (!window.confirm(offer)) savedWorkspace = null;
+      }
       if (savedWorkspace && savedWorkspace.tabs.length > 0) {
         const activeStore = tabs.activeStore();
         const restoreRows = activeStore?.state.rows ?? 24;
@@ -1225,6 +1619,9 @@ const App: Component = () => {
               restoreCols,
               undefined,
               config().tmuxMode,
+              config().ambiguousWide,
+              config().scrollbackLines,
+              frameTransport(),
             );
             createdSessionIds.push(result.session_id);
             if (result.inside_tmux) {
@@ -1404,6 +1801,25 @@ const App: Component = () => {
     );
   });
 
+  // Without a compositor, transparent windows render black and blur does
+  // nothing. Turn the affected settings off and tell the user why.
+  const applyCompositorCapabilities = (caps: CompositorCapabilities) => {
+    const cfg = config();
+    const patch: { windowOpacity?: number; backgroundBlurStrength?: number } = {};
+    if (!caps.transparency && cfg.windowOpacity < 1) patch.windowOpacity = 1;
+    if (!caps.blur && cfg.backgroundBlurStrength > 0) patch.backgroundBlurStrength = 0;
+    if (Object.keys(patch).length === 0) return;
+    updateConfig(patch);
+    if (caps.advice) showToast(caps.advice, "warning", 8000);
+  };
+
+  onMount(async () => {
+    getCompositorCapabilities()
+      .then(applyCompositorCapabilities)
+      .catch((e) => console.warn("[Rain] Compositor detection failed:", e));
+    unlisteners.push(await onCompositorCapabilitiesChanged(applyCompositorCapabilities));
+  });
+
   // Register configured global hotkey (platform support handled in backend).
   createEffect(() => {
     const accelerator = config().globalHotkey?.trim();
@@ -1413,13 +1829,208 @@ const App: Component = () => {
     );
   });
 
+  createEffect(() => {
+    const enabled = config().showTrayIcon;
+    setTrayEnabled(enabled).catch((e) =>
+      console.warn("[Rain] Failed to update tray icon:", e),
+    );
+  });
+
+  // OSC 9;4 progress on the dock icon / taskbar button: the active tab's,
+  // otherwise the first tab reporting any.
+  let windowProgressKey = "";
+  createEffect(() => {
+    const progressOf = (tabId: string) =>
+      tabs.stores.get(tabs.getActivePaneId(tabId))?.state.progress ?? null;
+    const active = tabs.activeTab();
+    const progress =
+      (active && progressOf(active.id)) ??
+      tabs.state.tabs.map((tab) => progressOf(tab.id)).find((p) => p !== null) ??
+      null;
+    const key = progress ? `${progress.state}:${progress.value}` : "none";
+    if (key === windowProgressKey) return;
+    windowProgressKey = key;
+    setWindowProgress(progress?.state ?? "none", progress?.value ?? 0).catch((e) =>
+      console.warn("[Rain] Failed to set window progress:", e),
+    );
+  });
+
+  // Tray session picks are broadcast; only the window holding the session reacts.
+  // Notification clicks go to the window that showed the notification.
+  onMount(async () => {
+    const focusSession = (sessionId: string) => {
+      const index = tabs.state.tabs.findIndex(
+        (tab) => tab.sessionId === sessionId || sessionInTree(tab.paneTree, sessionId),
+      );
+      if (index < 0) return;
+      tabs.switchTab(index);
+      const appWindow = getCurrentWindow();
+      appWindow.show().catch(() => {});
+      appWindow.setFocus().catch(() => {});
+    };
+    unlisteners.push(await onTrayFocusSession(focusSession));
+    unlisteners.push(await onNotificationClicked(focusSession));
+  });
+
+  async function restartToUpdate() {
+    try {
+      const outcome = await installUpdate(false);
+      if (outcome.status !== "deferred") return;
+      const count = outcome.busySessions;
+      const busy = count === 1 ? "1 session is" : `${count} sessions are`;
+      if (window.confirm(`${busy} still running commands. Restart Rain and install the update anyway?`)) {
+        await installUpdate(true);
+      } else {
+        showToast("The update will install once all sessions are idle", "info");
+      }
+    } catch (err) {
+      console.error("[Rain] Failed to install update:", err);
+      showToast(`Failed to install update: ${err}`, "error");
+    }
+  }
+
+  // Background update download and the "what's new" note after an update.
+  onMount(async () => {
+    if (getCurrentWindow().label !== "main") return;
+    unlisteners.push(await onUpdateDownloadProgress((progress) => setUpdateProgress(progress)));
+    unlisteners.push(
+      await onUpdateReady(() => {
+        setUpdateProgress(null);
+        setUpdateReady(true);
+      }),
+    );
+    unlisteners.push(
+      await onUpdateError((message) => {
+        setUpdateProgress(null);
+        showToast(message, "error");
+      }),
+    );
+    const installed = await takeUpdateChangelog().catch(() => null);
+    if (installed) setChangelog(installed);
+  });
+
+  // "Open Rain here" requests (file manager, second launch) land in the main window.
+  onMount(async () => {
+    if (getCurrentWindow().label !== "main") return;
+    const drain = async () => {
+      const dirs = await takePendingOpenDirectories().catch(() => [] as string[]);
+      for (const dir of dirs) await openTabAt(dir);
+    };
+    unlisteners.push(await onOpenDirectoryRequested(() => void drain()));
+    await drain();
+  });
+
+  // rain:// links, validated (and for commands, confirmed) by the backend.
+  onMount(async () => {
+    if (getCurrentWindow().label !== "main") return;
+    const drain = async () => {
+      const links = await takePendingDeepLinks().catch(() => [] as DeepLink[]);
+      for (const link of links) await runDeepLink(link);
+    };
+    unlisteners.push(await onDeepLinkRequested(() => void drain()));
+    await drain();
+  });
+
+  // Register user-defined OSC numbers so their payloads reach the UI.
+  createEffect(() => {
+    const codes = config().customOscCodes.filter(
+      (code) => Number.isInteger(code) && code >= 0 && code <= 65535,
+    );
+    setCustomOscCodes(codes).catch((e) =>
+      console.warn("[Rain] Failed to register custom OSC codes:", e),
+    );
+  });
+
+  // DA1/DA2 replies; implemented features like Sixel are added by the backend.
+  createEffect(() => {
+    const { conformanceLevel, deviceAttributeFeatures, hiddenDeviceAttributeFeatures } = config();
+    const valid = (p: number) => Number.isInteger(p) && p > 0 && p <= 65535;
+    setDeviceAttributes({
+      conformance: conformanceLevel,
+      extraFeatures: deviceAttributeFeatures.filter(valid),
+      hiddenFeatures: hiddenDeviceAttributeFeatures.filter(valid),
+    }).catch((e) => console.warn("[Rain] Failed to set device attributes:", e));
+  });
+
+  // Shell argument templates; invalid ones are reported and none applied.
+  createEffect(() => {
+    setShellArgs(config().shellArgs).catch((e) =>
+      console.warn("[Rain] Failed to apply shell arguments:", e),
+    );
+  });
+
+  // Apply scrollback limit changes to sessions that are already running.
+  createEffect(() => {
+    const lines = config().scrollbackLines;
+    const memoryMb = config().scrollbackMemoryMb;
+    const maxBytes = memoryMb > 0 ? memoryMb * 1024 * 1024 : undefined;
+    const compress = config().scrollbackCompression;
+    for (const sessionId of tabs.sessionIds()) {
+      setScrollbackLimit(sessionId, lines, maxBytes, compress).catch((e) =>
+        console.warn("[Rain] Failed to update scrollback limit:", e),
+      );
+    }
+  });
+
+  // Minimum contrast applies to every session, including ones opened later.
+  createEffect(() => {
+    const ratio = config().minimumContrast;
+    for (const sessionId of tabs.sessionIds()) {
+      setMinContrast(sessionId, ratio).catch((e) =>
+        console.warn("[Rain] Failed to update minimum contrast:", e),
+      );
+    }
+  });
+
+  createEffect(() => {
+    const bright = config().boldIsBright;
+    for (const sessionId of tabs.sessionIds()) {
+      setBoldIsBright(sessionId, bright).catch((e) =>
+        console.warn("[Rain] Failed to update bold-as-bright:", e),
+      );
+    }
+  });
+
+  createEffect(() => {
+    const enabled = config().fileDownloads;
+    if (!enabled) setDownloadOffers([]);
+    for (const sessionId of tabs.sessionIds()) {
+      setFileDownloads(sessionId, enabled).catch((e) =>
+        console.warn("[Rain] Failed to update file downloads:", e),
+      );
+    }
+  });
+
+  // Keep a shell started ahead of time for new tabs.
+  createEffect(() => {
+    setWarmPoolEnabled(config().warmShellPool).catch((e) =>
+      console.warn("[Rain] Failed to update warm shell pool:", e),
+    );
+  });
+
+  // Push idle-session hibernation thresholds to the backend sweeper.
+  createEffect(() => {
+    const { hibernateIdleSessions, hibernateIdleMinutes } = config();
+    setHibernationConfig({
+      enabled: hibernateIdleSessions,
+      idleThresholdSecs: Math.round(hibernateIdleMinutes * 60),
+    }).catch((e) =>
+      console.warn("[Rain] Failed to apply hibernation config:", e),
+    );
+  });
+
   const effectiveBgColor = createMemo(
-    () => config().customBgColor ?? THEME_BG_MAP.get(theme()) ?? "#0e0e0e",
+    () =>
+      config().customBgColor ??
+      THEME_BG_MAP.get(theme()) ??
+      fileThemeInfo(theme())?.background ??
+      "#0e0e0e",
   );
   const effectiveAccentColor = createMemo(
     () =>
       config().customAccentColor ??
       THEME_ACCENT_MAP.get(theme()) ??
+      fileThemeInfo(theme())?.ansi[4] ??
       "#01c1a2",
   );
 
@@ -1447,7 +2058,8 @@ const App: Component = () => {
   // Apply the selected macOS glass engine. Liquid mode uses the plugin and
   // falls back to CGS blur if runtime plugin calls fail.
   createEffect(() => {
-    if (!isMac) return;
+    // Safe mode skips the native glass plugin entirely.
+    if (!isMac || isSafeMode()) return;
 
     const selectedEngine = config().macosGlassEngine;
     const engine = effectiveMacGlassEngine();
@@ -1633,7 +2245,27 @@ const App: Component = () => {
         {(info) => (
           <div class="update-banner">
             <span class="update-banner-text">
-              Rain <strong>v{info().latestVersion}</strong> is available (you have v{info().currentVersion})
+              <Show
+                when={updateReady()}
+                fallback={
+                  <>
+                    Rain <strong>v{info().latestVersion}</strong> is available (you have v{info().currentVersion})
+                    {info().size ? ` · ${(info().size! / (1024 * 1024)).toFixed(1)} MB` : ""}
+                    <Show when={info().installable && updateProgress()}>
+                      {(progress) => (
+                        <>
+                          {" "}— downloading
+                          {progress().total
+                            ? ` ${Math.floor((progress().downloaded / progress().total!) * 100)}%`
+                            : "…"}
+                        </>
+                      )}
+                    </Show>
+                  </>
+                }
+              >
+                Rain <strong>v{info().latestVersion}</strong> is ready and will install once all sessions are idle
+              </Show>
             </span>
             <div class="update-banner-actions">
               <button
@@ -1646,12 +2278,57 @@ const App: Component = () => {
               </button>
               <Show when={info().releaseUrl}>
                 <button
-                  class="primary"
+                  class={updateReady() ? "" : "primary"}
                   onClick={() => window.open(info().releaseUrl!, "_blank")}
                 >
                   View Release
                 </button>
               </Show>
+              <Show when={updateReady()}>
+                <button class="primary" onClick={() => void restartToUpdate()}>
+                  Restart Now
+                </button>
+              </Show>
+            </div>
+          </div>
+        )}
+      </Show>
+      <Show when={downloadOffers().length > 0}>
+        <div class="update-banner">
+          <span class="update-banner-text">
+            <Show
+              when={downloadOffers().length > 1}
+              fallback={<><strong>{downloadOffers()[0].name ?? "A file"}</strong> was sent by the terminal</>}
+            >
+              <strong>{downloadOffers().length} files</strong> were sent by the terminal
+            </Show>
+            {` · ${formatOfferSize(downloadOffers().reduce((sum, offer) => sum + offer.size, 0))}`}
+          </span>
+          <div class="update-banner-actions">
+            <button onClick={discardDownloadOffers}>Discard</button>
+            <button class="primary" onClick={() => void saveDownloadOffers()}>
+              Save…
+            </button>
+          </div>
+        </div>
+      </Show>
+      <Show when={!updateInfo() && changelog()}>
+        {(installed) => (
+          <div class="update-banner">
+            <span class="update-banner-text">
+              Rain was updated to <strong>v{installed().version}</strong>
+            </span>
+            <div class="update-banner-actions">
+              <button onClick={() => setChangelog(null)}>Dismiss</button>
+              <button
+                class="primary"
+                onClick={() => {
+                  window.open(releaseUrlFor(installed().version), "_blank");
+                  setChangelog(null);
+                }}
+              >
+                What's New
+              </button>
             </div>
           </div>
         )}
@@ -1749,6 +2426,12 @@ const App: Component = () => {
           onClose={() => setShowPalette(false)}
         />
       </Show>
+      <Show when={clipboardEntries()}>
+        <CommandPalette
+          actions={clipboardActions()}
+          onClose={() => setClipboardEntries(null)}
+        />
+      </Show>
       <Show when={showHistory()}>
         <HistoryBrowser
           snapshots={allSnapshots()}
@@ -8,6 +8,7 @@ import type { TerminalStore } from "../stores/terminal";
 import { useConfig } from "../stores/config";
 import { useTheme, THEME_LIST, THEME_ANSI_PALETTES } from "../stores/theme";
 import { colorToCSS } from "../lib/color";
+import { spanLink } from "../lib/links";
 import { measureFontMetrics } from "../lib/font";
 
 /**
@@ -80,15 +81,20 @@ export const CanvasTerminal: Component<{
     for (const line of lines) {
       const spans = line.spans.map((s) => ({
         text: s.text,
+        cols: s.cols,
         fg: colorToCSS(s.fg, palette) ?? "#e0e0e0",
         bg: colorToCSS(s.bg, palette) ?? "transparent",
         bold: s.bold,
         italic: s.italic,
         underline: s.underline,
+        underlineStyle: s.underline_style,
+        underlineColor: s.underline_color ? colorToCSS(s.underline_color, palette) ?? undefined : undefined,
         strikethrough: s.strikethrough,
         dim: s.dim,
+        link: spanLink(s),
       }));
       renderer!.updateLine(line.index, spans);
+      renderer!.setLineSize(line.index, line.line_size);
     }
 
     // Render cursor
@@ -9,11 +9,26 @@ import {
 } from "solid-js";
 import { Portal } from "solid-js/web";
 import { useConfig, type MacosGlassEngine } from "../stores/config";
-import { useTheme, THEME_LIST } from "../stores/theme";
+import {
+  useTheme,
+  THEME_LIST,
+  fileThemes,
+  fileThemeName,
+  refreshFileThemes,
+} from "../stores/theme";
 import {
   computeBlurProfile,
 } from "../lib/glass";
-import { saveTextToFile } from "../lib/ipc";
+import {
+  saveTextToFile,
+  exportAppState,
+  importAppState,
+  getGlobalHotkeyCapabilities,
+  onGlobalHotkeyStatus,
+  setExplorerIntegration,
+  type HotkeyCapabilities,
+  type NewSessionCwd,
+} from "../lib/ipc";
 import {
   LIQUID_GLASS_VARIANTS,
   isLiquidGlassSupported,
@@ -46,6 +61,13 @@ import { showToast } from "./Toast";
 
 type Section = "appearance" | "terminal" | "shortcuts" | "profiles";
 
+const NEW_SESSION_CWDS: ReadonlyArray<{ value: NewSessionCwd; label: string }> = [
+  { value: "inherit", label: "Current directory" },
+  { value: "original", label: "Directory the pane started in" },
+  { value: "home", label: "Home directory" },
+  { value: "projectRoot", label: "Project root (git)" },
+];
+
 const MACOS_GLASS_ENGINES: ReadonlyArray<{
   value: MacosGlassEngine;
   label: string;
@@ -345,7 +367,23 @@ export const Settings: Component = () => {
   const [profileEnvDraft, setProfileEnvDraft] = createSignal("");
   const [triggerList, setTriggerList] = createSignal<OutputTrigger[]>(getTriggers());
   const [nativeLiquidSupported, setNativeLiquidSupported] = createSignal<boolean | null>(null);
+  const [hotkeyCaps, setHotkeyCaps] = createSignal<HotkeyCapabilities | null>(null);
+  const [exportScrollback, setExportScrollback] = createSignal(false);
   const isMac = typeof navigator !== "undefined" && navigator.userAgent.includes("Mac");
+  const isWindows = typeof navigator !== "undefined" && navigator.userAgent.includes("Windows");
+
+  async function handleExplorerIntegration(enabled: boolean) {
+    try {
+      await setExplorerIntegration(enabled);
+      showToast(
+        enabled ? "Added \"Open Rain here\" to Explorer" : "Removed Explorer integration",
+        "success",
+      );
+    } catch (e) {
+      console.error(e);
+      showToast(String(e), "error");
+    }
+  }
 
   function formatEnvMap(env?: Record<string, string>): string {
     if (!env || Object.keys(env).length === 0) return "";
@@ -444,6 +482,44 @@ export const Settings: Component = () => {
     reader.readAsText(file);
   }
 
+  // The backend decides which entries go into the archive and redacts them.
+  function frontendStorage(): Record<string, string> {
+    const entries: Record<string, string> = {};
+    for (let i = 0; i < localStorage.length; i++) {
+      const key = localStorage.key(i);
+      if (!key?.startsWith("rain-")) continue;
+      const value = localStorage.getItem(key);
+      if (value !== null) entries[key] = value;
+    }
+    return entries;
+  }
+
+  async function handleExportAppState() {
+    saveConfig();
+    try {
+      const path = await exportAppState(frontendStorage(), exportScrollback());
+      if (path) showToast(`Exported settings to ${path}`, "success");
+    } catch (e) {
+      console.error(e);
+      showToast(`Failed to export settings: ${e}`, "error");
+    }
+  }
+
+  async function handleImportAppState() {
+    try {
+      const entries = await importAppState();
+      if (!entries) return;
+      for (const [key, value] of Object.entries(entries)) {
+        localStorage.setItem(key, value);
+      }
+      showToast("Settings imported; reloading", "success");
+      window.setTimeout(() => window.location.reload(), 800);
+    } catch (e) {
+      console.error(e);
+      showToast(`Failed to import settings: ${e}`, "error");
+    }
+  }
+
   // Cmd+S saves settings
   function handleKeyDown(e: KeyboardEvent) {
     if (e.metaKey && e.key === "s") {
@@ -454,6 +530,7 @@ export const Settings: Component = () => {
 
   onMount(() => {
     document.addEventListener("keydown", handleKeyDown);
+    void refreshFileThemes();
     if (isMac) {
       isLiquidGlassSupported()
         .then((supported) => setNativeLiquidSupported(supported))
@@ -464,8 +541,19 @@ export const Settings: Component = () => {
     }
   });
 
+  let unlistenHotkeyStatus: (() => void) | undefined;
+  onMount(() => {
+    getGlobalHotkeyCapabilities()
+      .then(setHotkeyCaps)
+      .catch((error) => console.warn("[Rain] Failed to query global hotkey support:", error));
+    onGlobalHotkeyStatus(setHotkeyCaps).then((unlisten) => {
+      unlistenHotkeyStatus = unlisten;
+    });
+  });
+
   onCleanup(() => {
     document.removeEventListener("keydown", handleKeyDown);
+    unlistenHotkeyStatus?.();
   });
 
   function clampFontSize(val: number): number {
@@ -476,6 +564,10 @@ export const Settings: Component = () => {
     return Math.min(2.0, Math.max(0.0, Math.round(val * 100) / 100));
   }
 
+  function clampContrast(val: number): number {
+    return Math.min(21, Math.max(1, Math.round(val * 10) / 10));
+  }
+
   function clampScrollback(val: number): number {
     return Math.min(100_000, Math.max(1_000, val));
   }
@@ -563,6 +655,28 @@ export const Settings: Component = () => {
                     </button>
                   )}
                 </For>
+                <For each={fileThemes()}>
+                  {(t) => (
+                    <button
+                      class={`settings-theme-card ${theme() === fileThemeName(t) ? "settings-theme-card-active" : ""}`}
+                      onClick={() => selectTheme(fileThemeName(t))}
+                      title={t.name}
+                    >
+                      <div class="theme-preview">
+                        <div class="theme-preview-swatch" style={{ background: t.background }} />
+                        <div class="theme-preview-accent" style={{ background: t.ansi[4] }} />
+                      </div>
+                      <div class="theme-card-label">
+                        {t.label}
+                        <Show when={theme() === fileThemeName(t)}>
+                          <span class="theme-card-check">
+                            <IconCheck size={10} />
+                          </span>
+                        </Show>
+                      </div>
+                    </button>
+                  )}
+                </For>
               </div>
             </div>
 
@@ -1016,6 +1130,44 @@ export const Settings: Component = () => {
                   </div>
                 </div>
               </div>
+
+              <div class="settings-field">
+                <label class="settings-label">Minimum Contrast</label>
+                <p class="settings-hint">
+                  Lighten or darken text that is hard to read against its background, such as dark blue on black (1 - 21; 1 is off, 4.5 meets WCAG AA).
+                </p>
+                <div class="settings-number-input">
+                  <input
+                    class="settings-input settings-input-narrow"
+                    type="number"
+                    min="1"
+                    max="21"
+                    step="0.5"
+                    value={config().minimumContrast}
+                    onInput={(e) => {
+                      const v = parseFloat(e.currentTarget.value);
+                      if (!isNaN(v))
+                        updateConfig({ minimumContrast: clampContrast(v) });
+                    }}
+                  />
+                  <span class="settings-number-unit">: 1</span>
+                </div>
+              </div>
+
+              <div class="settings-field settings-field-row">
+                <div class="settings-field-info">
+                  <label class="settings-label">Bold Text in Bright Colors</label>
+                  <p class="settings-hint">
+                    Draw bold text in the first eight ANSI colors with their bright variants, as many older terminals do. Some ls and prompt color schemes rely on it.
+                  </p>
+                </div>
+                <button
+                  class={`settings-toggle ${config().boldIsBright ? "settings-toggle-on" : ""}`}
+                  onClick={() => updateConfig({ boldIsBright: !config().boldIsBright })}
+                >
+                  <span class="settings-toggle-knob" />
+                </button>
+              </div>
             </div>
 
             {/* Reset Appearance */}
@@ -1233,6 +1385,133 @@ export const Settings: Component = () => {
                 </button>
               </div>
 
+              <div class="settings-field settings-field-row">
+                <div class="settings-field-info">
+                  <label class="settings-label">Shell Integration over SSH</label>
+                  <p class="settings-hint">
+                    Offer to load Rain's bash/zsh hooks into remote shells for the length of the connection. Nothing is installed on the server.
+                  </p>
+                </div>
+                <button
+                  class={`settings-toggle ${config().offerRemoteShellIntegration ? "settings-toggle-on" : ""}`}
+                  onClick={() =>
+                    updateConfig({
+                      offerRemoteShellIntegration: !config().offerRemoteShellIntegration,
+                    })
+                  }
+                >
+                  <span class="settings-toggle-knob" />
+                </button>
+              </div>
+
+              <div class="settings-field settings-field-row">
+                <div class="settings-field-info">
+                  <label class="settings-label">File Downloads</label>
+                  <p class="settings-hint">
+                    Let programs send files to save, as iTerm2's imgcat and it2dl do. Offers are collected in one banner; nothing is written unless you save it. When off, such files are dropped.
+                  </p>
+                </div>
+                <button
+                  class={`settings-toggle ${config().fileDownloads ? "settings-toggle-on" : ""}`}
+                  onClick={() => updateConfig({ fileDownloads: !config().fileDownloads })}
+                >
+                  <span class="settings-toggle-knob" />
+                </button>
+              </div>
+
+              <div class="settings-field settings-field-row">
+                <div class="settings-field-info">
+                  <label class="settings-label">80/132 Column Switching</label>
+                  <p class="settings-hint">
+                    Let legacy programs and vttest set the terminal to 80 or 132 columns until they finish. When off, the pane keeps its own width.
+                  </p>
+                </div>
+                <button
+                  class={`settings-toggle ${config().allowColumnMode ? "settings-toggle-on" : ""}`}
+                  onClick={() => updateConfig({ allowColumnMode: !config().allowColumnMode })}
+                >
+                  <span class="settings-toggle-knob" />
+                </button>
+              </div>
+
+              <div class="settings-field settings-field-row">
+                <div class="settings-field-info">
+                  <label class="settings-label">Wide Ambiguous Characters</label>
+                  <p class="settings-hint">
+                    Give East Asian ambiguous-width characters such as ○ and → two columns, as CJK locales expect. Applies to new sessions; change an open one from the command palette.
+                  </p>
+                </div>
+                <button
+                  class={`settings-toggle ${config().ambiguousWide ? "settings-toggle-on" : ""}`}
+                  onClick={() => updateConfig({ ambiguousWide: !config().ambiguousWide })}
+                >
+                  <span class="settings-toggle-knob" />
+                </button>
+              </div>
+
+              <div class="settings-field settings-field-row">
+                <div class="settings-field-info">
+                  <label class="settings-label">Binary Render Frames</label>
+                  <p class="settings-hint">
+                    Send screen updates as MessagePack over a dedicated channel instead of JSON events, which is cheaper for busy sessions. Applies to new sessions.
+                  </p>
+                </div>
+                <button
+                  class={`settings-toggle ${config().binaryRenderFrames ? "settings-toggle-on" : ""}`}
+                  onClick={() => updateConfig({ binaryRenderFrames: !config().binaryRenderFrames })}
+                >
+                  <span class="settings-toggle-knob" />
+                </button>
+              </div>
+
+              <div class="settings-field settings-field-row">
+                <div class="settings-field-info">
+                  <label class="settings-label">Batched Frame Ring</label>
+                  <p class="settings-hint">
+                    Collect screen updates in a ring that the window reads in batches, for commands that print faster than updates can be sent one by one. Applies to new sessions.
+                  </p>
+                </div>
+                <button
+                  class={`settings-toggle ${config().frameRing ? "settings-toggle-on" : ""}`}
+                  onClick={() => updateConfig({ frameRing: !config().frameRing })}
+                >
+                  <span class="settings-toggle-knob" />
+                </button>
+              </div>
+
+              <div class="settings-field settings-field-row">
+                <div class="settings-field-info">
+                  <label class="settings-label">Pre-start Shells</label>
+                  <p class="settings-hint">
+                    Keep a shell running in the background for each profile so new tabs skip shell startup. Uses one extra shell process per profile.
+                  </p>
+                </div>
+                <button
+                  class={`settings-toggle ${config().warmShellPool ? "settings-toggle-on" : ""}`}
+                  onClick={() => updateConfig({ warmShellPool: !config().warmShellPool })}
+                >
+                  <span class="settings-toggle-knob" />
+                </button>
+              </div>
+
+              <div class="settings-field">
+                <label class="settings-label">New Tab & Split Directory</label>
+                <p class="settings-hint">
+                  Where new tabs and splits start. Falls back to the next best directory when the chosen one no longer exists.
+                </p>
+                <select
+                  class="settings-input"
+                  value={config().newSessionCwd}
+                  onChange={(e) => {
+                    updateConfig({ newSessionCwd: e.currentTarget.value as NewSessionCwd });
+                  }}
+                >
+                  <For each={NEW_SESSION_CWDS}>
+                    {(option) => <option value={option.value}>{option.label}</option>}
+                  </For>
+                </select>
+              </div>
+
               <div class="settings-field">
                 <label class="settings-label">Scrollback Lines</label>
                 <p class="settings-hint">
@@ -1246,18 +1525,67 @@ export const Settings: Component = () => {
                     max="100000"
                     step="1000"
                     value={config().scrollbackLines}
-                    onInput={(e) => {
+                    onChange={(e) => {
+                      // Applied on commit: live sessions drop lines past the
+                      // limit, so a half-typed value must not reach them.
                       const v = parseInt(e.currentTarget.value);
-                      if (!isNaN(v))
-                        updateConfig({
-                          scrollbackLines: clampScrollback(v),
-                        });
+                      if (isNaN(v)) {
+                        e.currentTarget.value = String(config().scrollbackLines);
+                        return;
+                      }
+                      const lines = clampScrollback(v);
+                      e.currentTarget.value = String(lines);
+                      updateConfig({ scrollbackLines: lines });
                     }}
                   />
                   <span class="settings-number-unit">lines</span>
                 </div>
               </div>
 
+              <div class="settings-field">
+                <label class="settings-label">Scrollback Memory Limit</label>
+                <p class="settings-hint">
+                  Memory each session's scrollback may use before its oldest lines are dropped (0 for no limit).
+                </p>
+                <div class="settings-number-input">
+                  <input
+                    class="settings-input settings-input-wide"
+                    type="number"
+                    min="0"
+                    max="4096"
+                    step="16"
+                    value={config().scrollbackMemoryMb}
+                    onChange={(e) => {
+                      // Applied on commit, like the line limit above.
+                      const v = parseInt(e.currentTarget.value);
+                      if (isNaN(v)) {
+                        e.currentTarget.value = String(config().scrollbackMemoryMb);
+                        return;
+                      }
+                      const mb = Math.min(4096, Math.max(0, v));
+                      e.currentTarget.value = String(mb);
+                      updateConfig({ scrollbackMemoryMb: mb });
+                    }}
+                  />
+                  <span class="settings-number-unit">MB</span>
+                </div>
+              </div>
+
+              <div class="settings-field settings-field-row">
+                <div class="settings-field-info">
+                  <label class="settings-label">Compress Older Scrollback</label>
+                  <p class="settings-hint">
+                    Keep scrollback beyond the newest few thousand lines compressed, so the same memory holds much more history. Older lines are decompressed when scrolled to or searched.
+                  </p>
+                </div>
+                <button
+                  class={`settings-toggle ${config().scrollbackCompression ? "settings-toggle-on" : ""}`}
+                  onClick={() => updateConfig({ scrollbackCompression: !config().scrollbackCompression })}
+                >
+                  <span class="settings-toggle-knob" />
+                </button>
+              </div>
+
               <div class="settings-field">
                 <label class="settings-label">Command History Limit</label>
                 <p class="settings-hint">
@@ -1559,7 +1887,49 @@ export const Settings: Component = () => {
                     updateConfig({ globalHotkey: value.length > 0 ? value : null });
                   }}
                 />
+                <Show when={hotkeyCaps()?.reason}>
+                  {(reason) => <p class="settings-hint">{reason()}</p>}
+                </Show>
+                <Show when={hotkeyCaps()?.boundTrigger}>
+                  {(trigger) => (
+                    <p class="settings-hint">
+                      Bound by the desktop as <code>{trigger()}</code>.
+                    </p>
+                  )}
+                </Show>
               </div>
+
+              <div class="settings-field settings-field-row">
+                <div class="settings-field-info">
+                  <label class="settings-label">Tray Icon</label>
+                  <p class="settings-hint">
+                    Show Rain in the system tray / menu bar with a list of open sessions.
+                  </p>
+                </div>
+                <button
+                  class={`settings-toggle ${config().showTrayIcon ? "settings-toggle-on" : ""}`}
+                  onClick={() => updateConfig({ showTrayIcon: !config().showTrayIcon })}
+                >
+                  <span class="settings-toggle-knob" />
+                </button>
+              </div>
+
+              <Show when={isWindows}>
+                <div class="settings-field">
+                  <label class="settings-label">Explorer Integration</label>
+                  <p class="settings-hint">
+                    Adds "Open Rain here" to the right-click menu of folders in File Explorer.
+                  </p>
+                  <div style={{ display: "flex", gap: "8px" }}>
+                    <button class="settings-btn" onClick={() => handleExplorerIntegration(true)}>
+                      Add to Explorer
+                    </button>
+                    <button class="settings-btn" onClick={() => handleExplorerIntegration(false)}>
+                      Remove
+                    </button>
+                  </div>
+                </div>
+              </Show>
             </div>
           </Show>
 
@@ -1584,6 +1954,30 @@ export const Settings: Component = () => {
               style={{ display: "none" }}
               onChange={handleFileSelected}
             />
+            <p class="settings-hint">
+              Everything else too: config, saved workspace, profiles, theme, keybindings and triggers, for support or
+              moving to a new machine. Secrets such as tokens are left out.
+            </p>
+            <div class="settings-field settings-field-row">
+              <div class="settings-field-info">
+                <label class="settings-label">Include Scrollback</label>
+                <p class="settings-hint">Add the output of open sessions to the export.</p>
+              </div>
+              <button
+                class={`settings-toggle ${exportScrollback() ? "settings-toggle-on" : ""}`}
+                onClick={() => setExportScrollback(!exportScrollback())}
+              >
+                <span class="settings-toggle-knob" />
+              </button>
+            </div>
+            <div style={{ display: "flex", gap: "8px" }}>
+              <button class="settings-btn" onClick={handleExportAppState}>
+                Export Everything…
+              </button>
+              <button class="settings-btn" onClick={handleImportAppState}>
+                Import Everything…
+              </button>
+            </div>
           </div>
 
           {/* ---- PROFILES ---- */}
@@ -1694,6 +2088,25 @@ export const Settings: Component = () => {
                         />
                       </div>
 
+                      <div class="settings-field">
+                        <label class="settings-label">Command</label>
+                        <p class="settings-hint">
+                          Run this command instead of a shell, e.g. <code>htop</code> or{" "}
+                          <code>ssh user@host</code>, found on your login shell's PATH. The tab closes when it exits successfully.
+                        </p>
+                        <input
+                          class="settings-input"
+                          type="text"
+                          value={profile().command ?? ""}
+                          placeholder="Start an interactive shell"
+                          onInput={(e) => {
+                            const command = e.currentTarget.value.trim();
+                            updateProfile(profile().id, { command: command || undefined });
+                            refreshProfiles(profile().id);
+                          }}
+                        />
+                      </div>
+
                       <div class="settings-field">
                         <label class="settings-label">Working Directory</label>
                         <p class="settings-hint">
@@ -34,6 +34,16 @@ export const StatusBar: Component<{ store: TerminalStore | undefined }> = (props
     return `${cols}x${rows}`;
   };
 
+  const formatRate = (bytesPerSecond: number) => {
+    if (bytesPerSecond < 1024) return `${Math.round(bytesPerSecond)} B/s`;
+    if (bytesPerSecond < 1024 * 1024) return `${(bytesPerSecond / 1024).toFixed(1)} KB/s`;
+    return `${(bytesPerSecond / (1024 * 1024)).toFixed(1)} MB/s`;
+  };
+  const ioActive = () => {
+    const io = props.store?.state.io;
+    return !!io && (io.readRate > 0 || io.writeRate > 0);
+  };
+
   const isConnected = () => props.store?.state.connected ?? false;
 
   // Extract process name from the terminal title
@@ -72,8 +82,35 @@ export const StatusBar: Component<{ store: TerminalStore | undefined }> = (props
         </div>
       </Show>
 
+      <Show when={props.store?.state.readOnly}>
+        <div class="status-item status-readonly-badge" title="Input is locked for this pane">
+          <span>read-only</span>
+        </div>
+      </Show>
+
+      <Show when={(props.store?.state.queuedCommands.length ?? 0) > 0}>
+        <div
+          class="status-item status-queue-badge"
+          title={props.store?.state.queuedCommands.map((q) => q.command).join("\n")}
+        >
+          <span>{props.store?.state.queuedCommands.length} queued</span>
+        </div>
+      </Show>
+
       <div class="status-spacer" />
 
+      <Show when={ioActive()}>
+        <div
+          class="status-item status-io-badge"
+          title={`${props.store?.state.io?.bytesRead ?? 0} bytes received, ${props.store?.state.io?.bytesWritten ?? 0} sent`}
+        >
+          <span>↓ {formatRate(props.store?.state.io?.readRate ?? 0)}</span>
+          <Show when={(props.store?.state.io?.writeRate ?? 0) > 0}>
+            <span>↑ {formatRate(props.store?.state.io?.writeRate ?? 0)}</span>
+          </Show>
+        </div>
+      </Show>
+
       <Show when={config().statusBarShowActiveProcess && activeProcess()}>
         <div class="status-item status-process-badge">
           <IconTerminal size={10} />
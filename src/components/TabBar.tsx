@@ -17,6 +17,8 @@ import {
   emitCrossWindow,
   requestFullRedraw,
   stageTabTransferManifest,
+  extendTabTransfer,
+  onTabTransferProgress,
   type TabTransferFailureReason,
   type WindowBounds,
 } from "../lib/ipc";
@@ -31,6 +33,10 @@ const PREPARE_RETRY_COUNT = 4;
 const TARGET_READY_ATTEMPTS = 15;
 const TARGET_READY_ATTEMPT_TIMEOUT_MS = 400;
 const TARGET_READY_RETRY_DELAY_MS = 60;
+// Staged manifests expire unless the source keeps extending them, so a
+// slow-loading target window doesn't lose a heavy tab mid-move.
+const TRANSFER_TTL_MS = 30_000;
+const TRANSFER_HEARTBEAT_MS = 10_000;
 
 type PrepareResultPayload = {
   requestId: string;
@@ -131,6 +137,8 @@ export const TabBar: Component<{
     return store.state.activeBlock !== null;
   };
 
+  const getProgress = (tab: TabData) => getTerminalStore(tab)?.state.progress ?? null;
+
   const startEditing = (tab: TabData) => {
     setEditingTabId(tab.id);
     setEditValue(tab.customLabel ?? tabLabel(tab));
@@ -367,7 +375,7 @@ export const TabBar: Component<{
     }
     const transferId = createTransferId();
     try {
-      await stageTabTransferManifest(transferId, payload.manifest);
+      await stageTabTransferManifest(transferId, payload.manifest, TRANSFER_TTL_MS);
       return {
         transferId,
         primarySessionId: payload.primarySessionId,
@@ -384,7 +392,7 @@ export const TabBar: Component<{
         })),
       };
       try {
-        await stageTabTransferManifest(transferId, compactManifest);
+        await stageTabTransferManifest(transferId, compactManifest, TRANSFER_TTL_MS);
         return {
           transferId,
           primarySessionId: payload.primarySessionId,
@@ -400,6 +408,32 @@ export const TabBar: Component<{
     }
   }
 
+  /** Extend the staged transfer until `run` settles or the transfer ends. */
+  async function withTransferHeartbeat<T>(transferId: string, run: () => Promise<T>): Promise<T> {
+    let timer: number | null = null;
+    const stop = () => {
+      if (timer != null) window.clearInterval(timer);
+      timer = null;
+    };
+    timer = window.setInterval(() => {
+      extendTabTransfer(transferId, TRANSFER_TTL_MS)
+        .then((expiresAt) => {
+          if (expiresAt == null) stop();
+        })
+        .catch(stop);
+    }, TRANSFER_HEARTBEAT_MS);
+    const unlisten = await onTabTransferProgress((progress) => {
+      if (progress.transfer_id !== transferId) return;
+      if (progress.stage === "committed" || progress.stage === "expired") stop();
+    }).catch(() => null);
+    try {
+      return await run();
+    } finally {
+      stop();
+      if (unlisten) void unlisten();
+    }
+  }
+
   function describeMoveFailure(reason?: TabTransferFailureReason | null): string {
     switch (reason) {
       case "duplicate_session":
@@ -563,6 +597,7 @@ export const TabBar: Component<{
           underline: span.underline,
           strikethrough: span.strikethrough,
           ...(span.url ? { url: span.url } : {}),
+          ...(span.path ? { path: span.path } : {}),
         })),
       })),
       timestamp: snap.timestamp,
@@ -803,28 +838,30 @@ export const TabBar: Component<{
     const staged = await stageTransferPayload(tab);
     if (!staged) return;
 
-    const createdWindow = await createChildWindow(
-      staged.primarySessionId,
-      staged.label,
-      window.screenX + 50,
-      window.screenY + 50,
-      window.innerWidth,
-      window.innerHeight,
-      staged.cwd || undefined,
-      staged.transferId,
-    ).catch((err) => {
-      console.error("[Rain] Failed to create child window:", err);
-      showMoveGuard("Failed to create target window.");
-      return null;
-    });
-    if (createdWindow) {
-      const ready = await waitForTargetAdoptReady(createdWindow, staged.transferId);
-      if (!ready) {
-        showMoveGuard("Target window is still starting up. Try again.");
-        return;
+    await withTransferHeartbeat(staged.transferId, async () => {
+      const createdWindow = await createChildWindow(
+        staged.primarySessionId,
+        staged.label,
+        window.screenX + 50,
+        window.screenY + 50,
+        window.innerWidth,
+        window.innerHeight,
+        staged.cwd || undefined,
+        staged.transferId,
+      ).catch((err) => {
+        console.error("[Rain] Failed to create child window:", err);
+        showMoveGuard("Failed to create target window.");
+        return null;
+      });
+      if (createdWindow) {
+        const ready = await waitForTargetAdoptReady(createdWindow, staged.transferId);
+        if (!ready) {
+          showMoveGuard("Target window is still starting up. Try again.");
+          return;
+        }
+        await executeMoveFlow(tabId, staged, createdWindow, tabIndex);
       }
-      await executeMoveFlow(tabId, staged, createdWindow, tabIndex);
-    }
+    });
   }
 
   async function handleMoveToWindow(tabId: string, targetLabel: string) {
@@ -836,7 +873,9 @@ export const TabBar: Component<{
     const staged = await stageTransferPayload(tab);
     if (!staged) return;
 
-    await executeMoveFlow(tabId, staged, targetLabel, tabIndex);
+    await withTransferHeartbeat(staged.transferId, () =>
+      executeMoveFlow(tabId, staged, targetLabel, tabIndex),
+    );
   }
 
   // Close context menu on outside click or Escape
@@ -987,6 +1026,15 @@ export const TabBar: Component<{
                 >
                   <IconClose size={10} />
                 </button>
+
+                <Show when={getProgress(tab)}>
+                  {(progress) => (
+                    <span
+                      class={`tab-progress tab-progress-${progress().state}`}
+                      style={progress().state === "indeterminate" ? undefined : { width: `${progress().value}%` }}
+                    />
+                  )}
+                </Show>
               </div>
             );
           }}
@@ -1,8 +1,9 @@
 import { Component, For, Show, onMount, onCleanup, createSignal, createEffect, createMemo, on, untrack } from "solid-js";
 import { ContextMenu } from "./ContextMenu";
+import { showToast } from "./Toast";
 import { produce } from "solid-js/store";
-import type { TerminalStore } from "../stores/terminal";
-import type { CommandSnapshot, RenderedLine, SearchMatch } from "../lib/types";
+import { visibleSearchMatches, type TerminalStore } from "../stores/terminal";
+import type { CommandSnapshot, MouseAction, MouseButton, RenderedLine, SearchMatch } from "../lib/types";
 import {
   createSelectionState,
   normalizeRange,
@@ -24,12 +25,14 @@ import {
   type FilesystemCache,
   type SnoopCacheEntry,
 } from "../lib/suggestions";
-import { writeInput, resizeTerminal, requestFullRedraw, tmuxSendKeys, tmuxResizePane, saveTextToFile, listDirectory, scanProjectCommands, scanPathCommands, snoopPathContext, type ProjectCommands } from "../lib/ipc";
+import { copyText, writeInputBatched, resizeTerminal, requestFullRedraw, restartSession, recoverSession, tmuxSendKeys, tmuxResizePane, saveTextToFile, setPrimarySelection, pastePrimary, showTerminalContextMenu, onTerminalContextAction, tmuxDetach, shareBlock, exportOutput, previewExport, setTextSelection, extendTextSelection, clearTextSelection, getSelectionText, type TextSelectionMode, getBlockText, copyBlockAsMarkdown, searchVisible, searchScrollback, setViewportTop, sendMouseEvent, setSessionFocus, setSessionVisible, sendSignal, pasteText, isReadOnlyError, type ContextMenuAction, type ShareService, listDirectory, scanProjectCommands, scanPathCommands, snoopPathContext, type ProjectCommands } from "../lib/ipc";
 import { checkPasteContent } from "../lib/pasteSafety";
+import { createInputBatcher } from "../lib/inputBatcher";
 import { keyEventToBytes } from "../lib/input";
 import { measureFontMetrics, calculateTerminalSize, invalidateFontMetrics, type FontMetrics } from "../lib/font";
 import { collectLinesForRange } from "../lib/terminal-output";
-import { TerminalLine } from "./TerminalLine";
+import { TerminalLine, OPEN_LINK_EVENT } from "./TerminalLine";
+import { openSpanLink, spanLink, type SpanLink } from "../lib/links";
 import { Cursor } from "./Cursor";
 import { IconFolder, IconCopy, IconCommand, IconArrowDown } from "./icons";
 import { matchesKeybinding } from "../lib/keybindings";
@@ -43,6 +46,13 @@ import { WebGLTerminalRenderer, canUseWebGLRenderer } from "../lib/webglRenderer
 import { useTheme, THEME_LIST, THEME_ANSI_PALETTES } from "../stores/theme";
 import { colorToCSS } from "../lib/color";
 
+// Shared by every pane; queues are kept per session.
+const inputBatcher = createInputBatcher(writeInputBatched);
+
+// Minimum gap between "read-only" toasts while typing into a locked pane.
+const READ_ONLY_NOTICE_MS = 3000;
+let lastReadOnlyNotice = 0;
+
 export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabActive?: boolean; onOpenSettings?: () => void; onSplitRight?: () => void; onSplitDown?: () => void }> = (props) => {
   let containerRef!: HTMLDivElement;
   let scrollRef!: HTMLDivElement;
@@ -191,7 +201,15 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
     if (tmuxPaneId != null) {
       return tmuxSendKeys(tmuxPaneId, data);
     }
-    return writeInput(sessionId, data);
+    return inputBatcher.write(sessionId, data).catch((e) => {
+      if (!isReadOnlyError(e)) throw e;
+      // Locked pane: keystrokes are dropped, with an occasional reminder.
+      const now = Date.now();
+      if (now - lastReadOnlyNotice > READ_ONLY_NOTICE_MS) {
+        lastReadOnlyNotice = now;
+        showToast("This pane is read-only", "warning");
+      }
+    });
   };
   const [metrics, setMetrics] = createSignal<FontMetrics | null>(null);
   const [isScrolledUp, setIsScrolledUp] = createSignal(false);
@@ -204,9 +222,16 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
   const [selection, setSelection] = createSignal<SelectionState>(createSelectionState());
 
   const inlineImages = () => props.store.state.inlineImages;
+  // Where each inline image's anchor cell is, in terminal-scroll content
+  // coordinates; images whose line isn't rendered have no entry.
+  const [inlineImagePx, setInlineImagePx] = createSignal<Record<string, { left: number; top: number }>>({});
 
   // Context menu state
-  const [contextMenu, setContextMenu] = createSignal<{ x: number; y: number; selectedText?: string; linkUrl?: string } | null>(null);
+  const [contextMenu, setContextMenu] = createSignal<{ x: number; y: number; selectedText?: string; linkUrl?: string; blockId?: string } | null>(null);
+  // `rain-asset` URL of the block output shown by "Preview Command Output".
+  const [exportPreview, setExportPreview] = createSignal<string | null>(null);
+  // Hit-test result for the open native menu; the backend only echoes link and block.
+  let nativeMenuTarget: { selectedText?: string; linkUrl?: string; blockId?: string } | null = null;
 
   // Visual bell flash state
   const [bellFlash, setBellFlash] = createSignal(false);
@@ -232,10 +257,28 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
       .catch(() => {});
 
     containerRef.focus();
+    let unlistenContextAction: (() => void) | undefined;
+    onTerminalContextAction((payload) => {
+      if (payload.sessionId !== props.store.state.sessionId) return;
+      const target = nativeMenuTarget ?? {};
+      nativeMenuTarget = null;
+      runContextAction(payload.action, {
+        selectedText: target.selectedText,
+        linkUrl: payload.linkUrl ?? undefined,
+        blockId: payload.blockId ?? undefined,
+      });
+    }).then((unlisten) => {
+      unlistenContextAction = unlisten;
+    });
     const onWindowFocus = () => emitFocusEvent(true);
     const onWindowBlur = () => emitFocusEvent(false);
     window.addEventListener("focus", onWindowFocus);
     window.addEventListener("blur", onWindowBlur);
+    const onOpenLink = (e: Event) => {
+      const link = (e as CustomEvent<SpanLink>).detail;
+      openSpanLink(link, props.store.state.cwd).catch(console.error);
+    };
+    containerRef.addEventListener(OPEN_LINK_EVENT, onOpenLink);
 
     // Horizontal padding on terminal-scroll (12px each side)
     const scrollPadding = 24;
@@ -264,7 +307,7 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
       const tmuxPaneId = props.store.state.tmuxPaneId;
       const resizeOp = tmuxPaneId != null
         ? tmuxResizePane(tmuxPaneId, rows, cols)
-        : resizeTerminal(sid, rows, cols);
+        : resizeTerminal(sid, rows, cols, Math.round(charWidth()), Math.round(lineHeight()));
       resizeOp
         .catch(console.error)
         .finally(() => {
@@ -305,7 +348,9 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
 
       const measured = calculateTerminalSize(containerWidth, containerHeightPx, met);
       const rows = props.store.state.altScreen ? Math.max(measured.rows, MIN_ALT_ROWS) : measured.rows;
-      const cols = props.store.state.altScreen ? Math.max(measured.cols, MIN_ALT_COLS) : measured.cols;
+      const forcedCols = config().allowColumnMode ? props.store.state.columnModeCols : null;
+      const cols = forcedCols
+        ?? (props.store.state.altScreen ? Math.max(measured.cols, MIN_ALT_COLS) : measured.cols);
 
       if (rows === props.store.state.rows && cols === props.store.state.cols) {
         lastIssuedRows = rows;
@@ -368,11 +413,28 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
       setTimeout(() => requestAnimationFrame(measure), 100);
     });
 
+    // DECCOLM already resized the grid. Give the PTY the same width, or
+    // with column switching off, resize back to the pane's width.
+    createEffect(on(() => props.store.state.columnModeCols, (columnModeCols) => {
+      const sid = props.store.state.sessionId;
+      if (columnModeCols !== null && config().allowColumnMode && sid) {
+        lastIssuedRows = props.store.state.rows;
+        lastIssuedCols = columnModeCols;
+        dispatchResize(sid, props.store.state.rows, columnModeCols);
+        return;
+      }
+      lastIssuedRows = -1;
+      lastIssuedCols = -1;
+      requestAnimationFrame(measure);
+    }, { defer: true }));
+
     onCleanup(() => {
       observer.disconnect();
       if (resizeTimer !== null) clearTimeout(resizeTimer);
       window.removeEventListener("focus", onWindowFocus);
       window.removeEventListener("blur", onWindowBlur);
+      containerRef.removeEventListener(OPEN_LINK_EVENT, onOpenLink);
+      unlistenContextAction?.();
     });
   });
 
@@ -499,9 +561,7 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
         e.preventDefault();
         const viewport = containerRef?.querySelector(".terminal-content, .alt-screen, .active-viewport, .terminal-history") as HTMLElement;
         if (!viewport) return;
-        const { row, col } = pixelToGrid(e as unknown as MouseEvent, viewport);
-        const button = e.deltaY < 0 ? 64 : 65; // wheel up/down
-        sendInput(sid, encodeMouseEvent(button, col, row, true)).catch(console.error);
+        reportMouse("press", e.deltaY < 0 ? "wheelUp" : "wheelDown", e, viewport);
         return;
       }
 
@@ -537,46 +597,57 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
     return { row: Math.max(0, row), col: Math.max(0, col) };
   }
 
-  // Encode mouse event as escape sequence for PTY
-  function encodeMouseEvent(button: number, col: number, row: number, press: boolean): number[] {
-    // 1-based coordinates for mouse reporting
-    const c = col + 1;
-    const r = row + 1;
-
-    if (props.store.state.sgrMouse) {
-      // SGR mode (1006): \x1b[<button;col;rowM or \x1b[<button;col;rowm
-      const suffix = press ? "M" : "m";
-      const seq = `\x1b[<${button};${c};${r}${suffix}`;
-      return Array.from(new TextEncoder().encode(seq));
-    } else {
-      // Normal mode (1000): \x1b[M + encoded bytes
-      if (press) {
-        const cb = button + 32;
-        const cx = Math.min(c + 32, 255);
-        const cy = Math.min(r + 32, 255);
-        return [0x1b, 0x5b, 0x4d, cb, cx, cy];
-      }
-      // Release in normal mode
-      const cb = 3 + 32; // release
-      const cx = Math.min(c + 32, 255);
-      const cy = Math.min(r + 32, 255);
-      return [0x1b, 0x5b, 0x4d, cb, cx, cy];
+  // Report a mouse event to the program; the backend encodes it for the
+  // mouse modes the program enabled.
+  function reportMouse(action: MouseAction, button: MouseButton, e: MouseEvent, viewport: HTMLElement) {
+    const sid = props.store.state.sessionId;
+    if (!sid) return;
+    const { row, col } = pixelToGrid(e, viewport);
+    const tmuxPaneId = props.store.state.tmuxPaneId;
+    if (tmuxPaneId != null) {
+      // tmux panes have no backend session; tmux itself re-encodes the
+      // legacy report for the pane's program.
+      tmuxSendKeys(tmuxPaneId, encodeTmuxMouse(action, button, col, row)).catch(console.error);
+      return;
     }
+    const rect = viewport.getBoundingClientRect();
+    sendMouseEvent(sid, {
+      action,
+      button,
+      row,
+      col,
+      x: Math.max(0, Math.floor(e.clientX - rect.left)),
+      y: Math.max(0, Math.floor(e.clientY - rect.top)),
+      shift: e.shiftKey,
+      alt: e.altKey,
+      ctrl: e.ctrlKey,
+    }).catch((err) => {
+      if (!isReadOnlyError(err)) console.error(err);
+    });
+  }
+
+  // Normal-mode (1000) report for a tmux pane: \x1b[M + one byte each.
+  function encodeTmuxMouse(action: MouseAction, button: MouseButton, col: number, row: number): number[] {
+    const codes: Record<MouseButton, number> = {
+      left: 0, middle: 1, right: 2, none: 3,
+      wheelUp: 64, wheelDown: 65, wheelLeft: 66, wheelRight: 67,
+    };
+    const code = action === "release" ? 3 : codes[button] + (action === "motion" ? 32 : 0);
+    return [0x1b, 0x5b, 0x4d, code + 32, Math.min(col + 33, 255), Math.min(row + 33, 255)];
   }
 
   // Mouse button mapping
-  function mouseButtonId(e: MouseEvent): number {
+  function mouseButtonId(e: MouseEvent): MouseButton {
     switch (e.button) {
-      case 0: return 0; // left
-      case 1: return 1; // middle
-      case 2: return 2; // right
-      default: return 0;
+      case 1: return "middle";
+      case 2: return "right";
+      default: return "left";
     }
   }
 
   // Track if mouse button is held (for motion tracking)
   let mouseButtonDown = false;
-  let lastMouseButton = 0;
+  let lastMouseButton: MouseButton = "left";
 
   // Mouse event handlers for PTY mouse reporting + text selection
   const handleTermMouseDown = (e: MouseEvent) => {
@@ -585,11 +656,11 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
     const { row, col } = pixelToGrid(e, viewport);
 
     if ((e.metaKey || e.ctrlKey) && activeCanvasRenderer) {
-      const url = activeCanvasRenderer.getUrlAt(row, col);
-      if (url) {
+      const link = activeCanvasRenderer.getLinkAt(row, col);
+      if (link) {
         e.preventDefault();
         e.stopPropagation();
-        window.open(url, "_blank");
+        openSpanLink(link, props.store.state.cwd).catch(console.error);
         return;
       }
     }
@@ -599,10 +670,7 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
       containerRef?.focus();
       mouseButtonDown = true;
       lastMouseButton = mouseButtonId(e);
-      const sid = props.store.state.sessionId;
-      if (sid) {
-        sendInput(sid, encodeMouseEvent(lastMouseButton, col, row, true)).catch(console.error);
-      }
+      reportMouse("press", lastMouseButton, e, viewport);
       return;
     }
 
@@ -613,6 +681,14 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
     }
     e.preventDefault();
     containerRef?.focus();
+
+    // Middle click pastes the PRIMARY selection (X11/Wayland convention).
+    if (e.button === 1) {
+      const sid = props.store.state.sessionId;
+      if (sid) pastePrimary(sid).catch(console.error);
+      return;
+    }
+
     mouseButtonDown = true;
 
     // Double-click: select word
@@ -639,8 +715,8 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
       const canvasEl = containerRef?.querySelector(".canvas-terminal") as HTMLElement;
       if (canvasEl) {
         const { row, col } = pixelToGrid(e, canvasEl);
-        const url = activeCanvasRenderer.getUrlAt(row, col);
-        canvasEl.style.cursor = url ? "pointer" : "";
+        const link = activeCanvasRenderer.getLinkAt(row, col);
+        canvasEl.style.cursor = link ? "pointer" : "";
       }
     } else {
       const canvasEl = containerRef?.querySelector(".canvas-terminal") as HTMLElement;
@@ -654,12 +730,7 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
 
     // Mouse motion tracking (1002 drag, 1003 all-motion)
     if ((props.store.state.mouseMotion || props.store.state.mouseAllMotion) && props.store.state.mouseTracking && !e.shiftKey) {
-      const sid = props.store.state.sessionId;
-      if (sid) {
-        const baseButton = mouseButtonDown ? lastMouseButton : 3;
-        const button = baseButton + 32; // motion flag
-        sendInput(sid, encodeMouseEvent(button, col, row, true)).catch(console.error);
-      }
+      reportMouse("motion", mouseButtonDown ? lastMouseButton : "none", e, viewport);
       return;
     }
 
@@ -676,15 +747,11 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
   const handleTermMouseUp = (e: MouseEvent) => {
     const viewport = containerRef?.querySelector(".terminal-content, .alt-screen, .active-viewport, .terminal-history") as HTMLElement;
     if (!viewport) return;
-    const { row, col } = pixelToGrid(e, viewport);
 
     // Mouse tracking: send release
     if (props.store.state.mouseTracking && !e.shiftKey) {
       mouseButtonDown = false;
-      const sid = props.store.state.sessionId;
-      if (sid) {
-        sendInput(sid, encodeMouseEvent(lastMouseButton, col, row, false)).catch(console.error);
-      }
+      reportMouse("release", lastMouseButton, e, viewport);
       return;
     }
 
@@ -698,9 +765,19 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
         range: hasSelection ? sel.range : null,
         selecting: false,
       });
+      if (hasSelection) mirrorSelection(sel.range, "char");
     }
+    syncPrimarySelection();
   };
 
+  function syncPrimarySelection() {
+    if (!config().copyToPrimarySelection) return;
+    const sel = selection();
+    if (!sel.active || !sel.range) return;
+    const text = extractSelectedText(getAllDisplayLines(), sel.range);
+    if (text) setPrimarySelection(text).catch(console.error);
+  }
+
   function selectWord(row: number, col: number) {
     // Get the line text and find word boundaries
     const lines = getAllDisplayLines();
@@ -720,6 +797,7 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
       range: { start: { row, col: start }, end: { row, col: end } },
       selecting: false,
     });
+    mirrorSelection({ start: { row, col }, end: { row, col } }, "word");
   }
 
   function selectLine(row: number) {
@@ -732,10 +810,39 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
       range: { start: { row, col: 0 }, end: { row, col: Math.max(0, text.length - 1) } },
       selecting: false,
     });
+    mirrorSelection({ start: { row, col: 0 }, end: { row, col: 0 } }, "line");
   }
 
   function clearSelection() {
     setSelection(createSelectionState());
+    const sid = props.store.state.sessionId;
+    if (sid && props.store.state.selectionMirrored) {
+      props.store.setState("selectionMirrored", false);
+      clearTextSelection(sid).catch(console.error);
+    }
+  }
+
+  /** Mirror a selection in the backend, which copies it from the cells and
+   *  widens words across wrapped rows. Columns here are text offsets, so
+   *  lines with wide characters stay frontend-only. */
+  function mirrorSelection(range: SelectionRange, mode: TextSelectionMode) {
+    const sid = props.store.state.sessionId;
+    props.store.setState("selectionMirrored", false);
+    if (!sid || props.store.state.tmuxPaneId != null) return;
+    const { start, end } = normalizeRange(range);
+    const lines = getAllDisplayLines();
+    const lineAt = (row: number) => {
+      const line = lines.find((l) => l.index === row);
+      const narrow = line?.spans.every((span) => span.cols === span.text.length);
+      return narrow ? line?.line_id : undefined;
+    };
+    const startId = lineAt(start.row);
+    const endId = lineAt(end.row);
+    if (startId == null || endId == null) return;
+    setTextSelection(sid, mode, { lineId: startId, col: start.col })
+      .then((started) => started && extendTextSelection(sid, { lineId: endId, col: end.col }))
+      .then((range) => props.store.setState("selectionMirrored", !!range))
+      .catch(console.error);
   }
 
   // Get all lines currently displayed (for selection text extraction)
@@ -755,17 +862,36 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
     return all;
   }
 
-  // Focus event reporting for DECSET 1004.
+  // Focus event reporting for DECSET 1004. The backend writes the reports,
+  // combining this with native window focus; tmux panes have no backend
+  // session and report here.
   const emitFocusEvent = (focused: boolean) => {
-    if (!props.active || !props.store.state.focusEvents) return;
     const sid = props.store.state.sessionId;
     if (!sid) return;
+    if (props.store.state.tmuxPaneId == null) {
+      setSessionFocus(sid, focused && props.active).catch(console.error);
+      return;
+    }
+    if (!props.active || !props.store.state.focusEvents) return;
     sendInput(sid, focused ? [0x1b, 0x5b, 0x49] : [0x1b, 0x5b, 0x4f]).catch(console.error);
   };
 
   const handleFocus = () => emitFocusEvent(true);
   const handleBlur = () => emitFocusEvent(false);
 
+  // Sessions in background tabs or hidden windows render at a few frames
+  // per second; tmux panes have no backend session to throttle.
+  const [documentVisible, setDocumentVisible] = createSignal(document.visibilityState === "visible");
+  const handleVisibilityChange = () => setDocumentVisible(document.visibilityState === "visible");
+  document.addEventListener("visibilitychange", handleVisibilityChange);
+  onCleanup(() => document.removeEventListener("visibilitychange", handleVisibilityChange));
+  createEffect(() => {
+    const sid = props.store.state.sessionId;
+    if (!sid || props.store.state.tmuxPaneId != null) return;
+    const visible = (props.isTabActive ?? true) && documentVisible();
+    setSessionVisible(sid, visible).catch(console.error);
+  });
+
   // Search functions
   function openSearch() {
     props.store.setState(produce((s) => {
@@ -774,6 +900,8 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
   }
 
   function closeSearch() {
+    const sid = props.store.state.sessionId;
+    if (sid) searchVisible(sid, "").catch(console.error);
     props.store.setState(produce((s) => {
       s.searchOpen = false;
       s.searchQuery = "";
@@ -782,6 +910,196 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
     }));
   }
 
+  // Paste into the PTY. The backend sanitizes, brackets and chunks it; tmux
+  // panes have no backend session and get the raw bracketed text.
+  function sendPaste(sid: string, text: string) {
+    if (props.store.state.tmuxPaneId != null) {
+      const wrapped = props.store.state.bracketedPaste ? "\x1b[200~" + text + "\x1b[201~" : text;
+      sendInput(sid, Array.from(new TextEncoder().encode(wrapped))).catch(console.error);
+      return;
+    }
+    pasteText(sid, text).catch((e) => {
+      if (!isReadOnlyError(e)) console.error(e);
+      else showToast("This pane is read-only", "warning");
+    });
+  }
+
+  async function pasteFromClipboard() {
+    try {
+      const text = await navigator.clipboard.readText();
+      if (!text) return;
+      if (props.store.state.altScreen || props.store.state.activeBlock) {
+        const sid = props.store.state.sessionId;
+        if (sid) sendPaste(sid, text);
+      } else {
+        inputBuffer.insert(text);
+      }
+    } catch (e) { console.error(e); }
+  }
+
+  function blockOutputText(blockId: string): string | null {
+    const snap = props.store.state.snapshots.find((s) => s.id === blockId);
+    if (!snap) return null;
+    return snap.lines.map((line) => line.spans.map((s) => s.text).join("").trimEnd()).join("\n");
+  }
+
+  // Normalized by the backend from cell data while the block is resident,
+  // falling back to the rendered snapshot.
+  async function copyBlockOutput(snapshotId: string) {
+    const sid = props.store.state.sessionId;
+    const blockId = props.store.state.snapshots.find((s) => s.id === snapshotId)?.blockId;
+    const { copyTrimTrailingWhitespace, copyBlockWithCommand, copyAsFencedCode } = config();
+    let text: string | null = null;
+    if (sid && blockId) {
+      try {
+        text = await getBlockText(sid, blockId, copyBlockWithCommand || copyAsFencedCode, {
+          trimTrailingWhitespace: copyTrimTrailingWhitespace,
+          stripPrompts: true,
+          fenced: copyAsFencedCode,
+        });
+      } catch {
+        text = null;
+      }
+    }
+    text ??= blockOutputText(snapshotId);
+    if (text) copyText(text).catch(console.error);
+  }
+
+  function copySnapshotMarkdown(snapshotId: string) {
+    const sid = props.store.state.sessionId;
+    const blockId = props.store.state.snapshots.find((s) => s.id === snapshotId)?.blockId;
+    if (!sid || !blockId) {
+      showToast("This block can no longer be copied", "error");
+      return;
+    }
+    copyBlockAsMarkdown(sid, blockId, config().markdownCopyMaxLines)
+      .then((text) => copyText(text))
+      .then(() => showToast("Copied as Markdown", "success"))
+      .catch((e) => showToast(String(e), "error"));
+  }
+
+  function shareSnapshot(snapshotId: string) {
+    const blockId = props.store.state.snapshots.find((s) => s.id === snapshotId)?.blockId;
+    if (!blockId) {
+      showToast("This block can no longer be shared", "error");
+      return;
+    }
+    const { shareService, shareEndpoint, shareFormat } = config();
+    let service: ShareService;
+    if (shareService === "paste") {
+      if (!shareEndpoint) {
+        showToast("Set a paste endpoint in settings first", "error");
+        return;
+      }
+      service = { type: "paste", endpoint: shareEndpoint };
+    } else {
+      service = { type: "gist" };
+    }
+    shareBlock(props.store.state.sessionId, blockId, service, shareFormat)
+      .then((url) => {
+        copyText(url).catch(console.error);
+        showToast(`Shared, link copied: ${url}`, "success");
+      })
+      .catch((e) => showToast(String(e), "error"));
+  }
+
+  function saveSnapshot(snapshotId: string) {
+    const sid = props.store.state.sessionId;
+    const blockId = props.store.state.snapshots.find((s) => s.id === snapshotId)?.blockId;
+    if (!sid || !blockId) {
+      showToast("This block can no longer be saved", "error");
+      return;
+    }
+    exportOutput(sid, { type: "block", blockId }, config().shareFormat)
+      .then((saved) => {
+        if (saved) showToast("Command output saved", "success");
+      })
+      .catch((e) => showToast(String(e), "error"));
+  }
+
+  function previewSnapshot(snapshotId: string) {
+    const sid = props.store.state.sessionId;
+    const blockId = props.store.state.snapshots.find((s) => s.id === snapshotId)?.blockId;
+    if (!sid || !blockId) {
+      showToast("This block can no longer be previewed", "error");
+      return;
+    }
+    previewExport(sid, { type: "block", blockId })
+      .then(setExportPreview)
+      .catch((e) => showToast(String(e), "error"));
+  }
+
+  /** Shared by the in-page menu and the native menu's `terminal-context-action` event. */
+  function runContextAction(
+    action: ContextMenuAction,
+    target: { selectedText?: string; linkUrl?: string; blockId?: string },
+  ) {
+    setContextMenu(null);
+    switch (action) {
+      case "copy":
+        if (target.selectedText) copyText(target.selectedText).catch(console.error);
+        break;
+      case "paste":
+        void pasteFromClipboard();
+        break;
+      case "select-all": {
+        const lines = getAllDisplayLines();
+        if (lines.length > 0) {
+          const lastLine = lines[lines.length - 1];
+          const lastText = lastLine.spans.map(s => s.text).join("");
+          setSelection({
+            active: true,
+            range: { start: { row: 0, col: 0 }, end: { row: lastLine.index, col: Math.max(0, lastText.length - 1) } },
+            selecting: false,
+          });
+        }
+        break;
+      }
+      case "search-selection":
+        if (target.selectedText) {
+          openSearch();
+          updateSearchQuery(target.selectedText);
+        }
+        break;
+      case "open-link":
+        if (target.linkUrl) window.open(target.linkUrl, "_blank");
+        break;
+      case "copy-link":
+        if (target.linkUrl) copyText(target.linkUrl).catch(console.error);
+        break;
+      case "copy-block-output":
+        if (target.blockId) void copyBlockOutput(target.blockId);
+        break;
+      case "copy-block-markdown":
+        if (target.blockId) copySnapshotMarkdown(target.blockId);
+        break;
+      case "share-block":
+        if (target.blockId) shareSnapshot(target.blockId);
+        break;
+      case "save-block":
+        if (target.blockId) saveSnapshot(target.blockId);
+        break;
+      case "preview-block":
+        if (target.blockId) previewSnapshot(target.blockId);
+        break;
+      case "split-right":
+        props.onSplitRight?.();
+        break;
+      case "split-down":
+        props.onSplitDown?.();
+        break;
+      case "tmux-detach":
+        tmuxDetach().catch(console.error);
+        break;
+      case "clear":
+        props.store.clearHistory();
+        break;
+      case "export":
+        exportScrollback();
+        break;
+    }
+  }
+
   function exportScrollback() {
     const lines: string[] = [];
     // Collect all snapshot output
@@ -804,6 +1122,57 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
   }
 
   function updateSearchQuery(query: string) {
+    // Full-screen apps redraw in place, so the backend scans the screen and
+    // refreshes the matches with every frame.
+    const sid = props.store.state.sessionId;
+    if (props.store.state.altScreen && sid && !searchUseRegex()) {
+      props.store.setState(produce((s) => {
+        s.searchQuery = query;
+      }));
+      searchVisible(sid, query)
+        .then((rects) => {
+          props.store.setState(produce((s) => {
+            if (s.searchQuery !== query) return;
+            s.searchMatches = visibleSearchMatches(rects);
+            s.searchCurrentIndex = s.searchMatches.length > 0 ? 0 : -1;
+          }));
+        })
+        .catch(console.error);
+      return;
+    }
+
+    // The backend searches its whole scrollback, so the results don't
+    // depend on how much history this view has loaded.
+    if (sid && query && !props.store.state.altScreen) {
+      props.store.setState(produce((s) => {
+        s.searchQuery = query;
+      }));
+      searchScrollback(sid, query, { regex: searchUseRegex() })
+        .then((found) => {
+          setSearchRegexError(false);
+          props.store.setState(produce((s) => {
+            if (s.searchQuery !== query) return;
+            s.searchMatches = found.map((m) => ({
+              globalRow: m.global_row,
+              lineId: m.line_id,
+              startCol: m.col,
+              endCol: m.col + m.cols - 1,
+            }));
+            s.searchCurrentIndex = s.searchMatches.length > 0 ? 0 : -1;
+          }));
+        })
+        .catch((e) => {
+          if (String(e).startsWith("Invalid search pattern")) setSearchRegexError(true);
+          else console.error(e);
+          props.store.setState(produce((s) => {
+            if (s.searchQuery !== query) return;
+            s.searchMatches = [];
+            s.searchCurrentIndex = -1;
+          }));
+        });
+      return;
+    }
+
     props.store.setState(produce((s) => {
       s.searchQuery = query;
       if (!query) {
@@ -836,6 +1205,7 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
             if (m[0].length === 0) { re.lastIndex++; continue; }
             matches.push({
               globalRow: line.index,
+              lineId: line.line_id,
               startCol: m.index,
               endCol: m.index + m[0].length - 1,
             });
@@ -849,6 +1219,7 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
           while ((idx = text.indexOf(lowerQuery, idx)) !== -1) {
             matches.push({
               globalRow: line.index,
+              lineId: line.line_id,
               startCol: idx,
               endCol: idx + query.length - 1,
             });
@@ -944,6 +1315,17 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
     },
   ));
 
+  createEffect(on(
+    () => props.store.state.capabilityNotice,
+    (visible) => {
+      if (!visible) return;
+      const timerId = setTimeout(() => {
+        props.store.setState("capabilityNotice", false);
+      }, 2800);
+      onCleanup(() => clearTimeout(timerId));
+    },
+  ));
+
   // PTY input lines (used for alt screen fallback and active output)
   const inputLines = createMemo(() => {
     const cursorRow = props.store.state.cursor.row;
@@ -1063,6 +1445,11 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
     };
   });
 
+  function stopActiveCommand() {
+    const sessionId = props.store.state.sessionId;
+    if (sessionId) sendSignal(sessionId, "interrupt").catch(console.error);
+  }
+
   // For active commands that remain on the primary screen (no alt-screen),
   // render the terminal grid starting from the command's outputStart row.
   // Rows before outputStart contain echoed command text already shown by
@@ -1222,20 +1609,30 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
     }
 
     const palette = THEME_ANSI_PALETTES[theme()] ?? THEME_ANSI_PALETTES["dark"];
+    const dynamicFg = props.store.state.dynamicColors.foreground;
+    const defaultFg = (dynamicFg && colorToCSS(dynamicFg, palette)) ?? "#e0e0e0";
     for (const line of lines) {
       activeCanvasRenderer.updateLine(
         line.index,
         line.spans.map((s) => ({
           text: s.text,
-          fg: colorToCSS(s.fg, palette) ?? "#e0e0e0",
+          cols: s.cols,
+          fg: colorToCSS(s.fg, palette) ?? defaultFg,
           bg: colorToCSS(s.bg, palette) ?? "transparent",
           bold: s.bold,
           italic: s.italic,
           underline: s.underline,
+          underlineStyle: s.underline_style,
+          underlineColor: s.underline_color ? colorToCSS(s.underline_color, palette) ?? undefined : undefined,
           strikethrough: s.strikethrough,
           dim: s.dim,
+          link: spanLink(s),
         })),
       );
+      // The WebGL renderer draws every row at single size.
+      if (activeCanvasRenderer instanceof CanvasTerminalRenderer) {
+        activeCanvasRenderer.setLineSize(line.index, line.line_size);
+      }
     }
 
     const sel = selection();
@@ -1339,6 +1736,30 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
     };
   });
 
+  // Stable ID of the output line at the top of the viewport; the backend
+  // maps it to a block for the sticky header.
+  const viewportTopLineId = createMemo(() => {
+    const heights = snapshotHeights();
+    const snapshots = props.store.state.snapshots;
+    const headerPx = isTraditional() ? 0 : 44;
+    let offset = scrollTop();
+    for (let i = 0; i < heights.length; i++) {
+      if (offset < heights[i]) {
+        const lines = snapshots[i].lines;
+        const row = Math.floor(Math.max(0, offset - headerPx) / lineHeight());
+        return lines[Math.min(row, lines.length - 1)]?.line_id ?? null;
+      }
+      offset -= heights[i];
+    }
+    return null;
+  });
+
+  createEffect(on(viewportTopLineId, (lineId) => {
+    const sid = props.store.state.sessionId;
+    if (!sid || lineId == null) return;
+    setViewportTop(sid, lineId).catch((e) => console.warn("[Rain] Failed to report viewport top:", e));
+  }));
+
   const visibleSnapshots = createMemo(() => {
     const { start, end } = visibleSnapshotRange();
     return props.store.state.snapshots.slice(start, end);
@@ -1371,7 +1792,13 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
     const sid = props.store.state.sessionId;
     if (!sid) return;
     const cfg = config();
-    const bytes = keyEventToBytes(e, cfg.optionAsMeta, props.store.state.cursorKeysApplication);
+    const bytes = keyEventToBytes(
+      e,
+      cfg.optionAsMeta,
+      props.store.state.cursorKeysApplication,
+      props.store.state.keyboardFlags,
+      props.store.state.modifyOtherKeys,
+    );
     if (bytes.length > 0) {
       sendInput(sid, Array.from(bytes)).catch(console.error);
     }
@@ -1397,6 +1824,33 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
   }
 
   // Key handler - split between local buffer (normal) and raw PTY (alt screen)
+  const restartEndedSession = async () => {
+    const sessionId = props.store.state.sessionId;
+    if (!sessionId || props.store.state.connected) return;
+    try {
+      await restartSession(sessionId);
+      props.store.setState("connected", true);
+    } catch (e) {
+      showToast(`Failed to restart shell: ${e}`, "error");
+    }
+  };
+
+  // The backend starts a blank terminal state whose frame and resize
+  // counters begin again at zero, so the store is reset before its first
+  // frame can arrive.
+  const recoverStalledSession = async () => {
+    const sessionId = props.store.state.sessionId;
+    if (!sessionId || !props.store.state.stalled) return;
+    props.store.clearHistory();
+    props.store.setState({ lastFrameSeq: 0, currentResizeEpoch: 0, altScreen: false, altScreenLines: [] });
+    try {
+      await recoverSession(sessionId);
+      props.store.setState("stalled", null);
+    } catch (e) {
+      showToast(`Failed to recover session: ${e}`, "error");
+    }
+  };
+
   const handleKeyDown = (e: KeyboardEvent) => {
     const key = e.key.length === 1 ? e.key.toLowerCase() : e.key;
 
@@ -1432,6 +1886,15 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
       return;
     }
 
+    // The shell exited: Enter starts it again, other keys go nowhere.
+    if (props.store.state.sessionId && !props.store.state.connected) {
+      if (key === "Enter" && !e.metaKey && !e.ctrlKey && !e.altKey) {
+        e.preventDefault();
+        void restartEndedSession();
+      }
+      return;
+    }
+
     // Cmd+F: toggle search
     if (matchesKeybinding(e, "search")) {
       e.preventDefault();
@@ -1479,9 +1942,19 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
         e.preventDefault();
         const lines = getAllDisplayLines();
         const text = extractSelectedText(lines, sel.range);
-        if (text) {
-          navigator.clipboard.writeText(text).catch(console.error);
+        const copy = (copied: string | null) => {
+          if (copied) copyText(copied).catch(console.error);
+        };
+        const sid = props.store.state.sessionId;
+        if (sid && props.store.state.selectionMirrored) {
+          setSelection(createSelectionState());
+          getSelectionText(sid)
+            .then((copied) => copy(copied ?? text))
+            .catch(() => copy(text))
+            .finally(clearSelection);
+          return;
         }
+        copy(text);
         clearSelection();
         return;
       }
@@ -1537,7 +2010,7 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
       e.preventDefault();
       const text = inputBuffer.getSelectedText();
       if (text) {
-        navigator.clipboard.writeText(text).catch(console.error);
+        copyText(text).catch(console.error);
       }
       return;
     }
@@ -1553,7 +2026,7 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
       e.preventDefault();
       const text = inputBuffer.getSelectedText();
       if (text) {
-        navigator.clipboard.writeText(text).catch(console.error);
+        copyText(text).catch(console.error);
         inputBuffer.deleteSelection();
       }
       return;
@@ -1721,20 +2194,7 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
       // Alt screen, running command, or no shell integration: send paste to PTY
       const sid = props.store.state.sessionId;
       if (!sid) return;
-      const encoder = new TextEncoder();
-      const textBytes = encoder.encode(text);
-      if (props.store.state.bracketedPaste) {
-        // Wrap in bracketed paste delimiters when mode 2004 is active
-        const pasteStart = new Uint8Array([0x1b, 0x5b, 0x32, 0x30, 0x30, 0x7e]);
-        const pasteEnd = new Uint8Array([0x1b, 0x5b, 0x32, 0x30, 0x31, 0x7e]);
-        const data = new Uint8Array(pasteStart.length + textBytes.length + pasteEnd.length);
-        data.set(pasteStart, 0);
-        data.set(textBytes, pasteStart.length);
-        data.set(pasteEnd, pasteStart.length + textBytes.length);
-        sendInput(sid, Array.from(data)).catch(console.error);
-      } else {
-        sendInput(sid, Array.from(textBytes)).catch(console.error);
-      }
+      sendPaste(sid, text);
     } else {
       // Normal mode: insert into local buffer
       if (!localInputReady()) return;
@@ -1871,6 +2331,50 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
     void split;
   });
 
+  // Inline images sit over the cells they were drawn at: measure the line
+  // each one is anchored to after every render that can move it.
+  createEffect(() => {
+    const images = inlineImages();
+    const _visible = props.store.state.visibleLinesByGlobal;
+    const _base = props.store.state.visibleBaseGlobal;
+    const _scrollback = props.store.state.scrollbackLines.length;
+    const _snapshots = props.store.state.snapshots.length;
+    const _active = props.store.state.activeBlock;
+    const cellWidth = charWidth() + config().letterSpacing;
+    const _cols = termCols();
+    const _lineHeight = lineHeight();
+    if (images.length === 0) {
+      setInlineImagePx({});
+      return;
+    }
+
+    requestAnimationFrame(() => {
+      const scroll = scrollRef;
+      if (!scroll) return;
+      const scrollRect = scroll.getBoundingClientRect();
+      const positions: Record<string, { left: number; top: number }> = {};
+      for (const img of images) {
+        // The live viewport is rendered after any snapshot of the same line.
+        const lines = scroll.querySelectorAll<HTMLElement>(`.term-line[data-line-id="${img.lineId}"]`);
+        const lineEl = lines[lines.length - 1];
+        if (!lineEl) continue;
+        const rect = lineEl.getBoundingClientRect();
+        positions[img.id] = {
+          left: rect.left - scrollRect.left + scroll.scrollLeft + img.col * cellWidth,
+          top: rect.top - scrollRect.top + scroll.scrollTop,
+        };
+      }
+      setInlineImagePx(positions);
+    });
+    void _visible;
+    void _base;
+    void _scrollback;
+    void _snapshots;
+    void _active;
+    void _cols;
+    void _lineHeight;
+  });
+
   const SUGGESTION_DEBOUNCE_MS = 230;
   // Debounce suggestion computation: wait briefly after typing before showing ghost text
   let suggestionTimer: ReturnType<typeof setTimeout> | null = null;
@@ -1950,11 +2454,31 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
     return rects;
   });
 
+  // OSC 10/11/12 colors override the theme for this session only.
+  const dynamicColorStyle = createMemo(() => {
+    const { foreground, background, cursor } = props.store.state.dynamicColors;
+    const style: Record<string, string> = {};
+    const fg = foreground && colorToCSS(foreground, []);
+    if (fg) {
+      style["--fg"] = fg;
+      style.color = fg;
+    }
+    const bg = background && colorToCSS(background, []);
+    if (bg) {
+      style["--bg"] = bg;
+      style["background-color"] = bg;
+    }
+    const cursorColor = cursor && colorToCSS(cursor, []);
+    if (cursorColor) style["--cursor-color"] = cursorColor;
+    return style;
+  });
+
   return (
     <div
       ref={containerRef}
       class="terminal-container"
-      classList={{ "terminal-hidden": !props.active, "terminal-traditional": isTraditional(), "terminal-bell-flash": bellFlash() }}
+      classList={{ "terminal-hidden": !props.active, "terminal-traditional": isTraditional(), "terminal-bell-flash": bellFlash(), "terminal-reverse-video": props.store.state.reverseVideo }}
+      style={dynamicColorStyle()}
       role="application"
       aria-label="Terminal"
       aria-roledescription="terminal emulator"
@@ -1983,9 +2507,24 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
         let linkUrl: string | undefined;
         const target = e.target as HTMLElement;
         if (target.classList.contains("term-url")) {
-          linkUrl = target.dataset.url || target.textContent || undefined;
+          linkUrl = target.dataset.url || undefined;
+        }
+        const blockId = target.closest<HTMLElement>("[data-block-id]")?.dataset.blockId;
+        const sid = props.store.state.sessionId;
+        if (config().nativeContextMenu && sid) {
+          nativeMenuTarget = { selectedText, linkUrl, blockId };
+          showTerminalContextMenu(sid, e.clientX, e.clientY, {
+            hasSelection: !!selectedText,
+            linkUrl,
+            blockId,
+          }).catch((err) => {
+            console.warn("[Rain] Native context menu unavailable:", err);
+            nativeMenuTarget = null;
+            setContextMenu({ x: e.clientX, y: e.clientY, selectedText, linkUrl, blockId });
+          });
+          return;
         }
-        setContextMenu({ x: e.clientX, y: e.clientY, selectedText, linkUrl });
+        setContextMenu({ x: e.clientX, y: e.clientY, selectedText, linkUrl, blockId });
       }}
       style={{
         "font-family": fontFamily(),
@@ -2024,13 +2563,34 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
       <Show when={props.store.state.sessionId && !props.store.state.connected}>
         <div class="terminal-disconnected-banner">
           Session ended
+          <button class="terminal-restart-button" onClick={() => void restartEndedSession()}>
+            Restart
+          </button>
+          <span class="terminal-restart-hint">or press Enter</span>
         </div>
       </Show>
+      <Show when={props.store.state.connected && props.store.state.stalled}>
+        {(health) => (
+          <div class="terminal-disconnected-banner">
+            {health() === "crashed" ? "Output stopped: the terminal parser crashed" : "Output stalled"}
+            <button class="terminal-restart-button" onClick={() => void recoverStalledSession()}>
+              Recover
+            </button>
+          </div>
+        )}
+      </Show>
       <Show when={props.store.state.tmuxCompatibilityNotice}>
         <div class="terminal-compat-banner">
           tmux running in compatibility mode (use Rain's native integration with Cmd+Shift+T)
         </div>
       </Show>
+      <Show when={props.store.state.capabilityNotice && props.store.state.capabilities}>
+        {(capabilities) => (
+          <div class="terminal-compat-banner">
+            TERM={capabilities().term}: no {capabilities().degraded.join(", ")} in this command
+          </div>
+        )}
+      </Show>
       {/* Fullscreen TUI: absolute overlay when setting is enabled.
            Always uses DOM rendering — canvas would be opaque and break
            Rain's transparency/glass aesthetic. */}
@@ -2103,6 +2663,7 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
                       charWidth={charWidth()}
                       letterSpacing={config().letterSpacing}
                       promptStyle={config().promptStyle}
+                      sessionId={props.store.state.sessionId}
                     />
                   )}
                 </For>
@@ -2176,6 +2737,7 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
                     charWidth={charWidth()}
                     letterSpacing={config().letterSpacing}
                     promptStyle={config().promptStyle}
+                    onStop={snap().command ? stopActiveCommand : undefined}
                   />
                 )}
               </Show>
@@ -2341,33 +2903,54 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
               />
             </Show>
           </Show>
+          {/* Inline images from image protocols, over the cells they cover */}
+          <Show when={inlineImages().length > 0}>
+            <div class="inline-images-overlay">
+              <For each={inlineImages()}>
+                {(img) => {
+                  const cellWidth = () => charWidth() + config().letterSpacing;
+                  const boxWidth = () =>
+                    img.columns ? img.columns * cellWidth() : img.width > 0 ? img.width : undefined;
+                  const boxHeight = () =>
+                    img.rows ? img.rows * lineHeight() : img.height > 0 ? img.height : undefined;
+                  return (
+                    <Show when={inlineImagePx()[img.id]}>
+                      {(pos) => (
+                        <div
+                          class="inline-image"
+                          style={{
+                            left: `${pos().left}px`,
+                            top: `${pos().top}px`,
+                            width: boxWidth() !== undefined ? `${boxWidth()}px` : undefined,
+                            height: boxHeight() !== undefined ? `${boxHeight()}px` : undefined,
+                          }}
+                        >
+                          <img
+                            src={img.dataUri}
+                            alt="Terminal inline image"
+                            style={{ "object-fit": img.stretch ? "fill" : "contain" }}
+                            loading="lazy"
+                          />
+                        </div>
+                      )}
+                    </Show>
+                  );
+                }}
+              </For>
+            </div>
+          </Show>
         </div>
 
-        {/* Inline images from image protocols */}
-        <Show when={inlineImages().length > 0}>
-          <div class="inline-images-overlay" style={{ position: "relative" }}>
-            <For each={inlineImages()}>
-              {(img) => (
-                <div
-                  class="inline-image"
-                  style={{
-                    "max-width": img.width > 0 ? `${img.width}px` : "100%",
-                  }}
-                >
-                  <img
-                    src={img.dataUri}
-                    alt="Terminal inline image"
-                    style={{
-                      "max-width": "100%",
-                      height: "auto",
-                      "border-radius": "4px",
-                    }}
-                    loading="lazy"
-                  />
-                </div>
-              )}
-            </For>
-          </div>
+        {/* Sticky header: the command whose output is scrolled into */}
+        <Show when={isScrolledUp() && !isTraditional() && !props.store.state.activeBlock && props.store.state.viewportBlock?.command}>
+          {(command) => (
+            <div class="sticky-block-header" title={command()}>
+              <Show when={props.store.state.viewportBlock?.cwd}>
+                {(cwd) => <span class="sticky-block-cwd">{formatCwdSimplified(cwd())}</span>}
+              </Show>
+              <span class="sticky-block-command">{command()}</span>
+            </div>
+          )}
         </Show>
 
         {/* Scroll-to-bottom FAB */}
@@ -2454,79 +3037,27 @@ export const Terminal: Component<{ store: TerminalStore; active: boolean; isTabA
             hasSelection={!!pos().selectedText}
             selectedText={pos().selectedText}
             linkUrl={pos().linkUrl}
-            onCopy={() => {
-              const text = pos().selectedText;
-              if (text) navigator.clipboard.writeText(text).catch(console.error);
-              setContextMenu(null);
-            }}
-            onPaste={async () => {
-              try {
-                const text = await navigator.clipboard.readText();
-                if (text) {
-                  if (props.store.state.altScreen || props.store.state.activeBlock) {
-                    const sid = props.store.state.sessionId;
-                    if (sid) {
-                      const encoder = new TextEncoder();
-                      if (props.store.state.bracketedPaste) {
-                        const wrapped = "\x1b[200~" + text + "\x1b[201~";
-                        sendInput(sid, Array.from(encoder.encode(wrapped))).catch(console.error);
-                      } else {
-                        sendInput(sid, Array.from(encoder.encode(text))).catch(console.error);
-                      }
-                    }
-                  } else {
-                    inputBuffer.insert(text);
-                  }
-                }
-              } catch (e) { console.error(e); }
-              setContextMenu(null);
-            }}
-            onClear={() => {
-              props.store.clearHistory();
-              setContextMenu(null);
-            }}
-            onSelectAll={() => {
-              const lines = getAllDisplayLines();
-              if (lines.length > 0) {
-                const lastLine = lines[lines.length - 1];
-                const lastText = lastLine.spans.map(s => s.text).join("");
-                setSelection({
-                  active: true,
-                  range: { start: { row: 0, col: 0 }, end: { row: lastLine.index, col: Math.max(0, lastText.length - 1) } },
-                  selecting: false,
-                });
-              }
-              setContextMenu(null);
-            }}
-            onSearchSelection={() => {
-              const text = pos().selectedText;
-              if (text) {
-                openSearch();
-                updateSearchQuery(text);
-              }
-              setContextMenu(null);
-            }}
-            onOpenLink={() => {
-              const url = pos().linkUrl;
-              if (url) window.open(url, "_blank");
-              setContextMenu(null);
-            }}
-            onSplitRight={() => {
-              props.onSplitRight?.();
-              setContextMenu(null);
-            }}
-            onSplitDown={() => {
-              props.onSplitDown?.();
-              setContextMenu(null);
-            }}
-            onExport={() => {
-              exportScrollback();
-              setContextMenu(null);
-            }}
+            onCopy={() => runContextAction("copy", pos())}
+            onPaste={() => runContextAction("paste", pos())}
+            onClear={() => runContextAction("clear", pos())}
+            onSelectAll={() => runContextAction("select-all", pos())}
+            onSearchSelection={() => runContextAction("search-selection", pos())}
+            onOpenLink={() => runContextAction("open-link", pos())}
+            onSplitRight={() => runContextAction("split-right", pos())}
+            onSplitDown={() => runContextAction("split-down", pos())}
+            onExport={() => runContextAction("export", pos())}
             onClose={() => setContextMenu(null)}
           />
         )}
       </Show>
+
+      <Show when={exportPreview()}>
+        {(url) => (
+          <div class="export-preview-overlay" onClick={() => setExportPreview(null)}>
+            <iframe class="export-preview-frame" src={url()} sandbox="" title="Command output preview" />
+          </div>
+        )}
+      </Show>
     </div>
   );
 };
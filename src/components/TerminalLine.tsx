@@ -4,9 +4,11 @@ import type { SelectionRange } from "../lib/selection";
 import { normalizeRange, isCellSelected } from "../lib/selection";
 import { useTheme, THEME_ANSI_PALETTES } from "../stores/theme";
 import { colorToCSS } from "../lib/color";
+import { spanColumns } from "../lib/canvasRenderer";
+import { pathLinkLabel, spanLink, type SpanLink } from "../lib/links";
 
-// URL detection regex
-const URL_REGEX = /https?:\/\/[^\s<>"{}|\\^`\[\]]+/g;
+/** Bubbles from a Cmd+clicked link; the terminal opens it against its cwd. */
+export const OPEN_LINK_EVENT = "rain-open-link";
 
 export interface TerminalLineProps {
   line: RenderedLine;
@@ -23,32 +25,34 @@ export const TerminalLine: Component<TerminalLineProps> = (props) => {
   const cellW = () => props.charWidth + props.letterSpacing;
 
   return (
-    <div class="term-line" data-row={props.line.index}>
-      <For each={props.line.spans}>
-        {(span, spanIdx) => {
-          // Compute the column offset for this span
-          const colOffset = () => {
-            let offset = 0;
-            for (let i = 0; i < spanIdx(); i++) {
-              offset += props.line.spans[i].cols;
-            }
-            return offset;
-          };
-
-          return (
-            <SpanElement
-              span={span}
-              row={props.line.index}
-              colOffset={colOffset()}
-              cellW={cellW()}
-              selectionRange={props.selectionRange}
-              searchMatches={props.searchMatches}
-              searchCurrentIndex={props.searchCurrentIndex}
-              cursorCol={props.cursorCol}
-            />
-          );
-        }}
-      </For>
+    <div class="term-line" data-row={props.line.index} data-line-id={props.line.line_id} data-line-size={props.line.line_size}>
+      <span class="term-line-content">
+        <For each={props.line.spans}>
+          {(span, spanIdx) => {
+            // Compute the column offset for this span
+            const colOffset = () => {
+              let offset = 0;
+              for (let i = 0; i < spanIdx(); i++) {
+                offset += props.line.spans[i].cols;
+              }
+              return offset;
+            };
+
+            return (
+              <SpanElement
+                span={span}
+                row={props.line.index}
+                colOffset={colOffset()}
+                cellW={cellW()}
+                selectionRange={props.selectionRange}
+                searchMatches={props.searchMatches}
+                searchCurrentIndex={props.searchCurrentIndex}
+                cursorCol={props.cursorCol}
+              />
+            );
+          }}
+        </For>
+      </span>
     </div>
   );
 };
@@ -86,6 +90,16 @@ const SpanElement: Component<SpanElementProps> = (props) => {
     const decorations: string[] = [];
     // TODO: re-enable underline once SGR state management is fixed
     // if (props.span.underline) decorations.push("underline");
+    // Styled underlines (spell-check, diagnostics) are always explicit SGR 4:x.
+    if (props.span.underline_style) {
+      decorations.push("underline");
+      s["text-decoration-style"] =
+        props.span.underline_style === "curly" ? "wavy" : props.span.underline_style;
+      const underlineColor = props.span.underline_color
+        ? colorToCSS(props.span.underline_color, ansiPalette())
+        : undefined;
+      if (underlineColor) s["text-decoration-color"] = underlineColor;
+    }
     if (props.span.strikethrough) decorations.push("line-through");
     if (decorations.length > 0) {
       s["text-decoration"] = decorations.join(" ");
@@ -94,6 +108,8 @@ const SpanElement: Component<SpanElementProps> = (props) => {
     return s;
   };
 
+  const spanClass = () => (props.span.blink ? `term-span term-blink-${props.span.blink}` : "term-span");
+
   const hasSelection = () => !!props.selectionRange;
   const hasSearchMatches = () => !!(props.searchMatches && props.searchMatches.length > 0);
   const hasCursor = () => props.cursorCol !== undefined;
@@ -186,35 +202,41 @@ const SpanElement: Component<SpanElementProps> = (props) => {
     return segs;
   });
 
-  const urls = createMemo(() => {
-    if (props.span.url) return [{ start: 0, end: props.span.text.length, url: props.span.url }];
-
-    const text = props.span.text;
-    if (text.length < 8 || !text.includes("://")) return [];
-
-    const matches: Array<{ start: number; end: number; url: string }> = [];
-    let match: RegExpExecArray | null;
-    const regex = new RegExp(URL_REGEX.source, "g");
-    while ((match = regex.exec(text)) !== null) {
-      matches.push({
-        start: match.index,
-        end: match.index + match[0].length,
-        url: match[0],
-      });
-    }
-    return matches;
+  // Clusters of a span of wide cells, each boxed to two columns: fonts draw
+  // ambiguous-width characters one column wide even when the session counts
+  // them as two.
+  const wideCells = createMemo(() => {
+    const cells = spanColumns(props.span.text);
+    return props.span.cols > cells.length ? cells : null;
   });
 
+  // The backend gives detected URLs and file paths spans of their own.
+  const link = createMemo(() => spanLink(props.span));
+
   return (
     <Show when={segments()} fallback={
-      <Show when={urls().length > 0} fallback={
-        <span class="term-span" style={style()}>{props.span.text}</span>
+      <Show when={wideCells()} fallback={
+        <Show when={link()} fallback={
+          <span class={spanClass()} style={style()}>{props.span.text}</span>
+        }>
+          {(target) => (
+            <LinkSpan text={props.span.text} link={target()} class={spanClass()} style={style()} />
+          )}
+        </Show>
       }>
-        <UrlSpan text={props.span.text} urls={urls()} style={style()} />
+        {(cells) => (
+          <span class={spanClass()} style={style()}>
+            <For each={cells()}>
+              {(cell) => (
+                <span style={{ display: "inline-block", width: `${props.cellW * 2}px` }}>{cell}</span>
+              )}
+            </For>
+          </span>
+        )}
       </Show>
     }>
       {(segs) => (
-        <span class="term-span" style={style()}>
+        <span class={spanClass()} style={style()}>
           <For each={segs()}>
             {(seg) => {
               let cls = "";
@@ -239,53 +261,34 @@ const SpanElement: Component<SpanElementProps> = (props) => {
   );
 };
 
-// Renders text with clickable URL portions
-const UrlSpan: Component<{
+// Renders a span the backend marked as a URL or file path
+const LinkSpan: Component<{
   text: string;
-  urls: Array<{ start: number; end: number; url: string }>;
+  link: SpanLink;
+  class: string;
   style: Record<string, string>;
 }> = (props) => {
-  const parts = createMemo(() => {
-    const result: Array<{ text: string; url?: string }> = [];
-    let pos = 0;
-    for (const u of props.urls) {
-      if (u.start > pos) {
-        result.push({ text: props.text.substring(pos, u.start) });
-      }
-      result.push({ text: props.text.substring(u.start, u.end), url: u.url });
-      pos = u.end;
-    }
-    if (pos < props.text.length) {
-      result.push({ text: props.text.substring(pos) });
-    }
-    return result;
-  });
+  const label = () => ("url" in props.link ? props.link.url : pathLinkLabel(props.link.path));
 
-  const handleUrlClick = (url: string, e: MouseEvent) => {
+  const handleClick = (e: MouseEvent) => {
     if (e.metaKey || e.ctrlKey) {
       e.preventDefault();
       e.stopPropagation();
-      // Open URL using window.open as fallback (Tauri intercepts and opens in system browser)
-      window.open(url, "_blank");
+      e.currentTarget?.dispatchEvent(
+        new CustomEvent<SpanLink>(OPEN_LINK_EVENT, { bubbles: true, detail: props.link }),
+      );
     }
   };
 
   return (
-    <span class="term-span" style={props.style}>
-      <For each={parts()}>
-        {(part) => (
-          <Show when={part.url} fallback={<>{part.text}</>}>
-            <span
-              class="term-url"
-              title={`${part.url} (Cmd+Click to open)`}
-              onClick={(e) => handleUrlClick(part.url!, e)}
-            >
-              {part.text}
-            </span>
-          </Show>
-        )}
-      </For>
+    <span
+      class={`${props.class} term-url`}
+      style={props.style}
+      data-url={"url" in props.link ? props.link.url : undefined}
+      title={`${label()} (Cmd+Click to open)`}
+      onClick={handleClick}
+    >
+      {props.text}
     </span>
   );
 };
-
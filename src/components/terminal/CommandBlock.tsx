@@ -1,16 +1,31 @@
 import { Component, For, Show, createSignal } from "solid-js";
 import type { CommandSnapshot } from "../../lib/types";
+import { copyText } from "../../lib/ipc";
 import { TerminalLine } from "../TerminalLine";
-import { IconFolder, IconCopy, IconCommand } from "../icons";
-import { formatCwdSimplified } from "./utils";
+import { IconFolder, IconCopy, IconCommand, IconClose } from "../icons";
+import { formatCwdSimplified, formatGitRef } from "./utils";
+import { StructuredView } from "./StructuredView";
 
 export const CommandBlock: Component<{
   snapshot: CommandSnapshot;
   charWidth: number;
   letterSpacing: number;
   promptStyle: "default" | "simplified" | "blank";
+  /** Needed to fetch structured output; the view is offered only with it. */
+  sessionId?: string | null;
+  /** Offered while the block's command runs, to interrupt it. */
+  onStop?: () => void;
 }> = (props) => {
   const [copied, setCopied] = createSignal<"command" | "output" | null>(null);
+  const [structuredOpen, setStructuredOpen] = createSignal(false);
+
+  const structured = () => {
+    const info = props.snapshot.structured;
+    const blockId = props.snapshot.blockId;
+    const sessionId = props.sessionId;
+    if (!info || !blockId || !sessionId) return null;
+    return { ...info, blockId, sessionId };
+  };
 
   const displayCwd = () => {
     const cwd = props.snapshot.cwd;
@@ -35,7 +50,7 @@ export const CommandBlock: Component<{
   const copyCommand = (e: MouseEvent) => {
     e.stopPropagation();
     if (props.snapshot.command) {
-      navigator.clipboard.writeText(props.snapshot.command).catch(console.error);
+      copyText(props.snapshot.command).catch(console.error);
       setCopied("command");
       setTimeout(() => setCopied(null), 1500);
     }
@@ -51,7 +66,7 @@ export const CommandBlock: Component<{
           .trimEnd(),
       )
       .join("\n");
-    navigator.clipboard.writeText(text).catch(console.error);
+    copyText(text).catch(console.error);
     setCopied("output");
     setTimeout(() => setCopied(null), 1500);
   };
@@ -59,10 +74,23 @@ export const CommandBlock: Component<{
   return (
     <div
       class="command-block"
+      data-block-id={props.snapshot.id}
       classList={{ "block-failed": props.snapshot.failed }}
     >
       {/* Floating action toolbar */}
       <div class="block-actions">
+        <Show when={props.onStop && props.snapshot.endTime == null}>
+          <button
+            class="block-action-btn"
+            onClick={(e) => {
+              e.stopPropagation();
+              props.onStop?.();
+            }}
+            title="Stop command"
+          >
+            <IconClose size={12} />
+          </button>
+        </Show>
         <Show when={props.snapshot.command}>
           <button
             class="block-action-btn"
@@ -86,6 +114,13 @@ export const CommandBlock: Component<{
       <div class="block-header">
         <span class="block-header-icon"><IconFolder size={11} /></span>
         <span class="block-cwd">{displayCwd()}</span>
+        <Show when={props.snapshot.git}>
+          {(git) => (
+            <span class="block-git" title={git().commit ? `Ran on commit ${git().commit}` : undefined}>
+              {formatGitRef(git())}
+            </span>
+          )}
+        </Show>
         <Show when={duration()}>
           <span class="block-duration">{duration()}</span>
         </Show>
@@ -107,6 +142,10 @@ export const CommandBlock: Component<{
         </div>
       </Show>
 
+      <Show when={structuredOpen() && structured()}>
+        {(info) => <StructuredView sessionId={info().sessionId} blockId={info().blockId} kind={info().kind} />}
+      </Show>
+
       {/* Footer for completed blocks with exit info */}
       <Show when={props.snapshot.endTime !== null && props.snapshot.endTime !== undefined}>
         <div class="block-footer">
@@ -120,6 +159,20 @@ export const CommandBlock: Component<{
             <IconCopy size={11} />
             {copied() === "output" ? "copied!" : "copy"}
           </button>
+          <Show when={structured()}>
+            {(info) => (
+              <button
+                class="block-action"
+                title={info().sample}
+                onClick={(e) => {
+                  e.stopPropagation();
+                  setStructuredOpen(!structuredOpen());
+                }}
+              >
+                {structuredOpen() ? "hide" : info().kind === "json" ? "json tree" : "table"}
+              </button>
+            )}
+          </Show>
         </div>
       </Show>
     </div>
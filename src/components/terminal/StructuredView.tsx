@@ -0,0 +1,97 @@
+import { Component, For, Match, Show, Switch, createResource } from "solid-js";
+import { getBlockStructured } from "../../lib/ipc";
+import type { StructuredData, StructuredKind } from "../../lib/types";
+
+/** Rows rendered before the table is cut off with a note. */
+const MAX_TABLE_ROWS = 500;
+
+const JsonNode: Component<{ name?: string; value: unknown; open?: boolean }> = (props) => {
+  const entries = () => {
+    const value = props.value;
+    if (Array.isArray(value)) return value.map((v, i) => [String(i), v] as const);
+    if (value !== null && typeof value === "object") return Object.entries(value);
+    return null;
+  };
+  const label = () => (props.name !== undefined ? <span class="structured-json-key">{props.name}: </span> : null);
+
+  return (
+    <Show
+      when={entries()}
+      fallback={
+        <div class="structured-json-leaf">
+          {label()}
+          <span class={`structured-json-${props.value === null ? "null" : typeof props.value}`}>
+            {JSON.stringify(props.value)}
+          </span>
+        </div>
+      }
+    >
+      {(children) => (
+        <details class="structured-json-node" open={props.open}>
+          <summary>
+            {label()}
+            <span class="structured-json-summary">
+              {Array.isArray(props.value) ? `[${children().length}]` : `{${children().length}}`}
+            </span>
+          </summary>
+          <For each={children()}>{([key, value]) => <JsonNode name={key} value={value} />}</For>
+        </details>
+      )}
+    </Show>
+  );
+};
+
+/** Table or JSON tree of a block's output, fetched from the backend on open. */
+export const StructuredView: Component<{
+  sessionId: string;
+  blockId: string;
+  kind: StructuredKind;
+}> = (props) => {
+  const [data] = createResource(
+    () => [props.sessionId, props.blockId, props.kind] as const,
+    ([sessionId, blockId, kind]) => getBlockStructured(sessionId, blockId, kind),
+  );
+
+  return (
+    <div class="block-structured">
+      <Show when={!data.error} fallback={<div class="structured-error">{String(data.error)}</div>}>
+        <Show when={data()}>
+          {(parsed) => (
+            <Switch>
+              <Match when={parsed().kind === "json" && (parsed() as Extract<StructuredData, { kind: "json" }>)}>
+                {(json) => <JsonNode value={json().value} open />}
+              </Match>
+              <Match when={parsed().kind === "table" && (parsed() as Extract<StructuredData, { kind: "table" }>)}>
+                {(table) => (
+                  <>
+                    <table class="structured-table">
+                      <thead>
+                        <tr>
+                          <For each={table().columns}>{(column) => <th>{column}</th>}</For>
+                        </tr>
+                      </thead>
+                      <tbody>
+                        <For each={table().rows.slice(0, MAX_TABLE_ROWS)}>
+                          {(row) => (
+                            <tr>
+                              <For each={row}>{(cell) => <td>{cell}</td>}</For>
+                            </tr>
+                          )}
+                        </For>
+                      </tbody>
+                    </table>
+                    <Show when={table().rows.length > MAX_TABLE_ROWS}>
+                      <div class="structured-note">
+                        Showing {MAX_TABLE_ROWS} of {table().rows.length} rows
+                      </div>
+                    </Show>
+                  </>
+                )}
+              </Match>
+            </Switch>
+          )}
+        </Show>
+      </Show>
+    </div>
+  );
+};
@@ -28,7 +28,7 @@ export const TraditionalBlock: Component<{
   };
 
   return (
-    <div class="traditional-block">
+    <div class="traditional-block" data-block-id={props.snapshot.id}>
       <Show when={props.snapshot.command}>
         <div class="term-line traditional-prompt-line">
           <Show when={displayPrompt()}>
@@ -1,4 +1,4 @@
-import type { RenderedLine } from "../../lib/types";
+import type { GitRef, RenderedLine } from "../../lib/types";
 import { getHostname as fetchHostname } from "../../lib/ipc";
 import { createSignal } from "solid-js";
 
@@ -13,6 +13,13 @@ export function buildFullGrid(buffer: RenderedLine[], rows: number): RenderedLin
   return result;
 }
 
+/** "feature/x @ abc1234", or "rebasing feature/x @ abc1234" mid-rebase. */
+export function formatGitRef(git: GitRef): string {
+  const commit = git.commit?.slice(0, 7);
+  const parts = [git.branch, commit].filter(Boolean).join(" @ ");
+  return git.rebasing ? `rebasing ${parts}` : parts;
+}
+
 // Shared cwd formatting utilities
 export function formatCwdSimplified(cwd: string): string {
   if (!cwd) return "~";
@@ -1,14 +1,26 @@
 import { render } from "solid-js/web";
 import App from "./App";
 import { initTheme } from "./stores/theme";
-import { loadConfigFromDisk } from "./stores/config";
+import { enterSafeMode, loadConfigFromDisk } from "./stores/config";
+import { getStartupMode } from "./lib/ipc";
 import "./styles/base.css";
 
 const root = document.getElementById("root");
 if (root) {
-  initTheme();
-  loadConfigFromDisk().catch((error) => {
-    console.warn("[Rain] Failed to bootstrap config from disk:", error);
-  });
-  render(() => <App />, root);
+  // Safe mode has to be known before the first render so a bad theme or
+  // window effect never gets applied.
+  void getStartupMode()
+    .catch(() => null)
+    .then((mode) => {
+      const safeMode = mode?.safeMode ?? false;
+      initTheme(safeMode);
+      if (safeMode) {
+        enterSafeMode();
+      } else {
+        loadConfigFromDisk().catch((error) => {
+          console.warn("[Rain] Failed to bootstrap config from disk:", error);
+        });
+      }
+      render(() => <App />, root);
+    });
 }
@@ -0,0 +1,58 @@
+import { describe, it, expect, vi } from "vitest";
+
+const getEventSchema = vi.fn();
+vi.mock("../ipc", () => ({
+  getEventSchema: () => getEventSchema(),
+}));
+
+import {
+  FRONTEND_EVENT_SCHEMA,
+  backendSupportsEvent,
+  filterKnownEvents,
+  negotiateEventSchema,
+  onUnknownEvent,
+} from "../eventSchema";
+
+describe("filterKnownEvents", () => {
+  it("returns known events untouched", () => {
+    const events = [{ type: "Bell" }, { type: "TitleChanged", title: "x" }];
+    expect(filterKnownEvents("terminal", events, FRONTEND_EVENT_SCHEMA)).toBe(events);
+  });
+
+  it("passes unknown events through to listeners", () => {
+    vi.spyOn(console, "debug").mockImplementation(() => {});
+    vi.spyOn(console, "warn").mockImplementation(() => {});
+    const seen: string[] = [];
+    const off = onUnknownEvent((e) => seen.push(`${e.source}:${e.type}`));
+    const kept = filterKnownEvents(
+      "terminal",
+      [{ type: "Bell" }, { type: "FutureThing", value: 1 }],
+      FRONTEND_EVENT_SCHEMA + 1,
+    );
+    off();
+    expect(kept).toEqual([{ type: "Bell" }]);
+    expect(seen).toEqual(["terminal:FutureThing"]);
+  });
+});
+
+describe("negotiateEventSchema", () => {
+  it("treats a backend without the command as emitting known events", async () => {
+    getEventSchema.mockRejectedValueOnce(new Error("unknown command"));
+    expect(await negotiateEventSchema()).toBeNull();
+    expect(backendSupportsEvent("terminal", "KittyImage")).toBe(true);
+    expect(backendSupportsEvent("terminal", "FutureThing")).toBe(false);
+  });
+
+  it("reports what an older backend does not emit", async () => {
+    vi.spyOn(console, "warn").mockImplementation(() => {});
+    getEventSchema.mockResolvedValueOnce({
+      version: FRONTEND_EVENT_SCHEMA - 1,
+      terminalEvents: ["Bell"],
+      tmuxEvents: ["Started"],
+    });
+    await negotiateEventSchema();
+    expect(backendSupportsEvent("terminal", "Bell")).toBe(true);
+    expect(backendSupportsEvent("terminal", "KittyImage")).toBe(false);
+    expect(backendSupportsEvent("tmux", "Ended")).toBe(false);
+  });
+});
@@ -0,0 +1,43 @@
+import { describe, it, expect } from "vitest";
+import { expandSpanStyles } from "../frameStyles";
+import type { RenderFrame } from "../types";
+
+const red = {
+  fg: { type: "Indexed", index: 1 },
+  bg: { type: "Default" },
+  bold: false,
+  dim: false,
+  italic: false,
+  underline: false,
+  strikethrough: false,
+};
+
+describe("expandSpanStyles", () => {
+  it("resolves style indices and leaves inline spans alone", () => {
+    const frame = {
+      lines: [
+        {
+          index: 0,
+          spans: [
+            { text: "a", cols: 1, style: 0, url: "https://example.com" },
+            { ...red, text: "b", cols: 1 },
+          ],
+        },
+      ],
+      scrolled_lines: [{ index: 0, spans: [{ text: "c", cols: 1, style: 0 }] }],
+      styles: [red],
+    } as unknown as RenderFrame;
+
+    expandSpanStyles(frame);
+
+    expect(frame.styles).toBeUndefined();
+    expect(frame.lines[0].spans[0]).toEqual({
+      ...red,
+      text: "a",
+      cols: 1,
+      url: "https://example.com",
+    });
+    expect(frame.lines[0].spans[1]).toEqual({ ...red, text: "b", cols: 1 });
+    expect(frame.scrolled_lines[0].spans[0]).toEqual({ ...red, text: "c", cols: 1 });
+  });
+});
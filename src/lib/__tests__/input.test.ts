@@ -1,5 +1,5 @@
 import { describe, it, expect } from "vitest";
-import { keyEventToBytes } from "../input";
+import { keyEventToBytes, KITTY_DISAMBIGUATE, KITTY_REPORT_ALL_KEYS } from "../input";
 
 function makeKeyEvent(overrides: Partial<KeyboardEvent>): KeyboardEvent {
   return {
@@ -147,3 +147,45 @@ describe("keyEventToBytes", () => {
     expect(Array.from(bytes)).toEqual([0x1b, 0x5b, 0x31, 0x3b, 0x32, 0x41]);
   });
 });
+
+describe("keyEventToBytes with kitty keyboard flags", () => {
+  const text = (bytes: Uint8Array) => new TextDecoder().decode(bytes);
+
+  it("disambiguates Escape and modified keys but keeps plain text", () => {
+    const flags = KITTY_DISAMBIGUATE;
+    expect(text(keyEventToBytes(makeKeyEvent({ key: "Escape" }), true, false, flags))).toBe("\x1b[27u");
+    expect(text(keyEventToBytes(makeKeyEvent({ key: "i", ctrlKey: true }), true, false, flags))).toBe("\x1b[105;5u");
+    expect(text(keyEventToBytes(makeKeyEvent({ key: "Enter", shiftKey: true }), true, false, flags))).toBe("\x1b[13;2u");
+    expect(text(keyEventToBytes(makeKeyEvent({ key: "a" }), true, false, flags))).toBe("a");
+    expect(Array.from(keyEventToBytes(makeKeyEvent({ key: "Enter" }), true, false, flags))).toEqual([0x0d]);
+  });
+
+  it("reports all keys as escape codes", () => {
+    const flags = KITTY_DISAMBIGUATE | KITTY_REPORT_ALL_KEYS;
+    expect(text(keyEventToBytes(makeKeyEvent({ key: "a" }), true, false, flags))).toBe("\x1b[97u");
+    expect(text(keyEventToBytes(makeKeyEvent({ key: "Enter" }), true, false, flags))).toBe("\x1b[13u");
+    expect(text(keyEventToBytes(makeKeyEvent({ key: "ArrowUp" }), true, false, flags))).toBe("\x1b[A");
+  });
+});
+
+describe("keyEventToBytes with modifyOtherKeys", () => {
+  const text = (bytes: Uint8Array) => new TextDecoder().decode(bytes);
+  const encode = (overrides: Partial<KeyboardEvent>, level: number) =>
+    text(keyEventToBytes(makeKeyEvent(overrides), true, false, 0, level));
+
+  it("only reports keys that would lose their modifiers at level 1", () => {
+    expect(encode({ key: "a", ctrlKey: true }, 1)).toBe("\x01");
+    expect(encode({ key: "x", altKey: true }, 1)).toBe("\x1bx");
+    expect(encode({ key: "1", ctrlKey: true }, 1)).toBe("\x1b[27;5;49~");
+    expect(encode({ key: "A", ctrlKey: true, shiftKey: true }, 1)).toBe("\x1b[27;6;65~");
+    expect(encode({ key: "Enter", shiftKey: true }, 1)).toBe("\x1b[27;2;13~");
+  });
+
+  it("reports every modified key at level 2", () => {
+    expect(encode({ key: "a", ctrlKey: true }, 2)).toBe("\x1b[27;5;97~");
+    expect(encode({ key: "x", altKey: true }, 2)).toBe("\x1b[27;3;120~");
+    expect(encode({ key: "A", shiftKey: true }, 2)).toBe("A");
+    expect(encode({ key: "Tab", shiftKey: true }, 2)).toBe("\x1b[Z");
+    expect(encode({ key: "a" }, 2)).toBe("a");
+  });
+});
@@ -0,0 +1,40 @@
+import { describe, it, expect, vi } from "vitest";
+import { createInputBatcher } from "../inputBatcher";
+
+describe("createInputBatcher", () => {
+  it("merges writes from the same tick into one call per session", async () => {
+    const flush = vi.fn().mockResolvedValue(undefined);
+    const batcher = createInputBatcher(flush);
+    await Promise.all([
+      batcher.write("a", [1]),
+      batcher.write("a", [2, 3]),
+      batcher.write("b", [9]),
+    ]);
+    expect(flush).toHaveBeenCalledTimes(2);
+    expect(flush).toHaveBeenCalledWith("a", [[1], [2, 3]]);
+    expect(flush).toHaveBeenCalledWith("b", [[9]]);
+  });
+
+  it("queues writes behind an in-flight batch, keeping order", async () => {
+    const calls: number[][][] = [];
+    let release!: () => void;
+    const flush = vi.fn((_sid: string, chunks: number[][]) => {
+      calls.push(chunks);
+      return calls.length === 1 ? new Promise<void>((r) => (release = r)) : Promise.resolve();
+    });
+    const batcher = createInputBatcher(flush);
+    const first = batcher.write("a", [1]);
+    await Promise.resolve();
+    const second = batcher.write("a", [2]);
+    const third = batcher.write("a", [3]);
+    release();
+    await Promise.all([first, second, third]);
+    expect(calls).toEqual([[[1]], [[2], [3]]]);
+  });
+
+  it("rejects every write of a failed batch", async () => {
+    const batcher = createInputBatcher(() => Promise.reject(new Error("gone")));
+    const results = await Promise.allSettled([batcher.write("a", [1]), batcher.write("a", [2])]);
+    expect(results.map((r) => r.status)).toEqual(["rejected", "rejected"]);
+  });
+});
@@ -0,0 +1,20 @@
+import { describe, it, expect } from "vitest";
+import { describeRunning, processLabel } from "../processes";
+
+const proc = (name: string, ...argv: string[]) => ({ pid: 1, name, argv });
+
+describe("describeRunning", () => {
+  it("labels processes by how they were started", () => {
+    expect(processLabel(proc("node", "/usr/bin/npm", "run", "dev", "--port=3000"))).toBe("npm run dev");
+    expect(processLabel(proc("vim"))).toBe("vim");
+  });
+
+  it("names a few processes and counts the rest", () => {
+    expect(describeRunning([proc("vim", "vim")])).toBe("vim is still running");
+    expect(describeRunning([proc("ssh", "ssh"), proc("node", "npm", "run", "dev")])).toBe(
+      "ssh and npm run dev are still running",
+    );
+    const many = ["a", "b", "c", "d", "e"].map((name) => proc(name, name));
+    expect(describeRunning(many)).toBe("a, b, c and 2 more are still running");
+  });
+});
@@ -4,6 +4,7 @@ import {
   ensureDefaultProfile,
   deleteProfile,
   getProfiles,
+  parseCommandLine,
   type ShellProfile,
 } from "../profiles";
 
@@ -135,3 +136,19 @@ describe("deleteProfile", () => {
     expect(getProfiles().some((p) => p.id === "default")).toBe(true);
   });
 });
+
+describe("parseCommandLine", () => {
+  it("splits on whitespace and honours quotes and escapes", () => {
+    expect(parseCommandLine("  htop  ")).toEqual(["htop"]);
+    expect(parseCommandLine(`ssh -t host 'tmux attach' "a \\"b\\"" c\\ d ''`)).toEqual([
+      "ssh",
+      "-t",
+      "host",
+      "tmux attach",
+      'a "b"',
+      "c d",
+      "",
+    ]);
+    expect(parseCommandLine("")).toEqual([]);
+  });
+});
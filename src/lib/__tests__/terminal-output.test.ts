@@ -1,5 +1,5 @@
 import { describe, it, expect } from "vitest";
-import { trimTrailingEmpty, collectLinesForRange } from "../terminal-output";
+import { trimTrailingEmpty, collectLinesForRange, findGlobalRowForLineId } from "../terminal-output";
 import type { RenderedLine } from "../types";
 
 function line(text: string, index = 0): RenderedLine {
@@ -125,3 +125,27 @@ describe("collectLinesForRange", () => {
     expect(scrollback[0].spans[0].text).toBe("scroll-0");
   });
 });
+
+describe("findGlobalRowForLineId", () => {
+  const withId = (text: string, index: number, lineId: number): RenderedLine => ({
+    ...line(text, index),
+    line_id: lineId,
+  });
+
+  it("finds lines in scrollback and the visible map", () => {
+    const scrollback = [withId("a", 0, 10), withId("b", 1, 11)];
+    const visible = { 2: withId("c", 0, 12) };
+    expect(findGlobalRowForLineId(scrollback, visible, 11)).toBe(1);
+    expect(findGlobalRowForLineId(scrollback, visible, 12)).toBe(2);
+  });
+
+  it("prefers the newest visible key for a line stored twice", () => {
+    const visible = { 3: withId("x", 0, 7), 5: withId("x", 0, 7) };
+    expect(findGlobalRowForLineId([], visible, 7)).toBe(5);
+  });
+
+  it("returns null for unknown or missing IDs", () => {
+    expect(findGlobalRowForLineId([withId("a", 0, 1)], {}, 99)).toBeNull();
+    expect(findGlobalRowForLineId([withId("a", 0, 1)], {}, undefined)).toBeNull();
+  });
+});
@@ -1,3 +1,6 @@
+import type { LineSize, UnderlineStyle } from "./types";
+import type { SpanLink } from "./links";
+
 export interface CanvasRendererConfig {
   fontFamily: string;
   fontSize: number;
@@ -20,6 +23,10 @@ export interface CanvasCell {
   bold: boolean;
   italic: boolean;
   underline: boolean;
+  /** Defaults to a single line. */
+  underlineStyle?: UnderlineStyle;
+  /** CSS color of the underline; defaults to `fg`. */
+  underlineColor?: string;
   strikethrough: boolean;
   dim: boolean;
 }
@@ -30,6 +37,35 @@ export function canUseCanvasRenderer(): boolean {
   return canvas.getContext("2d") !== null;
 }
 
+interface GraphemeSegmenter {
+  segment(text: string): Iterable<{ segment: string }>;
+}
+
+// Intl.Segmenter is missing from the ES2021 lib typings.
+const graphemeSegmenter: GraphemeSegmenter | null = (() => {
+  const Segmenter = (Intl as unknown as {
+    Segmenter?: new (locale: undefined, options: { granularity: "grapheme" }) => GraphemeSegmenter;
+  }).Segmenter;
+  return Segmenter ? new Segmenter(undefined, { granularity: "grapheme" }) : null;
+})();
+
+/** What each column of a span shows: one grapheme cluster per cell and ""
+ *  in the second column of a wide one. The backend never mixes wide and
+ *  narrow cells in a span, so `cols` over the cluster count is each one's
+ *  width, whichever way the session counts ambiguous-width characters. */
+export function spanColumns(text: string, cols?: number): string[] {
+  const graphemes = graphemeSegmenter
+    ? Array.from(graphemeSegmenter.segment(text), (s) => s.segment)
+    : Array.from(text);
+  const width = cols && graphemes.length > 0 ? Math.max(1, Math.round(cols / graphemes.length)) : 1;
+  const columns: string[] = [];
+  for (const grapheme of graphemes) {
+    columns.push(grapheme);
+    for (let i = 1; i < width; i++) columns.push("");
+  }
+  return columns;
+}
+
 type GlyphKey = string;
 
 function makeGlyphKey(
@@ -42,12 +78,11 @@ function makeGlyphKey(
   return `${char}\x00${fg}\x00${bold ? 1 : 0}${italic ? 1 : 0}${dim ? 1 : 0}`;
 }
 
-const URL_REGEX = /https?:\/\/[^\s<>"{}|\\^`\[\]]+/g;
 
-interface UrlRange {
+interface LinkRange {
   startCol: number;
   endCol: number;
-  url: string;
+  link: SpanLink;
 }
 
 class GlyphCache {
@@ -180,7 +215,9 @@ export class CanvasTerminalRenderer {
   private dirtyRows = new Set<number>();
   private rafId: number | null = null;
   private glyphCache: GlyphCache | null = null;
-  private urlRanges = new Map<number, UrlRange[]>();
+  private linkRanges = new Map<number, LinkRange[]>();
+  /** DECDWL/DECDHL rows; rows not in the map are single size. */
+  private lineSizes = new Map<number, LineSize>();
 
   constructor(canvas: HTMLCanvasElement, config: CanvasRendererConfig) {
     this.canvas = canvas;
@@ -252,7 +289,8 @@ export class CanvasTerminalRenderer {
       this.grid.push(row);
     }
 
-    this.urlRanges.clear();
+    this.linkRanges.clear();
+    this.lineSizes.clear();
     this.measureFont();
     this.fullDirty = true;
     this.scheduleRender();
@@ -278,20 +316,27 @@ export class CanvasTerminalRenderer {
     row: number,
     spans: Array<{
       text: string;
+      /** Columns the span covers; one per cluster when absent. */
+      cols?: number;
       fg: string;
       bg: string;
       bold: boolean;
       italic: boolean;
       underline: boolean;
+      underlineStyle?: UnderlineStyle;
+      underlineColor?: string;
       strikethrough: boolean;
       dim: boolean;
+      /** URL or file path the backend found on this span. */
+      link?: SpanLink;
     }>,
   ): void {
     if (row < 0 || row >= this.config.rows) return;
     let col = 0;
-    let lineText = "";
+    const links: LinkRange[] = [];
     for (const span of spans) {
-      for (const char of span.text) {
+      const startCol = col;
+      for (const char of spanColumns(span.text, span.cols)) {
         if (col >= this.config.cols) break;
         this.grid[row][col] = {
           char,
@@ -300,12 +345,16 @@ export class CanvasTerminalRenderer {
           bold: span.bold,
           italic: span.italic,
           underline: span.underline,
+          underlineStyle: span.underlineStyle,
+          underlineColor: span.underlineColor,
           strikethrough: span.strikethrough,
           dim: span.dim,
         };
         col++;
       }
-      lineText += span.text;
+      if (span.link && col > startCol) {
+        links.push({ startCol, endCol: col - 1, link: span.link });
+      }
     }
     while (col < this.config.cols) {
       this.grid[row][col] = {
@@ -321,30 +370,29 @@ export class CanvasTerminalRenderer {
       col++;
     }
 
-    if (lineText.length >= 8 && lineText.includes("://")) {
-      const urls: UrlRange[] = [];
-      const regex = new RegExp(URL_REGEX.source, "g");
-      let match: RegExpExecArray | null;
-      while ((match = regex.exec(lineText)) !== null) {
-        urls.push({
-          startCol: match.index,
-          endCol: match.index + match[0].length - 1,
-          url: match[0],
-        });
-      }
-      if (urls.length > 0) {
-        this.urlRanges.set(row, urls);
-      } else {
-        this.urlRanges.delete(row);
-      }
+    if (links.length > 0) {
+      this.linkRanges.set(row, links);
     } else {
-      this.urlRanges.delete(row);
+      this.linkRanges.delete(row);
     }
 
     this.dirtyRows.add(row);
     this.scheduleRender();
   }
 
+  /** Set a row's DEC line size; double-size rows draw their cells scaled up. */
+  setLineSize(row: number, size: LineSize | undefined): void {
+    if (row < 0 || row >= this.config.rows) return;
+    if (this.lineSizes.get(row) === size) return;
+    if (size) {
+      this.lineSizes.set(row, size);
+    } else {
+      this.lineSizes.delete(row);
+    }
+    this.dirtyRows.add(row);
+    this.scheduleRender();
+  }
+
   private scheduleRender(): void {
     if (this.rafId !== null) return;
     this.rafId = requestAnimationFrame(() => {
@@ -394,6 +442,20 @@ export class CanvasTerminalRenderer {
     const row = this.grid[r];
     if (!row) return;
 
+    // Double-size rows: scale about the row's top (or bottom, for the lower
+    // half of double height) and clip so only this row's half shows.
+    const lineSize = this.lineSizes.get(r);
+    ctx.save();
+    if (lineSize) {
+      ctx.beginPath();
+      ctx.rect(0, y, this.viewportWidth, ch);
+      ctx.clip();
+      const anchor = lineSize === "double_height_bottom" ? y + ch : y;
+      ctx.translate(0, anchor);
+      ctx.scale(2, lineSize === "double_width" ? 1 : 2);
+      ctx.translate(0, -anchor);
+    }
+
     let runStart = 0;
     let runBg = row[0]?.bg ?? this.config.defaultBg;
 
@@ -434,12 +496,9 @@ export class CanvasTerminalRenderer {
       }
 
       if (cell.underline) {
-        ctx.strokeStyle = cell.dim ? this.dimColor(cell.fg) : cell.fg;
-        ctx.lineWidth = 1;
-        ctx.beginPath();
-        ctx.moveTo(c * cw, y + ch - 2);
-        ctx.lineTo((c + 1) * cw, y + ch - 2);
-        ctx.stroke();
+        const color = cell.underlineColor ?? cell.fg;
+        ctx.strokeStyle = cell.dim ? this.dimColor(color) : color;
+        this.strokeUnderline(ctx, cell.underlineStyle ?? "single", c * cw, y + ch - 2, cw);
       }
 
       if (cell.strikethrough) {
@@ -452,7 +511,7 @@ export class CanvasTerminalRenderer {
       }
     }
 
-    const urls = this.urlRanges.get(r);
+    const urls = this.linkRanges.get(r);
     if (urls) {
       ctx.strokeStyle = "#58a6ff";
       ctx.lineWidth = 1;
@@ -467,6 +526,7 @@ export class CanvasTerminalRenderer {
       }
       ctx.globalAlpha = 1.0;
     }
+    ctx.restore();
   }
 
   private dimColor(color: string): string {
@@ -476,6 +536,49 @@ export class CanvasTerminalRenderer {
     return color;
   }
 
+  /** Stroke one cell's underline with its baseline at `y`, in the current stroke style. */
+  private strokeUnderline(
+    ctx: CanvasRenderingContext2D,
+    style: UnderlineStyle,
+    x: number,
+    y: number,
+    width: number,
+  ): void {
+    ctx.lineWidth = 1;
+    ctx.beginPath();
+    switch (style) {
+      case "double":
+        ctx.moveTo(x, y);
+        ctx.lineTo(x + width, y);
+        ctx.moveTo(x, y - 2);
+        ctx.lineTo(x + width, y - 2);
+        break;
+      case "curly": {
+        // One wave per cell so neighbouring cells join up.
+        const amplitude = 1.5;
+        ctx.moveTo(x, y - amplitude);
+        ctx.bezierCurveTo(x + width / 4, y - amplitude * 3, x + width / 4, y + amplitude, x + width / 2, y - amplitude);
+        ctx.bezierCurveTo(x + (width * 3) / 4, y - amplitude * 3, x + (width * 3) / 4, y + amplitude, x + width, y - amplitude);
+        break;
+      }
+      case "dotted":
+        ctx.setLineDash([1, 2]);
+        ctx.moveTo(x, y);
+        ctx.lineTo(x + width, y);
+        break;
+      case "dashed":
+        ctx.setLineDash([Math.max(2, width / 2 - 1), Math.max(1, width / 2 + 1)]);
+        ctx.moveTo(x, y);
+        ctx.lineTo(x + width, y);
+        break;
+      default:
+        ctx.moveTo(x, y);
+        ctx.lineTo(x + width, y);
+    }
+    ctx.stroke();
+    ctx.setLineDash([]);
+  }
+
   renderCursor(
     row: number,
     col: number,
@@ -552,11 +655,11 @@ export class CanvasTerminalRenderer {
     ctx.globalAlpha = 1.0;
   }
 
-  getUrlAt(row: number, col: number): string | null {
-    const urls = this.urlRanges.get(row);
-    if (!urls) return null;
-    for (const u of urls) {
-      if (col >= u.startCol && col <= u.endCol) return u.url;
+  getLinkAt(row: number, col: number): SpanLink | null {
+    const links = this.linkRanges.get(row);
+    if (!links) return null;
+    for (const l of links) {
+      if (col >= l.startCol && col <= l.endCol) return l.link;
     }
     return null;
   }
@@ -576,7 +679,7 @@ export class CanvasTerminalRenderer {
     this.stopRenderLoop();
     this.glyphCache?.clear();
     this.glyphCache = null;
-    this.urlRanges.clear();
+    this.linkRanges.clear();
   }
 
   getCharWidth(): number {
@@ -0,0 +1,144 @@
+// Event schema negotiation between the frontend and backend. During
+// live-reload development the two can be built from different revisions, so
+// events this frontend doesn't know are passed through to `onUnknownEvent`
+// listeners (and logged once) instead of reaching handlers that would
+// misinterpret them.
+
+import { getEventSchema, type EventSchema } from "./ipc";
+
+/** Must match `EVENT_SCHEMA_VERSION` in src-tauri/src/render/frame.rs. */
+export const FRONTEND_EVENT_SCHEMA = 19;
+
+const KNOWN_TERMINAL_EVENTS: ReadonlySet<string> = new Set([
+  "BlockStarted",
+  "BlockCommand",
+  "BlockCompleted",
+  "TitleChanged",
+  "AltScreenEntered",
+  "AltScreenExited",
+  "Bell",
+  "CwdChanged",
+  "SessionNameChanged",
+  "CustomOsc",
+  "MouseModeChanged",
+  "ScrollbackCleared",
+  "ScrollbackRewrapped",
+  "InlineImage",
+  "FileDownload",
+  "SixelImage",
+  "KittyImage",
+  "TmuxRequested",
+  "CommandQueueChanged",
+  "RemotePromptDetected",
+  "ColumnModeChanged",
+  "BlockStructuredData",
+  "PaletteChanged",
+  "DynamicColorsChanged",
+  "Notification",
+  "ProgressChanged",
+  "TerminalCapabilitiesChanged",
+  "SelectionCleared",
+]);
+
+const KNOWN_TMUX_EVENTS: ReadonlySet<string> = new Set([
+  "Started",
+  "PaneAdded",
+  "PaneRemoved",
+  "WindowAdded",
+  "WindowClosed",
+  "WindowRenamed",
+  "LayoutChanged",
+  "Detached",
+  "Ended",
+]);
+
+export type EventSource = "terminal" | "tmux";
+
+export interface UnknownEvent {
+  source: EventSource;
+  type: string;
+  payload: unknown;
+}
+
+type UnknownEventListener = (event: UnknownEvent) => void;
+
+const listeners = new Set<UnknownEventListener>();
+const loggedTypes = new Set<string>();
+let warnedFrameSchema: number | null = null;
+let backendSchema: EventSchema | null = null;
+
+export function isKnownEvent(source: EventSource, type: string): boolean {
+  return (source === "terminal" ? KNOWN_TERMINAL_EVENTS : KNOWN_TMUX_EVENTS).has(type);
+}
+
+/** Subscribe to events this frontend has no handler for. */
+export function onUnknownEvent(listener: UnknownEventListener): () => void {
+  listeners.add(listener);
+  return () => listeners.delete(listener);
+}
+
+/** Pass an unrecognized event on to listeners; logs each type once. */
+export function passThroughUnknownEvent(source: EventSource, payload: { type?: unknown }): void {
+  const type = String(payload.type);
+  const key = `${source}:${type}`;
+  if (!loggedTypes.has(key)) {
+    loggedTypes.add(key);
+    console.debug(`[Rain] Ignoring unknown ${source} event "${type}" (backend schema is newer?)`);
+  }
+  for (const listener of listeners) {
+    try {
+      listener({ source, type, payload });
+    } catch (e) {
+      console.error("[Rain] Unknown event listener failed:", e);
+    }
+  }
+}
+
+/**
+ * Split known from unknown events, passing the unknown ones through. Frames
+ * from backends predating the schema carry no `event_schema` field.
+ */
+export function filterKnownEvents<T extends { type: string }>(
+  source: EventSource,
+  events: T[],
+  frameSchema?: number,
+): T[] {
+  if (frameSchema !== undefined && frameSchema !== FRONTEND_EVENT_SCHEMA && warnedFrameSchema !== frameSchema) {
+    warnedFrameSchema = frameSchema;
+    console.warn(
+      `[Rain] Backend event schema v${frameSchema} differs from frontend v${FRONTEND_EVENT_SCHEMA}; unknown events will be ignored`,
+    );
+  }
+  if (events.every((event) => isKnownEvent(source, event.type))) return events;
+  return events.filter((event) => {
+    if (isKnownEvent(source, event.type)) return true;
+    passThroughUnknownEvent(source, event);
+    return false;
+  });
+}
+
+/** Fetch the backend's schema once at startup. Old backends without the
+ *  command resolve to null and are treated as emitting everything we know. */
+export async function negotiateEventSchema(): Promise<EventSchema | null> {
+  try {
+    backendSchema = await getEventSchema();
+  } catch {
+    backendSchema = null;
+    return null;
+  }
+  if (backendSchema.version !== FRONTEND_EVENT_SCHEMA) {
+    const missing = [...KNOWN_TERMINAL_EVENTS].filter((t) => !backendSchema!.terminalEvents.includes(t));
+    console.warn(
+      `[Rain] Event schema mismatch: backend v${backendSchema.version}, frontend v${FRONTEND_EVENT_SCHEMA}` +
+        (missing.length > 0 ? `; backend does not emit ${missing.join(", ")}` : ""),
+    );
+  }
+  return backendSchema;
+}
+
+/** Whether the backend emits an event type, so features depending on it can
+ *  be turned off against older backends. */
+export function backendSupportsEvent(source: EventSource, type: string): boolean {
+  if (!backendSchema) return isKnownEvent(source, type);
+  return (source === "terminal" ? backendSchema.terminalEvents : backendSchema.tmuxEvents).includes(type);
+}
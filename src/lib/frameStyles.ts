@@ -0,0 +1,23 @@
+import type { RenderFrame, StyledSpan } from "./types";
+
+/** A span as sent in a frame with a style table: its style is an index. */
+type InternedSpan = Pick<StyledSpan, "text" | "cols" | "url" | "path"> & { style: number };
+
+/** Resolve the `style` indices of a frame's spans against its style table,
+ *  in place, so the rest of the frontend sees self-contained spans. */
+export function expandSpanStyles(frame: RenderFrame): RenderFrame {
+  const styles = frame.styles;
+  if (!styles) return frame;
+  const expand = (spans: StyledSpan[]) => {
+    for (let i = 0; i < spans.length; i++) {
+      const span = spans[i] as unknown as Partial<InternedSpan>;
+      if (span.style === undefined) continue;
+      const { style, ...rest } = span;
+      spans[i] = { ...styles[style], ...rest } as StyledSpan;
+    }
+  };
+  for (const line of frame.lines) expand(line.spans);
+  for (const line of frame.scrolled_lines ?? []) expand(line.spans);
+  delete frame.styles;
+  return frame;
+}
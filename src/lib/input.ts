@@ -3,16 +3,33 @@
 
 const encoder = new TextEncoder();
 
+// Kitty keyboard protocol enhancement flags, as pushed with `CSI > flags u`
+// and reported in each render frame's `keyboard_flags`.
+export const KITTY_DISAMBIGUATE = 0b1;
+export const KITTY_REPORT_ALL_KEYS = 0b1000;
+
 export function keyEventToBytes(
   e: KeyboardEvent,
   optionAsMeta: boolean = true,
   cursorKeysApplication: boolean = false,
+  keyboardFlags: number = 0,
+  modifyOtherKeys: number = 0,
 ): Uint8Array {
   // Cmd+C / Cmd+V should be handled by the OS, not sent to terminal
   if (e.metaKey && (e.key === "c" || e.key === "v" || e.key === "a" || e.key === "x")) {
     return new Uint8Array([]);
   }
 
+  if (keyboardFlags & (KITTY_DISAMBIGUATE | KITTY_REPORT_ALL_KEYS)) {
+    const kitty = kittyKeyToBytes(e, keyboardFlags);
+    if (kitty) return kitty;
+  }
+
+  if (modifyOtherKeys > 0) {
+    const modified = modifyOtherKeyToBytes(e, modifyOtherKeys, optionAsMeta);
+    if (modified) return modified;
+  }
+
   // Ctrl+key combinations (C0 control codes)
   if (e.ctrlKey && !e.altKey && !e.metaKey && e.key.length === 1) {
     const code = e.key.toLowerCase().charCodeAt(0);
@@ -149,3 +166,67 @@ function modifierCode(e: KeyboardEvent): number {
   if (e.ctrlKey) code += 4;
   return code;
 }
+
+// Keys with their own code in the kitty protocol that legacy encoding
+// can't tell apart from control characters.
+const KITTY_FUNCTIONAL_KEYS: Record<string, number> = {
+  Escape: 27,
+  Enter: 13,
+  Tab: 9,
+  Backspace: 127,
+};
+
+// CSI <code>[;<mods>] u for keys the legacy encoding makes ambiguous. Returns
+// null where the legacy bytes are already what the protocol specifies
+// (plain text, arrows, function keys). Printable keys are reported by their
+// lowercased character; only press events are sent.
+function kittyKeyToBytes(e: KeyboardEvent, flags: number): Uint8Array | null {
+  const reportAll = (flags & KITTY_REPORT_ALL_KEYS) !== 0;
+  const mods = modifierCode(e);
+  const functional = KITTY_FUNCTIONAL_KEYS[e.key];
+  if (functional !== undefined) {
+    // Ctrl+Tab switches tabs; plain Enter/Tab/Backspace stay legacy so a
+    // shell left in this mode by a crashed program is still usable.
+    if (e.key === "Tab" && e.ctrlKey) return null;
+    if (!reportAll && e.key !== "Escape" && mods === 1) return null;
+    return csiU(functional, mods);
+  }
+  if (e.key.length !== 1 || e.metaKey) return null;
+  if (!reportAll && !e.ctrlKey && !e.altKey) return null;
+  return csiU(e.key.toLowerCase().codePointAt(0)!, mods);
+}
+
+function csiU(code: number, mods: number): Uint8Array {
+  return encoder.encode(mods > 1 ? `\x1b[${code};${mods}u` : `\x1b[${code}u`);
+}
+
+// Ctrl+key combinations with their own C0 control character.
+const CONTROL_CHARACTER_KEYS = /^[a-z[\\\]^_ ]$/;
+
+// CSI 27 ; <mods> ; <code> ~ for xterm's modifyOtherKeys (set with
+// `CSI > 4 ; level m`). Level 1 only covers combinations the legacy encoding
+// loses, such as Ctrl+digit, Ctrl+Shift+letter or a modified Enter; level 2
+// covers every modified key, leaving Shift on printable characters alone.
+function modifyOtherKeyToBytes(e: KeyboardEvent, level: number, optionAsMeta: boolean): Uint8Array | null {
+  // Option composes characters when it isn't Meta.
+  const alt = e.altKey && optionAsMeta;
+  if (e.metaKey || (!e.ctrlKey && !alt && !e.shiftKey)) return null;
+  const mods = 1 + (e.shiftKey ? 1 : 0) + (alt ? 2 : 0) + (e.ctrlKey ? 4 : 0);
+  const functional = KITTY_FUNCTIONAL_KEYS[e.key];
+  if (functional !== undefined) {
+    // Ctrl+Tab switches tabs and Shift+Tab has its own back-tab sequence.
+    if (e.key === "Tab" && (e.ctrlKey || mods === 2)) return null;
+    return modifiedOtherKey(functional, mods);
+  }
+  if (e.key.length !== 1 || (!e.ctrlKey && !alt)) return null;
+  if (level < 2) {
+    const hasControlCharacter = e.ctrlKey && !alt && CONTROL_CHARACTER_KEYS.test(e.key);
+    const hasMetaPrefix = alt && !e.ctrlKey;
+    if (hasControlCharacter || hasMetaPrefix) return null;
+  }
+  return modifiedOtherKey(e.key.codePointAt(0)!, mods);
+}
+
+function modifiedOtherKey(code: number, mods: number): Uint8Array {
+  return encoder.encode(`\x1b[27;${mods};${code}~`);
+}
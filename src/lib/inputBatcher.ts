@@ -0,0 +1,56 @@
+// Coalesces PTY writes per session. Writes issued in the same tick, or while
+// the previous batch is still crossing IPC, go out together as one
+// `write_input_batched` call. Batches for a session are sent one at a time,
+// so input can't be reordered by concurrent IPC calls.
+
+export type BatchFlush = (sessionId: string, chunks: number[][]) => Promise<void>;
+
+interface SessionQueue {
+  chunks: number[][];
+  waiters: { resolve: () => void; reject: (err: unknown) => void }[];
+  busy: boolean;
+}
+
+export interface InputBatcher {
+  write(sessionId: string, data: number[]): Promise<void>;
+}
+
+export function createInputBatcher(flush: BatchFlush): InputBatcher {
+  const queues = new Map<string, SessionQueue>();
+
+  async function drain(sessionId: string, queue: SessionQueue) {
+    while (queue.chunks.length > 0) {
+      const chunks = queue.chunks;
+      const waiters = queue.waiters;
+      queue.chunks = [];
+      queue.waiters = [];
+      try {
+        await flush(sessionId, chunks);
+        for (const w of waiters) w.resolve();
+      } catch (err) {
+        for (const w of waiters) w.reject(err);
+      }
+    }
+    queue.busy = false;
+    queues.delete(sessionId);
+  }
+
+  return {
+    write(sessionId, data) {
+      let queue = queues.get(sessionId);
+      if (!queue) {
+        queue = { chunks: [], waiters: [], busy: false };
+        queues.set(sessionId, queue);
+      }
+      const q = queue;
+      q.chunks.push(data);
+      const done = new Promise<void>((resolve, reject) => q.waiters.push({ resolve, reject }));
+      if (!q.busy) {
+        q.busy = true;
+        // Let the rest of this tick's writes join the batch.
+        queueMicrotask(() => void drain(sessionId, q));
+      }
+      return done;
+    },
+  };
+}
@@ -1,13 +1,30 @@
-import { invoke } from "@tauri-apps/api/core";
+import { Channel, invoke } from "@tauri-apps/api/core";
 import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 import type {
   RenderFramePayload,
   ResizeAckPayload,
   SessionEndPayload,
+  FrameReadyPayload,
+  FrameTransport,
+  SessionStalledPayload,
   CreateSessionResult,
   SessionTransferState,
   TabTransferManifest,
+  QueuedCommand,
+  SessionIoPayload,
+  SessionIoStats,
+  StructuredData,
+  StructuredKind,
+  CellRect,
+  ScrollbackMatch,
+  ScrollbackPage,
+  ViewportBlockPayload,
+  ProgressState,
+  CommandLine,
+  MouseReport,
 } from "./types";
+import { decode } from "@msgpack/msgpack";
+import { expandSpanStyles } from "./frameStyles";
 
 // Typed wrappers around Tauri IPC commands
 
@@ -18,7 +35,12 @@ export async function createSession(
   cols?: number,
   env?: Record<string, string>,
   tmuxMode?: "integrated" | "native",
+  ambiguousWide?: boolean,
+  scrollbackLimit?: number,
+  frames?: FrameTransport,
+  command?: string[],
 ): Promise<CreateSessionResult> {
+  const frameChannel = frames === "channel" ? createFrameChannel() : undefined;
   return invoke<CreateSessionResult>("create_session", {
     shell,
     cwd,
@@ -26,25 +48,228 @@ export async function createSession(
     cols,
     env,
     tmuxMode,
+    ambiguousWide,
+    scrollbackLimit,
+    frameChannel,
+    frameRing: frames === "ring",
+    command,
   });
 }
 
+/** What a frame channel or ring carries: MessagePack render frames and,
+ *  last, the end of the session, in the order the backend sent them. */
+type FrameChannelMessage =
+  | ({ type: "render-frame" } & RenderFramePayload)
+  | ({ type: "session-ended" } & SessionEndPayload);
+
+/** Feed a binary frame (or a session's end) to the same listeners as the
+ *  JSON events. */
+function dispatchFrameMessage(message: FrameChannelMessage) {
+  if (message.type === "session-ended") {
+    for (const handler of sessionEndedHandlers) handler(message);
+    return;
+  }
+  expandSpanStyles(message.frame);
+  for (const handler of renderFrameHandlers) handler(message);
+}
+
+/** A channel feeding a session's binary frames to `dispatchFrameMessage`. */
+function createFrameChannel(): Channel<ArrayBuffer> {
+  const channel = new Channel<ArrayBuffer>();
+  channel.onmessage = (data) => {
+    dispatchFrameMessage(decode(new Uint8Array(data)) as FrameChannelMessage);
+  };
+  return channel;
+}
+
+/** How far this window has read a session's frame ring. */
+interface FrameRingReader {
+  cursor: number;
+  /** Highest head announced so far. */
+  head: number;
+  reading: boolean;
+}
+
+const frameRingReaders = new Map<string, FrameRingReader>();
+
+/** Read the frame rings of sessions created with the `"ring"` transport
+ *  as `frame-ready` events announce new frames, feeding them to the
+ *  `onRenderFrame` listeners. Like `render-frame` events, they reach every
+ *  window. */
+export async function watchFrameRings(): Promise<UnlistenFn> {
+  return listen<FrameReadyPayload>("frame-ready", (event) => {
+    const { session_id, url, head } = event.payload;
+    let reader = frameRingReaders.get(session_id);
+    if (!reader) {
+      reader = { cursor: 0, head, reading: false };
+      frameRingReaders.set(session_id, reader);
+    }
+    reader.head = Math.max(reader.head, head);
+    if (!reader.reading) void readFrameRing(session_id, url, reader);
+  });
+}
+
+/** Fetch everything between the reader's cursor and the ring's head, one
+ *  request for however many frames that is. A reader that fell a whole
+ *  ring behind skips to the head and asks for a full redraw. */
+async function readFrameRing(sessionId: string, url: string, reader: FrameRingReader) {
+  reader.reading = true;
+  try {
+    while (reader.cursor < reader.head) {
+      const response = await fetch(`${url}?from=${reader.cursor}`);
+      if (response.status === 410) {
+        reader.cursor = reader.head;
+        await requestFullRedraw(sessionId);
+        continue;
+      }
+      if (!response.ok) {
+        frameRingReaders.delete(sessionId);
+        return;
+      }
+      const bytes = new Uint8Array(await response.arrayBuffer());
+      reader.cursor += bytes.byteLength;
+      const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+      for (let at = 0; at < bytes.byteLength; ) {
+        const len = view.getUint32(at, true);
+        const message = decode(bytes.subarray(at + 4, at + 4 + len)) as FrameChannelMessage;
+        at += 4 + len;
+        if (message.type === "session-ended") frameRingReaders.delete(sessionId);
+        dispatchFrameMessage(message);
+      }
+    }
+  } catch (e) {
+    console.error("[Rain] Failed to read frame ring:", e);
+  } finally {
+    reader.reading = false;
+  }
+}
+
+/** Have a session adopted from another window send its binary frames to
+ *  this one if it uses a frame channel (the old window's went with it),
+ *  and redraw it in full either way. */
+export async function attachFrameChannel(sessionId: string): Promise<void> {
+  return invoke("attach_frame_channel", { sessionId, frameChannel: createFrameChannel() });
+}
+
+/** A session idle at an empty prompt in `cwd`, limited to `among` if given. */
+export async function findIdleSession(cwd: string, among?: string[]): Promise<string | null> {
+  return invoke<string | null>("find_idle_session", { cwd, among });
+}
+
 export async function writeInput(sessionId: string, data: number[]): Promise<void> {
   return invoke("write_input", { sessionId, data });
 }
 
+/** Report a mouse event to the program, encoded for its mouse modes.
+ *  Resolves to false when the program doesn't track that kind of event. */
+export async function sendMouseEvent(sessionId: string, event: MouseReport): Promise<boolean> {
+  return invoke<boolean>("send_mouse_event", { sessionId, event });
+}
+
+/** Paste into the session. The backend strips control characters and escape
+ *  sequences and brackets the text when the program enabled mode 2004. */
+export async function pasteText(sessionId: string, text: string): Promise<void> {
+  return invoke("paste_text", { sessionId, text });
+}
+
+/** Rejection value of the input commands (`write_input`, `write_input_batched`,
+ *  `send_mouse_event`, `paste_text`). */
+export type InputError =
+  | { kind: "sessionNotFound"; sessionId: string }
+  | { kind: "readOnly"; sessionId: string }
+  | { kind: "write"; message: string };
+
+export function isReadOnlyError(error: unknown): error is Extract<InputError, { kind: "readOnly" }> {
+  return typeof error === "object" && error !== null && (error as { kind?: unknown }).kind === "readOnly";
+}
+
+/** Lock a session against input; writes fail with a `readOnly` InputError. */
+export async function setSessionReadOnly(sessionId: string, readOnly: boolean): Promise<void> {
+  return invoke("set_session_read_only", { sessionId, readOnly });
+}
+
+/** Run `command` at the next idle prompt (needs shell integration). */
+export async function queueCommand(sessionId: string, command: string): Promise<QueuedCommand> {
+  return invoke<QueuedCommand>("queue_command", { sessionId, command });
+}
+
+/** Resolves to false when the command already ran or was cancelled. */
+export async function cancelQueuedCommand(sessionId: string, id: string): Promise<boolean> {
+  return invoke<boolean>("cancel_queued_command", { sessionId, id });
+}
+
+/** Type Rain's hooks into the remote shell of a running ssh session. */
+export async function injectRemoteHooks(sessionId: string): Promise<void> {
+  return invoke("inject_remote_hooks", { sessionId });
+}
+
+export async function listQueuedCommands(sessionId: string): Promise<QueuedCommand[]> {
+  return invoke<QueuedCommand[]>("list_queued_commands", { sessionId });
+}
+
+/** A command running in some session, for the global jobs drawer. */
+export interface Job {
+  sessionId: string;
+  blockId: string;
+  command: string;
+  cwd: string;
+  /** Unix time in milliseconds. */
+  startedAt: number;
+  pid: number | null;
+  /** Percent of one core since the previous sample; null where unavailable. */
+  cpuPercent: number | null;
+}
+
+export async function listActiveJobs(): Promise<Job[]> {
+  return invoke<Job[]>("list_active_jobs");
+}
+
+/** Fires with the full list whenever jobs start, finish or change CPU usage. */
+export async function onJobsChanged(callback: (jobs: Job[]) => void): Promise<UnlistenFn> {
+  return listen<Job[]>("jobs-changed", (event) => {
+    callback(event.payload);
+  });
+}
+
+/** Several writes in one IPC call; see lib/inputBatcher.ts. */
+export async function writeInputBatched(sessionId: string, chunks: number[][]): Promise<void> {
+  return invoke("write_input_batched", { sessionId, chunks });
+}
+
 export async function resizeTerminal(
   sessionId: string,
   rows: number,
   cols: number,
+  cellWidth?: number,
+  cellHeight?: number,
 ): Promise<void> {
-  return invoke("resize_terminal", { sessionId, rows, cols });
+  return invoke("resize_terminal", { sessionId, rows, cols, cellWidth, cellHeight });
 }
 
 export async function destroySession(sessionId: string): Promise<void> {
   return invoke("destroy_session", { sessionId });
 }
 
+/** Start the shell of an ended session again, keeping its scrollback. */
+export async function restartSession(sessionId: string): Promise<void> {
+  return invoke("restart_session", { sessionId });
+}
+
+/** Move a stalled session onto a fresh parser and a blank, repainted screen. */
+export async function recoverSession(sessionId: string): Promise<void> {
+  return invoke("recover_session", { sessionId });
+}
+
+/** Where a new tab or split starts relative to the pane it was opened from. */
+export type NewSessionCwd = "inherit" | "original" | "home" | "projectRoot";
+
+export async function resolveNewSessionCwd(
+  sourceSessionId: string | null,
+  policy: NewSessionCwd,
+): Promise<string | null> {
+  return invoke("resolve_new_session_cwd", { sourceSessionId, policy });
+}
+
 export async function getBlockOutput(
   sessionId: string,
   startRow: number,
@@ -53,10 +278,323 @@ export async function getBlockOutput(
   return invoke<string>("get_block_output", { sessionId, startRow, endRow });
 }
 
+/** Normalization applied to copied text, computed from cell data. */
+export interface CopyOptions {
+  trimTrailingWhitespace?: boolean;
+  /** Drop prompt decorations (OSC 133 prompt regions), keeping the command. */
+  stripPrompts?: boolean;
+  /** Markdown fenced code block with commands as `$ command`. */
+  fenced?: boolean;
+}
+
+export async function getLineRangeOutput(
+  sessionId: string,
+  startLineId: number,
+  endLineId: number,
+  options?: CopyOptions,
+): Promise<string> {
+  return invoke<string>("get_line_range_output", { sessionId, startLineId, endLineId, options });
+}
+
+export async function getBlockText(
+  sessionId: string,
+  blockId: string,
+  includeCommand: boolean,
+  options: CopyOptions,
+): Promise<string> {
+  return invoke<string>("get_block_text", { sessionId, blockId, includeCommand, options });
+}
+
+/** Block as a Markdown snippet for docs; `maxOutputLines` 0 means no limit. */
+export async function copyBlockAsMarkdown(
+  sessionId: string,
+  blockId: string,
+  maxOutputLines: number,
+): Promise<string> {
+  return invoke<string>("copy_block_as_markdown", { sessionId, blockId, maxOutputLines });
+}
+
+/** Block output parsed as `format`, or as detected when omitted. */
+export async function getBlockStructured(
+  sessionId: string,
+  blockId: string,
+  format?: StructuredKind,
+): Promise<StructuredData> {
+  return invoke<StructuredData>("get_block_structured", { sessionId, blockId, format });
+}
+
+/** Search the active screen; matches then arrive in each frame until an empty pattern ends it. */
+export async function searchVisible(sessionId: string, pattern: string): Promise<CellRect[]> {
+  return invoke<CellRect[]>("search_visible", { sessionId, pattern });
+}
+
+export interface SearchOptions {
+  regex?: boolean;
+  caseSensitive?: boolean;
+}
+
+/** Search the session's scrollback and screen in the backend. Rejects with
+ *  "Invalid search pattern: ..." for a bad regex. */
+export async function searchScrollback(
+  sessionId: string,
+  query: string,
+  options?: SearchOptions,
+): Promise<ScrollbackMatch[]> {
+  return invoke<ScrollbackMatch[]>("search_scrollback", { sessionId, query, options });
+}
+
+/** Up to `count` scrollback rows from `startGlobalRow`, for loading history
+ *  the frontend never received in frames. */
+export async function getScrollbackRange(
+  sessionId: string,
+  startGlobalRow: number,
+  count: number,
+): Promise<ScrollbackPage> {
+  return invoke<ScrollbackPage>("get_scrollback_range", { sessionId, startGlobalRow, count });
+}
+
+export type TextSelectionMode = "char" | "word" | "line" | "block";
+
+/** A cell by the stable ID of its line. */
+export interface TextSelectionPoint {
+  lineId: number;
+  col: number;
+}
+
+/** A backend selection after word/line widening (inclusive columns). */
+export interface TextSelectionRange {
+  mode: TextSelectionMode;
+  start: TextSelectionPoint;
+  end: TextSelectionPoint;
+}
+
+/** Start a backend selection at `anchor`; null when the line is gone. */
+export async function setTextSelection(
+  sessionId: string,
+  mode: TextSelectionMode,
+  anchor: TextSelectionPoint,
+): Promise<TextSelectionRange | null> {
+  return invoke("set_selection", { sessionId, mode, anchor });
+}
+
+/** Move the free end of the backend selection to `head`. */
+export async function extendTextSelection(
+  sessionId: string,
+  head: TextSelectionPoint,
+): Promise<TextSelectionRange | null> {
+  return invoke("extend_selection", { sessionId, head });
+}
+
+export async function clearTextSelection(sessionId: string): Promise<void> {
+  return invoke("clear_selection", { sessionId });
+}
+
+/** The backend selection as plain text (default), ANSI or HTML; null when
+ *  there is none. */
+export async function getSelectionText(
+  sessionId: string,
+  format?: ExportFormat,
+): Promise<string | null> {
+  return invoke("get_selection_text", { sessionId, format });
+}
+
 export async function requestFullRedraw(sessionId: string): Promise<void> {
   return invoke("request_full_redraw", { sessionId });
 }
 
+export type ColorFilter = "none" | "deuteranopia" | "protanopia" | "high_contrast";
+
+/** Apply an accessibility color filter to every frame of a session. */
+export async function setColorFilter(sessionId: string, filter: ColorFilter): Promise<void> {
+  return invoke("set_color_filter", { sessionId, filter });
+}
+
+/** Keep text at `ratio` contrast against its background (1 turns it off). */
+export async function setMinContrast(sessionId: string, ratio: number): Promise<void> {
+  return invoke("set_min_contrast", { sessionId, ratio });
+}
+
+/** Draw bold text in ANSI colors 0-7 in their bright variants. */
+export async function setBoldIsBright(sessionId: string, bright: boolean): Promise<void> {
+  return invoke("set_bold_is_bright", { sessionId, bright });
+}
+
+/** A theme from `list_themes`, colors as `#rrggbb`. */
+export interface ThemeInfo {
+  name: string;
+  label: string;
+  builtin: boolean;
+  foreground: string;
+  background: string;
+  cursor: string;
+  ansi: string[];
+  /** `rain-asset` URL of the background image, set once the theme is active. */
+  backgroundImage: string | null;
+}
+
+/** Built-in themes and the ones in the config directory's `themes` folder. */
+export async function listThemes(): Promise<ThemeInfo[]> {
+  return invoke("list_themes");
+}
+
+/** Make a theme answer OSC color queries in every session. */
+export async function setTerminalTheme(name: string): Promise<ThemeInfo> {
+  return invoke("set_theme", { name });
+}
+
+/** Print East Asian ambiguous-width characters two columns wide (CJK) or one. */
+export async function setAmbiguousWidth(sessionId: string, wide: boolean): Promise<void> {
+  return invoke("set_ambiguous_width", { sessionId, wide });
+}
+
+/** Offer files programs send for download (`FileDownload`), or drop them. */
+export async function setFileDownloads(sessionId: string, enabled: boolean): Promise<void> {
+  return invoke("set_file_downloads", { sessionId, enabled });
+}
+
+/** Scrollback kept by a session after `set_scrollback_limit`. */
+export interface ScrollbackUsage {
+  rows: number;
+  limit: number;
+  /** Approximate memory held by the scrollback rows. */
+  bytes: number;
+  /** Memory budget of the scrollback, if any. */
+  max_bytes?: number;
+}
+
+/** Keep at most `lines` rows of scrollback, and at most `maxBytes` of memory
+ *  when given, trimming the oldest rows now. With `compress`, older
+ *  scrollback is kept compressed. */
+export async function setScrollbackLimit(
+  sessionId: string,
+  lines: number,
+  maxBytes?: number,
+  compress?: boolean,
+): Promise<ScrollbackUsage> {
+  return invoke<ScrollbackUsage>("set_scrollback_limit", { sessionId, lines, maxBytes, compress });
+}
+
+export type ShareService = { type: "gist"; public?: boolean } | { type: "paste"; endpoint: string };
+export type ShareFormat = "ansi" | "html";
+
+/** Upload a command block to a gist or paste service; resolves to its URL.
+ *  Tokens are read from the credential store (`share.gist-token`,
+ *  `share.paste-token`). */
+export async function shareBlock(
+  sessionId: string,
+  blockId: string,
+  service: ShareService,
+  format: ShareFormat,
+): Promise<string> {
+  return invoke("share_block", { sessionId, blockId, service, format });
+}
+
+export type ExportFormat = "ansi" | "text" | "html";
+export type ExportRange =
+  | { type: "lines"; startLineId: number; endLineId: number }
+  | { type: "block"; blockId: string };
+
+/** Save main-grid rows to a file the user picks, keeping colors for ANSI and
+ *  HTML. Resolves to false when the dialog is cancelled. */
+export async function exportOutput(
+  sessionId: string,
+  range: ExportRange,
+  format: ExportFormat,
+): Promise<boolean> {
+  return invoke("export_output", { sessionId, range, format });
+}
+
+/** URL of main-grid rows rendered as an HTML page, valid until the session
+ *  is destroyed. */
+export async function previewExport(sessionId: string, range: ExportRange): Promise<string> {
+  return invoke("preview_export", { sessionId, range });
+}
+
+export interface EventSchema {
+  version: number;
+  terminalEvents: string[];
+  tmuxEvents: string[];
+}
+
+/** Event types and schema version the backend emits. */
+export async function getEventSchema(): Promise<EventSchema> {
+  return invoke<EventSchema>("get_event_schema");
+}
+
+export interface SelftestResult {
+  feature: string;
+  passed: boolean;
+  detail: string | null;
+}
+
+/** Run the built-in escape-sequence self-test; `render` also prints the
+ *  test pattern and results into the session. */
+export async function runTerminalSelftest(
+  sessionId: string,
+  render: boolean,
+): Promise<SelftestResult[]> {
+  return invoke<SelftestResult[]>("run_terminal_selftest", { sessionId, render });
+}
+
+export interface HibernationConfig {
+  enabled: boolean;
+  idleThresholdSecs: number;
+}
+
+export async function setCustomOscCodes(codes: number[]): Promise<void> {
+  return invoke("set_custom_osc_codes", { codes });
+}
+
+export interface DeviceAttributes {
+  conformance: "default" | "vt220" | "vt420" | "vt520";
+  extraFeatures: number[];
+  hiddenFeatures: number[];
+}
+
+export async function setDeviceAttributes(attributes: DeviceAttributes): Promise<void> {
+  return invoke("set_device_attributes", { attributes });
+}
+
+export interface ShellArgTemplate {
+  /** Shell name as detected from its path ("fish", "nu", "pwsh"). */
+  shell: string;
+  /** Only apply on "macos", "linux" or "windows". */
+  platform?: string | null;
+  /** Start as a login shell; unset keeps Rain's default. */
+  login?: boolean | null;
+  args: string[];
+}
+
+export async function setShellArgs(templates: ShellArgTemplate[]): Promise<void> {
+  return invoke("set_shell_args", { templates });
+}
+
+export async function setWarmPoolEnabled(enabled: boolean): Promise<void> {
+  return invoke("set_warm_pool_enabled", { enabled });
+}
+
+export async function hibernateSession(sessionId: string): Promise<number> {
+  return invoke<number>("hibernate_session", { sessionId });
+}
+
+export async function setHibernationConfig(config: HibernationConfig): Promise<void> {
+  return invoke("set_hibernation_config", { config });
+}
+
+export async function transferTextBetweenSessions(
+  sourceSessionId: string,
+  targetSessionId: string,
+  text: string,
+  asPaste: boolean,
+): Promise<void> {
+  return invoke("transfer_text_between_sessions", {
+    sourceSessionId,
+    targetSessionId,
+    text,
+    asPaste,
+  });
+}
+
 // Window appearance
 
 export async function setWindowBlurRadius(radius: number): Promise<void> {
@@ -67,6 +605,27 @@ export async function setWindowOpacity(opacity: number): Promise<void> {
   return invoke("set_window_opacity", { opacity: Math.max(0, Math.min(1, opacity)) });
 }
 
+export interface CompositorCapabilities {
+  displayServer: "macos" | "windows" | "wayland" | "x11";
+  compositor: string | null;
+  composited: boolean;
+  transparency: boolean;
+  blur: boolean;
+  advice: string | null;
+}
+
+export async function getCompositorCapabilities(): Promise<CompositorCapabilities> {
+  return invoke<CompositorCapabilities>("get_compositor_capabilities");
+}
+
+export async function onCompositorCapabilitiesChanged(
+  callback: (caps: CompositorCapabilities) => void,
+): Promise<UnlistenFn> {
+  return listen<CompositorCapabilities>("compositor-capabilities-changed", (event) => {
+    callback(event.payload);
+  });
+}
+
 export async function getHostname(): Promise<string> {
   return invoke<string>("get_hostname");
 }
@@ -139,6 +698,20 @@ export interface TabTransferCommitResult {
   manifest: TabTransferManifest | null;
 }
 
+export type TabTransferStage =
+  | "staged"
+  | "prepared"
+  | "released"
+  | "extended"
+  | "committed"
+  | "expired";
+
+export interface TabTransferProgress {
+  transfer_id: string;
+  stage: TabTransferStage;
+  expires_at_ms: number | null;
+}
+
 export async function listRainWindows(): Promise<WindowBounds[]> {
   return invoke<WindowBounds[]>("list_rain_windows");
 }
@@ -167,8 +740,24 @@ export async function takeSessionTransferState(
 export async function stageTabTransferManifest(
   transferId: string,
   manifest: TabTransferManifest,
+  ttlMs?: number,
 ): Promise<void> {
-  return invoke("stage_tab_transfer_manifest", { transferId, manifest });
+  return invoke("stage_tab_transfer_manifest", { transferId, manifest, ttlMs: ttlMs ?? null });
+}
+
+export async function extendTabTransfer(
+  transferId: string,
+  ttlMs?: number,
+): Promise<number | null> {
+  return invoke<number | null>("extend_tab_transfer", { transferId, ttlMs: ttlMs ?? null });
+}
+
+export async function onTabTransferProgress(
+  callback: (progress: TabTransferProgress) => void,
+): Promise<UnlistenFn> {
+  return listen<TabTransferProgress>("tab-transfer-progress", (event) => {
+    callback(event.payload);
+  });
 }
 
 export async function takeTabTransferManifest(
@@ -290,6 +879,66 @@ export async function loadWorkspace(): Promise<string | null> {
   return invoke<string | null>("load_workspace");
 }
 
+// --- secrets (OS credential store) ---
+
+export async function storeSecret(key: string, secret: string): Promise<void> {
+  return invoke("store_secret", { key, secret });
+}
+
+export async function getSecret(key: string): Promise<string | null> {
+  return invoke<string | null>("get_secret", { key });
+}
+
+export async function deleteSecret(key: string): Promise<void> {
+  return invoke("delete_secret", { key });
+}
+
+// --- primary selection (Linux middle-click paste) ---
+
+export async function setPrimarySelection(text: string): Promise<void> {
+  return invoke("set_primary_selection", { text });
+}
+
+export async function pastePrimary(sessionId: string): Promise<boolean> {
+  return invoke<boolean>("paste_primary", { sessionId });
+}
+
+// --- clipboard history ---
+
+export interface ClipboardEntry {
+  id: number;
+  text: string;
+  source: "osc52" | "copy";
+  copiedAt: number;
+  pinned: boolean;
+}
+
+/** Copy text and add it to the backend's clipboard history. */
+export async function copyText(text: string): Promise<void> {
+  await navigator.clipboard.writeText(text);
+  invoke("record_clipboard_copy", { text }).catch(console.error);
+}
+
+export async function listClipboardHistory(): Promise<ClipboardEntry[]> {
+  return invoke<ClipboardEntry[]>("list_clipboard_history");
+}
+
+export async function pinClipboardEntry(id: number, pinned: boolean): Promise<void> {
+  return invoke("pin_clipboard_entry", { id, pinned });
+}
+
+export async function recopyClipboardEntry(id: number): Promise<void> {
+  return invoke("recopy_clipboard_entry", { id });
+}
+
+export async function removeClipboardEntry(id: number): Promise<void> {
+  return invoke("remove_clipboard_entry", { id });
+}
+
+export async function clearClipboardHistory(): Promise<void> {
+  return invoke("clear_clipboard_history");
+}
+
 // --- config file ---
 
 export async function readConfigFile(): Promise<string | null> {
@@ -310,12 +959,178 @@ export async function registerGlobalHotkey(accelerator: string): Promise<void> {
   return invoke("register_global_hotkey", { accelerator });
 }
 
+export interface HotkeyCapabilities {
+  backend: "native" | "portal" | "none";
+  supported: boolean;
+  reason: string | null;
+  /** Trigger the shortcuts portal actually bound (the user may change it). */
+  boundTrigger: string | null;
+}
+
+export async function getGlobalHotkeyCapabilities(): Promise<HotkeyCapabilities> {
+  return invoke<HotkeyCapabilities>("get_global_hotkey_capabilities");
+}
+
+export async function onGlobalHotkeyStatus(
+  callback: (status: HotkeyCapabilities) => void,
+): Promise<UnlistenFn> {
+  return listen<HotkeyCapabilities>("global-hotkey-status", (event) => {
+    callback(event.payload);
+  });
+}
+
+// --- notifications ---
+
+/** Desktop notification from OSC 9/777; clicking it focuses this window. */
+export async function showNotification(sessionId: string, title: string, body: string): Promise<void> {
+  return invoke("show_notification", { sessionId, title, body });
+}
+
+/** A notification shown for `sessionId` was clicked. */
+export async function onNotificationClicked(
+  callback: (sessionId: string) => void,
+): Promise<UnlistenFn> {
+  return listen<string>("notification-clicked", (event) => {
+    callback(event.payload);
+  });
+}
+
+// --- window progress ---
+
+/** Progress on this window's dock icon or taskbar button; "none" hides it. */
+export async function setWindowProgress(state: ProgressState, value: number): Promise<void> {
+  return invoke("set_window_progress", { state, value });
+}
+
+// --- tray ---
+
+export async function setTrayEnabled(enabled: boolean): Promise<void> {
+  return invoke("set_tray_enabled", { enabled });
+}
+
+/** Tray session item picked; the window holding `sessionId` should come forward. */
+export async function onTrayFocusSession(
+  callback: (sessionId: string) => void,
+): Promise<UnlistenFn> {
+  return listen<string>("tray-focus-session", (event) => {
+    callback(event.payload);
+  });
+}
+
+// --- open Rain here ---
+
+/** Directories queued by the file manager or a second `rain` launch. */
+export async function takePendingOpenDirectories(): Promise<string[]> {
+  return invoke<string[]>("take_pending_open_directories");
+}
+
+export async function onOpenDirectoryRequested(callback: () => void): Promise<UnlistenFn> {
+  return listen("open-directory-requested", () => callback());
+}
+
+/** A validated `rain://` link; `run` links were confirmed by the user. */
+export type DeepLink =
+  | { action: "open"; path: string }
+  | { action: "run"; command: string; cwd: string | null }
+  | { action: "tmux"; sessionName: string };
+
+export async function takePendingDeepLinks(): Promise<DeepLink[]> {
+  return invoke<DeepLink[]>("take_pending_deep_links");
+}
+
+export async function onDeepLinkRequested(callback: () => void): Promise<UnlistenFn> {
+  return listen("deep-link-requested", () => callback());
+}
+
+/** Windows only: add or remove the Explorer "Open Rain here" entry. */
+export async function setExplorerIntegration(enabled: boolean): Promise<void> {
+  return invoke("set_explorer_integration", { enabled });
+}
+
+// --- context menu ---
+
+export type ContextMenuAction =
+  | "copy"
+  | "paste"
+  | "select-all"
+  | "search-selection"
+  | "open-link"
+  | "copy-link"
+  | "copy-block-output"
+  | "copy-block-markdown"
+  | "share-block"
+  | "save-block"
+  | "preview-block"
+  | "split-right"
+  | "split-down"
+  | "tmux-detach"
+  | "clear"
+  | "export";
+
+export interface ContextMenuContext {
+  hasSelection: boolean;
+  linkUrl?: string;
+  blockId?: string;
+}
+
+export interface ContextMenuActionPayload {
+  sessionId: string;
+  action: ContextMenuAction;
+  linkUrl: string | null;
+  blockId: string | null;
+}
+
+/** Pop up the native terminal menu; the pick arrives via `onTerminalContextAction`. */
+export async function showTerminalContextMenu(
+  sessionId: string,
+  x: number,
+  y: number,
+  context: ContextMenuContext,
+): Promise<void> {
+  return invoke("show_terminal_context_menu", { sessionId, x, y, context });
+}
+
+export async function onTerminalContextAction(
+  callback: (payload: ContextMenuActionPayload) => void,
+): Promise<UnlistenFn> {
+  return listen<ContextMenuActionPayload>("terminal-context-action", (event) => {
+    callback(event.payload);
+  });
+}
+
 // --- scrollback export ---
 
 export async function saveTextToFile(content: string, defaultName: string): Promise<boolean> {
   return invoke<boolean>("save_text_to_file", { content, defaultName });
 }
 
+/** Save a file offered by a `FileDownload` event; false when cancelled. */
+export async function saveDownload(sessionId: string, downloadId: string): Promise<boolean> {
+  return invoke<boolean>("save_download", { sessionId, downloadId });
+}
+
+export async function discardDownload(sessionId: string, downloadId: string): Promise<void> {
+  return invoke("discard_download", { sessionId, downloadId });
+}
+
+// --- app state export ---
+
+/** Write a sanitized archive of all settings; `frontend` is this webview's
+ *  settings storage. Resolves to the path written, or null if cancelled. */
+export async function exportAppState(
+  frontend: Record<string, string>,
+  includeScrollback: boolean,
+  path?: string,
+): Promise<string | null> {
+  return invoke<string | null>("export_app_state", { path: path ?? null, frontend, includeScrollback });
+}
+
+/** Restore an archive. The backend writes its own files and returns the
+ *  frontend storage entries to apply; null if the dialog was cancelled. */
+export async function importAppState(path?: string): Promise<Record<string, string> | null> {
+  return invoke<Record<string, string> | null>("import_app_state", { path: path ?? null });
+}
+
 // --- filesystem ---
 
 export interface DirEntry {
@@ -361,14 +1176,90 @@ export async function getAppVersion(): Promise<string> {
   return invoke<string>("get_app_version");
 }
 
+export interface StartupMode {
+  safeMode: boolean;
+  consecutiveCrashes: number;
+  /** Requested with the RAIN_SAFE_MODE environment variable. */
+  forced: boolean;
+}
+
+export async function getStartupMode(): Promise<StartupMode> {
+  return invoke<StartupMode>("get_startup_mode");
+}
+
+// --- updates ---
+
+export interface ReleaseInfo {
+  version: string;
+  currentVersion: string;
+  notes: string | null;
+  date: string | null;
+  size: number | null;
+}
+
+export interface UpdateDownloadProgress {
+  version: string;
+  downloaded: number;
+  total: number | null;
+}
+
+export type InstallOutcome =
+  | { status: "installing" }
+  | { status: "deferred"; busySessions: number };
+
+/** Check the signed update feed. Rejects when this build has no updater key. */
+export async function checkForAppUpdate(): Promise<ReleaseInfo | null> {
+  return invoke<ReleaseInfo | null>("check_for_updates");
+}
+
+/** Install the downloaded update; `force` restarts even with busy sessions. */
+export async function installUpdate(force: boolean): Promise<InstallOutcome> {
+  return invoke<InstallOutcome>("install_update", { force });
+}
+
+/** Notes for the update installed before this launch (returned once). */
+export async function takeUpdateChangelog(): Promise<ReleaseInfo | null> {
+  return invoke<ReleaseInfo | null>("take_update_changelog");
+}
+
+export async function onUpdateDownloadProgress(
+  callback: (progress: UpdateDownloadProgress) => void,
+): Promise<UnlistenFn> {
+  return listen<UpdateDownloadProgress>("update-download-progress", (event) =>
+    callback(event.payload),
+  );
+}
+
+export async function onUpdateReady(callback: (release: ReleaseInfo) => void): Promise<UnlistenFn> {
+  return listen<ReleaseInfo>("update-ready", (event) => callback(event.payload));
+}
+
+export async function onUpdateError(callback: (message: string) => void): Promise<UnlistenFn> {
+  return listen<string>("update-error", (event) => callback(event.payload));
+}
+
 // Event listeners
 
+export async function ackFrame(sessionId: string, frameSeq: number): Promise<void> {
+  return invoke("ack_frame", { sessionId, frameSeq });
+}
+
+/** Listeners of `onRenderFrame`, also fed the binary frames of sessions
+ *  created with `binaryFrames`. */
+const renderFrameHandlers = new Set<(payload: RenderFramePayload) => void>();
+
 export async function onRenderFrame(
   callback: (payload: RenderFramePayload) => void,
 ): Promise<UnlistenFn> {
-  return listen<RenderFramePayload>("render-frame", (event) => {
+  const unlisten = await listen<RenderFramePayload>("render-frame", (event) => {
+    expandSpanStyles(event.payload.frame);
     callback(event.payload);
   });
+  renderFrameHandlers.add(callback);
+  return () => {
+    renderFrameHandlers.delete(callback);
+    unlisten();
+  };
 }
 
 export async function onSessionCreated(
@@ -379,10 +1270,27 @@ export async function onSessionCreated(
   });
 }
 
+/** Listeners of `onSessionEnded`, also told about sessions whose end
+ *  comes over their frame channel. */
+const sessionEndedHandlers = new Set<(payload: SessionEndPayload) => void>();
+
 export async function onSessionEnded(
   callback: (payload: SessionEndPayload) => void,
 ): Promise<UnlistenFn> {
-  return listen<SessionEndPayload>("session-ended", (event) => {
+  const unlisten = await listen<SessionEndPayload>("session-ended", (event) => {
+    callback(event.payload);
+  });
+  sessionEndedHandlers.add(callback);
+  return () => {
+    sessionEndedHandlers.delete(callback);
+    unlisten();
+  };
+}
+
+export async function onSessionStalled(
+  callback: (payload: SessionStalledPayload) => void,
+): Promise<UnlistenFn> {
+  return listen<SessionStalledPayload>("session-stalled", (event) => {
     callback(event.payload);
   });
 }
@@ -394,3 +1302,96 @@ export async function onResizeAck(
     callback(event.payload);
   });
 }
+
+/** Report the main-grid line at the top of the viewport as it scrolls. */
+export async function setViewportTop(sessionId: string, lineId: number): Promise<void> {
+  return invoke("set_viewport_top", { sessionId, lineId });
+}
+
+/** Fired when the viewport top moves into a different block. */
+export async function onViewportBlockChanged(
+  callback: (payload: ViewportBlockPayload) => void,
+): Promise<UnlistenFn> {
+  return listen<ViewportBlockPayload>("viewport-block-changed", (event) => {
+    callback(event.payload);
+  });
+}
+
+/** The session with keyboard focus in this window changed; drives focus
+ *  in/out reports (mode 1004) together with native window focus. */
+export async function setSessionFocus(sessionId: string, focused: boolean): Promise<void> {
+  return invoke("set_session_focus", { sessionId, focused });
+}
+
+/** Whether a session is on screen; hidden ones render at a few frames per second. */
+export async function setSessionVisible(sessionId: string, visible: boolean): Promise<void> {
+  return invoke("set_session_visible", { sessionId, visible });
+}
+
+/** What is typed at the prompt; null while a command runs. */
+export async function getCurrentCommandLine(sessionId: string): Promise<CommandLine | null> {
+  return invoke<CommandLine | null>("get_current_command_line", { sessionId });
+}
+
+/** The process in the foreground of a session's PTY. */
+export interface ForegroundProcess {
+  pid: number;
+  /** Executable name, e.g. `vim`. */
+  name: string;
+  argv: string[];
+  /** The session's shell itself, so nothing is running. */
+  is_shell: boolean;
+}
+
+/** `null` where the OS can't tell. */
+export async function getForegroundProcess(sessionId: string): Promise<ForegroundProcess | null> {
+  return invoke<ForegroundProcess | null>("get_foreground_process", { sessionId });
+}
+
+/** A process running under a session's shell. */
+export interface RunningProcess {
+  pid: number;
+  name: string;
+  argv: string[];
+}
+
+/** What still runs under a session's shell; empty when it is idle. */
+export async function sessionHasRunningChildren(sessionId: string): Promise<RunningProcess[]> {
+  return invoke<RunningProcess[]>("session_has_running_children", { sessionId });
+}
+
+/** Signals `sendSignal` can deliver. Windows only supports `interrupt`. */
+export type JobSignal = "interrupt" | "terminate" | "hangup" | "kill";
+
+/** Deliver `signal` to the foreground job of a session's PTY. */
+export async function sendSignal(sessionId: string, signal: JobSignal): Promise<void> {
+  return invoke("send_signal", { sessionId, signal });
+}
+
+export async function getSessionIoStats(sessionId: string): Promise<SessionIoStats> {
+  return invoke<SessionIoStats>("get_session_io_stats", { sessionId });
+}
+
+/** At most once a second per session while bytes move, and once when they stop. */
+export async function onSessionIo(
+  callback: (payload: SessionIoPayload) => void,
+): Promise<UnlistenFn> {
+  return listen<SessionIoPayload>("session-io", (event) => {
+    callback(event.payload);
+  });
+}
+
+// --- test automation (debug / `automation` feature builds only) ---
+
+export interface BackendResetReport {
+  sessions: number;
+  tmuxControllers: number;
+  sessionTransfers: number;
+  tabTransfers: number;
+  ghostWindows: number;
+  warmShells: number;
+}
+
+export async function resetBackendState(): Promise<BackendResetReport> {
+  return invoke<BackendResetReport>("reset_backend_state");
+}
@@ -0,0 +1,42 @@
+import { homeDir } from "@tauri-apps/api/path";
+import type { PathLink, StyledSpan } from "./types";
+
+/** Link the backend found on a span: a URL or a file path. */
+export type SpanLink = { url: string } | { path: PathLink };
+
+export function spanLink(span: Pick<StyledSpan, "url" | "path">): SpanLink | undefined {
+  if (span.url) return { url: span.url };
+  if (span.path) return { path: span.path };
+  return undefined;
+}
+
+/** "src/main.rs:12:5", as the path was printed. */
+export function pathLinkLabel(link: PathLink): string {
+  let label = link.path;
+  if (link.line !== undefined) label += `:${link.line}`;
+  if (link.col !== undefined) label += `:${link.col}`;
+  return label;
+}
+
+/** Absolute form of a printed path, relative to the session's cwd. */
+export async function resolvePathLink(link: PathLink, cwd: string): Promise<string> {
+  const path = link.path;
+  if (path.startsWith("/")) return path;
+  if (path === "~" || path.startsWith("~/")) {
+    const home = (await homeDir()).replace(/\/$/, "");
+    return home + path.slice(1);
+  }
+  const base = cwd.replace(/\/$/, "");
+  return `${base}/${path.replace(/^\.\//, "")}`;
+}
+
+/** Open a link in the system browser or the file's default application. */
+export async function openSpanLink(link: SpanLink, cwd: string): Promise<void> {
+  if ("url" in link) {
+    // Tauri intercepts window.open and hands the URL to the system browser.
+    window.open(link.url, "_blank");
+    return;
+  }
+  const path = await resolvePathLink(link.path, cwd);
+  window.open(`file://${encodeURI(path)}`, "_blank");
+}
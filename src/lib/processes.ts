@@ -0,0 +1,23 @@
+import type { RunningProcess } from "./ipc";
+
+/** Most processes named in a summary; the rest are counted. */
+const MAX_NAMED = 3;
+
+/** A process as the user typed it: the program and its first arguments,
+ *  e.g. `npm run dev`. */
+export function processLabel(process: RunningProcess): string {
+  const [program, ...args] = process.argv;
+  if (!program) return process.name;
+  const base = program.split(/[\\/]/).pop() || process.name;
+  return [base, ...args.slice(0, 2)].join(" ");
+}
+
+/** "ssh and npm run dev are still running", for a close confirmation. */
+export function describeRunning(processes: RunningProcess[]): string {
+  const labels = processes.slice(0, MAX_NAMED).map(processLabel);
+  const more = processes.length - labels.length;
+  if (more > 0) labels.push(`${more} more`);
+  const list =
+    labels.length === 1 ? labels[0] : `${labels.slice(0, -1).join(", ")} and ${labels[labels.length - 1]}`;
+  return `${list} ${processes.length === 1 ? "is" : "are"} still running`;
+}
@@ -7,6 +7,8 @@ export interface ShellProfile {
   shell?: string;
   cwd?: string;
   env?: Record<string, string>;
+  /** Command line run instead of the shell; the tab closes when it exits. */
+  command?: string;
   icon?: string;
   color?: string;
 }
@@ -39,9 +41,46 @@ export function normalizeProfile(profile: ShellProfile): ShellProfile {
     shell: profile.shell?.trim() || undefined,
     cwd: profile.cwd?.trim() || undefined,
     env: env && Object.keys(env).length > 0 ? env : undefined,
+    command: profile.command?.trim() || undefined,
   };
 }
 
+/** Split a command line into arguments the way a POSIX shell would for
+ *  plain words: whitespace separates, quotes group and backslashes escape.
+ *  Expansions and operators are not interpreted. */
+export function parseCommandLine(line: string): string[] {
+  const args: string[] = [];
+  let current = "";
+  let inWord = false;
+  let quote: "'" | '"' | null = null;
+  for (let i = 0; i < line.length; i++) {
+    const ch = line[i];
+    if (quote === "'") {
+      if (ch === "'") quote = null;
+      else current += ch;
+    } else if (quote === '"') {
+      if (ch === '"') quote = null;
+      else if (ch === "\\" && i + 1 < line.length && '"\\$`'.includes(line[i + 1])) current += line[++i];
+      else current += ch;
+    } else if (ch === "'" || ch === '"') {
+      quote = ch;
+      inWord = true;
+    } else if (ch === "\\" && i + 1 < line.length) {
+      current += line[++i];
+      inWord = true;
+    } else if (/\s/.test(ch)) {
+      if (inWord) args.push(current);
+      current = "";
+      inWord = false;
+    } else {
+      current += ch;
+      inWord = true;
+    }
+  }
+  if (inWord) args.push(current);
+  return args;
+}
+
 export function ensureDefaultProfile(profiles: ShellProfile[]): ShellProfile[] {
   if (profiles.some((profile) => profile.id === "default")) {
     return profiles.map(normalizeProfile);
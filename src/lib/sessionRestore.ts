@@ -30,6 +30,8 @@ export interface SavedWorkspace {
   tabs: SavedTab[];
   activeTabIndex: number;
   savedAt: number;
+  /** Set when the backend rebuilt this from its crash journal. */
+  recoveredSessions?: number;
 }
 
 type PaneTreeLike =
@@ -185,6 +187,9 @@ export async function restoreWorkspace(): Promise<SavedWorkspace | null> {
         normalizedTabs.length - 1,
       ),
       savedAt: parsed.savedAt,
+      recoveredSessions: typeof parsed.recoveredSessions === "number"
+        ? parsed.recoveredSessions
+        : undefined,
     };
   } catch (e) {
     console.warn("[Rain] Failed to load workspace:", e);
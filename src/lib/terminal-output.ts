@@ -3,6 +3,8 @@ import type { RenderedLine } from "./types";
 function copyLine(line: RenderedLine, index: number): RenderedLine {
   return {
     index,
+    line_id: line.line_id,
+    line_size: line.line_size,
     spans: line.spans.map((sp) => ({ ...sp })),
   };
 }
@@ -32,6 +34,34 @@ function bsearchScrollback(lines: RenderedLine[], targetIndex: number): Rendered
   return null;
 }
 
+/**
+ * Resolve a backend line ID to the global row it is currently stored under.
+ * Block boundaries are recorded as line IDs so they don't drift when resizes
+ * or resets renumber global rows. Returns null once the line is gone.
+ */
+export function findGlobalRowForLineId(
+  scrollbackLines: RenderedLine[],
+  visibleLinesByGlobal: Record<number, RenderedLine>,
+  lineId: number | undefined,
+): number | null {
+  if (lineId === undefined || lineId <= 0) return null;
+
+  // Visible history can hold the same line under an older key; the newest wins.
+  let found: number | null = null;
+  for (const key of Object.keys(visibleLinesByGlobal)) {
+    const global = Number(key);
+    if (visibleLinesByGlobal[global]?.line_id === lineId && (found === null || global > found)) {
+      found = global;
+    }
+  }
+  if (found !== null) return found;
+
+  for (let i = scrollbackLines.length - 1; i >= 0; i--) {
+    if (scrollbackLines[i].line_id === lineId) return scrollbackLines[i].index;
+  }
+  return null;
+}
+
 export function collectLinesForRange(
   scrollbackLines: RenderedLine[],
   visibleLinesByGlobal: Record<number, RenderedLine>,
@@ -6,6 +6,8 @@ export interface RenderFramePayload {
 }
 
 export interface RenderFrame {
+  /** Backend event schema version (see lib/eventSchema.ts). */
+  event_schema?: number;
   frame_seq: number;
   resize_epoch: number;
   lines: RenderedLine[];
@@ -15,13 +17,62 @@ export interface RenderFrame {
   visible_cols: number;
   cursor: CursorRender;
   events: TerminalEvent[];
+  /** Full viewport resend after the frontend fell behind on acks. */
+  keyframe: boolean;
+  /** Kitty keyboard enhancement flags of the active screen. */
+  keyboard_flags?: number;
+  /** DECSCNM: swap the default foreground and background. */
+  reverse_video?: boolean;
+  /** Matches of the active `search_visible` pattern, when lines changed. */
+  visible_search?: CellRect[];
+  /** Style table of the frame as sent; spans name theirs by index in
+   *  `style`. `expandSpanStyles` resolves them before frames reach handlers. */
+  styles?: SpanStyle[];
+}
+
+/** Cells `col..col + cols` of screen row `row`. */
+export interface CellRect {
+  row: number;
+  col: number;
+  cols: number;
+}
+
+/** A `search_scrollback` match: cells `col..col + cols` of a main-grid row. */
+export interface ScrollbackMatch {
+  global_row: number;
+  line_id: number;
+  col: number;
+  cols: number;
+  /** Text of the whole row, trailing blanks trimmed. */
+  context: string;
+}
+
+/** A `get_scrollback_range` page; `lines[i]` is global row
+ *  `start_global_row + i`. */
+export interface ScrollbackPage {
+  start_global_row: number;
+  /** Oldest row the backend still holds. */
+  first_global_row: number;
+  visible_base_global: number;
+  lines: RenderedLine[];
 }
 
 export interface RenderedLine {
   index: number;
+  /** Stable backend line identity; survives scroll, resize and reset. */
+  line_id?: number;
+  /** DEC double-width/double-height line attribute; absent for normal lines. */
+  line_size?: LineSize;
+  /** Set on partial updates: `spans` replace the cells from this column on
+   *  and the rest of the line is unchanged. */
+  start_col?: number;
   spans: StyledSpan[];
 }
 
+export type LineSize = "double_width" | "double_height_top" | "double_height_bottom";
+
+export type UnderlineStyle = "single" | "double" | "curly" | "dotted" | "dashed";
+
 export interface StyledSpan {
   text: string;
   cols: number;
@@ -31,12 +82,32 @@ export interface StyledSpan {
   dim: boolean;
   italic: boolean;
   underline: boolean;
+  /** Underline variant (SGR 4:x); absent for plain underlines. */
+  underline_style?: UnderlineStyle;
+  /** Underline color (SGR 58); absent when the underline follows `fg`. */
+  underline_color?: SerializableColor;
   strikethrough: boolean;
+  /** Blink rate (SGR 5 slow, SGR 6 rapid); absent for steady text. */
+  blink?: "slow" | "rapid";
+  /** OSC 8 hyperlink, or a URL the backend found in the text. */
   url?: string;
+  /** File path the backend found in the text. */
+  path?: PathLink;
+}
+
+/** The look of a span, shared through a frame's style table. */
+export type SpanStyle = Omit<StyledSpan, "text" | "cols" | "url" | "path">;
+
+/** A file path in output, with the `:line:col` printed after it. */
+export interface PathLink {
+  path: string;
+  line?: number;
+  col?: number;
 }
 
 export interface SearchMatch {
   globalRow: number;
+  lineId?: number;
   startCol: number;
   endCol: number;
 }
@@ -54,14 +125,16 @@ export interface CursorRender {
 }
 
 export type TerminalEvent =
-  | { type: "BlockStarted"; id: string; cwd: string; global_row: number }
-  | { type: "BlockCommand"; id: string; command: string; global_row: number }
-  | { type: "BlockCompleted"; id: string; exit_code: number; global_row: number }
+  | { type: "BlockStarted"; id: string; cwd: string; global_row: number; line_id: number; git?: GitRef }
+  | { type: "BlockCommand"; id: string; command: string; global_row: number; line_id: number }
+  | { type: "BlockCompleted"; id: string; exit_code: number; global_row: number; line_id: number }
   | { type: "TitleChanged"; title: string }
   | { type: "AltScreenEntered" }
   | { type: "AltScreenExited" }
   | { type: "Bell" }
   | { type: "CwdChanged"; path: string }
+  | { type: "SessionNameChanged"; name: string | null }
+  | { type: "CustomOsc"; code: number; payload: string }
   | {
       type: "MouseModeChanged";
       tracking: boolean;
@@ -74,21 +147,107 @@ export type TerminalEvent =
       synchronized_output: boolean;
       bracketed_paste: boolean;
       cursor_keys_application: boolean;
+      modify_other_keys: number;
     }
   | { type: "ScrollbackCleared" }
-  | { type: "InlineImage"; id: string; data_base64: string; width: number; height: number; row: number; col: number }
-  | { type: "SixelImage"; id: string; data_base64: string; width: number; height: number; row: number; col: number }
-  | { type: "KittyImage"; id: string; action: string; data_base64: string; width: number; height: number; row: number; col: number; image_id: number; placement_id: number }
-  | { type: "TmuxRequested"; args: string };
+  | { type: "ScrollbackRewrapped" }
+  | { type: "InlineImage"; id: string; data_base64: string; url: string | null; name: string | null; width: number; height: number; columns: number; rows: number; preserve_aspect_ratio: boolean; row: number; col: number; line_id: number }
+  | { type: "FileDownload"; id: string; name: string | null; size: number }
+  | { type: "SixelImage"; id: string; data_base64: string; url: string | null; width: number; height: number; columns: number; rows: number; row: number; col: number; line_id: number }
+  | { type: "KittyImage"; id: string; action: string; data_base64: string; url: string | null; width: number; height: number; row: number; col: number; line_id: number; image_id: number; placement_id: number }
+  | { type: "TmuxRequested"; args: string }
+  | { type: "CommandQueueChanged"; queued: QueuedCommand[] }
+  | { type: "RemotePromptDetected"; block_id: string; host: string }
+  | { type: "ColumnModeChanged"; cols: number }
+  | { type: "BlockStructuredData"; id: string; kind: StructuredKind; sample: string }
+  | { type: "PaletteChanged"; colors: PaletteEntry[] }
+  | ({ type: "DynamicColorsChanged" } & DynamicColors)
+  | { type: "Notification"; title: string | null; body: string }
+  | ({ type: "ProgressChanged" } & Progress)
+  | ({ type: "TerminalCapabilitiesChanged" } & TerminalCapabilities)
+  | { type: "SelectionCleared" };
+
+/** What the running command can use under its TERM; `degraded` lists what it lost. */
+export interface TerminalCapabilities {
+  term: string | null;
+  colors: number;
+  hyperlinks: boolean;
+  images: boolean;
+  mouse: boolean;
+  degraded: string[];
+}
+
+/** Progress reported with OSC 9;4; `value` is a percentage. */
+export type ProgressState = "none" | "normal" | "error" | "indeterminate" | "paused";
+
+export interface Progress {
+  state: ProgressState;
+  value: number;
+}
+
+/** Default colors set by OSC 10/11/12; null follows the theme. */
+export interface DynamicColors {
+  foreground: SerializableColor | null;
+  background: SerializableColor | null;
+  cursor: SerializableColor | null;
+}
+
+/** A 256-color palette entry changed by OSC 4. */
+export interface PaletteEntry {
+  index: number;
+  r: number;
+  g: number;
+  b: number;
+}
+
+/** Structured formats the backend recognizes in block output. */
+export type StructuredKind = "json" | "csv" | "tsv";
+
+/** Block output parsed by `get_block_structured`. */
+export type StructuredData =
+  | { kind: "json"; value: unknown }
+  | { kind: "table"; columns: string[]; rows: string[][] };
+
+/** A command waiting to be run at the next idle prompt. */
+export interface QueuedCommand {
+  id: string;
+  command: string;
+}
 
 export interface SessionEndPayload {
   session_id: string;
   exit_code: number | null;
+  /** The session ran a profile's command instead of a shell. */
+  command: boolean;
+}
+
+/** How a stuck session's parser thread failed (see the parser watchdog). */
+export type ParserHealth = "stalled" | "crashed";
+
+export interface SessionStalledPayload {
+  session_id: string;
+  health: ParserHealth;
 }
 
 export interface CreateSessionResult {
   session_id: string;
   inside_tmux: boolean;
+  /** Render frames arrive as MessagePack over the session's frame channel. */
+  binary_frames: boolean;
+  /** Render frames go through the session's frame ring. */
+  frame_ring: boolean;
+}
+
+/** How a session sends render frames other than as JSON events: a
+ *  MessagePack channel bound to the window, or a frame ring any
+ *  window reads after `frame-ready` events. */
+export type FrameTransport = "channel" | "ring";
+
+/** `frame-ready`: the frame ring at `url` holds frames up to `head`. */
+export interface FrameReadyPayload {
+  session_id: string;
+  url: string;
+  head: number;
 }
 
 export interface ResizeAckPayload {
@@ -99,30 +258,100 @@ export interface ResizeAckPayload {
   resize_epoch: number;
 }
 
+/** Bytes through a session's PTY; rates are bytes per second. */
+export interface SessionIoStats {
+  bytesRead: number;
+  bytesWritten: number;
+  readRate: number;
+  writeRate: number;
+}
+
+export interface SessionIoPayload {
+  session_id: string;
+  stats: SessionIoStats;
+}
+
+/** Block at the top of the viewport; all null above the first block. */
+export interface ViewportBlock {
+  block_id: string | null;
+  command: string | null;
+  cwd: string | null;
+}
+
+export interface ViewportBlockPayload extends ViewportBlock {
+  session_id: string;
+}
+
+/** The command line being typed at a prompt (get_current_command_line). */
+export interface CommandLine {
+  text: string;
+  /** Character offset of the cursor in `text`, when known. */
+  cursor: number | null;
+  /** "echo" when read from the screen, "typed" when rebuilt from input. */
+  source: "echo" | "typed";
+}
+
+export type MouseAction = "press" | "release" | "motion";
+export type MouseButton =
+  | "left" | "middle" | "right" | "none"
+  | "wheelUp" | "wheelDown" | "wheelLeft" | "wheelRight";
+
+/** A mouse event over the grid, for send_mouse_event. */
+export interface MouseReport {
+  action: MouseAction;
+  /** "none" for motion with no button held. */
+  button: MouseButton;
+  row: number;
+  col: number;
+  /** Pixel offset from the grid's top-left corner (SGR-Pixels mode). */
+  x: number;
+  y: number;
+  shift: boolean;
+  alt: boolean;
+  ctrl: boolean;
+}
+
+/** Git state of a block's working directory when it started. */
+export interface GitRef {
+  branch?: string;
+  commit?: string;
+  /** A rebase was in progress. */
+  rebasing?: boolean;
+}
 
 // A frozen snapshot of a completed command's output.
 export interface CommandSnapshot {
   id: string;
+  /** Backend shell block ID, for commands that address the block's rows. */
+  blockId?: string;
   command: string;
   lines: RenderedLine[];
   timestamp: number;
   endTime: number | null;
   cwd: string;
+  /** Git branch/commit the command ran on. */
+  git?: GitRef;
   failed: boolean;
+  /** Set when the output is a JSON document or a CSV/TSV table. */
+  structured?: { kind: StructuredKind; sample: string };
 }
 
 
 export interface PendingBlock {
   id: string;
   cwd: string;
+  git?: GitRef;
 }
 
 export interface ActiveBlock {
   id: string;
   command: string;
   cwd: string;
+  git?: GitRef;
   startTime: number;
   outputStart: number;
+  /** Line ID of the command row; preferred over `outputStart` when still known. */
+  outputStartLineId?: number;
   tmuxCommand: boolean;
 }
 
@@ -134,6 +363,8 @@ export interface TerminalStoreState {
   cursor: CursorRender;
   sessionId: string | null;
   connected: boolean;
+  // Set while the session's parser thread is stuck or gone (session-stalled)
+  stalled: ParserHealth | null;
   title: string;
   rows: number;
   cols: number;
@@ -176,11 +407,37 @@ export interface TerminalStoreState {
   synchronizedOutput: boolean;
   bracketedPaste: boolean;
   cursorKeysApplication: boolean;
+  // xterm modifyOtherKeys level (CSI > 4 ; level m)
+  modifyOtherKeys: number;
+  // Kitty keyboard protocol flags (CSI > flags u), from render frames
+  keyboardFlags: number;
+  // DECSCNM (CSI ? 5 h), from render frames
+  reverseVideo: boolean;
+  // Width requested by DECCOLM (CSI ? 3 h/l) until the command that set it completes
+  columnModeCols: number | null;
+  // Palette entries set by OSC 4; spans already arrive with them applied
+  paletteOverrides: PaletteEntry[];
+  // Default fg/bg/cursor colors set by OSC 10/11/12
+  dynamicColors: DynamicColors;
+  // Progress reported with OSC 9;4 (null when none is shown)
+  progress: Progress | null;
+  // Limits of the running command's TERM (null when it has Rain's full feature set)
+  capabilities: TerminalCapabilities | null;
+  // Briefly show the capability limits when a limited command starts
+  capabilityNotice: boolean;
   // tmux-aware rendering fallback state
   tmuxActive: boolean;
   tmuxCompatibilityNotice: boolean;
   // tmux control mode: pane ID assigned by the controller (null for regular sessions)
   tmuxPaneId: number | null;
+  // Input locked with set_session_read_only
+  readOnly: boolean;
+  // Commands waiting for the next idle prompt (queue_command)
+  queuedCommands: QueuedCommand[];
+  // PTY transfer totals and rates from the last session-io event
+  io: SessionIoStats | null;
+  // Block scrolled into at the top of the viewport (viewport-block-changed)
+  viewportBlock: ViewportBlock | null;
   // Viewport origin at the moment of the last alt-screen exit.
   // Used by finalizeActiveBlock to capture farewell text that may
   // start before the original outputStart.
@@ -194,6 +451,9 @@ export interface TerminalStoreState {
   bell: boolean;
   // Inline images from image protocols (iTerm2 OSC 1337, Sixel, Kitty)
   inlineImages: InlineImageEntry[];
+  // The current selection is mirrored in the backend, which copies it from
+  // the cells; cleared when the backend drops it.
+  selectionMirrored: boolean;
 }
 
 export interface InlineImageEntry {
@@ -201,8 +461,14 @@ export interface InlineImageEntry {
   dataUri: string;
   width: number;
   height: number;
-  row: number;
+  /** Cells covered, when known; the image is sized to them. */
+  columns?: number;
+  rows?: number;
+  /** Line the image starts on, and its column there. */
+  lineId: number;
   col: number;
+  /** Stretch to `width` x `height` instead of keeping the image's shape. */
+  stretch?: boolean;
 }
 
 // Tab types
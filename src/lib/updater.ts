@@ -1,6 +1,8 @@
-// Auto-update: check for new versions of Rain via GitHub releases.
+// Auto-update: check for new versions of Rain. Builds with an updater key
+// use the signed feed (staged rollout, background download, installed by the
+// backend); others fall back to linking the latest GitHub release.
 
-import { getAppVersion } from "./ipc";
+import { checkForAppUpdate, getAppVersion } from "./ipc";
 
 const CHECK_INTERVAL_MS = 24 * 60 * 60 * 1000;
 const LAST_CHECK_KEY = "rain-last-update-check";
@@ -15,6 +17,10 @@ export interface UpdateInfo {
   updateAvailable: boolean;
   releaseUrl: string | null;
   releaseNotes: string | null;
+  /** Download size in bytes, when the feed announces it. */
+  size: number | null;
+  /** The backend is downloading this release and can install it. */
+  installable: boolean;
 }
 
 let _cachedVersion: string | null = null;
@@ -46,9 +52,28 @@ export function compareVersions(current: string, latest: string): boolean {
   return false;
 }
 
+export function releaseUrlFor(version: string): string {
+  return `https://github.com/${GITHUB_REPO}/releases/tag/v${version.replace(/^v/, "")}`;
+}
+
 export async function checkForUpdates(): Promise<UpdateInfo> {
   const currentVersion = await getCurrentVersion();
 
+  try {
+    const release = await checkForAppUpdate();
+    return {
+      currentVersion,
+      latestVersion: release?.version ?? null,
+      updateAvailable: !!release,
+      releaseUrl: release ? releaseUrlFor(release.version) : null,
+      releaseNotes: release?.notes ? release.notes.substring(0, 500) : null,
+      size: release?.size ?? null,
+      installable: !!release,
+    };
+  } catch {
+    // No updater key in this build; fall through to the GitHub API.
+  }
+
   try {
     const response = await fetch(RELEASES_URL, {
       headers: { Accept: "application/vnd.github.v3+json" },
@@ -62,6 +87,8 @@ export async function checkForUpdates(): Promise<UpdateInfo> {
         updateAvailable: false,
         releaseUrl: null,
         releaseNotes: null,
+        size: null,
+        installable: false,
       };
     }
 
@@ -78,6 +105,8 @@ export async function checkForUpdates(): Promise<UpdateInfo> {
       updateAvailable: !!latestVersion && compareVersions(currentVersion, latestVersion),
       releaseUrl,
       releaseNotes,
+      size: null,
+      installable: false,
     };
   } catch {
     return {
@@ -86,6 +115,8 @@ export async function checkForUpdates(): Promise<UpdateInfo> {
       updateAvailable: false,
       releaseUrl: null,
       releaseNotes: null,
+      size: null,
+      installable: false,
     };
   }
 }
@@ -1,11 +1,12 @@
-import type { CanvasRendererConfig, CanvasCell } from "./canvasRenderer";
+import { spanColumns, type CanvasRendererConfig, type CanvasCell } from "./canvasRenderer";
+import type { UnderlineStyle } from "./types";
+import type { SpanLink } from "./links";
 
-const URL_REGEX = /https?:\/\/[^\s<>"{}|\\^`\[\]]+/g;
 
-interface UrlRange {
+interface LinkRange {
   startCol: number;
   endCol: number;
-  url: string;
+  link: SpanLink;
 }
 
 type GlyphKey = string;
@@ -494,7 +495,7 @@ export class WebGLTerminalRenderer {
   private dirtyRows = new Set<number>();
   private rafId: number | null = null;
   private contextLost: boolean = false;
-  private urlRanges = new Map<number, UrlRange[]>();
+  private linkRanges = new Map<number, LinkRange[]>();
   private defaultBgColor: Rgba = [0, 0, 0, 1];
   private projectionMatrix = new Float32Array(16);
   private glyphAtlas: WebGLGlyphAtlas | null = null;
@@ -907,7 +908,7 @@ export class WebGLTerminalRenderer {
       this.grid.push(row);
     }
 
-    this.urlRanges.clear();
+    this.linkRanges.clear();
     this.measureFont();
     this.syncGlyphAtlas(false);
     this.allocateInstanceArrays();
@@ -943,20 +944,27 @@ export class WebGLTerminalRenderer {
     row: number,
     spans: Array<{
       text: string;
+      /** Columns the span covers; one per cluster when absent. */
+      cols?: number;
       fg: string;
       bg: string;
       bold: boolean;
       italic: boolean;
       underline: boolean;
+      underlineStyle?: UnderlineStyle;
+      underlineColor?: string;
       strikethrough: boolean;
       dim: boolean;
+      /** URL or file path the backend found on this span. */
+      link?: SpanLink;
     }>,
   ): void {
     if (row < 0 || row >= this.config.rows) return;
     let col = 0;
-    let lineText = "";
+    const links: LinkRange[] = [];
     for (const span of spans) {
-      for (const char of span.text) {
+      const startCol = col;
+      for (const char of spanColumns(span.text, span.cols)) {
         if (col >= this.config.cols) break;
         this.grid[row][col] = {
           char,
@@ -965,12 +973,16 @@ export class WebGLTerminalRenderer {
           bold: span.bold,
           italic: span.italic,
           underline: span.underline,
+          underlineStyle: span.underlineStyle,
+          underlineColor: span.underlineColor,
           strikethrough: span.strikethrough,
           dim: span.dim,
         };
         col++;
       }
-      lineText += span.text;
+      if (span.link && col > startCol) {
+        links.push({ startCol, endCol: col - 1, link: span.link });
+      }
     }
     while (col < this.config.cols) {
       this.grid[row][col] = {
@@ -986,24 +998,10 @@ export class WebGLTerminalRenderer {
       col++;
     }
 
-    if (lineText.length >= 8 && lineText.includes("://")) {
-      const urls: UrlRange[] = [];
-      const regex = new RegExp(URL_REGEX.source, "g");
-      let match: RegExpExecArray | null;
-      while ((match = regex.exec(lineText)) !== null) {
-        urls.push({
-          startCol: match.index,
-          endCol: match.index + match[0].length - 1,
-          url: match[0],
-        });
-      }
-      if (urls.length > 0) {
-        this.urlRanges.set(row, urls);
-      } else {
-        this.urlRanges.delete(row);
-      }
+    if (links.length > 0) {
+      this.linkRanges.set(row, links);
     } else {
-      this.urlRanges.delete(row);
+      this.linkRanges.delete(row);
     }
 
     this.dirtyRows.add(row);
@@ -1111,12 +1109,16 @@ export class WebGLTerminalRenderer {
         const cell = rowCells[col];
         if (!cell || (!cell.underline && !cell.strikethrough)) continue;
 
-        const [fr, fg, fb, fa] = this.colorToRgba(cell.fg ?? this.config.defaultFg);
         const dimScale = cell.dim ? 0.5 : 1;
-        const color: Rgba = [fr * dimScale, fg * dimScale, fb * dimScale, fa];
+        const dimmed = (css: string): Rgba => {
+          const [r, g, b, a] = this.colorToRgba(css);
+          return [r * dimScale, g * dimScale, b * dimScale, a];
+        };
+        const color = dimmed(cell.fg ?? this.config.defaultFg);
 
         if (cell.underline) {
-          this.drawOverlayQuad(col, row + underlineOffset, 1, lineThickness, color);
+          const underlineColor = cell.underlineColor ? dimmed(cell.underlineColor) : color;
+          this.drawUnderline(cell.underlineStyle ?? "single", col, row + underlineOffset, lineThickness, underlineColor);
         }
         if (cell.strikethrough) {
           this.drawOverlayQuad(col, row + strikeOffset, 1, lineThickness, color);
@@ -1125,11 +1127,41 @@ export class WebGLTerminalRenderer {
     }
   }
 
+  /** One cell's underline as quads, `y` in rows and `thickness` one device pixel. */
+  private drawUnderline(style: UnderlineStyle, col: number, y: number, thickness: number, color: Rgba): void {
+    const px = 1 / Math.max(1, this.charWidth);
+    switch (style) {
+      case "double":
+        this.drawOverlayQuad(col, y, 1, thickness, color);
+        this.drawOverlayQuad(col, y - thickness * 2, 1, thickness, color);
+        break;
+      case "curly": {
+        // Zigzag in quarter-cell steps; one period per cell so cells join up.
+        const steps = [0, -1, -2, -1];
+        steps.forEach((step, i) => {
+          this.drawOverlayQuad(col + i / 4, y + step * thickness, 0.25, thickness, color);
+        });
+        break;
+      }
+      case "dotted":
+        for (let x = 0; x < 1; x += px * 3) {
+          this.drawOverlayQuad(col + x, y, px, thickness, color);
+        }
+        break;
+      case "dashed":
+        this.drawOverlayQuad(col, y, 0.4, thickness, color);
+        this.drawOverlayQuad(col + 0.5, y, 0.4, thickness, color);
+        break;
+      default:
+        this.drawOverlayQuad(col, y, 1, thickness, color);
+    }
+  }
+
   private drawUrlUnderlines(rows: number, cols: number): void {
     const lineThickness = 1 / Math.max(1, this.charHeight);
     const urlOffset = Math.max(0, this.charHeight - 1) / Math.max(1, this.charHeight);
     const urlColor: Rgba = [0x58 / 255, 0xa6 / 255, 0xff / 255, 0.6];
-    for (const [row, urls] of this.urlRanges) {
+    for (const [row, urls] of this.linkRanges) {
       if (row < 0 || row >= rows || urls.length === 0) continue;
       for (const url of urls) {
         const startCol = Math.max(0, Math.min(cols - 1, url.startCol));
@@ -1260,11 +1292,11 @@ export class WebGLTerminalRenderer {
     this.scheduleRender();
   }
 
-  getUrlAt(row: number, col: number): string | null {
-    const urls = this.urlRanges.get(row);
-    if (!urls) return null;
-    for (const u of urls) {
-      if (col >= u.startCol && col <= u.endCol) return u.url;
+  getLinkAt(row: number, col: number): SpanLink | null {
+    const links = this.linkRanges.get(row);
+    if (!links) return null;
+    for (const l of links) {
+      if (col >= l.startCol && col <= l.endCol) return l.link;
     }
     return null;
   }
@@ -1303,7 +1335,7 @@ export class WebGLTerminalRenderer {
       gl.deleteProgram(this.glyphProgram);
     }
 
-    this.urlRanges.clear();
+    this.linkRanges.clear();
     this.grid = [];
     this.colorCache.clear();
   }
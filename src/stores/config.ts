@@ -1,7 +1,14 @@
 import { createSignal } from "solid-js";
-import { readConfigFile, writeConfigFile } from "../lib/ipc";
+import {
+  readConfigFile,
+  writeConfigFile,
+  type NewSessionCwd,
+  type ShellArgTemplate,
+} from "../lib/ipc";
 
 export type MacosGlassEngine = "liquid" | "cgs" | "cssSafe";
+/** "default" keeps the reports Rain always sent (VT220 in DA1, VT100 in DA2). */
+export type ConformanceLevel = "default" | "vt220" | "vt420" | "vt520";
 
 export interface RainConfig {
   fontFamily: string;
@@ -17,6 +24,10 @@ export interface RainConfig {
   cursorShape: "block" | "underline" | "bar";
   optionAsMeta: boolean;
   scrollbackLines: number;
+  /** Memory budget of each session's scrollback in MB; 0 for none. */
+  scrollbackMemoryMb: number;
+  /** Keep older scrollback zstd-compressed, decompressed when read. */
+  scrollbackCompression: boolean;
   snapshotLimit: number;
   customBgColor: string | null;
   customFgColor: string | null;
@@ -30,6 +41,10 @@ export interface RainConfig {
   customBorderColor: string | null;
   customSelectionColor: string | null;
   letterSpacing: number;
+  /** Contrast ratio text is kept at against its background (1 = off). */
+  minimumContrast: number;
+  /** Bold text in ANSI colors 0-7 uses the bright colors 8-15. */
+  boldIsBright: boolean;
   promptStyle: "default" | "simplified" | "blank";
   terminalStyle: "chat" | "traditional";
   tmuxMode: "integrated" | "native";
@@ -41,7 +56,56 @@ export interface RainConfig {
   clearHistoryForTuis: boolean;
   enableLigatures: boolean;
   globalHotkey: string | null;
+  hibernateIdleSessions: boolean;
+  hibernateIdleMinutes: number;
+  /** OSC numbers forwarded to the UI as CustomOsc events (e.g. 7770). */
+  customOscCodes: number[];
+  /** Terminal conformance level advertised to applications in DA1/DA2. */
+  conformanceLevel: ConformanceLevel;
+  /** DA1 feature parameters advertised beyond the implemented ones. */
+  deviceAttributeFeatures: number[];
+  /** DA1 feature parameters never advertised (e.g. 4 to hide Sixel). */
+  hiddenDeviceAttributeFeatures: number[];
+  /** Mirror mouse selections into the Linux PRIMARY selection. */
+  copyToPrimarySelection: boolean;
+  /** Strip trailing whitespace from copied block text. */
+  copyTrimTrailingWhitespace: boolean;
+  /** Copy Block Output includes the command, without the prompt decoration. */
+  copyBlockWithCommand: boolean;
+  /** Copy Block Output as a Markdown fenced code block. */
+  copyAsFencedCode: boolean;
+  /** Output lines kept by Copy as Markdown before truncating (0 = all). */
+  markdownCopyMaxLines: number;
+  /** Use the OS-native right-click menu instead of the in-page one. */
+  nativeContextMenu: boolean;
+  /** Tray / menu bar icon listing windows and sessions. */
+  showTrayIcon: boolean;
   renderer: "dom" | "canvas" | "webgl" | "auto";
+  /** Where "Share Command Output" uploads to. */
+  shareService: "gist" | "paste";
+  /** POST endpoint used when `shareService` is "paste". */
+  shareEndpoint: string | null;
+  shareFormat: "ansi" | "html";
+  /** Per-shell launch arguments, e.g. `{ shell: "pwsh", args: ["-NoLogo"] }`. */
+  shellArgs: ShellArgTemplate[];
+  /** Offer to inject shell hooks when an ssh session reaches a remote prompt. */
+  offerRemoteShellIntegration: boolean;
+  /** Let programs send files to save (iTerm2 OSC 1337 without inline=1). */
+  fileDownloads: boolean;
+  /** Let programs switch the pane to 80/132 columns (DECCOLM). */
+  allowColumnMode: boolean;
+  /** Keep a pre-started shell per profile so new tabs open instantly. */
+  warmShellPool: boolean;
+  /** Where new tabs and splits opened from a pane start. */
+  newSessionCwd: NewSessionCwd;
+  /** New sessions print East Asian ambiguous-width characters two columns wide. */
+  ambiguousWide: boolean;
+  /** New sessions send render frames as MessagePack instead of JSON. */
+  binaryRenderFrames: boolean;
+  /** New sessions send render frames through a frame ring read in batches, for
+   *  output too fast to send frame by frame. Takes precedence over
+   *  `binaryRenderFrames`. */
+  frameRing: boolean;
 }
 
 const STORAGE_KEY = "rain-config";
@@ -66,6 +130,8 @@ const defaultConfig: RainConfig = {
   cursorShape: "block",
   optionAsMeta: true,
   scrollbackLines: 10_000,
+  scrollbackMemoryMb: 0,
+  scrollbackCompression: false,
   snapshotLimit: 1_000,
   customBgColor: null,
   customFgColor: null,
@@ -79,6 +145,8 @@ const defaultConfig: RainConfig = {
   customBorderColor: null,
   customSelectionColor: null,
   letterSpacing: 0,
+  minimumContrast: 1,
+  boldIsBright: false,
   promptStyle: "simplified",
   terminalStyle: "chat",
   tmuxMode: "integrated",
@@ -90,7 +158,32 @@ const defaultConfig: RainConfig = {
   clearHistoryForTuis: false,
   enableLigatures: false,
   globalHotkey: null,
+  hibernateIdleSessions: false,
+  hibernateIdleMinutes: 30,
+  customOscCodes: [],
+  conformanceLevel: "default",
+  deviceAttributeFeatures: [],
+  hiddenDeviceAttributeFeatures: [],
+  copyToPrimarySelection: true,
+  copyTrimTrailingWhitespace: true,
+  copyBlockWithCommand: false,
+  copyAsFencedCode: false,
+  markdownCopyMaxLines: 50,
+  nativeContextMenu: true,
+  showTrayIcon: false,
   renderer: "dom",
+  shareService: "gist",
+  shareEndpoint: null,
+  shareFormat: "html",
+  shellArgs: [],
+  offerRemoteShellIntegration: false,
+  fileDownloads: false,
+  allowColumnMode: true,
+  warmShellPool: false,
+  newSessionCwd: "inherit",
+  ambiguousWide: false,
+  binaryRenderFrames: false,
+  frameRing: false,
 };
 
 function clampOpacity(value: number): number {
@@ -201,6 +294,24 @@ function loadConfig(): RainConfig {
 
 const [config, setConfig] = createSignal<RainConfig>(loadConfig());
 const [isDirty, setIsDirty] = createSignal(false);
+const [safeMode, setSafeMode] = createSignal(false);
+
+/** True when the backend started in safe mode after repeated crashes. */
+export const isSafeMode = safeMode;
+
+/**
+ * Run on defaults with window effects off. The saved config is left alone
+ * until the user changes a setting, which then persists as usual.
+ */
+export function enterSafeMode(): void {
+  setSafeMode(true);
+  setConfig({
+    ...defaultConfig,
+    windowOpacity: 1,
+    backgroundBlurStrength: 0,
+    macosGlassEngine: "cssSafe",
+  });
+}
 
 function persistConfigNow() {
   try {
@@ -221,6 +332,7 @@ async function syncConfigToDisk() {
 
 // Load config from disk on startup (async, overrides localStorage if present)
 export async function loadConfigFromDisk(): Promise<void> {
+  if (safeMode()) return;
   try {
     const raw = await readConfigFile();
     if (raw) {
@@ -275,6 +387,8 @@ export function useConfig() {
           liquidVariant: defaultConfig.liquidVariant,
           liquidCornerRadius: defaultConfig.liquidCornerRadius,
           liquidTintColor: defaultConfig.liquidTintColor,
+          minimumContrast: defaultConfig.minimumContrast,
+          boldIsBright: defaultConfig.boldIsBright,
           customBgColor: null,
           customFgColor: null,
           customAccentColor: null,
@@ -294,6 +408,8 @@ export function useConfig() {
           cursorShape: defaultConfig.cursorShape,
           optionAsMeta: defaultConfig.optionAsMeta,
           scrollbackLines: defaultConfig.scrollbackLines,
+          scrollbackMemoryMb: defaultConfig.scrollbackMemoryMb,
+          scrollbackCompression: defaultConfig.scrollbackCompression,
           snapshotLimit: defaultConfig.snapshotLimit,
           promptStyle: defaultConfig.promptStyle,
           terminalStyle: defaultConfig.terminalStyle,
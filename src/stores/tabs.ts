@@ -303,6 +303,24 @@ export function createTabsStore(): TabsStore {
     if (idx >= 0) switchTab(idx);
   }
 
+  /** Switch to the tab showing a session and focus its pane. */
+  function focusSession(sessionId: string): boolean {
+    const paneId = sessionToTab.get(sessionId);
+    if (!paneId) return false;
+    const tab = state.tabs.find(
+      (t) => t.id === paneId || (t.paneTree !== undefined && collectLeafIds(t.paneTree).includes(paneId)),
+    );
+    if (!tab) return false;
+    switchTabById(tab.id);
+    if (tab.paneTree) setActivePane(tab.id, paneId);
+    return true;
+  }
+
+  /** IDs of every session shown in this window. */
+  function sessionIds(): string[] {
+    return [...sessionToTab.keys()];
+  }
+
   function nextTab() {
     switchTab((state.activeIndex + 1) % state.tabs.length);
   }
@@ -894,6 +912,8 @@ export function createTabsStore(): TabsStore {
     closeTab,
     switchTab,
     switchTabById,
+    focusSession,
+    sessionIds,
     nextTab,
     prevTab,
     activeTab,
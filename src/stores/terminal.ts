@@ -1,13 +1,23 @@
 import { createStore, produce } from "solid-js/store";
 import type {
+  CellRect,
   RenderedLine,
   RenderFramePayload,
   ResizeAckPayload,
+  SearchMatch,
+  StyledSpan,
   TerminalEvent,
   TerminalStoreState,
 } from "../lib/types";
-import { collectLinesForRange, trimTrailingEmpty } from "../lib/terminal-output";
+import {
+  collectLinesForRange,
+  findGlobalRowForLineId,
+  trimTrailingEmpty,
+} from "../lib/terminal-output";
+import { spanColumns } from "../lib/canvasRenderer";
 import { checkOutput, executeTriggerAction } from "../lib/triggers";
+import { filterKnownEvents } from "../lib/eventSchema";
+import { getScrollbackRange, showNotification } from "../lib/ipc";
 import { useConfig } from "./config";
 
 export interface TerminalStore {
@@ -16,6 +26,7 @@ export interface TerminalStore {
   applyRenderFrame: (payload: RenderFramePayload) => void;
   applyResizeAck: (payload: ResizeAckPayload) => void;
   clearHistory: () => void;
+  loadScrollbackHistory: () => Promise<void>;
   scrollUp: (lines: number) => void;
   scrollDown: (lines: number) => void;
   scrollToBottom: () => void;
@@ -25,6 +36,9 @@ let snapshotCounter = 0;
 
 const DEFAULT_SNAPSHOT_LIMIT = 1_000;
 
+/** Rows requested per `get_scrollback_range` call. */
+const SCROLLBACK_PAGE_ROWS = 1_000;
+
 function resolveSnapshotLimit(value: number | undefined): number {
   if (typeof value !== "number" || !Number.isFinite(value)) return DEFAULT_SNAPSHOT_LIMIT;
   return Math.max(100, Math.floor(value));
@@ -79,6 +93,19 @@ function isClearCommand(command: string | null | undefined): boolean {
   return /^clear(\s+-[A-Za-z0-9-]+)*\s*$/.test(trimmed);
 }
 
+/** Matches from `search_visible`; alt-screen lines are indexed by screen row. */
+export function visibleSearchMatches(rects: CellRect[]): SearchMatch[] {
+  return rects.map((r) => ({ globalRow: r.row, startCol: r.col, endCol: r.col + r.cols - 1 }));
+}
+
+/** OSC 9/777 notifications only pop up while the window is in the background. */
+function notifyUnfocused(sessionId: string, title: string, body: string) {
+  if (typeof document !== "undefined" && document.hasFocus()) return;
+  showNotification(sessionId, title, body).catch((e) =>
+    console.warn("[Rain] Failed to show notification:", e),
+  );
+}
+
 function resetStoreHistory(state: TerminalStoreState) {
   if (state.altScreen) {
     state.snapshots = [];
@@ -129,6 +156,7 @@ export function createTerminalStore(): TerminalStore {
     cursor: { row: 0, col: 0, visible: true, shape: "block" },
     sessionId: null,
     connected: false,
+    stalled: null,
     title: "Rain",
     rows: 24,
     cols: 80,
@@ -158,25 +186,41 @@ export function createTerminalStore(): TerminalStore {
     synchronizedOutput: false,
     bracketedPaste: false,
     cursorKeysApplication: false,
+    modifyOtherKeys: 0,
+    keyboardFlags: 0,
+    reverseVideo: false,
+    columnModeCols: null,
+    paletteOverrides: [],
+    dynamicColors: { foreground: null, background: null, cursor: null },
+    progress: null,
+    capabilities: null,
+    capabilityNotice: false,
     tmuxActive: false,
     tmuxCompatibilityNotice: false,
     tmuxPaneId: null,
+    readOnly: false,
+    queuedCommands: [],
+    io: null,
+    viewportBlock: null,
     searchOpen: false,
     searchQuery: "",
     searchMatches: [],
     searchCurrentIndex: -1,
     bell: false,
     inlineImages: [],
+    selectionMirrored: false,
   });
 
   function applyRenderFrame(payload: RenderFramePayload) {
     const { frame } = payload;
     const snapshotLimit = resolveSnapshotLimit(config().snapshotLimit);
     const scrollbackLimit = resolveScrollbackLimit(config().scrollbackLines);
+    let scrollbackGap: { from: number; to: number } | null = null;
+    let historyRewrapped = false;
 
     setState(
       produce((s) => {
-        for (const event of frame.events) {
+        for (const event of filterKnownEvents("terminal", frame.events, frame.event_schema)) {
           switch (event.type) {
             case "AltScreenEntered":
               s.altScreen = true;
@@ -199,6 +243,7 @@ export function createTerminalStore(): TerminalStore {
               s.lastAltExitVisibleBase = frame.visible_base_global;
               if (s.activeBlock) {
                 s.activeBlock.outputStart = frame.visible_base_global;
+                s.activeBlock.outputStartLineId = undefined;
               }
               break;
             case "TitleChanged":
@@ -221,19 +266,29 @@ export function createTerminalStore(): TerminalStore {
               s.synchronizedOutput = event.synchronized_output;
               s.bracketedPaste = event.bracketed_paste;
               s.cursorKeysApplication = event.cursor_keys_application;
+              s.modifyOtherKeys = event.modify_other_keys ?? 0;
               break;
             case "ScrollbackCleared":
               s.scrollbackLines = [];
               break;
+            case "ScrollbackRewrapped":
+              // Rows are renumbered at the new width; the held copy is
+              // refetched below and blocks find their lines by ID.
+              s.scrollbackLines = [];
+              s.visibleLinesByGlobal = {};
+              historyRewrapped = true;
+              break;
             case "InlineImage": {
-              const imgEvent = event as { type: "InlineImage"; id: string; data_base64: string; width: number; height: number; row: number; col: number };
               s.inlineImages = [...s.inlineImages, {
-                id: imgEvent.id,
-                dataUri: `data:image/png;base64,${imgEvent.data_base64}`,
-                width: imgEvent.width,
-                height: imgEvent.height,
-                row: imgEvent.row,
-                col: imgEvent.col,
+                id: event.id,
+                dataUri: event.url ?? `data:image/png;base64,${event.data_base64}`,
+                width: event.width,
+                height: event.height,
+                columns: event.columns,
+                rows: event.rows,
+                lineId: event.line_id,
+                col: event.col,
+                stretch: !event.preserve_aspect_ratio,
               }];
               if (s.inlineImages.length > 50) {
                 s.inlineImages = s.inlineImages.slice(-50);
@@ -241,14 +296,15 @@ export function createTerminalStore(): TerminalStore {
               break;
             }
             case "SixelImage": {
-              const sixelEvent = event as { type: "SixelImage"; id: string; data_base64: string; width: number; height: number; row: number; col: number };
               s.inlineImages = [...s.inlineImages, {
-                id: sixelEvent.id,
-                dataUri: `data:image/png;base64,${sixelEvent.data_base64}`,
-                width: sixelEvent.width,
-                height: sixelEvent.height,
-                row: sixelEvent.row,
-                col: sixelEvent.col,
+                id: event.id,
+                dataUri: event.url ?? `data:image/png;base64,${event.data_base64}`,
+                width: event.width,
+                height: event.height,
+                columns: event.columns,
+                rows: event.rows,
+                lineId: event.line_id,
+                col: event.col,
               }];
               if (s.inlineImages.length > 50) {
                 s.inlineImages = s.inlineImages.slice(-50);
@@ -256,14 +312,24 @@ export function createTerminalStore(): TerminalStore {
               break;
             }
             case "KittyImage": {
-              const kittyEvent = event as { type: "KittyImage"; id: string; data_base64: string; width: number; height: number; row: number; col: number };
-              s.inlineImages = [...s.inlineImages, {
-                id: kittyEvent.id,
-                dataUri: `data:image/png;base64,${kittyEvent.data_base64}`,
-                width: kittyEvent.width,
-                height: kittyEvent.height,
-                row: kittyEvent.row,
-                col: kittyEvent.col,
+              if (event.action === "delete") {
+                // 0 matches every image or placement.
+                s.inlineImages = s.inlineImages.filter((img) => {
+                  const m = /^kitty-(\d+)-(\d+)$/.exec(img.id);
+                  if (!m) return true;
+                  const imageMatches = event.image_id === 0 || Number(m[1]) === event.image_id;
+                  const placementMatches = event.placement_id === 0 || Number(m[2]) === event.placement_id;
+                  return !(imageMatches && placementMatches);
+                });
+                break;
+              }
+              s.inlineImages = [...s.inlineImages.filter((img) => img.id !== event.id), {
+                id: event.id,
+                dataUri: event.url ?? `data:image/png;base64,${event.data_base64}`,
+                width: event.width,
+                height: event.height,
+                lineId: event.line_id,
+                col: event.col,
               }];
               if (s.inlineImages.length > 50) {
                 s.inlineImages = s.inlineImages.slice(-50);
@@ -271,16 +337,67 @@ export function createTerminalStore(): TerminalStore {
               break;
             }
             case "TmuxRequested":
+            case "RemotePromptDetected":
+            case "FileDownload":
+              break;
+            case "CommandQueueChanged":
+              s.queuedCommands = event.queued;
+              break;
+            case "ColumnModeChanged":
+              s.columnModeCols = event.cols;
+              break;
+            case "PaletteChanged":
+              s.paletteOverrides = event.colors;
+              break;
+            case "DynamicColorsChanged":
+              s.dynamicColors = {
+                foreground: event.foreground,
+                background: event.background,
+                cursor: event.cursor,
+              };
+              break;
+            case "ProgressChanged":
+              s.progress = event.state === "none" ? null : { state: event.state, value: event.value };
+              break;
+            case "TerminalCapabilitiesChanged":
+              s.capabilities = event.degraded.length > 0
+                ? {
+                    term: event.term,
+                    colors: event.colors,
+                    hyperlinks: event.hyperlinks,
+                    images: event.images,
+                    mouse: event.mouse,
+                    degraded: event.degraded,
+                  }
+                : null;
+              s.capabilityNotice = s.capabilities !== null;
+              break;
+            case "SelectionCleared":
+              s.selectionMirrored = false;
+              break;
+            case "Notification":
+              notifyUnfocused(payload.session_id, event.title ?? (s.title || "Rain"), event.body);
               break;
             default:
-              if (event.type === "BlockCompleted" || s.altScreen || s.awaitingNonAltReseed) {
+              // Column mode lasts as long as the program that set it.
+              if (event.type === "BlockCompleted") {
+                s.columnModeCols = null;
+              }
+              // Structured-output metadata belongs to the snapshot that
+              // BlockCompleted creates, so it waits alongside it.
+              if (
+                event.type === "BlockCompleted" ||
+                event.type === "BlockStructuredData" ||
+                s.altScreen ||
+                s.awaitingNonAltReseed
+              ) {
                 queuedBlockEvents.push(event);
               } else {
                 if (queuedBlockEvents.length > 0) {
                   const kept: typeof queuedBlockEvents = [];
                   const flushed = queuedBlockEvents.splice(0, queuedBlockEvents.length);
                   for (const q of flushed) {
-                    if (q.type === "BlockCompleted") {
+                    if (q.type === "BlockCompleted" || q.type === "BlockStructuredData") {
                       kept.push(q);
                     } else {
                       processBlockEvent(s, q, config().terminalStyle, snapshotLimit);
@@ -324,12 +441,26 @@ export function createTerminalStore(): TerminalStore {
         }
         s.lastFrameSeq = Math.max(s.lastFrameSeq, frame.frame_seq);
 
+        if (frame.lines.some((line) => line.start_col !== undefined)) {
+          const previous = s.altScreen ? s.altScreenLines : s.fallbackLines;
+          frame.lines = frame.lines.map((line) => mergePartialLine(previous, line));
+        }
+
         const prevRows = s.rows;
         const prevCols = s.cols;
         const frameRows = Math.max(1, frame.visible_rows || s.rows);
         const frameCols = Math.max(1, frame.visible_cols || s.cols);
         const viewportChanged = frameRows !== prevRows || frameCols !== prevCols;
         s.cursor = frame.cursor;
+        s.keyboardFlags = frame.keyboard_flags ?? 0;
+        s.reverseVideo = frame.reverse_video ?? false;
+        if (frame.visible_search && s.altScreen && s.searchOpen && s.searchQuery) {
+          s.searchMatches = visibleSearchMatches(frame.visible_search);
+          s.searchCurrentIndex = Math.min(
+            Math.max(s.searchCurrentIndex, 0),
+            s.searchMatches.length - 1,
+          );
+        }
         s.rows = frameRows;
         s.cols = frameCols;
 
@@ -348,11 +479,19 @@ export function createTerminalStore(): TerminalStore {
             0,
             frame.visible_base_global - frame.scrolled_lines.length,
           );
+          // A backend flooded by a lagging view drops scrolled lines from
+          // frames; they are still in its scrollback.
+          const lastHeld = s.scrollbackLines[s.scrollbackLines.length - 1]?.index;
+          if (lastHeld !== undefined && startGlobal > lastHeld + 1) {
+            scrollbackGap = { from: lastHeld + 1, to: startGlobal };
+          }
           for (let i = 0; i < frame.scrolled_lines.length; i++) {
             const line = frame.scrolled_lines[i];
             const global = startGlobal + i;
             s.scrollbackLines.push({
               index: global,
+              line_id: line.line_id,
+              line_size: line.line_size,
               spans: line.spans,
             });
           }
@@ -363,6 +502,7 @@ export function createTerminalStore(): TerminalStore {
           if (viewportChanged) {
             s.altScreenLines = frame.lines.map((line) => ({
               index: line.index,
+              line_size: line.line_size,
               spans: line.spans,
             }));
           } else {
@@ -393,7 +533,12 @@ export function createTerminalStore(): TerminalStore {
 
           for (const line of frame.lines) {
             const global = frame.visible_base_global + line.index;
-            const incoming: RenderedLine = { index: global, spans: line.spans };
+            const incoming: RenderedLine = {
+              index: global,
+              line_id: line.line_id,
+              line_size: line.line_size,
+              spans: line.spans,
+            };
 
             if (preserveHistory && global < visibleStart) {
               const existing = nextVisible[global];
@@ -427,6 +572,13 @@ export function createTerminalStore(): TerminalStore {
       }),
     );
 
+    const gap = scrollbackGap as { from: number; to: number } | null;
+    if (historyRewrapped) {
+      loadScrollbackHistory().catch(console.error);
+    } else if (gap && state.sessionId) {
+      fetchScrollbackRows(state.sessionId, gap.from, gap.to).catch(console.error);
+    }
+
     for (const line of frame.lines) {
       const text = line.spans.map((s: { text: string }) => s.text).join("");
       if (text.trim()) {
@@ -487,6 +639,39 @@ export function createTerminalStore(): TerminalStore {
     );
   }
 
+  /** Fetch scrollback the backend holds but this view never received (a
+   *  window adopting a session without its transfer state), newest pages
+   *  first so the history nearest the screen shows up soonest. */
+  async function loadScrollbackHistory() {
+    const sessionId = state.sessionId;
+    if (!sessionId) return;
+    const scrollbackLimit = resolveScrollbackLimit(config().scrollbackLines);
+    const bounds = await getScrollbackRange(sessionId, 0, 0);
+    const floor = Math.max(bounds.first_global_row, bounds.visible_base_global - scrollbackLimit);
+    const end = state.scrollbackLines[0]?.index ?? bounds.visible_base_global;
+    await fetchScrollbackRows(sessionId, floor, end);
+  }
+
+  /** Fetch the scrollback rows `floor..end` this view lacks, newest first. */
+  async function fetchScrollbackRows(sessionId: string, floor: number, end: number) {
+    while (end > floor && state.sessionId === sessionId) {
+      const start = Math.max(floor, end - SCROLLBACK_PAGE_ROWS);
+      const page = await getScrollbackRange(sessionId, start, end - start);
+      if (page.lines.length === 0) break;
+      setState(
+        produce((s) => {
+          const held = new Set(s.scrollbackLines.map((line) => line.index));
+          const missing = page.lines
+            .map((line, i) => ({ ...line, index: page.start_global_row + i }))
+            .filter((line) => !held.has(line.index));
+          if (missing.length === 0) return;
+          s.scrollbackLines = [...missing, ...s.scrollbackLines].sort((a, b) => a.index - b.index);
+        }),
+      );
+      end = page.start_global_row;
+    }
+  }
+
   function scrollUp(lines: number) {
     setState(
       produce((s) => {
@@ -508,7 +693,60 @@ export function createTerminalStore(): TerminalStore {
     setState("scrollOffset", 0);
   }
 
-  return { state, setState, applyRenderFrame, applyResizeAck, clearHistory, scrollUp, scrollDown, scrollToBottom };
+  return {
+    state,
+    setState,
+    applyRenderFrame,
+    applyResizeAck,
+    clearHistory,
+    loadScrollbackHistory,
+    scrollUp,
+    scrollDown,
+    scrollToBottom,
+  };
+}
+
+/** Splice a partial line into the last full copy of its row, so frames
+ *  carry whole lines past this point. */
+function mergePartialLine(buffer: RenderedLine[], line: RenderedLine): RenderedLine {
+  if (line.start_col === undefined) return line;
+  const { start_col: start, ...rest } = line;
+  const end = start + line.spans.reduce((cols, span) => cols + span.cols, 0);
+  const before: StyledSpan[] = [];
+  const after: StyledSpan[] = [];
+  let col = 0;
+  for (const span of buffer.find((l) => l.index === line.index)?.spans ?? []) {
+    const spanEnd = col + span.cols;
+    if (spanEnd <= start) {
+      before.push(span);
+    } else if (col >= end) {
+      after.push(span);
+    } else {
+      const columns = spanColumns(span.text, span.cols);
+      if (col < start) {
+        before.push({ ...span, text: columns.slice(0, start - col).join(""), cols: start - col });
+      }
+      if (spanEnd > end) {
+        after.push({ ...span, text: columns.slice(end - col).join(""), cols: spanEnd - end });
+      }
+    }
+    col = spanEnd;
+  }
+  if (col < start) {
+    // A row we never saw whole: pad up to the update with blanks.
+    before.push({
+      text: " ".repeat(start - col),
+      cols: start - col,
+      fg: { type: "Default" },
+      bg: { type: "Default" },
+      bold: false,
+      dim: false,
+      italic: false,
+      underline: false,
+      strikethrough: false,
+    });
+  }
+  return { ...rest, spans: [...before, ...line.spans, ...after] };
 }
 
 function applyLinesToBuffer(buffer: RenderedLine[], incoming: RenderedLine[]) {
@@ -564,7 +802,7 @@ function processBlockEvent(
   switch (event.type) {
     case "BlockStarted": {
       state.shellIntegrationActive = true;
-      state.pendingBlock = { id: event.id, cwd: event.cwd };
+      state.pendingBlock = { id: event.id, cwd: event.cwd, git: event.git };
       break;
     }
     case "BlockCommand": {
@@ -584,8 +822,10 @@ function processBlockEvent(
         id: event.id,
         command: event.command,
         cwd: pending?.cwd ?? state.cwd,
+        git: pending?.git,
         startTime: Date.now(),
         outputStart: event.global_row,
+        outputStartLineId: event.line_id,
         tmuxCommand,
       };
       break;
@@ -595,6 +835,11 @@ function processBlockEvent(
       finalizeActiveBlock(state, event, snapshotLimit);
       break;
     }
+    case "BlockStructuredData": {
+      const snap = state.snapshots.find((s) => s.blockId === event.id);
+      if (snap) snap.structured = { kind: event.kind, sample: event.sample };
+      break;
+    }
     default:
       break;
   }
@@ -614,8 +859,17 @@ function finalizeActiveBlock(
     return;
   }
 
-  const endExclusive = event.global_row + 1;
-  let start = active.outputStart ?? endExclusive;
+  const endGlobal = findGlobalRowForLineId(
+    state.scrollbackLines,
+    state.visibleLinesByGlobal,
+    event.line_id,
+  ) ?? event.global_row;
+  const endExclusive = endGlobal + 1;
+  let start = findGlobalRowForLineId(
+    state.scrollbackLines,
+    state.visibleLinesByGlobal,
+    active.outputStartLineId,
+  ) ?? active.outputStart ?? endExclusive;
   if (state.lastAltExitVisibleBase !== null) {
     start = Math.min(start, state.lastAltExitVisibleBase);
     state.lastAltExitVisibleBase = null;
@@ -633,11 +887,13 @@ function finalizeActiveBlock(
   if (active.command || trimmed.length > 0) {
     state.snapshots.push({
       id: `snap-${event.id}-${++snapshotCounter}`,
+      blockId: event.id,
       command: active.command,
       lines: trimmed,
       timestamp: active.startTime || Date.now(),
       endTime: Date.now(),
       cwd: active.cwd || state.cwd,
+      git: active.git,
       failed,
     });
 
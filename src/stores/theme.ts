@@ -1,6 +1,7 @@
 import { createSignal } from "solid-js";
+import { listThemes, setTerminalTheme, type ThemeInfo } from "../lib/ipc";
 
-export type ThemeName =
+export type BuiltinThemeName =
   | "dark"
   | "light"
   | "nord"
@@ -12,7 +13,12 @@ export type ThemeName =
   | "tokyo-night"
   | "one-dark";
 
-export const THEME_LIST: { name: ThemeName; label: string; bg: string; accent: string }[] = [
+/** A built-in theme, or one loaded from the config directory's themes folder. */
+export type ThemeName = BuiltinThemeName | `file:${string}`;
+
+const FILE_THEME_PREFIX = "file:";
+
+export const THEME_LIST: { name: BuiltinThemeName; label: string; bg: string; accent: string }[] = [
   { name: "dark",           label: "Dark",           bg: "#0e0e0e", accent: "#01c1a2" },
   { name: "light",          label: "Light",          bg: "#f8f7f5", accent: "#0d9373" },
   { name: "nord",           label: "Nord",           bg: "#2e3440", accent: "#88c0d0" },
@@ -30,7 +36,7 @@ const THEME_STORAGE_KEY = "rain-theme";
 function loadTheme(): ThemeName {
   try {
     const saved = localStorage.getItem(THEME_STORAGE_KEY);
-    if (saved && THEME_LIST.some((t) => t.name === saved)) {
+    if (saved && (THEME_LIST.some((t) => t.name === saved) || saved.startsWith(FILE_THEME_PREFIX))) {
       return saved as ThemeName;
     }
   } catch {
@@ -40,13 +46,77 @@ function loadTheme(): ThemeName {
 }
 
 const [currentTheme, setCurrentTheme] = createSignal<ThemeName>(loadTheme());
+const [fileThemes, setFileThemes] = createSignal<ThemeInfo[]>([]);
+
+/** Themes loaded from files, as of the last `refreshFileThemes`. */
+export { fileThemes };
+
+export function fileThemeName(info: ThemeInfo): ThemeName {
+  return `${FILE_THEME_PREFIX}${info.name}`;
+}
+
+export function fileThemeInfo(name: ThemeName): ThemeInfo | undefined {
+  if (!name.startsWith(FILE_THEME_PREFIX)) return undefined;
+  const fileName = name.slice(FILE_THEME_PREFIX.length);
+  return fileThemes().find((t) => t.name === fileName);
+}
+
+/** Relative luminance of a `#rrggbb` color, enough to pick a light or dark UI. */
+function isLightColor(hex: string): boolean {
+  const value = parseInt(hex.slice(1), 16);
+  const r = (value >> 16) & 0xff;
+  const g = (value >> 8) & 0xff;
+  const b = value & 0xff;
+  return 0.2126 * r + 0.7152 * g + 0.0722 * b > 140;
+}
+
+const FILE_THEME_VARS = ["--bg", "--fg", "--cursor-color", "--terminal-background-image"] as const;
+
+/**
+ * Show a theme in the DOM and make the backend answer color queries with
+ * it. A file theme borrows the dark or light UI and overrides its colors;
+ * the backend sends its ANSI colors as RGB.
+ */
+function applyTheme(name: ThemeName) {
+  const root = document.documentElement;
+  const info = fileThemeInfo(name);
+  for (const v of FILE_THEME_VARS) root.style.removeProperty(v);
+  if (info) {
+    root.setAttribute("data-theme", isLightColor(info.background) ? "light" : "dark");
+    root.style.setProperty("--bg", info.background);
+    root.style.setProperty("--fg", info.foreground);
+    root.style.setProperty("--cursor-color", info.cursor);
+  } else {
+    root.setAttribute("data-theme", name.startsWith(FILE_THEME_PREFIX) ? "dark" : name);
+  }
+  const backendName = name.startsWith(FILE_THEME_PREFIX) ? name.slice(FILE_THEME_PREFIX.length) : name;
+  setTerminalTheme(backendName)
+    .then((applied) => {
+      if (applied.backgroundImage && currentTheme() === name) {
+        root.style.setProperty("--terminal-background-image", `url("${applied.backgroundImage}")`);
+      }
+    })
+    .catch((e) => console.warn(`[Rain] Theme ${backendName} not applied to the terminal:`, e));
+}
+
+/** Read the themes folder again, and re-apply the current theme if it is one of them. */
+export async function refreshFileThemes(): Promise<void> {
+  try {
+    const themes = await listThemes();
+    setFileThemes(themes.filter((t) => !t.builtin));
+  } catch (e) {
+    console.warn("[Rain] Failed to list themes:", e);
+    return;
+  }
+  if (currentTheme().startsWith(FILE_THEME_PREFIX)) applyTheme(currentTheme());
+}
 
 export function useTheme() {
   return {
     theme: currentTheme,
     setTheme: (name: ThemeName) => {
       setCurrentTheme(name);
-      document.documentElement.setAttribute("data-theme", name);
+      applyTheme(name);
       try {
         localStorage.setItem(THEME_STORAGE_KEY, name);
       } catch {
@@ -56,7 +126,7 @@ export function useTheme() {
     toggleTheme: () => {
       const next = currentTheme() === "dark" ? "light" : "dark";
       setCurrentTheme(next);
-      document.documentElement.setAttribute("data-theme", next);
+      applyTheme(next);
       try {
         localStorage.setItem(THEME_STORAGE_KEY, next);
       } catch {
@@ -66,8 +136,9 @@ export function useTheme() {
   };
 }
 
-// Per-theme ANSI 16-color palettes
-export const THEME_ANSI_PALETTES: Record<ThemeName, string[]> = {
+// Per-theme ANSI 16-color palettes. The backend mirrors these; file themes
+// have none here since their spans arrive in RGB.
+export const THEME_ANSI_PALETTES: Record<string, string[]> = {
   "dark": [
     "#0e0e0e", "#f85149", "#56d364", "#e3b341", "#58a6ff", "#bc8cff", "#39d2c0", "#c9d1d9",
     "#484f58", "#ff7b72", "#7ee787", "#f0c85c", "#79c0ff", "#d2a8ff", "#56d4cf", "#f0f6fc",
@@ -111,8 +182,10 @@ export const THEME_ANSI_PALETTES: Record<ThemeName, string[]> = {
 };
 
 // Initialize theme on startup (applies saved theme to DOM)
-export function initTheme() {
-  const saved = loadTheme();
+/** In safe mode the default theme is shown without replacing the saved one. */
+export function initTheme(safeMode = false) {
+  const saved = safeMode ? "dark" : loadTheme();
   setCurrentTheme(saved);
-  document.documentElement.setAttribute("data-theme", saved);
+  applyTheme(saved);
+  if (saved.startsWith(FILE_THEME_PREFIX)) void refreshFileThemes();
 }